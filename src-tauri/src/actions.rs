@@ -0,0 +1,353 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    app_lock, blobs, checkpoints, compaction, diagnostics, digests, export, focus, indexes, mail,
+    presentation, retention, setup, sync,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArgKind {
+    String,
+    Number,
+    Boolean,
+    /// A file or folder, for the palette to offer a picker for
+    Path,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionArg {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: ArgKind,
+    pub required: bool,
+}
+
+/// Something the command palette can run in the backend with `invoke_action`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Action {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub category: &'static str,
+    pub args: &'static [ActionArg],
+}
+
+const fn arg(name: &'static str, description: &'static str, kind: ArgKind) -> ActionArg {
+    ActionArg {
+        name,
+        description,
+        kind,
+        required: true,
+    }
+}
+
+const fn optional(name: &'static str, description: &'static str, kind: ArgKind) -> ActionArg {
+    ActionArg {
+        name,
+        description,
+        kind,
+        required: false,
+    }
+}
+
+const YAK: ActionArg = arg("yak_id", "Yak", ArgKind::String);
+const PATH: ActionArg = arg("path", "Where to write it", ArgKind::Path);
+
+const ACTIONS: &[Action] = &[
+    Action {
+        id: "app.lock",
+        title: "Lock Yaks",
+        category: "App",
+        args: &[],
+    },
+    Action {
+        id: "app.presentation",
+        title: "Toggle presentation mode",
+        category: "App",
+        args: &[],
+    },
+    Action {
+        id: "focus.start",
+        title: "Start a focus session",
+        category: "Focus",
+        args: &[
+            arg("duration", "Length in seconds", ArgKind::Number),
+            optional("frame_id", "Note or task to focus on", ArgKind::String),
+        ],
+    },
+    Action {
+        id: "focus.stop",
+        title: "Stop the focus session",
+        category: "Focus",
+        args: &[],
+    },
+    Action {
+        id: "export.markdown",
+        title: "Export yak as markdown",
+        category: "Export",
+        args: &[YAK, PATH],
+    },
+    Action {
+        id: "export.json_feed",
+        title: "Export yak as a JSON Feed",
+        category: "Export",
+        args: &[YAK, PATH],
+    },
+    Action {
+        id: "export.sqlite",
+        title: "Export everything to SQLite",
+        category: "Export",
+        args: &[PATH],
+    },
+    Action {
+        id: "export.config",
+        title: "Back up settings",
+        category: "Export",
+        args: &[PATH],
+    },
+    Action {
+        id: "export.diagnostics",
+        title: "Export diagnostics",
+        category: "Export",
+        args: &[PATH],
+    },
+    Action {
+        id: "checkpoint.create",
+        title: "Create a checkpoint",
+        category: "Yak",
+        args: &[YAK, arg("name", "Checkpoint name", ArgKind::String)],
+    },
+    Action {
+        id: "digest.generate",
+        title: "Write last week's review",
+        category: "Yak",
+        args: &[optional(
+            "summarize",
+            "Open with a summary from the model",
+            ArgKind::Boolean,
+        )],
+    },
+    Action {
+        id: "mail.check",
+        title: "Check mail now",
+        category: "Capture",
+        args: &[],
+    },
+    Action {
+        id: "sync.now",
+        title: "Sync now",
+        category: "Sync",
+        args: &[],
+    },
+    Action {
+        id: "index.rebuild",
+        title: "Rebuild search indexes",
+        category: "Maintenance",
+        args: &[optional(
+            "kind",
+            "full_text, semantic or read_model; all when left out",
+            ArgKind::String,
+        )],
+    },
+    Action {
+        id: "store.compact",
+        title: "Compact the store",
+        category: "Maintenance",
+        args: &[],
+    },
+    Action {
+        id: "store.prune",
+        title: "Apply retention policies",
+        category: "Maintenance",
+        args: &[],
+    },
+    Action {
+        id: "store.collect_garbage",
+        title: "Remove unreferenced content",
+        category: "Maintenance",
+        args: &[],
+    },
+];
+
+/// Refuses arguments the action doesn't take, is missing or has the wrong type for.
+fn check_args(action: &Action, args: &Map<String, Value>) -> Result<(), String> {
+    if let Some(name) = args
+        .keys()
+        .find(|name| !action.args.iter().any(|arg| arg.name == name.as_str()))
+    {
+        return Err(format!("{} takes no argument {name}", action.id));
+    }
+    for arg in action.args {
+        let value = match args.get(arg.name) {
+            None | Some(Value::Null) if arg.required => {
+                return Err(format!("{} needs {}", action.id, arg.name));
+            }
+            None | Some(Value::Null) => continue,
+            Some(value) => value,
+        };
+        let fits = match arg.kind {
+            ArgKind::String | ArgKind::Path => value.is_string(),
+            ArgKind::Number => value.is_u64(),
+            ArgKind::Boolean => value.is_boolean(),
+        };
+        if !fits {
+            return Err(format!("{} of {} has the wrong type", arg.name, action.id));
+        }
+    }
+    Ok(())
+}
+
+fn get<T: DeserializeOwned>(args: &Map<String, Value>, name: &str) -> Result<T, String> {
+    let value = args.get(name).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|e| format!("Invalid {name}: {e}"))
+}
+
+fn reply<T: Serialize>(result: Result<T, String>) -> Result<Value, String> {
+    serde_json::to_value(result?).map_err(|e| format!("Failed to serialize result: {e}"))
+}
+
+async fn run(app: AppHandle, id: &str, args: &Map<String, Value>) -> Result<Value, String> {
+    match id {
+        "app.lock" => reply(app_lock::lock_app(app).await),
+        "app.presentation" => {
+            presentation::toggle(&app);
+            Ok(Value::Null)
+        }
+        "focus.start" => reply(
+            focus::start_focus(
+                app.clone(),
+                app.state(),
+                app.state(),
+                get(args, "duration")?,
+                get(args, "frame_id")?,
+            )
+            .await,
+        ),
+        "focus.stop" => reply(focus::stop_focus(app.clone(), app.state(), app.state()).await),
+        "export.markdown" => reply(
+            export::export_yak_markdown(
+                app.state(),
+                app.state(),
+                get(args, "yak_id")?,
+                get(args, "path")?,
+            )
+            .await,
+        ),
+        "export.json_feed" => reply(
+            export::export_json_feed(
+                app.state(),
+                app.state(),
+                get(args, "yak_id")?,
+                get(args, "path")?,
+            )
+            .await,
+        ),
+        "export.sqlite" => {
+            reply(export::export_sqlite(app.state(), app.state(), get(args, "path")?).await)
+        }
+        "export.config" => reply(setup::export_config(app.state(), get(args, "path")?).await),
+        "export.diagnostics" => reply(
+            diagnostics::export_diagnostics(
+                app.clone(),
+                app.state(),
+                app.state(),
+                get(args, "path")?,
+            )
+            .await,
+        ),
+        "checkpoint.create" => reply(
+            checkpoints::create_checkpoint(
+                app.clone(),
+                app.state(),
+                get(args, "yak_id")?,
+                get(args, "name")?,
+            )
+            .await,
+        ),
+        "digest.generate" => reply(
+            digests::generate_digest(
+                app.clone(),
+                app.state(),
+                app.state(),
+                None,
+                get(args, "summarize")?,
+            )
+            .await,
+        ),
+        "mail.check" => reply(mail::check_mail_now(app.state(), app.clone()).await),
+        "sync.now" => reply(sync::sync_now(app.state(), app.clone(), app.state()).await),
+        "index.rebuild" => reply(
+            indexes::rebuild_indexes(app.clone(), app.state(), app.state(), get(args, "kind")?)
+                .await,
+        ),
+        "store.compact" => reply(compaction::compact_store(app.clone(), app.state()).await),
+        "store.prune" => reply(retention::prune_expired(app.clone(), app.state()).await),
+        "store.collect_garbage" => reply(blobs::collect_garbage(app.clone(), app.state()).await),
+        _ => Err(format!("Unknown action: {id}")),
+    }
+}
+
+/// Every action the backend can run, for the command palette to list alongside its own.
+#[tauri::command]
+pub async fn list_actions() -> Result<Vec<Action>, String> {
+    Ok(ACTIONS.to_vec())
+}
+
+/// Runs the action `id` with `args`, an object keyed by the names `list_actions` gives.
+/// Returns whatever the command behind the action does.
+#[tauri::command]
+pub async fn invoke_action(
+    app: AppHandle,
+    id: String,
+    args: Option<Map<String, Value>>,
+) -> Result<Value, String> {
+    let action = ACTIONS
+        .iter()
+        .find(|action| action.id == id)
+        .ok_or_else(|| format!("Unknown action: {id}"))?;
+    let args = args.unwrap_or_default();
+    check_args(action, &args)?;
+    run(app, action.id, &args).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashSet;
+
+    fn args(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_check_args() {
+        let ids: HashSet<&str> = ACTIONS.iter().map(|action| action.id).collect();
+        assert_eq!(ids.len(), ACTIONS.len());
+
+        let action = |id: &str| ACTIONS.iter().find(|action| action.id == id).unwrap();
+        let focus = action("focus.start");
+        assert!(check_args(focus, &args(json!({ "duration": 1500 }))).is_ok());
+        assert!(check_args(focus, &args(json!({ "duration": 60, "frame_id": null }))).is_ok());
+        assert_eq!(
+            check_args(focus, &args(json!({}))).unwrap_err(),
+            "focus.start needs duration"
+        );
+        assert_eq!(
+            check_args(focus, &args(json!({ "duration": "soon" }))).unwrap_err(),
+            "duration of focus.start has the wrong type"
+        );
+        assert_eq!(
+            check_args(action("sync.now"), &args(json!({ "path": "/tmp" }))).unwrap_err(),
+            "sync.now takes no argument path"
+        );
+        assert!(check_args(
+            action("export.markdown"),
+            &args(json!({ "yak_id": "abc", "path": "/tmp/out" }))
+        )
+        .is_ok());
+    }
+}
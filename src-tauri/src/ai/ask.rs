@@ -0,0 +1,143 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use xs::store::Store;
+
+use super::{generate, load_config};
+use crate::export::note_title;
+use crate::locks::LockState;
+use crate::projection::{Note, Projection};
+use crate::search::{keyword_search, Match};
+use crate::semantic::SemanticState;
+use crate::windows::emit_frame;
+use crate::{append_frame, read_all_frames};
+
+const KEYWORD_SOURCES: usize = 4;
+const SEMANTIC_SOURCES: usize = 4;
+/// Each source is cut off here so a few long notes can't crowd out the rest of the prompt
+const MAX_SOURCE_CHARS: usize = 3000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Answer {
+    pub question_id: String,
+    pub answer_id: String,
+    pub answer: String,
+    /// Note ids the answer was drawn from, in the order they were numbered in the prompt
+    pub citations: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Progress<'a> {
+    question_id: &'a str,
+    chunk: &'a str,
+}
+
+/// The notes to answer from: the best keyword matches, then the closest semantic matches
+/// (when the embedding model is available).
+async fn retrieve<'a>(
+    app: &AppHandle,
+    projection: &'a Projection,
+    question: &str,
+    yak_id: Option<&str>,
+) -> Vec<&'a Note> {
+    let mut sources = keyword_search(projection, question, yak_id, Match::Any, KEYWORD_SOURCES);
+    let semantic = app.state::<SemanticState>();
+    match semantic.embed(vec![question.to_string()]).await {
+        Ok(mut vectors) => {
+            let vector = vectors.pop().unwrap_or_default();
+            let index = semantic.index().await;
+            let hits = index.nearest(&vector, SEMANTIC_SOURCES * 2, |id| {
+                sources.iter().any(|note| note.id == id)
+            });
+            sources.extend(
+                hits.iter()
+                    .filter_map(|hit| projection.notes.get(&hit.note_id))
+                    .filter(|note| yak_id.map_or(true, |yak_id| note.yak_id == yak_id))
+                    .take(SEMANTIC_SOURCES),
+            );
+        }
+        Err(e) => eprintln!("Answering from keyword matches only: {e}"),
+    }
+    sources
+}
+
+fn prompt(question: &str, sources: &[&Note]) -> String {
+    let mut prompt = String::from(
+        "Answer the question using only the numbered notes below. Cite the notes you use \
+         like [1]. If the notes don't contain the answer, say so.\n",
+    );
+    for (i, note) in sources.iter().enumerate() {
+        let content: String = note
+            .content
+            .as_deref()
+            .unwrap_or_default()
+            .chars()
+            .take(MAX_SOURCE_CHARS)
+            .collect();
+        prompt.push_str(&format!("\n[{}] {}\n{content}\n", i + 1, note_title(note)));
+    }
+    prompt.push_str(&format!("\nQuestion: {question}\n"));
+    prompt
+}
+
+/// Answers a question from the notes, optionally only one yak's. The question is appended
+/// as an `ask.question` frame, the answer streamed as `ask-progress` events and then
+/// appended as an `ask.answer` frame citing the notes it was given.
+#[tauri::command]
+pub async fn ask_notes(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    app: AppHandle,
+    question: String,
+    yak_id: Option<String>,
+) -> Result<Answer, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let frames = read_all_frames(&store).await;
+    let mut projection = Projection::from_frames(&frames).into_current();
+    projection.resolve_content(&store).await;
+    locks.redact(&mut projection, true);
+    let sources = retrieve(&app, &projection, &question, yak_id.as_deref()).await;
+    if sources.is_empty() {
+        return Err("No notes match the question".to_string());
+    }
+
+    let meta = serde_json::json!({ "yak_id": yak_id });
+    let question_frame = append_frame(
+        &store,
+        "ask.question",
+        Some(question.as_bytes()),
+        Some(meta),
+    )
+    .await?;
+    let _ = emit_frame(&app, &question_frame);
+    let question_id = question_frame.id.to_string();
+
+    let config = load_config(&store).await;
+    let answer = generate(&config, &prompt(&question, &sources), |chunk| {
+        let _ = app.emit(
+            "ask-progress",
+            Progress {
+                question_id: &question_id,
+                chunk,
+            },
+        );
+    })
+    .await?;
+
+    let citations: Vec<String> = sources.iter().map(|note| note.id.clone()).collect();
+    let meta = serde_json::json!({
+        "question_id": question_id,
+        "yak_id": yak_id,
+        "citations": citations,
+        "model": config.model,
+    });
+    let answer_frame =
+        append_frame(&store, "ask.answer", Some(answer.as_bytes()), Some(meta)).await?;
+    let _ = emit_frame(&app, &answer_frame);
+
+    Ok(Answer {
+        question_id,
+        answer_id: answer_frame.id.to_string(),
+        answer,
+        citations,
+    })
+}
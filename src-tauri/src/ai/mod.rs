@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::State;
+use xs::store::Store;
+
+use crate::settings::{load_setting, save_setting};
+
+mod ask;
+mod summarize;
+mod tags;
+
+pub use ask::ask_notes;
+pub use summarize::{summarize_note, summarize_yak};
+pub(crate) use tags::watch as watch_tag_suggestions;
+pub use tags::{accept_suggestion, dismiss_suggestion, enable_tag_suggestions, list_suggestions};
+
+const CONFIG_TOPIC: &str = "ai.config";
+const TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Api {
+    /// Ollama's native `/api/generate`
+    #[default]
+    Ollama,
+    /// Any `/v1/chat/completions` endpoint (llama.cpp, LM Studio, vLLM, OpenAI itself)
+    OpenAi,
+}
+
+fn default_base_url() -> String {
+    "http://localhost:11434".to_string()
+}
+
+fn default_model() -> String {
+    "llama3.2".to_string()
+}
+
+/// The language model endpoint AI features use. Local to each device, since it usually
+/// points at a model server running on that machine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiConfig {
+    #[serde(default)]
+    pub api: Api,
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    #[serde(default = "default_model")]
+    pub model: String,
+    pub api_key: Option<String>,
+}
+
+impl Default for AiConfig {
+    fn default() -> Self {
+        Self {
+            api: Api::default(),
+            base_url: default_base_url(),
+            model: default_model(),
+            api_key: None,
+        }
+    }
+}
+
+pub(crate) async fn load_config(store: &Store) -> AiConfig {
+    load_setting(store, CONFIG_TOPIC).await
+}
+
+/// Pulls the generated text out of one line of a streamed response: a JSON object for
+/// Ollama, an SSE `data:` line for OpenAI-compatible servers.
+fn parse_chunk(api: Api, line: &str) -> Option<String> {
+    let value: serde_json::Value = match api {
+        Api::Ollama => serde_json::from_str(line).ok()?,
+        Api::OpenAi => serde_json::from_str(line.strip_prefix("data:")?.trim()).ok()?,
+    };
+    let text = match api {
+        Api::Ollama => value.get("response")?,
+        Api::OpenAi => value.get("choices")?.get(0)?.get("delta")?.get("content")?,
+    };
+    text.as_str().map(String::from)
+}
+
+/// Sends a prompt to the configured model, calling `on_chunk` with each piece of the
+/// response as it streams in, and returns the full text.
+pub(crate) async fn generate(
+    config: &AiConfig,
+    prompt: &str,
+    mut on_chunk: impl FnMut(&str),
+) -> Result<String, String> {
+    let base = config.base_url.trim_end_matches('/');
+    let (url, body) = match config.api {
+        Api::Ollama => (
+            format!("{base}/api/generate"),
+            serde_json::json!({ "model": config.model, "prompt": prompt, "stream": true }),
+        ),
+        Api::OpenAi => (
+            format!("{base}/v1/chat/completions"),
+            serde_json::json!({
+                "model": config.model,
+                "messages": [{ "role": "user", "content": prompt }],
+                "stream": true,
+            }),
+        ),
+    };
+    let mut request = reqwest::Client::new()
+        .post(&url)
+        .timeout(TIMEOUT)
+        .json(&body);
+    if let Some(key) = &config.api_key {
+        request = request.bearer_auth(key);
+    }
+    let mut response = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to reach {url}: {e}"))?;
+
+    let mut text = String::new();
+    let mut pending = Vec::new();
+    while let Some(bytes) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Model response failed: {e}"))?
+    {
+        pending.extend_from_slice(&bytes);
+        while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            if let Some(chunk) = parse_chunk(config.api, String::from_utf8_lossy(&line).trim()) {
+                on_chunk(&chunk);
+                text.push_str(&chunk);
+            }
+        }
+    }
+    if let Some(chunk) = parse_chunk(config.api, String::from_utf8_lossy(&pending).trim()) {
+        on_chunk(&chunk);
+        text.push_str(&chunk);
+    }
+    Ok(text.trim().to_string())
+}
+
+#[tauri::command]
+pub async fn configure_ai(store: State<'_, Store>, config: AiConfig) -> Result<(), String> {
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chunk() {
+        assert_eq!(
+            parse_chunk(Api::Ollama, r#"{"response":"Hel","done":false}"#).as_deref(),
+            Some("Hel")
+        );
+        assert_eq!(
+            parse_chunk(
+                Api::OpenAi,
+                r#"data: {"choices":[{"delta":{"content":"lo"}}]}"#
+            )
+            .as_deref(),
+            Some("lo")
+        );
+        assert_eq!(parse_chunk(Api::OpenAi, "data: [DONE]"), None);
+    }
+}
@@ -0,0 +1,118 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+use xs::store::Store;
+
+use super::{generate, load_config};
+use crate::append_frame;
+use crate::export::{created_id, load_yak, note_title};
+use crate::locks::LockState;
+use crate::time::TimeRange;
+use crate::windows::emit_frame;
+
+/// A piece of a summary as it's generated, emitted as `summary-progress`.
+#[derive(Debug, Clone, Serialize)]
+struct Progress<'a> {
+    source_id: &'a str,
+    chunk: &'a str,
+}
+
+/// Generates a summary, streaming it to the frontend, and appends it as a `summary.create`
+/// frame whose meta links it to its source.
+async fn summarize(
+    store: &Store,
+    app: &AppHandle,
+    source_id: &str,
+    prompt: &str,
+    mut meta: serde_json::Value,
+) -> Result<String, String> {
+    let config = load_config(store).await;
+    let summary = generate(&config, prompt, |chunk| {
+        let _ = app.emit("summary-progress", Progress { source_id, chunk });
+    })
+    .await?;
+    if summary.is_empty() {
+        return Err("The model returned an empty summary".to_string());
+    }
+    if let Some(fields) = meta.as_object_mut() {
+        fields.insert("model".into(), config.model.into());
+    }
+    let frame = append_frame(
+        store,
+        "summary.create",
+        Some(summary.as_bytes()),
+        Some(meta),
+    )
+    .await?;
+    emit_frame(&app, &frame).map_err(|e| format!("Failed to emit frame: {e}"))?;
+    Ok(frame.id.to_string())
+}
+
+#[tauri::command]
+pub async fn summarize_note(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    app: AppHandle,
+    frame_id: String,
+) -> Result<String, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let frames = crate::read_all_frames(&store).await;
+    let mut projection = crate::projection::Projection::from_frames(&frames);
+    let note_id = projection.resolve(&frame_id);
+    projection.resolve_content(&store).await;
+    locks.redact(&mut projection, false);
+    let note = projection
+        .notes
+        .get(&note_id)
+        .ok_or_else(|| format!("Note not found: {frame_id}"))?;
+    let content = note.content.as_deref().unwrap_or_default();
+    if content.trim().is_empty() {
+        return Err("The note is empty".to_string());
+    }
+
+    let prompt = format!(
+        "Summarize the following note in a few sentences. Reply with the summary only.\n\n{content}"
+    );
+    let meta = serde_json::json!({ "yak_id": note.yak_id, "note_id": note.id });
+    summarize(&store, &app, &frame_id, &prompt, meta).await
+}
+
+/// Summarizes the notes of a yak, optionally only those created within `range`.
+#[tauri::command]
+pub async fn summarize_yak(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    app: AppHandle,
+    yak_id: String,
+    range: Option<TimeRange>,
+) -> Result<String, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let range = range.unwrap_or_default();
+    let (projection, notes) = load_yak(&store, &locks, &yak_id).await?;
+    let notes: Vec<_> = notes
+        .iter()
+        .filter(|note| {
+            crate::time::from_id(&created_id(&projection, note)).is_some_and(|t| range.contains(t))
+        })
+        .collect();
+    if notes.is_empty() {
+        return Err("No notes to summarize".to_string());
+    }
+
+    let mut prompt = String::from(
+        "Summarize the following notes: the main themes, decisions and open questions. \
+         Reply with the summary only.\n",
+    );
+    for note in &notes {
+        prompt.push_str(&format!(
+            "\n## {}\n{}\n",
+            note_title(note),
+            note.content.as_deref().unwrap_or_default()
+        ));
+    }
+    let meta = serde_json::json!({
+        "yak_id": yak_id,
+        "note_ids": notes.iter().map(|note| note.id.as_str()).collect::<Vec<_>>(),
+        "range": range,
+    });
+    summarize(&store, &app, &yak_id, &prompt, meta).await
+}
@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use tauri::{AppHandle, State};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use super::{generate, load_config};
+use crate::projection::Projection;
+use crate::settings::{load_setting, save_setting};
+use crate::windows::emit_frame;
+use crate::{append_frame, read_all_frames};
+
+const CONFIG_TOPIC: &str = "ai.suggest";
+const MAX_SUGGESTIONS: usize = 5;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SuggestConfig {
+    pub enabled: bool,
+}
+
+/// A `tag.suggest` frame that hasn't been accepted or dismissed yet.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub id: String,
+    pub yak_id: String,
+    pub note_id: String,
+    pub tag: String,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+/// Suggestions still awaiting a decision: accepting appends a `tag.add` carrying the
+/// `suggestion_id`, dismissing appends `tag.dismiss`.
+pub(crate) fn pending(frames: &[Frame]) -> Vec<Suggestion> {
+    let decided: HashSet<&str> = frames
+        .iter()
+        .filter(|frame| frame.topic == "tag.add" || frame.topic == "tag.dismiss")
+        .filter_map(|frame| meta_str(frame, "suggestion_id"))
+        .collect();
+    frames
+        .iter()
+        .filter(|frame| frame.topic == "tag.suggest")
+        .filter(|frame| !decided.contains(frame.id.to_string().as_str()))
+        .filter_map(|frame| {
+            Some(Suggestion {
+                id: frame.id.to_string(),
+                yak_id: meta_str(frame, "yak_id")?.to_string(),
+                note_id: meta_str(frame, "note_id")?.to_string(),
+                tag: meta_str(frame, "tag")?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Reads tags out of a model reply like `#rust, release notes\nplanning`.
+fn parse_tags(reply: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    for tag in reply.split(|c| c == ',' || c == '\n') {
+        let tag = tag
+            .trim()
+            .trim_start_matches(|c: char| c == '-' || c == '*' || c == '#' || c.is_whitespace())
+            .trim_end_matches('.')
+            .to_lowercase();
+        if !tag.is_empty() && tag.len() <= 40 && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags.truncate(MAX_SUGGESTIONS);
+    tags
+}
+
+/// Asks the model for tags for a note, preferring ones already in use, and appends a
+/// `tag.suggest` frame for each that the note doesn't already have.
+async fn suggest(app: &AppHandle, store: &Store, note_id: &str) -> Result<(), String> {
+    let frames = read_all_frames(store).await;
+    let mut projection = Projection::from_frames(&frames);
+    let note_id = projection.resolve(note_id);
+    let vocabulary: BTreeSet<String> = projection
+        .notes
+        .values()
+        .flat_map(|note| note.tags.iter().cloned())
+        .collect();
+    projection.resolve_content(store).await;
+    let Some(note) = projection.notes.get(&note_id) else {
+        return Ok(());
+    };
+    let content = note.content.as_deref().unwrap_or_default();
+    if content.trim().is_empty() {
+        return Ok(());
+    }
+    let already: HashSet<String> = pending(&frames)
+        .into_iter()
+        .filter(|suggestion| projection.resolve(&suggestion.note_id) == note_id)
+        .map(|suggestion| suggestion.tag)
+        .chain(note.tags.iter().cloned())
+        .collect();
+
+    let prompt = format!(
+        "Suggest up to {MAX_SUGGESTIONS} short tags for this note, as a comma-separated list \
+         with nothing else. Prefer these existing tags where they fit: {}.\n\n{content}",
+        vocabulary.into_iter().collect::<Vec<_>>().join(", ")
+    );
+    let reply = generate(&load_config(store).await, &prompt, |_| {}).await?;
+    for tag in parse_tags(&reply)
+        .into_iter()
+        .filter(|tag| !already.contains(tag))
+    {
+        let meta = serde_json::json!({ "yak_id": note.yak_id, "note_id": note.id, "tag": tag });
+        let frame = append_frame(store, "tag.suggest", None, Some(meta)).await?;
+        let _ = emit_frame(app, &frame);
+    }
+    Ok(())
+}
+
+/// Suggests tags for notes created or edited on this device while suggestions are enabled.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    let mut caught_up = false;
+    while let Some(frame) = rx.recv().await {
+        if frame.topic == "xs.threshold" {
+            caught_up = true;
+            continue;
+        }
+        let is_note = frame.topic == "note.create" || frame.topic == "note.edit";
+        if !caught_up || !is_note || crate::sync::origin(&frame).is_some() {
+            continue;
+        }
+        let config: SuggestConfig = load_setting(&store, CONFIG_TOPIC).await;
+        if !config.enabled {
+            continue;
+        }
+        let (app, store) = (app.clone(), store.clone());
+        tokio::spawn(async move {
+            if let Err(e) = suggest(&app, &store, &frame.id.to_string()).await {
+                eprintln!("Failed to suggest tags: {e}");
+            }
+        });
+    }
+}
+
+#[tauri::command]
+pub async fn enable_tag_suggestions(store: State<'_, Store>, enabled: bool) -> Result<(), String> {
+    save_setting(&store, CONFIG_TOPIC, &SuggestConfig { enabled })?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_suggestions(
+    store: State<'_, Store>,
+    yak_id: Option<String>,
+) -> Result<Vec<Suggestion>, String> {
+    let frames = read_all_frames(&store).await;
+    Ok(pending(&frames)
+        .into_iter()
+        .filter(|suggestion| yak_id.as_ref().map_or(true, |id| &suggestion.yak_id == id))
+        .collect())
+}
+
+async fn find_pending(store: &Store, suggestion_id: &str) -> Result<Suggestion, String> {
+    let frames = read_all_frames(store).await;
+    pending(&frames)
+        .into_iter()
+        .find(|suggestion| suggestion.id == suggestion_id)
+        .ok_or_else(|| format!("No pending suggestion: {suggestion_id}"))
+}
+
+/// Turns a suggestion into a real tag on its note.
+#[tauri::command]
+pub async fn accept_suggestion(
+    store: State<'_, Store>,
+    app: AppHandle,
+    suggestion_id: String,
+) -> Result<String, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let suggestion = find_pending(&store, &suggestion_id).await?;
+    let meta = serde_json::json!({
+        "yak_id": suggestion.yak_id,
+        "note_id": suggestion.note_id,
+        "tag": suggestion.tag,
+        "suggestion_id": suggestion.id,
+    });
+    let frame = append_frame(&store, "tag.add", None, Some(meta)).await?;
+    emit_frame(&app, &frame).map_err(|e| format!("Failed to emit frame: {e}"))?;
+    Ok(frame.id.to_string())
+}
+
+#[tauri::command]
+pub async fn dismiss_suggestion(
+    store: State<'_, Store>,
+    app: AppHandle,
+    suggestion_id: String,
+) -> Result<(), String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let suggestion = find_pending(&store, &suggestion_id).await?;
+    let meta = serde_json::json!({
+        "yak_id": suggestion.yak_id,
+        "note_id": suggestion.note_id,
+        "suggestion_id": suggestion.id,
+    });
+    let frame = append_frame(&store, "tag.dismiss", None, Some(meta)).await?;
+    emit_frame(&app, &frame).map_err(|e| format!("Failed to emit frame: {e}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags("#Rust, release notes\n- planning.\nrust"),
+            vec!["rust", "release notes", "planning"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pending_suggestions() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let suggest = |tag: &str| serde_json::json!({ "yak_id": "y", "note_id": "n", "tag": tag });
+        let accepted = append_frame(&store, "tag.suggest", None, Some(suggest("a")))
+            .await
+            .unwrap();
+        let dismissed = append_frame(&store, "tag.suggest", None, Some(suggest("b")))
+            .await
+            .unwrap();
+        append_frame(&store, "tag.suggest", None, Some(suggest("c")))
+            .await
+            .unwrap();
+        for (topic, frame) in [("tag.add", &accepted), ("tag.dismiss", &dismissed)] {
+            let meta = serde_json::json!({ "suggestion_id": frame.id.to_string() });
+            append_frame(&store, topic, None, Some(meta)).await.unwrap();
+        }
+
+        let pending = pending(&read_all_frames(&store).await);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].tag, "c");
+    }
+}
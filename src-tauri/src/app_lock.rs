@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::crypto;
+use crate::locks::LockState;
+use crate::settings::{latest_setting, save_setting};
+
+const CONFIG_TOPIC: &str = "lock.app";
+/// The shortcut action that locks the app straight away (see `shortcuts`).
+pub(crate) const LOCK_ACTION: &str = "app.lock";
+/// The error content-reading commands fail with while the app is locked.
+pub(crate) const LOCKED: &str = "Locked";
+/// How often the idle timeout is checked.
+const CHECK: Duration = Duration::from_secs(15);
+/// Encrypted with the passphrase's key, to check unlock attempts against.
+const VERIFIER: &[u8] = b"yaks";
+
+/// Checked by content-reading commands, not all of which have an app handle to reach
+/// managed state with.
+static IS_LOCKED: AtomicBool = AtomicBool::new(false);
+
+/// Held by tests that lock the app, and by those reading through a gate it would close.
+#[cfg(test)]
+pub(crate) static TEST_GATE: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct AppLockConfig {
+    salt: Option<String>,
+    verifier: Option<String>,
+    /// Lock after this many minutes without activity; only on demand when unset
+    pub idle_minutes: Option<u64>,
+}
+
+impl AppLockConfig {
+    fn enabled(&self) -> bool {
+        self.verifier.is_some()
+    }
+
+    /// Sets the passphrase, or removes it when empty.
+    fn protect(&mut self, passphrase: &str) -> Result<(), String> {
+        if passphrase.is_empty() {
+            self.salt = None;
+            self.verifier = None;
+            return Ok(());
+        }
+        let salt = crypto::random_salt();
+        let key = crypto::derive_key(passphrase, &salt)?;
+        self.salt = Some(crypto::to_hex(&salt));
+        self.verifier = Some(crypto::to_hex(&crypto::encrypt(&key, VERIFIER)?));
+        Ok(())
+    }
+
+    /// Whether `passphrase` is the app's; anything is while none is set.
+    fn check(&self, passphrase: &str) -> Result<(), String> {
+        let (Some(salt), Some(verifier)) = (&self.salt, &self.verifier) else {
+            return Ok(());
+        };
+        let key = crypto::derive_key(passphrase, &crypto::from_hex(salt)?)?;
+        crypto::decrypt(&key, &crypto::from_hex(verifier)?)
+            .map_err(|_| "Wrong passphrase".to_string())?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppLockStatus {
+    /// Whether a passphrase is set
+    pub enabled: bool,
+    pub locked: bool,
+    pub idle_minutes: Option<u64>,
+}
+
+pub struct AppLockState {
+    config: Mutex<AppLockConfig>,
+    last_activity: Mutex<Instant>,
+}
+
+impl Default for AppLockState {
+    fn default() -> Self {
+        Self {
+            config: Mutex::new(AppLockConfig::default()),
+            last_activity: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl AppLockState {
+    fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    /// Whether the idle timeout has passed without activity as of `now`.
+    fn is_idle(&self, now: Instant) -> bool {
+        let config = self.config.lock().unwrap();
+        let last_activity = *self.last_activity.lock().unwrap();
+        config.enabled()
+            && config.idle_minutes.is_some_and(|minutes| {
+                now.duration_since(last_activity) >= Duration::from_secs(minutes * 60)
+            })
+    }
+
+    fn status(&self) -> AppLockStatus {
+        let config = self.config.lock().unwrap();
+        AppLockStatus {
+            enabled: config.enabled(),
+            locked: IS_LOCKED.load(Ordering::SeqCst),
+            idle_minutes: config.idle_minutes,
+        }
+    }
+}
+
+/// Fails with `Locked` while the app is locked.
+pub(crate) fn ensure_unlocked() -> Result<(), String> {
+    if IS_LOCKED.load(Ordering::SeqCst) {
+        return Err(LOCKED.to_string());
+    }
+    Ok(())
+}
+
+fn set_locked(app: &AppHandle, locked: bool) {
+    IS_LOCKED.store(locked, Ordering::SeqCst);
+    if locked {
+        app.state::<LockState>().forget_keys();
+    }
+    if let Err(e) = app.emit("app-lock", locked) {
+        eprintln!("Failed to emit app lock: {e}");
+    }
+}
+
+/// Locks the app now, as `lock_app` and its shortcut do.
+pub(crate) fn lock(app: &AppHandle) -> Result<(), String> {
+    if !app.state::<AppLockState>().config.lock().unwrap().enabled() {
+        return Err("Set a passphrase for the app lock first".to_string());
+    }
+    set_locked(app, true);
+    Ok(())
+}
+
+/// Loads the lock's settings, starting locked when a passphrase is set, and locks the app
+/// whenever it goes idle.
+pub(crate) async fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let config: AppLockConfig = latest_setting(frames, CONFIG_TOPIC);
+    let enabled = config.enabled();
+    *app.state::<AppLockState>().config.lock().unwrap() = config;
+    if enabled {
+        set_locked(app, true);
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(CHECK).await;
+            let idle = app.state::<AppLockState>().is_idle(Instant::now());
+            if idle && ensure_unlocked().is_ok() {
+                set_locked(&app, true);
+            }
+        }
+    });
+}
+
+/// Protects the app with a passphrase. Once locked, after `idle_minutes` without activity
+/// or on demand, yak keys unlocked this session are dropped and content-reading commands
+/// fail with `Locked` until `unlock_app`. Changing the passphrase, or removing it with an
+/// empty one, needs the current passphrase; without `passphrase` only the timeout changes.
+#[tauri::command]
+pub async fn configure_app_lock(
+    store: State<'_, Store>,
+    state: State<'_, AppLockState>,
+    passphrase: Option<String>,
+    current_passphrase: Option<String>,
+    idle_minutes: Option<u64>,
+) -> Result<AppLockStatus, String> {
+    ensure_unlocked()?;
+    if idle_minutes == Some(0) {
+        return Err("The idle timeout must be at least a minute".to_string());
+    }
+    let mut config = state.config.lock().unwrap().clone();
+    if let Some(passphrase) = passphrase {
+        config.check(current_passphrase.as_deref().unwrap_or_default())?;
+        config.protect(&passphrase)?;
+    }
+    config.idle_minutes = idle_minutes;
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    *state.config.lock().unwrap() = config;
+    state.touch();
+    Ok(state.status())
+}
+
+#[tauri::command]
+pub async fn get_app_lock(state: State<'_, AppLockState>) -> Result<AppLockStatus, String> {
+    Ok(state.status())
+}
+
+/// Locks the app straight away. Windows hear "app-lock" whenever it locks or unlocks.
+#[tauri::command]
+pub async fn lock_app(app: AppHandle) -> Result<(), String> {
+    lock(&app)
+}
+
+#[tauri::command]
+pub async fn unlock_app(
+    app: AppHandle,
+    state: State<'_, AppLockState>,
+    passphrase: String,
+) -> Result<(), String> {
+    let config = state.config.lock().unwrap().clone();
+    config.check(&passphrase)?;
+    state.touch();
+    set_locked(&app, false);
+    Ok(())
+}
+
+/// Restarts the idle timeout; windows call this as the user types, clicks or scrolls.
+#[tauri::command]
+pub async fn report_activity(state: State<'_, AppLockState>) -> Result<(), String> {
+    state.touch();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_app_lock() {
+        let state = AppLockState::default();
+        let later = Instant::now() + Duration::from_secs(10 * 60);
+        state.config.lock().unwrap().idle_minutes = Some(5);
+        // No passphrase, nothing to lock
+        assert!(!state.is_idle(later));
+        assert!(state.config.lock().unwrap().check("anything").is_ok());
+
+        let mut config = AppLockConfig::default();
+        config.protect("hunter2").unwrap();
+        config.idle_minutes = Some(5);
+        assert!(config.check("hunter2").is_ok());
+        assert!(config.check("wrong").is_err());
+        *state.config.lock().unwrap() = config;
+        assert!(!state.is_idle(Instant::now()));
+        assert!(state.is_idle(later));
+
+        state.config.lock().unwrap().protect("").unwrap();
+        assert!(!state.status().enabled);
+        assert!(!state.is_idle(later));
+    }
+
+    #[tokio::test]
+    async fn test_export_while_locked() {
+        let _gate = TEST_GATE.lock().await;
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = crate::import::create_yak(&store, "Diary").await.unwrap();
+        crate::import::add_note(&store, &yak_id, "dear diary", serde_json::json!({}))
+            .await
+            .unwrap();
+        let path = dir.path().join("diary.org");
+        let locks = LockState::default();
+        let export = || {
+            let path = path.to_string_lossy().into_owned();
+            crate::export::write_org(&store, &locks, &yak_id, path)
+        };
+
+        IS_LOCKED.store(true, Ordering::SeqCst);
+        let refused = export().await;
+        IS_LOCKED.store(false, Ordering::SeqCst);
+        assert_eq!(refused.unwrap_err(), LOCKED);
+        assert!(!path.exists());
+
+        assert_eq!(export().await.unwrap().notes, 1);
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("dear diary"));
+    }
+}
@@ -0,0 +1,254 @@
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use std::io::{Cursor, Read};
+use tauri::State;
+use xs::store::Store;
+
+/// Entries bigger than this aren't extracted, in case of archive bombs.
+const MAX_ENTRY_BYTES: u64 = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ArchiveEntry {
+    /// Path within the archive, as stored (directories usually end in `/`)
+    pub path: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+/// The archive format, from the first bytes rather than the attachment's name.
+fn format(bytes: &[u8]) -> Option<Format> {
+    if bytes.starts_with(b"PK\x03\x04") || bytes.starts_with(b"PK\x05\x06") {
+        Some(Format::Zip)
+    } else if bytes.starts_with(&[0x1f, 0x8b]) {
+        Some(Format::TarGz)
+    } else if bytes.get(257..262) == Some(b"ustar".as_slice()) {
+        Some(Format::Tar)
+    } else {
+        None
+    }
+}
+
+/// Reads an entry, refusing ones past `MAX_ENTRY_BYTES` whatever size they claim.
+fn read_limited(reader: impl Read, size: u64) -> Result<Vec<u8>, String> {
+    let too_big = || format!("Entry is larger than {} MB", MAX_ENTRY_BYTES / 1024 / 1024);
+    if size > MAX_ENTRY_BYTES {
+        return Err(too_big());
+    }
+    let mut bytes = Vec::new();
+    reader
+        .take(MAX_ENTRY_BYTES + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read entry: {e}"))?;
+    if bytes.len() as u64 > MAX_ENTRY_BYTES {
+        return Err(too_big());
+    }
+    Ok(bytes)
+}
+
+fn open_zip(bytes: &[u8]) -> Result<zip::ZipArchive<Cursor<&[u8]>>, String> {
+    zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("Failed to open zip: {e}"))
+}
+
+/// Walks a tar stream's entries in order, stopping when `visit` returns something.
+fn walk_tar<T>(
+    reader: impl Read,
+    mut visit: impl FnMut(ArchiveEntry, &mut dyn Read) -> Result<Option<T>, String>,
+) -> Result<Option<T>, String> {
+    let mut archive = tar::Archive::new(reader);
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("Failed to open tar: {e}"))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Failed to read tar: {e}"))?;
+        let path = entry
+            .path()
+            .map_err(|e| format!("Failed to read tar: {e}"))?
+            .to_string_lossy()
+            .into_owned();
+        let listed = ArchiveEntry {
+            path,
+            size: entry.size(),
+            is_dir: entry.header().entry_type().is_dir(),
+        };
+        if let Some(found) = visit(listed, &mut entry)? {
+            return Ok(Some(found));
+        }
+    }
+    Ok(None)
+}
+
+fn list(bytes: &[u8]) -> Result<Vec<ArchiveEntry>, String> {
+    let mut entries = Vec::new();
+    match format(bytes).ok_or("Not a zip or tar archive")? {
+        Format::Zip => {
+            let mut zip = open_zip(bytes)?;
+            for i in 0..zip.len() {
+                let file = zip
+                    .by_index_raw(i)
+                    .map_err(|e| format!("Failed to read zip: {e}"))?;
+                entries.push(ArchiveEntry {
+                    path: file.name().to_string(),
+                    size: file.size(),
+                    is_dir: file.is_dir(),
+                });
+            }
+        }
+        Format::Tar => {
+            walk_tar::<()>(bytes, |entry, _| {
+                entries.push(entry);
+                Ok(None)
+            })?;
+        }
+        Format::TarGz => {
+            walk_tar::<()>(GzDecoder::new(bytes), |entry, _| {
+                entries.push(entry);
+                Ok(None)
+            })?;
+        }
+    }
+    Ok(entries)
+}
+
+fn extract(bytes: &[u8], path: &str) -> Result<Vec<u8>, String> {
+    let not_found = || format!("No entry {path} in the archive");
+    let mut visit = |entry: ArchiveEntry, reader: &mut dyn Read| {
+        if entry.path != path || entry.is_dir {
+            return Ok(None);
+        }
+        read_limited(reader, entry.size).map(Some)
+    };
+    match format(bytes).ok_or("Not a zip or tar archive")? {
+        Format::Zip => {
+            let mut zip = open_zip(bytes)?;
+            let file = zip.by_name(path).map_err(|e| match e {
+                zip::result::ZipError::FileNotFound => not_found(),
+                e => format!("Failed to read {path}: {e}"),
+            })?;
+            if file.is_dir() {
+                return Err(not_found());
+            }
+            let size = file.size();
+            read_limited(file, size)
+        }
+        Format::Tar => walk_tar(bytes, &mut visit)?.ok_or_else(not_found),
+        Format::TarGz => walk_tar(GzDecoder::new(bytes), &mut visit)?.ok_or_else(not_found),
+    }
+}
+
+async fn read(store: &Store, hash: &str) -> Result<Vec<u8>, String> {
+    let integrity = hash
+        .parse::<ssri::Integrity>()
+        .map_err(|e| format!("Invalid hash format: {e}"))?;
+    crate::integrity::read(store, &integrity)
+        .await
+        .map_err(String::from)
+}
+
+/// The files and folders in a zip, tar or tar.gz attachment, in archive order.
+#[tauri::command]
+pub async fn list_archive_entries(
+    store: State<'_, Store>,
+    hash: String,
+) -> Result<Vec<ArchiveEntry>, String> {
+    let bytes = read(&store, &hash).await?;
+    tokio::task::spawn_blocking(move || list(&bytes))
+        .await
+        .map_err(|e| format!("Failed to list archive: {e}"))?
+}
+
+/// One file out of a zip, tar or tar.gz attachment, decompressed in memory without
+/// unpacking the rest. `entry` is a path as listed by `list_archive_entries`.
+#[tauri::command]
+pub async fn extract_archive_entry(
+    store: State<'_, Store>,
+    hash: String,
+    entry: String,
+) -> Result<tauri::ipc::Response, String> {
+    let bytes = read(&store, &hash).await?;
+    let file = tokio::task::spawn_blocking(move || extract(&bytes, &entry))
+        .await
+        .map_err(|e| format!("Failed to extract entry: {e}"))??;
+    Ok(tauri::ipc::Response::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    const FILES: &[(&str, &[u8])] = &[
+        ("project/README.md", b"# Project\n"),
+        ("project/src/main.rs", b"fn main() {}\n"),
+    ];
+
+    fn zip() -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.add_directory("project/", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        for (name, bytes) in FILES {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(bytes).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn tar_gz() -> Vec<u8> {
+        let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Default::default()));
+        for (name, bytes) in FILES {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, *bytes).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_zip() {
+        let zip = zip();
+        assert_eq!(format(&zip), Some(Format::Zip));
+        let entries = list(&zip).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].is_dir);
+        assert_eq!(
+            entries[2],
+            ArchiveEntry {
+                path: "project/src/main.rs".to_string(),
+                size: 13,
+                is_dir: false,
+            }
+        );
+        assert_eq!(extract(&zip, "project/README.md").unwrap(), b"# Project\n");
+        assert!(extract(&zip, "project/").is_err());
+        assert!(extract(&zip, "missing.txt").is_err());
+    }
+
+    #[test]
+    fn test_tar_gz() {
+        let tar_gz = tar_gz();
+        assert_eq!(format(&tar_gz), Some(Format::TarGz));
+        let paths: Vec<String> = list(&tar_gz)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        assert_eq!(paths, vec!["project/README.md", "project/src/main.rs"]);
+        assert_eq!(
+            extract(&tar_gz, "project/src/main.rs").unwrap(),
+            b"fn main() {}\n"
+        );
+        assert!(extract(&tar_gz, "missing.txt").is_err());
+        assert_eq!(format(b"plain text"), None);
+    }
+}
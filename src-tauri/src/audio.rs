@@ -0,0 +1,208 @@
+use base64::Engine;
+use std::io::Cursor;
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::append_frame;
+use crate::import::add_note;
+use crate::windows::emit_frame;
+
+/// A recording ready to store: the encoded bytes and what's known about them.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Encoded {
+    bytes: Vec<u8>,
+    mime: String,
+    extension: &'static str,
+    duration_ms: Option<u64>,
+    sample_rate: Option<u32>,
+}
+
+/// Re-encodes uncompressed WAV as FLAC, roughly halving it without loss. Float samples are
+/// quantized to 16 bits on the way.
+fn wav_to_flac(wav: &[u8]) -> Result<Encoded, String> {
+    let mut reader =
+        hound::WavReader::new(Cursor::new(wav)).map_err(|e| format!("Invalid WAV: {e}"))?;
+    let spec = reader.spec();
+    let (samples, bits) = match spec.sample_format {
+        hound::SampleFormat::Int => (
+            reader
+                .samples::<i32>()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Invalid WAV: {e}"))?,
+            usize::from(spec.bits_per_sample),
+        ),
+        hound::SampleFormat::Float => (
+            reader
+                .samples::<f32>()
+                .map(|s| s.map(|s| (s.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i32))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Invalid WAV: {e}"))?,
+            16,
+        ),
+    };
+    let channels = usize::from(spec.channels);
+    let frames = samples.len() / channels.max(1);
+
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| format!("Invalid FLAC settings: {e:?}"))?;
+    let source = flacenc::source::MemSource::from_samples(
+        &samples,
+        channels,
+        bits,
+        usize::try_from(spec.sample_rate).unwrap_or_default(),
+    );
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| format!("Failed to encode FLAC: {e:?}"))?;
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    flacenc::component::BitRepr::write(&stream, &mut sink)
+        .map_err(|e| format!("Failed to encode FLAC: {e:?}"))?;
+
+    Ok(Encoded {
+        bytes: sink.as_slice().to_vec(),
+        mime: "audio/flac".to_string(),
+        extension: "flac",
+        duration_ms: Some(frames as u64 * 1000 / u64::from(spec.sample_rate.max(1))),
+        sample_rate: Some(spec.sample_rate),
+    })
+}
+
+/// Compressed recordings (what the webview's MediaRecorder produces) are kept as they are;
+/// WAV is compressed to FLAC.
+fn encode(bytes: Vec<u8>, mime: &str, duration_ms: Option<u64>) -> Result<Encoded, String> {
+    let mime = mime.split(';').next().unwrap_or(mime).trim().to_lowercase();
+    if matches!(mime.as_str(), "audio/wav" | "audio/wave" | "audio/x-wav") {
+        return wav_to_flac(&bytes);
+    }
+    let extension = match mime.as_str() {
+        "audio/webm" => "webm",
+        "audio/ogg" => "ogg",
+        "audio/mp4" | "audio/m4a" | "audio/x-m4a" => "m4a",
+        "audio/mpeg" => "mp3",
+        "audio/flac" => "flac",
+        _ => return Err(format!("Unsupported audio format: {mime}")),
+    };
+    Ok(Encoded {
+        bytes,
+        mime,
+        extension,
+        duration_ms,
+        sample_rate: None,
+    })
+}
+
+/// Decodes and encodes a recording made in the frontend (base64 `data`).
+pub(crate) async fn prepare(
+    data: &str,
+    mime: String,
+    duration_ms: Option<u64>,
+) -> Result<Encoded, String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| format!("Invalid audio data: {e}"))?;
+    tokio::task::spawn_blocking(move || encode(bytes, &mime, duration_ms))
+        .await
+        .map_err(|e| format!("Failed to encode audio: {e}"))?
+}
+
+/// Attaches a recording to a note as an `attachment.audio` frame. `extra` adds to the meta.
+pub(crate) async fn append_audio(
+    store: &Store,
+    yak_id: &str,
+    note_id: &str,
+    encoded: Encoded,
+    extra: serde_json::Value,
+) -> Result<Frame, String> {
+    let name = format!(
+        "voice-memo-{}.{}",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+        encoded.extension
+    );
+    let mut meta = match extra {
+        serde_json::Value::Object(fields) => fields,
+        _ => serde_json::Map::new(),
+    };
+    for (key, value) in [
+        ("yak_id", serde_json::json!(yak_id)),
+        ("note_id", serde_json::json!(note_id)),
+        ("name", serde_json::json!(name)),
+        ("mime", serde_json::json!(encoded.mime)),
+        ("size", serde_json::json!(encoded.bytes.len())),
+        ("duration_ms", serde_json::json!(encoded.duration_ms)),
+        ("sample_rate", serde_json::json!(encoded.sample_rate)),
+    ] {
+        meta.insert(key.to_string(), value);
+    }
+    append_frame(
+        store,
+        "attachment.audio",
+        Some(&encoded.bytes),
+        Some(meta.into()),
+    )
+    .await
+}
+
+/// Stores a voice memo recorded in the frontend (base64 `data`) as an `attachment.audio`
+/// frame. Without a `note_id`, a new "Voice memo" note is created for it. `duration_ms`
+/// is only needed for compressed formats, whose length isn't worked out here.
+#[tauri::command]
+pub async fn add_audio_note(
+    store: State<'_, Store>,
+    app: AppHandle,
+    yak_id: String,
+    note_id: Option<String>,
+    data: String,
+    mime: String,
+    duration_ms: Option<u64>,
+) -> Result<String, String> {
+    let encoded = prepare(&data, mime, duration_ms).await?;
+    let note_id = match note_id {
+        Some(note_id) => note_id,
+        None => {
+            let note = add_note(&store, &yak_id, "Voice memo", serde_json::json!({})).await?;
+            let _ = emit_frame(&app, &note);
+            note.id.to_string()
+        }
+    };
+    let frame = append_audio(&store, &yak_id, &note_id, encoded, serde_json::json!({})).await?;
+    emit_frame(&app, &frame).map_err(|e| format!("Failed to emit frame: {e}"))?;
+    Ok(frame.id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav_to_flac() {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+        for i in 0..16000 {
+            writer
+                .write_sample(((i % 100) * 300 - 15000) as i16)
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+        let wav = wav.into_inner();
+
+        let encoded = encode(wav.clone(), "audio/wav", None).unwrap();
+        assert_eq!(encoded.mime, "audio/flac");
+        assert_eq!(encoded.duration_ms, Some(2000));
+        assert!(encoded.bytes.starts_with(b"fLaC"));
+        assert!(encoded.bytes.len() < wav.len());
+    }
+
+    #[test]
+    fn test_compressed_audio_kept() {
+        let encoded = encode(vec![1, 2, 3], "audio/webm;codecs=opus", Some(1500)).unwrap();
+        assert_eq!(encoded.extension, "webm");
+        assert_eq!(encoded.bytes, vec![1, 2, 3]);
+        assert!(encode(vec![], "video/mp4", None).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::mpsc;
+use xs::store::{FollowOption, ReadOptions, Store};
+
+use crate::read_model::ReadModel;
+
+/// Waited after a change before the index is rebuilt, so a burst of edits rebuilds it once
+/// and the read model has folded them in.
+const SETTLE: Duration = Duration::from_secs(1);
+const DEFAULT_LIMIT: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LinkSuggestion {
+    pub note_id: String,
+    pub yak_id: String,
+    pub title: String,
+}
+
+#[derive(Debug)]
+struct Entry {
+    note_id: String,
+    yak_id: String,
+    title: String,
+    lower: String,
+}
+
+/// Note titles, sorted for prefix lookups, with a trigram index for matches mid-title.
+#[derive(Debug, Default)]
+struct TitleIndex {
+    entries: Vec<Entry>,
+    /// Trigram -> entries whose title contains it
+    trigrams: HashMap<String, Vec<u32>>,
+}
+
+fn trigrams(text: &str) -> HashSet<String> {
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .windows(3)
+        .map(|window| window.iter().collect())
+        .collect()
+}
+
+impl TitleIndex {
+    fn build(titles: Vec<(String, String, String)>) -> Self {
+        let mut entries: Vec<Entry> = titles
+            .into_iter()
+            .filter(|(_, _, title)| !title.trim().is_empty())
+            .map(|(note_id, yak_id, title)| Entry {
+                note_id,
+                yak_id,
+                lower: title.to_lowercase(),
+                title,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.lower.cmp(&b.lower).then(a.note_id.cmp(&b.note_id)));
+        let mut index: HashMap<String, Vec<u32>> = HashMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            for trigram in trigrams(&entry.lower) {
+                index.entry(trigram).or_default().push(i as u32);
+            }
+        }
+        Self {
+            entries,
+            trigrams: index,
+        }
+    }
+
+    /// Titles starting with `prefix`, then titles containing it elsewhere, each in title
+    /// order, ignoring case.
+    fn suggest(&self, prefix: &str, yak_id: Option<&str>, limit: usize) -> Vec<LinkSuggestion> {
+        let query = prefix.trim().to_lowercase();
+        let in_yak = |entry: &&Entry| yak_id.map_or(true, |yak_id| entry.yak_id == yak_id);
+        let start = self
+            .entries
+            .partition_point(|entry| entry.lower.as_str() < query.as_str());
+        let mut found: Vec<&Entry> = self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.lower.starts_with(&query))
+            .filter(in_yak)
+            .take(limit)
+            .collect();
+
+        // Mid-title matches, from the rarest of the query's trigrams
+        let query_trigrams = trigrams(&query);
+        let rarest = query_trigrams
+            .iter()
+            .map(|trigram| self.trigrams.get(trigram).map_or(&[][..], Vec::as_slice))
+            .min_by_key(|ids| ids.len());
+        if let Some(ids) = rarest.filter(|_| found.len() < limit) {
+            let mut inside: Vec<&Entry> = ids
+                .iter()
+                .map(|&i| &self.entries[i as usize])
+                .filter(|entry| !entry.lower.starts_with(&query) && entry.lower.contains(&query))
+                .filter(in_yak)
+                .take(limit - found.len())
+                .collect();
+            found.append(&mut inside);
+        }
+
+        found
+            .into_iter()
+            .map(|entry| LinkSuggestion {
+                note_id: entry.note_id.clone(),
+                yak_id: entry.yak_id.clone(),
+                title: entry.title.clone(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+pub struct AutocompleteState {
+    index: RwLock<TitleIndex>,
+}
+
+pub(crate) async fn rebuild(app: &AppHandle, store: &Store) {
+    let titles = app.state::<ReadModel>().titles(store).await;
+    let index = tokio::task::spawn_blocking(move || TitleIndex::build(titles)).await;
+    match index {
+        Ok(index) => *app.state::<AutocompleteState>().index.write().unwrap() = index,
+        Err(e) => eprintln!("Failed to build link index: {e}"),
+    }
+}
+
+/// Keeps the title index current as notes are created, edited, renamed, moved or locked.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let (kick, mut kicked) = mpsc::channel::<()>(1);
+    let follower_store = store.clone();
+    tokio::spawn(async move {
+        let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+        let mut rx = follower_store.read(read_options).await;
+        while let Some(frame) = rx.recv().await {
+            let topic = frame.topic.as_str();
+            if topic == "xs.threshold"
+                || ["note.", "yak.", "lock."]
+                    .iter()
+                    .any(|p| topic.starts_with(p))
+            {
+                let _ = kick.try_send(());
+            }
+        }
+    });
+
+    while kicked.recv().await.is_some() {
+        tokio::time::sleep(SETTLE).await;
+        let _ = kicked.try_recv();
+        rebuild(&app, &store).await;
+    }
+}
+
+/// Notes whose title starts with, or else contains, `prefix`, for completing `[[` links as
+/// they're typed. `yak_id` keeps to one yak. Answered from an in-memory index, so it stays
+/// fast with tens of thousands of notes.
+#[tauri::command]
+pub async fn suggest_links(
+    state: State<'_, AutocompleteState>,
+    prefix: String,
+    yak_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<LinkSuggestion>, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let index = state.index.read().unwrap();
+    Ok(index.suggest(&prefix, yak_id.as_deref(), limit.unwrap_or(DEFAULT_LIMIT)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(titles: &[(&str, &str)]) -> TitleIndex {
+        TitleIndex::build(
+            titles
+                .iter()
+                .enumerate()
+                .map(|(i, (yak, title))| (format!("n{i}"), yak.to_string(), title.to_string()))
+                .collect(),
+        )
+    }
+
+    fn suggested(index: &TitleIndex, prefix: &str, yak_id: Option<&str>) -> Vec<String> {
+        index
+            .suggest(prefix, yak_id, 10)
+            .into_iter()
+            .map(|suggestion| suggestion.title)
+            .collect()
+    }
+
+    #[test]
+    fn test_suggest() {
+        let index = titles(&[
+            ("home", "Groceries"),
+            ("home", "Weekly groceries"),
+            ("work", "Growth plan"),
+            ("work", "Q3 roadmap"),
+            ("home", ""),
+        ]);
+        assert_eq!(
+            suggested(&index, "gro", None),
+            vec!["Groceries", "Growth plan", "Weekly groceries"]
+        );
+        assert_eq!(suggested(&index, "GROC", Some("home")).len(), 2);
+        assert_eq!(suggested(&index, "gro", Some("work")), vec!["Growth plan"]);
+        assert_eq!(suggested(&index, "road", None), vec!["Q3 roadmap"]);
+        assert!(suggested(&index, "zebra", None).is_empty());
+        // Too short to match mid-title
+        assert_eq!(suggested(&index, "ro", None), Vec::<String>::new());
+        assert_eq!(index.suggest("", None, 2).len(), 2);
+    }
+}
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use tauri_plugin_autostart::ManagerExt;
+use xs::store::{Frame, Store};
+
+use crate::settings::{latest_setting, load_setting, save_setting};
+
+const CONFIG_TOPIC: &str = "autostart.config";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct AutostartConfig {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AutostartStatus {
+    pub enabled: bool,
+    /// Whether the OS entry (Launch Agent, registry key or XDG autostart file) is present
+    pub registered: bool,
+}
+
+/// Adds or removes the OS login entry to match `enabled`.
+fn apply(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    let autolaunch = app.autolaunch();
+    let registered = autolaunch
+        .is_enabled()
+        .map_err(|e| format!("Failed to check autostart: {e}"))?;
+    match (enabled, registered) {
+        (true, false) => autolaunch
+            .enable()
+            .map_err(|e| format!("Failed to enable autostart: {e}")),
+        (false, true) => autolaunch
+            .disable()
+            .map_err(|e| format!("Failed to disable autostart: {e}")),
+        _ => Ok(()),
+    }
+}
+
+fn status(app: &AppHandle, config: &AutostartConfig) -> AutostartStatus {
+    AutostartStatus {
+        enabled: config.enabled,
+        registered: app.autolaunch().is_enabled().unwrap_or(false),
+    }
+}
+
+/// Restores the login entry if it went missing, e.g. after the app was moved or reinstalled.
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let config: AutostartConfig = latest_setting(frames, CONFIG_TOPIC);
+    if let Err(e) = apply(app, config.enabled) {
+        eprintln!("{e}");
+    }
+}
+
+/// Starts Yaks at login, so the capture shortcut is available after a reboot.
+#[tauri::command]
+pub async fn set_autostart(
+    app: AppHandle,
+    store: State<'_, Store>,
+    enabled: bool,
+) -> Result<AutostartStatus, String> {
+    apply(&app, enabled)?;
+    let config = AutostartConfig { enabled };
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(status(&app, &config))
+}
+
+#[tauri::command]
+pub async fn get_autostart(
+    app: AppHandle,
+    store: State<'_, Store>,
+) -> Result<AutostartStatus, String> {
+    let config: AutostartConfig = load_setting(&store, CONFIG_TOPIC).await;
+    Ok(status(&app, &config))
+}
@@ -0,0 +1,326 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use xs::store::Store;
+
+use crate::projection::Projection;
+use crate::search::{keyword_search, Match};
+use crate::{append_frame, read_all_frames, read_model};
+
+const DEFAULT_SIZES: &[usize] = &[10_000, 100_000, 1_000_000];
+/// Bumped when the report's shape changes, so tooling comparing runs can tell.
+const REPORT_VERSION: u32 = 1;
+/// Notes per yak in the synthetic store.
+const NOTES_PER_YAK: usize = 1_000;
+/// Share of note frames that revise an earlier note rather than create one.
+const EDIT_RATIO: f64 = 0.2;
+const SEARCH_LIMIT: usize = 50;
+const WORDS: &[&str] = &[
+    "yak", "shave", "deploy", "garden", "invoice", "meeting", "review", "draft", "kernel",
+    "recipe", "travel", "budget", "release", "sketch", "backup", "migrate", "lecture", "podcast",
+    "errand", "refactor", "harvest", "ticket", "journal", "archive",
+];
+/// Searched for in every run: common words, a pair, and one that never matches.
+const QUERIES: &[&str] = &["yak", "garden", "deploy review", "kernel release", "zebra"];
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Latency {
+    pub count: usize,
+    pub mean_us: u64,
+    pub p50_us: u64,
+    pub p95_us: u64,
+    pub p99_us: u64,
+    pub max_us: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppendReport {
+    pub latency: Latency,
+    pub frames_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayReport {
+    /// Reading every frame back from the store
+    pub read_ms: u64,
+    /// Folding them into a projection
+    pub fold_ms: u64,
+    /// Reading note content from the CAS
+    pub content_ms: u64,
+    pub frames_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchReport {
+    pub queries: Vec<String>,
+    /// Over the projection, as before the read model is ready
+    pub keyword: Latency,
+    /// Building the read model's database from the log
+    pub index_ms: u64,
+    /// Through the read model's full-text table
+    pub full_text: Latency,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SizeReport {
+    pub frames: usize,
+    pub notes: usize,
+    pub append: AppendReport,
+    pub replay: ReplayReport,
+    pub search: SearchReport,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub version: u32,
+    pub started_at: String,
+    pub seed: u64,
+    pub runs: Vec<SizeReport>,
+}
+
+#[derive(Debug)]
+struct Args {
+    sizes: Vec<usize>,
+    out: Option<PathBuf>,
+    dir: PathBuf,
+    seed: u64,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut parsed = Args {
+        sizes: DEFAULT_SIZES.to_vec(),
+        out: None,
+        dir: std::env::temp_dir(),
+        seed: 0,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--sizes" => {
+                parsed.sizes = value()?
+                    .split(',')
+                    .map(|size| size.trim().replace('_', ""))
+                    .map(|size| size.parse().map_err(|_| format!("Invalid size: {size}")))
+                    .collect::<Result<_, _>>()?;
+            }
+            "--out" => parsed.out = Some(PathBuf::from(value()?)),
+            "--dir" => parsed.dir = PathBuf::from(value()?),
+            "--seed" => {
+                let seed = value()?;
+                parsed.seed = seed.parse().map_err(|_| format!("Invalid seed: {seed}"))?;
+            }
+            _ => return Err(format!("Unknown argument: {arg}")),
+        }
+    }
+    if parsed.sizes.is_empty() || parsed.sizes.contains(&0) {
+        return Err("Sizes must be at least 1".to_string());
+    }
+    Ok(parsed)
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros() as u64
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+fn per_sec(count: usize, elapsed: Duration) -> f64 {
+    count as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+}
+
+fn latency(mut samples: Vec<Duration>) -> Latency {
+    if samples.is_empty() {
+        return Latency::default();
+    }
+    samples.sort();
+    let at = |quantile: f64| {
+        let index = ((samples.len() - 1) as f64 * quantile).round() as usize;
+        micros(samples[index])
+    };
+    let total: Duration = samples.iter().sum();
+    Latency {
+        count: samples.len(),
+        mean_us: micros(total / samples.len() as u32),
+        p50_us: at(0.5),
+        p95_us: at(0.95),
+        p99_us: at(0.99),
+        max_us: micros(samples[samples.len() - 1]),
+    }
+}
+
+fn sentence(rng: &mut StdRng) -> String {
+    let count = rng.gen_range(4..16);
+    (0..count)
+        .map(|_| *WORDS.choose(rng).unwrap())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Fills `store` with `frames` synthetic frames, one yak per `NOTES_PER_YAK` notes and the
+/// rest notes or edits of earlier ones, timing each append.
+async fn fill(store: &Store, frames: usize, seed: u64) -> Result<Vec<Duration>, String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut latencies = Vec::with_capacity(frames);
+    let mut yak_id = String::new();
+    let mut notes: Vec<String> = Vec::new();
+    for i in 0..frames {
+        let (topic, content, meta) = if i % (NOTES_PER_YAK + 1) == 0 {
+            let name = format!("Yak {}", i / (NOTES_PER_YAK + 1));
+            ("yak.create", None, json!({ "name": name }))
+        } else if !notes.is_empty() && rng.gen_bool(EDIT_RATIO) {
+            let note_id = notes.choose(&mut rng).unwrap().clone();
+            let content = format!("# {}\n{}", sentence(&mut rng), sentence(&mut rng));
+            let meta = json!({ "yak_id": yak_id, "note_id": note_id });
+            ("note.edit", Some(content), meta)
+        } else {
+            let content = format!("# {}\n{}", sentence(&mut rng), sentence(&mut rng));
+            ("note.create", Some(content), json!({ "yak_id": yak_id }))
+        };
+        let started = Instant::now();
+        let frame = append_frame(
+            store,
+            topic,
+            content.as_deref().map(str::as_bytes),
+            Some(meta),
+        )
+        .await?;
+        latencies.push(started.elapsed());
+        match topic {
+            "yak.create" => {
+                yak_id = frame.id.to_string();
+                notes.clear();
+            }
+            "note.create" => notes.push(frame.id.to_string()),
+            _ => {}
+        }
+    }
+    Ok(latencies)
+}
+
+/// Runs every measurement against a fresh store of `frames` frames in `dir`.
+async fn run_size(dir: &Path, frames: usize, seed: u64) -> Result<SizeReport, String> {
+    let store = Store::new(dir.join("store"));
+
+    let started = Instant::now();
+    let appends = fill(&store, frames, seed).await?;
+    let append = AppendReport {
+        latency: latency(appends),
+        frames_per_sec: per_sec(frames, started.elapsed()),
+    };
+
+    let started = Instant::now();
+    let log = read_all_frames(&store).await;
+    let read = started.elapsed();
+    let started = Instant::now();
+    let mut projection = Projection::from_frames(&log).into_current();
+    let fold = started.elapsed();
+    let started = Instant::now();
+    projection.resolve_content(&store).await;
+    let content = started.elapsed();
+    let replay = ReplayReport {
+        read_ms: millis(read),
+        fold_ms: millis(fold),
+        content_ms: millis(content),
+        frames_per_sec: per_sec(log.len(), read + fold),
+    };
+
+    let mut keyword = Vec::new();
+    for query in QUERIES {
+        let started = Instant::now();
+        keyword_search(&projection, query, None, Match::All, SEARCH_LIMIT);
+        keyword.push(started.elapsed());
+    }
+    let started = Instant::now();
+    let conn = read_model::build_database(&store, &dir.join("read-model.sqlite")).await?;
+    let index = started.elapsed();
+    let mut full_text = Vec::new();
+    for query in QUERIES {
+        let started = Instant::now();
+        read_model::search_database(&conn, query, SEARCH_LIMIT)?;
+        full_text.push(started.elapsed());
+    }
+
+    Ok(SizeReport {
+        frames: log.len(),
+        notes: projection.notes.len(),
+        append,
+        replay,
+        search: SearchReport {
+            queries: QUERIES.iter().map(|query| query.to_string()).collect(),
+            keyword: latency(keyword),
+            index_ms: millis(index),
+            full_text: latency(full_text),
+        },
+    })
+}
+
+async fn bench(args: &Args) -> Result<BenchReport, String> {
+    let mut report = BenchReport {
+        version: REPORT_VERSION,
+        started_at: chrono::Utc::now().to_rfc3339(),
+        seed: args.seed,
+        runs: Vec::new(),
+    };
+    for &frames in &args.sizes {
+        eprintln!("Benchmarking {frames} frames...");
+        let dir = args.dir.join(format!("yaks-bench-{}", scru128::new()));
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+        let run = run_size(&dir, frames, args.seed).await;
+        let _ = std::fs::remove_dir_all(&dir);
+        report.runs.push(run?);
+    }
+    Ok(report)
+}
+
+/// `bench [--sizes 10000,100000] [--out report.json] [--dir DIR] [--seed N]`: measures append
+/// latency, replay speed and search latency on synthetic stores of each size (10k, 100k and
+/// 1M frames by default) built in throwaway dirs under `DIR`, and writes a JSON report to
+/// `--out` or stdout. The same seed always builds the same stores.
+pub fn run(args: Vec<String>) -> Result<(), String> {
+    let args = parse_args(&args)?;
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    let report = runtime.block_on(bench(&args))?;
+    let json = serde_json::to_string_pretty(&report)
+        .map_err(|e| format!("Failed to serialize report: {e}"))?;
+    match &args.out {
+        Some(path) => std::fs::write(path, json)
+            .map_err(|e| format!("Failed to write {}: {e}", path.display())),
+        None => {
+            println!("{json}");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_run_size() {
+        let dir = tempdir().unwrap();
+        let report = run_size(dir.path(), 2_500, 7).await.unwrap();
+        assert_eq!(report.frames, 2_500);
+        assert_eq!(report.append.latency.count, 2_500);
+        assert!(report.notes > 1_500 && report.notes < 2_497);
+        assert_eq!(report.search.keyword.count, QUERIES.len());
+        assert!(report.append.latency.p50_us <= report.append.latency.p99_us);
+
+        let args: Vec<String> = ["--sizes", "10_000,500", "--seed", "3"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let parsed = parse_args(&args).unwrap();
+        assert_eq!((parsed.sizes, parsed.seed), (vec![10_000, 500], 3));
+        assert!(parse_args(&["--sizes".to_string(), "0".to_string()]).is_err());
+    }
+}
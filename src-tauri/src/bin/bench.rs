@@ -0,0 +1,6 @@
+fn main() {
+    if let Err(e) = yaks_lib::bench::run(std::env::args().skip(1).collect()) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,274 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::location::StoreLocation;
+use crate::read_all_frames;
+use crate::shutdown::begin_write;
+
+/// Where xs keeps content inside the store dir: one file per blob, at
+/// `<algorithm>/<first two hex digits>/<next two>/<rest>`.
+const CONTENT_DIR: &str = "cacache/content-v2";
+/// Blobs this recent are never collected: an append inserts its content before writing
+/// the frame that references it.
+const GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// The CAS already stores identical content once; this is how much that saves, and what a
+/// sweep could free.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct BlobStats {
+    pub blobs: usize,
+    pub stored_bytes: u64,
+    /// References from frames, counting a frame once per blob
+    pub references: usize,
+    /// Blobs referenced by more than one frame
+    pub shared: usize,
+    /// What storing each reference's content separately would take beyond `stored_bytes`
+    pub saved_bytes: u64,
+    /// Blobs no frame references any more
+    pub unreferenced: usize,
+    pub unreferenced_bytes: u64,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SweepReport {
+    pub removed: usize,
+    pub freed_bytes: u64,
+}
+
+struct Blob {
+    key: String,
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// The blob's key in the content dir's layout: its algorithm and hex digest.
+fn key(hash: &ssri::Integrity) -> String {
+    let (algorithm, hex) = hash.to_hex();
+    format!("{algorithm}/{hex}")
+}
+
+/// Hashes anywhere in `meta`, such as a video's chunks and poster or a page snapshot.
+fn meta_hashes(meta: &Value, found: &mut HashSet<String>) {
+    match meta {
+        Value::String(value) if value.starts_with("sha") => {
+            if let Ok(hash) = value.parse::<ssri::Integrity>() {
+                found.insert(key(&hash));
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|value| meta_hashes(value, found)),
+        Value::Object(fields) => fields.values().for_each(|value| meta_hashes(value, found)),
+        _ => {}
+    }
+}
+
+/// How many frames reference each blob, by key.
+fn references(frames: &[Frame]) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for frame in frames {
+        let mut keys = HashSet::new();
+        if let Some(hash) = &frame.hash {
+            keys.insert(key(hash));
+        }
+        if let Some(meta) = &frame.meta {
+            meta_hashes(meta, &mut keys);
+        }
+        for key in keys {
+            *counts.entry(key).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+fn walk(dir: &Path, blobs: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => walk(&entry.path(), blobs),
+            Ok(_) => blobs.push(entry.path()),
+            Err(_) => {}
+        }
+    }
+}
+
+/// Every blob in the CAS of the store at `store_path`.
+fn stored(store_path: &Path) -> Vec<Blob> {
+    let root = store_path.join(CONTENT_DIR);
+    let mut paths = Vec::new();
+    walk(&root, &mut paths);
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let parts: Vec<String> = path
+                .strip_prefix(&root)
+                .ok()?
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            let (algorithm, hex) = parts.split_first()?;
+            let metadata = std::fs::metadata(&path).ok()?;
+            Some(Blob {
+                key: format!("{algorithm}/{}", hex.concat()),
+                size: metadata.len(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                path,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn stats(store_path: &Path, frames: &[Frame]) -> BlobStats {
+    let references = references(frames);
+    let mut stats = BlobStats::default();
+    for blob in stored(store_path) {
+        stats.blobs += 1;
+        stats.stored_bytes += blob.size;
+        match references.get(&blob.key).copied().unwrap_or(0) {
+            0 => {
+                stats.unreferenced += 1;
+                stats.unreferenced_bytes += blob.size;
+            }
+            count => {
+                stats.references += count;
+                if count > 1 {
+                    stats.shared += 1;
+                    stats.saved_bytes += blob.size * (count as u64 - 1);
+                }
+            }
+        }
+    }
+    stats
+}
+
+/// Removes blobs no frame references, leaving any written after `now - GRACE`. A blob is
+/// only removed once the last frame referencing it is gone, however many notes shared it.
+fn sweep_blobs(store_path: &Path, frames: &[Frame], now: SystemTime) -> SweepReport {
+    let references = references(frames);
+    let mut report = SweepReport::default();
+    for blob in stored(store_path) {
+        let recent = now
+            .duration_since(blob.modified)
+            .map_or(true, |age| age < GRACE);
+        if recent || references.contains_key(&blob.key) {
+            continue;
+        }
+        match std::fs::remove_file(&blob.path) {
+            Ok(()) => {
+                report.removed += 1;
+                report.freed_bytes += blob.size;
+            }
+            Err(e) => eprintln!("Failed to remove blob {}: {e}", blob.key),
+        }
+    }
+    report
+}
+
+/// Sweeps the CAS after frames were removed, e.g. by compaction or retention.
+pub(crate) async fn sweep(app: &AppHandle, store: &Store) -> Result<SweepReport, String> {
+    let store_path = app.state::<StoreLocation>().0.clone();
+    let frames = read_all_frames(store).await;
+    tokio::task::spawn_blocking(move || sweep_blobs(&store_path, &frames, SystemTime::now()))
+        .await
+        .map_err(|e| format!("Failed to sweep content: {e}"))
+}
+
+/// How many blobs the CAS holds, how many are shared between frames and the bytes that
+/// saves, and how many are no longer referenced.
+#[tauri::command]
+pub async fn get_blob_stats(
+    store: State<'_, Store>,
+    location: State<'_, StoreLocation>,
+) -> Result<BlobStats, String> {
+    let frames = read_all_frames(&store).await;
+    let store_path = location.0.clone();
+    tokio::task::spawn_blocking(move || stats(&store_path, &frames))
+        .await
+        .map_err(|e| format!("Failed to count content: {e}"))
+}
+
+/// Removes content no frame references any more.
+#[tauri::command]
+pub async fn collect_garbage(
+    app: AppHandle,
+    store: State<'_, Store>,
+) -> Result<SweepReport, String> {
+    let _guard = begin_write(&app)?;
+    sweep(&app, &store).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_sweep_blobs() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let shared = b"same text".as_slice();
+        let first = append_frame(
+            &store,
+            "note.create",
+            Some(shared),
+            Some(json!({ "yak_id": "y" })),
+        )
+        .await
+        .unwrap();
+        append_frame(
+            &store,
+            "note.create",
+            Some(shared),
+            Some(json!({ "yak_id": "y" })),
+        )
+        .await
+        .unwrap();
+        let chunk = store.cas_insert(b"chunk").await.unwrap().to_string();
+        append_frame(
+            &store,
+            "attachment.video",
+            None,
+            Some(json!({ "chunks": [chunk] })),
+        )
+        .await
+        .unwrap();
+        let purged = append_frame(
+            &store,
+            "note.create",
+            Some(b"purged".as_slice()),
+            Some(json!({ "yak_id": "y" })),
+        )
+        .await
+        .unwrap();
+
+        let frames = read_all_frames(&store).await;
+        let counted = stats(dir.path(), &frames);
+        assert_eq!(
+            (counted.blobs, counted.shared, counted.references),
+            (3, 1, 4)
+        );
+        assert_eq!(counted.saved_bytes, shared.len() as u64);
+
+        store.remove(&first.id).unwrap();
+        store.remove(&purged.id).unwrap();
+        let frames = read_all_frames(&store).await;
+        assert_eq!(stats(dir.path(), &frames).unreferenced, 1);
+        // Nothing is old enough yet
+        let now = SystemTime::now();
+        assert_eq!(sweep_blobs(dir.path(), &frames, now).removed, 0);
+
+        let report = sweep_blobs(dir.path(), &frames, now + GRACE * 2);
+        assert_eq!(report.removed, 1);
+        let hash = ssri::Integrity::from(shared);
+        assert!(store.cas_read(&hash).await.is_ok());
+        assert!(store.cas_read(&purged.hash.unwrap()).await.is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use tauri::{AppHandle, State};
+use xs::store::Store;
+
+use crate::projection::{BoardColumn, Projection};
+use crate::windows::emit_frames;
+use crate::{append_batch_to_store, read_all_frames, shutdown, AppendRequest};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Board {
+    pub yak_id: String,
+    pub columns: Vec<BoardColumn>,
+    /// Current notes not in any column yet, in creation order
+    pub unplaced: Vec<String>,
+}
+
+fn board(projection: &Projection, yak_id: &str) -> Board {
+    let columns = projection.board(yak_id);
+    let placed: HashSet<&String> = columns.iter().flat_map(|column| &column.note_ids).collect();
+    let unplaced = projection
+        .notes_by_yak
+        .get(yak_id)
+        .map(|ids| {
+            ids.iter()
+                .filter(|id| !placed.contains(id))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    Board {
+        yak_id: yak_id.to_string(),
+        columns,
+        unplaced,
+    }
+}
+
+/// The `note.order` request putting `ordered_ids` (any revision ids) in `column`.
+fn order_request(
+    projection: &Projection,
+    yak_id: &str,
+    column: &str,
+    ordered_ids: &[String],
+) -> Result<AppendRequest, String> {
+    let current = projection
+        .notes_by_yak
+        .get(yak_id)
+        .ok_or_else(|| format!("Unknown yak {yak_id}"))?;
+    if column.trim().is_empty() {
+        return Err("Column name can't be empty".to_string());
+    }
+    let mut note_ids = Vec::with_capacity(ordered_ids.len());
+    for id in ordered_ids {
+        let note_id = projection.resolve(id);
+        if !current.contains(&note_id) {
+            return Err(format!("Note {id} isn't in yak {yak_id}"));
+        }
+        if !note_ids.contains(&note_id) {
+            note_ids.push(note_id);
+        }
+    }
+    Ok(AppendRequest {
+        topic: "note.order".to_string(),
+        content: String::new(),
+        meta: Some(HashMap::from([
+            ("yak_id".to_string(), yak_id.into()),
+            ("column".to_string(), column.into()),
+            ("note_ids".to_string(), note_ids.into()),
+        ])),
+    })
+}
+
+/// Sets the notes in a board column, top to bottom. Notes listed here leave whatever column
+/// they were in; a column that isn't on the board yet is added at the end.
+#[tauri::command]
+pub async fn set_note_order(
+    store: State<'_, Store>,
+    app: AppHandle,
+    yak_id: String,
+    column: String,
+    ordered_ids: Vec<String>,
+) -> Result<Board, String> {
+    let _guard = shutdown::begin_write(&app)?;
+    let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+    let request = order_request(&projection, &yak_id, &column, &ordered_ids)?;
+    let frames = append_batch_to_store(&store, vec![request]).await?;
+    for frame in &frames {
+        projection.apply(frame);
+    }
+    emit_frames(&app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))?;
+    Ok(board(&projection, &yak_id))
+}
+
+#[tauri::command]
+pub async fn get_board(store: State<'_, Store>, yak_id: String) -> Result<Board, String> {
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    if !projection.yaks.contains_key(&yak_id) {
+        return Err(format!("Unknown yak {yak_id}"));
+    }
+    Ok(board(&projection, &yak_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_board_follows_edits_and_deletes() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let mut notes = Vec::new();
+        for content in ["a", "b", "c"] {
+            let note = append_frame(
+                &store,
+                "note.create",
+                Some(content.as_bytes()),
+                Some(json!({ "yak_id": yak_id })),
+            )
+            .await
+            .unwrap();
+            notes.push(note.id.to_string());
+        }
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let todo = vec![notes[1].clone(), notes[0].clone()];
+        let request = order_request(&projection, &yak_id, "todo", &todo).unwrap();
+        append_batch_to_store(&store, vec![request]).await.unwrap();
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let request = order_request(&projection, &yak_id, "done", &notes[..1]).unwrap();
+        append_batch_to_store(&store, vec![request]).await.unwrap();
+        assert!(order_request(&projection, &yak_id, "todo", &["nope".to_string()]).is_err());
+
+        let edit = append_frame(
+            &store,
+            "note.edit",
+            Some(b"b2".as_slice()),
+            Some(json!({ "yak_id": yak_id, "note_id": notes[1] })),
+        )
+        .await
+        .unwrap();
+        append_frame(
+            &store,
+            "note.delete",
+            None,
+            Some(json!({ "yak_id": yak_id, "note_id": notes[0] })),
+        )
+        .await
+        .unwrap();
+
+        let board = board(
+            &Projection::from_frames(&read_all_frames(&store).await),
+            &yak_id,
+        );
+        let columns: Vec<(&str, &[String])> = board
+            .columns
+            .iter()
+            .map(|column| (column.name.as_str(), column.note_ids.as_slice()))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("todo", [edit.id.to_string()].as_slice()),
+                ("done", [].as_slice())
+            ]
+        );
+        assert_eq!(board.unplaced, vec![notes[2].clone()]);
+    }
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::organize::note_moves;
+use crate::projection::Projection;
+use crate::windows::emit_frames;
+use crate::{append_batch_to_store, read_all_frames, AppendRequest};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BulkOp {
+    Tag { tag: String },
+    Untag { tag: String },
+    Move { yak_id: String },
+    SetTaskState { done: bool },
+    Delete,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct BulkReport {
+    /// Id of the `bulk.apply` frame summarizing the operation
+    pub summary_id: Option<String>,
+    pub applied: usize,
+    /// Ids the operation doesn't apply to, e.g. tagging a task, or that it wouldn't change
+    pub skipped: Vec<String>,
+}
+
+fn request(topic: &str, meta: serde_json::Value) -> AppendRequest {
+    let serde_json::Value::Object(meta) = meta else {
+        unreachable!("bulk frame meta is always an object");
+    };
+    AppendRequest {
+        topic: topic.to_string(),
+        content: String::new(),
+        meta: Some(meta.into_iter().collect::<HashMap<_, _>>()),
+    }
+}
+
+/// The frames `op` needs for each id, plus ids it doesn't apply to.
+fn plan(
+    projection: &Projection,
+    frame_ids: &[String],
+    op: &BulkOp,
+) -> Result<(Vec<AppendRequest>, Vec<String>), String> {
+    let mut requests = Vec::new();
+    let mut skipped = Vec::new();
+    for frame_id in frame_ids {
+        if let Some(task) = projection.tasks.get(frame_id) {
+            let meta = serde_json::json!({ "yak_id": task.yak_id, "task_id": task.id });
+            match op {
+                BulkOp::SetTaskState { done } if task.done != *done => {
+                    let mut meta = meta;
+                    meta["done"] = (*done).into();
+                    requests.push(request("task.update", meta));
+                }
+                BulkOp::Move { yak_id } if &task.yak_id != yak_id => {
+                    if !projection.yaks.contains_key(yak_id) {
+                        return Err(format!("Unknown yak {yak_id}"));
+                    }
+                    let mut meta = meta;
+                    meta["yak_id"] = yak_id.clone().into();
+                    meta["from_yak_id"] = task.yak_id.clone().into();
+                    requests.push(request("task.move", meta));
+                }
+                BulkOp::Delete => requests.push(request("task.delete", meta)),
+                _ => skipped.push(frame_id.clone()),
+            }
+            continue;
+        }
+
+        let note_id = projection.resolve(frame_id);
+        let note = projection
+            .notes
+            .get(&note_id)
+            .ok_or_else(|| format!("Unknown note or task {frame_id}"))?;
+        let meta = serde_json::json!({ "yak_id": note.yak_id, "note_id": note_id });
+        match op {
+            BulkOp::Tag { tag } | BulkOp::Untag { tag }
+                if note.tags.contains(tag) == matches!(op, BulkOp::Untag { .. }) =>
+            {
+                let topic = match op {
+                    BulkOp::Tag { .. } => "tag.add",
+                    _ => "tag.remove",
+                };
+                let mut meta = meta;
+                meta["tag"] = tag.clone().into();
+                requests.push(request(topic, meta));
+            }
+            BulkOp::Move { yak_id } => {
+                let moves = note_moves(projection, std::slice::from_ref(frame_id), yak_id)?;
+                if moves.is_empty() {
+                    skipped.push(frame_id.clone());
+                }
+                requests.extend(moves);
+            }
+            BulkOp::Delete => requests.push(request("note.delete", meta)),
+            _ => skipped.push(frame_id.clone()),
+        }
+    }
+    Ok((requests, skipped))
+}
+
+async fn apply(
+    store: &Store,
+    frame_ids: &[String],
+    op: &BulkOp,
+) -> Result<(Vec<Frame>, BulkReport), String> {
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    let (mut requests, skipped) = plan(&projection, frame_ids, op)?;
+    let mut report = BulkReport {
+        summary_id: None,
+        applied: requests.len(),
+        skipped,
+    };
+    if requests.is_empty() {
+        return Ok((Vec::new(), report));
+    }
+
+    let summary = serde_json::json!({
+        "op": op,
+        "frame_ids": frame_ids,
+        "applied": report.applied,
+        "skipped": report.skipped.len(),
+    });
+    requests.push(request("bulk.apply", summary));
+    let frames = append_batch_to_store(store, requests).await?;
+    report.summary_id = frames.last().map(|frame| frame.id.to_string());
+    Ok((frames, report))
+}
+
+/// Applies one operation to many notes and tasks as a single all-or-nothing batch, closed
+/// by a `bulk.apply` summary frame.
+#[tauri::command]
+pub async fn bulk_apply(
+    store: State<'_, Store>,
+    app: AppHandle,
+    frame_ids: Vec<String>,
+    op: BulkOp,
+) -> Result<BulkReport, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let (frames, report) = apply(&store, &frame_ids, &op).await?;
+    emit_frames(&app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_bulk_apply() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let mut ids = Vec::new();
+        for content in ["one", "two", "three"] {
+            let note = append_frame(
+                &store,
+                "note.create",
+                Some(content.as_bytes()),
+                Some(json!({ "yak_id": yak_id })),
+            )
+            .await
+            .unwrap();
+            ids.push(note.id.to_string());
+        }
+        let task = append_frame(
+            &store,
+            "task.create",
+            Some(b"todo".as_slice()),
+            Some(json!({ "yak_id": yak_id })),
+        )
+        .await
+        .unwrap();
+
+        let mut targets = ids.clone();
+        targets.push(task.id.to_string());
+        let tag = BulkOp::Tag {
+            tag: "clip".to_string(),
+        };
+        let (_, report) = apply(&store, &targets, &tag).await.unwrap();
+        assert_eq!(report.applied, 3);
+        assert_eq!(report.skipped, vec![task.id.to_string()]);
+
+        let (_, report) = apply(&store, &ids[..2], &BulkOp::Delete).await.unwrap();
+        assert_eq!(report.applied, 2);
+
+        let frames = read_all_frames(&store).await;
+        assert_eq!(frames.iter().filter(|f| f.topic == "bulk.apply").count(), 2);
+        let projection = Projection::from_frames(&frames);
+        let remaining = projection.current_notes(&yak_id);
+        assert_eq!(remaining.len(), 1);
+        assert!(remaining[0].tags.contains("clip"));
+    }
+}
@@ -0,0 +1,277 @@
+use lru::LruCache;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use xs::store::Store;
+
+use crate::integrity::{self, IntegrityMismatch, ReadError};
+
+/// Memory the cache may hold before evicting the least recently read content.
+const CAPACITY_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: usize,
+    pub capacity_bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+}
+
+struct Inner {
+    entries: LruCache<String, Arc<str>>,
+    bytes: usize,
+    hits: u64,
+    misses: u64,
+}
+
+/// Decoded CAS content keyed by integrity hash, bounded by total size. CAS content never
+/// changes under a hash, so entries only leave by eviction or `invalidate`/`clear`, e.g.
+/// when content is found damaged or the store is swapped.
+pub struct CasCache {
+    inner: Mutex<Inner>,
+    capacity_bytes: usize,
+}
+
+impl Default for CasCache {
+    fn default() -> Self {
+        Self::new(CAPACITY_BYTES)
+    }
+}
+
+impl CasCache {
+    pub(crate) fn new(capacity_bytes: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                entries: LruCache::unbounded(),
+                bytes: 0,
+                hits: 0,
+                misses: 0,
+            }),
+            capacity_bytes,
+        }
+    }
+
+    pub(crate) fn get(&self, hash: &str) -> Option<Arc<str>> {
+        let mut inner = self.inner.lock().unwrap();
+        let content = inner.entries.get(hash).cloned();
+        match content {
+            Some(_) => inner.hits += 1,
+            None => inner.misses += 1,
+        }
+        content
+    }
+
+    pub(crate) fn insert(&self, hash: &str, content: Arc<str>) {
+        // Content bigger than the whole cache would only evict everything else
+        if content.len() > self.capacity_bytes {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        inner.bytes += content.len();
+        if let Some(previous) = inner.entries.put(hash.to_string(), content) {
+            inner.bytes -= previous.len();
+        }
+        while inner.bytes > self.capacity_bytes {
+            match inner.entries.pop_lru() {
+                Some((_, evicted)) => inner.bytes -= evicted.len(),
+                None => break,
+            }
+        }
+    }
+
+    pub(crate) fn invalidate(&self, hash: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(content) = inner.entries.pop(hash) {
+            inner.bytes -= content.len();
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.bytes = 0;
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        let inner = self.inner.lock().unwrap();
+        let reads = inner.hits + inner.misses;
+        CacheStats {
+            entries: inner.entries.len(),
+            bytes: inner.bytes,
+            capacity_bytes: self.capacity_bytes,
+            hits: inner.hits,
+            misses: inner.misses,
+            hit_rate: if reads == 0 {
+                0.0
+            } else {
+                inner.hits as f64 / reads as f64
+            },
+        }
+    }
+
+    /// Reads content as text, from the cache if possible.
+    pub(crate) async fn read(&self, store: &Store, hash: &str) -> Result<Arc<str>, String> {
+        self.read_verified(store, hash).await.map_err(String::from)
+    }
+
+    /// Like `read`, keeping content that fails its integrity check apart from other errors.
+    pub(crate) async fn read_verified(
+        &self,
+        store: &Store,
+        hash: &str,
+    ) -> Result<Arc<str>, ReadError> {
+        if let Some(content) = self.get(hash) {
+            return Ok(content);
+        }
+        let integrity = hash
+            .parse::<ssri::Integrity>()
+            .map_err(|e| ReadError::Failed(format!("Invalid hash format: {e}")))?;
+        let started = std::time::Instant::now();
+        let bytes = integrity::read(store, &integrity).await?;
+        crate::metrics::record_cas_read(bytes.len(), started.elapsed());
+        let content: Arc<str> = String::from_utf8(bytes)
+            .map_err(|e| ReadError::Failed(format!("Invalid UTF-8 content: {e}")))?
+            .into();
+        self.insert(hash, content.clone());
+        Ok(content)
+    }
+}
+
+/// Reads running at once in `get_cas_batch`.
+const BATCH_CONCURRENCY: usize = 16;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CasResult {
+    Content(String),
+    Error(String),
+    #[serde(rename = "integrity_mismatch")]
+    IntegrityMismatch(IntegrityMismatch),
+}
+
+async fn read_batch(
+    store: &Store,
+    cache: Arc<CasCache>,
+    hashes: Vec<String>,
+) -> HashMap<String, CasResult> {
+    let permits = Arc::new(Semaphore::new(BATCH_CONCURRENCY));
+    let mut reads = JoinSet::new();
+    for hash in hashes.into_iter().collect::<HashSet<_>>() {
+        // Each read gets its own store handle, so nothing is held across another's awaits
+        let (store, cache, permits) = (store.clone(), cache.clone(), permits.clone());
+        reads.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let result = match cache.read_verified(&store, &hash).await {
+                Ok(content) => CasResult::Content(content.to_string()),
+                Err(ReadError::IntegrityMismatch(mismatch)) => {
+                    CasResult::IntegrityMismatch(mismatch)
+                }
+                Err(e) => CasResult::Error(e.to_string()),
+            };
+            (hash, result)
+        });
+    }
+    let mut results = HashMap::new();
+    while let Some(read) = reads.join_next().await {
+        match read {
+            Ok((hash, result)) => {
+                results.insert(hash, result);
+            }
+            Err(e) => eprintln!("CAS read task failed: {e}"),
+        }
+    }
+    results
+}
+
+/// Reads many blobs concurrently in one round trip, e.g. for a list view. Each hash maps to
+/// its content, the error reading it, or `integrity_mismatch` for content that's corrupted.
+#[tauri::command]
+pub async fn get_cas_batch(
+    store: tauri::State<'_, Store>,
+    cache: tauri::State<'_, Arc<CasCache>>,
+    locks: tauri::State<'_, crate::locks::LockState>,
+    hashes: Vec<String>,
+) -> Result<HashMap<String, CasResult>, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let mut results = read_batch(&store, cache.inner().clone(), hashes).await;
+    for (hash, result) in results.iter_mut() {
+        if let CasResult::Content(content) = result {
+            *result = match locks.open(hash, content) {
+                Ok(content) => CasResult::Content(content),
+                Err(e) => CasResult::Error(e),
+            };
+        }
+    }
+    Ok(results)
+}
+
+/// `get_cas_batch` encoded as MessagePack and returned as raw bytes, for payloads large
+/// enough that JSON serialization shows.
+#[tauri::command]
+pub async fn get_cas_packed(
+    store: tauri::State<'_, Store>,
+    cache: tauri::State<'_, Arc<CasCache>>,
+    locks: tauri::State<'_, crate::locks::LockState>,
+    hashes: Vec<String>,
+) -> Result<tauri::ipc::Response, String> {
+    let results = get_cas_batch(store, cache, locks, hashes).await?;
+    Ok(tauri::ipc::Response::new(crate::windows::pack(&results)?))
+}
+
+#[tauri::command]
+pub fn cas_cache_stats(cache: tauri::State<'_, Arc<CasCache>>) -> CacheStats {
+    cache.stats()
+}
+
+#[tauri::command]
+pub fn clear_cas_cache(cache: tauri::State<'_, Arc<CasCache>>) {
+    cache.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evicts_least_recently_read() {
+        let cache = CasCache::new(10);
+        cache.insert("a", "aaaa".into());
+        cache.insert("b", "bbbb".into());
+        assert!(cache.get("a").is_some());
+        cache.insert("c", "cccc".into());
+
+        assert!(cache.get("b").is_none());
+        assert_eq!(cache.get("a").as_deref(), Some("aaaa"));
+        assert_eq!(cache.get("c").as_deref(), Some("cccc"));
+
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.bytes), (2, 8));
+        assert_eq!((stats.hits, stats.misses), (3, 1));
+
+        cache.invalidate("a");
+        assert_eq!(cache.stats().bytes, 4);
+        cache.insert("huge", "x".repeat(11).into());
+        assert!(cache.get("huge").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_read_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let one = store.cas_insert(b"one").await.unwrap().to_string();
+        let two = store.cas_insert(b"two").await.unwrap().to_string();
+        let cache = Arc::new(CasCache::default());
+
+        let hashes = vec![one.clone(), two.clone(), one.clone(), "bogus".to_string()];
+        let results = read_batch(&store, cache.clone(), hashes).await;
+        assert_eq!(results.len(), 3);
+        assert!(matches!(&results[&one], CasResult::Content(c) if c == "one"));
+        assert!(matches!(&results[&two], CasResult::Content(c) if c == "two"));
+        assert!(matches!(results["bogus"], CasResult::Error(_)));
+        assert_eq!(cache.stats().entries, 2);
+    }
+}
@@ -0,0 +1,318 @@
+use regex::Regex;
+use serde_json::json;
+use std::sync::OnceLock;
+use tauri::{AppHandle, State};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::locks::is_sealed;
+use crate::projection::{Projection, Task};
+use crate::shutdown::begin_write;
+use crate::windows::{emit_frame, emit_frames};
+use crate::{append_frame, read_all_frames, sync};
+
+/// Task meta holding the text of the checkbox a task was extracted from.
+const CHECKBOX_KEY: &str = "checkbox";
+
+/// A `- [ ]` / `- [x]` line of a note.
+#[derive(Debug, Clone, PartialEq)]
+struct Checkbox {
+    line: usize,
+    text: String,
+    done: bool,
+}
+
+fn checkboxes(content: &str) -> Vec<Checkbox> {
+    static CHECKBOX: OnceLock<Regex> = OnceLock::new();
+    let checkbox = CHECKBOX.get_or_init(|| Regex::new(r"^\s*[-*+] \[([ xX])\] (.*\S)").unwrap());
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let caps = checkbox.captures(text)?;
+            Some(Checkbox {
+                line,
+                text: caps[2].trim().to_string(),
+                done: &caps[1] != " ",
+            })
+        })
+        .collect()
+}
+
+/// `content` with the checkbox on `line` ticked or cleared.
+fn set_checkbox(content: &str, line: usize, done: bool) -> String {
+    content
+        .split_inclusive('\n')
+        .enumerate()
+        .map(|(n, text)| {
+            if n != line {
+                return text.to_string();
+            }
+            let (from, to) = if done { ("[ ]", "[x]") } else { ("[x]", "[ ]") };
+            text.replacen(from, to, 1).replacen("[X]", to, 1)
+        })
+        .collect()
+}
+
+fn checkbox_text(task: &Task) -> Option<&str> {
+    task.meta.as_ref()?.get(CHECKBOX_KEY)?.as_str()
+}
+
+/// Pairs each extracted task of a note with the checkbox it came from, matching on text and
+/// taking duplicates in order. Tasks whose checkbox was reworded or removed go unpaired.
+fn pair<'a>(checkboxes: &'a [Checkbox], tasks: &[&'a Task]) -> Vec<(&'a Checkbox, &'a Task)> {
+    let mut taken = vec![false; checkboxes.len()];
+    let mut tasks: Vec<&Task> = tasks.to_vec();
+    tasks.sort_by(|a, b| a.id.cmp(&b.id));
+    tasks
+        .into_iter()
+        .filter_map(|task| {
+            let text = checkbox_text(task)?;
+            let index = (0..checkboxes.len()).find(|&i| !taken[i] && checkboxes[i].text == text)?;
+            taken[index] = true;
+            Some((&checkboxes[index], task))
+        })
+        .collect()
+}
+
+/// The tasks extracted from the note whose current revision is `note_id`.
+fn linked<'a>(projection: &'a Projection, note_id: &str) -> Vec<&'a Task> {
+    projection
+        .tasks
+        .values()
+        .filter(|task| checkbox_text(task).is_some())
+        .filter(|task| {
+            task.note_id
+                .as_deref()
+                .is_some_and(|id| projection.resolve(id) == note_id)
+        })
+        .collect()
+}
+
+async fn note_content(store: &Store, projection: &Projection, note_id: &str) -> Option<String> {
+    let hash = projection.notes.get(note_id)?.hash.as_ref()?;
+    let bytes = store.cas_read(hash).await.ok()?;
+    String::from_utf8(bytes)
+        .ok()
+        .filter(|content| !is_sealed(content))
+}
+
+/// Creates a task for each checkbox of a note that doesn't have one yet.
+async fn extract(app: &AppHandle, store: &Store, frame_id: &str) -> Result<Vec<Frame>, String> {
+    let _guard = begin_write(app)?;
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    let note_id = projection.resolve(frame_id);
+    let note = projection
+        .notes
+        .get(&note_id)
+        .ok_or_else(|| format!("Note not found: {frame_id}"))?;
+    let content = note_content(store, &projection, &note_id)
+        .await
+        .ok_or_else(|| format!("Note {frame_id} has no readable content"))?;
+    let checkboxes = checkboxes(&content);
+    let existing = linked(&projection, &note_id);
+    let paired = pair(&checkboxes, &existing);
+
+    let mut frames = Vec::new();
+    for checkbox in &checkboxes {
+        if paired
+            .iter()
+            .any(|(paired, _)| paired.line == checkbox.line)
+        {
+            continue;
+        }
+        let meta = json!({
+            "yak_id": note.yak_id,
+            "note_id": note_id,
+            "done": checkbox.done,
+            CHECKBOX_KEY: checkbox.text,
+        });
+        let frame = append_frame(
+            store,
+            "task.create",
+            Some(checkbox.text.as_bytes()),
+            Some(meta),
+        )
+        .await?;
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+/// Brings a note and its extracted tasks back into agreement after `frame` changed one side:
+/// a `note.edit` updates the tasks, a `task.update` ticks or clears the checkbox. Each side
+/// is only written when it differs, so the write this makes doesn't bounce back.
+async fn reconcile(app: &AppHandle, store: &Store, frame: &Frame) -> Result<(), String> {
+    let relevant = match frame.topic.as_str() {
+        // Most edits have no checkboxes, so they're let go before folding the log
+        "note.edit" => match &frame.hash {
+            Some(hash) => store
+                .cas_read(hash)
+                .await
+                .is_ok_and(|bytes| !checkboxes(&String::from_utf8_lossy(&bytes)).is_empty()),
+            None => false,
+        },
+        "task.update" => frame.meta.as_ref().is_some_and(|m| m.get("done").is_some()),
+        _ => false,
+    };
+    if !relevant {
+        return Ok(());
+    }
+
+    let _guard = begin_write(app)?;
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    let note_id = match frame.topic.as_str() {
+        "note.edit" => projection.resolve(&frame.id.to_string()),
+        _ => {
+            let Some(note_id) = frame
+                .meta
+                .as_ref()
+                .and_then(|meta| meta.get("task_id"))
+                .and_then(|task_id| projection.tasks.get(task_id.as_str()?))
+                .filter(|task| checkbox_text(task).is_some())
+                .and_then(|task| task.note_id.as_deref())
+            else {
+                return Ok(());
+            };
+            projection.resolve(note_id)
+        }
+    };
+    let tasks = linked(&projection, &note_id);
+    if tasks.is_empty() {
+        return Ok(());
+    }
+    let Some(content) = note_content(store, &projection, &note_id).await else {
+        return Ok(());
+    };
+    let checkboxes = checkboxes(&content);
+    let paired = pair(&checkboxes, &tasks);
+
+    if frame.topic == "note.edit" {
+        let mut frames = Vec::new();
+        for (checkbox, task) in paired.iter().filter(|(c, t)| c.done != t.done) {
+            let meta = json!({ "task_id": task.id, "done": checkbox.done });
+            frames.push(append_frame(store, "task.update", None, Some(meta)).await?);
+        }
+        let _ = emit_frames(app, &frames);
+        return Ok(());
+    }
+
+    let mut updated = content.clone();
+    for (checkbox, task) in paired.iter().filter(|(c, t)| c.done != t.done) {
+        updated = set_checkbox(&updated, checkbox.line, task.done);
+    }
+    if updated == content {
+        return Ok(());
+    }
+    let note = &projection.notes[&note_id];
+    let meta = json!({ "yak_id": note.yak_id, "note_id": note_id });
+    let frame = append_frame(store, "note.edit", Some(updated.as_bytes()), Some(meta)).await?;
+    let _ = emit_frame(app, &frame);
+    Ok(())
+}
+
+/// Keeps checkboxes and the tasks extracted from them in step as either changes.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    let mut caught_up = false;
+    while let Some(frame) = rx.recv().await {
+        if frame.topic == "xs.threshold" {
+            caught_up = true;
+            continue;
+        }
+        // Synced copies were reconciled on the device that wrote them
+        if !caught_up || sync::origin(&frame).is_some() {
+            continue;
+        }
+        if let Err(e) = reconcile(&app, &store, &frame).await {
+            eprintln!("Failed to sync checkboxes: {e}");
+        }
+    }
+}
+
+/// Turns the `- [ ]` checkboxes of a note into tasks linked to it, skipping ones already
+/// extracted. From then on ticking either the checkbox or the task updates the other.
+#[tauri::command]
+pub async fn extract_tasks(
+    app: AppHandle,
+    store: State<'_, Store>,
+    frame_id: String,
+) -> Result<Vec<Frame>, String> {
+    let frames = extract(&app, &store, &frame_id).await?;
+    let _ = emit_frames(&app, &frames);
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_checkboxes() {
+        let content = "# Trip\n- [ ] pack\n  * [x] book hotel\n- [] nope\n- [ ] pack\n";
+        let found = checkboxes(content);
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[1].text, "book hotel");
+        assert!(found[1].done);
+        assert_eq!(
+            set_checkbox(content, 1, true),
+            "# Trip\n- [x] pack\n  * [x] book hotel\n- [] nope\n- [ ] pack\n"
+        );
+        assert_eq!(
+            set_checkbox(content, 2, false),
+            "# Trip\n- [ ] pack\n  * [ ] book hotel\n- [] nope\n- [ ] pack\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pairing_follows_edits() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let note = append_frame(
+            &store,
+            "note.create",
+            Some(b"- [ ] milk\n- [x] eggs\n- [ ] milk".as_slice()),
+            Some(json!({ "yak_id": yak_id })),
+        )
+        .await
+        .unwrap();
+        let note_id = note.id.to_string();
+        for (text, done) in [("milk", false), ("eggs", true), ("milk", false)] {
+            let meta =
+                json!({ "yak_id": yak_id, "note_id": note_id, "done": done, "checkbox": text });
+            append_frame(&store, "task.create", None, Some(meta))
+                .await
+                .unwrap();
+        }
+        let edit = append_frame(
+            &store,
+            "note.edit",
+            Some(b"- [x] eggs\n- [ ] milk\n- [x] milk\n- [ ] bread".as_slice()),
+            Some(json!({ "yak_id": yak_id, "note_id": note_id })),
+        )
+        .await
+        .unwrap();
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let edit_id = edit.id.to_string();
+        let tasks = linked(&projection, &edit_id);
+        assert_eq!(tasks.len(), 3);
+        let content = note_content(&store, &projection, &edit_id).await.unwrap();
+        let checkboxes = checkboxes(&content);
+        let paired = pair(&checkboxes, &tasks);
+        // bread has no task yet; the second milk task pairs with the second milk line
+        assert_eq!(paired.len(), 3);
+        let changed: Vec<&str> = paired
+            .iter()
+            .filter(|(checkbox, task)| checkbox.done != task.done)
+            .map(|(checkbox, _)| checkbox.text.as_str())
+            .collect();
+        assert_eq!(changed, vec!["milk"]);
+    }
+}
@@ -0,0 +1,256 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::export::{created_id, note_title};
+use crate::locks::{self, LockState};
+use crate::projection::{Note, Projection};
+use crate::shutdown::begin_write;
+use crate::snapshot::fold_until;
+use crate::windows::emit_frame;
+use crate::{append_frame, read_all_frames};
+
+const TOPIC: &str = "checkpoint.create";
+
+/// A named point in a yak's history, recorded by a `checkpoint.create` frame.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Checkpoint {
+    pub id: String,
+    pub yak_id: String,
+    pub name: String,
+    /// The store's head when the checkpoint was made
+    pub frame_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NoteChange {
+    /// The note's latest revision: the current one, or for removed notes, the last before
+    /// the checkpoint
+    pub note_id: String,
+    pub title: String,
+}
+
+/// How a yak's notes differ now from when a checkpoint was made.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckpointDiff {
+    pub checkpoint: Checkpoint,
+    pub added: Vec<NoteChange>,
+    /// Notes edited or retagged since
+    pub changed: Vec<NoteChange>,
+    /// Notes deleted or moved to another yak since
+    pub removed: Vec<NoteChange>,
+}
+
+fn checkpoints(frames: &[Frame]) -> Vec<Checkpoint> {
+    frames
+        .iter()
+        .filter(|frame| frame.topic == TOPIC)
+        .filter_map(|frame| {
+            let meta = frame.meta.as_ref()?;
+            let field = |key: &str| meta.get(key)?.as_str().map(String::from);
+            Some(Checkpoint {
+                id: frame.id.to_string(),
+                yak_id: field("yak_id")?,
+                name: field("name")?,
+                frame_id: field("frame_id")?,
+            })
+        })
+        .collect()
+}
+
+/// A yak's current notes keyed by the id of their first revision, which edits don't change.
+fn by_origin<'a>(projection: &'a Projection, yak_id: &str) -> HashMap<String, &'a Note> {
+    projection
+        .current_notes(yak_id)
+        .into_iter()
+        .map(|note| (created_id(projection, note), note))
+        .collect()
+}
+
+fn change(note: &Note) -> NoteChange {
+    NoteChange {
+        note_id: note.id.clone(),
+        title: note_title(note),
+    }
+}
+
+fn diff(then: &Projection, now: &Projection, checkpoint: Checkpoint) -> CheckpointDiff {
+    let before = by_origin(then, &checkpoint.yak_id);
+    let after = by_origin(now, &checkpoint.yak_id);
+    let mut diff = CheckpointDiff {
+        checkpoint,
+        added: Vec::new(),
+        changed: Vec::new(),
+        removed: Vec::new(),
+    };
+    for (origin, note) in &after {
+        match before.get(origin) {
+            None => diff.added.push(change(note)),
+            Some(old) if old.id != note.id || old.tags != note.tags => {
+                diff.changed.push(change(note))
+            }
+            Some(_) => {}
+        }
+    }
+    for (origin, note) in &before {
+        if !after.contains_key(origin) {
+            diff.removed.push(change(note));
+        }
+    }
+    for notes in [&mut diff.added, &mut diff.changed, &mut diff.removed] {
+        notes.sort_by(|a, b| a.note_id.cmp(&b.note_id));
+    }
+    diff
+}
+
+/// Keeps only `yak_id`'s notes, with their content readable. Earlier revisions stay, since
+/// they connect a note to its first revision.
+async fn yak_view(
+    store: &Store,
+    locks: &LockState,
+    hide_locked: bool,
+    mut projection: Projection,
+    yak_id: &str,
+) -> Projection {
+    projection.notes.retain(|_, note| note.yak_id == yak_id);
+    projection.resolve_content(store).await;
+    locks.redact(&mut projection, hide_locked);
+    projection
+}
+
+/// Names the current state of a yak, so it can later be compared with `diff_checkpoint`,
+/// e.g. before a big cleanup or import. Names are unique.
+#[tauri::command]
+pub async fn create_checkpoint(
+    app: AppHandle,
+    store: State<'_, Store>,
+    yak_id: String,
+    name: String,
+) -> Result<Checkpoint, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("A checkpoint needs a name".to_string());
+    }
+    let _guard = begin_write(&app)?;
+    let frames = read_all_frames(&store).await;
+    if !Projection::from_frames(&frames).yaks.contains_key(&yak_id) {
+        return Err(format!("Yak not found: {yak_id}"));
+    }
+    if checkpoints(&frames)
+        .iter()
+        .any(|checkpoint| checkpoint.name == name)
+    {
+        return Err(format!("There's already a checkpoint named {name}"));
+    }
+    let head = frames
+        .last()
+        .map(|frame| frame.id.to_string())
+        .ok_or("The store is empty")?;
+    let meta = serde_json::json!({ "yak_id": yak_id, "name": name, "frame_id": head });
+    let frame = append_frame(&store, TOPIC, None, Some(meta)).await?;
+    let _ = emit_frame(&app, &frame);
+    Ok(Checkpoint {
+        id: frame.id.to_string(),
+        yak_id,
+        name,
+        frame_id: head,
+    })
+}
+
+/// Checkpoints, oldest first, of one yak or of every yak.
+#[tauri::command]
+pub async fn list_checkpoints(
+    store: State<'_, Store>,
+    yak_id: Option<String>,
+) -> Result<Vec<Checkpoint>, String> {
+    let mut checkpoints = checkpoints(&read_all_frames(&store).await);
+    if let Some(yak_id) = yak_id {
+        checkpoints.retain(|checkpoint| checkpoint.yak_id == yak_id);
+    }
+    Ok(checkpoints)
+}
+
+/// The notes added, changed and removed in a checkpoint's yak since it was made.
+#[tauri::command]
+pub async fn diff_checkpoint(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    name: String,
+) -> Result<CheckpointDiff, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let frames = read_all_frames(&store).await;
+    let checkpoint = checkpoints(&frames)
+        .into_iter()
+        .find(|checkpoint| checkpoint.name == name)
+        .ok_or_else(|| format!("No checkpoint named {name}"))?;
+    let head = checkpoint
+        .frame_id
+        .parse::<scru128::Scru128Id>()
+        .map_err(|e| format!("Invalid checkpoint: {e}"))?;
+    let hide_locked = locks::load_config(&store).await.hide_locked;
+    let yak_id = checkpoint.yak_id.clone();
+    let then = fold_until(&frames, head);
+    let then = yak_view(&store, &locks, hide_locked, then, &yak_id).await;
+    let now = Projection::from_frames(&frames);
+    let now = yak_view(&store, &locks, hide_locked, now, &yak_id).await;
+    Ok(diff(&then, &now, checkpoint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{add_note, add_tag, create_yak};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_diff() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Work").await.unwrap();
+        add_note(&store, &yak_id, "Kept", json!({})).await.unwrap();
+        let edited = add_note(&store, &yak_id, "Draft", json!({})).await.unwrap();
+        let tagged = add_note(&store, &yak_id, "Tagged", json!({}))
+            .await
+            .unwrap();
+        let deleted = add_note(&store, &yak_id, "Stale", json!({})).await.unwrap();
+        let head = read_all_frames(&store).await.last().unwrap().id;
+
+        let meta = json!({ "yak_id": yak_id, "note_id": edited.id.to_string() });
+        let edit = append_frame(&store, "note.edit", Some(b"Final".as_slice()), Some(meta))
+            .await
+            .unwrap();
+        add_tag(&store, &yak_id, &tagged.id.to_string(), "done")
+            .await
+            .unwrap();
+        let meta = json!({ "yak_id": yak_id, "note_id": deleted.id.to_string() });
+        append_frame(&store, "note.delete", None, Some(meta))
+            .await
+            .unwrap();
+        let added = add_note(&store, &yak_id, "New", json!({})).await.unwrap();
+
+        let frames = read_all_frames(&store).await;
+        let checkpoint = Checkpoint {
+            id: String::new(),
+            yak_id: yak_id.clone(),
+            name: "before cleanup".to_string(),
+            frame_id: head.to_string(),
+        };
+        let mut then = fold_until(&frames, head);
+        let mut now = Projection::from_frames(&frames);
+        then.resolve_content(&store).await;
+        now.resolve_content(&store).await;
+        let diff = diff(&then, &now, checkpoint);
+
+        let ids = |notes: &[NoteChange]| -> Vec<String> {
+            notes.iter().map(|note| note.note_id.clone()).collect()
+        };
+        assert_eq!(ids(&diff.added), vec![added.id.to_string()]);
+        let mut changed = vec![edit.id.to_string(), tagged.id.to_string()];
+        changed.sort();
+        assert_eq!(ids(&diff.changed), changed);
+        assert_eq!(ids(&diff.removed), vec![deleted.id.to_string()]);
+        assert_eq!(diff.removed[0].title, "Stale");
+    }
+}
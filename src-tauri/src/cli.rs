@@ -0,0 +1,314 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use xs::store::{Frame, Store};
+
+use crate::shutdown::begin_write;
+use crate::windows::emit_frame;
+use crate::{append_frame, location, profiles, store_lock, topics};
+
+/// Where the running app takes appends from `yaks append`, in the profile's data dir.
+const SOCKET_NAME: &str = "append.sock";
+const USAGE: &str = concat!(
+    "usage: yaks append --topic <topic> [--meta key=value]... [--json] [--profile <name>]",
+    " [- | <content>]"
+);
+
+/// An append, as sent over the socket: content is base64, so stdin can carry any bytes.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+struct Request {
+    topic: String,
+    meta: Map<String, Value>,
+    content: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    topic: String,
+    meta: Map<String, Value>,
+    /// `-`: read the content from stdin
+    stdin: bool,
+    content: Option<String>,
+    /// Print the whole frame as JSON rather than its id
+    json: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next().cloned())
+                .ok_or_else(|| format!("{flag} needs a value\n{USAGE}"))
+        };
+        match flag {
+            "--topic" => parsed.topic = value()?,
+            "--meta" => {
+                let pair = value()?;
+                let (key, value) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("--meta takes key=value, not {pair:?}"))?;
+                parsed.meta.insert(key.to_string(), value.into());
+            }
+            // Read by `profiles::resolve_dir`
+            "--profile" => {
+                value()?;
+            }
+            "--json" => parsed.json = true,
+            "-" => parsed.stdin = true,
+            flag if flag.starts_with("--") => return Err(format!("Unknown flag {flag}\n{USAGE}")),
+            content if parsed.content.is_none() => parsed.content = Some(content.to_string()),
+            extra => return Err(format!("Unexpected argument {extra:?}\n{USAGE}")),
+        }
+    }
+    if parsed.topic.is_empty() {
+        return Err(format!("--topic is required\n{USAGE}"));
+    }
+    if parsed.stdin && parsed.content.is_some() {
+        return Err(format!("Give the content or -, not both\n{USAGE}"));
+    }
+    Ok(parsed)
+}
+
+/// Topics are dot-separated words, outside the namespaces the app keeps for itself.
+fn validate_topic(topic: &str) -> Result<(), String> {
+    let valid = topic
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if topic.is_empty() || !valid || topic.starts_with('.') || topic.ends_with('.') {
+        return Err(format!("Invalid topic {topic:?}"));
+    }
+    topics::check_user(topic)
+}
+
+async fn append(store: &Store, request: Request) -> Result<Frame, String> {
+    validate_topic(&request.topic)?;
+    let content = request
+        .content
+        .map(|content| base64::engine::general_purpose::STANDARD.decode(content))
+        .transpose()
+        .map_err(|e| format!("Invalid content: {e}"))?;
+    let meta = (!request.meta.is_empty()).then_some(Value::Object(request.meta));
+    append_frame(store, &request.topic, content.as_deref(), meta).await
+}
+
+fn socket_path(profile_dir: &Path) -> PathBuf {
+    profile_dir.join(SOCKET_NAME)
+}
+
+/// Serves appends from `yaks append`, one JSON request per line, each answered with the
+/// frame written or an error.
+#[cfg(unix)]
+async fn serve(app: AppHandle, store: Store, path: PathBuf) -> Result<(), String> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path)
+        .map_err(|e| format!("Failed to listen on {}: {e}", path.display()))?;
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| format!("Append socket accept failed: {e}"))?;
+        let (app, store) = (app.clone(), store.clone());
+        tokio::spawn(async move {
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let result = match serde_json::from_str::<Request>(&line) {
+                    Ok(request) => match begin_write(&app) {
+                        Ok(_guard) => append(&store, request).await,
+                        Err(e) => Err(e),
+                    },
+                    Err(e) => Err(format!("Invalid request: {e}")),
+                };
+                let response = match result {
+                    Ok(frame) => {
+                        let _ = emit_frame(&app, &frame);
+                        json!({ "frame": frame })
+                    }
+                    Err(e) => json!({ "error": e }),
+                };
+                let mut out = response.to_string();
+                out.push('\n');
+                if write.write_all(out.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Listens for `yaks append` while the app has the store open for writing.
+pub(crate) fn initialize(app: &AppHandle, store: &Store) {
+    #[cfg(unix)]
+    {
+        let path = match profiles::data_dir(app) {
+            Ok(dir) => socket_path(&dir),
+            Err(e) => {
+                eprintln!("Append socket disabled: {e}");
+                return;
+            }
+        };
+        let (app, store) = (app.clone(), store.clone());
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = serve(app, store, path).await {
+                eprintln!("{e}");
+            }
+        });
+    }
+    #[cfg(not(unix))]
+    let _ = (app, store);
+}
+
+#[cfg(unix)]
+async fn exchange(stream: tokio::net::UnixStream, request: &Request) -> Result<Value, String> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (read, mut write) = stream.into_split();
+    let mut line = serde_json::to_string(request).map_err(|e| e.to_string())?;
+    line.push('\n');
+    write
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to send to Yaks: {e}"))?;
+    let response = BufReader::new(read)
+        .lines()
+        .next_line()
+        .await
+        .map_err(|e| format!("Failed to read from Yaks: {e}"))?
+        .ok_or("Yaks closed the connection")?;
+    let mut response: Value =
+        serde_json::from_str(&response).map_err(|e| format!("Invalid response: {e}"))?;
+    match response.get("error").and_then(Value::as_str) {
+        Some(error) => Err(error.to_string()),
+        None => Ok(response["frame"].take()),
+    }
+}
+
+/// Sends `request` to the running app. `None` when no app is listening.
+#[cfg(unix)]
+async fn send(socket: &Path, request: &Request) -> Option<Result<Value, String>> {
+    let stream = tokio::net::UnixStream::connect(socket).await.ok()?;
+    Some(exchange(stream, request).await)
+}
+
+#[cfg(not(unix))]
+async fn send(_socket: &Path, _request: &Request) -> Option<Result<Value, String>> {
+    None
+}
+
+/// Writes to the store directly when Yaks isn't running, holding its write lock meanwhile.
+async fn append_directly(profile_dir: &Path, request: Request) -> Result<Value, String> {
+    let store_path = location::resolve(profile_dir);
+    std::fs::create_dir_all(&store_path)
+        .map_err(|e| format!("Failed to create {}: {e}", store_path.display()))?;
+    let _lock = store_lock::acquire(&store_path)
+        .map_err(|holder| format!("The store is in use by {holder}"))?;
+    let store = Store::new(store_path);
+    let frame = append(&store, request).await?;
+    serde_json::to_value(&frame).map_err(|e| format!("Failed to serialize frame: {e}"))
+}
+
+/// `yaks append`: appends a frame with content from the arguments or stdin, through the
+/// running app if there is one, so shell pipelines and cron jobs can feed the store.
+/// Prints the new frame's id, or with `--json` the frame.
+pub fn run_append(args: Vec<String>) -> Result<(), String> {
+    let parsed = parse_args(&args)?;
+    validate_topic(&parsed.topic)?;
+    let content = match (parsed.stdin, parsed.content) {
+        (true, _) => {
+            let mut bytes = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut bytes)
+                .map_err(|e| format!("Failed to read stdin: {e}"))?;
+            Some(bytes).filter(|bytes| !bytes.is_empty())
+        }
+        (false, content) => content.map(String::into_bytes),
+    };
+    let request = Request {
+        topic: parsed.topic,
+        meta: parsed.meta,
+        content: content.map(|bytes| base64::engine::general_purpose::STANDARD.encode(bytes)),
+    };
+
+    let app_data_dir = dirs::data_dir()
+        .ok_or("No data directory")?
+        .join("stream.cross.yaks");
+    let profile_dir = profiles::resolve_dir(&app_data_dir, std::env::args());
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    let frame = runtime.block_on(async {
+        match send(&socket_path(&profile_dir), &request).await {
+            Some(result) => result,
+            None => append_directly(&profile_dir, request).await,
+        }
+    })?;
+    if parsed.json {
+        println!("{frame}");
+    } else {
+        println!("{}", frame["id"].as_str().unwrap_or_default());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = parse_args(&args(&[
+            "--topic",
+            "clip",
+            "--meta",
+            "source=cron",
+            "--meta=url=https://example.com/?a=1",
+            "--json",
+            "-",
+        ]))
+        .unwrap();
+        assert_eq!(parsed.topic, "clip");
+        assert_eq!(parsed.meta["source"], "cron");
+        assert_eq!(parsed.meta["url"], "https://example.com/?a=1");
+        assert!(parsed.json && parsed.stdin);
+
+        let parsed = parse_args(&args(&["--topic=clip", "hello"])).unwrap();
+        assert_eq!(parsed.content.as_deref(), Some("hello"));
+        assert!(parse_args(&args(&["-"])).is_err());
+        assert!(parse_args(&args(&["--topic", "clip", "--meta", "novalue"])).is_err());
+        assert!(parse_args(&args(&["--topic", "clip", "-", "text"])).is_err());
+        assert!(validate_topic("xs.threshold").is_err());
+        assert!(validate_topic("clip big").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_append_directly() {
+        let dir = tempdir().unwrap();
+        let mut meta = Map::new();
+        meta.insert("source".to_string(), "cron".into());
+        let request = Request {
+            topic: "clip".to_string(),
+            meta,
+            content: Some(base64::engine::general_purpose::STANDARD.encode(b"disk 91%")),
+        };
+        let frame = append_directly(dir.path(), request).await.unwrap();
+        assert_eq!(frame["topic"], "clip");
+        assert_eq!(frame["meta"]["source"], "cron");
+        let hash = ssri::Integrity::from(b"disk 91%").to_string();
+        assert_eq!(frame["hash"], hash);
+    }
+}
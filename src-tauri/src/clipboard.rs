@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Mutex;
+use xs::store::{Frame, Store};
+
+use crate::append_frame;
+use crate::rates::RatesState;
+use crate::settings::{latest_setting, save_setting};
+use crate::shutdown::{self, begin_write};
+use crate::windows::emit_frame;
+
+const CONFIG_TOPIC: &str = "capture.clipboard";
+const POLL_INTERVAL: Duration = Duration::from_millis(750);
+/// How many recent clips a new one is checked against before it's captured
+const RECENT_CLIPS: usize = 50;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ClipboardConfig {
+    pub enabled: bool,
+    /// The yak clips are captured into, created the first time capture is enabled
+    pub yak_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipboardStatus {
+    pub enabled: bool,
+    pub paused: bool,
+    pub yak_id: Option<String>,
+}
+
+/// Capture is persisted as a setting; pausing only lasts until the app restarts.
+#[derive(Default)]
+pub struct ClipboardState {
+    config: Mutex<ClipboardConfig>,
+    paused: AtomicBool,
+    /// Text the app put on the clipboard that mustn't be captured (see `copy_secure`)
+    withheld: Mutex<Option<String>>,
+}
+
+impl ClipboardState {
+    pub(crate) async fn withhold(&self, text: String) {
+        *self.withheld.lock().await = Some(text);
+    }
+
+    /// Stops withholding `text`, unless something else is withheld by now.
+    pub(crate) async fn release(&self, text: &str) {
+        let mut withheld = self.withheld.lock().await;
+        if withheld.as_deref() == Some(text) {
+            *withheld = None;
+        }
+    }
+}
+
+/// Recently captured text, so copying the same thing again doesn't add another clip.
+#[derive(Default)]
+struct Recent(VecDeque<String>);
+
+impl Recent {
+    /// Records `text`, returning false if it was already among the recent clips.
+    fn insert(&mut self, text: &str) -> bool {
+        if self.0.iter().any(|seen| seen == text) {
+            return false;
+        }
+        if self.0.len() == RECENT_CLIPS {
+            self.0.pop_front();
+        }
+        self.0.push_back(text.to_string());
+        true
+    }
+}
+
+fn read_clipboard() -> Option<String> {
+    let text = arboard::Clipboard::new().ok()?.get_text().ok()?;
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// The frontmost application, which is almost always the one the text was copied from.
+fn source_app() -> serde_json::Value {
+    match active_win_pos_rs::get_active_window() {
+        Ok(window) => serde_json::json!({ "app": window.app_name, "title": window.title }),
+        Err(()) => serde_json::Value::Null,
+    }
+}
+
+async fn capture(app: AppHandle, store: Store) {
+    let mut recent = Recent::default();
+    // Whatever is on the clipboard at startup was there before capture (re)started
+    if let Ok(Some(text)) = tokio::task::spawn_blocking(read_clipboard).await {
+        recent.insert(&text);
+    }
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let state = app.state::<ClipboardState>();
+        let config = state.config.lock().await.clone();
+        let Some(yak_id) = config.yak_id.filter(|_| config.enabled) else {
+            continue;
+        };
+        if state.paused.load(Ordering::Relaxed) || app.state::<RatesState>().is_paused("clipboard")
+        {
+            continue;
+        }
+
+        let Ok(Some(text)) = tokio::task::spawn_blocking(read_clipboard).await else {
+            continue;
+        };
+        if state.withheld.lock().await.as_deref() == Some(text.as_str()) {
+            continue;
+        }
+        if !recent.insert(&text) {
+            continue;
+        }
+
+        let source = tokio::task::spawn_blocking(source_app)
+            .await
+            .unwrap_or_default();
+        let text = crate::secrets::check(&app, "clip", text);
+        let Ok(_guard) = begin_write(&app) else {
+            continue;
+        };
+        let meta = serde_json::json!({
+            "yak_id": yak_id,
+            "source": source,
+            "content_type": crate::mime::sniff(&text),
+        });
+        match append_frame(&store, "clip", Some(text.as_bytes()), Some(meta)).await {
+            Ok(frame) => {
+                let _ = emit_frame(&app, &frame);
+            }
+            Err(e) => eprintln!("Failed to capture clipboard: {e}"),
+        }
+    }
+}
+
+pub(crate) async fn initialize(app: &AppHandle, store: &Store, frames: &[Frame]) {
+    let config: ClipboardConfig = latest_setting(frames, CONFIG_TOPIC);
+    *app.state::<ClipboardState>().config.lock().await = config;
+    shutdown::track(app, tokio::spawn(capture(app.clone(), store.clone())));
+}
+
+async fn status(state: &ClipboardState) -> ClipboardStatus {
+    let config = state.config.lock().await;
+    ClipboardStatus {
+        enabled: config.enabled,
+        paused: state.paused.load(Ordering::Relaxed),
+        yak_id: config.yak_id.clone(),
+    }
+}
+
+/// Turns clipboard capture on or off. Captured text is appended as `clip` frames into a
+/// dedicated "Clipboard" yak.
+#[tauri::command]
+pub async fn enable_clipboard_capture(
+    store: State<'_, Store>,
+    state: State<'_, ClipboardState>,
+    enabled: bool,
+) -> Result<ClipboardStatus, String> {
+    let mut config = state.config.lock().await.clone();
+    if enabled && config.yak_id.is_none() {
+        let yak = append_frame(
+            &store,
+            "yak.create",
+            None,
+            Some(serde_json::json!({ "name": "Clipboard" })),
+        )
+        .await?;
+        config.yak_id = Some(yak.id.to_string());
+    }
+    config.enabled = enabled;
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    *state.config.lock().await = config;
+    Ok(status(&state).await)
+}
+
+#[tauri::command]
+pub async fn pause_clipboard_capture(
+    state: State<'_, ClipboardState>,
+    paused: bool,
+) -> Result<ClipboardStatus, String> {
+    state.paused.store(paused, Ordering::Relaxed);
+    Ok(status(&state).await)
+}
+
+#[tauri::command]
+pub async fn clipboard_capture_status(
+    state: State<'_, ClipboardState>,
+) -> Result<ClipboardStatus, String> {
+    Ok(status(&state).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_deduplicates() {
+        let mut recent = Recent::default();
+        assert!(recent.insert("a"));
+        assert!(recent.insert("b"));
+        assert!(!recent.insert("a"));
+        for i in 0..RECENT_CLIPS {
+            recent.insert(&i.to_string());
+        }
+        assert!(recent.insert("a"));
+    }
+}
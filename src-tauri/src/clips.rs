@@ -0,0 +1,187 @@
+use serde_json::Value;
+use std::sync::Arc;
+use tauri::State;
+use xs::store::Store;
+
+use crate::cache::CasCache;
+use crate::locks::LockState;
+use crate::projection::Projection;
+use crate::read_all_frames;
+
+/// One step of a path like `.items[0].name` or `.results[].id`.
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Key(String),
+    /// Negative indexes count from the end
+    Index(i64),
+    /// `[]`: every element of an array, or every value of an object
+    Each,
+}
+
+/// Parses a jq-style path: `.key`, `["quoted key"]`, `[2]`, `[-1]` and `[]`. A lone `.`
+/// is the whole document.
+fn parse_path(path: &str) -> Result<Vec<Step>, String> {
+    let invalid = |reason: &str| format!("Invalid path {path}: {reason}");
+    let mut steps = Vec::new();
+    let mut rest = path.trim();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(|| invalid("missing ]"))?;
+            let inner = after[..end].trim();
+            steps.push(if inner.is_empty() {
+                Step::Each
+            } else if let Some(key) = inner.strip_prefix('"').and_then(|k| k.strip_suffix('"')) {
+                Step::Key(key.to_string())
+            } else {
+                Step::Index(inner.parse().map_err(|_| invalid("bad index"))?)
+            });
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let key = &after[..end];
+            if !key.is_empty() {
+                steps.push(Step::Key(key.to_string()));
+            }
+            rest = &after[end..];
+        } else {
+            return Err(invalid("expected . or ["));
+        }
+    }
+    Ok(steps)
+}
+
+/// Applies a path to a document. Missing keys and out-of-range indexes give null, as in jq;
+/// paths with `[]` return an array of everything they reach.
+fn query(document: &Value, path: &str) -> Result<Value, String> {
+    let steps = parse_path(path)?;
+    let mut current = vec![document];
+    for step in &steps {
+        let mut next = Vec::new();
+        for value in current {
+            match (step, value) {
+                (_, Value::Null) => next.push(&Value::Null),
+                (Step::Key(key), Value::Object(fields)) => {
+                    next.push(fields.get(key).unwrap_or(&Value::Null))
+                }
+                (Step::Index(index), Value::Array(items)) => {
+                    let index = if *index < 0 {
+                        items.len().checked_sub(index.unsigned_abs() as usize)
+                    } else {
+                        Some(*index as usize)
+                    };
+                    next.push(index.and_then(|i| items.get(i)).unwrap_or(&Value::Null));
+                }
+                (Step::Each, Value::Array(items)) => next.extend(items),
+                (Step::Each, Value::Object(fields)) => next.extend(fields.values()),
+                (step, value) => {
+                    return Err(format!("Can't apply {step:?} to {}", kind(value)));
+                }
+            }
+        }
+        current = next;
+    }
+    if steps.contains(&Step::Each) {
+        Ok(Value::Array(current.into_iter().cloned().collect()))
+    } else {
+        Ok(current.first().copied().cloned().unwrap_or(Value::Null))
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// The parsed content of a clip or note holding JSON.
+async fn clip_json(
+    store: &Store,
+    cache: &CasCache,
+    locks: &LockState,
+    frame_id: &str,
+) -> Result<Value, String> {
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    let note_id = projection.resolve(frame_id);
+    let note = projection
+        .notes
+        .get(&note_id)
+        .ok_or_else(|| format!("Clip not found: {frame_id}"))?;
+    let hash = note.hash.as_ref().ok_or("The clip is empty")?;
+    let hash = hash.to_string();
+    let content = cache.read(store, &hash).await?;
+    let content = locks.open(&hash, &content)?;
+    serde_json::from_str(content.trim()).map_err(|e| format!("The clip isn't JSON: {e}"))
+}
+
+/// Slices a JSON clip with a jq-style path such as `.data.items[0].name` or `.results[].id`.
+#[tauri::command]
+pub async fn query_clip(
+    store: State<'_, Store>,
+    cache: State<'_, Arc<CasCache>>,
+    locks: State<'_, LockState>,
+    frame_id: String,
+    path: String,
+) -> Result<Value, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let document = clip_json(&store, &cache, &locks, &frame_id).await?;
+    query(&document, &path)
+}
+
+/// A JSON clip pretty-printed for display.
+#[tauri::command]
+pub async fn render_clip(
+    store: State<'_, Store>,
+    cache: State<'_, Arc<CasCache>>,
+    locks: State<'_, LockState>,
+    frame_id: String,
+) -> Result<String, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let document = clip_json(&store, &cache, &locks, &frame_id).await?;
+    serde_json::to_string_pretty(&document).map_err(|e| format!("Failed to render clip: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(parse_path(".").unwrap(), vec![]);
+        assert_eq!(
+            parse_path(r#".data["a b"][0][].id"#).unwrap(),
+            vec![
+                Step::Key("data".to_string()),
+                Step::Key("a b".to_string()),
+                Step::Index(0),
+                Step::Each,
+                Step::Key("id".to_string()),
+            ]
+        );
+        assert!(parse_path(".items[x]").is_err());
+        assert!(parse_path("items").is_err());
+    }
+
+    #[test]
+    fn test_query() {
+        let document = json!({
+            "status": "ok",
+            "results": [
+                { "id": 1, "tags": ["a"] },
+                { "id": 2, "tags": [] },
+            ],
+        });
+        assert_eq!(query(&document, ".status").unwrap(), json!("ok"));
+        assert_eq!(query(&document, ".results[-1].id").unwrap(), json!(2));
+        assert_eq!(query(&document, ".results[].id").unwrap(), json!([1, 2]));
+        assert_eq!(query(&document, ".missing.deeper").unwrap(), Value::Null);
+        assert_eq!(query(&document, ".results[5]").unwrap(), Value::Null);
+        assert!(query(&document, ".status[0]").is_err());
+        assert_eq!(query(&document, ".").unwrap(), document);
+    }
+}
@@ -0,0 +1,248 @@
+use chrono::{TimeZone, Utc};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::location::StoreLocation;
+use crate::settings::{load_setting, save_setting};
+
+const CONFIG_TOPIC: &str = "retention.cold_archive";
+/// Kept next to the store, one gzipped JSON Lines file per month the frames were written in.
+const DIR: &str = "cold";
+const EXTENSION: &str = ".jsonl.gz";
+const DEFAULT_SEARCH_LIMIT: usize = 100;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColdArchiveConfig {
+    pub enabled: bool,
+}
+
+/// A frame retention or compaction removed, as it was before it went.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedFrame {
+    pub frame: Frame,
+    /// The frame's content when it's text; binary content isn't kept
+    pub content: Option<String>,
+    /// What removed it: "retention" or "compaction"
+    pub removed_by: String,
+}
+
+fn archive_dir(app: &AppHandle) -> PathBuf {
+    app.state::<StoreLocation>().0.join(DIR)
+}
+
+fn month(frame: &Frame) -> String {
+    Utc.timestamp_millis_opt(frame.id.timestamp() as i64)
+        .single()
+        .map_or_else(
+            || "unknown".to_string(),
+            |time| time.format("%Y-%m").to_string(),
+        )
+}
+
+/// Appends `archived` to each month's file. Every write adds a gzip member, which readers
+/// decode back to back, so earlier writes are never rewritten.
+fn write(dir: &Path, archived: &[ArchivedFrame]) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create cold archive: {e}"))?;
+    let mut by_month: BTreeMap<String, Vec<&ArchivedFrame>> = BTreeMap::new();
+    for entry in archived {
+        by_month.entry(month(&entry.frame)).or_default().push(entry);
+    }
+    for (month, entries) in by_month {
+        let path = dir.join(format!("{month}{EXTENSION}"));
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        for entry in entries {
+            serde_json::to_writer(&mut encoder, entry)
+                .map_err(|e| format!("Failed to archive frame: {e}"))?;
+            encoder
+                .write_all(b"\n")
+                .map_err(|e| format!("Failed to archive frame: {e}"))?;
+        }
+        encoder
+            .finish()
+            .and_then(|file| file.sync_all())
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn matches(entry: &ArchivedFrame, query: &str) -> bool {
+    let meta = entry
+        .frame
+        .meta
+        .as_ref()
+        .map(|meta| meta.to_string())
+        .unwrap_or_default();
+    [
+        entry.frame.topic.as_str(),
+        &meta,
+        entry.content.as_deref().unwrap_or_default(),
+    ]
+    .iter()
+    .any(|text| text.to_lowercase().contains(query))
+}
+
+/// Archived frames matching `query` in their topic, meta or content, newest first.
+fn search(dir: &Path, query: &str, limit: usize) -> Result<Vec<ArchivedFrame>, String> {
+    let query = query.trim().to_lowercase();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let mut found = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.to_string_lossy().ends_with(EXTENSION) {
+            continue;
+        }
+        let file =
+            File::open(&path).map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+        for line in BufReader::new(MultiGzDecoder::new(file)).lines() {
+            let line = line.map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+            match serde_json::from_str::<ArchivedFrame>(&line) {
+                Ok(archived) if matches(&archived, &query) => found.push(archived),
+                Ok(_) => {}
+                Err(e) => eprintln!("Skipping unreadable line in {}: {e}", path.display()),
+            }
+        }
+    }
+    found.sort_by(|a, b| b.frame.id.cmp(&a.frame.id));
+    found.truncate(limit);
+    Ok(found)
+}
+
+/// Copies frames about to be removed, with their text content, into the cold archive when
+/// it's enabled. Callers remove nothing if this fails, so pruning never loses a frame the
+/// archive was meant to keep.
+pub(crate) async fn preserve(
+    app: &AppHandle,
+    store: &Store,
+    frames: &[&Frame],
+    removed_by: &str,
+) -> Result<usize, String> {
+    let config: ColdArchiveConfig = load_setting(store, CONFIG_TOPIC).await;
+    if !config.enabled || frames.is_empty() {
+        return Ok(0);
+    }
+    let mut archived = Vec::with_capacity(frames.len());
+    for frame in frames {
+        let content = match &frame.hash {
+            Some(hash) => store
+                .cas_read(hash)
+                .await
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok()),
+            None => None,
+        };
+        archived.push(ArchivedFrame {
+            frame: (*frame).clone(),
+            content,
+            removed_by: removed_by.to_string(),
+        });
+    }
+    let dir = archive_dir(app);
+    let count = archived.len();
+    tokio::task::spawn_blocking(move || write(&dir, &archived))
+        .await
+        .map_err(|e| format!("Failed to write cold archive: {e}"))??;
+    Ok(count)
+}
+
+#[tauri::command]
+pub async fn get_cold_archive_config(store: State<'_, Store>) -> Result<ColdArchiveConfig, String> {
+    Ok(load_setting(&store, CONFIG_TOPIC).await)
+}
+
+/// Turns the cold archive on or off. While on, frames retention policies and compaction
+/// remove are kept in compressed monthly files beside the store.
+#[tauri::command]
+pub async fn configure_cold_archive(
+    store: State<'_, Store>,
+    config: ColdArchiveConfig,
+) -> Result<(), String> {
+    save_setting(&store, CONFIG_TOPIC, &config)
+}
+
+/// Looks through pruned frames for `query`. Reads every archive file, so it's meant for
+/// rare lookups rather than everyday search.
+#[tauri::command]
+pub async fn search_cold_archive(
+    app: AppHandle,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<ArchivedFrame>, String> {
+    let dir = archive_dir(&app);
+    let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+    tokio::task::spawn_blocking(move || search(&dir, &query, limit))
+        .await
+        .map_err(|e| format!("Failed to search cold archive: {e}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+    use xs::store::ZERO_CONTEXT;
+
+    fn archived(time: u64, topic: &str, content: &str) -> ArchivedFrame {
+        ArchivedFrame {
+            frame: Frame {
+                id: scru128::Scru128Id::from_fields(time, 0, 0, 0),
+                context_id: ZERO_CONTEXT,
+                topic: topic.to_string(),
+                hash: None,
+                meta: Some(json!({ "handler": "summarize" })),
+                ttl: None,
+            },
+            content: Some(content.to_string()),
+            removed_by: "retention".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_write_and_search() {
+        let dir = tempdir().unwrap();
+        // 2024-01-01 and 2024-02-01
+        let january = 1_704_067_200_000;
+        let february = 1_706_745_600_000;
+        write(
+            dir.path(),
+            &[
+                archived(january, "handler.output", "Quarterly numbers"),
+                archived(february, "plugin.log", "started"),
+            ],
+        )
+        .unwrap();
+        // A second write to the same month adds to its file
+        write(
+            dir.path(),
+            &[archived(january + 1, "handler.output", "More numbers")],
+        )
+        .unwrap();
+        assert!(dir.path().join("2024-01.jsonl.gz").exists());
+        assert!(dir.path().join("2024-02.jsonl.gz").exists());
+
+        let found = search(dir.path(), "NUMBERS", 10).unwrap();
+        let contents: Vec<_> = found.iter().filter_map(|a| a.content.as_deref()).collect();
+        assert_eq!(contents, vec!["More numbers", "Quarterly numbers"]);
+        assert_eq!(search(dir.path(), "summarize", 10).unwrap().len(), 3);
+        assert_eq!(search(dir.path(), "plugin.log", 10).unwrap().len(), 1);
+        assert_eq!(search(dir.path(), "numbers", 1).unwrap().len(), 1);
+        assert!(search(&dir.path().join("missing"), "x", 10)
+            .unwrap()
+            .is_empty());
+    }
+}
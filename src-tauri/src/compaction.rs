@@ -0,0 +1,356 @@
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::projection::Projection;
+use crate::settings::{latest_setting, load_setting, save_setting};
+use crate::shutdown::begin_write;
+use crate::sync::ORIGIN_KEY;
+use crate::windows::emit_frame;
+use crate::{append_frame, blobs, cold_archive, read_all_frames};
+
+const CONFIG_TOPIC: &str = "compaction.config";
+/// Written by each run, recording what it folded away.
+pub(crate) const CHECKPOINT_TOPIC: &str = "compaction.checkpoint";
+/// Topics whose superseded frames can be pruned: notes that were deleted, and revisions
+/// that later edits replaced.
+const COMPACTABLE: &[&str] = &["clip", "feed.item", "note.create", "note.edit"];
+const TICK: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactionConfig {
+    pub enabled: bool,
+    pub topics: Vec<String>,
+    /// Frames younger than this are never pruned
+    pub retention_days: u32,
+    pub interval_hours: u32,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            topics: vec!["clip".to_string(), "note.edit".to_string()],
+            retention_days: 30,
+            interval_hours: 24,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompactionReport {
+    pub checkpoint_id: String,
+    pub pruned: usize,
+}
+
+/// What a run removes, and the revision each removed revision is folded into.
+#[derive(Debug, Default)]
+struct Plan {
+    pruned: Vec<scru128::Scru128Id>,
+    folded: BTreeMap<String, String>,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+/// Pruned revision id -> the revision it was folded into, from every checkpoint.
+pub(crate) fn folded<'a>(frames: impl IntoIterator<Item = &'a Frame>) -> HashMap<String, String> {
+    frames
+        .into_iter()
+        .filter(|frame| frame.topic == CHECKPOINT_TOPIC)
+        .filter_map(checkpoint_folds)
+        .flatten()
+        .collect()
+}
+
+pub(crate) fn checkpoint_folds(frame: &Frame) -> Option<HashMap<String, String>> {
+    serde_json::from_value(frame.meta.as_ref()?.get("folded")?.clone()).ok()
+}
+
+/// Picks the frames on `topics` that nothing current depends on and that are older than
+/// `cutoff` (ms since the epoch):
+/// - every frame of a note deleted before the cutoff
+/// - revisions replaced by an edit itself made before the cutoff, so the revision current
+///   at the cutoff survives as the start of the recent history
+///
+/// Revisions on a branch (see `conflicts`) and copies made by sync are left alone.
+fn plan(frames: &[Frame], topics: &[String], cutoff: u64) -> Plan {
+    let configured = |topic: &str| topics.iter().any(|t| t == topic);
+    let old = |id: &str| {
+        id.parse::<scru128::Scru128Id>()
+            .is_ok_and(|id| id.timestamp() < cutoff)
+    };
+    let projection = Projection::from_frames(frames);
+    let current: HashSet<&String> = projection.notes_by_yak.values().flatten().collect();
+
+    let mut edits_of: HashMap<&str, usize> = HashMap::new();
+    let mut deleted_at: HashMap<String, String> = HashMap::new();
+    for frame in frames {
+        match frame.topic.as_str() {
+            "note.edit" => {
+                if let Some(original) = meta_str(frame, "note_id") {
+                    *edits_of.entry(original).or_default() += 1;
+                }
+            }
+            // The latest delete of each note; a later restore makes it current again
+            "note.delete" => {
+                if let Some(note_id) = meta_str(frame, "note_id") {
+                    deleted_at.insert(projection.resolve(note_id), frame.id.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut prune: HashSet<String> = HashSet::new();
+    for (head, deleted) in &deleted_at {
+        if current.contains(&head) || !old(deleted) {
+            continue;
+        }
+        let mut id = Some(head);
+        while let Some(note) = id.and_then(|id| projection.notes.get(id)) {
+            prune.insert(note.id.clone());
+            id = note.edited_note_id.as_ref();
+        }
+    }
+
+    let synced = |frame: &Frame| {
+        frame
+            .meta
+            .as_ref()
+            .is_some_and(|m| m.get(ORIGIN_KEY).is_some())
+    };
+    let linear = |id: &str| edits_of.get(id).copied().unwrap_or(0) <= 1;
+    let mut folded = BTreeMap::new();
+    if configured("note.edit") {
+        for frame in frames.iter().filter(|frame| frame.topic == "note.edit") {
+            let id = frame.id.to_string();
+            let Some(note) = projection.notes.get(&id) else {
+                continue;
+            };
+            let Some(original) = note.edited_note_id.as_deref() else {
+                continue;
+            };
+            let replaced = projection
+                .replaced_by
+                .get(&id)
+                .is_some_and(|next| old(next));
+            if !old(&id) || !replaced || synced(frame) || prune.contains(&id) {
+                continue;
+            }
+            if !linear(&id) || !linear(original) {
+                continue;
+            }
+            folded.insert(id, original.to_string());
+        }
+        // Chains of pruned revisions fold into the revision before the first of them
+        let targets: Vec<(String, String)> = folded
+            .iter()
+            .map(|(id, original)| {
+                let mut kept = original;
+                while let Some(earlier) = folded.get(kept) {
+                    kept = earlier;
+                }
+                (id.clone(), kept.clone())
+            })
+            .collect();
+        folded = targets.into_iter().collect();
+    }
+
+    let pruned = frames
+        .iter()
+        .filter(|frame| configured(&frame.topic) && !synced(frame))
+        .filter(|frame| {
+            let id = frame.id.to_string();
+            prune.contains(&id) || folded.contains_key(&id)
+        })
+        .map(|frame| frame.id)
+        .collect();
+    Plan { pruned, folded }
+}
+
+/// Records a checkpoint, then removes the frames it covers. The checkpoint goes first, so a
+/// run cut short leaves frames the projection already skips. With the cold archive on, the
+/// frames are copied there before anything is written.
+async fn compact(
+    app: &AppHandle,
+    store: &Store,
+    config: &CompactionConfig,
+) -> Result<CompactionReport, String> {
+    let _guard = begin_write(app)?;
+    let frames = read_all_frames(store).await;
+    let cutoff = Utc::now() - Duration::days(config.retention_days.into());
+    let plan = plan(&frames, &config.topics, cutoff.timestamp_millis() as u64);
+    let pruning: HashSet<&scru128::Scru128Id> = plan.pruned.iter().collect();
+    let pruning: Vec<&Frame> = frames
+        .iter()
+        .filter(|frame| pruning.contains(&frame.id))
+        .collect();
+    cold_archive::preserve(app, store, &pruning, "compaction").await?;
+
+    let meta = serde_json::json!({
+        "topics": config.topics,
+        "retention_days": config.retention_days,
+        "pruned": plan.pruned.len(),
+        "folded": plan.folded,
+    });
+    let checkpoint = append_frame(store, CHECKPOINT_TOPIC, None, Some(meta)).await?;
+    let mut pruned = 0;
+    for id in &plan.pruned {
+        match store.remove(id) {
+            Ok(_) => pruned += 1,
+            Err(e) => eprintln!("Failed to prune frame {id}: {e}"),
+        }
+    }
+    let _ = emit_frame(app, &checkpoint);
+    println!("Compaction pruned {pruned} frames");
+    if pruned > 0 {
+        match blobs::sweep(app, store).await {
+            Ok(swept) => println!("Compaction freed {} bytes of content", swept.freed_bytes),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+    Ok(CompactionReport {
+        checkpoint_id: checkpoint.id.to_string(),
+        pruned,
+    })
+}
+
+/// Runs compaction when it's enabled and the last checkpoint is at least an interval old.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    loop {
+        let frames = read_all_frames(&store).await;
+        let config: CompactionConfig = latest_setting(&frames, CONFIG_TOPIC);
+        let last = frames
+            .iter()
+            .rev()
+            .find(|frame| frame.topic == CHECKPOINT_TOPIC)
+            .map(|frame| frame.id.timestamp());
+        drop(frames);
+        let due = Utc::now() - Duration::hours(config.interval_hours.into());
+        if config.enabled && last.map_or(true, |last| last < due.timestamp_millis() as u64) {
+            if let Err(e) = compact(&app, &store, &config).await {
+                eprintln!("Compaction failed: {e}");
+            }
+        }
+        tokio::time::sleep(TICK).await;
+    }
+}
+
+fn validate(config: &CompactionConfig) -> Result<(), String> {
+    if let Some(topic) = config
+        .topics
+        .iter()
+        .find(|topic| !COMPACTABLE.contains(&topic.as_str()))
+    {
+        return Err(format!("{topic} frames can't be compacted"));
+    }
+    if config.retention_days == 0 || config.interval_hours == 0 {
+        return Err("Retention and interval must be at least 1".to_string());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_compaction_config(store: State<'_, Store>) -> Result<CompactionConfig, String> {
+    Ok(load_setting(&store, CONFIG_TOPIC).await)
+}
+
+/// Turns scheduled compaction on or off, and sets which topics it covers and how much
+/// history it keeps.
+#[tauri::command]
+pub async fn configure_compaction(
+    store: State<'_, Store>,
+    config: CompactionConfig,
+) -> Result<(), String> {
+    validate(&config)?;
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(())
+}
+
+/// Compacts now with the saved settings, whether or not the schedule is enabled.
+#[tauri::command]
+pub async fn compact_store(
+    app: AppHandle,
+    store: State<'_, Store>,
+) -> Result<CompactionReport, String> {
+    let config: CompactionConfig = load_setting(&store, CONFIG_TOPIC).await;
+    compact(&app, &store, &config).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use xs::store::ZERO_CONTEXT;
+
+    /// A frame made at `time` ms.
+    fn frame(time: u64, topic: &str, meta: Value) -> Frame {
+        Frame {
+            id: scru128::Scru128Id::from_fields(time, 0, 0, 0),
+            context_id: ZERO_CONTEXT,
+            topic: topic.to_string(),
+            hash: None,
+            meta: Some(meta),
+            ttl: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_keeps_final_state_and_recent_history() {
+        let yak = frame(1, "yak.create", json!({}));
+        let yak_id = yak.id.to_string();
+        let clip = frame(2, "clip", json!({ "yak_id": yak_id }));
+        let clip_id = clip.id.to_string();
+        let edit = |time, original: &Frame| {
+            frame(
+                time,
+                "note.edit",
+                json!({ "yak_id": yak_id, "note_id": original.id.to_string() }),
+            )
+        };
+        let first = edit(3, &clip);
+        let second = edit(4, &first);
+        let tag = frame(
+            5,
+            "tag.add",
+            json!({ "note_id": second.id.to_string(), "tag": "t" }),
+        );
+        let third = edit(6, &second);
+        let recent = edit(20, &third);
+        let gone = frame(7, "clip", json!({ "yak_id": yak_id }));
+        let delete = frame(8, "note.delete", json!({ "note_id": gone.id.to_string() }));
+        let frames = vec![yak, clip, first, second, tag, third, gone, delete, recent];
+        let before = Projection::from_frames(&frames);
+
+        let topics = vec!["clip".to_string(), "note.edit".to_string()];
+        let plan = plan(&frames, &topics, 10);
+        let pruned: HashSet<String> = plan.pruned.iter().map(|id| id.to_string()).collect();
+        // The revision current at the cutoff and everything after it stay
+        assert_eq!(pruned.len(), 3);
+        assert!(pruned.contains(&frames[2].id.to_string()));
+        assert!(pruned.contains(&frames[3].id.to_string()));
+        assert!(pruned.contains(&frames[6].id.to_string()));
+        assert_eq!(plan.folded[&frames[3].id.to_string()], clip_id);
+
+        let checkpoint = frame(30, CHECKPOINT_TOPIC, json!({ "folded": plan.folded }));
+        let mut after: Vec<Frame> = frames
+            .iter()
+            .filter(|frame| !pruned.contains(&frame.id.to_string()))
+            .cloned()
+            .collect();
+        after.push(checkpoint);
+        let after = Projection::from_frames(&after);
+        assert_eq!(
+            after.current_notes(&yak_id).len(),
+            before.current_notes(&yak_id).len()
+        );
+        let head = after.resolve(&clip_id);
+        assert_eq!(head, frames[8].id.to_string());
+        assert_eq!(after.resolve(&frames[3].id.to_string()), head);
+        assert!(after.notes[&head].tags.contains("t"));
+    }
+}
@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::projection::Projection;
+use crate::windows::emit_frame;
+use crate::{prepare_frame, read_all_frames, AppendRequest};
+
+/// Meta key on a `note.edit` listing the conflicting branch ids it settles.
+const RESOLVES_KEY: &str = "resolves";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConflictVersion {
+    /// The first edit of this branch
+    pub frame_id: String,
+    /// The latest edit along this branch
+    pub head_id: String,
+    pub hash: Option<ssri::Integrity>,
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conflict {
+    /// The note that was edited concurrently
+    pub note_id: String,
+    pub yak_id: String,
+    pub versions: Vec<ConflictVersion>,
+}
+
+struct Edit {
+    id: String,
+    yak_id: String,
+    hash: Option<ssri::Integrity>,
+}
+
+/// Detects notes with more than one direct edit (typically made on different devices before a
+/// sync) whose branches haven't been settled by a resolving edit. Branches that ended up with
+/// the same content, as when two devices merged the same edits, don't conflict.
+pub(crate) fn detect_conflicts(frames: &[Frame]) -> Vec<Conflict> {
+    let mut edits: HashMap<String, Edit> = HashMap::new();
+    let mut children: HashMap<String, Vec<String>> = HashMap::new();
+    let mut resolved: HashSet<String> = HashSet::new();
+
+    for frame in frames.iter().filter(|frame| frame.topic == "note.edit") {
+        let Some(meta) = frame.meta.as_ref() else {
+            continue;
+        };
+        let (Some(parent), Some(yak_id)) = (
+            meta.get("note_id").and_then(|v| v.as_str()),
+            meta.get("yak_id").and_then(|v| v.as_str()),
+        ) else {
+            continue;
+        };
+        if let Some(ids) = meta.get(RESOLVES_KEY).and_then(|v| v.as_array()) {
+            resolved.extend(ids.iter().filter_map(|id| id.as_str()).map(String::from));
+        }
+
+        let id = frame.id.to_string();
+        children
+            .entry(parent.to_string())
+            .or_default()
+            .push(id.clone());
+        edits.insert(
+            id.clone(),
+            Edit {
+                id,
+                yak_id: yak_id.to_string(),
+                hash: frame.hash.clone(),
+            },
+        );
+    }
+
+    let head = |mut id: &String| {
+        while let Some(next) = children.get(id).and_then(|ids| ids.last()) {
+            id = next;
+        }
+        id.clone()
+    };
+
+    let mut conflicts: Vec<Conflict> = children
+        .iter()
+        .filter(|(_, branches)| branches.len() > 1)
+        .filter(|(_, branches)| !branches.iter().all(|id| resolved.contains(id)))
+        .filter(|(_, branches)| {
+            let hashes: HashSet<Option<&ssri::Integrity>> = branches
+                .iter()
+                .map(|id| edits[&head(id)].hash.as_ref())
+                .collect();
+            hashes.len() > 1 || hashes.contains(&None)
+        })
+        .map(|(note_id, branches)| Conflict {
+            note_id: note_id.clone(),
+            yak_id: edits[&branches[0]].yak_id.clone(),
+            versions: branches
+                .iter()
+                .map(|id| {
+                    let head_id = head(id);
+                    ConflictVersion {
+                        frame_id: edits[id].id.clone(),
+                        hash: edits[&head_id].hash.clone(),
+                        head_id,
+                        content: None,
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+    conflicts.sort_by(|a, b| a.note_id.cmp(&b.note_id));
+    conflicts
+}
+
+async fn load_conflicts(store: &Store) -> (Vec<Frame>, Vec<Conflict>) {
+    let frames = read_all_frames(store).await;
+    let mut conflicts = detect_conflicts(&frames);
+    for version in conflicts.iter_mut().flat_map(|c| c.versions.iter_mut()) {
+        if let Some(hash) = &version.hash {
+            version.content = store
+                .cas_read(hash)
+                .await
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok());
+        }
+    }
+    (frames, conflicts)
+}
+
+#[tauri::command]
+pub async fn list_conflicts(store: State<'_, Store>) -> Result<Vec<Conflict>, String> {
+    Ok(load_conflicts(&store).await.1)
+}
+
+/// Settles a conflict on `frame_id` (the concurrently edited note) by appending an edit with
+/// either the content of the chosen version or merged content supplied by the user.
+#[tauri::command]
+pub async fn resolve_conflict(
+    store: State<'_, Store>,
+    app: AppHandle,
+    frame_id: String,
+    choice: Option<String>,
+    merged_content: Option<String>,
+) -> Result<String, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let (frames, conflicts) = load_conflicts(&store).await;
+    let conflict = conflicts
+        .into_iter()
+        .find(|conflict| conflict.note_id == frame_id)
+        .ok_or_else(|| format!("No conflict found for {frame_id}"))?;
+
+    let content = match (choice, merged_content) {
+        (_, Some(merged)) => merged,
+        (Some(choice), None) => conflict
+            .versions
+            .iter()
+            .find(|v| v.frame_id == choice || v.head_id == choice)
+            .ok_or_else(|| format!("{choice} is not a version of this conflict"))?
+            .content
+            .clone()
+            .unwrap_or_default(),
+        (None, None) => return Err("Either a choice or merged content is required".to_string()),
+    };
+
+    let request = resolution(&frames, &conflict, content);
+    let frame = prepare_frame(&store, request).await?;
+    let appended =
+        crate::append_to_store(&store, frame).map_err(|e| e.context("Failed to append frame"))?;
+    emit_frame(&app, &appended).map_err(|e| format!("Failed to emit frame: {e}"))?;
+
+    Ok(appended.id.to_string())
+}
+
+/// The edit settling `conflict` with `content`, made on whichever branch is currently visible
+/// so it replaces it in place.
+pub(crate) fn resolution(frames: &[Frame], conflict: &Conflict, content: String) -> AppendRequest {
+    let projection = Projection::from_frames(frames);
+    let visible = projection.current_notes(&conflict.yak_id);
+    let parent = conflict
+        .versions
+        .iter()
+        .map(|v| &v.head_id)
+        .find(|head| visible.iter().any(|note| &note.id == *head))
+        .unwrap_or(&conflict.versions[0].head_id)
+        .clone();
+
+    let resolves: Vec<serde_json::Value> = conflict
+        .versions
+        .iter()
+        .map(|v| serde_json::Value::String(v.frame_id.clone()))
+        .collect();
+    AppendRequest {
+        topic: "note.edit".to_string(),
+        content,
+        meta: Some(HashMap::from([
+            ("yak_id".to_string(), conflict.yak_id.clone().into()),
+            ("note_id".to_string(), parent.into()),
+            (RESOLVES_KEY.to_string(), resolves.into()),
+        ])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xs::store::ZERO_CONTEXT;
+
+    fn edit(note_id: &str, resolves: Option<Vec<String>>) -> Frame {
+        let mut meta = serde_json::json!({ "yak_id": "yak", "note_id": note_id });
+        if let Some(resolves) = resolves {
+            meta[RESOLVES_KEY] = resolves.into();
+        }
+        Frame {
+            id: scru128::new(),
+            context_id: ZERO_CONTEXT,
+            topic: "note.edit".to_string(),
+            hash: None,
+            meta: Some(meta),
+            ttl: None,
+        }
+    }
+
+    #[test]
+    fn test_detect_and_resolve_conflicts() {
+        let laptop = edit("note", None);
+        let desktop = edit("note", None);
+        let laptop_again = edit(&laptop.id.to_string(), None);
+
+        let frames = vec![laptop.clone(), desktop.clone(), laptop_again.clone()];
+        let conflicts = detect_conflicts(&frames);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].note_id, "note");
+        assert_eq!(
+            conflicts[0].versions[0].head_id,
+            laptop_again.id.to_string()
+        );
+        assert_eq!(conflicts[0].versions[1].head_id, desktop.id.to_string());
+
+        let resolution = edit(
+            &laptop_again.id.to_string(),
+            Some(vec![laptop.id.to_string(), desktop.id.to_string()]),
+        );
+        let frames = vec![laptop, desktop, laptop_again, resolution];
+        assert!(detect_conflicts(&frames).is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+pub(crate) const KEY_LEN: usize = 32;
+pub(crate) const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Derives a symmetric key from a passphrase with Argon2id.
+pub(crate) fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; KEY_LEN], String> {
+    let mut key = [0u8; KEY_LEN];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive key: {e}"))?;
+    Ok(key)
+}
+
+pub(crate) fn random_salt() -> [u8; SALT_LEN] {
+    use rand::RngCore;
+    let mut salt = [0u8; SALT_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+/// Encrypts with XChaCha20-Poly1305; the random nonce is prepended to the ciphertext.
+pub(crate) fn encrypt(key: &[u8; KEY_LEN], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|e| format!("Failed to encrypt: {e}"))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+pub(crate) fn decrypt(key: &[u8; KEY_LEN], sealed: &[u8]) -> Result<Vec<u8>, String> {
+    if sealed.len() < NONCE_LEN {
+        return Err("Encrypted data is truncated".to_string());
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(key.into())
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt: wrong passphrase or corrupted data".to_string())
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub(crate) fn from_hex(s: &str) -> Result<Vec<u8>, String> {
+    if !s.is_ascii() || s.len() % 2 != 0 {
+        return Err("Invalid hex string".to_string());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| format!("Invalid hex: {e}")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_roundtrip() {
+        let key = derive_key("correct horse", &random_salt()).unwrap();
+        let sealed = encrypt(&key, b"secret note").unwrap();
+        assert_ne!(&sealed[NONCE_LEN..], b"secret note");
+        assert_eq!(decrypt(&key, &sealed).unwrap(), b"secret note");
+
+        let wrong = derive_key("wrong", &random_salt()).unwrap();
+        assert!(decrypt(&wrong, &sealed).is_err());
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let bytes = [0u8, 15, 16, 255];
+        assert_eq!(from_hex(&to_hex(&bytes)).unwrap(), bytes);
+        // Multi-byte characters are refused rather than sliced through
+        assert!(from_hex("éa").is_err());
+        assert!(from_hex("abc").is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use std::path::{Path, PathBuf};
+use xs::store::Store;
+
+use crate::append_frame;
+use crate::import::{add_note, add_tag, add_task, set_flag};
+
+/// Launch flag that swaps the real store for a throwaway one filled with sample data.
+pub(crate) const FLAG: &str = "--demo";
+
+struct DemoNote {
+    content: &'static str,
+    tags: &'static [&'static str],
+    tasks: &'static [(&'static str, bool)],
+    pinned: bool,
+}
+
+const YAKS: &[(&str, &[DemoNote])] = &[
+    (
+        "Garden",
+        &[
+            DemoNote {
+                content: "# Spring planting\n\nTomatoes go in after the last frost. Basil between the rows keeps pests down.",
+                tags: &["plants", "todo"],
+                tasks: &[("Buy tomato seedlings", false), ("Turn the compost", true)],
+                pinned: true,
+            },
+            DemoNote {
+                content: "# Watering\n\nDeep and infrequent beats a daily sprinkle. Mornings are best.",
+                tags: &["plants"],
+                tasks: &[],
+                pinned: false,
+            },
+        ],
+    ),
+    (
+        "Reading",
+        &[
+            DemoNote {
+                content: "# The Mythical Man-Month\n\nAdding people to a late project makes it later.",
+                tags: &["books", "software"],
+                tasks: &[("Finish chapter 4", false)],
+                pinned: false,
+            },
+            DemoNote {
+                content: "# To read\n\n- A Pattern Language\n- Thinking in Systems\n- The Timeless Way of Building",
+                tags: &["books"],
+                tasks: &[],
+                pinned: true,
+            },
+        ],
+    ),
+    (
+        "Trip to Lisbon",
+        &[DemoNote {
+            content: "# Itinerary\n\nDay 1: Alfama and the castle. Day 2: Belém, pastéis de nata. Day 3: Sintra.",
+            tags: &["travel"],
+            tasks: &[("Book the Sintra train", false), ("Renew passport", true)],
+            pinned: false,
+        }],
+    ),
+];
+
+/// Fills a store with a few yaks of sample notes, tags and tasks, for screenshots,
+/// onboarding and tests that shouldn't touch real data. Returns the new yak ids.
+pub(crate) async fn seed_demo_data(store: &Store) -> Result<Vec<String>, String> {
+    let mut yak_ids = Vec::new();
+    for (name, notes) in YAKS {
+        let yak = append_frame(
+            store,
+            "yak.create",
+            None,
+            Some(serde_json::json!({ "name": name, "demo": true })),
+        )
+        .await?;
+        let yak_id = yak.id.to_string();
+        for note in notes.iter() {
+            let frame = add_note(store, &yak_id, note.content, serde_json::json!({})).await?;
+            let note_id = frame.id.to_string();
+            for tag in note.tags {
+                add_tag(store, &yak_id, &note_id, tag).await?;
+            }
+            for (text, done) in note.tasks {
+                add_task(store, &yak_id, Some(&note_id), text, *done).await?;
+            }
+            if note.pinned {
+                set_flag(store, "note.pin", &yak_id, &note_id, true).await?;
+            }
+        }
+        yak_ids.push(yak_id);
+    }
+    Ok(yak_ids)
+}
+
+/// A fresh directory for a demo store, so each demo launch starts from the same sample data.
+pub(crate) fn store_path() -> PathBuf {
+    std::env::temp_dir().join(format!("yaks-demo-{}", scru128::new()))
+}
+
+/// Opens a store at `path` seeded with the demo data.
+pub(crate) async fn demo_store(path: &Path) -> Result<Store, String> {
+    let store = Store::new(path.to_path_buf());
+    seed_demo_data(&store).await?;
+    Ok(store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection::Projection;
+    use crate::read_all_frames;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_demo_store() {
+        let dir = tempdir().unwrap();
+        let store = demo_store(dir.path()).await.unwrap();
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+
+        assert_eq!(projection.yaks.len(), YAKS.len());
+        let garden = projection
+            .yaks
+            .values()
+            .find(|yak| yak.name.as_deref() == Some("Garden"))
+            .unwrap();
+        let notes = projection.current_notes(&garden.id);
+        assert_eq!(notes.len(), 2);
+        assert!(notes[0].pinned);
+        assert!(notes[0].tags.contains("todo"));
+        assert_eq!(projection.yak_tasks(&garden.id).len(), 2);
+    }
+}
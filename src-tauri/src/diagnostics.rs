@@ -0,0 +1,312 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::blobs::{self, BlobStats};
+use crate::cache::CasCache;
+use crate::location::StoreLocation;
+use crate::projection::Projection;
+use crate::{migrations, read_all_frames, recovery};
+
+/// How many log lines are kept for the bundle.
+const LOG_CAPACITY: usize = 1000;
+
+/// Settings topics included in the bundle, with secrets redacted.
+pub(crate) const SETTING_TOPICS: &[&str] = &[
+    "ai.config",
+    "ai.suggest",
+    "ai.transcribe",
+    "autostart.config",
+    "capture.clipboard",
+    "capture.folder",
+    "capture.inbox",
+    "capture.location",
+    "capture.mail",
+    "capture.secrets",
+    "compaction.config",
+    "copy.secure",
+    "digest.config",
+    "disk.watchdog",
+    "draft.config",
+    "emit.policy",
+    "enrich.config",
+    "export.git",
+    "export.ics",
+    "export.spotlight",
+    "extract.ocr",
+    "integration.scopes",
+    "integrations.chat",
+    "integrations.tasks",
+    "limits.content",
+    "locale.config",
+    "lock.config",
+    "mcp.config",
+    "plugin.config",
+    "publish.config",
+    "rates.alerts",
+    "retention.cold_archive",
+    "retention.policies",
+    "shortcut.bindings",
+    "speech.config",
+    "store.location",
+    "store.replay",
+    "sync.config",
+    "sync.p2p",
+    "sync.s3",
+    "usage.insights",
+];
+
+/// Setting fields whose names contain any of these are replaced before export.
+const SECRET_MARKERS: &[&str] = &[
+    "token",
+    "secret",
+    "password",
+    "passphrase",
+    "api_key",
+    "access_key",
+    "private_key",
+    "webhook_url",
+];
+
+fn logs() -> &'static Mutex<VecDeque<String>> {
+    static LOGS: OnceLock<Mutex<VecDeque<String>>> = OnceLock::new();
+    LOGS.get_or_init(|| Mutex::new(VecDeque::with_capacity(LOG_CAPACITY)))
+}
+
+/// Keeps a log line for the next diagnostics bundle.
+pub(crate) fn record_log(level: &str, message: &str) {
+    let mut logs = logs().lock().unwrap();
+    if logs.len() == LOG_CAPACITY {
+        logs.pop_front();
+    }
+    logs.push_back(format!(
+        "{} {level} {message}",
+        chrono::Utc::now().to_rfc3339()
+    ));
+}
+
+/// Whether a setting field holds a credential, going by its name.
+pub(crate) fn is_secret(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SECRET_MARKERS.iter().any(|marker| key.contains(marker))
+}
+
+/// Replaces the values of secret-looking fields, at any depth.
+pub(crate) fn redact(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                if is_secret(key) {
+                    if !value.is_null() {
+                        *value = Value::from("[redacted]");
+                    }
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn redacted_config(frames: &[Frame]) -> BTreeMap<&'static str, Value> {
+    SETTING_TOPICS
+        .iter()
+        .filter_map(|topic| {
+            let mut meta = frames
+                .iter()
+                .rev()
+                .find(|frame| frame.topic == *topic)?
+                .meta
+                .clone()?;
+            redact(&mut meta);
+            Some((*topic, meta))
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+struct StoreStats {
+    path: String,
+    size_bytes: u64,
+    frames: usize,
+    frames_with_content: usize,
+    topics: BTreeMap<String, usize>,
+    yaks: usize,
+    notes: usize,
+    tasks: usize,
+    content: BlobStats,
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+fn store_stats(path: &Path, frames: &[Frame]) -> StoreStats {
+    let mut topics = BTreeMap::new();
+    for frame in frames {
+        *topics.entry(frame.topic.clone()).or_insert(0) += 1;
+    }
+    let projection = Projection::from_frames(frames);
+    StoreStats {
+        path: path.display().to_string(),
+        size_bytes: dir_size(path),
+        frames: frames.len(),
+        frames_with_content: frames.iter().filter(|frame| frame.hash.is_some()).count(),
+        topics,
+        yaks: projection.yaks.len(),
+        notes: projection.notes.len(),
+        tasks: projection.tasks.len(),
+        content: blobs::stats(path, frames),
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    serde_json::to_vec_pretty(value).map_err(|e| format!("Failed to serialize: {e}"))
+}
+
+/// Gathers everything in the bundle as `(file name, contents)`.
+async fn collect(
+    store: &Store,
+    store_path: &Path,
+    extra: Value,
+) -> Result<Vec<(&'static str, Vec<u8>)>, String> {
+    let frames = read_all_frames(store).await;
+    let integrity = recovery::scan(store, &frames, recovery::TAIL).await;
+    let logs = logs()
+        .lock()
+        .unwrap()
+        .iter()
+        .fold(String::new(), |mut out, line| {
+            out.push_str(line);
+            out.push('\n');
+            out
+        });
+
+    Ok(vec![
+        (
+            "info.json",
+            to_json(&json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "os": std::env::consts::OS,
+                "arch": std::env::consts::ARCH,
+                "created_at": chrono::Utc::now().to_rfc3339(),
+                "runtime": extra,
+            }))?,
+        ),
+        ("store.json", to_json(&store_stats(store_path, &frames))?),
+        ("config.json", to_json(&redacted_config(&frames))?),
+        ("schema.json", to_json(&migrations::status(&frames))?),
+        ("integrity.json", to_json(&integrity)?),
+        ("logs.txt", logs.into_bytes()),
+    ])
+}
+
+fn write_zip(path: &Path, files: Vec<(&str, Vec<u8>)>) -> Result<(), String> {
+    let file = std::fs::File::create(path)
+        .map_err(|e| format!("Failed to create {}: {e}", path.display()))?;
+    let mut zip = zip::ZipWriter::new(file);
+    for (name, bytes) in files {
+        zip.start_file(name, zip::write::SimpleFileOptions::default())
+            .and_then(|()| zip.write_all(&bytes).map_err(Into::into))
+            .map_err(|e| format!("Failed to write {name}: {e}"))?;
+    }
+    zip.finish()
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok(())
+}
+
+/// Writes a zip of recent logs, store stats, redacted settings, schema version and an
+/// integrity summary to attach to a bug report.
+#[tauri::command]
+pub async fn export_diagnostics(
+    app: AppHandle,
+    store: State<'_, Store>,
+    location: State<'_, StoreLocation>,
+    path: String,
+) -> Result<(), String> {
+    let runtime = json!({
+        "metrics": crate::metrics::snapshot(),
+        "cas_cache": app.state::<Arc<CasCache>>().stats(),
+        "pending_appends": crate::outbox::get_pending_appends(app.state()).len(),
+    });
+    let files = collect(&store, &location.0, runtime).await?;
+    write_zip(Path::new(&path), files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::save_setting;
+    use std::io::Read;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_redact() {
+        let mut value = json!({
+            "bucket": "notes",
+            "secret_key": "hunter2",
+            "peers": [{ "name": "laptop", "Auth_Token": "abc" }],
+            "password": null,
+        });
+        redact(&mut value);
+        assert_eq!(value["bucket"], "notes");
+        assert_eq!(value["secret_key"], "[redacted]");
+        assert_eq!(value["peers"][0]["name"], "laptop");
+        assert_eq!(value["peers"][0]["Auth_Token"], "[redacted]");
+        assert!(value["password"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_bundle_leaves_out_secrets() {
+        let dir = tempdir().unwrap();
+        let store_path = dir.path().join("store");
+        let store = Store::new(store_path.clone());
+        save_setting(
+            &store,
+            "sync.s3",
+            &json!({ "bucket": "notes", "secret_key": "hunter2" }),
+        )
+        .unwrap();
+        record_log("error", "something broke");
+
+        let path = dir.path().join("diagnostics.zip");
+        let files = collect(&store, &store_path, json!({})).await.unwrap();
+        write_zip(&path, files).unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut read = |name: &str| {
+            let mut text = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            text
+        };
+        let config = read("config.json");
+        assert!(config.contains("notes"));
+        assert!(!config.contains("hunter2"));
+        assert!(read("logs.txt").contains("something broke"));
+        let stats: Value = serde_json::from_str(&read("store.json")).unwrap();
+        assert_eq!(stats["frames"], 1);
+        read("schema.json");
+        read("integrity.json");
+    }
+}
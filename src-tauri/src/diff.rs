@@ -0,0 +1,350 @@
+use serde::Serialize;
+use tauri::State;
+use xs::store::Store;
+
+use crate::locks::LockState;
+
+/// Unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+/// Past this many token pairs, the changed middle is shown as replaced outright rather
+/// than diffed.
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// A run of words within a changed line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Span {
+    pub op: Op,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiffLine {
+    pub op: Op,
+    pub text: String,
+    /// 1-based line numbers in each revision
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    /// For a changed line paired with the line that replaced it (or that it replaced), the
+    /// words that stayed and the ones that changed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<Span>,
+}
+
+/// A group of changes with the unchanged lines around them, like a unified diff's hunk.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RevisionDiff {
+    pub from: String,
+    pub to: String,
+    /// Lines added and removed overall
+    pub added: usize,
+    pub removed: usize,
+    pub hunks: Vec<Hunk>,
+}
+
+/// The edits turning `old` into `new`, from their longest common subsequence. Deletions
+/// come before the insertions that replace them.
+pub(crate) fn diff_tokens<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Op, &'a str)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    let mut ops: Vec<(Op, &str)> = old[..prefix].iter().map(|t| (Op::Equal, *t)).collect();
+
+    if a.len().saturating_mul(b.len()) > MAX_CELLS {
+        ops.extend(a.iter().map(|t| (Op::Delete, *t)));
+        ops.extend(b.iter().map(|t| (Op::Insert, *t)));
+    } else {
+        // lcs[i][j] is the length of the common subsequence of a[i..] and b[j..]
+        let width = b.len() + 1;
+        let mut lcs = vec![0u32; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i * width + j] = if a[i] == b[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push((Op::Equal, a[i]));
+                (i, j) = (i + 1, j + 1);
+            } else if j == b.len()
+                || (i < a.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+            {
+                ops.push((Op::Delete, a[i]));
+                i += 1;
+            } else {
+                ops.push((Op::Insert, b[j]));
+                j += 1;
+            }
+        }
+    }
+    ops.extend(old[old.len() - suffix..].iter().map(|t| (Op::Equal, *t)));
+    ops
+}
+
+/// Splits a line into words, runs of spaces and single punctuation marks.
+pub(crate) fn tokens(line: &str) -> Vec<&str> {
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut previous = None;
+    for (i, c) in line.char_indices() {
+        let current = class(c);
+        if i > start && (previous != Some(current) || current == 2) {
+            tokens.push(&line[start..i]);
+            start = i;
+        }
+        previous = Some(current);
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+/// The spans of `old` and of `new`, each with its unchanged words and the ones only it has.
+fn diff_words(old: &str, new: &str) -> (Vec<Span>, Vec<Span>) {
+    let (mut old_spans, mut new_spans) = (Vec::new(), Vec::new());
+    let push = |spans: &mut Vec<Span>, op: Op, text: &str| match spans.last_mut() {
+        Some(last) if last.op == op => last.text.push_str(text),
+        _ => spans.push(Span {
+            op,
+            text: text.to_string(),
+        }),
+    };
+    for (op, token) in diff_tokens(&tokens(old), &tokens(new)) {
+        match op {
+            Op::Equal => {
+                push(&mut old_spans, op, token);
+                push(&mut new_spans, op, token);
+            }
+            Op::Delete => push(&mut old_spans, op, token),
+            Op::Insert => push(&mut new_spans, op, token),
+        }
+    }
+    (old_spans, new_spans)
+}
+
+/// Every line of both texts, numbered, with word-level changes for lines that replace each
+/// other.
+fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let (old, new): (Vec<&str>, Vec<&str>) = (old.lines().collect(), new.lines().collect());
+    let (mut old_line, mut new_line) = (0, 0);
+    let mut lines: Vec<DiffLine> = diff_tokens(&old, &new)
+        .into_iter()
+        .map(|(op, text)| {
+            if op != Op::Insert {
+                old_line += 1;
+            }
+            if op != Op::Delete {
+                new_line += 1;
+            }
+            DiffLine {
+                op,
+                text: text.to_string(),
+                old_line: (op != Op::Insert).then_some(old_line),
+                new_line: (op != Op::Delete).then_some(new_line),
+                words: Vec::new(),
+            }
+        })
+        .collect();
+
+    // Pair each run of deleted lines with the inserted lines right after it
+    let mut i = 0;
+    while i < lines.len() {
+        let deletes = lines[i..]
+            .iter()
+            .take_while(|line| line.op == Op::Delete)
+            .count();
+        let inserts = lines[i + deletes..]
+            .iter()
+            .take_while(|line| line.op == Op::Insert)
+            .count();
+        for k in 0..deletes.min(inserts) {
+            let (old, new) = (i + k, i + deletes + k);
+            let (old_words, new_words) = diff_words(&lines[old].text, &lines[new].text);
+            lines[old].words = old_words;
+            lines[new].words = new_words;
+        }
+        i += (deletes + inserts).max(1);
+    }
+    lines
+}
+
+/// Groups changed lines with `CONTEXT` lines around them, merging groups that overlap.
+fn hunks(lines: &[DiffLine]) -> Vec<Hunk> {
+    let changed: Vec<usize> = (0..lines.len())
+        .filter(|&i| lines[i].op != Op::Equal)
+        .collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for i in changed {
+        let (start, end) = (
+            i.saturating_sub(CONTEXT),
+            (i + CONTEXT + 1).min(lines.len()),
+        );
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let lines = lines[start..end].to_vec();
+            // Lines before the hunk in each revision, for where it starts
+            let old_before = lines
+                .iter()
+                .find_map(|line| line.old_line)
+                .map_or(0, |n| n - 1);
+            let new_before = lines
+                .iter()
+                .find_map(|line| line.new_line)
+                .map_or(0, |n| n - 1);
+            let old_lines = lines.iter().filter(|line| line.op != Op::Insert).count();
+            let new_lines = lines.iter().filter(|line| line.op != Op::Delete).count();
+            Hunk {
+                old_start: old_before + 1,
+                old_lines,
+                new_start: new_before + 1,
+                new_lines,
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// The content of a frame by id, unsealed if its yak is unlocked.
+async fn revision(store: &Store, locks: &LockState, frame_id: &str) -> Result<String, String> {
+    let id = frame_id
+        .parse::<scru128::Scru128Id>()
+        .map_err(|e| format!("Invalid frame id: {e}"))?;
+    let frame = store
+        .get(&id)
+        .ok_or_else(|| format!("Frame not found: {frame_id}"))?;
+    let Some(hash) = frame.hash else {
+        return Ok(String::new());
+    };
+    let bytes = store
+        .cas_read(&hash)
+        .await
+        .map_err(|e| format!("Failed to read content: {e}"))?;
+    locks.open(&hash.to_string(), &String::from_utf8_lossy(&bytes))
+}
+
+/// Diffs the content of two revisions (any two frames with content), line by line with
+/// word-level changes within replaced lines, for the history view.
+#[tauri::command]
+pub async fn diff_revisions(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    frame_id_a: String,
+    frame_id_b: String,
+) -> Result<RevisionDiff, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let old = revision(&store, &locks, &frame_id_a).await?;
+    let new = revision(&store, &locks, &frame_id_b).await?;
+    let lines = diff_lines(&old, &new);
+    let added = lines.iter().filter(|line| line.op == Op::Insert).count();
+    let removed = lines.iter().filter(|line| line.op == Op::Delete).count();
+    Ok(RevisionDiff {
+        from: frame_id_a,
+        to: frame_id_b,
+        added,
+        removed,
+        hunks: hunks(&lines),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens() {
+        assert_eq!(
+            tokens("Buy  milk, eggs"),
+            vec!["Buy", "  ", "milk", ",", " ", "eggs"]
+        );
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        use Op::*;
+        let old = "# Groceries\nmilk\neggs\nbread\n";
+        let new = "# Groceries\nmilk\nfree-range eggs\nbread\nbutter\n";
+        let lines = diff_lines(old, new);
+        let ops: Vec<Op> = lines.iter().map(|line| line.op).collect();
+        assert_eq!(ops, vec![Equal, Equal, Delete, Insert, Equal, Insert]);
+        assert_eq!(lines[3].old_line, None);
+        assert_eq!(lines[3].new_line, Some(3));
+        assert_eq!(
+            lines[3].words,
+            vec![
+                Span {
+                    op: Insert,
+                    text: "free-range ".to_string()
+                },
+                Span {
+                    op: Equal,
+                    text: "eggs".to_string()
+                },
+            ]
+        );
+        assert!(lines[5].words.is_empty());
+    }
+
+    #[test]
+    fn test_hunks() {
+        let old: String = (1..=20).map(|n| format!("line {n}\n")).collect();
+        let new = old
+            .replace("line 2\n", "line two\n")
+            .replace("line 18\n", "");
+        let hunks = hunks(&diff_lines(&old, &new));
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(
+            (hunks[0].old_start, hunks[0].old_lines, hunks[0].new_lines),
+            (1, 5, 5)
+        );
+        assert_eq!(
+            (hunks[1].old_start, hunks[1].old_lines, hunks[1].new_start),
+            (15, 6, 15)
+        );
+        assert_eq!(hunks[1].new_lines, 5);
+    }
+}
@@ -0,0 +1,426 @@
+use chrono::{Duration, Local, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::export::{content_title, created_id, note_title};
+use crate::focus;
+use crate::locale::{Locale, LocaleState};
+use crate::locks::{is_sealed, LockState};
+use crate::projection::{Projection, Task};
+use crate::settings::{latest_setting, load_setting, save_setting};
+use crate::shutdown::begin_write;
+use crate::time::{from_id, TimeRange};
+use crate::windows::emit_frames;
+use crate::{ai, append_batch_to_store, read_all_frames, AppendRequest};
+
+const CONFIG_TOPIC: &str = "digest.config";
+/// Meta key on a digest note holding the range it covers.
+const DIGEST_KEY: &str = "digest";
+/// The yak digests are appended to, made the first time one is.
+const REVIEWS_YAK: &str = "Reviews";
+const TICK: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DigestConfig {
+    /// Write a digest of the week before once each week starts
+    pub enabled: bool,
+    /// Open with a summary from the configured model
+    pub summarize: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Item {
+    title: String,
+    yak: String,
+}
+
+/// What happened over a range, as written up in a digest.
+#[derive(Debug, Default)]
+struct Digest {
+    notes_created: Vec<Item>,
+    tasks_completed: Vec<Item>,
+    tracked_secs: u64,
+    /// Yak name -> seconds, most first
+    tracked_by_yak: Vec<(String, u64)>,
+    /// Every task still open, whenever it was made
+    open_tasks: Vec<Item>,
+}
+
+fn yak_name(projection: &Projection, yak_id: &str) -> String {
+    projection
+        .yaks
+        .get(yak_id)
+        .and_then(|yak| yak.name.clone())
+        .unwrap_or_else(|| "Untitled".to_string())
+}
+
+fn readable(content: Option<&str>) -> Option<&str> {
+    content.filter(|content| !is_sealed(content))
+}
+
+fn compile(
+    projection: &Projection,
+    frames: &[Frame],
+    range: &TimeRange,
+    locale: &Locale,
+    reviews_yak: Option<&str>,
+) -> Digest {
+    let in_range = |id: &str| from_id(id).is_some_and(|time| range.contains(time));
+    let mut digest = Digest::default();
+
+    // In yak order, oldest first, leaving out locked notes and earlier digests
+    let mut yak_ids: Vec<&String> = projection.notes_by_yak.keys().collect();
+    yak_ids.sort();
+    for yak_id in yak_ids {
+        if Some(yak_id.as_str()) == reviews_yak {
+            continue;
+        }
+        let mut notes: Vec<_> = projection
+            .current_notes(yak_id)
+            .into_iter()
+            .filter(|note| readable(note.content.as_deref()).is_some())
+            .map(|note| (created_id(projection, note), note))
+            .filter(|(created, _)| in_range(created))
+            .collect();
+        notes.sort_by(|a, b| a.0.cmp(&b.0));
+        digest
+            .notes_created
+            .extend(notes.into_iter().map(|(_, note)| Item {
+                title: note_title(note),
+                yak: yak_name(projection, yak_id),
+            }));
+    }
+
+    let task_item = |task: &Task| {
+        readable(task.content.as_deref()).map(|content| Item {
+            title: content_title(content),
+            yak: yak_name(projection, &task.yak_id),
+        })
+    };
+    let mut completed = HashSet::new();
+    for frame in frames {
+        let id = frame.id.to_string();
+        let meta = frame.meta.as_ref();
+        let done = meta
+            .and_then(|meta| meta.get("done"))
+            .and_then(|done| done.as_bool())
+            .unwrap_or(false);
+        let task_id = match frame.topic.as_str() {
+            "task.create" => id.clone(),
+            "task.update" => match meta.and_then(|meta| meta.get("task_id")) {
+                Some(task_id) => task_id.as_str().unwrap_or_default().to_string(),
+                None => continue,
+            },
+            _ => continue,
+        };
+        if !done || !in_range(&id) || !completed.insert(task_id.clone()) {
+            continue;
+        }
+        // Only tasks still done; one reopened since doesn't count
+        let task = projection.tasks.get(&task_id).filter(|task| task.done);
+        digest.tasks_completed.extend(task.and_then(task_item));
+    }
+    let mut open: Vec<_> = projection
+        .tasks
+        .values()
+        .filter(|task| !task.done)
+        .collect();
+    open.sort_by(|a, b| a.id.cmp(&b.id));
+    digest.open_tasks = open.into_iter().filter_map(task_item).collect();
+
+    let report = focus::report(frames, range, locale);
+    digest.tracked_secs = report.total_secs;
+    let mut by_yak: HashMap<String, u64> = HashMap::new();
+    for (yak_id, secs) in report.by_yak {
+        *by_yak.entry(yak_name(projection, &yak_id)).or_default() += secs;
+    }
+    digest.tracked_by_yak = by_yak.into_iter().collect();
+    digest
+        .tracked_by_yak
+        .sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    digest
+}
+
+fn duration(secs: u64) -> String {
+    match (secs / 3600, secs % 3600 / 60) {
+        (0, minutes) => format!("{minutes}m"),
+        (hours, 0) => format!("{hours}h"),
+        (hours, minutes) => format!("{hours}h {minutes}m"),
+    }
+}
+
+/// The range as local dates, the last day inclusive.
+fn span(range: &TimeRange) -> String {
+    let from = range
+        .from
+        .map(|from| from.with_timezone(&Local).date_naive().to_string());
+    let to = range.to.map(|to| {
+        (to.with_timezone(&Local) - Duration::milliseconds(1))
+            .date_naive()
+            .to_string()
+    });
+    match (from, to) {
+        (Some(from), Some(to)) if from == to => from,
+        (Some(from), Some(to)) => format!("{from} – {to}"),
+        (Some(from), None) => format!("since {from}"),
+        (None, Some(to)) => format!("until {to}"),
+        (None, None) => "all time".to_string(),
+    }
+}
+
+fn section(markdown: &mut String, heading: &str, items: &[Item]) {
+    markdown.push_str(&format!("\n## {heading} ({})\n\n", items.len()));
+    if items.is_empty() {
+        markdown.push_str("None\n");
+    }
+    for item in items {
+        markdown.push_str(&format!("- {} ({})\n", item.title, item.yak));
+    }
+}
+
+fn markdown(digest: &Digest, range: &TimeRange, summary: Option<&str>) -> String {
+    let mut markdown = format!("# Review: {}\n", span(range));
+    if let Some(summary) = summary {
+        markdown.push_str(&format!("\n## Summary\n\n{}\n", summary.trim()));
+    }
+    section(&mut markdown, "Notes created", &digest.notes_created);
+    section(&mut markdown, "Tasks completed", &digest.tasks_completed);
+    markdown.push_str(&format!(
+        "\n## Time tracked ({})\n\n",
+        duration(digest.tracked_secs)
+    ));
+    if digest.tracked_by_yak.is_empty() {
+        markdown.push_str("None\n");
+    }
+    for (yak, secs) in &digest.tracked_by_yak {
+        markdown.push_str(&format!("- {yak}: {}\n", duration(*secs)));
+    }
+    section(&mut markdown, "Open tasks", &digest.open_tasks);
+    markdown
+}
+
+/// The week before the one `today` falls in, per the locale's first day of the week.
+fn last_week(today: NaiveDate, locale: &Locale) -> TimeRange {
+    let start = |date: NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+            .earliest()
+            .map(|time| time.with_timezone(&Utc))
+    };
+    let this_week = locale.week_of(today);
+    TimeRange {
+        from: start(this_week - Duration::days(7)),
+        to: start(this_week),
+    }
+}
+
+async fn summarize(store: &Store, markdown: &str) -> Result<String, String> {
+    let config = ai::load_config(store).await;
+    let prompt = format!(
+        "Here is a review of a week of notes and tasks. Summarize it in a short paragraph: \
+         what got done, where the time went and what is still open. Reply with the summary \
+         only.\n\n{markdown}"
+    );
+    ai::generate(&config, &prompt, |_| {}).await
+}
+
+/// Writes the digest of `range` as a note in the "Reviews" yak, making the yak if needed.
+/// A summary that fails to generate is left out rather than failing the digest.
+async fn generate(
+    app: &AppHandle,
+    store: &Store,
+    range: &TimeRange,
+    summarize_with_model: bool,
+) -> Result<Frame, String> {
+    let frames = read_all_frames(store).await;
+    let mut projection = Projection::from_frames(&frames);
+    projection.resolve_content(store).await;
+    let reviews_yak = projection
+        .yaks
+        .values()
+        .filter(|yak| yak.name.as_deref() == Some(REVIEWS_YAK))
+        .map(|yak| yak.id.clone())
+        .min();
+    let locale = app.state::<LocaleState>().get();
+    let digest = compile(&projection, &frames, range, &locale, reviews_yak.as_deref());
+    drop(frames);
+
+    let mut summary = None;
+    if summarize_with_model {
+        match summarize(store, &markdown(&digest, range, None)).await {
+            Ok(text) if !text.trim().is_empty() => summary = Some(text),
+            Ok(_) => eprintln!("The model returned an empty digest summary"),
+            Err(e) => eprintln!("Failed to summarize digest: {e}"),
+        }
+    }
+    let content = markdown(&digest, range, summary.as_deref());
+
+    let _guard = begin_write(app)?;
+    let mut appended = Vec::new();
+    let yak_id = match reviews_yak {
+        Some(yak_id) => yak_id,
+        None => {
+            let request = AppendRequest {
+                topic: "yak.create".to_string(),
+                content: String::new(),
+                meta: Some(HashMap::from([("name".to_string(), REVIEWS_YAK.into())])),
+            };
+            let yak = append_batch_to_store(store, vec![request]).await?.remove(0);
+            appended.push(yak.clone());
+            yak.id.to_string()
+        }
+    };
+    let meta = HashMap::from([
+        ("yak_id".to_string(), yak_id.into()),
+        ("content_type".to_string(), "text/markdown".into()),
+        (DIGEST_KEY.to_string(), json!(range)),
+    ]);
+    let request = AppendRequest {
+        topic: "note.create".to_string(),
+        content,
+        meta: Some(meta),
+    };
+    let request = app.state::<LockState>().seal_request(request)?;
+    let note = append_batch_to_store(store, vec![request]).await?.remove(0);
+    appended.push(note.clone());
+    let _ = emit_frames(app, &appended);
+    Ok(note)
+}
+
+/// Writes last week's digest once the week has started, when digests are enabled.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    loop {
+        let frames = read_all_frames(&store).await;
+        let config: DigestConfig = latest_setting(&frames, CONFIG_TOPIC);
+        let locale = app.state::<LocaleState>().get();
+        let range = last_week(Local::now().date_naive(), &locale);
+        let written = frames
+            .iter()
+            .filter(|frame| frame.topic == "note.create")
+            .filter_map(|frame| frame.meta.as_ref()?.get(DIGEST_KEY).cloned())
+            .filter_map(|value| serde_json::from_value::<TimeRange>(value).ok())
+            .any(|written| written.from == range.from);
+        drop(frames);
+        if config.enabled && !written {
+            if let Err(e) = generate(&app, &store, &range, config.summarize).await {
+                eprintln!("Failed to write weekly digest: {e}");
+            }
+        }
+        tokio::time::sleep(TICK).await;
+    }
+}
+
+/// Writes a digest of `range` (last week by default): notes created, tasks completed, time
+/// tracked in focus sessions and tasks still open, as a markdown note in the "Reviews" yak.
+/// `summarize` overrides the configured choice of opening with a summary from the model.
+#[tauri::command]
+pub async fn generate_digest(
+    app: AppHandle,
+    store: State<'_, Store>,
+    locale: State<'_, LocaleState>,
+    range: Option<TimeRange>,
+    summarize: Option<bool>,
+) -> Result<Frame, String> {
+    let config: DigestConfig = load_setting(&store, CONFIG_TOPIC).await;
+    let range = range.unwrap_or_else(|| last_week(Local::now().date_naive(), &locale.get()));
+    generate(&app, &store, &range, summarize.unwrap_or(config.summarize)).await
+}
+
+#[tauri::command]
+pub async fn get_digest_config(store: State<'_, Store>) -> Result<DigestConfig, String> {
+    Ok(load_setting(&store, CONFIG_TOPIC).await)
+}
+
+/// Turns the weekly digest on or off, and sets whether it opens with a summary.
+#[tauri::command]
+pub async fn configure_digests(
+    store: State<'_, Store>,
+    config: DigestConfig,
+) -> Result<(), String> {
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use crate::import::{add_note, add_task, create_yak};
+    use chrono::{Datelike, Weekday};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_digest() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Garden").await.unwrap();
+        add_note(&store, &yak_id, "# Seed order\npeas", json!({}))
+            .await
+            .unwrap();
+        let done = add_task(&store, &yak_id, None, "Water the beds", false)
+            .await
+            .unwrap();
+        add_task(&store, &yak_id, None, "Fix the fence", false)
+            .await
+            .unwrap();
+        let meta = json!({ "task_id": done.id.to_string(), "done": true });
+        append_frame(&store, "task.update", None, Some(meta))
+            .await
+            .unwrap();
+        let meta = json!({ "yak_id": yak_id, "duration_secs": 1500 });
+        let start = append_frame(&store, "focus.start", None, Some(meta))
+            .await
+            .unwrap();
+        let meta = json!({ "focus_id": start.id.to_string(), "elapsed_secs": 5400 });
+        append_frame(&store, "focus.end", None, Some(meta))
+            .await
+            .unwrap();
+
+        let frames = read_all_frames(&store).await;
+        let mut projection = Projection::from_frames(&frames);
+        projection.resolve_content(&store).await;
+        let range = TimeRange::default();
+        let digest = compile(&projection, &frames, &range, &Locale::default(), None);
+        let garden = |title: &str| Item {
+            title: title.to_string(),
+            yak: "Garden".to_string(),
+        };
+        assert_eq!(digest.notes_created, vec![garden("Seed order")]);
+        assert_eq!(digest.tasks_completed, vec![garden("Water the beds")]);
+        assert_eq!(digest.open_tasks, vec![garden("Fix the fence")]);
+        assert_eq!(digest.tracked_by_yak, vec![("Garden".to_string(), 5400)]);
+
+        let markdown = markdown(&digest, &range, Some("A quiet week."));
+        assert!(markdown.starts_with("# Review: all time\n\n## Summary\n\nA quiet week.\n"));
+        assert!(markdown.contains("## Time tracked (1h 30m)\n\n- Garden: 1h 30m\n"));
+        assert!(markdown.contains("## Open tasks (1)\n\n- Fix the fence (Garden)\n"));
+
+        // Earlier digests aren't counted as notes created
+        let digest = compile(
+            &projection,
+            &frames,
+            &range,
+            &Locale::default(),
+            Some(&yak_id),
+        );
+        assert!(digest.notes_created.is_empty());
+
+        let locale = Locale {
+            tag: "en-GB".to_string(),
+            week_start: Weekday::Mon,
+        };
+        let today = NaiveDate::from_ymd_opt(2025, 3, 13).unwrap();
+        let week = last_week(today, &locale);
+        let from = week.from.unwrap().with_timezone(&Local);
+        assert_eq!(
+            from.date_naive(),
+            NaiveDate::from_ymd_opt(2025, 3, 3).unwrap()
+        );
+        assert_eq!(from.weekday(), Weekday::Mon);
+        assert_eq!(span(&week), "2025-03-03 – 2025-03-09");
+    }
+}
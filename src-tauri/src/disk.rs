@@ -0,0 +1,279 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::health;
+use crate::location::StoreLocation;
+use crate::rates::{RatesState, INGESTERS};
+use crate::settings::{latest_setting, save_setting};
+
+const CONFIG_TOPIC: &str = "disk.watchdog";
+const TICK: std::time::Duration = std::time::Duration::from_secs(60);
+const GIB: u64 = 1024 * 1024 * 1024;
+/// Store sizes older than this are dropped; growth is measured over what's left.
+const GROWTH_WINDOW: Duration = Duration::hours(1);
+/// Growth isn't judged on less than this much history.
+const MIN_GROWTH_SPAN: Duration = Duration::minutes(5);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskConfig {
+    /// Free space, in bytes, below which windows are warned
+    pub low_bytes: u64,
+    /// Free space below which appends are at risk, and ingesters are paused
+    pub critical_bytes: u64,
+    /// Store growth per hour that's warned about; 0 to not watch growth
+    pub growth_bytes_per_hour: u64,
+    /// Pause the feed, mail, folder and clipboard ingesters while space is critical
+    pub pause_ingesters: bool,
+}
+
+impl Default for DiskConfig {
+    fn default() -> Self {
+        Self {
+            low_bytes: 2 * GIB,
+            critical_bytes: GIB / 2,
+            growth_bytes_per_hour: GIB,
+            pause_ingesters: true,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiskLevel {
+    #[default]
+    Ok,
+    Low,
+    Critical,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskStatus {
+    pub path: PathBuf,
+    /// Free space on the store's disk
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+    pub store_bytes: u64,
+    /// How fast the store has grown over the last hour, once there's enough history
+    pub growth_bytes_per_hour: Option<u64>,
+    pub level: DiskLevel,
+    pub growing_fast: bool,
+    /// Ingesters paused until space is freed
+    pub paused: Vec<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Sent as "disk-warning" when free space drops a level or the store starts growing fast.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskWarning {
+    /// `low`, `critical` or `growth`
+    pub reason: &'static str,
+    pub status: DiskStatus,
+}
+
+#[derive(Default)]
+pub struct DiskState {
+    config: Mutex<DiskConfig>,
+    /// Store sizes over the growth window, oldest first
+    samples: Mutex<VecDeque<(DateTime<Utc>, u64)>>,
+    last: Mutex<Option<DiskStatus>>,
+    /// Ingesters paused here, so only those are resumed once space is back
+    paused: Mutex<BTreeSet<String>>,
+}
+
+fn level(available: u64, config: &DiskConfig) -> DiskLevel {
+    if available < config.critical_bytes {
+        DiskLevel::Critical
+    } else if available < config.low_bytes {
+        DiskLevel::Low
+    } else {
+        DiskLevel::Ok
+    }
+}
+
+/// Records the store's size at `now` and returns its growth per hour over the window.
+fn growth(
+    samples: &mut VecDeque<(DateTime<Utc>, u64)>,
+    now: DateTime<Utc>,
+    size: u64,
+) -> Option<u64> {
+    samples.push_back((now, size));
+    while samples
+        .front()
+        .is_some_and(|(at, _)| now - *at > GROWTH_WINDOW)
+    {
+        samples.pop_front();
+    }
+    let (first_at, first_size) = *samples.front()?;
+    let span = now - first_at;
+    if span < MIN_GROWTH_SPAN {
+        return None;
+    }
+    let grown = size.saturating_sub(first_size);
+    Some(grown.saturating_mul(3600) / span.num_seconds().max(1) as u64)
+}
+
+/// Bytes in every file under `path`, not following links.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Measures the store and its disk, warning windows of anything worse than last time and
+/// pausing or resuming the ingesters to match.
+async fn check(app: &AppHandle) -> Result<DiskStatus, String> {
+    let path = app.state::<StoreLocation>().0.clone();
+    let measured = path.clone();
+    let (available_bytes, total_bytes, store_bytes) = tokio::task::spawn_blocking(move || {
+        let available = fs4::available_space(&measured)
+            .map_err(|e| format!("Failed to read free space for {}: {e}", measured.display()))?;
+        let total = fs4::total_space(&measured)
+            .map_err(|e| format!("Failed to read disk size for {}: {e}", measured.display()))?;
+        Ok::<_, String>((available, total, dir_size(&measured)))
+    })
+    .await
+    .map_err(|e| format!("Failed to check disk space: {e}"))??;
+
+    let state = app.state::<DiskState>();
+    let config = state.config.lock().unwrap().clone();
+    let now = Utc::now();
+    let growth_bytes_per_hour = growth(&mut state.samples.lock().unwrap(), now, store_bytes);
+    let level = level(available_bytes, &config);
+    let growing_fast = config.growth_bytes_per_hour > 0
+        && growth_bytes_per_hour.is_some_and(|growth| growth > config.growth_bytes_per_hour);
+
+    let rates = app.state::<RatesState>();
+    let paused: Vec<String> = {
+        let mut paused = state.paused.lock().unwrap();
+        if level == DiskLevel::Critical && config.pause_ingesters {
+            for ingester in INGESTERS {
+                if rates.pause(ingester) {
+                    paused.insert(ingester.to_string());
+                }
+            }
+        } else {
+            for ingester in std::mem::take(&mut *paused) {
+                rates.resume(&ingester);
+            }
+        }
+        paused.iter().cloned().collect()
+    };
+
+    let status = DiskStatus {
+        path,
+        available_bytes,
+        total_bytes,
+        store_bytes,
+        growth_bytes_per_hour,
+        level,
+        growing_fast,
+        paused,
+        checked_at: now,
+    };
+    let previous = state.last.lock().unwrap().replace(status.clone());
+    let (was, was_growing) = previous.map_or((DiskLevel::Ok, false), |previous| {
+        (previous.level, previous.growing_fast)
+    });
+    let reason = match level {
+        DiskLevel::Critical if was < DiskLevel::Critical => Some("critical"),
+        DiskLevel::Low if was < DiskLevel::Low => Some("low"),
+        _ if growing_fast && !was_growing => Some("growth"),
+        _ => None,
+    };
+    if let Some(reason) = reason {
+        eprintln!(
+            "Disk space {reason}: {} bytes free, store is {store_bytes} bytes",
+            status.available_bytes
+        );
+        let warning = DiskWarning {
+            reason,
+            status: status.clone(),
+        };
+        if let Err(e) = app.emit("disk-warning", &warning) {
+            eprintln!("Failed to emit disk warning: {e}");
+        }
+    }
+    Ok(status)
+}
+
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let config: DiskConfig = latest_setting(frames, CONFIG_TOPIC);
+    *app.state::<DiskState>().config.lock().unwrap() = config;
+}
+
+/// Checks free space and store growth every minute.
+pub(crate) async fn watch(app: AppHandle) {
+    loop {
+        match check(&app).await {
+            Ok(_) => health::ok(&app, "disk"),
+            Err(e) => {
+                eprintln!("{e}");
+                health::error(&app, "disk", &e);
+            }
+        }
+        tokio::time::sleep(TICK).await;
+    }
+}
+
+/// Free space on the store's disk, the store's size and growth, and whether either is a
+/// concern, measured now.
+#[tauri::command]
+pub async fn get_disk_status(app: AppHandle) -> Result<DiskStatus, String> {
+    check(&app).await
+}
+
+/// Sets the free space windows are warned at, the level at which ingesters are paused, and
+/// the store growth that's warned about.
+#[tauri::command]
+pub async fn configure_disk_watchdog(
+    store: State<'_, Store>,
+    state: State<'_, DiskState>,
+    config: DiskConfig,
+) -> Result<DiskConfig, String> {
+    if config.critical_bytes > config.low_bytes {
+        return Err("The critical level must be below the low level".to_string());
+    }
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    *state.config.lock().unwrap() = config.clone();
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_and_growth() {
+        let config = DiskConfig::default();
+        assert_eq!(level(10 * GIB, &config), DiskLevel::Ok);
+        assert_eq!(level(GIB, &config), DiskLevel::Low);
+        assert_eq!(level(GIB / 4, &config), DiskLevel::Critical);
+
+        let start = Utc::now();
+        let mut samples = VecDeque::new();
+        assert_eq!(growth(&mut samples, start, GIB), None);
+        // Not enough history yet
+        let at = start + Duration::minutes(1);
+        assert_eq!(growth(&mut samples, at, GIB), None);
+        let at = start + Duration::minutes(30);
+        assert_eq!(growth(&mut samples, at, 2 * GIB), Some(2 * GIB));
+        // Sizes older than the window are dropped, shrinking counts as no growth
+        let at = start + Duration::minutes(80);
+        assert_eq!(growth(&mut samples, at, GIB), Some(0));
+        assert_eq!(samples.len(), 2);
+    }
+}
@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::task::JoinHandle;
+use xs::store::{Frame, Store};
+
+use crate::projection::Projection;
+use crate::settings::{latest_setting, save_setting};
+use crate::shutdown::begin_write;
+use crate::windows::emit_frame;
+use crate::{prepare_frame, read_all_frames, AppendRequest};
+
+const CONFIG_TOPIC: &str = "draft.config";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DraftConfig {
+    /// How long typing must pause before a draft is committed as a revision
+    pub quiet_ms: u64,
+}
+
+impl Default for DraftConfig {
+    fn default() -> Self {
+        Self { quiet_ms: 2000 }
+    }
+}
+
+struct Draft {
+    content: String,
+    timer: JoinHandle<()>,
+}
+
+/// Unsaved editor content per note, keyed by the frame id the editor was opened on.
+pub struct DraftState {
+    drafts: Mutex<HashMap<String, Draft>>,
+    quiet: Mutex<Duration>,
+}
+
+impl Default for DraftState {
+    fn default() -> Self {
+        Self {
+            drafts: Mutex::new(HashMap::new()),
+            quiet: Mutex::new(Duration::from_millis(DraftConfig::default().quiet_ms)),
+        }
+    }
+}
+
+impl DraftState {
+    fn take(&self, frame_id: &str) -> Option<Draft> {
+        self.drafts.lock().unwrap().remove(frame_id)
+    }
+}
+
+/// Appends a `note.edit` revision of the note `frame_id` now resolves to, unless the
+/// content is unchanged.
+async fn commit(store: &Store, frame_id: &str, content: &str) -> Result<Option<Frame>, String> {
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    let note_id = projection.resolve(frame_id);
+    let note = projection
+        .notes
+        .get(&note_id)
+        .ok_or_else(|| format!("Unknown note {frame_id}"))?;
+
+    if let Some(hash) = &note.hash {
+        let current = store
+            .cas_read(hash)
+            .await
+            .map_err(|e| format!("Failed to read content: {e}"))?;
+        if current == content.as_bytes() {
+            return Ok(None);
+        }
+    } else if content.is_empty() {
+        return Ok(None);
+    }
+
+    let request = AppendRequest {
+        topic: "note.edit".to_string(),
+        content: content.to_string(),
+        meta: Some(HashMap::from([
+            ("yak_id".to_string(), note.yak_id.clone().into()),
+            ("note_id".to_string(), note_id.into()),
+        ])),
+    };
+    let frame = prepare_frame(store, request).await?;
+    let frame =
+        crate::append_to_store(store, frame).map_err(|e| e.context("Failed to append frame"))?;
+    Ok(Some(frame))
+}
+
+async fn flush(app: &AppHandle, frame_id: &str, content: &str) -> Result<Option<String>, String> {
+    let _guard = begin_write(app)?;
+    let store = app.state::<Store>();
+    let Some(frame) = commit(&store, frame_id, content).await? else {
+        return Ok(None);
+    };
+    emit_frame(app, &frame).map_err(|e| format!("Failed to emit frame: {e}"))?;
+    Ok(Some(frame.id.to_string()))
+}
+
+/// Commits every pending draft, e.g. before quitting.
+pub(crate) async fn flush_all(app: &AppHandle) {
+    let drafts: Vec<(String, Draft)> = app
+        .state::<DraftState>()
+        .drafts
+        .lock()
+        .unwrap()
+        .drain()
+        .collect();
+    for (frame_id, draft) in drafts {
+        draft.timer.abort();
+        if let Err(e) = flush(app, &frame_id, &draft.content).await {
+            eprintln!("Failed to commit draft of {frame_id}: {e}");
+        }
+    }
+}
+
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let config: DraftConfig = latest_setting(frames, CONFIG_TOPIC);
+    *app.state::<DraftState>().quiet.lock().unwrap() = Duration::from_millis(config.quiet_ms);
+}
+
+/// Buffers the editor's latest content for a note; it is committed as a single `note.edit`
+/// once typing has been quiet for the configured period.
+#[tauri::command]
+pub async fn draft_update(
+    app: AppHandle,
+    state: State<'_, DraftState>,
+    frame_id: String,
+    content: String,
+) -> Result<(), String> {
+    let quiet = *state.quiet.lock().unwrap();
+    let mut drafts = state.drafts.lock().unwrap();
+    if let Some(draft) = drafts.remove(&frame_id) {
+        draft.timer.abort();
+    }
+
+    let timer_app = app.clone();
+    let timer_id = frame_id.clone();
+    let timer = tokio::spawn(async move {
+        tokio::time::sleep(quiet).await;
+        let Some(draft) = timer_app.state::<DraftState>().take(&timer_id) else {
+            return;
+        };
+        if let Err(e) = flush(&timer_app, &timer_id, &draft.content).await {
+            eprintln!("Failed to commit draft of {timer_id}: {e}");
+        }
+    });
+    drafts.insert(frame_id, Draft { content, timer });
+    Ok(())
+}
+
+/// Commits a note's pending draft right away, e.g. when the editor closes. Returns the new
+/// revision's id, or `None` if there was nothing to commit.
+#[tauri::command]
+pub async fn commit_draft(
+    app: AppHandle,
+    state: State<'_, DraftState>,
+    frame_id: String,
+) -> Result<Option<String>, String> {
+    let Some(draft) = state.take(&frame_id) else {
+        return Ok(None);
+    };
+    draft.timer.abort();
+    flush(&app, &frame_id, &draft.content).await
+}
+
+#[tauri::command]
+pub async fn configure_drafts(
+    store: State<'_, Store>,
+    state: State<'_, DraftState>,
+    config: DraftConfig,
+) -> Result<(), String> {
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    *state.quiet.lock().unwrap() = Duration::from_millis(config.quiet_ms);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_commit_chains_revisions() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let note = append_frame(
+            &store,
+            "note.create",
+            Some(b"draft".as_slice()),
+            Some(serde_json::json!({ "yak_id": yak_id })),
+        )
+        .await
+        .unwrap();
+        let note_id = note.id.to_string();
+
+        assert!(commit(&store, &note_id, "draft").await.unwrap().is_none());
+
+        let first = commit(&store, &note_id, "draft one")
+            .await
+            .unwrap()
+            .unwrap();
+        // The editor may still hold the original id; the revision chains onto the latest
+        let second = commit(&store, &note_id, "draft two")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            second.meta.unwrap()["note_id"],
+            serde_json::json!(first.id.to_string())
+        );
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        assert_eq!(
+            projection.current_notes(&yak_id)[0].id,
+            second.id.to_string()
+        );
+    }
+}
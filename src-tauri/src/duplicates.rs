@@ -0,0 +1,321 @@
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store, ZERO_CONTEXT};
+
+use crate::projection::{Note, Projection};
+use crate::read_all_frames;
+use crate::windows::emit_frames;
+
+/// Words per shingle when comparing notes for near-duplicates.
+const SHINGLE: usize = 3;
+/// Jaccard similarity of shingle sets above which two notes count as near-duplicates.
+const NEAR_THRESHOLD: f64 = 0.8;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateKind {
+    /// Identical content (same CAS hash)
+    Exact,
+    /// Mostly the same words, e.g. the same note imported with different formatting
+    Near,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateCluster {
+    pub kind: DuplicateKind,
+    /// Oldest first; the first is the natural one to keep
+    pub note_ids: Vec<String>,
+    /// Lowest pairwise similarity within the cluster (1.0 for exact duplicates)
+    pub similarity: f64,
+}
+
+fn shingles(text: &str) -> HashSet<String> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.len() < SHINGLE {
+        return HashSet::from([words.join(" ")]);
+    }
+    words
+        .windows(SHINGLE)
+        .map(|window| window.join(" "))
+        .collect()
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+fn root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Clusters notes (with content resolved) into exact and near duplicates. Notes in an
+/// exact cluster are compared for near-duplication by their oldest member only.
+fn find(notes: &[&Note]) -> Vec<DuplicateCluster> {
+    let mut notes: Vec<&Note> = notes.to_vec();
+    notes.sort_by(|a, b| created(a).cmp(created(b)));
+
+    let mut clusters = Vec::new();
+    let mut by_hash: HashMap<String, Vec<&Note>> = HashMap::new();
+    let mut distinct: Vec<&Note> = Vec::new();
+    for note in notes {
+        if let Some(hash) = &note.hash {
+            let group = by_hash.entry(hash.to_string()).or_default();
+            if group.is_empty() {
+                distinct.push(note);
+            }
+            group.push(note);
+        }
+    }
+    for note in &distinct {
+        let group = &by_hash[&note.hash.as_ref().unwrap().to_string()];
+        if group.len() > 1 {
+            clusters.push(DuplicateCluster {
+                kind: DuplicateKind::Exact,
+                note_ids: group.iter().map(|note| note.id.clone()).collect(),
+                similarity: 1.0,
+            });
+        }
+    }
+
+    // Union-find over pairs of distinct notes that are similar enough
+    let sets: Vec<HashSet<String>> = distinct
+        .iter()
+        .map(|note| shingles(note.content.as_deref().unwrap_or_default()))
+        .collect();
+    let mut parent: Vec<usize> = (0..distinct.len()).collect();
+    let mut similarity: HashMap<(usize, usize), f64> = HashMap::new();
+    for i in 0..sets.len() {
+        for j in i + 1..sets.len() {
+            // Jaccard can't reach the threshold when the sizes differ too much
+            let (small, large) = (
+                sets[i].len().min(sets[j].len()),
+                sets[i].len().max(sets[j].len()),
+            );
+            if (small as f64) < large as f64 * NEAR_THRESHOLD {
+                continue;
+            }
+            let score = jaccard(&sets[i], &sets[j]);
+            if score >= NEAR_THRESHOLD {
+                similarity.insert((i, j), score);
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[b.max(a)] = a.min(b);
+            }
+        }
+    }
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..distinct.len() {
+        let r = root(&mut parent, i);
+        groups.entry(r).or_default().push(i);
+    }
+    let mut near: Vec<Vec<usize>> = groups.into_values().filter(|g| g.len() > 1).collect();
+    near.sort();
+    for members in near {
+        let lowest = similarity
+            .iter()
+            .filter(|((i, j), _)| members.contains(i) && members.contains(j))
+            .map(|(_, score)| *score)
+            .fold(1.0, f64::min);
+        clusters.push(DuplicateCluster {
+            kind: DuplicateKind::Near,
+            note_ids: members.iter().map(|&i| distinct[i].id.clone()).collect(),
+            similarity: lowest,
+        });
+    }
+    clusters
+}
+
+/// Id of a note's first revision, which orders notes by creation.
+fn created(note: &Note) -> &str {
+    note.edited_note_id.as_deref().unwrap_or(&note.id)
+}
+
+/// Reports clusters of duplicate notes in one yak, or across all yaks.
+#[tauri::command]
+pub async fn find_duplicates(
+    store: State<'_, Store>,
+    yak_id: Option<String>,
+) -> Result<Vec<DuplicateCluster>, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let mut projection = Projection::from_frames(&read_all_frames(&store).await).into_current();
+    projection.resolve_content(&store).await;
+    let notes: Vec<&Note> = match &yak_id {
+        Some(yak_id) => projection.current_notes(yak_id),
+        None => projection
+            .yaks
+            .keys()
+            .flat_map(|yak_id| projection.current_notes(yak_id))
+            .collect(),
+    };
+    Ok(find(&notes))
+}
+
+fn frame(topic: &str, hash: Option<ssri::Integrity>, meta: serde_json::Value) -> Frame {
+    Frame {
+        id: scru128::new(),
+        context_id: ZERO_CONTEXT,
+        topic: topic.to_string(),
+        hash,
+        meta: Some(meta),
+        ttl: None,
+    }
+}
+
+/// Folds duplicates into the note kept: its tags and attachments are added to the kept
+/// note, then the duplicates are deleted.
+async fn merge(
+    store: &Store,
+    note_ids: &[String],
+    keep: Option<&str>,
+) -> Result<Vec<Frame>, String> {
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    let notes: Vec<&Note> = note_ids
+        .iter()
+        .map(|id| {
+            projection
+                .notes
+                .get(&projection.resolve(id))
+                .ok_or_else(|| format!("Unknown note {id}"))
+        })
+        .collect::<Result<_, _>>()?;
+    let kept = match keep {
+        Some(keep) => {
+            let keep = projection.resolve(keep);
+            notes
+                .iter()
+                .find(|note| note.id == keep)
+                .ok_or("The note to keep must be one of the duplicates")?
+        }
+        None => notes
+            .iter()
+            .min_by(|a, b| created(a).cmp(created(b)))
+            .ok_or("No notes to merge")?,
+    };
+
+    let mut frames = Vec::new();
+    let mut tags: BTreeSet<&String> = kept.tags.iter().collect();
+    let attachments: HashSet<String> = kept
+        .attachments
+        .iter()
+        .filter_map(|attachment| attachment.hash.as_ref().map(|hash| hash.to_string()))
+        .collect();
+    for note in notes.iter().filter(|note| note.id != kept.id) {
+        for tag in &note.tags {
+            if tags.insert(tag) {
+                let meta =
+                    serde_json::json!({ "yak_id": kept.yak_id, "note_id": kept.id, "tag": tag });
+                frames.push(frame("tag.add", None, meta));
+            }
+        }
+        for attachment in &note.attachments {
+            let Some(hash) = &attachment.hash else {
+                continue;
+            };
+            if attachments.contains(&hash.to_string()) {
+                continue;
+            }
+            // Same content, re-attached by hash without copying
+            let mut meta = attachment
+                .meta
+                .clone()
+                .unwrap_or_else(|| serde_json::json!({}));
+            meta["yak_id"] = kept.yak_id.clone().into();
+            meta["note_id"] = kept.id.clone().into();
+            frames.push(frame("attachment.add", Some(hash.clone()), meta));
+        }
+        let meta = serde_json::json!({ "yak_id": note.yak_id, "note_id": note.id, "duplicate_of": kept.id });
+        frames.push(frame("note.delete", None, meta));
+    }
+
+    frames
+        .into_iter()
+        .map(|frame| {
+            crate::append_to_store(store, frame)
+                .map_err(|e| e.context("Failed to append frame").into())
+        })
+        .collect()
+}
+
+/// Merges a cluster reported by `find_duplicates`, keeping `keep` or else the oldest note.
+#[tauri::command]
+pub async fn merge_duplicates(
+    store: State<'_, Store>,
+    app: AppHandle,
+    note_ids: Vec<String>,
+    keep: Option<String>,
+) -> Result<(), String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let frames = merge(&store, &note_ids, keep.as_deref()).await?;
+    emit_frames(&app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_find_and_merge_duplicates() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let text = "the quick brown fox jumps over the lazy dog near the river bank today";
+        let mut ids = Vec::new();
+        for content in [
+            text.to_string(),
+            text.to_string(),
+            format!("# {text}!"),
+            "something else entirely".to_string(),
+        ] {
+            let note = append_frame(
+                &store,
+                "note.create",
+                Some(content.as_bytes()),
+                Some(json!({ "yak_id": yak_id })),
+            )
+            .await
+            .unwrap();
+            ids.push(note.id.to_string());
+        }
+        append_frame(
+            &store,
+            "tag.add",
+            None,
+            Some(json!({ "yak_id": yak_id, "note_id": ids[1], "tag": "evernote" })),
+        )
+        .await
+        .unwrap();
+
+        let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+        projection.resolve_content(&store).await;
+        let clusters = find(&projection.current_notes(&yak_id));
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].kind, DuplicateKind::Exact);
+        assert_eq!(clusters[0].note_ids, ids[..2]);
+        assert_eq!(clusters[1].kind, DuplicateKind::Near);
+        assert_eq!(clusters[1].note_ids, [ids[0].clone(), ids[2].clone()]);
+
+        merge(&store, &clusters[0].note_ids, None).await.unwrap();
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let notes = projection.current_notes(&yak_id);
+        assert_eq!(notes.len(), 3);
+        assert!(notes[0].tags.contains("evernote"));
+    }
+}
@@ -0,0 +1,322 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_opener::OpenerExt;
+use tokio::process::Child;
+use tokio::task::JoinHandle;
+use xs::store::Store;
+
+use crate::export::{created_id, note_title, unique_file_name};
+use crate::locks::LockState;
+use crate::projection::{Note, Projection};
+use crate::shutdown::begin_write;
+use crate::windows::emit_frame;
+use crate::{app_lock, append_frame, locks, read_all_frames};
+
+/// How often an open file is checked for saves.
+const POLL: Duration = Duration::from_secs(1);
+
+struct Session {
+    path: PathBuf,
+    watcher: JoinHandle<()>,
+}
+
+/// Notes open in an external editor, by the id of their first revision.
+#[derive(Default)]
+pub struct EditorState {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExternalEdit {
+    /// The note's first revision, which `stop_editing_externally` takes
+    pub note_id: String,
+    pub path: String,
+}
+
+/// The meta of a revision of `note_id` (any of its revisions) with `content`, or `None` when
+/// that's already the note's content.
+fn revision(
+    projection: &Projection,
+    note_id: &str,
+    content: &str,
+) -> Result<Option<Value>, String> {
+    let current_id = projection.resolve(note_id);
+    let note = projection
+        .notes
+        .get(&current_id)
+        .filter(|note| {
+            projection
+                .notes_by_yak
+                .get(&note.yak_id)
+                .is_some_and(|ids| ids.contains(&current_id))
+        })
+        .ok_or_else(|| format!("Note {note_id} no longer exists"))?;
+    if note.hash.as_ref() == Some(&ssri::Integrity::from(content.as_bytes())) {
+        return Ok(None);
+    }
+    Ok(Some(
+        json!({ "yak_id": note.yak_id, "note_id": current_id }),
+    ))
+}
+
+/// Appends the file's content as a revision of the note, if it changed.
+async fn commit(app: &AppHandle, store: &Store, note_id: &str, path: &Path) -> Result<(), String> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let _guard = begin_write(app)?;
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    if let Some(meta) = revision(&projection, note_id, &content)? {
+        let frame = append_frame(store, "note.edit", Some(content.as_bytes()), Some(meta)).await?;
+        let _ = emit_frame(app, &frame);
+    }
+    Ok(())
+}
+
+/// The current revision of the note `frame_id` is a revision of, with its content, and the
+/// id of its first revision. Notes of locked yaks are refused, since their content would be
+/// written to the temp dir in the clear.
+async fn load(store: &Store, locks: &LockState, frame_id: &str) -> Result<(String, Note), String> {
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    let mut note = projection
+        .notes
+        .get(&projection.resolve(frame_id))
+        .cloned()
+        .ok_or_else(|| format!("Note {frame_id} not found"))?;
+    if locks.is_locked(&note.yak_id) {
+        return Err("Notes in locked yaks can't be edited externally".to_string());
+    }
+    let content = match &note.hash {
+        Some(hash) => store
+            .cas_read(hash)
+            .await
+            .map_err(|e| format!("Failed to read note content: {e}"))?,
+        None => Vec::new(),
+    };
+    let content =
+        String::from_utf8(content).map_err(|_| "Only text notes can be edited".to_string())?;
+    if locks::is_sealed(&content) {
+        return Err("Notes in locked yaks can't be edited externally".to_string());
+    }
+    let content = match &note.hash {
+        Some(hash) => locks.open(&hash.to_string(), &content)?,
+        None => content,
+    };
+    note.content = Some(content);
+    Ok((created_id(&projection, &note), note))
+}
+
+/// Writes the note's content to a file of its own in the temp dir.
+async fn materialize(note_id: &str, note: &Note) -> Result<PathBuf, String> {
+    let dir = std::env::temp_dir().join("yaks-edit").join(note_id);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    let path = dir.join(unique_file_name(
+        &note_title(note),
+        ".md",
+        &mut HashSet::new(),
+    ));
+    tokio::fs::write(&path, note.content.as_deref().unwrap_or_default())
+        .await
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok(path)
+}
+
+/// Opens `path` with `$VISUAL` or `$EDITOR` when set (a GUI editor, or a command such as
+/// `code --wait`), else with the app the system opens Markdown with. Returns the editor's
+/// process when it was started here.
+fn open(app: &AppHandle, path: &Path) -> Result<Option<Child>, String> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty());
+    match editor {
+        Some(editor) => {
+            let mut words = editor.split_whitespace();
+            let program = words.next().unwrap_or_default();
+            tokio::process::Command::new(program)
+                .args(words)
+                .arg(path)
+                .spawn()
+                .map(Some)
+                .map_err(|e| format!("Failed to start {program}: {e}"))
+        }
+        None => app
+            .opener()
+            .open_path(path.to_string_lossy(), None::<&str>)
+            .map(|()| None)
+            .map_err(|e| format!("Failed to open {}: {e}", path.display())),
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).ok()?.modified().ok()
+}
+
+/// Commits each save of the file until the editor started for it exits, the file is
+/// removed or the note is deleted, then cleans up.
+async fn watch(
+    app: AppHandle,
+    store: Store,
+    note_id: String,
+    path: PathBuf,
+    mut editor: Option<Child>,
+) {
+    let mut seen = modified(&path);
+    loop {
+        tokio::time::sleep(POLL).await;
+        let exited = editor
+            .as_mut()
+            .is_some_and(|editor| !matches!(editor.try_wait(), Ok(None)));
+        let Some(changed) = modified(&path) else {
+            break;
+        };
+        if Some(changed) != seen {
+            seen = Some(changed);
+            if let Err(e) = commit(&app, &store, &note_id, &path).await {
+                eprintln!("Failed to save external edit: {e}");
+                if e.contains("no longer exists") {
+                    break;
+                }
+            }
+        }
+        if exited {
+            break;
+        }
+    }
+    app.state::<EditorState>()
+        .sessions
+        .lock()
+        .unwrap()
+        .remove(&note_id);
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
+
+/// Opens a note in the user's editor as a Markdown file and saves each write back as a new
+/// revision, for writing long notes in vim or VS Code. Editing stops when an editor started
+/// from `$VISUAL`/`$EDITOR` exits, or with `stop_editing_externally`.
+#[tauri::command]
+pub async fn edit_externally(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, EditorState>,
+    locks: State<'_, LockState>,
+    frame_id: String,
+) -> Result<ExternalEdit, String> {
+    app_lock::ensure_unlocked()?;
+    let (note_id, note) = load(&store, &locks, &frame_id).await?;
+    let open_path = state
+        .sessions
+        .lock()
+        .unwrap()
+        .get(&note_id)
+        .map(|session| session.path.clone());
+    // Already open: bring the same file up again rather than overwrite unsaved work
+    if let Some(open_path) = open_path {
+        open(&app, &open_path)?;
+        return Ok(ExternalEdit {
+            note_id,
+            path: open_path.display().to_string(),
+        });
+    }
+
+    let path = materialize(&note_id, &note).await?;
+    let editor = open(&app, &path)?;
+    let watcher = tokio::spawn(watch(
+        app.clone(),
+        store.inner().clone(),
+        note_id.clone(),
+        path.clone(),
+        editor,
+    ));
+    state.sessions.lock().unwrap().insert(
+        note_id.clone(),
+        Session {
+            path: path.clone(),
+            watcher,
+        },
+    );
+    Ok(ExternalEdit {
+        note_id,
+        path: path.display().to_string(),
+    })
+}
+
+/// Saves the file one last time and stops watching it.
+#[tauri::command]
+pub async fn stop_editing_externally(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, EditorState>,
+    note_id: String,
+) -> Result<(), String> {
+    let session = state.sessions.lock().unwrap().remove(&note_id);
+    let Some(session) = session else {
+        return Ok(());
+    };
+    session.watcher.abort();
+    let saved = commit(&app, &store, &note_id, &session.path).await;
+    if let Some(dir) = session.path.parent() {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+    saved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{add_note, create_yak};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_revision() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Drafts").await.unwrap();
+        let note = add_note(&store, &yak_id, "# Essay\nFirst draft", json!({}))
+            .await
+            .unwrap();
+        let note_id = note.id.to_string();
+        let edit = append_frame(
+            &store,
+            "note.edit",
+            Some(b"# Essay\nSecond draft".as_slice()),
+            Some(json!({ "yak_id": yak_id, "note_id": note_id })),
+        )
+        .await
+        .unwrap();
+
+        let (first, note) = load(&store, &LockState::default(), &edit.id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(first, note_id);
+        let path = materialize(&first, &note).await.unwrap();
+        assert!(path.ends_with("Essay.md"));
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written, "# Essay\nSecond draft");
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        // Saving without changes writes nothing; a change revises the latest revision
+        assert!(revision(&projection, &note_id, &written).unwrap().is_none());
+        let meta = revision(&projection, &note_id, "# Essay\nThird")
+            .unwrap()
+            .unwrap();
+        assert_eq!(meta["note_id"], edit.id.to_string());
+
+        let meta = json!({ "yak_id": yak_id, "note_id": edit.id.to_string() });
+        append_frame(&store, "note.delete", None, Some(meta))
+            .await
+            .unwrap();
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        assert!(revision(&projection, &note_id, "# Essay\nThird").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::settings::{latest_setting, save_setting};
+
+const CONFIG_TOPIC: &str = "emit.policy";
+/// Meta key on an emitted frame listing the fields left out of it.
+const OMITTED_KEY: &str = "omitted_meta";
+const DEFAULT_MAX_FIELD_BYTES: usize = 4 * 1024;
+/// How much of a long string a summary keeps.
+const PREVIEW_CHARS: usize = 200;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trim {
+    /// Leave the field out
+    Strip,
+    /// Put its size and, for text, how it starts in its place
+    #[default]
+    Summarize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmitPolicy {
+    pub enabled: bool,
+    /// Meta fields larger than this once serialized are trimmed from emitted frames
+    pub max_field_bytes: usize,
+    pub trim: Trim,
+}
+
+impl Default for EmitPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_field_bytes: DEFAULT_MAX_FIELD_BYTES,
+            trim: Trim::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EmissionState {
+    policy: Mutex<EmitPolicy>,
+}
+
+fn summary(value: &Value, bytes: usize) -> Value {
+    match value {
+        Value::String(text) if text.chars().count() > PREVIEW_CHARS => json!({
+            "bytes": bytes,
+            "preview": text.chars().take(PREVIEW_CHARS).collect::<String>(),
+        }),
+        Value::Array(items) => json!({ "bytes": bytes, "items": items.len() }),
+        Value::Object(fields) => json!({ "bytes": bytes, "keys": fields.len() }),
+        _ => json!({ "bytes": bytes }),
+    }
+}
+
+/// `frame` as windows are sent it: meta fields over the policy's size are stripped or
+/// summarized, and named in `omitted_meta` so the window knows to ask `get_frame_meta`.
+/// `None` when nothing needs trimming.
+pub(crate) fn shape(frame: &Frame, policy: &EmitPolicy) -> Option<Frame> {
+    if !policy.enabled {
+        return None;
+    }
+    let fields = frame.meta.as_ref()?.as_object()?;
+    let heavy: Vec<(&String, usize)> = fields
+        .iter()
+        .map(|(key, value)| (key, value.to_string().len()))
+        .filter(|(_, bytes)| *bytes > policy.max_field_bytes)
+        .collect();
+    if heavy.is_empty() {
+        return None;
+    }
+    let mut trimmed = fields.clone();
+    for (key, bytes) in &heavy {
+        match policy.trim {
+            Trim::Strip => {
+                trimmed.remove(*key);
+            }
+            Trim::Summarize => {
+                let value = summary(&fields[*key], *bytes);
+                trimmed.insert((*key).clone(), value);
+            }
+        }
+    }
+    let omitted: Vec<&String> = heavy.iter().map(|(key, _)| *key).collect();
+    trimmed.insert(OMITTED_KEY.to_string(), json!(omitted));
+    Some(Frame {
+        meta: Some(Value::Object(trimmed)),
+        ..frame.clone()
+    })
+}
+
+/// The policy emitted frames are shaped with.
+pub(crate) fn policy(app: &AppHandle) -> EmitPolicy {
+    match app.try_state::<EmissionState>() {
+        Some(state) => state.policy.lock().unwrap().clone(),
+        None => EmitPolicy::default(),
+    }
+}
+
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let policy: EmitPolicy = latest_setting(frames, CONFIG_TOPIC);
+    *app.state::<EmissionState>().policy.lock().unwrap() = policy;
+}
+
+#[tauri::command]
+pub async fn get_emit_policy(state: State<'_, EmissionState>) -> Result<EmitPolicy, String> {
+    Ok(state.policy.lock().unwrap().clone())
+}
+
+/// Sets how frames with large meta, such as enrichment or transcripts, are cut down before
+/// windows are sent them.
+#[tauri::command]
+pub async fn configure_emit_policy(
+    store: State<'_, Store>,
+    state: State<'_, EmissionState>,
+    policy: EmitPolicy,
+) -> Result<(), String> {
+    save_setting(&store, CONFIG_TOPIC, &policy)?;
+    *state.policy.lock().unwrap() = policy;
+    Ok(())
+}
+
+/// A frame's full meta, for a window sent it with fields omitted.
+#[tauri::command]
+pub async fn get_frame_meta(store: State<'_, Store>, id: String) -> Result<Option<Value>, String> {
+    let id = id
+        .parse::<scru128::Scru128Id>()
+        .map_err(|e| format!("Invalid frame id: {e}"))?;
+    let frame = store
+        .get(&id)
+        .ok_or_else(|| format!("Frame not found: {id}"))?;
+    Ok(frame.meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+
+    #[tokio::test]
+    async fn test_shape() {
+        let (_dir, store) = testing::store();
+        let transcript = "word ".repeat(2000);
+        let meta = json!({ "yak_id": "y", "transcript": transcript });
+        let frame = append(&store, "note.create", Some(meta));
+        let policy = EmitPolicy::default();
+        let shaped = shape(&frame, &policy).unwrap();
+        let meta = shaped.meta.as_ref().unwrap();
+        assert_eq!(meta["yak_id"], "y");
+        assert_eq!(meta["transcript"]["bytes"], transcript.len() + 2);
+        assert_eq!(meta["transcript"]["preview"], "word ".repeat(40));
+        assert_eq!(meta[OMITTED_KEY], json!(["transcript"]));
+
+        let strip = EmitPolicy {
+            trim: Trim::Strip,
+            ..policy.clone()
+        };
+        let shaped = shape(&frame, &strip).unwrap();
+        assert!(shaped.meta.as_ref().unwrap().get("transcript").is_none());
+
+        let small = append(&store, "note.create", Some(json!({ "yak_id": "y" })));
+        assert!(shape(&small, &policy).is_none());
+        let off = EmitPolicy {
+            enabled: false,
+            ..policy
+        };
+        assert!(shape(&frame, &off).is_none());
+    }
+}
@@ -0,0 +1,292 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::export::content_title;
+use crate::settings::{latest_setting, save_setting};
+use crate::{locks, stats, AppendRequest};
+
+const CONFIG_TOPIC: &str = "enrich.config";
+/// Topics whose content is enriched when appended.
+const TOPICS: &[&str] = &["note.create", "note.edit"];
+pub(crate) const WORD_COUNT: &str = "word_count";
+/// Only so many links are kept, for notes that are mostly a list of them.
+const MAX_URLS: usize = 50;
+
+fn enabled() -> bool {
+    true
+}
+
+/// Which enrichers run on appended notes. Each writes a meta field, unless the caller
+/// already set it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnrichConfig {
+    /// `word_count`: words with at least one letter or digit
+    #[serde(default = "enabled")]
+    pub word_count: bool,
+    /// `urls`: the web links in the text, in order
+    #[serde(default = "enabled")]
+    pub urls: bool,
+    /// `language`: a rough guess at the ISO 639-1 code, left out when unsure
+    #[serde(default = "enabled")]
+    pub language: bool,
+    /// `title`: the first line, without heading markers
+    #[serde(default = "enabled")]
+    pub title: bool,
+}
+
+impl Default for EnrichConfig {
+    fn default() -> Self {
+        Self {
+            word_count: true,
+            urls: true,
+            language: true,
+            title: true,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EnrichState {
+    config: Mutex<EnrichConfig>,
+}
+
+/// Common words that mark text as being in a language written with Latin letters.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "is", "of", "to", "in", "that", "it", "for", "with", "was", "on", "are",
+            "this", "you",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "und", "das", "ist", "nicht", "ein", "ich", "zu", "den", "mit", "sie",
+            "es", "auf", "für",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "est", "un", "une", "des", "que", "pas", "je", "pour", "dans",
+            "du", "il",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "la", "los", "las", "y", "es", "que", "de", "en", "un", "una", "por", "con",
+            "para", "no",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "di", "che", "è", "e", "la", "per", "un", "una", "non", "sono", "con", "gli",
+            "del", "della",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "a", "os", "as", "e", "é", "que", "de", "não", "um", "uma", "para", "com", "do",
+            "da",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "is", "van", "dat", "niet", "ik", "op", "te", "zijn", "met",
+            "voor", "je",
+        ],
+    ),
+];
+
+/// The language of a script only one language here is written in, if most letters use it.
+fn script_language(text: &str) -> Option<&'static str> {
+    let mut counts: HashMap<&'static str, usize> = HashMap::new();
+    let mut letters = 0;
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        letters += 1;
+        let language = match c {
+            '\u{3040}'..='\u{30ff}' => "ja",
+            '\u{ac00}'..='\u{d7af}' | '\u{1100}'..='\u{11ff}' => "ko",
+            '\u{4e00}'..='\u{9fff}' => "zh",
+            '\u{0370}'..='\u{03ff}' => "el",
+            '\u{0400}'..='\u{04ff}' => "ru",
+            '\u{0590}'..='\u{05ff}' => "he",
+            '\u{0600}'..='\u{06ff}' => "ar",
+            _ => continue,
+        };
+        *counts.entry(language).or_default() += 1;
+    }
+    // Japanese mixes kana with Chinese characters
+    if counts.get("ja").is_some_and(|&kana| kana * 5 >= letters) {
+        return Some("ja");
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .filter(|(_, count)| count * 2 > letters)
+        .map(|(language, _)| language)
+}
+
+fn detect_language(text: &str) -> Option<&'static str> {
+    if let Some(language) = script_language(text) {
+        return Some(language);
+    }
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.len() < 5 {
+        return None;
+    }
+    let mut scores: Vec<(&str, usize)> = STOPWORDS
+        .iter()
+        .map(|(language, stopwords)| {
+            let hits = words
+                .iter()
+                .filter(|word| stopwords.contains(&word.as_str()))
+                .count();
+            (*language, hits)
+        })
+        .collect();
+    scores.sort_by(|a, b| b.1.cmp(&a.1));
+    match scores.as_slice() {
+        [(language, best), (_, next), ..] if *best >= 2 && best > next => Some(language),
+        _ => None,
+    }
+}
+
+fn url_regex() -> &'static Regex {
+    static URL: OnceLock<Regex> = OnceLock::new();
+    URL.get_or_init(|| Regex::new(r#"https?://[^\s<>()\[\]"'`]+"#).unwrap())
+}
+
+fn extract_urls(text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for m in url_regex().find_iter(text) {
+        let url = m.as_str().trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        if !urls.iter().any(|seen| seen == url) {
+            urls.push(url.to_string());
+        }
+        if urls.len() == MAX_URLS {
+            break;
+        }
+    }
+    urls
+}
+
+/// The meta fields the enabled enrichers derive from `content`.
+fn derive(config: &EnrichConfig, content: &str) -> Vec<(&'static str, serde_json::Value)> {
+    let mut fields = Vec::new();
+    if config.word_count {
+        fields.push((WORD_COUNT, stats::count_words(content).into()));
+    }
+    if config.urls {
+        let urls = extract_urls(content);
+        if !urls.is_empty() {
+            fields.push(("urls", urls.into()));
+        }
+    }
+    if config.language {
+        // Links are mostly English-looking words whatever the note is written in
+        let text = url_regex().replace_all(content, " ");
+        if let Some(language) = detect_language(&text) {
+            fields.push(("language", language.into()));
+        }
+    }
+    if config.title && !content.trim().is_empty() {
+        fields.push(("title", content_title(content).into()));
+    }
+    fields
+}
+
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let config: EnrichConfig = latest_setting(frames, CONFIG_TOPIC);
+    *app.state::<EnrichState>().config.lock().unwrap() = config;
+}
+
+/// Adds what the enrichers derive from a note's content to its meta before it's appended,
+/// so projections and search can read it rather than work it out again. Sealed content is
+/// left alone, since its meta isn't encrypted.
+pub(crate) fn enrich(app: &AppHandle, request: &mut AppendRequest) {
+    if !TOPICS.contains(&request.topic.as_str())
+        || request.content.is_empty()
+        || locks::is_sealed(&request.content)
+    {
+        return;
+    }
+    let config = app.state::<EnrichState>().config.lock().unwrap().clone();
+    let meta = request.meta.get_or_insert_with(HashMap::new);
+    for (key, value) in derive(&config, &request.content) {
+        meta.entry(key.to_string()).or_insert(value);
+    }
+}
+
+#[tauri::command]
+pub async fn configure_enrichers(
+    store: State<'_, Store>,
+    state: State<'_, EnrichState>,
+    config: EnrichConfig,
+) -> Result<EnrichConfig, String> {
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    *state.config.lock().unwrap() = config.clone();
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(
+            detect_language("This is the plan for the week, and it is ambitious"),
+            Some("en")
+        );
+        assert_eq!(
+            detect_language("Das ist nicht der Plan, und ich habe keine Zeit"),
+            Some("de")
+        );
+        assert_eq!(detect_language("これは日本語の文です"), Some("ja"));
+        assert_eq!(detect_language("Привет, как дела?"), Some("ru"));
+        assert_eq!(detect_language("milk eggs"), None);
+    }
+
+    #[test]
+    fn test_derive() {
+        let content = "## Reading list\n\nSee https://example.com/a, and \
+                       (https://example.com/b). This is the one to start with: \
+                       https://example.com/a.";
+        let fields: HashMap<_, _> = derive(&EnrichConfig::default(), content)
+            .into_iter()
+            .collect();
+        assert_eq!(fields[WORD_COUNT], json!(14));
+        assert_eq!(fields["language"], json!("en"));
+        assert_eq!(
+            fields["urls"],
+            json!(["https://example.com/a", "https://example.com/b"])
+        );
+        assert_eq!(fields["title"], json!("Reading list"));
+
+        let config = EnrichConfig {
+            title: false,
+            urls: false,
+            ..EnrichConfig::default()
+        };
+        let keys: Vec<&str> = derive(&config, content)
+            .iter()
+            .map(|(key, _)| *key)
+            .collect();
+        assert_eq!(keys, vec![WORD_COUNT, "language"]);
+    }
+}
@@ -0,0 +1,218 @@
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tauri::State;
+use xs::store::{Frame, Store};
+
+use crate::crypto::to_hex;
+use crate::diagnostics::redact;
+use crate::provenance::{self, Provenance};
+use crate::read_all_frames;
+use crate::time::{self, TimeRange};
+
+const FORMAT: &str = "yaks-audit/1";
+/// The field each line's hash is kept in; it's left out of what gets hashed.
+const DIGEST: &str = "digest";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditReport {
+    pub path: String,
+    pub frames: usize,
+    /// The last line's digest. Kept somewhere else, it shows the whole file is unchanged.
+    pub head: String,
+}
+
+/// One frame in the log: when it was written, what it says, where it came from, and the
+/// content's integrity hash rather than the content itself.
+#[derive(Debug, Clone, Serialize)]
+struct Entry {
+    seq: usize,
+    frame_id: String,
+    written: Option<String>,
+    topic: String,
+    context_id: String,
+    content_hash: Option<String>,
+    meta: Option<Value>,
+    provenance: Provenance,
+}
+
+/// The SHA-256 of the previous line's digest followed by `line` without its own digest.
+fn chain(previous: &str, line: &Value) -> String {
+    let mut line = line.clone();
+    if let Some(fields) = line.as_object_mut() {
+        fields.remove(DIGEST);
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(previous.as_bytes());
+    hasher.update(line.to_string().as_bytes());
+    to_hex(&hasher.finalize())
+}
+
+/// The frame's log entry, with credentials such as webhook secrets redacted from its meta.
+fn entry(seq: usize, frame: &Frame) -> Entry {
+    let id = frame.id.to_string();
+    let mut meta = frame.meta.clone();
+    if let Some(meta) = meta.as_mut() {
+        redact(meta);
+    }
+    Entry {
+        seq,
+        written: time::from_id(&id).map(|at| at.to_rfc3339()),
+        frame_id: id,
+        topic: frame.topic.clone(),
+        context_id: frame.context_id.to_string(),
+        content_hash: frame.hash.as_ref().map(|hash| hash.to_string()),
+        meta,
+        provenance: provenance::of(frame),
+    }
+}
+
+/// Writes a header line and then a line per frame in `range`, each carrying a digest that
+/// covers it and every line before it, so changing, dropping or reordering any line breaks
+/// the chain from there on.
+fn write(path: &Path, frames: &[Frame], range: &TimeRange) -> Result<(usize, String), String> {
+    let file = std::fs::File::create(path)
+        .map_err(|e| format!("Failed to create {}: {e}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    let mut write_line = |line: &mut Value, previous: &str| -> Result<String, String> {
+        let digest = chain(previous, line);
+        line[DIGEST] = json!(digest);
+        writeln!(out, "{line}").map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        Ok(digest)
+    };
+
+    let mut header = json!({
+        "format": FORMAT,
+        "exported_at": Utc::now().to_rfc3339(),
+        "range": range,
+    });
+    let mut head = write_line(&mut header, "")?;
+    let mut count = 0;
+    for frame in frames {
+        let in_range = time::from_id(&frame.id.to_string()).map_or(true, |at| range.contains(at));
+        if !in_range {
+            continue;
+        }
+        count += 1;
+        let mut line = serde_json::to_value(entry(count, frame))
+            .map_err(|e| format!("Failed to serialize frame {}: {e}", frame.id))?;
+        head = write_line(&mut line, &head)?;
+    }
+    out.flush()
+        .and_then(|_| out.get_ref().sync_all())
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok((count, head))
+}
+
+/// Checks every line's digest in an exported log, returning the number of frames and the
+/// head digest, or the first line that doesn't match.
+fn verify(path: &Path) -> Result<(usize, String), String> {
+    let file =
+        std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+    let mut head = String::new();
+    let mut lines = 0;
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let value: Value = serde_json::from_str(&line)
+            .map_err(|e| format!("Line {} isn't valid JSON: {e}", number + 1))?;
+        let recorded = value.get(DIGEST).and_then(|digest| digest.as_str());
+        let expected = chain(&head, &value);
+        if recorded != Some(expected.as_str()) {
+            return Err(format!("Line {} doesn't match the chain", number + 1));
+        }
+        if number == 0 && value.get("format").and_then(|f| f.as_str()) != Some(FORMAT) {
+            return Err(format!("Not a {FORMAT} log"));
+        }
+        head = expected;
+        lines += 1;
+    }
+    if lines == 0 {
+        return Err("The log is empty".to_string());
+    }
+    Ok((lines - 1, head))
+}
+
+/// Exports every frame written in `range`, or all of them, as a hash-chained JSON Lines
+/// log with each frame's provenance, for showing when notes were created and changed.
+#[tauri::command]
+pub async fn export_audit_log(
+    store: State<'_, Store>,
+    range: Option<TimeRange>,
+    path: String,
+) -> Result<AuditReport, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let frames = read_all_frames(&store).await;
+    let range = range.unwrap_or_default();
+    let target = PathBuf::from(&path);
+    let partial = target.with_extension("partial");
+    let (frames, head) = tokio::task::spawn_blocking(move || {
+        let written = write(&partial, &frames, &range).inspect_err(|_| {
+            let _ = std::fs::remove_file(&partial);
+        })?;
+        std::fs::rename(&partial, &target)
+            .map_err(|e| format!("Failed to write {}: {e}", target.display()))?;
+        Ok::<_, String>(written)
+    })
+    .await
+    .map_err(|e| format!("Failed to export audit log: {e}"))??;
+    Ok(AuditReport { path, frames, head })
+}
+
+/// Checks an exported audit log hasn't been altered since it was written.
+#[tauri::command]
+pub async fn verify_audit_log(path: String) -> Result<AuditReport, String> {
+    let checked = path.clone();
+    let (frames, head) = tokio::task::spawn_blocking(move || verify(Path::new(&checked)))
+        .await
+        .map_err(|e| format!("Failed to verify audit log: {e}"))??;
+    Ok(AuditReport { path, frames, head })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_chain() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().join("store"));
+        for name in ["Garden", "Kitchen"] {
+            append_frame(&store, "yak.create", None, Some(json!({ "name": name })))
+                .await
+                .unwrap();
+        }
+        let frames = read_all_frames(&store).await;
+
+        let path = dir.path().join("audit.jsonl");
+        let (count, head) = write(&path, &frames, &TimeRange::default()).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(verify(&path).unwrap(), (2, head));
+
+        // Credentials in settings don't make it into the log
+        let meta = json!({ "host": "imap.example.com", "password": "hunter2" });
+        let setting = crate::testing::append(&store, "capture.mail", Some(meta));
+        assert_eq!(entry(3, &setting).meta.unwrap()["password"], "[redacted]");
+
+        // Nothing after the range ends
+        let range = TimeRange {
+            from: None,
+            to: time::from_id(&frames[0].id.to_string()),
+        };
+        let (count, _) = write(&dir.path().join("empty.jsonl"), &frames, &range).unwrap();
+        assert_eq!(count, 0);
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, log.replace("Kitchen", "Pantry")).unwrap();
+        assert_eq!(verify(&path).unwrap_err(), "Line 3 doesn't match the chain");
+
+        let mut lines: Vec<_> = log.lines().collect();
+        lines.remove(1);
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        assert_eq!(verify(&path).unwrap_err(), "Line 2 doesn't match the chain");
+    }
+}
@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use xs::store::Store;
+
+use super::html::render_markdown;
+use super::{created_id, note_title};
+use crate::clipboard::ClipboardState;
+use crate::html::to_text;
+use crate::locks::LockState;
+use crate::projection::{Note, Projection};
+use crate::read_all_frames;
+use crate::settings::{load_setting, save_setting};
+
+const SECURE_TOPIC: &str = "copy.secure";
+
+fn default_clear_after() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecureCopyConfig {
+    /// How long `copy_secure` leaves a note on the clipboard
+    #[serde(default = "default_clear_after")]
+    pub clear_after_seconds: u64,
+}
+
+impl Default for SecureCopyConfig {
+    fn default() -> Self {
+        Self {
+            clear_after_seconds: default_clear_after(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CopyFormat {
+    Markdown,
+    /// Markdown rendered and stripped of its markup
+    #[serde(alias = "plain")]
+    Text,
+    /// Rendered markdown, with plain text beside it for apps that don't take HTML
+    Html,
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+struct CopiedNote<'a> {
+    id: &'a str,
+    created_id: String,
+    yak_id: &'a str,
+    title: String,
+    content: &'a str,
+    tags: Vec<&'a str>,
+}
+
+/// The clipboard text for `notes` in `format`, and HTML too for `CopyFormat::Html`.
+fn compose(
+    projection: &Projection,
+    notes: &[&Note],
+    format: CopyFormat,
+) -> Result<(String, Option<String>), String> {
+    let content = |note: &&Note| note.content.clone().unwrap_or_default();
+    let plain = || {
+        notes
+            .iter()
+            .map(|note| to_text(&render_markdown(&content(note))).trim().to_string())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+    Ok(match format {
+        CopyFormat::Markdown => {
+            let markdown: Vec<String> = notes.iter().map(content).collect();
+            (markdown.join("\n\n---\n\n"), None)
+        }
+        CopyFormat::Text => (plain(), None),
+        CopyFormat::Html => {
+            let html: Vec<String> = notes
+                .iter()
+                .map(|note| render_markdown(&content(note)))
+                .collect();
+            (plain(), Some(html.join("<hr>\n")))
+        }
+        CopyFormat::Json => {
+            let copied: Vec<CopiedNote> = notes
+                .iter()
+                .map(|note| CopiedNote {
+                    id: &note.id,
+                    created_id: created_id(projection, note),
+                    yak_id: &note.yak_id,
+                    title: note_title(note),
+                    content: note.content.as_deref().unwrap_or_default(),
+                    tags: note.tags.iter().map(String::as_str).collect(),
+                })
+                .collect();
+            let json = serde_json::to_string_pretty(&copied)
+                .map_err(|e| format!("Failed to serialize notes: {e}"))?;
+            (json, None)
+        }
+    })
+}
+
+fn write_clipboard(text: String, html: Option<String>) -> Result<(), String> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| format!("Failed to open clipboard: {e}"))?;
+    match html {
+        Some(html) => clipboard.set_html(html, Some(text)),
+        None => clipboard.set_text(text),
+    }
+    .map_err(|e| format!("Failed to write clipboard: {e}"))
+}
+
+/// Empties the clipboard if it still holds `text`, so anything copied since is left alone.
+fn clear_clipboard(text: &str) -> Result<(), String> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| format!("Failed to open clipboard: {e}"))?;
+    if clipboard.get_text().ok().as_deref() == Some(text) {
+        clipboard
+            .clear()
+            .map_err(|e| format!("Failed to clear clipboard: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Copies notes to the clipboard in `format`, in the order given. Ids may name any revision
+/// of a note; its current revision is copied. Returns how many notes were copied.
+#[tauri::command]
+pub async fn copy_notes(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    frame_ids: Vec<String>,
+    format: CopyFormat,
+) -> Result<usize, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+    projection.resolve_content(&store).await;
+    locks.redact(&mut projection, false);
+
+    let mut notes = Vec::new();
+    for id in &frame_ids {
+        let note = projection
+            .notes
+            .get(&projection.resolve(id))
+            .ok_or_else(|| format!("Note not found: {id}"))?;
+        if note.content.is_none() {
+            return Err(format!("Note {id} is locked"));
+        }
+        notes.push(note);
+    }
+    let (text, html) = compose(&projection, &notes, format)?;
+    tokio::task::spawn_blocking(move || write_clipboard(text, html))
+        .await
+        .map_err(|e| format!("Failed to write clipboard: {e}"))??;
+    Ok(notes.len())
+}
+
+/// Copies a note's content to the clipboard for a while, for codes and passwords: it's
+/// cleared after `clear_after_seconds` unless something else was copied meanwhile, and
+/// clipboard capture doesn't record it. Returns the seconds until it's cleared.
+#[tauri::command]
+pub async fn copy_secure(
+    app: AppHandle,
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    frame_id: String,
+) -> Result<u64, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+    projection.resolve_content(&store).await;
+    locks.redact(&mut projection, false);
+    let note = projection
+        .notes
+        .get(&projection.resolve(&frame_id))
+        .ok_or_else(|| format!("Note not found: {frame_id}"))?;
+    let text = note
+        .content
+        .as_deref()
+        .ok_or_else(|| format!("Note {frame_id} is locked"))?
+        .trim()
+        .to_string();
+    let config: SecureCopyConfig = load_setting(&store, SECURE_TOPIC).await;
+
+    // Withheld before it's copied, so capture can't catch it in between
+    app.state::<ClipboardState>().withhold(text.clone()).await;
+    let copied = text.clone();
+    tokio::task::spawn_blocking(move || write_clipboard(copied, None))
+        .await
+        .map_err(|e| format!("Failed to write clipboard: {e}"))??;
+
+    let seconds = config.clear_after_seconds;
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(seconds)).await;
+        let copied = text.clone();
+        match tokio::task::spawn_blocking(move || clear_clipboard(&copied)).await {
+            Ok(Err(e)) => eprintln!("{e}"),
+            Err(e) => eprintln!("Failed to clear clipboard: {e}"),
+            Ok(Ok(())) => {}
+        }
+        app.state::<ClipboardState>().release(&text).await;
+    });
+    Ok(seconds)
+}
+
+#[tauri::command]
+pub async fn configure_secure_copy(
+    store: State<'_, Store>,
+    config: SecureCopyConfig,
+) -> Result<SecureCopyConfig, String> {
+    if config.clear_after_seconds == 0 {
+        return Err("The clipboard has to keep the note for at least a second".to_string());
+    }
+    save_setting(&store, SECURE_TOPIC, &config)?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_compose() {
+        let (_dir, store) = testing::store();
+        let yak = append(&store, "yak.create", None);
+        let yak_id = yak.id.to_string();
+        let first = append(&store, "note.create", Some(json!({ "yak_id": yak_id })));
+        let second = append(&store, "note.create", Some(json!({ "yak_id": yak_id })));
+        let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+        for (frame, content) in [(&first, "# Cats\n\n*purr*"), (&second, "Dogs")] {
+            let note = projection.notes.get_mut(&frame.id.to_string()).unwrap();
+            note.content = Some(content.to_string());
+        }
+        let notes = vec![
+            &projection.notes[&first.id.to_string()],
+            &projection.notes[&second.id.to_string()],
+        ];
+
+        let (markdown, html) = compose(&projection, &notes, CopyFormat::Markdown).unwrap();
+        assert_eq!(markdown, "# Cats\n\n*purr*\n\n---\n\nDogs");
+        assert!(html.is_none());
+
+        let (text, _) = compose(&projection, &notes, CopyFormat::Text).unwrap();
+        assert!(!text.contains('#') && !text.contains('*'));
+        assert!(text.contains("purr") && text.ends_with("Dogs"));
+
+        let (alt, html) = compose(&projection, &notes, CopyFormat::Html).unwrap();
+        assert_eq!(alt, text);
+        assert!(html.unwrap().contains("<h1>Cats</h1>"));
+
+        let (json, _) = compose(&projection, &notes, CopyFormat::Json).unwrap();
+        let copied: Vec<Value> = serde_json::from_str(&json).unwrap();
+        assert_eq!(copied[0]["title"], "Cats");
+        assert_eq!(copied[1]["content"], "Dogs");
+    }
+}
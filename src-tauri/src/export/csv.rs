@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::path::Path;
+use tauri::State;
+use xs::store::{Frame, Store};
+
+use crate::locks::LockState;
+use crate::projection::{Projection, Task};
+use crate::{read_all_frames, time};
+
+const COLUMNS: &[&str] = &[
+    "title",
+    "state",
+    "due",
+    "tags",
+    "yak",
+    "created",
+    "completed",
+];
+
+/// When each task that's done now was last checked off, by task id.
+fn completed_at(frames: &[Frame]) -> HashMap<String, String> {
+    let mut completed = HashMap::new();
+    for frame in frames.iter().filter(|frame| frame.topic == "task.update") {
+        let Some(meta) = frame.meta.as_ref() else {
+            continue;
+        };
+        let (Some(task_id), Some(done)) = (
+            meta.get("task_id").and_then(|id| id.as_str()),
+            meta.get("done").and_then(|done| done.as_bool()),
+        ) else {
+            continue;
+        };
+        match time::from_id(&frame.id.to_string()).filter(|_| done) {
+            Some(at) => completed.insert(task_id.to_string(), at.to_rfc3339()),
+            None => completed.remove(task_id),
+        };
+    }
+    completed
+}
+
+/// Quotes a field that holds the separator, a quote or a line break.
+fn field(value: &str, separator: char) -> String {
+    if value.contains([separator, '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn row(projection: &Projection, completed: &HashMap<String, String>, task: &Task) -> Vec<String> {
+    let title = task
+        .content
+        .as_deref()
+        .and_then(|content| content.lines().map(str::trim).find(|line| !line.is_empty()))
+        .unwrap_or_default();
+    // Tasks take the tags of the note they belong to
+    let tags = task
+        .note_id
+        .as_ref()
+        .and_then(|note_id| projection.notes.get(&projection.resolve(note_id)))
+        .map(|note| note.tags.iter().cloned().collect::<Vec<_>>().join(", "))
+        .unwrap_or_default();
+    let yak = projection
+        .yaks
+        .get(&task.yak_id)
+        .and_then(|yak| yak.name.clone())
+        .unwrap_or_default();
+    let created = time::from_id(&task.id)
+        .map(|at| at.to_rfc3339())
+        .unwrap_or_default();
+    vec![
+        title.to_string(),
+        if task.done { "done" } else { "open" }.to_string(),
+        task.reminder.clone().unwrap_or_default(),
+        tags,
+        yak,
+        created,
+        completed
+            .get(&task.id)
+            .filter(|_| task.done)
+            .cloned()
+            .unwrap_or_default(),
+    ]
+}
+
+/// The tasks of one yak, or of every yak, as CSV (or TSV with `\t`), oldest first.
+fn render(
+    projection: &Projection,
+    frames: &[Frame],
+    yak_id: Option<&str>,
+    separator: char,
+) -> (String, usize) {
+    let completed = completed_at(frames);
+    let mut tasks: Vec<&Task> = projection
+        .tasks
+        .values()
+        .filter(|task| yak_id.map_or(true, |yak_id| task.yak_id == yak_id))
+        .collect();
+    tasks.sort_by(|a, b| a.id.cmp(&b.id));
+    let lines = std::iter::once(
+        COLUMNS
+            .iter()
+            .map(|column| column.to_string())
+            .collect::<Vec<_>>(),
+    )
+    .chain(tasks.iter().map(|task| row(projection, &completed, task)))
+    .map(|fields| {
+        let fields: Vec<String> = fields.iter().map(|f| field(f, separator)).collect();
+        fields.join(&separator.to_string()) + "\r\n"
+    });
+    (lines.collect(), tasks.len())
+}
+
+/// Writes tasks to a spreadsheet file with their state, due date, note tags and when they
+/// were created and completed: TSV if `path` ends in `.tsv`, else CSV. Covers every yak
+/// unless `yak_id` is given. Returns how many tasks were written.
+#[tauri::command]
+pub async fn export_tasks_csv(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    yak_id: Option<String>,
+    path: String,
+) -> Result<usize, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let frames = read_all_frames(&store).await;
+    let mut projection = Projection::from_frames(&frames);
+    if let Some(yak_id) = &yak_id {
+        if !projection.yaks.contains_key(yak_id) {
+            return Err(format!("Yak not found: {yak_id}"));
+        }
+    }
+    projection.resolve_content(&store).await;
+    locks.redact(&mut projection, false);
+    let tsv = Path::new(&path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tsv"));
+    let separator = if tsv { '\t' } else { ',' };
+    let (table, count) = render(&projection, &frames, yak_id.as_deref(), separator);
+    tokio::fs::write(&path, table)
+        .await
+        .map_err(|e| format!("Failed to write {path}: {e}"))?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use crate::import::{add_note, add_tag, add_task, create_yak};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_field() {
+        assert_eq!(field("plain", ','), "plain");
+        assert_eq!(field("milk, eggs", ','), "\"milk, eggs\"");
+        assert_eq!(field("milk, eggs", '\t'), "milk, eggs");
+        assert_eq!(field("say \"hi\"", ','), "\"say \"\"hi\"\"\"");
+    }
+
+    #[tokio::test]
+    async fn test_render() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Work").await.unwrap();
+        let other = create_yak(&store, "Home").await.unwrap();
+        let note = add_note(&store, &yak_id, "Launch", serde_json::json!({}))
+            .await
+            .unwrap();
+        let note_id = note.id.to_string();
+        add_tag(&store, &yak_id, &note_id, "q3").await.unwrap();
+        let task = add_task(
+            &store,
+            &yak_id,
+            Some(&note_id),
+            "Ship, then celebrate",
+            false,
+        )
+        .await
+        .unwrap();
+        add_task(&store, &yak_id, None, "Write notes", false)
+            .await
+            .unwrap();
+        add_task(&store, &other, None, "Water plants", false)
+            .await
+            .unwrap();
+        let meta = serde_json::json!({ "task_id": task.id.to_string(), "done": true });
+        let done = append_frame(&store, "task.update", None, Some(meta))
+            .await
+            .unwrap();
+
+        let frames = read_all_frames(&store).await;
+        let mut projection = Projection::from_frames(&frames);
+        projection.resolve_content(&store).await;
+        let (csv, count) = render(&projection, &frames, Some(&yak_id), ',');
+        assert_eq!(count, 2);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "title,state,due,tags,yak,created,completed");
+        let created = time::from_id(&task.id.to_string()).unwrap().to_rfc3339();
+        let completed = time::from_id(&done.id.to_string()).unwrap().to_rfc3339();
+        assert_eq!(
+            lines[1],
+            format!("\"Ship, then celebrate\",done,,q3,Work,{created},{completed}")
+        );
+        assert!(lines[2].starts_with("Write notes,open,,,Work,"));
+
+        let (_, count) = render(&projection, &frames, None, '\t');
+        assert_eq!(count, 3);
+    }
+}
@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use xs::store::{FollowOption, ReadOptions, Store};
+
+use super::markdown::export_yak;
+use crate::read_all_frames;
+use crate::settings::{load_setting, save_setting};
+use crate::windows::frame_yak_id;
+
+const CONFIG_TOPIC: &str = "export.git";
+/// Edits arriving together become one commit.
+const SETTLE: Duration = Duration::from_secs(5);
+/// Trailer recording the last frame a commit covers, where the next one picks up.
+const TRAILER: &str = "Yaks-Frame:";
+/// Frame ids listed in one commit message, at most.
+const LISTED: usize = 50;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GitMirrorConfig {
+    /// yak id -> the repository its notes are written to
+    pub mirrors: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MirrorReport {
+    pub notes: usize,
+    /// The commit made, if anything changed
+    pub commit: Option<String>,
+}
+
+async fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let command = args
+            .iter()
+            .find(|arg| !arg.starts_with('-') && !arg.contains('='));
+        let command = command.copied().unwrap_or("command");
+        return Err(format!("git {command} failed: {}", stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Removes what the last mirror wrote, leaving `.git` and anything else hidden alone, so
+/// notes deleted or renamed since don't linger.
+fn clear(dir: &Path) -> Result<(), String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {e}", dir.display()))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let removed = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        removed.map_err(|e| format!("Failed to remove {name}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// The frame the last mirror commit covered, from its trailer.
+async fn last_mirrored(dir: &Path) -> Option<String> {
+    let message = git(dir, &["log", "-1", "--format=%B"]).await.ok()?;
+    message
+        .lines()
+        .find_map(|line| line.strip_prefix(TRAILER))
+        .map(|id| id.trim().to_string())
+}
+
+/// Writes the yak's notes into the repository at `dir` and commits if anything changed,
+/// listing the yak's frames since the last mirror commit in the message.
+async fn mirror(store: &Store, yak_id: &str, dir: &Path) -> Result<MirrorReport, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    // The repository belongs to the mirror, which rewrites it on every change
+    if !dir.join(".git").exists() {
+        let empty = std::fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none());
+        if !empty {
+            return Err(format!(
+                "{} isn't empty; choose an empty folder",
+                dir.display()
+            ));
+        }
+        git(dir, &["init", "--quiet"]).await?;
+    }
+    clear(dir)?;
+    // Without this session's keys, notes of locked yaks are left blank
+    let exported = export_yak(store, &LockState::default(), yak_id, dir).await?;
+    // An empty assets folder isn't tracked anyway
+    let _ = std::fs::remove_dir(dir.join("assets"));
+    let mut report = MirrorReport {
+        notes: exported.notes,
+        commit: None,
+    };
+    git(dir, &["add", "--all"]).await?;
+    if git(dir, &["status", "--porcelain"]).await?.is_empty() {
+        return Ok(report);
+    }
+
+    let after = last_mirrored(dir).await;
+    let frames: Vec<_> = read_all_frames(store)
+        .await
+        .into_iter()
+        .filter(|frame| {
+            after
+                .as_ref()
+                .map_or(true, |after| frame.id.to_string() > *after)
+        })
+        .filter(|frame| frame_yak_id(frame).as_deref() == Some(yak_id))
+        .collect();
+    let mut message = match frames.len() {
+        1 => "Mirror 1 change".to_string(),
+        n => format!("Mirror {n} changes"),
+    };
+    message.push_str("\n\n");
+    for frame in frames.iter().rev().take(LISTED).rev() {
+        message.push_str(&format!("{} {}\n", frame.topic, frame.id));
+    }
+    if frames.len() > LISTED {
+        message.push_str(&format!("and {} earlier\n", frames.len() - LISTED));
+    }
+    if let Some(last) = frames.last() {
+        message.push_str(&format!("\n{TRAILER} {}\n", last.id));
+    }
+
+    // Commits in repositories without an identity of their own are made as Yaks
+    let mut args = Vec::new();
+    if git(dir, &["config", "user.email"]).await.is_err() {
+        args.extend(["-c", "user.name=Yaks", "-c", "user.email=yaks@localhost"]);
+    }
+    args.extend(["commit", "--quiet", "--message", message.as_str()]);
+    git(dir, &args).await?;
+    report.commit = Some(git(dir, &["rev-parse", "HEAD"]).await?);
+    Ok(report)
+}
+
+fn affects_mirror(topic: &str) -> bool {
+    ["note.", "tag.", "attachment.", "yak."]
+        .iter()
+        .any(|prefix| topic.starts_with(prefix))
+}
+
+/// Keeps each mirrored yak's repository current, committing as notes change.
+pub(crate) async fn watch(_app: AppHandle, store: Store) {
+    let (kick, mut kicked) = mpsc::channel::<()>(1);
+    let follower_store = store.clone();
+    tokio::spawn(async move {
+        let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+        let mut rx = follower_store.read(read_options).await;
+        while let Some(frame) = rx.recv().await {
+            // Catching up also commits edits made while the app was closed
+            if frame.topic == "xs.threshold"
+                || affects_mirror(&frame.topic)
+                || frame.topic == CONFIG_TOPIC
+            {
+                let _ = kick.try_send(());
+            }
+        }
+    });
+
+    while kicked.recv().await.is_some() {
+        tokio::time::sleep(SETTLE).await;
+        let _ = kicked.try_recv();
+        let config: GitMirrorConfig = load_setting(&store, CONFIG_TOPIC).await;
+        for (yak_id, dir) in &config.mirrors {
+            if let Err(e) = mirror(&store, yak_id, Path::new(dir)).await {
+                eprintln!("Failed to mirror yak {yak_id} to {dir}: {e}");
+            }
+        }
+    }
+}
+
+/// Mirrors a yak into a local git repository at `dir`, which must be empty or an earlier
+/// mirror: its notes are written there as markdown and committed on every change, with the
+/// frame ids behind each commit in its message. Without `dir`, the yak stops being
+/// mirrored; the repository is left as it is.
+#[tauri::command]
+pub async fn configure_git_mirror(
+    store: State<'_, Store>,
+    yak_id: String,
+    dir: Option<String>,
+) -> Result<MirrorReport, String> {
+    let mut config: GitMirrorConfig = load_setting(&store, CONFIG_TOPIC).await;
+    let report = match dir.filter(|dir| !dir.trim().is_empty()) {
+        Some(dir) => {
+            let report = mirror(&store, &yak_id, Path::new(&dir)).await?;
+            config.mirrors.insert(yak_id, dir);
+            report
+        }
+        None => {
+            config.mirrors.remove(&yak_id);
+            MirrorReport::default()
+        }
+    };
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn get_git_mirrors(store: State<'_, Store>) -> Result<GitMirrorConfig, String> {
+    Ok(load_setting(&store, CONFIG_TOPIC).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use crate::import::{add_note, create_yak};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_mirror() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().join("store"));
+        let repo = dir.path().join("mirror");
+        let yak_id = create_yak(&store, "Garden").await.unwrap();
+        let note = add_note(&store, &yak_id, "# Beds\nDig", json!({}))
+            .await
+            .unwrap();
+
+        let report = mirror(&store, &yak_id, &repo).await.unwrap();
+        assert_eq!(report.notes, 1);
+        assert!(report.commit.is_some());
+        let message = git(&repo, &["log", "-1", "--format=%B"]).await.unwrap();
+        assert!(message.contains(&format!("note.create {}", note.id)));
+        assert_eq!(last_mirrored(&repo).await, Some(note.id.to_string()));
+
+        // Nothing changed, nothing to commit
+        let report = mirror(&store, &yak_id, &repo).await.unwrap();
+        assert!(report.commit.is_none());
+
+        let meta = json!({ "yak_id": yak_id, "note_id": note.id.to_string() });
+        let edit = append_frame(
+            &store,
+            "note.edit",
+            Some(b"# Plots\nDig".as_slice()),
+            Some(meta),
+        )
+        .await
+        .unwrap();
+        let report = mirror(&store, &yak_id, &repo).await.unwrap();
+        assert!(report.commit.is_some());
+        assert!(repo.join("Plots.md").exists());
+        assert!(!repo.join("Beds.md").exists());
+        let message = git(&repo, &["log", "-1", "--format=%B"]).await.unwrap();
+        assert!(message.starts_with("Mirror 1 change"));
+        assert!(message.contains(&format!("note.edit {}", edit.id)));
+        assert!(!message.contains("note.create"));
+    }
+}
@@ -0,0 +1,252 @@
+use base64::Engine;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::OnceLock;
+use tauri::State;
+use xs::store::Store;
+
+use super::{load_yak, note_title, unique_file_name, ExportReport};
+use crate::html::escape;
+use crate::locks::LockState;
+use crate::projection::Note;
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:46rem;margin:2rem auto;\
+padding:0 1rem;line-height:1.6;color:#222}a{color:#0b62c4}img{max-width:100%}\
+pre{background:#f4f4f4;padding:.75rem;overflow:auto}nav{margin-bottom:2rem}\
+.tags span{background:#eee;border-radius:3px;padding:0 .4rem;margin-right:.3rem}";
+
+/// Renders markdown to HTML and sanitizes the result, allowing inlined `data:` attachments.
+pub(crate) fn render_markdown(markdown: &str) -> String {
+    use pulldown_cmark::{html, Options, Parser};
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options));
+
+    let mut sanitizer = ammonia::Builder::default();
+    sanitizer.add_url_schemes(&["data"]);
+    sanitizer.clean(&unsafe_html).to_string()
+}
+
+pub(crate) fn page(title: &str, nav: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+         <title>{}</title><style>{STYLE}</style></head>\
+         <body>{nav}<main>{body}</main></body></html>\n",
+        escape(title)
+    )
+}
+
+/// Rewrites `[[target|label]]` wiki links, where the target is a note id or title, into
+/// markdown links to the page for that note.
+pub(crate) fn link_notes(markdown: &str, pages: &HashMap<String, String>) -> String {
+    static WIKI: OnceLock<Regex> = OnceLock::new();
+    let wiki = WIKI.get_or_init(|| Regex::new(r"(!?)\[\[([^\]|#]+)(?:[|#]([^\]]*))?\]\]").unwrap());
+    wiki.replace_all(markdown, |caps: &regex::Captures| {
+        let target = caps[2].trim();
+        match (caps[1].is_empty(), pages.get(&target.to_lowercase())) {
+            (true, Some(page)) => {
+                let label = caps.get(3).map_or(target, |label| label.as_str());
+                format!("[{label}]({page})")
+            }
+            _ => caps[0].to_string(),
+        }
+    })
+    .into_owned()
+}
+
+fn data_uri(mime: &str, bytes: &[u8]) -> String {
+    format!(
+        "data:{mime};base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    )
+}
+
+/// Replaces references to a note's attachments with inlined `data:` URIs, and lists any the
+/// content doesn't reference at the end.
+pub(crate) async fn inline_attachments(
+    store: &Store,
+    note: &Note,
+    mut markdown: String,
+) -> Result<String, String> {
+    for attachment in &note.attachments {
+        let Some(hash) = &attachment.hash else {
+            continue;
+        };
+        let bytes = store
+            .cas_read(hash)
+            .await
+            .map_err(|e| format!("Failed to read attachment {}: {e}", attachment.id))?;
+        let mime = attachment
+            .mime
+            .as_deref()
+            .unwrap_or("application/octet-stream");
+        let uri = data_uri(mime, &bytes);
+        let name = attachment.name.as_deref().unwrap_or("attachment");
+        let link = attachment
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.get("link"))
+            .and_then(|link| link.as_str());
+
+        match link {
+            Some(link) if markdown.contains(link) => {
+                markdown = markdown
+                    .replace(&format!("]({link})"), &format!("]({uri})"))
+                    .replace(&format!("![[{link}]]"), &format!("![{name}]({uri})"));
+            }
+            _ if mime.starts_with("image/") => {
+                markdown.push_str(&format!("\n\n![{name}]({uri})\n"));
+            }
+            _ => markdown.push_str(&format!("\n\n[{name}]({uri})\n")),
+        }
+    }
+    Ok(markdown)
+}
+
+pub(crate) async fn publish(
+    store: &Store,
+    locks: &LockState,
+    yak_id: &str,
+    dir: &Path,
+) -> Result<ExportReport, String> {
+    let (projection, notes) = load_yak(store, locks, yak_id).await?;
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+
+    // Links resolve by any revision id or by (case-insensitive) title
+    let mut taken = HashSet::from(["index.html".to_string()]);
+    let mut pages = HashMap::new();
+    let mut files = Vec::new();
+    for note in &notes {
+        let title = note_title(note);
+        let file = unique_file_name(&title, ".html", &mut taken);
+        let href = file.replace(' ', "%20");
+        pages.insert(title.to_lowercase(), href.clone());
+        pages.insert(note.id.clone(), href.clone());
+        for (old, _) in projection
+            .replaced_by
+            .iter()
+            .filter(|(old, _)| projection.resolve(old) == note.id)
+        {
+            pages.insert(old.clone(), href.clone());
+        }
+        files.push((title, file, href));
+    }
+
+    let name = projection
+        .yaks
+        .get(yak_id)
+        .and_then(|yak| yak.name.clone())
+        .unwrap_or_else(|| "Notes".to_string());
+    let nav = format!("<nav><a href=\"index.html\">{}</a></nav>", escape(&name));
+
+    let mut report = ExportReport {
+        path: dir.to_string_lossy().into_owned(),
+        ..Default::default()
+    };
+    for (note, (title, file, _)) in notes.iter().zip(&files) {
+        let markdown = link_notes(note.content.as_deref().unwrap_or_default(), &pages);
+        let markdown = inline_attachments(store, note, markdown).await?;
+        let mut body = render_markdown(&markdown);
+        if !note.tags.is_empty() {
+            let tags: String = note
+                .tags
+                .iter()
+                .map(|tag| format!("<span>{}</span>", escape(tag)))
+                .collect();
+            body.push_str(&format!("<p class=\"tags\">{tags}</p>"));
+        }
+        tokio::fs::write(dir.join(file), page(title, &nav, &body))
+            .await
+            .map_err(|e| format!("Failed to write {file}: {e}"))?;
+        report.notes += 1;
+        report.attachments += note.attachments.len();
+    }
+
+    let items: String = files
+        .iter()
+        .map(|(title, _, href)| format!("<li><a href=\"{href}\">{}</a></li>", escape(title)))
+        .collect();
+    let index = page(
+        &name,
+        "",
+        &format!("<h1>{}</h1><ul>{items}</ul>", escape(&name)),
+    );
+    tokio::fs::write(dir.join("index.html"), index)
+        .await
+        .map_err(|e| format!("Failed to write index.html: {e}"))?;
+
+    Ok(report)
+}
+
+/// Publishes a yak as a static site: one sanitized HTML page per note plus an index, with
+/// internal links resolved and attachments inlined.
+#[tauri::command]
+pub async fn publish_yak_html(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    yak_id: String,
+    path: String,
+) -> Result<ExportReport, String> {
+    crate::app_lock::ensure_unlocked()?;
+    publish(&store, &locks, &yak_id, Path::new(&path)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{add_note, create_yak};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_render_markdown_sanitizes() {
+        let html =
+            render_markdown("# Hi\n<script>alert(1)</script>\n![x](data:image/png;base64,AA==)");
+        assert!(html.contains("<h1>Hi</h1>"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("data:image/png;base64,AA=="));
+    }
+
+    #[test]
+    fn test_link_notes() {
+        let pages = HashMap::from([("cats".to_string(), "Cats.html".to_string())]);
+        assert_eq!(
+            link_notes("see [[Cats]] and [[Dogs|dogs]]", &pages),
+            "see [Cats](Cats.html) and [[Dogs|dogs]]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().join("store"));
+        let yak_id = create_yak(&store, "Garden").await.unwrap();
+        add_note(&store, &yak_id, "# Cats\nmeow", serde_json::json!({}))
+            .await
+            .unwrap();
+        add_note(
+            &store,
+            &yak_id,
+            "# Home\nsee [[Cats]]",
+            serde_json::json!({}),
+        )
+        .await
+        .unwrap();
+
+        let out = dir.path().join("site");
+        let report = publish(&store, &LockState::default(), &yak_id, &out)
+            .await
+            .unwrap();
+        assert_eq!(report.notes, 2);
+        let index = std::fs::read_to_string(out.join("index.html")).unwrap();
+        assert!(index.contains("<a href=\"Cats.html\">Cats</a>"));
+        let home = std::fs::read_to_string(out.join("Home.html")).unwrap();
+        assert!(home.contains("<a href=\"Cats.html\""));
+    }
+}
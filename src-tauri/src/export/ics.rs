@@ -0,0 +1,250 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tauri::State;
+use tokio::sync::mpsc;
+use xs::store::{FollowOption, ReadOptions, Store};
+
+use super::note_title;
+use crate::projection::Projection;
+use crate::read_all_frames;
+use crate::settings::{load_setting, save_setting};
+
+const CONFIG_TOPIC: &str = "export.ics";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IcsConfig {
+    /// When set, the feed is rewritten here whenever reminders or dated notes change
+    pub path: Option<String>,
+}
+
+enum When {
+    At(DateTime<Utc>),
+    AllDay(NaiveDate),
+}
+
+struct Event {
+    uid: String,
+    summary: String,
+    description: String,
+    when: When,
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Folds content lines longer than 75 octets, as RFC 5545 requires.
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+/// Dates a note is tagged with, either as a `YYYY-MM-DD` tag or a front-matter `date`.
+fn note_dates(
+    tags: impl Iterator<Item = String>,
+    meta: Option<&serde_json::Value>,
+) -> Vec<NaiveDate> {
+    let frontmatter_date = meta
+        .and_then(|meta| meta.pointer("/frontmatter/date"))
+        .and_then(|date| date.as_str())
+        .map(String::from);
+    let mut dates: Vec<NaiveDate> = tags
+        .chain(frontmatter_date)
+        .filter_map(|s| NaiveDate::parse_from_str(s.get(..10)?, "%Y-%m-%d").ok())
+        .collect();
+    dates.sort();
+    dates.dedup();
+    dates
+}
+
+fn events(projection: &Projection) -> Vec<Event> {
+    let mut events = Vec::new();
+    for note in projection.notes.values() {
+        let current = projection
+            .notes_by_yak
+            .get(&note.yak_id)
+            .is_some_and(|ids| ids.contains(&note.id));
+        if !current || note.archived {
+            continue;
+        }
+        let summary = note_title(note);
+        let description = note.content.clone().unwrap_or_default();
+        if let Some(at) = note
+            .reminder
+            .as_deref()
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+        {
+            events.push(Event {
+                uid: format!("{}-reminder@yaks", note.id),
+                summary: summary.clone(),
+                description: description.clone(),
+                when: When::At(at.with_timezone(&Utc)),
+            });
+        }
+        for date in note_dates(note.tags.iter().cloned(), note.meta.as_ref()) {
+            events.push(Event {
+                uid: format!("{}-{date}@yaks", note.id),
+                summary: summary.clone(),
+                description: description.clone(),
+                when: When::AllDay(date),
+            });
+        }
+    }
+    for task in projection.tasks.values().filter(|task| !task.done) {
+        if let Some(at) = task
+            .reminder
+            .as_deref()
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+        {
+            events.push(Event {
+                uid: format!("{}-reminder@yaks", task.id),
+                summary: task.content.clone().unwrap_or_default(),
+                description: String::new(),
+                when: When::At(at.with_timezone(&Utc)),
+            });
+        }
+    }
+    events.sort_by(|a, b| a.uid.cmp(&b.uid));
+    events
+}
+
+pub(crate) fn render(projection: &Projection) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut ics = String::new();
+    ics.push_str(&fold("BEGIN:VCALENDAR"));
+    ics.push_str(&fold("VERSION:2.0"));
+    ics.push_str(&fold("PRODID:-//Yaks//Reminders//EN"));
+    ics.push_str(&fold("X-WR-CALNAME:Yaks"));
+    for event in events(projection) {
+        ics.push_str(&fold("BEGIN:VEVENT"));
+        ics.push_str(&fold(&format!("UID:{}", event.uid)));
+        ics.push_str(&fold(&format!("DTSTAMP:{stamp}")));
+        match event.when {
+            When::At(at) => {
+                ics.push_str(&fold(&format!("DTSTART:{}", at.format("%Y%m%dT%H%M%SZ"))));
+            }
+            When::AllDay(date) => {
+                ics.push_str(&fold(&format!(
+                    "DTSTART;VALUE=DATE:{}",
+                    date.format("%Y%m%d")
+                )));
+            }
+        }
+        ics.push_str(&fold(&format!("SUMMARY:{}", escape_text(&event.summary))));
+        if !event.description.is_empty() {
+            ics.push_str(&fold(&format!(
+                "DESCRIPTION:{}",
+                escape_text(&event.description)
+            )));
+        }
+        ics.push_str(&fold("END:VEVENT"));
+    }
+    ics.push_str(&fold("END:VCALENDAR"));
+    ics
+}
+
+pub(crate) async fn write_ics(store: &Store, path: &Path) -> Result<(), String> {
+    let mut projection = Projection::from_frames(&read_all_frames(store).await);
+    projection.resolve_content(store).await;
+    tokio::fs::write(path, render(&projection))
+        .await
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+fn affects_calendar(topic: &str) -> bool {
+    ["reminder.", "note.", "tag.", "task."]
+        .iter()
+        .any(|prefix| topic.starts_with(prefix))
+}
+
+/// Rewrites the configured feed whenever a frame that could change it is appended.
+pub(crate) async fn watch(store: Store) {
+    let (kick, mut kicked) = mpsc::channel::<()>(1);
+    let follower_store = store.clone();
+    tokio::spawn(async move {
+        let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+        let mut rx = follower_store.read(read_options).await;
+        let mut caught_up = false;
+        while let Some(frame) = rx.recv().await {
+            if frame.topic == "xs.threshold" {
+                caught_up = true;
+            } else if caught_up && (affects_calendar(&frame.topic) || frame.topic == CONFIG_TOPIC) {
+                let _ = kick.try_send(());
+            }
+        }
+    });
+
+    while kicked.recv().await.is_some() {
+        let config: IcsConfig = load_setting(&store, CONFIG_TOPIC).await;
+        if let Some(path) = config.path {
+            if let Err(e) = write_ics(&store, Path::new(&path)).await {
+                eprintln!("Failed to update calendar feed: {e}");
+            }
+        }
+    }
+}
+
+/// Writes reminders and dated notes as an iCalendar feed. With `auto_update`, the feed at
+/// `path` is kept up to date as reminders change; `auto_update: false` stops that.
+#[tauri::command]
+pub async fn export_ics(
+    store: State<'_, Store>,
+    path: String,
+    auto_update: Option<bool>,
+) -> Result<(), String> {
+    crate::app_lock::ensure_unlocked()?;
+    write_ics(&store, Path::new(&path)).await?;
+    if let Some(auto_update) = auto_update {
+        let config = IcsConfig {
+            path: auto_update.then_some(path),
+        };
+        save_setting(&store, CONFIG_TOPIC, &config)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_and_escape() {
+        let folded = fold(&"x".repeat(80));
+        assert_eq!(
+            folded,
+            format!("{}\r\n {}\r\n", "x".repeat(75), "x".repeat(5))
+        );
+        assert_eq!(escape_text("a,b;c\nd"), "a\\,b\\;c\\nd");
+    }
+
+    #[test]
+    fn test_note_dates() {
+        let meta = serde_json::json!({ "frontmatter": { "date": "2024-03-01" } });
+        let dates = note_dates(
+            vec!["2024-02-01".to_string(), "work".to_string()].into_iter(),
+            Some(&meta),
+        );
+        assert_eq!(
+            dates,
+            vec![
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()
+            ]
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use serde_json::{json, Value};
+use tauri::State;
+use xs::store::Store;
+
+use super::html::render_markdown;
+use super::{created_id, load_yak, note_title, ExportReport};
+use crate::locks::{is_sealed, LockState};
+use crate::projection::{Note, Projection};
+use crate::time::from_id;
+
+const VERSION: &str = "https://jsonfeed.org/version/1.1";
+/// Feed readers only show the latest entries, so older notes are left out.
+const MAX_ITEMS: usize = 50;
+pub(crate) const CONTENT_TYPE: &str = "application/feed+json";
+
+/// A JSON Feed of a yak's latest notes, newest first. Archived and locked notes are left
+/// out. With `base_url`, where the yak is served (see `publish`), items and the feed link
+/// to their pages there.
+pub(crate) fn render(
+    projection: &Projection,
+    yak_id: &str,
+    notes: &[Note],
+    base_url: Option<&str>,
+) -> Value {
+    let title = projection
+        .yaks
+        .get(yak_id)
+        .and_then(|yak| yak.name.clone())
+        .unwrap_or_else(|| "Notes".to_string());
+    let mut notes: Vec<(String, &Note)> = notes
+        .iter()
+        .filter(|note| !note.archived)
+        .filter(|note| !note.content.as_deref().is_some_and(is_sealed))
+        .map(|note| (created_id(projection, note), note))
+        .collect();
+    notes.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let base_url = base_url.map(|url| url.trim_end_matches('/'));
+    let items: Vec<Value> = notes
+        .into_iter()
+        .take(MAX_ITEMS)
+        .map(|(created, note)| {
+            let content = note.content.as_deref().unwrap_or_default();
+            let date = |id: &str| from_id(id).map(|time| time.to_rfc3339());
+            let mut item = json!({
+                "id": created,
+                "title": note_title(note),
+                "content_html": render_markdown(content),
+                "content_text": content,
+                "date_published": date(&created),
+                "date_modified": date(&note.id),
+            });
+            if let Some(base_url) = base_url {
+                item["url"] = format!("{base_url}/note/{}", note.id).into();
+            }
+            if !note.tags.is_empty() {
+                item["tags"] = json!(note.tags);
+            }
+            item
+        })
+        .collect();
+
+    let mut feed = json!({ "version": VERSION, "title": title, "items": items });
+    if let Some(base_url) = base_url {
+        feed["home_page_url"] = format!("{base_url}/").into();
+        feed["feed_url"] = format!("{base_url}/feed.json").into();
+    }
+    feed
+}
+
+/// Writes a yak's latest notes as a JSON Feed, for feed readers to follow like a microblog.
+/// Served live as `/feed.json` when the yak is published.
+#[tauri::command]
+pub async fn export_json_feed(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    yak_id: String,
+    path: String,
+) -> Result<ExportReport, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let (projection, notes) = load_yak(&store, &locks, &yak_id).await?;
+    let feed = render(&projection, &yak_id, &notes, None);
+    let items = feed["items"].as_array().map_or(0, Vec::len);
+    let json = serde_json::to_string_pretty(&feed)
+        .map_err(|e| format!("Failed to serialize feed: {e}"))?;
+    tokio::fs::write(&path, json)
+        .await
+        .map_err(|e| format!("Failed to write {path}: {e}"))?;
+    Ok(ExportReport {
+        path,
+        notes: items,
+        attachments: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{add_note, add_tag, create_yak, set_flag};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_render() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Microblog").await.unwrap();
+        let first = add_note(&store, &yak_id, "# Hello\nFirst *post*", json!({}))
+            .await
+            .unwrap();
+        add_tag(&store, &yak_id, &first.id.to_string(), "intro")
+            .await
+            .unwrap();
+        let second = add_note(&store, &yak_id, "Second post", json!({}))
+            .await
+            .unwrap();
+        let hidden = add_note(&store, &yak_id, "Draft", json!({})).await.unwrap();
+        set_flag(
+            &store,
+            "note.archive",
+            &yak_id,
+            &hidden.id.to_string(),
+            true,
+        )
+        .await
+        .unwrap();
+
+        let (projection, notes) = load_yak(&store, &LockState::default(), &yak_id)
+            .await
+            .unwrap();
+        let feed = render(&projection, &yak_id, &notes, Some("http://10.0.0.2:8421/"));
+        assert_eq!(feed["version"], VERSION);
+        assert_eq!(feed["title"], "Microblog");
+        assert_eq!(feed["feed_url"], "http://10.0.0.2:8421/feed.json");
+        let items = feed["items"].as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["id"], second.id.to_string());
+        assert_eq!(items[1]["title"], "Hello");
+        assert_eq!(
+            items[1]["url"],
+            format!("http://10.0.0.2:8421/note/{}", first.id)
+        );
+        assert_eq!(items[1]["tags"], json!(["intro"]));
+        assert!(items[1]["content_html"]
+            .as_str()
+            .unwrap()
+            .contains("<em>post</em>"));
+        assert!(items[1]["date_published"].is_string());
+    }
+}
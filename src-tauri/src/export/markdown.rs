@@ -0,0 +1,208 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::OnceLock;
+use tauri::State;
+use xs::store::Store;
+
+use super::{created_id, load_yak, note_title, unique_file_name, ExportReport};
+use crate::locks::LockState;
+use crate::{frontmatter, time};
+
+/// Rewrites `[[<note id>]]` links to `[[<file name>]]` so they resolve inside the vault.
+pub(crate) fn translate_wiki_links(content: &str, names: &HashMap<String, String>) -> String {
+    static WIKI_ID: OnceLock<Regex> = OnceLock::new();
+    let wiki_id =
+        WIKI_ID.get_or_init(|| Regex::new(r"\[\[([0-9a-z]{25})((?:[|#][^\]]*)?)\]\]").unwrap());
+    wiki_id
+        .replace_all(content, |caps: &regex::Captures| {
+            match names.get(&caps[1]) {
+                Some(name) => format!("[[{name}{}]]", &caps[2]),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+pub(crate) async fn export_yak(
+    store: &Store,
+    locks: &LockState,
+    yak_id: &str,
+    dir: &Path,
+) -> Result<ExportReport, String> {
+    let (projection, notes) = load_yak(store, locks, yak_id).await?;
+    let assets = dir.join("assets");
+    tokio::fs::create_dir_all(&assets)
+        .await
+        .map_err(|e| format!("Failed to create {}: {e}", assets.display()))?;
+
+    // Any revision id of a note maps to its file name, so older links still resolve
+    let mut taken = HashSet::new();
+    let mut names = HashMap::new();
+    let mut files = Vec::new();
+    for note in &notes {
+        let file_name = unique_file_name(&note_title(note), ".md", &mut taken);
+        let stem = file_name.trim_end_matches(".md").to_string();
+        for id in projection
+            .replaced_by
+            .keys()
+            .filter(|id| projection.resolve(id) == note.id)
+        {
+            names.insert(id.clone(), stem.clone());
+        }
+        names.insert(note.id.clone(), stem);
+        files.push(file_name);
+    }
+
+    let mut report = ExportReport {
+        path: dir.to_string_lossy().into_owned(),
+        ..Default::default()
+    };
+    let mut taken_assets = HashSet::new();
+
+    for (note, file_name) in notes.iter().zip(files) {
+        let mut body = translate_wiki_links(note.content.as_deref().unwrap_or_default(), &names);
+
+        for attachment in &note.attachments {
+            let Some(hash) = &attachment.hash else {
+                continue;
+            };
+            let bytes = store
+                .cas_read(hash)
+                .await
+                .map_err(|e| format!("Failed to read attachment {}: {e}", attachment.id))?;
+            let name = attachment.name.as_deref().unwrap_or("attachment");
+            let asset = unique_file_name(name, "", &mut taken_assets);
+            tokio::fs::write(assets.join(&asset), bytes)
+                .await
+                .map_err(|e| format!("Failed to write {asset}: {e}"))?;
+            report.attachments += 1;
+
+            let link = attachment
+                .meta
+                .as_ref()
+                .and_then(|meta| meta.get("link"))
+                .and_then(|link| link.as_str());
+            let target = format!("assets/{}", asset.replace(' ', "%20"));
+            match link {
+                Some(link) if body.contains(link) => {
+                    body = body
+                        .replace(&format!("]({link})"), &format!("]({target})"))
+                        .replace(&format!("![[{link}"), &format!("![[{asset}"));
+                }
+                _ => body.push_str(&format!("\n![{name}]({target})\n")),
+            }
+        }
+
+        let mut fields = note
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.get("frontmatter"))
+            .and_then(|fields| fields.as_object())
+            .cloned()
+            .unwrap_or_default();
+        // Properties first, so one synced or imported under a reserved name can't replace
+        // the fields set below
+        fields.extend(note.properties.clone());
+        let created = time::from_id(&created_id(&projection, note));
+        let updated = time::from_id(&note.id);
+        fields.insert("id".to_string(), note.id.clone().into());
+        if let Some(created) = created {
+            fields.insert("created".to_string(), created.to_rfc3339().into());
+        }
+        if let Some(updated) = updated {
+            fields.insert("updated".to_string(), updated.to_rfc3339().into());
+        }
+        if !note.tags.is_empty() {
+            fields.insert(
+                "tags".to_string(),
+                note.tags.iter().cloned().collect::<Vec<_>>().into(),
+            );
+        }
+        if let Some(label) = &note.label {
+            fields.insert("label".to_string(), serde_json::json!(label));
+        }
+
+        let markdown = frontmatter::render(&fields.into(), &body)?;
+        tokio::fs::write(dir.join(&file_name), markdown)
+            .await
+            .map_err(|e| format!("Failed to write {file_name}: {e}"))?;
+        report.notes += 1;
+    }
+
+    Ok(report)
+}
+
+/// Writes a yak as an Obsidian-compatible vault: one markdown file per note with YAML
+/// front-matter, and attachments under `assets/`.
+#[tauri::command]
+pub async fn export_yak_markdown(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    yak_id: String,
+    path: String,
+) -> Result<ExportReport, String> {
+    crate::app_lock::ensure_unlocked()?;
+    export_yak(&store, &locks, &yak_id, Path::new(&path)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::markdown::import_dir;
+    use crate::projection::Projection;
+    use crate::properties::append_property;
+    use crate::read_all_frames;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_translate_wiki_links() {
+        let id = scru128::new().to_string();
+        let names = HashMap::from([(id.clone(), "Cats".to_string())]);
+        let content = format!("see [[{id}|cats]] and [[Dogs]]");
+        assert_eq!(
+            translate_wiki_links(&content, &names),
+            "see [[Cats|cats]] and [[Dogs]]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_markdown_roundtrip() {
+        let vault = tempdir().unwrap();
+        std::fs::write(vault.path().join("cat.png"), b"png").unwrap();
+        std::fs::write(
+            vault.path().join("Cats.md"),
+            "---\ntags: [pets]\n---\n# Cats\n![cat](cat.png)\n",
+        )
+        .unwrap();
+
+        let store_dir = tempdir().unwrap();
+        let store = Store::new(store_dir.path().to_path_buf());
+        let imported = import_dir(&store, vault.path(), None).await.unwrap();
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let note_id = projection.current_notes(&imported.yak_id)[0].id.clone();
+        for (key, value) in [("rating", json!(5)), ("id", json!("bogus"))] {
+            append_property(&store, &imported.yak_id, &note_id, key, value)
+                .await
+                .unwrap();
+        }
+
+        let out = tempdir().unwrap();
+        let report = export_yak(&store, &LockState::default(), &imported.yak_id, out.path())
+            .await
+            .unwrap();
+        assert_eq!(report.notes, 1);
+        assert_eq!(report.attachments, 1);
+
+        let exported = std::fs::read_to_string(out.path().join("Cats.md")).unwrap();
+        let (fields, body) = frontmatter::split(&exported);
+        let fields = fields.unwrap();
+        assert_eq!(frontmatter::tags(&fields), vec!["pets"]);
+        assert_eq!(fields["rating"], 5);
+        // A property can't stand in for a reserved field
+        assert_eq!(fields["id"], json!(note_id));
+        assert!(body.contains("![cat](assets/cat.png)"));
+        assert!(out.path().join("assets/cat.png").exists());
+    }
+}
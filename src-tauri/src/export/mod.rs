@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use xs::store::Store;
+
+use crate::locks::LockState;
+use crate::projection::{Note, Projection};
+use crate::read_all_frames;
+
+mod audit;
+mod copy;
+mod csv;
+mod git;
+pub(crate) mod html;
+mod ics;
+pub(crate) mod json_feed;
+mod markdown;
+mod org;
+mod pdf;
+mod spotlight;
+mod sqlite;
+
+pub use audit::{export_audit_log, verify_audit_log};
+pub use copy::{configure_secure_copy, copy_notes, copy_secure};
+pub use csv::export_tasks_csv;
+pub(crate) use git::watch as watch_git_mirrors;
+pub use git::{configure_git_mirror, get_git_mirrors};
+pub use html::publish_yak_html;
+pub use ics::export_ics;
+pub(crate) use ics::watch as watch_ics;
+pub use json_feed::export_json_feed;
+pub use markdown::export_yak_markdown;
+pub use org::export_org;
+#[cfg(test)]
+pub(crate) use org::write_org;
+pub use pdf::export_note_pdf;
+pub use spotlight::configure_spotlight;
+pub(crate) use spotlight::watch as watch_spotlight;
+pub use sqlite::export_sqlite;
+
+/// What an exporter wrote, returned to the frontend once it finishes.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ExportReport {
+    pub path: String,
+    pub notes: usize,
+    pub attachments: usize,
+}
+
+/// Folds the store and returns the current notes of a yak with their content resolved, and
+/// opened or blanked as `locks` allows.
+pub(crate) async fn load_yak(
+    store: &Store,
+    locks: &LockState,
+    yak_id: &str,
+) -> Result<(Projection, Vec<Note>), String> {
+    let frames = read_all_frames(store).await;
+    let mut projection = Projection::from_frames(&frames);
+    if !projection.yaks.contains_key(yak_id) {
+        return Err(format!("Yak not found: {yak_id}"));
+    }
+    projection.resolve_content(store).await;
+    locks.redact(&mut projection, false);
+    let notes = projection
+        .current_notes(yak_id)
+        .into_iter()
+        .cloned()
+        .collect();
+    Ok((projection, notes))
+}
+
+/// The id of a note's first revision, i.e. when it was created.
+pub(crate) fn created_id(projection: &Projection, note: &Note) -> String {
+    let mut id = &note.id;
+    while let Some(previous) = projection
+        .notes
+        .get(id)
+        .and_then(|note| note.edited_note_id.as_ref())
+    {
+        id = previous;
+    }
+    id.clone()
+}
+
+/// A note's title: the one it was renamed to, else `title` from its meta, else the first
+/// non-empty line of content.
+pub(crate) fn note_title(note: &Note) -> String {
+    if let Some(title) = note.title.as_deref().or_else(|| {
+        note.meta
+            .as_ref()
+            .and_then(|meta| meta.get("title"))
+            .and_then(|title| title.as_str())
+    }) {
+        return title.to_string();
+    }
+    content_title(note.content.as_deref().unwrap_or_default())
+}
+
+/// The first non-empty line of `content`, without heading markers.
+pub(crate) fn content_title(content: &str) -> String {
+    content
+        .lines()
+        .map(|line| line.trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or("Untitled")
+        .chars()
+        .take(80)
+        .collect()
+}
+
+/// Makes `name` safe as a file name and unique among `taken`.
+pub(crate) fn unique_file_name(name: &str, extension: &str, taken: &mut HashSet<String>) -> String {
+    let base: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => '-',
+            c => c,
+        })
+        .collect();
+    let base = base.trim().trim_matches('.');
+    let base = if base.is_empty() { "Untitled" } else { base };
+
+    let mut candidate = format!("{base}{extension}");
+    let mut n = 2;
+    while !taken.insert(candidate.to_lowercase()) {
+        candidate = format!("{base} {n}{extension}");
+        n += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unique_file_name() {
+        let mut taken = HashSet::new();
+        assert_eq!(unique_file_name("a/b", ".md", &mut taken), "a-b.md");
+        assert_eq!(unique_file_name("A/B", ".md", &mut taken), "A-B 2.md");
+        assert_eq!(unique_file_name("..", ".md", &mut taken), "Untitled.md");
+    }
+}
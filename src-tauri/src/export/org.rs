@@ -0,0 +1,241 @@
+use chrono::{DateTime, Local};
+use std::path::Path;
+use tauri::State;
+use xs::store::Store;
+
+use super::{created_id, load_yak, note_title, ExportReport};
+use crate::locks::LockState;
+use crate::projection::{Projection, Task};
+use crate::time;
+
+fn org_timestamp(time: DateTime<Local>, active: bool) -> String {
+    let (open, close) = if active { ('<', '>') } else { ('[', ']') };
+    format!("{open}{}{close}", time.format("%Y-%m-%d %a %H:%M"))
+}
+
+fn scheduled(reminder: Option<&str>) -> Option<String> {
+    let at = DateTime::parse_from_rfc3339(reminder?).ok()?;
+    Some(format!(
+        "SCHEDULED: {}",
+        org_timestamp(at.with_timezone(&Local), true)
+    ))
+}
+
+/// Org tags may only contain letters, digits, `_` and `@`.
+fn org_tag(tag: &str) -> String {
+    tag.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '@' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn heading(level: usize, title: &str, tags: &[String]) -> String {
+    let mut line = format!("{} {title}", "*".repeat(level));
+    if !tags.is_empty() {
+        let tags: Vec<String> = tags.iter().map(|tag| org_tag(tag)).collect();
+        line.push_str(&format!(" :{}:", tags.join(":")));
+    }
+    line
+}
+
+/// Converts a markdown body to org under a heading at `level`: markdown headings nest below it
+/// and lines that org would read as headings are turned into list items.
+pub(crate) fn markdown_body(content: &str, level: usize) -> String {
+    let mut out = String::new();
+    for line in content.lines() {
+        let hashes = line.chars().take_while(|c| *c == '#').count();
+        if hashes > 0 && line[hashes..].starts_with(' ') {
+            out.push_str(&format!(
+                "{}{}\n",
+                "*".repeat(level + hashes),
+                &line[hashes..]
+            ));
+        } else if let Some(item) = line.strip_prefix("* ") {
+            out.push_str(&format!("- {item}\n"));
+        } else {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn task_entry(task: &Task, level: usize) -> String {
+    let keyword = if task.done { "DONE" } else { "TODO" };
+    let text = task.content.as_deref().unwrap_or_default();
+    let mut lines = text.lines();
+    let mut entry = format!(
+        "{} {keyword} {}\n",
+        "*".repeat(level),
+        lines.next().unwrap_or_default()
+    );
+    if let Some(scheduled) = scheduled(task.reminder.as_deref()) {
+        entry.push_str(&format!("{scheduled}\n"));
+    }
+    for line in lines {
+        entry.push_str(line);
+        entry.push('\n');
+    }
+    entry
+}
+
+pub(crate) fn render(projection: &Projection, yak_id: &str) -> String {
+    let mut org = String::new();
+    if let Some(name) = projection
+        .yaks
+        .get(yak_id)
+        .and_then(|yak| yak.name.as_ref())
+    {
+        org.push_str(&format!("#+TITLE: {name}\n\n"));
+    }
+
+    let tasks = projection.yak_tasks(yak_id);
+    for note in projection.current_notes(yak_id) {
+        let tags: Vec<String> = note.tags.iter().cloned().collect();
+        org.push_str(&heading(1, &note_title(note), &tags));
+        org.push('\n');
+        if let Some(scheduled) = scheduled(note.reminder.as_deref()) {
+            org.push_str(&format!("{scheduled}\n"));
+        }
+        org.push_str(":PROPERTIES:\n");
+        org.push_str(&format!(":ID: {}\n", note.id));
+        if let Some(created) = time::from_id(&created_id(projection, note)) {
+            org.push_str(&format!(
+                ":CREATED: {}\n",
+                org_timestamp(created.with_timezone(&Local), false)
+            ));
+        }
+        org.push_str(":END:\n");
+
+        let content = note.content.as_deref().unwrap_or_default();
+        // The title line becomes the heading, so leave it out of the body
+        let body = match content.split_once('\n') {
+            Some((first, rest)) if first.trim_start_matches('#').trim() == note_title(note) => rest,
+            None if content.trim_start_matches('#').trim() == note_title(note) => "",
+            _ => content,
+        };
+        org.push_str(&markdown_body(body.trim_matches('\n'), 1));
+
+        let note_ids = note_revisions(projection, &note.id);
+        for task in tasks.iter().filter(|task| {
+            task.note_id
+                .as_ref()
+                .is_some_and(|id| note_ids.contains(id))
+        }) {
+            org.push_str(&task_entry(task, 2));
+        }
+        org.push('\n');
+    }
+
+    let loose: Vec<&&Task> = tasks
+        .iter()
+        .filter(|task| {
+            task.note_id
+                .as_ref()
+                .map_or(true, |id| !projection.notes.contains_key(id))
+        })
+        .collect();
+    if !loose.is_empty() {
+        org.push_str("* Tasks\n");
+        for task in loose {
+            org.push_str(&task_entry(task, 2));
+        }
+    }
+    org
+}
+
+/// Every revision id of the note whose latest revision is `note_id`.
+fn note_revisions(projection: &Projection, note_id: &str) -> Vec<String> {
+    let mut ids = vec![note_id.to_string()];
+    let mut id = note_id;
+    while let Some(previous) = projection
+        .notes
+        .get(id)
+        .and_then(|note| note.edited_note_id.as_deref())
+    {
+        ids.push(previous.to_string());
+        id = previous;
+    }
+    ids
+}
+
+/// Writes a yak as a single org file: notes become headings, tasks TODO/DONE entries,
+/// reminders SCHEDULED timestamps and tags org tags.
+#[tauri::command]
+pub async fn export_org(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    yak_id: String,
+    path: String,
+) -> Result<ExportReport, String> {
+    write_org(&store, &locks, &yak_id, path).await
+}
+
+/// `export_org` itself, refused while the app is locked.
+pub(crate) async fn write_org(
+    store: &Store,
+    locks: &LockState,
+    yak_id: &str,
+    path: String,
+) -> Result<ExportReport, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let (projection, notes) = load_yak(store, locks, yak_id).await?;
+    let org = render(&projection, yak_id);
+    tokio::fs::write(&path, org)
+        .await
+        .map_err(|e| format!("Failed to write {path}: {e}"))?;
+    Ok(ExportReport {
+        path,
+        notes: notes.len(),
+        attachments: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{add_note, add_tag, add_task, create_yak};
+    use crate::read_all_frames;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_markdown_body() {
+        assert_eq!(
+            markdown_body("intro\n## Details\n* item", 1),
+            "intro\n*** Details\n- item\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_org() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Project").await.unwrap();
+        let note = add_note(&store, &yak_id, "# Plan\nship it", serde_json::json!({}))
+            .await
+            .unwrap();
+        let note_id = note.id.to_string();
+        add_tag(&store, &yak_id, &note_id, "work-stuff")
+            .await
+            .unwrap();
+        add_task(&store, &yak_id, Some(&note_id), "write docs", true)
+            .await
+            .unwrap();
+        add_task(&store, &yak_id, None, "loose end", false)
+            .await
+            .unwrap();
+
+        let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+        projection.resolve_content(&store).await;
+        let org = render(&projection, &yak_id);
+        assert!(org.starts_with("#+TITLE: Project\n"));
+        assert!(org.contains("* Plan :work_stuff:\n"));
+        assert!(org.contains("ship it\n** DONE write docs\n"));
+        assert!(org.contains("* Tasks\n** TODO loose end\n"));
+    }
+}
@@ -0,0 +1,149 @@
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use tauri::State;
+use xs::store::Store;
+
+use super::html::{inline_attachments, page};
+use super::note_title;
+use crate::locks::LockState;
+use crate::projection::Projection;
+use crate::read_all_frames;
+
+/// Placeholder a code block is swapped out for while the rest of the HTML is sanitized.
+fn placeholder(n: usize) -> String {
+    format!("<pre>yaks-code-{n}</pre>")
+}
+
+fn highlight(code: &str, language: &str) -> String {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    let syntaxes = SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines);
+    let themes = THEMES.get_or_init(ThemeSet::load_defaults);
+    let syntax = syntaxes
+        .find_syntax_by_token(language)
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+    syntect::html::highlighted_html_for_string(
+        code,
+        syntaxes,
+        syntax,
+        &themes.themes["InspiredGitHub"],
+    )
+    .unwrap_or_else(|_| format!("<pre>{}</pre>", crate::html::escape(code)))
+}
+
+/// Like `render_markdown`, with syntax-highlighted code blocks. The highlighter's output
+/// escapes the code, so it's spliced in after sanitizing (which would strip its styles).
+fn render_highlighted(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let mut blocks = Vec::new();
+    let mut code: Option<(String, String)> = None;
+    let mut events = Vec::new();
+    for event in Parser::new_ext(markdown, options) {
+        match (event, code.as_mut()) {
+            (Event::Start(Tag::CodeBlock(kind)), _) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code = Some((language, String::new()));
+            }
+            (Event::Text(text), Some((_, body))) => body.push_str(&text),
+            (Event::End(TagEnd::CodeBlock), Some(_)) => {
+                let (language, body) = code.take().unwrap_or_default();
+                events.push(Event::Html(placeholder(blocks.len()).into()));
+                blocks.push(highlight(&body, &language));
+            }
+            (event, _) => events.push(event),
+        }
+    }
+
+    let mut unsafe_html = String::new();
+    pulldown_cmark::html::push_html(&mut unsafe_html, events.into_iter());
+    let mut sanitizer = ammonia::Builder::default();
+    sanitizer.add_url_schemes(&["data"]);
+    let mut html = sanitizer.clean(&unsafe_html).to_string();
+    for (n, block) in blocks.iter().enumerate() {
+        html = html.replace(&placeholder(n), block);
+    }
+    html
+}
+
+/// Prints an HTML file to PDF with a headless Chrome/Chromium found on the system.
+fn print_pdf(html_path: &Path) -> Result<Vec<u8>, String> {
+    let browser = headless_chrome::Browser::default()
+        .map_err(|e| format!("Couldn't start Chrome or Chromium for PDF export: {e}"))?;
+    let tab = browser
+        .new_tab()
+        .map_err(|e| format!("PDF export failed: {e}"))?;
+    let url = format!("file://{}", html_path.display());
+    tab.navigate_to(&url)
+        .and_then(|tab| tab.wait_until_navigated())
+        .map_err(|e| format!("PDF export failed: {e}"))?;
+    tab.print_to_pdf(Some(headless_chrome::types::PrintToPdfOptions {
+        print_background: Some(true),
+        ..Default::default()
+    }))
+    .map_err(|e| format!("PDF export failed: {e}"))
+}
+
+/// Renders a note, with its attachments inlined and code highlighted, to a PDF at `path`.
+#[tauri::command]
+pub async fn export_note_pdf(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    frame_id: String,
+    path: String,
+) -> Result<(), String> {
+    crate::app_lock::ensure_unlocked()?;
+    let frames = read_all_frames(&store).await;
+    let mut projection = Projection::from_frames(&frames);
+    let note_id = projection.resolve(&frame_id);
+    projection.resolve_content(&store).await;
+    locks.redact(&mut projection, false);
+    let note = projection
+        .notes
+        .get(&note_id)
+        .ok_or_else(|| format!("Note not found: {frame_id}"))?;
+
+    let markdown = note.content.clone().unwrap_or_default();
+    let markdown = inline_attachments(&store, note, markdown).await?;
+    let html = page(&note_title(note), "", &render_highlighted(&markdown));
+
+    let html_path = std::env::temp_dir().join(format!("yaks-note-{}.html", scru128::new()));
+    tokio::fs::write(&html_path, html)
+        .await
+        .map_err(|e| format!("Failed to write {}: {e}", html_path.display()))?;
+    let print_path = html_path.clone();
+    let pdf = tokio::task::spawn_blocking(move || print_pdf(&print_path))
+        .await
+        .map_err(|e| format!("PDF export failed: {e}"));
+    let _ = tokio::fs::remove_file(&html_path).await;
+
+    tokio::fs::write(&path, pdf??)
+        .await
+        .map_err(|e| format!("Failed to write {path}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_highlighted() {
+        let html = render_highlighted("Intro <script>x()</script>\n\n```rust\nfn main() {}\n```\n");
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("yaks-code-"));
+        assert!(html.contains("style=\""));
+        assert!(html.contains("main"));
+    }
+}
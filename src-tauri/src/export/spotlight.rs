@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::mpsc;
+use xs::store::{FollowOption, ReadOptions, Store};
+
+use super::{created_id, note_title, unique_file_name};
+use crate::cache::CasCache;
+use crate::projection::Projection;
+use crate::read_all_frames;
+use crate::settings::{load_setting, save_setting};
+
+const CONFIG_TOPIC: &str = "export.spotlight";
+const SNIPPET_CHARS: usize = 500;
+const STUB_EXTENSION: &str = ".txt";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpotlightConfig {
+    pub enabled: bool,
+    /// Where stubs are kept; defaults to a "Yaks Search" folder in Documents
+    pub dir: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpotlightStatus {
+    pub enabled: bool,
+    pub dir: Option<String>,
+    pub stubs: usize,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct StubSync {
+    pub written: usize,
+    pub removed: usize,
+}
+
+fn stub_dir(config: &SpotlightConfig) -> Option<PathBuf> {
+    config
+        .dir
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(|| dirs::document_dir().map(|dir| dir.join("Yaks Search")))
+}
+
+pub(crate) fn deep_link(note_id: &str) -> String {
+    format!("yaks://note/{note_id}")
+}
+
+fn render_stub(title: &str, content: &str, note_id: &str) -> String {
+    let mut snippet: String = content.chars().take(SNIPPET_CHARS).collect();
+    if snippet.len() < content.len() {
+        snippet.push('…');
+    }
+    format!("{title}\n\n{snippet}\n\n{}\n", deep_link(note_id))
+}
+
+/// The stub files that should exist, keyed by file name. Names are derived from the note's
+/// first revision so an edit rewrites the same file unless the title changes.
+async fn stubs(store: &Store, cache: &CasCache) -> HashMap<String, String> {
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    let current = projection
+        .notes_by_yak
+        .values()
+        .flatten()
+        .filter_map(|id| projection.notes.get(id))
+        .filter(|note| !note.archived);
+    let mut taken = HashSet::new();
+    let mut stubs = HashMap::new();
+    for note in current {
+        let mut note = note.clone();
+        if let Some(hash) = &note.hash {
+            match cache.read(store, &hash.to_string()).await {
+                Ok(content) => note.content = Some(content.to_string()),
+                Err(e) => eprintln!("Failed to read content for note {}: {e}", note.id),
+            }
+        }
+        let title = note_title(&note);
+        let created = created_id(&projection, &note);
+        let name = unique_file_name(&format!("{title} ({created})"), STUB_EXTENSION, &mut taken);
+        // Locked notes are listed by title only
+        let snippet = note
+            .content
+            .as_deref()
+            .filter(|content| !crate::locks::is_sealed(content))
+            .unwrap_or_default();
+        let stub = render_stub(&title, snippet, &note.id);
+        stubs.insert(name, stub);
+    }
+    stubs
+}
+
+/// Brings `dir` in line with `stubs`, only touching files whose contents changed so the OS
+/// indexer isn't handed the whole set on every edit. The directory is owned by the exporter:
+/// any other stub-like files in it are removed.
+fn sync_dir(dir: &Path, stubs: &HashMap<String, String>) -> Result<StubSync, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    let mut report = StubSync::default();
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {e}", dir.display()))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.ends_with(STUB_EXTENSION) && !stubs.contains_key(&name) {
+            std::fs::remove_file(entry.path())
+                .map_err(|e| format!("Failed to remove {name}: {e}"))?;
+            report.removed += 1;
+        }
+    }
+    for (name, stub) in stubs {
+        let path = dir.join(name);
+        if std::fs::read_to_string(&path).is_ok_and(|existing| existing == *stub) {
+            continue;
+        }
+        std::fs::write(&path, stub).map_err(|e| format!("Failed to write {name}: {e}"))?;
+        report.written += 1;
+    }
+    Ok(report)
+}
+
+async fn update(store: &Store, cache: &CasCache, dir: &Path) -> Result<StubSync, String> {
+    let stubs = stubs(store, cache).await;
+    sync_dir(dir, &stubs)
+}
+
+fn affects_stubs(topic: &str) -> bool {
+    ["note.", "yak.merge"]
+        .iter()
+        .any(|prefix| topic.starts_with(prefix))
+}
+
+/// Keeps the stub directory current while the exporter is enabled.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let (kick, mut kicked) = mpsc::channel::<()>(1);
+    let follower_store = store.clone();
+    tokio::spawn(async move {
+        let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+        let mut rx = follower_store.read(read_options).await;
+        while let Some(frame) = rx.recv().await {
+            // Catching up also refreshes stubs for edits made while the app was closed
+            if frame.topic == "xs.threshold"
+                || affects_stubs(&frame.topic)
+                || frame.topic == CONFIG_TOPIC
+            {
+                let _ = kick.try_send(());
+            }
+        }
+    });
+
+    while kicked.recv().await.is_some() {
+        let config: SpotlightConfig = load_setting(&store, CONFIG_TOPIC).await;
+        let Some(dir) = stub_dir(&config).filter(|_| config.enabled) else {
+            continue;
+        };
+        let cache = app.state::<Arc<CasCache>>();
+        if let Err(e) = update(&store, &cache, &dir).await {
+            eprintln!("Failed to update search stubs: {e}");
+        }
+    }
+}
+
+/// Turns the OS search exporter on or off. While on, every current note has a small text
+/// stub (title, snippet and a `yaks://` link) in `dir`, which Spotlight, Windows Search and
+/// desktop indexers pick up. Turning it off removes the stubs.
+#[tauri::command]
+pub async fn configure_spotlight(
+    app: AppHandle,
+    store: State<'_, Store>,
+    enabled: bool,
+    dir: Option<String>,
+) -> Result<SpotlightStatus, String> {
+    let previous: SpotlightConfig = load_setting(&store, CONFIG_TOPIC).await;
+    let config = SpotlightConfig {
+        enabled,
+        dir: dir.or(previous.dir.clone()),
+    };
+    let target = stub_dir(&config).ok_or("No folder for search stubs")?;
+    let stubs = if enabled {
+        let cache = app.state::<Arc<CasCache>>();
+        stubs(&store, &cache).await
+    } else {
+        HashMap::new()
+    };
+    sync_dir(&target, &stubs)?;
+    // Moving the folder leaves nothing behind in the old one
+    if let Some(old) = stub_dir(&previous).filter(|old| *old != target && previous.enabled) {
+        sync_dir(&old, &HashMap::new())?;
+    }
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(SpotlightStatus {
+        enabled,
+        dir: Some(target.display().to_string()),
+        stubs: stubs.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_stubs_follow_notes() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().join("store"));
+        let cache = CasCache::new(1024 * 1024);
+        let stub_dir = dir.path().join("stubs");
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let note = append_frame(
+            &store,
+            "note.create",
+            Some(b"# Groceries\nmilk, eggs".as_slice()),
+            Some(json!({ "yak_id": yak_id })),
+        )
+        .await
+        .unwrap();
+
+        let report = update(&store, &cache, &stub_dir).await.unwrap();
+        assert_eq!(report.written, 1);
+        let name = format!("Groceries ({}).txt", note.id);
+        let stub = std::fs::read_to_string(stub_dir.join(&name)).unwrap();
+        assert!(stub.starts_with("Groceries\n"));
+        assert!(stub.contains("milk, eggs"));
+        assert!(stub.ends_with(&format!("yaks://note/{}\n", note.id)));
+
+        // Unchanged notes aren't rewritten
+        let report = update(&store, &cache, &stub_dir).await.unwrap();
+        assert_eq!(report.written, 0);
+
+        // An edit keeps the file name but links the latest revision
+        let edit = append_frame(
+            &store,
+            "note.edit",
+            Some(b"# Groceries\nmilk, eggs, bread".as_slice()),
+            Some(json!({ "yak_id": yak_id, "note_id": note.id.to_string() })),
+        )
+        .await
+        .unwrap();
+        let report = update(&store, &cache, &stub_dir).await.unwrap();
+        assert_eq!(report.written, 1);
+        let stub = std::fs::read_to_string(stub_dir.join(&name)).unwrap();
+        assert!(stub.contains(&format!("yaks://note/{}", edit.id)));
+
+        append_frame(
+            &store,
+            "note.delete",
+            None,
+            Some(json!({ "yak_id": yak_id, "note_id": edit.id.to_string() })),
+        )
+        .await
+        .unwrap();
+        let report = update(&store, &cache, &stub_dir).await.unwrap();
+        assert_eq!(report.removed, 1);
+        assert_eq!(std::fs::read_dir(&stub_dir).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,271 @@
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::State;
+use xs::store::Store;
+
+use super::{created_id, note_title, ExportReport};
+use crate::locks::LockState;
+use crate::projection::Projection;
+use crate::semantic::wiki_targets;
+use crate::{locks, read_all_frames, time};
+
+/// Plain tables with the content inlined, meant for reading with `sqlite3` or any tool
+/// that speaks SQL; unlike the read model they don't hold serialized projection state.
+const SCHEMA: &str = "
+CREATE TABLE yaks (
+    id TEXT PRIMARY KEY,
+    name TEXT,
+    created TEXT
+);
+CREATE TABLE notes (
+    id TEXT PRIMARY KEY,
+    yak_id TEXT NOT NULL REFERENCES yaks (id),
+    title TEXT NOT NULL,
+    content TEXT,
+    created TEXT,
+    updated TEXT,
+    pinned INTEGER NOT NULL,
+    archived INTEGER NOT NULL,
+    reminder TEXT,
+    properties TEXT NOT NULL,
+    color TEXT,
+    icon TEXT
+);
+CREATE INDEX notes_by_yak ON notes (yak_id);
+CREATE TABLE tags (
+    note_id TEXT NOT NULL REFERENCES notes (id),
+    tag TEXT NOT NULL,
+    PRIMARY KEY (note_id, tag)
+);
+CREATE INDEX tags_by_tag ON tags (tag);
+CREATE TABLE tasks (
+    id TEXT PRIMARY KEY,
+    yak_id TEXT NOT NULL REFERENCES yaks (id),
+    note_id TEXT REFERENCES notes (id),
+    text TEXT,
+    done INTEGER NOT NULL,
+    reminder TEXT,
+    created TEXT
+);
+CREATE INDEX tasks_by_yak ON tasks (yak_id, done);
+CREATE TABLE links (
+    note_id TEXT NOT NULL REFERENCES notes (id),
+    target TEXT NOT NULL,
+    target_note_id TEXT REFERENCES notes (id),
+    PRIMARY KEY (note_id, target)
+);
+CREATE INDEX links_by_target ON links (target_note_id);
+";
+
+fn created(id: &str) -> Option<String> {
+    time::from_id(id).map(|at| at.to_rfc3339())
+}
+
+/// Writes the current notes, their tags and wiki links, and tasks of every yak to a new
+/// database at `path`. Sealed content is left out. Returns how many notes were written.
+fn write(projection: &Projection, path: &Path) -> rusqlite::Result<usize> {
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+    let tx = conn.transaction()?;
+    for yak in projection.yaks.values() {
+        tx.execute(
+            "INSERT INTO yaks (id, name, created) VALUES (?1, ?2, ?3)",
+            params![yak.id, yak.name, created(&yak.id)],
+        )?;
+    }
+
+    let notes: Vec<_> = projection
+        .yaks
+        .keys()
+        .flat_map(|yak_id| projection.current_notes(yak_id))
+        .collect();
+    let by_title: HashMap<String, &str> = notes
+        .iter()
+        .map(|note| (note_title(note).to_lowercase(), note.id.as_str()))
+        .collect();
+    for note in &notes {
+        let content = note
+            .content
+            .as_deref()
+            .filter(|content| !locks::is_sealed(content));
+        tx.execute(
+            "INSERT INTO notes (id, yak_id, title, content, created, updated, pinned, archived,
+                 reminder, properties, color, icon)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                note.id,
+                note.yak_id,
+                note_title(note),
+                content,
+                created(&created_id(projection, note)),
+                created(&note.id),
+                note.pinned,
+                note.archived,
+                note.reminder,
+                serde_json::to_string(&note.properties).unwrap_or_default(),
+                note.label.as_ref().and_then(|label| label.color.as_deref()),
+                note.label.as_ref().and_then(|label| label.icon.as_deref()),
+            ],
+        )?;
+        for tag in &note.tags {
+            tx.execute(
+                "INSERT OR IGNORE INTO tags (note_id, tag) VALUES (?1, ?2)",
+                params![note.id, tag],
+            )?;
+        }
+        for target in content.into_iter().flat_map(wiki_targets) {
+            tx.execute(
+                "INSERT OR IGNORE INTO links (note_id, target, target_note_id)
+                 VALUES (?1, ?2, ?3)",
+                params![note.id, target, by_title.get(&target).copied()],
+            )?;
+        }
+    }
+
+    for task in projection.tasks.values() {
+        let text = task
+            .content
+            .as_deref()
+            .filter(|content| !locks::is_sealed(content));
+        tx.execute(
+            "INSERT INTO tasks (id, yak_id, note_id, text, done, reminder, created)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                task.id,
+                task.yak_id,
+                task.note_id.as_ref().map(|id| projection.resolve(id)),
+                text,
+                task.done,
+                task.reminder,
+                created(&task.id),
+            ],
+        )?;
+    }
+    tx.commit()?;
+    Ok(notes.len())
+}
+
+/// Exports every yak to a standalone SQLite database at `path`: tables of yaks, notes (with
+/// content), tags, tasks and wiki links, for querying with standard tools. An existing file
+/// at `path` is replaced.
+#[tauri::command]
+pub async fn export_sqlite(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    path: String,
+) -> Result<ExportReport, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+    projection.resolve_content(&store).await;
+    locks.redact(&mut projection, false);
+    let target = PathBuf::from(&path);
+    // Written beside the target and moved into place, so a failed export leaves no half a
+    // database behind and an old file's tables never mix with the new ones
+    let partial = target.with_extension("partial");
+    let notes = tokio::task::spawn_blocking(move || {
+        let _ = std::fs::remove_file(&partial);
+        let written = write(&projection, &partial).map_err(|e| {
+            let _ = std::fs::remove_file(&partial);
+            format!("Failed to write {}: {e}", partial.display())
+        })?;
+        std::fs::rename(&partial, &target)
+            .map_err(|e| format!("Failed to write {}: {e}", target.display()))?;
+        Ok::<_, String>(written)
+    })
+    .await
+    .map_err(|e| format!("Failed to export: {e}"))??;
+    Ok(ExportReport {
+        path,
+        notes,
+        attachments: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use crate::import::{add_note, add_tag, add_task, create_yak};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_write() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Garden").await.unwrap();
+        let beds = add_note(
+            &store,
+            &yak_id,
+            "# Beds\nSee [[Compost]] and [[Shed]]",
+            json!({}),
+        )
+        .await
+        .unwrap();
+        let beds_id = beds.id.to_string();
+        let compost = add_note(&store, &yak_id, "# Compost\nTurn weekly", json!({}))
+            .await
+            .unwrap();
+        add_tag(&store, &yak_id, &beds_id, "spring").await.unwrap();
+        add_task(&store, &yak_id, Some(&beds_id), "Dig beds", true)
+            .await
+            .unwrap();
+        let meta = json!({ "yak_id": yak_id, "note_id": beds_id });
+        let edit = append_frame(
+            &store,
+            "note.edit",
+            Some(b"# Beds\nSee [[Compost]] and [[Shed]], then sow".as_slice()),
+            Some(meta),
+        )
+        .await
+        .unwrap();
+
+        let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+        projection.resolve_content(&store).await;
+        let export = tempdir().unwrap();
+        let path = export.path().join("yaks.sqlite");
+        assert_eq!(write(&projection, &path).unwrap(), 2);
+
+        let conn = Connection::open(&path).unwrap();
+        let (title, content, created): (String, String, String) = conn
+            .query_row(
+                "SELECT title, content, created FROM notes WHERE id = ?1",
+                [edit.id.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(title, "Beds");
+        assert!(content.ends_with("then sow"));
+        assert_eq!(Some(created), super::created(&beds_id));
+        let tag: String = conn
+            .query_row(
+                "SELECT tag FROM tags JOIN notes ON notes.id = tags.note_id",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(tag, "spring");
+        let (note_id, done): (String, bool) = conn
+            .query_row("SELECT note_id, done FROM tasks", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((note_id, done), (edit.id.to_string(), true));
+        let mut links = conn
+            .prepare("SELECT target, target_note_id FROM links ORDER BY target")
+            .unwrap();
+        let links: Vec<(String, Option<String>)> = links
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            links,
+            vec![
+                ("compost".to_string(), Some(compost.id.to_string())),
+                ("shed".to_string(), None),
+            ]
+        );
+    }
+}
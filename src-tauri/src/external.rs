@@ -0,0 +1,143 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::location::StoreLocation;
+use crate::read_model::ReadModel;
+use crate::windows::emit_frames;
+use crate::{autocomplete, read_all_frames, store_lock};
+
+/// Where the store keeps its frames; content in the CAS only matters once a frame points at it.
+const FRAMES_DIR: &str = "fjall";
+const POLL: Duration = Duration::from_secs(2);
+
+/// Sent as "store-changed" when frames another process appended have been folded in.
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreChanged {
+    pub frames: usize,
+}
+
+/// File count, total size and latest modification under `dir`: it moves whenever another
+/// process writes to the store.
+fn fingerprint(dir: &Path) -> (usize, u64, Option<SystemTime>) {
+    let mut fingerprint = (0, 0, None);
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+                continue;
+            }
+            fingerprint.0 += 1;
+            fingerprint.1 += metadata.len();
+            fingerprint.2 = fingerprint.2.max(metadata.modified().ok());
+        }
+    }
+    fingerprint
+}
+
+/// Frames past `cursor`, oldest first.
+fn after(frames: Vec<Frame>, cursor: Option<scru128::Scru128Id>) -> Vec<Frame> {
+    frames
+        .into_iter()
+        .filter(|frame| cursor.map_or(true, |cursor| frame.id > cursor))
+        .collect()
+}
+
+/// Every frame in the store at `path`, as it is on disk now.
+async fn read_fresh(path: PathBuf) -> (Store, Vec<Frame>) {
+    let store = Store::new(path);
+    let read_options = ReadOptions::builder().follow(FollowOption::Off).build();
+    let mut rx = store.read(read_options).await;
+    let mut frames = Vec::new();
+    while let Some(frame) = rx.recv().await {
+        frames.push(frame);
+    }
+    (store, frames)
+}
+
+/// While another process holds the store (the `xs` CLI, `xs serve` or another Yaks), polls
+/// its files for changes and folds the frames it appended into the read model and link
+/// index, sending them to windows as if appended here. A store this app writes to has no
+/// other writers, so there's nothing to watch.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    if !store_lock::is_read_only() {
+        return;
+    }
+    let path = app.state::<StoreLocation>().0.clone();
+    let dir = path.join(FRAMES_DIR);
+    let mut cursor = read_all_frames(&store).await.last().map(|frame| frame.id);
+    let mut last = fingerprint(&dir);
+    loop {
+        tokio::time::sleep(POLL).await;
+        let dir = dir.clone();
+        let Ok(current) = tokio::task::spawn_blocking(move || fingerprint(&dir)).await else {
+            continue;
+        };
+        if current == last {
+            continue;
+        }
+        last = current;
+
+        let (fresh, frames) = read_fresh(path.clone()).await;
+        let frames = after(frames, cursor);
+        let Some(newest) = frames.last() else {
+            continue;
+        };
+        cursor = Some(newest.id);
+        if let Err(e) = app
+            .state::<ReadModel>()
+            .apply_external(&fresh, &frames)
+            .await
+        {
+            eprintln!("Failed to fold in external changes: {e}");
+        }
+        autocomplete::rebuild(&app, &fresh).await;
+        let _ = emit_frames(&app, &frames);
+        let changed = StoreChanged {
+            frames: frames.len(),
+        };
+        if let Err(e) = app.emit("store-changed", &changed) {
+            eprintln!("Failed to emit store change: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_fingerprint_and_cursor() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let first = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let frames_dir = dir.path().join(FRAMES_DIR);
+        let before = fingerprint(&frames_dir);
+        assert!(before.0 > 0);
+
+        std::fs::write(frames_dir.join("written-elsewhere"), b"frames").unwrap();
+        assert_ne!(fingerprint(&frames_dir), before);
+
+        let second = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let frames = read_all_frames(&store).await;
+        let new = after(frames.clone(), Some(first.id));
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].id, second.id);
+        assert_eq!(after(frames, None).len(), 2);
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+use xs::store::{Frame, Store};
+
+use crate::{append_frame, read_all_frames};
+
+mod ocr;
+mod pdf;
+mod transcribe;
+
+pub(crate) use ocr::watch as watch_ocr;
+pub use ocr::{configure_ocr, ocr_attachment};
+pub use pdf::get_pdf_page;
+pub(crate) use pdf::watch as watch_pdf;
+pub(crate) use transcribe::watch as watch_transcription;
+pub(crate) use transcribe::Segment;
+pub use transcribe::{configure_transcription, transcribe_attachment};
+
+/// An attachment that text can be extracted from.
+#[derive(Debug, Clone)]
+pub(crate) struct Source {
+    pub attachment_id: String,
+    pub yak_id: String,
+    pub note_id: String,
+    pub hash: ssri::Integrity,
+    pub mime: String,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+pub(crate) fn source(frame: &Frame) -> Option<Source> {
+    if !frame.topic.starts_with("attachment.") {
+        return None;
+    }
+    Some(Source {
+        attachment_id: frame.id.to_string(),
+        yak_id: meta_str(frame, "yak_id")?.to_string(),
+        note_id: meta_str(frame, "note_id")?.to_string(),
+        hash: frame.hash.clone()?,
+        mime: meta_str(frame, "mime").unwrap_or_default().to_string(),
+    })
+}
+
+/// Attachments accepted by `wanted` that don't yet have a `kind` extract, oldest first.
+pub(crate) fn pending(
+    frames: &[Frame],
+    kind: &str,
+    wanted: impl Fn(&Source) -> bool,
+) -> Vec<Source> {
+    let done: HashSet<&str> = frames
+        .iter()
+        .filter(|frame| frame.topic == "text.extract" && meta_str(frame, "kind") == Some(kind))
+        .filter_map(|frame| meta_str(frame, "attachment_id"))
+        .collect();
+    frames
+        .iter()
+        .filter_map(source)
+        .filter(|source| !done.contains(source.attachment_id.as_str()) && wanted(source))
+        .collect()
+}
+
+pub(crate) async fn find_source(store: &Store, attachment_id: &str) -> Result<Source, String> {
+    read_all_frames(store)
+        .await
+        .iter()
+        .find(|frame| frame.id.to_string() == attachment_id)
+        .and_then(source)
+        .ok_or_else(|| format!("Attachment not found: {attachment_id}"))
+}
+
+/// Appends text derived from an attachment as a `text.extract` frame linked to it (and its
+/// note), which puts the text in search. `extra` adds to the meta, e.g. the PDF page.
+pub(crate) async fn append_extract(
+    store: &Store,
+    source: &Source,
+    kind: &str,
+    extra: serde_json::Value,
+    text: &str,
+) -> Result<Frame, String> {
+    let mut meta = match extra {
+        serde_json::Value::Object(fields) => fields,
+        _ => serde_json::Map::new(),
+    };
+    meta.insert("yak_id".to_string(), source.yak_id.clone().into());
+    meta.insert("note_id".to_string(), source.note_id.clone().into());
+    meta.insert(
+        "attachment_id".to_string(),
+        source.attachment_id.clone().into(),
+    );
+    meta.insert("kind".to_string(), kind.into());
+    append_frame(
+        store,
+        "text.extract",
+        Some(text.as_bytes()),
+        Some(meta.into()),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{add_note, create_yak};
+    use crate::projection::Projection;
+    use crate::search::{keyword_search, Match};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_extracts_are_searchable() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Memos").await.unwrap();
+        let note = add_note(&store, &yak_id, "Voice memo", serde_json::json!({}))
+            .await
+            .unwrap();
+        let meta = serde_json::json!({
+            "yak_id": yak_id,
+            "note_id": note.id.to_string(),
+            "mime": "audio/flac",
+        });
+        append_frame(
+            &store,
+            "attachment.audio",
+            Some(b"audio".as_slice()),
+            Some(meta),
+        )
+        .await
+        .unwrap();
+
+        let frames = read_all_frames(&store).await;
+        let sources = pending(&frames, "transcript", |s| s.mime.starts_with("audio/"));
+        assert_eq!(sources.len(), 1);
+        append_extract(
+            &store,
+            &sources[0],
+            "transcript",
+            serde_json::json!({}),
+            "remember the milk",
+        )
+        .await
+        .unwrap();
+
+        let frames = read_all_frames(&store).await;
+        assert!(pending(&frames, "transcript", |_| true).is_empty());
+        let mut projection = Projection::from_frames(&frames);
+        projection.resolve_content(&store).await;
+        let hits = keyword_search(&projection, "milk", None, Match::All, 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, note.id.to_string());
+    }
+}
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use tokio::sync::mpsc;
+use xs::store::{FollowOption, ReadOptions, Store};
+
+use super::{append_extract, find_source, pending, Source};
+use crate::read_all_frames;
+use crate::settings::{load_setting, save_setting};
+use crate::windows::emit_frame;
+
+const CONFIG_TOPIC: &str = "extract.ocr";
+const KIND: &str = "ocr";
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+fn default_command() -> String {
+    "tesseract".to_string()
+}
+
+fn default_languages() -> String {
+    "eng".to_string()
+}
+
+/// OCR runs the Tesseract command line tool, which has to be installed separately.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrConfig {
+    pub enabled: bool,
+    #[serde(default = "default_command")]
+    pub command: String,
+    /// Tesseract language codes, joined with `+`
+    #[serde(default = "default_languages")]
+    pub languages: String,
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: default_command(),
+            languages: default_languages(),
+        }
+    }
+}
+
+fn is_image(source: &Source) -> bool {
+    matches!(
+        source.mime.as_str(),
+        "image/png" | "image/jpeg" | "image/gif" | "image/bmp" | "image/tiff" | "image/webp"
+    )
+}
+
+async fn recognize(config: &OcrConfig, store: &Store, source: &Source) -> Result<String, String> {
+    let image = store
+        .cas_read(&source.hash)
+        .await
+        .map_err(|e| format!("Failed to read image: {e}"))?;
+    let path = std::env::temp_dir().join(format!("yaks-ocr-{}", scru128::new()));
+    tokio::fs::write(&path, image)
+        .await
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+
+    let output = tokio::process::Command::new(&config.command)
+        .arg(&path)
+        .arg("stdout")
+        .args(["-l", &config.languages])
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(TIMEOUT, output).await;
+    let _ = tokio::fs::remove_file(&path).await;
+    let output = output
+        .map_err(|_| format!("OCR timed out after {}s", TIMEOUT.as_secs()))?
+        .map_err(|e| format!("Failed to run {}: {e}", config.command))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            config.command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+async fn process(
+    app: &AppHandle,
+    store: &Store,
+    config: &OcrConfig,
+    source: &Source,
+) -> Result<(), String> {
+    let text = recognize(config, store, source).await?;
+    // An empty extract still records that the image was processed
+    let frame = append_extract(store, source, KIND, serde_json::json!({}), &text).await?;
+    let _ = emit_frame(&app, &frame);
+    Ok(())
+}
+
+/// Runs OCR over image attachments that haven't been processed yet, one at a time.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let (kick, mut kicked) = mpsc::channel::<()>(1);
+    let follower_store = store.clone();
+    tokio::spawn(async move {
+        let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+        let mut rx = follower_store.read(read_options).await;
+        let mut caught_up = false;
+        while let Some(frame) = rx.recv().await {
+            if frame.topic == "xs.threshold" {
+                caught_up = true;
+                let _ = kick.try_send(());
+            } else if caught_up && (frame.topic == "attachment.add" || frame.topic == CONFIG_TOPIC)
+            {
+                let _ = kick.try_send(());
+            }
+        }
+    });
+
+    while kicked.recv().await.is_some() {
+        let config: OcrConfig = load_setting(&store, CONFIG_TOPIC).await;
+        if !config.enabled {
+            continue;
+        }
+        let frames = read_all_frames(&store).await;
+        for source in pending(&frames, KIND, is_image) {
+            if let Err(e) = process(&app, &store, &config, &source).await {
+                eprintln!("Failed to OCR {}: {e}", source.attachment_id);
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn configure_ocr(store: State<'_, Store>, config: OcrConfig) -> Result<(), String> {
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(())
+}
+
+/// Runs OCR on one image attachment now, whether or not background OCR is on.
+#[tauri::command]
+pub async fn ocr_attachment(
+    store: State<'_, Store>,
+    app: AppHandle,
+    attachment_id: String,
+) -> Result<(), String> {
+    let source = find_source(&store, &attachment_id).await?;
+    if !is_image(&source) {
+        return Err(format!("Not an image attachment: {attachment_id}"));
+    }
+    let config: OcrConfig = load_setting(&store, CONFIG_TOPIC).await;
+    process(&app, &store, &config, &source).await
+}
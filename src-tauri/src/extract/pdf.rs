@@ -0,0 +1,202 @@
+use std::io::Cursor;
+use tauri::AppHandle;
+use tokio::sync::mpsc;
+use xs::store::{FollowOption, ReadOptions, Store};
+
+use super::{append_extract, pending, Source};
+use crate::read_all_frames;
+use crate::windows::emit_frame;
+
+const KIND: &str = "pdf";
+/// Width rendered pages are scaled to, in pixels
+const RENDER_WIDTH: i32 = 1200;
+
+fn is_pdf(source: &Source) -> bool {
+    source.mime == "application/pdf"
+}
+
+/// The text of each page, in order.
+fn page_texts(pdf: &[u8]) -> Result<Vec<String>, String> {
+    let document = lopdf::Document::load_mem(pdf).map_err(|e| format!("Invalid PDF: {e}"))?;
+    Ok(document
+        .get_pages()
+        .keys()
+        .map(|&number| {
+            document
+                .extract_text(&[number])
+                .map(|text| text.trim().to_string())
+                .unwrap_or_default()
+        })
+        .collect())
+}
+
+/// Extracts a PDF's text into one `text.extract` frame per page with text. A PDF without any
+/// text (e.g. a scan) gets a single empty extract so it isn't retried.
+async fn process(app: &AppHandle, store: &Store, source: &Source) -> Result<(), String> {
+    let pdf = store
+        .cas_read(&source.hash)
+        .await
+        .map_err(|e| format!("Failed to read PDF: {e}"))?;
+    let pages = tokio::task::spawn_blocking(move || page_texts(&pdf))
+        .await
+        .map_err(|e| format!("PDF extraction failed: {e}"))??;
+
+    let mut pages: Vec<(u64, String)> = (1..)
+        .zip(pages)
+        .filter(|(_, text)| !text.is_empty())
+        .collect();
+    if pages.is_empty() {
+        pages.push((1, String::new()));
+    }
+    for (page, text) in pages {
+        let frame = append_extract(
+            store,
+            source,
+            KIND,
+            serde_json::json!({ "page": page }),
+            &text,
+        )
+        .await?;
+        let _ = emit_frame(app, &frame);
+    }
+    Ok(())
+}
+
+/// Extracts text from PDF attachments as they arrive (and any missed earlier).
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let (kick, mut kicked) = mpsc::channel::<()>(1);
+    let follower_store = store.clone();
+    tokio::spawn(async move {
+        let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+        let mut rx = follower_store.read(read_options).await;
+        let mut caught_up = false;
+        while let Some(frame) = rx.recv().await {
+            if frame.topic == "xs.threshold" {
+                caught_up = true;
+                let _ = kick.try_send(());
+            } else if caught_up && frame.topic == "attachment.add" {
+                let _ = kick.try_send(());
+            }
+        }
+    });
+
+    while kicked.recv().await.is_some() {
+        let frames = read_all_frames(&store).await;
+        for source in pending(&frames, KIND, is_pdf) {
+            if let Err(e) = process(&app, &store, &source).await {
+                eprintln!("Failed to extract text from {}: {e}", source.attachment_id);
+            }
+        }
+    }
+}
+
+/// Renders a page (1-based) to PNG with pdfium, which is loaded from the system at runtime.
+fn render_page(pdf: &[u8], page: u16) -> Result<Vec<u8>, String> {
+    use pdfium_render::prelude::*;
+
+    let bindings =
+        Pdfium::bind_to_system_library().map_err(|e| format!("pdfium is not available: {e}"))?;
+    let pdfium = Pdfium::new(bindings);
+    let document = pdfium
+        .load_pdf_from_byte_slice(pdf, None)
+        .map_err(|e| format!("Invalid PDF: {e}"))?;
+    let page = document
+        .pages()
+        .get(page.checked_sub(1).ok_or("Pages are numbered from 1")?)
+        .map_err(|e| format!("No such page: {e}"))?;
+    let image = page
+        .render_with_config(&PdfRenderConfig::new().set_target_width(RENDER_WIDTH))
+        .map_err(|e| format!("Failed to render page: {e}"))?
+        .as_image();
+    let mut png = Cursor::new(Vec::new());
+    image
+        .write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode page: {e}"))?;
+    Ok(png.into_inner())
+}
+
+/// A PDF page rendered as PNG bytes, for previews.
+#[tauri::command]
+pub async fn get_pdf_page(
+    store: tauri::State<'_, Store>,
+    hash: String,
+    page: u16,
+) -> Result<tauri::ipc::Response, String> {
+    let integrity = hash
+        .parse::<ssri::Integrity>()
+        .map_err(|e| format!("Invalid hash format: {e}"))?;
+    let pdf = crate::integrity::read(&store, &integrity).await?;
+    let png = tokio::task::spawn_blocking(move || render_page(&pdf, page))
+        .await
+        .map_err(|e| format!("Failed to render page: {e}"))??;
+    Ok(tauri::ipc::Response::new(png))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Object, Stream};
+
+    /// A PDF with one line of text on each page.
+    fn pdf(pages: &[&str]) -> Vec<u8> {
+        let mut doc = lopdf::Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+        let kids: Vec<Object> = pages
+            .iter()
+            .map(|text| {
+                let content = Content {
+                    operations: vec![
+                        Operation::new("BT", vec![]),
+                        Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                        Operation::new("Td", vec![72.into(), 720.into()]),
+                        Operation::new("Tj", vec![Object::string_literal(*text)]),
+                        Operation::new("ET", vec![]),
+                    ],
+                };
+                let content_id =
+                    doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "Contents" => content_id,
+                    "Resources" => resources_id,
+                    "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                })
+                .into()
+            })
+            .collect();
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_page_texts() {
+        let texts = page_texts(&pdf(&["First page", "Second page"])).unwrap();
+        assert_eq!(texts.len(), 2);
+        assert!(texts[0].contains("First page"));
+        assert!(texts[1].contains("Second page"));
+    }
+}
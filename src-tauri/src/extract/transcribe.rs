@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use tokio::sync::mpsc;
+use xs::store::{FollowOption, ReadOptions, Store};
+
+use super::{append_extract, find_source, pending, Source};
+use crate::read_all_frames;
+use crate::settings::{load_setting, save_setting};
+use crate::windows::emit_frame;
+
+const CONFIG_TOPIC: &str = "ai.transcribe";
+const KIND: &str = "transcript";
+const TIMEOUT: Duration = Duration::from_secs(600);
+
+fn default_base_url() -> String {
+    "http://localhost:8000".to_string()
+}
+
+fn default_model() -> String {
+    "whisper-1".to_string()
+}
+
+/// An OpenAI-compatible transcription endpoint (`/v1/audio/transcriptions`), such as
+/// whisper.cpp's server or faster-whisper-server. Off until enabled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscribeConfig {
+    pub enabled: bool,
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    #[serde(default = "default_model")]
+    pub model: String,
+    pub api_key: Option<String>,
+}
+
+impl Default for TranscribeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            base_url: default_base_url(),
+            model: default_model(),
+            api_key: None,
+        }
+    }
+}
+
+/// A stretch of a transcript, timed from the start of the recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Segment {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+struct Transcript {
+    text: String,
+    segments: Vec<Segment>,
+}
+
+fn is_audio(source: &Source) -> bool {
+    source.mime.starts_with("audio/")
+}
+
+async fn transcribe(
+    config: &TranscribeConfig,
+    store: &Store,
+    source: &Source,
+) -> Result<Transcript, String> {
+    let audio = store
+        .cas_read(&source.hash)
+        .await
+        .map_err(|e| format!("Failed to read audio: {e}"))?;
+    let file = reqwest::multipart::Part::bytes(audio)
+        .file_name("audio")
+        .mime_str(&source.mime)
+        .map_err(|e| format!("Invalid audio type: {e}"))?;
+    let form = reqwest::multipart::Form::new()
+        .part("file", file)
+        .text("model", config.model.clone())
+        .text("response_format", "verbose_json");
+
+    let url = format!(
+        "{}/v1/audio/transcriptions",
+        config.base_url.trim_end_matches('/')
+    );
+    let mut request = reqwest::Client::new()
+        .post(&url)
+        .timeout(TIMEOUT)
+        .multipart(form);
+    if let Some(key) = &config.api_key {
+        request = request.bearer_auth(key);
+    }
+    let response: serde_json::Value = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to reach {url}: {e}"))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid transcription response: {e}"))?;
+    let text = response
+        .get("text")
+        .and_then(|text| text.as_str())
+        .map(|text| text.trim().to_string())
+        .ok_or_else(|| "Transcription response has no text".to_string())?;
+    // Segments are in seconds; servers that only return text leave them out
+    let ms = |segment: &serde_json::Value, key: &str| {
+        segment
+            .get(key)
+            .and_then(|s| s.as_f64())
+            .map(|s| (s * 1000.0) as u64)
+    };
+    let segments = response
+        .get("segments")
+        .and_then(|segments| segments.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|segment| {
+            Some(Segment {
+                start_ms: ms(segment, "start")?,
+                end_ms: ms(segment, "end")?,
+                text: segment.get("text")?.as_str()?.trim().to_string(),
+            })
+        })
+        .collect();
+    Ok(Transcript { text, segments })
+}
+
+async fn process(
+    app: &AppHandle,
+    store: &Store,
+    config: &TranscribeConfig,
+    source: &Source,
+) -> Result<(), String> {
+    let transcript = transcribe(config, store, source).await?;
+    let extra = serde_json::json!({ "segments": transcript.segments });
+    let frame = append_extract(store, source, KIND, extra, &transcript.text).await?;
+    let _ = emit_frame(&app, &frame);
+    Ok(())
+}
+
+/// Transcribes audio attachments that don't have a transcript yet, one at a time: at
+/// startup, whenever a new one arrives, and when transcription is enabled.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let (kick, mut kicked) = mpsc::channel::<()>(1);
+    let follower_store = store.clone();
+    tokio::spawn(async move {
+        let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+        let mut rx = follower_store.read(read_options).await;
+        let mut caught_up = false;
+        while let Some(frame) = rx.recv().await {
+            if frame.topic == "xs.threshold" {
+                caught_up = true;
+                let _ = kick.try_send(());
+            } else if caught_up
+                && (frame.topic == "attachment.audio" || frame.topic == CONFIG_TOPIC)
+            {
+                let _ = kick.try_send(());
+            }
+        }
+    });
+
+    while kicked.recv().await.is_some() {
+        let config: TranscribeConfig = load_setting(&store, CONFIG_TOPIC).await;
+        if !config.enabled {
+            continue;
+        }
+        let frames = read_all_frames(&store).await;
+        for source in pending(&frames, KIND, is_audio) {
+            if let Err(e) = process(&app, &store, &config, &source).await {
+                eprintln!("Failed to transcribe {}: {e}", source.attachment_id);
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn configure_transcription(
+    store: State<'_, Store>,
+    config: TranscribeConfig,
+) -> Result<(), String> {
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(())
+}
+
+/// Transcribes one audio attachment now, whether or not background transcription is on.
+#[tauri::command]
+pub async fn transcribe_attachment(
+    store: State<'_, Store>,
+    app: AppHandle,
+    attachment_id: String,
+) -> Result<(), String> {
+    let source = find_source(&store, &attachment_id).await?;
+    if !is_audio(&source) {
+        return Err(format!("Not an audio attachment: {attachment_id}"));
+    }
+    let config: TranscribeConfig = load_setting(&store, CONFIG_TOPIC).await;
+    process(&app, &store, &config, &source).await
+}
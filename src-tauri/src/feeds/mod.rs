@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::rates::RatesState;
+use crate::shutdown::begin_write;
+use crate::windows::emit_frame;
+use crate::{append_frame, health, html, read_all_frames, web};
+
+mod parse;
+
+use parse::{parse_feed, FeedItem};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// A subscription, registered with a `feed.add` frame and dropped by `feed.remove`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Feed {
+    pub id: String,
+    pub url: String,
+    pub yak_id: String,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+fn feeds(frames: &[Frame]) -> Vec<Feed> {
+    let mut feeds: Vec<Feed> = Vec::new();
+    for frame in frames {
+        match frame.topic.as_str() {
+            "feed.add" => {
+                if let (Some(url), Some(yak_id)) =
+                    (meta_str(frame, "url"), meta_str(frame, "yak_id"))
+                {
+                    feeds.push(Feed {
+                        id: frame.id.to_string(),
+                        url: url.to_string(),
+                        yak_id: yak_id.to_string(),
+                    });
+                }
+            }
+            "feed.remove" => {
+                let feed_id = meta_str(frame, "feed_id");
+                feeds.retain(|feed| Some(feed.id.as_str()) != feed_id);
+            }
+            _ => {}
+        }
+    }
+    feeds
+}
+
+/// Item guids already appended, per feed, so restarts don't duplicate items.
+fn seen_guids(frames: &[Frame]) -> HashMap<String, HashSet<String>> {
+    let mut seen: HashMap<String, HashSet<String>> = HashMap::new();
+    for frame in frames.iter().filter(|frame| frame.topic == "feed.item") {
+        if let (Some(feed_id), Some(guid)) = (meta_str(frame, "feed_id"), meta_str(frame, "guid")) {
+            seen.entry(feed_id.to_string())
+                .or_default()
+                .insert(guid.to_string());
+        }
+    }
+    seen
+}
+
+fn item_content(item: &FeedItem) -> String {
+    let title = item.title.as_deref().unwrap_or("Untitled");
+    let mut content = match &item.link {
+        Some(link) => format!("[{title}]({link})"),
+        None => title.to_string(),
+    };
+    if let Some(summary) = item.summary.as_deref().map(html::to_text) {
+        content.push_str("\n\n");
+        content.push_str(&summary);
+    }
+    content
+}
+
+/// Fetches a feed and appends its unseen items as `feed.item` frames, oldest first.
+async fn poll(
+    app: &AppHandle,
+    store: &Store,
+    feed: &Feed,
+    seen: &HashSet<String>,
+) -> Result<usize, String> {
+    let (_, body) = web::fetch_page(&feed.url).await?;
+    let items = parse_feed(&body)?;
+    let _guard = begin_write(app)?;
+    let mut added = 0;
+    for item in items.iter().rev().filter(|item| !seen.contains(&item.guid)) {
+        if app.state::<RatesState>().is_paused("feeds") {
+            break;
+        }
+        let meta = serde_json::json!({
+            "yak_id": feed.yak_id,
+            "feed_id": feed.id,
+            "guid": item.guid,
+            "title": item.title,
+            "link": item.link,
+            "summary": item.summary,
+            "published": item.published,
+        });
+        let content = item_content(item);
+        let frame = append_frame(store, "feed.item", Some(content.as_bytes()), Some(meta)).await?;
+        let _ = emit_frame(&app, &frame);
+        added += 1;
+    }
+    Ok(added)
+}
+
+/// Polls every registered feed on an interval.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        if app.state::<RatesState>().is_paused("feeds") {
+            continue;
+        }
+        let frames = read_all_frames(&store).await;
+        let seen = seen_guids(&frames);
+        for feed in feeds(&frames) {
+            let seen = seen.get(&feed.id).cloned().unwrap_or_default();
+            match poll(&app, &store, &feed, &seen).await {
+                Ok(_) => health::ok(&app, "feeds"),
+                Err(e) => {
+                    health::error(&app, "feeds", &format!("{}: {e}", feed.url));
+                    eprintln!("Failed to poll feed {}: {e}", feed.url);
+                }
+            }
+        }
+    }
+}
+
+/// Subscribes a yak to an RSS or Atom feed and pulls in its current items.
+#[tauri::command]
+pub async fn add_feed(
+    store: State<'_, Store>,
+    app: AppHandle,
+    url: String,
+    yak_id: String,
+) -> Result<Feed, String> {
+    let _guard = begin_write(&app)?;
+    // Check the feed parses before registering it
+    let (_, body) = web::fetch_page(&url).await?;
+    parse_feed(&body)?;
+
+    let meta = serde_json::json!({ "url": url, "yak_id": yak_id });
+    let frame = append_frame(&store, "feed.add", None, Some(meta)).await?;
+    let feed = Feed {
+        id: frame.id.to_string(),
+        url,
+        yak_id,
+    };
+    poll(&app, &store, &feed, &HashSet::new()).await?;
+    Ok(feed)
+}
+
+#[tauri::command]
+pub async fn remove_feed(
+    store: State<'_, Store>,
+    app: AppHandle,
+    feed_id: String,
+) -> Result<(), String> {
+    let _guard = begin_write(&app)?;
+    let meta = serde_json::json!({ "feed_id": feed_id });
+    append_frame(&store, "feed.remove", None, Some(meta)).await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_feeds(store: State<'_, Store>) -> Result<Vec<Feed>, String> {
+    Ok(feeds(&read_all_frames(&store).await))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_feeds_and_seen_guids() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let add = |url: &str| serde_json::json!({ "url": url, "yak_id": "yak" });
+        let kept = append_frame(&store, "feed.add", None, Some(add("a")))
+            .await
+            .unwrap();
+        let dropped = append_frame(&store, "feed.add", None, Some(add("b")))
+            .await
+            .unwrap();
+        let remove = serde_json::json!({ "feed_id": dropped.id.to_string() });
+        append_frame(&store, "feed.remove", None, Some(remove))
+            .await
+            .unwrap();
+        let item = serde_json::json!({ "feed_id": kept.id.to_string(), "guid": "g1" });
+        append_frame(&store, "feed.item", None, Some(item))
+            .await
+            .unwrap();
+
+        let frames = read_all_frames(&store).await;
+        let feeds = feeds(&frames);
+        assert_eq!(feeds.len(), 1);
+        assert_eq!(feeds[0].url, "a");
+        assert!(seen_guids(&frames)[&kept.id.to_string()].contains("g1"));
+    }
+}
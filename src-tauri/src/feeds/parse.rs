@@ -0,0 +1,148 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// An entry from an RSS or Atom feed.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct FeedItem {
+    pub guid: String,
+    pub title: Option<String>,
+    pub link: Option<String>,
+    pub summary: Option<String>,
+    pub published: Option<String>,
+}
+
+fn attr(element: &BytesStart, name: &str) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name.as_bytes())
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.into_owned())
+}
+
+/// Atom `<link>`s carry the URL as an attribute; only the page link (`alternate`) counts.
+fn atom_link(element: &BytesStart) -> Option<String> {
+    let rel = attr(element, "rel");
+    if rel.as_deref().map_or(true, |rel| rel == "alternate") {
+        attr(element, "href")
+    } else {
+        None
+    }
+}
+
+fn date(value: &str) -> Option<String> {
+    chrono::DateTime::parse_from_rfc2822(value)
+        .or_else(|_| chrono::DateTime::parse_from_rfc3339(value))
+        .ok()
+        .map(|date| date.to_rfc3339())
+}
+
+/// Parses the items of an RSS 2.0 or Atom feed. Items without a guid/id are keyed by their
+/// link, then title.
+pub(crate) fn parse_feed(xml: &str) -> Result<Vec<FeedItem>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut items = Vec::new();
+    let mut item: Option<FeedItem> = None;
+    let mut text = String::new();
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Invalid feed: {e}"))?;
+        match event {
+            Event::Start(element) => {
+                match element.local_name().as_ref() {
+                    b"item" | b"entry" => item = Some(FeedItem::default()),
+                    b"link" => {
+                        if let Some(item) = item.as_mut().filter(|item| item.link.is_none()) {
+                            item.link = atom_link(&element);
+                        }
+                    }
+                    _ => {}
+                }
+                text.clear();
+            }
+            Event::Empty(element) if element.local_name().as_ref() == b"link" => {
+                if let Some(item) = item.as_mut().filter(|item| item.link.is_none()) {
+                    item.link = atom_link(&element);
+                }
+            }
+            Event::Text(t) => {
+                text.push_str(&t.unescape().map_err(|e| format!("Invalid feed: {e}"))?)
+            }
+            Event::CData(data) => text.push_str(&String::from_utf8_lossy(&data)),
+            Event::End(element) => {
+                let value = std::mem::take(&mut text).trim().to_string();
+                let Some(current) = item.as_mut() else {
+                    continue;
+                };
+                let value = (!value.is_empty()).then_some(value);
+                match element.local_name().as_ref() {
+                    b"item" | b"entry" => {
+                        let mut done = item.take().unwrap_or_default();
+                        if done.guid.is_empty() {
+                            done.guid =
+                                done.link.clone().or(done.title.clone()).unwrap_or_default();
+                        }
+                        if !done.guid.is_empty() {
+                            items.push(done);
+                        }
+                    }
+                    b"guid" | b"id" => current.guid = value.unwrap_or_default(),
+                    b"title" => current.title = value,
+                    b"link" if current.link.is_none() => current.link = value,
+                    b"description" | b"summary" => current.summary = value,
+                    b"encoded" | b"content" if current.summary.is_none() => current.summary = value,
+                    b"pubDate" | b"published" | b"updated" | b"date" => {
+                        if current.published.is_none() {
+                            current.published = value.as_deref().and_then(date);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rss() {
+        let xml = r#"<rss version="2.0"><channel><title>Blog</title>
+            <item><title>First</title><link>https://example.com/1</link>
+            <guid>post-1</guid><description><![CDATA[<p>Hello</p>]]></description>
+            <pubDate>Tue, 10 Jun 2025 04:00:00 GMT</pubDate></item>
+            <item><title>No guid</title><link>https://example.com/2</link></item>
+            </channel></rss>"#;
+        let items = parse_feed(xml).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].guid, "post-1");
+        assert_eq!(items[0].summary.as_deref(), Some("<p>Hello</p>"));
+        assert_eq!(
+            items[0].published.as_deref(),
+            Some("2025-06-10T04:00:00+00:00")
+        );
+        assert_eq!(items[1].guid, "https://example.com/2");
+    }
+
+    #[test]
+    fn test_parse_atom() {
+        let xml = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Blog</title>
+            <link href="https://example.com/"/>
+            <entry><id>tag:example.com,2025:1</id><title>Entry</title>
+            <link rel="edit" href="https://example.com/edit/1"/>
+            <link href="https://example.com/1"/>
+            <updated>2025-06-10T04:00:00Z</updated><summary>Short</summary></entry></feed>"#;
+        let items = parse_feed(xml).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].guid, "tag:example.com,2025:1");
+        assert_eq!(items[0].link.as_deref(), Some("https://example.com/1"));
+        assert_eq!(items[0].summary.as_deref(), Some("Short"));
+    }
+}
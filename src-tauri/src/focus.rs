@@ -0,0 +1,427 @@
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_notification::NotificationExt;
+use xs::store::{Frame, Store};
+
+use crate::export::{content_title, note_title};
+use crate::locale::{Locale, LocaleState};
+use crate::projection::Projection;
+use crate::shutdown::begin_write;
+use crate::time::{from_id, TimeRange};
+use crate::windows::emit_frame;
+use crate::{append_frame, locks, read_all_frames};
+
+const START_TOPIC: &str = "focus.start";
+const END_TOPIC: &str = "focus.end";
+const FOCUS_KEY: &str = "focus_id";
+const MAX_DURATION_SECS: u64 = 4 * 60 * 60;
+
+struct Active {
+    id: String,
+    started_ms: u64,
+    duration_secs: u64,
+    timer: JoinHandle<()>,
+}
+
+/// The focus session under way, if any.
+#[derive(Default)]
+pub struct FocusState {
+    active: Mutex<Option<Active>>,
+}
+
+/// Emitted as "focus-tick" every second of a session.
+#[derive(Debug, Clone, Serialize)]
+pub struct FocusTick {
+    pub focus_id: String,
+    pub elapsed_secs: u64,
+    pub remaining_secs: u64,
+}
+
+/// A finished focus session, from its `focus.start` and `focus.end` frames.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FocusSession {
+    pub id: String,
+    pub started: DateTime<Utc>,
+    /// The length it was started with
+    pub duration_secs: u64,
+    /// How long it actually ran: less than `duration_secs` if stopped early
+    pub elapsed_secs: u64,
+    pub completed: bool,
+    pub yak_id: Option<String>,
+    /// The note or task worked on, if any
+    pub note_id: Option<String>,
+    pub task_id: Option<String>,
+    pub title: Option<String>,
+}
+
+/// Time spent in focus sessions over a range, by yak and by day.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeReport {
+    pub total_secs: u64,
+    pub by_yak: BTreeMap<String, u64>,
+    pub by_day: BTreeMap<NaiveDate, u64>,
+    /// By the first day of each week, per the locale
+    pub by_week: BTreeMap<NaiveDate, u64>,
+    /// Newest first
+    pub sessions: Vec<FocusSession>,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+fn meta_u64(frame: &Frame, key: &str) -> Option<u64> {
+    frame.meta.as_ref()?.get(key)?.as_u64()
+}
+
+fn elapsed_secs(started_ms: u64) -> u64 {
+    (Utc::now().timestamp_millis() as u64).saturating_sub(started_ms) / 1000
+}
+
+/// Sessions that have ended, oldest first.
+fn sessions(frames: &[Frame]) -> Vec<FocusSession> {
+    let mut ends = BTreeMap::new();
+    for frame in frames.iter().filter(|frame| frame.topic == END_TOPIC) {
+        if let Some(focus_id) = meta_str(frame, FOCUS_KEY) {
+            ends.insert(focus_id.to_string(), frame);
+        }
+    }
+    frames
+        .iter()
+        .filter(|frame| frame.topic == START_TOPIC)
+        .filter_map(|start| {
+            let id = start.id.to_string();
+            let end = ends.get(&id)?;
+            let field = |key: &str| meta_str(start, key).map(String::from);
+            Some(FocusSession {
+                started: from_id(&id)?,
+                duration_secs: meta_u64(start, "duration_secs").unwrap_or_default(),
+                elapsed_secs: meta_u64(end, "elapsed_secs").unwrap_or_default(),
+                completed: end
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get("completed"))
+                    .and_then(|completed| completed.as_bool())
+                    .unwrap_or(false),
+                yak_id: field("yak_id"),
+                note_id: field("note_id"),
+                task_id: field("task_id"),
+                title: field("title"),
+                id,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn report(frames: &[Frame], range: &TimeRange, locale: &Locale) -> TimeReport {
+    let mut report = TimeReport {
+        total_secs: 0,
+        by_yak: BTreeMap::new(),
+        by_day: BTreeMap::new(),
+        by_week: BTreeMap::new(),
+        sessions: Vec::new(),
+    };
+    for session in sessions(frames) {
+        if !range.contains(session.started) {
+            continue;
+        }
+        report.total_secs += session.elapsed_secs;
+        if let Some(yak_id) = &session.yak_id {
+            *report.by_yak.entry(yak_id.clone()).or_default() += session.elapsed_secs;
+        }
+        let day = session.started.with_timezone(&Local).date_naive();
+        *report.by_day.entry(day).or_default() += session.elapsed_secs;
+        *report.by_week.entry(locale.week_of(day)).or_default() += session.elapsed_secs;
+        report.sessions.push(session);
+    }
+    report.sessions.reverse();
+    report
+}
+
+/// The `focus.start` meta linking a session to the note or task `frame_id`, with its title
+/// for the report (none for sealed content).
+async fn target(store: &Store, frame_id: &str) -> Result<serde_json::Value, String> {
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    let read = |hash: Option<ssri::Integrity>| async move {
+        let bytes = store.cas_read(&hash?).await.ok()?;
+        String::from_utf8(bytes)
+            .ok()
+            .filter(|content| !locks::is_sealed(content))
+    };
+    if let Some(task) = projection.tasks.get(frame_id) {
+        let title = read(task.hash.clone())
+            .await
+            .map(|text| content_title(&text));
+        return Ok(json!({ "yak_id": task.yak_id, "task_id": task.id, "title": title }));
+    }
+    let note_id = projection.resolve(frame_id);
+    let mut note = projection
+        .notes
+        .get(&note_id)
+        .cloned()
+        .ok_or_else(|| format!("No note or task {frame_id}"))?;
+    note.content = read(note.hash.clone()).await;
+    let title = note.content.is_some().then(|| note_title(&note));
+    Ok(json!({ "yak_id": note.yak_id, "note_id": note_id, "title": title }))
+}
+
+/// Appends the `focus.end` frame closing a session.
+async fn finish(
+    app: &AppHandle,
+    store: &Store,
+    focus_id: &str,
+    elapsed_secs: u64,
+    completed: bool,
+) -> Result<(), String> {
+    let _guard = begin_write(app)?;
+    let meta = json!({
+        FOCUS_KEY: focus_id,
+        "elapsed_secs": elapsed_secs,
+        "completed": completed,
+    });
+    let frame = append_frame(store, END_TOPIC, None, Some(meta)).await?;
+    let _ = emit_frame(app, &frame);
+    if let Err(e) = app.emit("focus-ended", focus_id) {
+        eprintln!("Failed to emit focus end: {e}");
+    }
+    Ok(())
+}
+
+/// Ticks once a second until the session's time is up, then ends it and says so with a
+/// notification. Stopping the session aborts this first.
+async fn run(app: AppHandle, store: Store, focus_id: String, started_ms: u64, duration_secs: u64) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let elapsed = elapsed_secs(started_ms);
+        if elapsed >= duration_secs {
+            break;
+        }
+        let tick = FocusTick {
+            focus_id: focus_id.clone(),
+            elapsed_secs: elapsed,
+            remaining_secs: duration_secs - elapsed,
+        };
+        if let Err(e) = app.emit("focus-tick", &tick) {
+            eprintln!("Failed to emit focus tick: {e}");
+        }
+    }
+    app.state::<FocusState>().active.lock().unwrap().take();
+    if let Err(e) = finish(&app, &store, &focus_id, duration_secs, true).await {
+        eprintln!("Failed to end focus session: {e}");
+    }
+    let minutes = duration_secs.div_ceil(60);
+    let shown = app
+        .notification()
+        .builder()
+        .title("Focus session done")
+        .body(format!("{minutes} min of focus. Time for a break."))
+        .show();
+    if let Err(e) = shown {
+        eprintln!("Failed to show focus notification: {e}");
+    }
+}
+
+fn begin(app: &AppHandle, store: &Store, focus_id: String, started_ms: u64, duration_secs: u64) {
+    let state = app.state::<FocusState>();
+    // Held while spawning, so a timer that's already due can't finish before it's recorded
+    let mut active = state.active.lock().unwrap();
+    let timer = tauri::async_runtime::spawn(run(
+        app.clone(),
+        store.clone(),
+        focus_id.clone(),
+        started_ms,
+        duration_secs,
+    ));
+    *active = Some(Active {
+        id: focus_id,
+        started_ms,
+        duration_secs,
+        timer,
+    });
+}
+
+/// Picks up a session left running when the app last quit, or ends it if its time ran out
+/// in the meantime. Sessions started on other devices are theirs to end.
+pub(crate) async fn initialize(app: &AppHandle, store: &Store, frames: &[Frame]) {
+    let Some(start) = frames
+        .iter()
+        .rev()
+        .find(|frame| frame.topic == START_TOPIC && crate::sync::origin(frame).is_none())
+    else {
+        return;
+    };
+    let id = start.id.to_string();
+    let ended = frames
+        .iter()
+        .any(|frame| frame.topic == END_TOPIC && meta_str(frame, FOCUS_KEY) == Some(id.as_str()));
+    if ended {
+        return;
+    }
+    let started_ms = start.id.timestamp();
+    let duration_secs = meta_u64(start, "duration_secs").unwrap_or_default();
+    if elapsed_secs(started_ms) >= duration_secs {
+        if let Err(e) = finish(app, store, &id, duration_secs, true).await {
+            eprintln!("Failed to end focus session: {e}");
+        }
+        return;
+    }
+    begin(app, store, id, started_ms, duration_secs);
+}
+
+/// Starts a focus session of `duration` seconds, optionally on the note or task `frame_id`.
+/// The backend keeps time: it emits "focus-tick" every second, and when the time is up
+/// ends the session, emits "focus-ended" and shows a notification. Sessions are logged as
+/// `focus.start` and `focus.end` frames, which `get_time_report` adds up.
+#[tauri::command]
+pub async fn start_focus(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, FocusState>,
+    duration: u64,
+    frame_id: Option<String>,
+) -> Result<String, String> {
+    if duration == 0 || duration > MAX_DURATION_SECS {
+        return Err(format!(
+            "A focus session runs from 1 second to {} hours",
+            MAX_DURATION_SECS / 3600
+        ));
+    }
+    if state.active.lock().unwrap().is_some() {
+        return Err("A focus session is already under way".to_string());
+    }
+    let mut meta = match &frame_id {
+        Some(frame_id) => target(&store, frame_id).await?,
+        None => json!({}),
+    };
+    meta["duration_secs"] = duration.into();
+    let frame = {
+        let _guard = begin_write(&app)?;
+        append_frame(&store, START_TOPIC, None, Some(meta)).await?
+    };
+    let _ = emit_frame(&app, &frame);
+    let focus_id = frame.id.to_string();
+    begin(
+        &app,
+        &store,
+        focus_id.clone(),
+        frame.id.timestamp(),
+        duration,
+    );
+    Ok(focus_id)
+}
+
+/// Ends the session under way early, logging the time spent so far.
+#[tauri::command]
+pub async fn stop_focus(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, FocusState>,
+) -> Result<String, String> {
+    let Some(active) = state.active.lock().unwrap().take() else {
+        return Err("No focus session is under way".to_string());
+    };
+    active.timer.abort();
+    let elapsed = elapsed_secs(active.started_ms).min(active.duration_secs);
+    finish(&app, &store, &active.id, elapsed, false).await?;
+    Ok(active.id)
+}
+
+/// The session under way, if any, so a window opened mid-session can show it.
+#[tauri::command]
+pub async fn get_focus(state: State<'_, FocusState>) -> Result<Option<FocusTick>, String> {
+    let active = state.active.lock().unwrap();
+    Ok(active.as_ref().map(|active| {
+        let elapsed = elapsed_secs(active.started_ms).min(active.duration_secs);
+        FocusTick {
+            focus_id: active.id.clone(),
+            elapsed_secs: elapsed,
+            remaining_secs: active.duration_secs - elapsed,
+        }
+    }))
+}
+
+/// Time spent in focus sessions started within `range`, by yak, by local day and by week.
+#[tauri::command]
+pub async fn get_time_report(
+    store: State<'_, Store>,
+    locale: State<'_, LocaleState>,
+    range: Option<TimeRange>,
+) -> Result<TimeReport, String> {
+    let frames = read_all_frames(&store).await;
+    Ok(report(&frames, &range.unwrap_or_default(), &locale.get()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{add_note, add_task, create_yak};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_report() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Work").await.unwrap();
+        let note = add_note(&store, &yak_id, "# Plan\nsteps", json!({}))
+            .await
+            .unwrap();
+        let task = add_task(&store, &yak_id, None, "Write tests", false)
+            .await
+            .unwrap();
+
+        let on_note = target(&store, &note.id.to_string()).await.unwrap();
+        assert_eq!(on_note["title"], "Plan");
+        let on_task = target(&store, &task.id.to_string()).await.unwrap();
+        assert_eq!(on_task["task_id"], task.id.to_string());
+        assert_eq!(on_task["title"], "Write tests");
+        assert!(target(&store, "missing").await.is_err());
+
+        let log = |mut meta: serde_json::Value, elapsed: u64, completed: bool| {
+            let store = &store;
+            async move {
+                meta["duration_secs"] = 1500.into();
+                let start = append_frame(store, START_TOPIC, None, Some(meta))
+                    .await
+                    .unwrap();
+                let meta = json!({
+                    FOCUS_KEY: start.id.to_string(),
+                    "elapsed_secs": elapsed,
+                    "completed": completed,
+                });
+                append_frame(store, END_TOPIC, None, Some(meta))
+                    .await
+                    .unwrap();
+            }
+        };
+        log(on_note, 1500, true).await;
+        log(on_task, 600, false).await;
+        log(json!({}), 300, false).await;
+        // Still running, so not counted
+        append_frame(
+            &store,
+            START_TOPIC,
+            None,
+            Some(json!({ "duration_secs": 60 })),
+        )
+        .await
+        .unwrap();
+
+        let frames = read_all_frames(&store).await;
+        let report = report(&frames, &TimeRange::default(), &Locale::default());
+        assert_eq!(report.total_secs, 2400);
+        assert_eq!(report.by_yak[&yak_id], 2100);
+        assert_eq!(report.by_day.values().sum::<u64>(), 2400);
+        assert_eq!(report.by_week.values().sum::<u64>(), 2400);
+        assert_eq!(report.sessions.len(), 3);
+        assert_eq!(report.sessions[1].task_id, Some(task.id.to_string()));
+        assert!(!report.sessions[1].completed);
+        assert!(report.sessions[2].completed);
+    }
+}
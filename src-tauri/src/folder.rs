@@ -0,0 +1,325 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::import::{add_attachment, add_note, create_yak};
+use crate::rates::RatesState;
+use crate::settings::{load_setting, save_setting};
+use crate::shutdown::begin_write;
+use crate::windows::emit_frames;
+use crate::{health, read_all_frames};
+
+const CONFIG_TOPIC: &str = "capture.folder";
+/// Where imported files are moved, inside the watched folder, when archiving is on.
+const ARCHIVE_DIR: &str = "Imported";
+/// Files changed more recently than this may still be being written.
+const SETTLE: Duration = Duration::from_secs(5);
+/// Extensions read as the note itself rather than attached to one.
+const TEXT_EXTENSIONS: &[&str] = &["md", "markdown", "txt", "yaknote"];
+
+fn default_poll_seconds() -> u64 {
+    30
+}
+
+/// A folder whose files are imported as notes, for scanners and other apps that can save
+/// to a folder but can't call anything. Text files become notes; anything else is attached
+/// to a note named after it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderConfig {
+    pub enabled: bool,
+    pub path: Option<String>,
+    /// The yak files are filed into; an "Imported files" yak is created if unset
+    pub yak_id: Option<String>,
+    /// Move imported files into an `Imported` subfolder rather than leaving them in place
+    #[serde(default)]
+    pub archive: bool,
+    #[serde(default = "default_poll_seconds")]
+    pub poll_seconds: u64,
+}
+
+impl Default for FolderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: None,
+            yak_id: None,
+            archive: false,
+            poll_seconds: default_poll_seconds(),
+        }
+    }
+}
+
+/// A file left where it was is recognised by its path and modification time, so it's
+/// imported again only if it changes.
+fn file_key(path: &str, modified: u64) -> String {
+    format!("{path}@{modified}")
+}
+
+fn modified_millis(modified: SystemTime) -> u64 {
+    modified
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|since| since.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Files already imported from the folder.
+fn imported(frames: &[Frame]) -> HashSet<String> {
+    frames
+        .iter()
+        .filter(|frame| frame.topic == "note.create")
+        .filter_map(|frame| {
+            let meta = frame.meta.as_ref()?;
+            let path = meta.get("source_path")?.as_str()?;
+            Some(file_key(path, meta.get("source_modified")?.as_u64()?))
+        })
+        .collect()
+}
+
+/// Hidden files and the partial downloads browsers and sync tools leave while writing.
+fn is_ignored(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with('.')
+        || name.starts_with('~')
+        || name.ends_with('~')
+        || [".part", ".crdownload", ".download", ".tmp"]
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+}
+
+/// The files ready to import, oldest first, with their modification times. Subfolders,
+/// including the archive, are left alone.
+async fn ready_files(dir: &Path) -> Result<Vec<(PathBuf, SystemTime)>, String> {
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .map_err(|e| format!("Failed to read {}: {e}", dir.display()))?;
+    let mut files = Vec::new();
+    let now = SystemTime::now();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| format!("Failed to read {}: {e}", dir.display()))?
+    {
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !metadata.is_file() || is_ignored(&name) {
+            continue;
+        }
+        let Ok(modified) = metadata.modified() else {
+            continue;
+        };
+        if now.duration_since(modified).is_ok_and(|age| age >= SETTLE) {
+            files.push((entry.path(), modified));
+        }
+    }
+    files.sort_by_key(|(_, modified)| *modified);
+    Ok(files)
+}
+
+/// A path in the archive that doesn't clash with an earlier file of the same name.
+fn archive_path(archive: &Path, name: &str) -> PathBuf {
+    let candidate = archive.join(name);
+    if !candidate.exists() {
+        return candidate;
+    }
+    let path = Path::new(name);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+    let extension = path.extension().and_then(|e| e.to_str());
+    (2..)
+        .map(|n| match extension {
+            Some(extension) => archive.join(format!("{stem} ({n}).{extension}")),
+            None => archive.join(format!("{stem} ({n})")),
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(candidate)
+}
+
+/// Imports one file: text files as the note's content, anything else as an attachment
+/// of a note titled with the file's name. Returns every frame appended, the note first.
+async fn import_file(
+    store: &Store,
+    yak_id: &str,
+    path: &Path,
+    modified: SystemTime,
+) -> Result<Vec<Frame>, String> {
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("file");
+    let title = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(name);
+    let is_text = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| TEXT_EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e)));
+    let meta = serde_json::json!({
+        "title": title,
+        "source": "folder",
+        "source_path": path.to_string_lossy(),
+        "source_modified": modified_millis(modified),
+    });
+    if is_text {
+        let content = String::from_utf8_lossy(&bytes);
+        return Ok(vec![add_note(store, yak_id, &content, meta).await?]);
+    }
+    let note = add_note(store, yak_id, &format!("# {title}\n"), meta).await?;
+    let note_id = note.id.to_string();
+    let attachment =
+        add_attachment(store, yak_id, &note_id, name, &bytes, serde_json::json!({})).await?;
+    Ok(vec![note, attachment])
+}
+
+/// Imports the folder's new files, moving them to the archive if asked. Returns how many
+/// were imported.
+async fn scan(app: &AppHandle, store: &Store, config: &FolderConfig) -> Result<usize, String> {
+    let (Some(dir), Some(yak_id)) = (&config.path, &config.yak_id) else {
+        return Err("The watched folder isn't configured".to_string());
+    };
+    let dir = Path::new(dir);
+    let files = ready_files(dir).await?;
+    if files.is_empty() {
+        return Ok(0);
+    }
+    let _guard = begin_write(app)?;
+    let yak_id = &crate::inbox::capture_yak(store, yak_id).await;
+    let mut seen = imported(&read_all_frames(store).await);
+    let archive = dir.join(ARCHIVE_DIR);
+    if config.archive {
+        tokio::fs::create_dir_all(&archive)
+            .await
+            .map_err(|e| format!("Failed to create {}: {e}", archive.display()))?;
+    }
+    let mut added = 0;
+    for (path, modified) in files {
+        let key = file_key(&path.to_string_lossy(), modified_millis(modified));
+        // Files left in place are skipped until they change
+        if seen.insert(key) {
+            let frames = import_file(store, yak_id, &path, modified).await?;
+            let _ = emit_frames(app, &frames);
+            added += 1;
+        }
+        if config.archive {
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("file");
+            let target = archive_path(&archive, name);
+            tokio::fs::rename(&path, &target)
+                .await
+                .map_err(|e| format!("Failed to archive {}: {e}", path.display()))?;
+        }
+    }
+    Ok(added)
+}
+
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    loop {
+        let config: FolderConfig = load_setting(&store, CONFIG_TOPIC).await;
+        if config.enabled && !app.state::<RatesState>().is_paused("folder") {
+            match scan(&app, &store, &config).await {
+                Ok(_) => health::ok(&app, "folder"),
+                Err(e) => {
+                    health::error(&app, "folder", &e);
+                    eprintln!("Failed to import from watched folder: {e}");
+                }
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(config.poll_seconds.max(1))).await;
+    }
+}
+
+/// Sets up the watched folder, creating an "Imported files" yak if none is given.
+#[tauri::command]
+pub async fn configure_folder_import(
+    store: State<'_, Store>,
+    mut config: FolderConfig,
+) -> Result<FolderConfig, String> {
+    if config.enabled {
+        let path = config
+            .path
+            .as_deref()
+            .ok_or_else(|| "Choose a folder to watch".to_string())?;
+        if !Path::new(path).is_dir() {
+            return Err(format!("{path} is not a folder"));
+        }
+    }
+    if config.yak_id.is_none() {
+        config.yak_id = Some(create_yak(&store, "Imported files").await?);
+    }
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(config)
+}
+
+/// Imports the watched folder's files immediately, returning how many were imported.
+#[tauri::command]
+pub async fn import_folder_now(store: State<'_, Store>, app: AppHandle) -> Result<usize, String> {
+    let config: FolderConfig = load_setting(&store, CONFIG_TOPIC).await;
+    if !config.enabled {
+        return Err("The watched folder is not enabled".to_string());
+    }
+    scan(&app, &store, &config).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_is_ignored() {
+        assert!(is_ignored(".DS_Store"));
+        assert!(is_ignored("scan.pdf.crdownload"));
+        assert!(is_ignored("~$report.docx"));
+        assert!(!is_ignored("scan 2024-05-01.pdf"));
+    }
+
+    #[test]
+    fn test_archive_path() {
+        let dir = tempdir().unwrap();
+        assert_eq!(
+            archive_path(dir.path(), "scan.pdf"),
+            dir.path().join("scan.pdf")
+        );
+        std::fs::write(dir.path().join("scan.pdf"), b"%PDF").unwrap();
+        std::fs::write(dir.path().join("scan (2).pdf"), b"%PDF").unwrap();
+        assert_eq!(
+            archive_path(dir.path(), "scan.pdf"),
+            dir.path().join("scan (3).pdf")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_file() {
+        let dir = tempdir().unwrap();
+        let store_dir = tempdir().unwrap();
+        let store = Store::new(store_dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Imported files").await.unwrap();
+        let note_path = dir.path().join("Ideas.md");
+        std::fs::write(&note_path, "- more yaks\n").unwrap();
+        let scan_path = dir.path().join("receipt.pdf");
+        std::fs::write(&scan_path, b"%PDF-1.4").unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let frames = import_file(&store, &yak_id, &note_path, modified)
+            .await
+            .unwrap();
+        assert_eq!(frames.len(), 1);
+        let frames = import_file(&store, &yak_id, &scan_path, modified)
+            .await
+            .unwrap();
+        assert_eq!(frames[1].topic, "attachment.add");
+
+        let seen = imported(&read_all_frames(&store).await);
+        assert_eq!(seen.len(), 2);
+        assert!(seen.contains(&file_key(&note_path.to_string_lossy(), 1_700_000_000_000)));
+    }
+}
@@ -0,0 +1,80 @@
+/// Splits a leading `---` YAML front-matter block from markdown, returning it as JSON.
+pub(crate) fn split(content: &str) -> (Option<serde_json::Value>, &str) {
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (None, content);
+    };
+
+    let Some(end) = rest
+        .match_indices("\n---")
+        .map(|(i, _)| i)
+        .find(|&i| matches!(rest[i + 4..].chars().next(), None | Some('\n') | Some('\r')))
+    else {
+        return (None, content);
+    };
+
+    let yaml = &rest[..end];
+    let body = rest[end + 4..].trim_start_matches(['\r', '\n']);
+    match serde_yaml::from_str::<serde_json::Value>(yaml) {
+        Ok(serde_json::Value::Null) => (None, body),
+        Ok(value) => (Some(value), body),
+        Err(e) => {
+            eprintln!("Ignoring invalid front-matter: {e}");
+            (None, content)
+        }
+    }
+}
+
+/// Renders `fields` as a YAML front-matter block followed by `body`.
+pub(crate) fn render(fields: &serde_json::Value, body: &str) -> Result<String, String> {
+    let yaml = serde_yaml::to_string(fields).map_err(|e| format!("Invalid front-matter: {e}"))?;
+    Ok(format!("---\n{yaml}---\n\n{body}"))
+}
+
+/// Tags listed in front-matter, accepting both a YAML list and a comma/space separated string.
+pub(crate) fn tags(fields: &serde_json::Value) -> Vec<String> {
+    let tags = match fields.get("tags") {
+        Some(serde_json::Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str())
+            .map(String::from)
+            .collect(),
+        Some(serde_json::Value::String(s)) => {
+            s.split([',', ' ']).map(String::from).collect::<Vec<_>>()
+        }
+        _ => Vec::new(),
+    };
+    tags.into_iter()
+        .map(|tag| tag.trim().trim_start_matches('#').to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_render() {
+        let content = "---\ntitle: Shaving\ntags: [rust, tauri]\n---\n\n# Body\n";
+        let (fields, body) = split(content);
+        let fields = fields.unwrap();
+        assert_eq!(fields["title"], "Shaving");
+        assert_eq!(tags(&fields), vec!["rust", "tauri"]);
+        assert_eq!(body, "# Body\n");
+
+        let rendered = render(&fields, body).unwrap();
+        let (roundtrip, body) = split(&rendered);
+        assert_eq!(roundtrip.unwrap(), fields);
+        assert_eq!(body, "# Body\n");
+    }
+
+    #[test]
+    fn test_split_without_front_matter() {
+        let (fields, body) = split("just a note\n---\n");
+        assert!(fields.is_none());
+        assert_eq!(body, "just a note\n");
+    }
+}
@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::export::note_title;
+use crate::locks::LockState;
+use crate::projection::{Note, Projection};
+use crate::read_all_frames;
+use crate::settings::{latest_setting, save_setting};
+
+const CONFIG_TOPIC: &str = "capture.location";
+/// Coordinates are kept to two decimal places, about a kilometre.
+const PRECISION: f64 = 100.0;
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Coordinates {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Place {
+    #[serde(flatten)]
+    pub at: Coordinates,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct LocationConfig {
+    pub enabled: bool,
+    /// Used instead of whatever the OS reports, e.g. while travelling without a fix
+    #[serde(default)]
+    pub manual: Option<Place>,
+}
+
+/// The config, and the last position the OS reported through the webview.
+#[derive(Default)]
+pub struct GeoState {
+    config: Mutex<LocationConfig>,
+    reported: Mutex<Option<Coordinates>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NearbyNote {
+    pub note_id: String,
+    pub yak_id: String,
+    pub title: String,
+    pub place: Place,
+    pub distance_km: f64,
+}
+
+fn coarse(at: Coordinates) -> Coordinates {
+    Coordinates {
+        lat: (at.lat * PRECISION).round() / PRECISION,
+        lon: (at.lon * PRECISION).round() / PRECISION,
+    }
+}
+
+fn valid(at: Coordinates) -> Result<Coordinates, String> {
+    if (-90.0..=90.0).contains(&at.lat) && (-180.0..=180.0).contains(&at.lon) {
+        Ok(at)
+    } else {
+        Err(format!("Invalid coordinates {}, {}", at.lat, at.lon))
+    }
+}
+
+/// Great-circle distance.
+pub(crate) fn distance_km(a: Coordinates, b: Coordinates) -> f64 {
+    let (lat_a, lat_b) = (a.lat.to_radians(), b.lat.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.lon - a.lon).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+/// Where captures are being made, if location is enabled and known: the manual place, else
+/// the last reported position. Always coarse.
+pub(crate) fn current(app: &AppHandle) -> Option<Place> {
+    let state = app.state::<GeoState>();
+    let config = state.config.lock().unwrap();
+    if !config.enabled {
+        return None;
+    }
+    let place = config.manual.clone().or_else(|| {
+        let at = (*state.reported.lock().unwrap())?;
+        Some(Place { at, label: None })
+    })?;
+    Some(Place {
+        at: coarse(place.at),
+        ..place
+    })
+}
+
+/// The place recorded in a frame's meta.
+fn place(meta: Option<&Value>) -> Option<Place> {
+    serde_json::from_value(meta?.get("location")?.clone()).ok()
+}
+
+/// Edits don't carry the location, so it's looked up on the revisions they replace.
+fn note_place(projection: &Projection, note: &Note) -> Option<Place> {
+    let mut note = note;
+    loop {
+        if let Some(place) = place(note.meta.as_ref()) {
+            return Some(place);
+        }
+        note = projection.notes.get(note.edited_note_id.as_ref()?)?;
+    }
+}
+
+fn near(projection: &Projection, center: Coordinates, radius_km: f64) -> Vec<NearbyNote> {
+    let current: Vec<&Note> = projection
+        .notes_by_yak
+        .values()
+        .flatten()
+        .filter_map(|id| projection.notes.get(id))
+        .collect();
+    let mut nearby: Vec<NearbyNote> = current
+        .into_iter()
+        .filter_map(|note| {
+            let place = note_place(projection, note)?;
+            let distance_km = distance_km(center, place.at);
+            (distance_km <= radius_km).then(|| NearbyNote {
+                note_id: note.id.clone(),
+                yak_id: note.yak_id.clone(),
+                title: note_title(note),
+                place,
+                distance_km,
+            })
+        })
+        .collect();
+    nearby.sort_by(|a, b| a.distance_km.total_cmp(&b.distance_km));
+    nearby
+}
+
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let config: LocationConfig = latest_setting(frames, CONFIG_TOPIC);
+    *app.state::<GeoState>().config.lock().unwrap() = config;
+}
+
+/// Turns location stamping of quick captures on or off, optionally with a fixed place.
+#[tauri::command]
+pub async fn configure_location(
+    store: State<'_, Store>,
+    state: State<'_, GeoState>,
+    config: LocationConfig,
+) -> Result<(), String> {
+    if let Some(manual) = &config.manual {
+        valid(manual.at)?;
+    }
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    *state.config.lock().unwrap() = config;
+    Ok(())
+}
+
+/// Records the position the OS gave the webview's geolocation API. It's only kept in memory.
+#[tauri::command]
+pub fn report_location(state: State<'_, GeoState>, lat: f64, lon: f64) -> Result<(), String> {
+    let at = valid(Coordinates { lat, lon })?;
+    *state.reported.lock().unwrap() = Some(at);
+    Ok(())
+}
+
+/// Current notes captured within `radius_km` of a point, nearest first.
+#[tauri::command]
+pub async fn list_notes_near(
+    app: AppHandle,
+    store: State<'_, Store>,
+    lat: f64,
+    lon: f64,
+    radius_km: f64,
+) -> Result<Vec<NearbyNote>, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let center = valid(Coordinates { lat, lon })?;
+    let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+    projection.resolve_content(&store).await;
+    app.state::<LockState>().redact(&mut projection, true);
+    Ok(near(&projection, center, radius_km))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+    use serde_json::json;
+
+    #[test]
+    fn test_distance_km() {
+        let paris = Coordinates {
+            lat: 48.86,
+            lon: 2.35,
+        };
+        let london = Coordinates {
+            lat: 51.51,
+            lon: -0.13,
+        };
+        assert!((distance_km(paris, london) - 344.0).abs() < 5.0);
+        assert_eq!(
+            coarse(Coordinates {
+                lat: 48.85661,
+                lon: 2.35222
+            }),
+            paris
+        );
+    }
+
+    #[tokio::test]
+    async fn test_near_follows_edits() {
+        let (_dir, store) = testing::store();
+        let yak = append(&store, "yak.create", None);
+        let yak_id = yak.id.to_string();
+        let paris = json!({ "lat": 48.86, "lon": 2.35, "label": "Paris" });
+        let cafe = append(
+            &store,
+            "note.create",
+            Some(json!({ "yak_id": yak_id, "location": paris })),
+        );
+        let edit = append(
+            &store,
+            "note.edit",
+            Some(json!({ "yak_id": yak_id, "note_id": cafe.id.to_string() })),
+        );
+        let elsewhere = json!({ "yak_id": yak_id, "location": { "lat": 51.51, "lon": -0.13 } });
+        append(&store, "note.create", Some(elsewhere));
+        append(&store, "note.create", Some(json!({ "yak_id": yak_id })));
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+
+        let hits = near(
+            &projection,
+            Coordinates {
+                lat: 48.85,
+                lon: 2.34,
+            },
+            10.0,
+        );
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].note_id, edit.id.to_string());
+        assert_eq!(hits[0].place.label.as_deref(), Some("Paris"));
+        assert_eq!(near(&projection, hits[0].place.at, 1000.0).len(), 2);
+    }
+}
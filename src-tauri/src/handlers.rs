@@ -0,0 +1,331 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use tokio::io::AsyncWriteExt;
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::permissions::{self, Integration};
+use crate::sync;
+use crate::windows::emit_frame;
+use crate::{append_frame, read_all_frames};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Output beyond this is discarded rather than parsed or logged
+const MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// A nushell script registered with a `handler.register` frame (the script is the frame's
+/// content) that runs against every new frame whose topic starts with one of `topics`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Handler {
+    pub id: String,
+    pub name: String,
+    pub topics: Vec<String>,
+    #[serde(skip)]
+    pub(crate) script: String,
+}
+
+/// A frame a handler asks to append, one JSON object per line of its output.
+#[derive(Debug, PartialEq, Deserialize)]
+struct Output {
+    topic: String,
+    content: Option<String>,
+    meta: Option<serde_json::Value>,
+}
+
+impl Handler {
+    fn matches(&self, frame: &Frame) -> bool {
+        // Handlers never see handler bookkeeping, nor frames they appended themselves
+        let own = frame
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.get("handler_id"))
+            .and_then(|id| id.as_str())
+            == Some(self.id.as_str());
+        !own && !frame.topic.starts_with("handler.")
+            && self
+                .topics
+                .iter()
+                .any(|topic| frame.topic.starts_with(topic.as_str()))
+    }
+}
+
+/// The handler a `handler.register` frame sets up. Only handlers registered on this device
+/// are run: one that arrived through sync is ignored, as it would run code from elsewhere.
+async fn load_handler(store: &Store, frame: &Frame) -> Option<Handler> {
+    if sync::origin(frame).is_some() {
+        return None;
+    }
+    let meta = frame.meta.as_ref()?;
+    let script = store.cas_read(frame.hash.as_ref()?).await.ok()?;
+    Some(Handler {
+        id: frame.id.to_string(),
+        name: meta.get("name")?.as_str()?.to_string(),
+        topics: serde_json::from_value(meta.get("topics")?.clone()).ok()?,
+        script: String::from_utf8_lossy(&script).into_owned(),
+    })
+}
+
+fn parse_output(stdout: &str) -> Vec<Result<Output, String>> {
+    stdout
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_str(line).map_err(|e| format!("Ignored output {line:?}: {e}")))
+        .collect()
+}
+
+/// Runs the script with `nu`, the triggering frame as JSON on stdin. The script runs without
+/// config files, with only `PATH` in its environment, in a scratch directory, and is killed
+/// if it takes longer than `TIMEOUT`.
+async fn execute(handler: &Handler, frame: &Frame) -> Result<String, String> {
+    let scratch = std::env::temp_dir().join(format!("yaks-handler-{}", scru128::new()));
+    tokio::fs::create_dir_all(&scratch)
+        .await
+        .map_err(|e| format!("Failed to create scratch directory: {e}"))?;
+
+    let mut command = tokio::process::Command::new("nu");
+    command
+        .args(["--no-config-file", "--stdin", "-c", &handler.script])
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .current_dir(&scratch)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let result = async {
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("Failed to run nu: {e}"))?;
+        let input = serde_json::to_vec(frame).map_err(|e| e.to_string())?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(&input)
+                .await
+                .map_err(|e| format!("Failed to write to handler: {e}"))?;
+        }
+        let output = tokio::time::timeout(TIMEOUT, child.wait_with_output())
+            .await
+            .map_err(|_| format!("Timed out after {}s", TIMEOUT.as_secs()))?
+            .map_err(|e| format!("Handler failed: {e}"))?;
+        let text = |bytes: &[u8]| {
+            String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_OUTPUT_BYTES)]).into_owned()
+        };
+        if output.status.success() {
+            Ok(text(&output.stdout))
+        } else {
+            Err(format!(
+                "{}: {}",
+                output.status,
+                text(&output.stderr).trim()
+            ))
+        }
+    }
+    .await;
+
+    let _ = tokio::fs::remove_dir_all(&scratch).await;
+    result
+}
+
+/// Runs a handler against a frame, appends what it outputs, and records the run as a
+/// `handler.log` frame.
+async fn run(app: &AppHandle, store: &Store, handler: &Handler, frame: &Frame) {
+    let (appended, messages, error) = match execute(handler, frame).await {
+        Ok(stdout) => {
+            let mut appended = Vec::new();
+            let mut messages = Vec::new();
+            for output in parse_output(&stdout) {
+                let output = match output {
+                    Ok(output) => output,
+                    Err(message) => {
+                        messages.push(message);
+                        continue;
+                    }
+                };
+                let mut meta = match output.meta {
+                    Some(serde_json::Value::Object(meta)) => meta,
+                    _ => serde_json::Map::new(),
+                };
+                meta.insert("handler_id".into(), handler.id.clone().into());
+                let content = output.content.as_deref().map(str::as_bytes);
+                let append = append_frame(store, &output.topic, content, Some(meta.into()));
+                let appended_frame = match crate::shutdown::begin_write(app) {
+                    Ok(_guard) => permissions::scope(Integration::Handlers, append).await,
+                    Err(e) => Err(e),
+                };
+                match appended_frame {
+                    Ok(appended_frame) => {
+                        let _ = emit_frame(app, &appended_frame);
+                        appended.push(appended_frame.id.to_string());
+                    }
+                    Err(e) => messages.push(e),
+                }
+            }
+            (appended, messages, None)
+        }
+        Err(e) => (Vec::new(), Vec::new(), Some(e)),
+    };
+
+    let log = serde_json::json!({
+        "handler_id": handler.id,
+        "frame_id": frame.id.to_string(),
+        "appended": appended,
+        "messages": messages,
+        "error": error,
+    });
+    if let Err(e) = append_frame(store, "handler.log", None, Some(log)).await {
+        eprintln!("Failed to log handler {}: {e}", handler.name);
+    }
+}
+
+/// Tracks registered handlers as their frames go by, and once history has been read, runs
+/// the matching handlers on each new frame.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let mut handlers: HashMap<String, Handler> = HashMap::new();
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    let mut caught_up = false;
+    while let Some(frame) = rx.recv().await {
+        match frame.topic.as_str() {
+            "xs.threshold" => caught_up = true,
+            "handler.register" => {
+                if let Some(handler) = load_handler(&store, &frame).await {
+                    handlers.insert(handler.id.clone(), handler);
+                }
+            }
+            "handler.unregister" => {
+                if let Some(id) = frame
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get("handler_id"))
+                    .and_then(|id| id.as_str())
+                {
+                    handlers.remove(id);
+                }
+            }
+            _ if caught_up => {
+                if !permissions::triggers(Integration::Handlers, &frame) {
+                    continue;
+                }
+                for handler in handlers.values().filter(|handler| handler.matches(&frame)) {
+                    let (app, store, handler, frame) =
+                        (app.clone(), store.clone(), handler.clone(), frame.clone());
+                    tokio::spawn(async move { run(&app, &store, &handler, &frame).await });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Registers a nushell script to run on new frames whose topic starts with one of `topics`.
+/// The script gets the frame as JSON on stdin; each line it prints as
+/// `{"topic": ..., "content"?: ..., "meta"?: ...}` is appended as a new frame.
+#[tauri::command]
+pub async fn register_handler(
+    store: State<'_, Store>,
+    app: AppHandle,
+    name: String,
+    topics: Vec<String>,
+    script: String,
+) -> Result<String, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    if topics.is_empty() {
+        return Err("A handler needs at least one topic".to_string());
+    }
+    let meta = serde_json::json!({ "name": name, "topics": topics });
+    let frame = append_frame(
+        &store,
+        "handler.register",
+        Some(script.as_bytes()),
+        Some(meta),
+    )
+    .await?;
+    Ok(frame.id.to_string())
+}
+
+#[tauri::command]
+pub async fn unregister_handler(
+    store: State<'_, Store>,
+    app: AppHandle,
+    handler_id: String,
+) -> Result<(), String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let meta = serde_json::json!({ "handler_id": handler_id });
+    append_frame(&store, "handler.unregister", None, Some(meta)).await?;
+    Ok(())
+}
+
+/// The handlers registered and not since unregistered.
+pub(crate) async fn handlers(store: &Store) -> Vec<Handler> {
+    let mut handlers: Vec<Handler> = Vec::new();
+    for frame in read_all_frames(store).await {
+        match frame.topic.as_str() {
+            "handler.register" => handlers.extend(load_handler(store, &frame).await),
+            "handler.unregister" => {
+                let id = frame.meta.as_ref().and_then(|meta| meta.get("handler_id"));
+                handlers
+                    .retain(|handler| id.and_then(|id| id.as_str()) != Some(handler.id.as_str()));
+            }
+            _ => {}
+        }
+    }
+    handlers
+}
+
+#[tauri::command]
+pub async fn list_handlers(store: State<'_, Store>) -> Result<Vec<Handler>, String> {
+    Ok(handlers(&store).await)
+}
+
+/// The most recent runs of a handler, newest first.
+#[tauri::command]
+pub async fn handler_logs(
+    store: State<'_, Store>,
+    handler_id: String,
+    limit: Option<usize>,
+) -> Result<Vec<Frame>, String> {
+    Ok(read_all_frames(&store)
+        .await
+        .into_iter()
+        .rev()
+        .filter(|frame| {
+            frame.topic == "handler.log"
+                && frame.meta.as_ref().and_then(|meta| meta.get("handler_id"))
+                    == Some(&serde_json::Value::from(handler_id.as_str()))
+        })
+        .take(limit.unwrap_or(100))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+
+    #[tokio::test]
+    async fn test_handler_matches() {
+        let (_dir, store) = testing::store();
+        let handler = Handler {
+            id: "h1".to_string(),
+            name: "tagger".to_string(),
+            topics: vec!["note.".to_string()],
+            script: String::new(),
+        };
+        assert!(handler.matches(&append(&store, "note.create", None)));
+        assert!(!handler.matches(&append(&store, "yak.create", None)));
+        let own = Some(serde_json::json!({ "handler_id": "h1" }));
+        assert!(!handler.matches(&append(&store, "note.create", own)));
+    }
+
+    #[test]
+    fn test_parse_output() {
+        let outputs =
+            parse_output("{\"topic\": \"tag.add\", \"meta\": {\"tag\": \"x\"}}\n\nnope\n");
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[0].as_ref().unwrap().topic, "tag.add");
+        assert!(outputs[1].is_err());
+    }
+}
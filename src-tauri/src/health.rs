@@ -0,0 +1,136 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State};
+use tokio::task::{AbortHandle, JoinHandle};
+use xs::store::Store;
+
+use crate::{shutdown, sync};
+
+type Spawn = Arc<dyn Fn() -> JoinHandle<()> + Send + Sync>;
+
+#[derive(Default)]
+struct Subsystem {
+    /// Set for background tasks started by `supervise`, which can be restarted
+    task: Option<AbortHandle>,
+    spawn: Option<Spawn>,
+    last_success: Option<String>,
+    last_error: Option<String>,
+    errors: u64,
+    restarts: u64,
+}
+
+/// What each background subsystem has reported: the tasks started with `supervise`, and
+/// the ones that report through `ok` / `error` as they work.
+#[derive(Default)]
+pub struct HealthState {
+    subsystems: Mutex<BTreeMap<&'static str, Subsystem>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubsystemHealth {
+    pub name: String,
+    /// Whether the task is still going; `None` for subsystems that only report
+    pub running: Option<bool>,
+    pub restartable: bool,
+    /// RFC 3339 time of the last run that went through
+    pub last_success: Option<String>,
+    pub last_error: Option<String>,
+    pub errors: u64,
+    pub restarts: u64,
+}
+
+fn start(app: &AppHandle, subsystem: &mut Subsystem, spawn: Spawn) {
+    let task = spawn();
+    subsystem.task = Some(task.abort_handle());
+    subsystem.spawn = Some(spawn);
+    shutdown::track(app, task);
+}
+
+/// Starts a long-running background task under `name`, so its health is reported and it
+/// can be restarted if it dies or gets stuck.
+pub(crate) fn supervise<F, Fut>(app: &AppHandle, store: &Store, name: &'static str, task: F)
+where
+    F: Fn(AppHandle, Store) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let (task_app, store) = (app.clone(), store.clone());
+    let spawn: Spawn = Arc::new(move || tokio::spawn(task(task_app.clone(), store.clone())));
+    let state = app.state::<HealthState>();
+    let mut subsystems = state.subsystems.lock().unwrap();
+    start(app, subsystems.entry(name).or_default(), spawn);
+}
+
+fn record(app: &AppHandle, name: &'static str, outcome: Result<(), &str>) {
+    let Some(state) = app.try_state::<HealthState>() else {
+        return;
+    };
+    let mut subsystems = state.subsystems.lock().unwrap();
+    let subsystem = subsystems.entry(name).or_default();
+    match outcome {
+        Ok(()) => subsystem.last_success = Some(chrono::Utc::now().to_rfc3339()),
+        Err(e) => {
+            subsystem.errors += 1;
+            subsystem.last_error = Some(e.to_string());
+        }
+    }
+}
+
+/// Notes that a run of `name` went through.
+pub(crate) fn ok(app: &AppHandle, name: &'static str) {
+    record(app, name, Ok(()));
+}
+
+/// Notes that a run of `name` failed.
+pub(crate) fn error(app: &AppHandle, name: &'static str, error: &str) {
+    record(app, name, Err(error));
+}
+
+#[tauri::command]
+pub async fn get_health(state: State<'_, HealthState>) -> Result<Vec<SubsystemHealth>, String> {
+    let subsystems = state.subsystems.lock().unwrap();
+    Ok(subsystems
+        .iter()
+        .map(|(name, subsystem)| SubsystemHealth {
+            name: name.to_string(),
+            running: subsystem.task.as_ref().map(|task| !task.is_finished()),
+            restartable: subsystem.spawn.is_some() || *name == "sync",
+            last_success: subsystem.last_success.clone(),
+            last_error: subsystem.last_error.clone(),
+            errors: subsystem.errors,
+            restarts: subsystem.restarts,
+        })
+        .collect())
+}
+
+/// Stops a background task and starts it afresh, e.g. a feed poller stuck on a request
+/// that never returns. Sync restarts its follower.
+#[tauri::command]
+pub async fn restart_subsystem(
+    app: AppHandle,
+    state: State<'_, HealthState>,
+    name: String,
+) -> Result<(), String> {
+    if name == "sync" {
+        sync::restart(&app).await;
+        if let Some(subsystem) = state.subsystems.lock().unwrap().get_mut("sync") {
+            subsystem.restarts += 1;
+        }
+        return Ok(());
+    }
+    let mut subsystems = state.subsystems.lock().unwrap();
+    let subsystem = subsystems
+        .get_mut(name.as_str())
+        .ok_or_else(|| format!("No subsystem named {name}"))?;
+    let spawn = subsystem
+        .spawn
+        .clone()
+        .ok_or_else(|| format!("{name} can't be restarted"))?;
+    if let Some(task) = subsystem.task.take() {
+        task.abort();
+    }
+    subsystem.restarts += 1;
+    start(&app, subsystem, spawn);
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use xs::store::Store;
+
+use crate::app_lock;
+use crate::locks::{self, LockState};
+use crate::presentation::PresentationState;
+use crate::projection::{Note, Projection};
+use crate::read_model::ReadModel;
+use crate::settings::{load_setting, save_setting};
+
+const CONFIG_TOPIC: &str = "store.replay";
+/// Notes per `load_older` call when the caller doesn't say.
+const DEFAULT_PAGE: usize = 50;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ReplayConfig {
+    /// Most recent notes per yak in the startup snapshot; every note when unset
+    pub note_limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OlderNotes {
+    /// Oldest first, like a yak's notes in the snapshot
+    pub notes: Vec<Note>,
+    /// How many notes are older still
+    pub remaining: usize,
+}
+
+pub(crate) async fn load_config(store: &Store) -> ReplayConfig {
+    load_setting(store, CONFIG_TOPIC).await
+}
+
+/// Up to `count` of the yak's current notes created before `before_id`, which may be any
+/// revision of a note.
+fn older(
+    projection: &Projection,
+    yak_id: &str,
+    before_id: &str,
+    count: usize,
+) -> Result<OlderNotes, String> {
+    let ids = projection
+        .notes_by_yak
+        .get(yak_id)
+        .ok_or_else(|| format!("Yak {yak_id} not found"))?;
+    let before = projection.resolve(before_id);
+    let end = ids
+        .iter()
+        .position(|id| *id == before)
+        .ok_or_else(|| format!("Note {before_id} not found in yak {yak_id}"))?;
+    let start = end.saturating_sub(count);
+    Ok(OlderNotes {
+        notes: ids[start..end]
+            .iter()
+            .filter_map(|id| projection.notes.get(id))
+            .cloned()
+            .collect(),
+        remaining: start,
+    })
+}
+
+/// Limits the startup snapshot to each yak's most recent `note_limit` notes, so a store
+/// holding years of notes opens quickly; older ones are fetched with `load_older` on
+/// scrolling back. Without a limit every note is loaded.
+#[tauri::command]
+pub async fn set_replay_limit(
+    store: State<'_, Store>,
+    note_limit: Option<usize>,
+) -> Result<ReplayConfig, String> {
+    if note_limit == Some(0) {
+        return Err("The replay limit must be at least 1".to_string());
+    }
+    let config = ReplayConfig { note_limit };
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(config)
+}
+
+#[tauri::command]
+pub async fn get_replay_limit(store: State<'_, Store>) -> Result<ReplayConfig, String> {
+    Ok(load_config(&store).await)
+}
+
+/// The page of a yak's notes before `before_id`, the oldest note loaded so far, for
+/// scrolling back past what the snapshot held.
+#[tauri::command]
+pub async fn load_older(
+    store: State<'_, Store>,
+    state: State<'_, ReadModel>,
+    locks: State<'_, LockState>,
+    presentation: State<'_, PresentationState>,
+    yak_id: String,
+    before_id: String,
+    count: Option<usize>,
+) -> Result<OlderNotes, String> {
+    app_lock::ensure_unlocked()?;
+    let mut projection = state.snapshot(&store).await;
+    projection.yaks.retain(|id, _| *id == yak_id);
+    projection.notes.retain(|_, note| note.yak_id == yak_id);
+    projection.notes_by_yak.retain(|id, _| *id == yak_id);
+    let config = locks::load_config(&store).await;
+    locks.redact(&mut projection, config.hide_locked);
+    presentation.redact(&mut projection);
+    let count = count.unwrap_or(DEFAULT_PAGE);
+    older(&projection, &yak_id, &before_id, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_all_frames;
+    use crate::testing::{self, append};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_load_older() {
+        let (_dir, store) = testing::store();
+        let yak = append(&store, "yak.create", Some(json!({ "name": "Log" })));
+        let yak_id = yak.id.to_string();
+        let ids: Vec<String> = (0..5)
+            .map(|_| append(&store, "note.create", Some(json!({ "yak_id": yak_id }))))
+            .map(|note| note.id.to_string())
+            .collect();
+        let edit = json!({ "yak_id": yak_id, "note_id": ids[3] });
+        let edit_id = append(&store, "note.edit", Some(edit)).id.to_string();
+        let projection = Projection::from_frames(&read_all_frames(&store).await).into_current();
+
+        let mut snapshot = projection.clone();
+        snapshot.truncate(2);
+        assert_eq!(
+            snapshot.notes_by_yak[&yak_id],
+            vec![edit_id, ids[4].clone()]
+        );
+        assert_eq!(snapshot.notes.len(), 2);
+        assert_eq!(snapshot.older[&yak_id], 3);
+
+        // Any revision of the oldest loaded note works as the cursor
+        let page = older(&projection, &yak_id, &ids[3], 2).unwrap();
+        let page_ids: Vec<&str> = page.notes.iter().map(|note| note.id.as_str()).collect();
+        assert_eq!(page_ids, vec![ids[1].as_str(), ids[2].as_str()]);
+        assert_eq!(page.remaining, 1);
+        let page = older(&projection, &yak_id, &ids[1], 5).unwrap();
+        assert_eq!((page.notes.len(), page.remaining), (1, 0));
+        assert!(older(&projection, &yak_id, "missing", 5).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Decodes the handful of entities that matter for plain text.
+pub(crate) fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Crude HTML to text conversion: drops scripts and styles, turns block elements into line
+/// breaks and strips remaining tags.
+pub(crate) fn to_text(html: &str) -> String {
+    static DROP: OnceLock<Regex> = OnceLock::new();
+    static BLOCK: OnceLock<Regex> = OnceLock::new();
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let drop = DROP.get_or_init(|| {
+        Regex::new(
+            r"(?is)<(script|style|noscript|head)\b.*?</(script|style|noscript|head)>|<!--.*?-->",
+        )
+        .unwrap()
+    });
+    let block = BLOCK.get_or_init(|| {
+        Regex::new(r"(?i)<br\s*/?>|</(p|div|li|h[1-6]|tr|blockquote|pre|section|article)>").unwrap()
+    });
+    let tag = TAG.get_or_init(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+
+    let text = drop.replace_all(html, "");
+    let text = block.replace_all(&text, "\n");
+    let text = decode_entities(&tag.replace_all(&text, ""));
+
+    let mut out = String::new();
+    let mut blank = false;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            if !blank && !out.is_empty() {
+                out.push('\n');
+            }
+            blank = true;
+        } else {
+            out.push_str(line);
+            out.push('\n');
+            blank = false;
+        }
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_text() {
+        let html = "<html><head><title>x</title></head><body><h1>Hi &amp; bye</h1>\
+                    <script>alert(1)</script><p>one<br>two</p></body></html>";
+        assert_eq!(to_text(html), "Hi & bye\none\ntwo");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+use tauri::{AppHandle, State};
+use xs::store::Store;
+
+use super::{add_note, add_tag, create_yak, ImportReport};
+use crate::html::decode_entities;
+use crate::windows::emit_frame;
+
+/// Folders browsers export as the roots of their bookmark trees; they aren't made tags.
+const ROOT_FOLDER_ATTRS: &[&str] = &["PERSONAL_TOOLBAR_FOLDER", "UNFILED_BOOKMARKS_FOLDER"];
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Bookmark {
+    title: String,
+    url: String,
+    /// Seconds since the epoch, as `ADD_DATE`
+    added: Option<i64>,
+    /// Folders from the outermost in, then any `TAGS` Firefox kept
+    tags: Vec<String>,
+    description: String,
+}
+
+fn attrs(tag: &str) -> BTreeMap<String, String> {
+    static ATTR: OnceLock<Regex> = OnceLock::new();
+    let attr = ATTR.get_or_init(|| Regex::new(r#"([A-Za-z_]+)\s*=\s*"([^"]*)""#).unwrap());
+    attr.captures_iter(tag)
+        .map(|caps| (caps[1].to_uppercase(), decode_entities(&caps[2])))
+        .collect()
+}
+
+/// Parses the Netscape bookmark file Chrome, Firefox, Safari and Edge all export. Each
+/// `<DL>` opens the folder named by the `<H3>` before it, and a `<DD>` describes the
+/// bookmark above it.
+fn parse(html: &str) -> Vec<Bookmark> {
+    static TOKEN: OnceLock<Regex> = OnceLock::new();
+    let token = TOKEN.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?is)<H3([^>]*)>(.*?)</H3>",
+            r"|<A\s([^>]*)>(.*?)</A>",
+            r"|<DL\b[^>]*>|</DL>",
+            r"|<DD>([^<]*)",
+        ))
+        .unwrap()
+    });
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    let mut folders: Vec<Option<String>> = Vec::new();
+    let mut pending: Option<String> = None;
+    for caps in token.captures_iter(html) {
+        let text = |i: usize| decode_entities(caps.get(i).map_or("", |m| m.as_str()).trim());
+        let matched = caps[0].to_ascii_uppercase();
+        if let Some(folder_attrs) = caps.get(1) {
+            let is_root = attrs(folder_attrs.as_str())
+                .keys()
+                .any(|key| ROOT_FOLDER_ATTRS.contains(&key.as_str()));
+            pending = (!is_root).then(|| text(2)).filter(|name| !name.is_empty());
+        } else if let Some(link_attrs) = caps.get(3) {
+            let attrs = attrs(link_attrs.as_str());
+            let Some(url) = attrs.get("HREF") else {
+                continue;
+            };
+            let mut tags: Vec<String> = folders.iter().flatten().cloned().collect();
+            for tag in attrs
+                .get("TAGS")
+                .into_iter()
+                .flat_map(|tags| tags.split(','))
+            {
+                let tag = tag.trim();
+                if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+                    tags.push(tag.to_string());
+                }
+            }
+            bookmarks.push(Bookmark {
+                title: text(4),
+                url: url.clone(),
+                added: attrs.get("ADD_DATE").and_then(|date| date.parse().ok()),
+                tags,
+                description: String::new(),
+            });
+        } else if let Some(description) = caps.get(5) {
+            if let Some(last) = bookmarks.last_mut() {
+                last.description = decode_entities(description.as_str().trim());
+            }
+        } else if matched.starts_with("</DL") {
+            folders.pop();
+        } else {
+            folders.push(pending.take());
+        }
+    }
+    bookmarks
+}
+
+fn note_content(bookmark: &Bookmark) -> String {
+    let title = if bookmark.title.is_empty() {
+        &bookmark.url
+    } else {
+        &bookmark.title
+    };
+    let mut content = format!("# {title}\n\n{}\n", bookmark.url);
+    if !bookmark.description.is_empty() {
+        content.push_str(&format!("\n{}\n", bookmark.description));
+    }
+    content
+}
+
+/// The import, returning the notes written for bookmarks and their URLs.
+pub(crate) async fn import_file(
+    store: &Store,
+    path: &Path,
+    yak_id: Option<String>,
+) -> Result<(ImportReport, Vec<(String, String)>), String> {
+    let html = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let bookmarks = parse(&html);
+    if bookmarks.is_empty() {
+        return Err("No bookmarks found; is this a bookmarks HTML export?".to_string());
+    }
+    let yak_id = match yak_id {
+        Some(yak_id) => yak_id,
+        None => create_yak(store, "Bookmarks").await?,
+    };
+    let mut report = ImportReport {
+        yak_id: yak_id.clone(),
+        ..Default::default()
+    };
+    let mut links = Vec::new();
+    for bookmark in bookmarks {
+        if !bookmark.url.starts_with("http://") && !bookmark.url.starts_with("https://") {
+            report
+                .skipped
+                .push(format!("{}: not a web link", bookmark.url));
+            continue;
+        }
+        let meta = serde_json::json!({
+            "title": bookmark.title,
+            "url": bookmark.url,
+            "source": "bookmarks",
+            "created": bookmark
+                .added
+                .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+                .map(|time| time.to_rfc3339()),
+        });
+        let frame = add_note(store, &yak_id, &note_content(&bookmark), meta).await?;
+        let note_id = frame.id.to_string();
+        report.notes += 1;
+        for tag in &bookmark.tags {
+            add_tag(store, &yak_id, &note_id, tag).await?;
+            report.tags += 1;
+        }
+        links.push((note_id, bookmark.url));
+    }
+    Ok((report, links))
+}
+
+/// Imports a bookmarks HTML export from Chrome, Firefox, Safari or Edge: a note per link,
+/// tagged with the folders it was in. With `archive`, each page is then fetched and
+/// archived in the background, one at a time, as if added with `archive_url`.
+#[tauri::command]
+pub async fn import_bookmarks(
+    store: State<'_, Store>,
+    app: AppHandle,
+    path: String,
+    yak_id: Option<String>,
+    archive: Option<bool>,
+) -> Result<ImportReport, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let (report, links) = import_file(&store, Path::new(&path), yak_id).await?;
+    if archive.unwrap_or(false) {
+        let (store, yak_id) = (store.inner().clone(), report.yak_id.clone());
+        tokio::spawn(async move {
+            for (note_id, url) in links {
+                match crate::web::archive(&store, &url, &yak_id, Some(&note_id)).await {
+                    Ok(frame) => {
+                        let _ = emit_frame(&app, &frame);
+                    }
+                    Err(e) => eprintln!("Failed to archive {url}: {e}"),
+                }
+            }
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection::Projection;
+    use crate::read_all_frames;
+    use tempfile::tempdir;
+
+    const BOOKMARKS: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><H3 ADD_DATE="1700000000" PERSONAL_TOOLBAR_FOLDER="true">Bookmarks bar</H3>
+    <DL><p>
+        <DT><H3>Dev</H3>
+        <DL><p>
+            <DT><H3>Rust</H3>
+            <DL><p>
+                <DT><A HREF="https://doc.rust-lang.org/" ADD_DATE="1700000100" TAGS="docs,rust">The Rust &amp; Cargo docs</A>
+                <DD>Reference
+            </DL><p>
+            <DT><A HREF="javascript:alert(1)">Bookmarklet</A>
+        </DL><p>
+    </DL><p>
+    <DT><A HREF="https://example.com/">Example</A>
+</DL><p>
+"#;
+
+    #[test]
+    fn test_parse() {
+        let bookmarks = parse(BOOKMARKS);
+        assert_eq!(bookmarks.len(), 3);
+        assert_eq!(
+            bookmarks[0],
+            Bookmark {
+                title: "The Rust & Cargo docs".to_string(),
+                url: "https://doc.rust-lang.org/".to_string(),
+                added: Some(1700000100),
+                tags: vec!["Dev".into(), "Rust".into(), "docs".into(), "rust".into()],
+                description: "Reference".to_string(),
+            }
+        );
+        assert_eq!(bookmarks[1].tags, vec!["Dev"]);
+        assert!(bookmarks[2].tags.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_import_bookmarks() {
+        let dir = tempdir().unwrap();
+        let export = tempdir().unwrap();
+        let path = export.path().join("bookmarks.html");
+        std::fs::write(&path, BOOKMARKS).unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let (report, links) = import_file(&store, &path, None).await.unwrap();
+        assert_eq!(report.notes, 2);
+        assert_eq!(report.tags, 4);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(links[1].1, "https://example.com/");
+
+        let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+        projection.resolve_content(&store).await;
+        let note = &projection.notes[&links[0].0];
+        assert!(note.tags.contains("Rust"));
+        assert_eq!(
+            note.content.as_deref(),
+            Some("# The Rust & Cargo docs\n\nhttps://doc.rust-lang.org/\n\nReference\n")
+        );
+    }
+}
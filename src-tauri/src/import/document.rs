@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use xs::store::Store;
+
+use super::markdown::embedded_links;
+use super::{add_attachment, add_note, create_yak, ImportReport};
+
+const PANDOC: &str = "pandoc";
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Pandoc's reader for each document type it's asked to convert.
+fn input_format(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "docx" => Some("docx"),
+        "odt" => Some("odt"),
+        "epub" => Some("epub"),
+        "html" | "htm" => Some("html"),
+        _ => None,
+    }
+}
+
+/// Converts a document to markdown, writing its images below `media`. Raw HTML is left out
+/// so sized images come through as plain markdown images.
+async fn convert(path: &Path, format: &str, media: &Path) -> Result<String, String> {
+    let output = tokio::process::Command::new(PANDOC)
+        .arg(path)
+        .args(["--from", format, "--to", "gfm-raw_html", "--wrap=none"])
+        .arg(format!("--extract-media={}", media.display()))
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(TIMEOUT, output)
+        .await
+        .map_err(|_| format!("Conversion timed out after {}s", TIMEOUT.as_secs()))?
+        .map_err(|e| format!("Failed to run {PANDOC} (is it installed?): {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{PANDOC} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Rewrites the links pandoc gave extracted images, which point into `media`, to their
+/// path inside it. Returns the markdown and each image's new link and file.
+fn relink(markdown: &str, media: &Path) -> (String, Vec<(String, PathBuf)>) {
+    let mut relinked = markdown.to_string();
+    let mut images = Vec::new();
+    for link in embedded_links(markdown) {
+        let Ok(relative) = Path::new(&link).strip_prefix(media) else {
+            continue;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        relinked = relinked.replace(&link, &relative);
+        images.push((relative, PathBuf::from(&link)));
+    }
+    (relinked, images)
+}
+
+/// Adds the converted document as a note, with the images pandoc extracted to `media`.
+async fn add_document(
+    store: &Store,
+    path: &Path,
+    markdown: &str,
+    media: &Path,
+    yak_id: Option<String>,
+) -> Result<ImportReport, String> {
+    let (markdown, images) = relink(markdown, media);
+    let title = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("Document");
+    let yak_id = match yak_id {
+        Some(yak_id) => yak_id,
+        None => create_yak(store, title).await?,
+    };
+    let mut report = ImportReport {
+        yak_id: yak_id.clone(),
+        ..Default::default()
+    };
+    let meta = serde_json::json!({
+        "title": title,
+        "source_path": path.to_string_lossy(),
+    });
+    let note = add_note(store, &yak_id, markdown.trim(), meta).await?;
+    let note_id = note.id.to_string();
+    report.notes += 1;
+
+    for (link, file) in images {
+        let Ok(bytes) = tokio::fs::read(&file).await else {
+            report.skipped.push(format!("missing image {link}"));
+            continue;
+        };
+        let name = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&link);
+        let extra = serde_json::json!({ "link": link });
+        add_attachment(store, &yak_id, &note_id, name, &bytes, extra).await?;
+        report.attachments += 1;
+    }
+    Ok(report)
+}
+
+pub(crate) async fn import_file(
+    store: &Store,
+    path: &Path,
+    yak_id: Option<String>,
+) -> Result<ImportReport, String> {
+    let format = input_format(path).ok_or_else(|| {
+        format!(
+            "Can't import {}: not a docx, odt, epub or html file",
+            path.display()
+        )
+    })?;
+    let media = std::env::temp_dir().join(format!("yaks-import-{}", scru128::new()));
+    let result = match convert(path, format, &media).await {
+        Ok(markdown) => add_document(store, path, &markdown, &media, yak_id).await,
+        Err(e) => Err(e),
+    };
+    let _ = tokio::fs::remove_dir_all(&media).await;
+    result
+}
+
+/// Converts a Word, OpenDocument, EPUB or HTML document to a markdown note with pandoc,
+/// keeping its images as attachments. The note goes into a new yak named after the file
+/// unless `yak_id` is given.
+#[tauri::command]
+pub async fn import_document(
+    store: State<'_, Store>,
+    app: AppHandle,
+    path: String,
+    yak_id: Option<String>,
+) -> Result<ImportReport, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    import_file(&store, Path::new(&path), yak_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_format() {
+        assert_eq!(input_format(Path::new("Report.DOCX")), Some("docx"));
+        assert_eq!(input_format(Path::new("book.epub")), Some("epub"));
+        assert_eq!(input_format(Path::new("notes.md")), None);
+    }
+
+    #[test]
+    fn test_relink() {
+        let media = Path::new("/tmp/yaks-import-1");
+        let markdown = "# Report\n\n![Chart](/tmp/yaks-import-1/media/image1.png)\n\n\
+                        ![Logo](https://example.com/logo.png)\n";
+        let (markdown, images) = relink(markdown, media);
+        assert_eq!(
+            markdown,
+            "# Report\n\n![Chart](media/image1.png)\n\n![Logo](https://example.com/logo.png)\n"
+        );
+        assert_eq!(
+            images,
+            vec![(
+                "media/image1.png".to_string(),
+                PathBuf::from("/tmp/yaks-import-1/media/image1.png")
+            )]
+        );
+    }
+}
@@ -0,0 +1,354 @@
+use base64::Engine;
+use md5::{Digest, Md5};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::Serialize;
+use std::io::BufRead;
+use std::path::Path;
+use tauri::{AppHandle, Emitter, State};
+use xs::store::Store;
+
+use super::{add_attachment, add_note, add_tag, create_yak, ImportReport};
+use crate::crypto::to_hex;
+
+#[derive(Debug, Default)]
+struct Resource {
+    data: Vec<u8>,
+    mime: Option<String>,
+    file_name: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct EnexNote {
+    title: String,
+    content: String,
+    created: Option<String>,
+    updated: Option<String>,
+    source_url: Option<String>,
+    tags: Vec<String>,
+    resources: Vec<Resource>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportProgress {
+    pub source: String,
+    pub notes: usize,
+}
+
+/// Evernote timestamps look like `20200101T120000Z`.
+fn parse_enex_time(s: &str) -> Option<String> {
+    chrono::NaiveDateTime::parse_from_str(s.trim(), "%Y%m%dT%H%M%SZ")
+        .ok()
+        .map(|time| time.and_utc().to_rfc3339())
+}
+
+fn attr(element: &BytesStart, name: &str) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name.as_bytes())
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.into_owned())
+}
+
+/// Converts ENML (Evernote's XHTML dialect) into markdown-flavored text. `en-media` elements
+/// become image links keyed by the resource's md5 hash.
+pub(crate) fn enml_to_markdown(enml: &str) -> String {
+    let mut reader = Reader::from_str(enml);
+    let mut out = String::new();
+
+    let open = |out: &mut String, element: &BytesStart| match element.name().as_ref() {
+        b"br" => out.push('\n'),
+        b"li" => out.push_str("- "),
+        b"en-todo" => {
+            let checked = attr(element, "checked").as_deref() == Some("true");
+            out.push_str(if checked { "- [x] " } else { "- [ ] " });
+        }
+        b"en-media" => {
+            if let Some(hash) = attr(element, "hash") {
+                out.push_str(&format!("![]({hash})"));
+            }
+        }
+        name @ (b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6") => {
+            let level = (name[1] - b'0') as usize;
+            out.push_str(&format!("{} ", "#".repeat(level)));
+        }
+        _ => {}
+    };
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) => open(&mut out, &element),
+            Ok(Event::Empty(element)) => open(&mut out, &element),
+            Ok(Event::End(element)) => {
+                if matches!(
+                    element.name().as_ref(),
+                    b"div" | b"p" | b"li" | b"tr" | b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6"
+                ) && !out.ends_with('\n')
+                {
+                    out.push('\n');
+                }
+            }
+            Ok(Event::Text(text)) => {
+                if let Ok(text) = text.unescape() {
+                    out.push_str(&text);
+                }
+            }
+            Ok(Event::CData(data)) => out.push_str(&String::from_utf8_lossy(&data)),
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Malformed ENML, keeping partial content: {e}");
+                break;
+            }
+        }
+    }
+
+    let mut markdown = String::new();
+    let mut blank = false;
+    for line in out.lines().map(str::trim_end) {
+        if line.is_empty() {
+            if !blank && !markdown.is_empty() {
+                markdown.push('\n');
+            }
+            blank = true;
+        } else {
+            markdown.push_str(line);
+            markdown.push('\n');
+            blank = false;
+        }
+    }
+    markdown
+}
+
+/// Streams `<note>` elements out of an ENEX file one at a time.
+struct EnexReader<R: BufRead> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+}
+
+impl<R: BufRead> EnexReader<R> {
+    fn new(source: R) -> Self {
+        Self {
+            reader: Reader::from_reader(source),
+            buf: Vec::new(),
+        }
+    }
+
+    fn next_note(&mut self) -> Result<Option<EnexNote>, String> {
+        let mut note: Option<EnexNote> = None;
+        let mut resource: Option<Resource> = None;
+        let mut path: Vec<Vec<u8>> = Vec::new();
+        let mut text = String::new();
+
+        loop {
+            self.buf.clear();
+            let event = self
+                .reader
+                .read_event_into(&mut self.buf)
+                .map_err(|e| format!("Invalid ENEX file: {e}"))?;
+            match event {
+                Event::Start(element) => {
+                    let name = element.name().as_ref().to_vec();
+                    match name.as_slice() {
+                        b"note" => note = Some(EnexNote::default()),
+                        b"resource" => resource = Some(Resource::default()),
+                        _ => {}
+                    }
+                    path.push(name);
+                    text.clear();
+                }
+                Event::Text(t) => text.push_str(
+                    &t.unescape()
+                        .map_err(|e| format!("Invalid ENEX text: {e}"))?,
+                ),
+                Event::CData(data) => text.push_str(&String::from_utf8_lossy(&data)),
+                Event::End(_) => {
+                    let name = path.pop().unwrap_or_default();
+                    let value = std::mem::take(&mut text);
+                    let (Some(note), parent) = (note.as_mut(), path.last().map(Vec::as_slice))
+                    else {
+                        continue;
+                    };
+                    match (name.as_slice(), parent) {
+                        (b"note", _) => return Ok(Some(std::mem::take(note))),
+                        (b"title", Some(b"note")) => note.title = value.trim().to_string(),
+                        (b"content", Some(b"note")) => note.content = value,
+                        (b"created", Some(b"note")) => note.created = parse_enex_time(&value),
+                        (b"updated", Some(b"note")) => note.updated = parse_enex_time(&value),
+                        (b"tag", Some(b"note")) => note.tags.push(value.trim().to_string()),
+                        (b"source-url", _) => note.source_url = Some(value.trim().to_string()),
+                        (b"data", Some(b"resource")) => {
+                            let cleaned: String =
+                                value.chars().filter(|c| !c.is_whitespace()).collect();
+                            if let Some(resource) = resource.as_mut() {
+                                resource.data = base64::engine::general_purpose::STANDARD
+                                    .decode(cleaned)
+                                    .map_err(|e| format!("Invalid resource data: {e}"))?;
+                            }
+                        }
+                        (b"mime", Some(b"resource")) => {
+                            if let Some(resource) = resource.as_mut() {
+                                resource.mime = Some(value.trim().to_string());
+                            }
+                        }
+                        (b"file-name", _) => {
+                            if let Some(resource) = resource.as_mut() {
+                                resource.file_name = Some(value.trim().to_string());
+                            }
+                        }
+                        (b"resource", _) => {
+                            if let Some(resource) = resource.take() {
+                                note.resources.push(resource);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+pub(crate) async fn import_file(
+    store: &Store,
+    path: &Path,
+    yak_id: Option<String>,
+    progress: impl Fn(usize),
+) -> Result<ImportReport, String> {
+    let file =
+        std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+    let mut notes = EnexReader::new(std::io::BufReader::new(file));
+
+    let yak_id = match yak_id {
+        Some(yak_id) => yak_id,
+        None => {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("Evernote");
+            create_yak(store, name).await?
+        }
+    };
+    let mut report = ImportReport {
+        yak_id: yak_id.clone(),
+        ..Default::default()
+    };
+
+    while let Some(note) = notes.next_note()? {
+        let body = enml_to_markdown(&note.content);
+        let content = if note.title.is_empty() {
+            body
+        } else {
+            format!("# {}\n\n{body}", note.title)
+        };
+        let meta = serde_json::json!({
+            "title": note.title,
+            "created": note.created,
+            "updated": note.updated,
+            "source": "evernote",
+            "source_url": note.source_url,
+        });
+        let frame = add_note(store, &yak_id, &content, meta).await?;
+        let note_id = frame.id.to_string();
+        report.notes += 1;
+
+        for tag in note.tags.iter().filter(|tag| !tag.is_empty()) {
+            add_tag(store, &yak_id, &note_id, tag).await?;
+            report.tags += 1;
+        }
+
+        for (i, resource) in note.resources.iter().enumerate() {
+            let hash = to_hex(&Md5::digest(&resource.data));
+            let name = resource
+                .file_name
+                .clone()
+                .unwrap_or_else(|| format!("resource-{}", i + 1));
+            let mut extra = serde_json::json!({ "link": hash });
+            if let Some(mime) = &resource.mime {
+                extra["mime"] = mime.clone().into();
+            }
+            add_attachment(store, &yak_id, &note_id, &name, &resource.data, extra).await?;
+            report.attachments += 1;
+        }
+
+        progress(report.notes);
+    }
+
+    Ok(report)
+}
+
+/// Imports an Evernote `.enex` export, emitting `import-progress` events as notes are written.
+#[tauri::command]
+pub async fn import_enex(
+    store: State<'_, Store>,
+    app: AppHandle,
+    path: String,
+    yak_id: Option<String>,
+) -> Result<ImportReport, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let path = Path::new(&path);
+    import_file(&store, path, yak_id, |notes| {
+        let progress = ImportProgress {
+            source: path.to_string_lossy().into_owned(),
+            notes,
+        };
+        if let Err(e) = app.emit("import-progress", &progress) {
+            eprintln!("Failed to emit import progress: {e}");
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection::Projection;
+    use crate::read_all_frames;
+    use tempfile::tempdir;
+
+    const ENEX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<en-export>
+  <note>
+    <title>Groceries</title>
+    <content><![CDATA[<?xml version="1.0" encoding="UTF-8"?><en-note><div><en-todo checked="true"/>milk</div><div><en-media hash="5d41402abc4b2a76b9719d911017c592" type="text/plain"/></div></en-note>]]></content>
+    <created>20200101T120000Z</created>
+    <tag>home</tag>
+    <resource>
+      <data encoding="base64">aGVsbG8=</data>
+      <mime>text/plain</mime>
+      <resource-attributes><file-name>hello.txt</file-name></resource-attributes>
+    </resource>
+  </note>
+</en-export>"#;
+
+    #[test]
+    fn test_enml_to_markdown() {
+        let markdown = enml_to_markdown("<en-note><h1>Hi</h1><div>one<br/>two</div></en-note>");
+        assert_eq!(markdown, "# Hi\none\ntwo\n");
+    }
+
+    #[tokio::test]
+    async fn test_import_enex() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("export.enex");
+        std::fs::write(&path, ENEX).unwrap();
+
+        let store = Store::new(dir.path().join("store"));
+        let report = import_file(&store, &path, None, |_| {}).await.unwrap();
+        assert_eq!(report.notes, 1);
+        assert_eq!(report.tags, 1);
+        assert_eq!(report.attachments, 1);
+
+        let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+        projection.resolve_content(&store).await;
+        let note = projection.current_notes(&report.yak_id)[0];
+        let content = note.content.as_deref().unwrap();
+        assert!(content.starts_with("# Groceries"));
+        assert!(content.contains("- [x] milk"));
+        assert!(content.contains("![](5d41402abc4b2a76b9719d911017c592)"));
+        assert_eq!(note.attachments[0].name.as_deref(), Some("hello.txt"));
+    }
+}
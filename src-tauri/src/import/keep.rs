@@ -0,0 +1,226 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tauri::{AppHandle, State};
+use xs::store::Store;
+
+use super::{
+    add_attachment, add_note, add_tag, add_task, create_yak, read_dir, read_zip, set_flag,
+    ImportReport,
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct KeepNote {
+    title: String,
+    text_content: String,
+    is_pinned: bool,
+    is_archived: bool,
+    is_trashed: bool,
+    created_timestamp_usec: Option<i64>,
+    user_edited_timestamp_usec: Option<i64>,
+    color: Option<String>,
+    labels: Vec<KeepLabel>,
+    list_content: Vec<KeepListItem>,
+    attachments: Vec<KeepAttachment>,
+    annotations: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct KeepLabel {
+    name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct KeepListItem {
+    text: String,
+    is_checked: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct KeepAttachment {
+    file_path: String,
+    mimetype: Option<String>,
+}
+
+fn usec_to_rfc3339(usec: Option<i64>) -> Option<String> {
+    chrono::DateTime::from_timestamp_micros(usec?).map(|time| time.to_rfc3339())
+}
+
+/// Notes in the archive, oldest first, with their path inside the archive.
+fn keep_notes(entries: &BTreeMap<String, Vec<u8>>) -> Vec<(String, KeepNote)> {
+    let mut notes: Vec<(String, KeepNote)> = entries
+        .iter()
+        .filter(|(path, _)| path.ends_with(".json"))
+        .filter_map(|(path, bytes)| {
+            let note: KeepNote = serde_json::from_slice(bytes).ok()?;
+            // Takeout also contains non-note JSON, such as Labels.json
+            (note.created_timestamp_usec.is_some() || note.user_edited_timestamp_usec.is_some())
+                .then(|| (path.clone(), note))
+        })
+        .collect();
+    notes.sort_by_key(|(_, note)| {
+        note.created_timestamp_usec
+            .or(note.user_edited_timestamp_usec)
+            .unwrap_or_default()
+    });
+    notes
+}
+
+pub(crate) async fn import_takeout(
+    store: &Store,
+    path: &Path,
+    yak_id: Option<String>,
+) -> Result<ImportReport, String> {
+    let entries = if path.is_dir() {
+        read_dir(path)?
+    } else {
+        read_zip(path)?
+    };
+
+    let yak_id = match yak_id {
+        Some(yak_id) => yak_id,
+        None => create_yak(store, "Google Keep").await?,
+    };
+    let mut report = ImportReport {
+        yak_id: yak_id.clone(),
+        ..Default::default()
+    };
+
+    for (source_path, note) in keep_notes(&entries) {
+        if note.is_trashed {
+            report.skipped.push(format!("{source_path}: in trash"));
+            continue;
+        }
+
+        let mut content = String::new();
+        if !note.title.is_empty() {
+            content.push_str(&format!("# {}\n\n", note.title));
+        }
+        content.push_str(&note.text_content);
+        let meta = serde_json::json!({
+            "title": note.title,
+            "source": "google-keep",
+            "source_path": source_path,
+            "created": usec_to_rfc3339(note.created_timestamp_usec),
+            "updated": usec_to_rfc3339(note.user_edited_timestamp_usec),
+            "color": note.color,
+            "annotations": note.annotations,
+        });
+        let frame = add_note(store, &yak_id, content.trim_end(), meta).await?;
+        let note_id = frame.id.to_string();
+        report.notes += 1;
+
+        if note.is_pinned {
+            set_flag(store, "note.pin", &yak_id, &note_id, true).await?;
+        }
+        if note.is_archived {
+            set_flag(store, "note.archive", &yak_id, &note_id, true).await?;
+        }
+
+        for label in note.labels.iter().filter(|label| !label.name.is_empty()) {
+            add_tag(store, &yak_id, &note_id, &label.name).await?;
+            report.tags += 1;
+        }
+
+        for item in note
+            .list_content
+            .iter()
+            .filter(|item| !item.text.is_empty())
+        {
+            add_task(store, &yak_id, Some(&note_id), &item.text, item.is_checked).await?;
+        }
+
+        let folder = source_path
+            .rsplit_once('/')
+            .map_or(String::new(), |(folder, _)| format!("{folder}/"));
+        for attachment in &note.attachments {
+            // Takeout sometimes records `.jpeg` for files written as `.jpg`
+            let candidates = [
+                format!("{folder}{}", attachment.file_path),
+                format!("{folder}{}", attachment.file_path.replace(".jpeg", ".jpg")),
+            ];
+            let Some((name, bytes)) = candidates
+                .iter()
+                .find_map(|candidate| entries.get_key_value(candidate))
+            else {
+                report.skipped.push(format!(
+                    "{source_path}: missing attachment {}",
+                    attachment.file_path
+                ));
+                continue;
+            };
+            let name = name.rsplit('/').next().unwrap_or(name);
+            let mut extra = serde_json::json!({ "link": attachment.file_path });
+            if let Some(mime) = &attachment.mimetype {
+                extra["mime"] = mime.clone().into();
+            }
+            add_attachment(store, &yak_id, &note_id, name, bytes, extra).await?;
+            report.attachments += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Imports notes from a Google Takeout Keep export (the `Keep` folder or the Takeout zip).
+#[tauri::command]
+pub async fn import_keep_takeout(
+    store: State<'_, Store>,
+    app: AppHandle,
+    path: String,
+    yak_id: Option<String>,
+) -> Result<ImportReport, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    import_takeout(&store, Path::new(&path), yak_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection::Projection;
+    use crate::read_all_frames;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_import_keep_takeout() {
+        let dir = tempdir().unwrap();
+        let keep = dir.path().join("Keep");
+        std::fs::create_dir(&keep).unwrap();
+        std::fs::write(
+            keep.join("Shopping.json"),
+            r#"{
+                "title": "Shopping",
+                "textContent": "",
+                "isPinned": true,
+                "isArchived": false,
+                "isTrashed": false,
+                "createdTimestampUsec": 1600000000000000,
+                "labels": [{ "name": "home" }],
+                "listContent": [
+                    { "text": "milk", "isChecked": true },
+                    { "text": "eggs", "isChecked": false }
+                ]
+            }"#,
+        )
+        .unwrap();
+        std::fs::write(keep.join("Labels.txt"), "home\n").unwrap();
+
+        let store = Store::new(dir.path().join("store"));
+        let report = import_takeout(&store, &keep, None).await.unwrap();
+        assert_eq!(report.notes, 1);
+        assert_eq!(report.tags, 1);
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let note = projection.current_notes(&report.yak_id)[0];
+        assert!(note.pinned);
+        assert!(!note.archived);
+        let tasks = projection.yak_tasks(&report.yak_id);
+        assert_eq!(tasks.len(), 2);
+        assert!(tasks[0].done);
+        assert_eq!(tasks[0].note_id.as_deref(), Some(note.id.as_str()));
+    }
+}
@@ -0,0 +1,291 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, State};
+use xs::store::Store;
+
+use super::{add_attachment, add_note, add_tag, create_yak, percent_decode, ImportReport};
+use crate::frontmatter;
+use crate::properties::{self, append_property};
+
+struct MarkdownFile {
+    path: PathBuf,
+    /// Seconds since the epoch; files are imported oldest first
+    mtime: u64,
+}
+
+fn mtime(path: &Path) -> u64 {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Walks `dir`, skipping hidden entries such as `.obsidian` and `.git`, and symlinks, which
+/// could loop or lead out of the vault.
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with('.'));
+        let file_type = entry.file_type()?;
+        if hidden || file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+}
+
+/// Image targets embedded in markdown: `![alt](path)` and Obsidian's `![[file]]`.
+pub(crate) fn embedded_links(content: &str) -> Vec<String> {
+    static MARKDOWN: OnceLock<Regex> = OnceLock::new();
+    static WIKI: OnceLock<Regex> = OnceLock::new();
+    let markdown = MARKDOWN
+        .get_or_init(|| Regex::new(r#"!\[[^\]]*\]\(<?([^)\s>]+)>?(?:\s+"[^"]*")?\)"#).unwrap());
+    let wiki = WIKI.get_or_init(|| Regex::new(r"!\[\[([^\]|#]+)(?:[|#][^\]]*)?\]\]").unwrap());
+
+    let mut links: Vec<String> = markdown
+        .captures_iter(content)
+        .chain(wiki.captures_iter(content))
+        .map(|caps| caps[1].trim().to_string())
+        .filter(|link| !link.contains("://") && !link.starts_with("data:"))
+        .collect();
+    let mut seen = HashSet::new();
+    links.retain(|link| seen.insert(link.clone()));
+    links
+}
+
+/// The file an embedded link points at, if it's inside `root` (already canonical). Links
+/// that climb out of the vault, directly or through a symlink, resolve to nothing.
+fn resolve_link(
+    link: &str,
+    note_dir: &Path,
+    root: &Path,
+    by_name: &HashMap<String, PathBuf>,
+) -> Option<PathBuf> {
+    let inside = |path: &Path| {
+        let path = path.canonicalize().ok()?;
+        (path.starts_with(root) && path.is_file()).then_some(path)
+    };
+    let link = percent_decode(link);
+    if let Some(path) = inside(&note_dir.join(&link)) {
+        return Some(path);
+    }
+    // Obsidian resolves bare file names anywhere in the vault
+    let name = Path::new(&link).file_name()?.to_str()?.to_lowercase();
+    inside(by_name.get(&name)?)
+}
+
+pub(crate) async fn import_dir(
+    store: &Store,
+    root: &Path,
+    yak_id: Option<String>,
+) -> Result<ImportReport, String> {
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", root.display()));
+    }
+    let canonical_root = root
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {e}", root.display()))?;
+
+    let mut paths = Vec::new();
+    walk(root, &mut paths).map_err(|e| format!("Failed to read {}: {e}", root.display()))?;
+
+    let by_name: HashMap<String, PathBuf> = paths
+        .iter()
+        .filter_map(|path| Some((path.file_name()?.to_str()?.to_lowercase(), path.clone())))
+        .collect();
+
+    let mut files: Vec<MarkdownFile> = paths
+        .into_iter()
+        .filter(|path| is_markdown(path))
+        .map(|path| MarkdownFile {
+            mtime: mtime(&path),
+            path,
+        })
+        .collect();
+    files.sort_by(|a, b| a.mtime.cmp(&b.mtime).then_with(|| a.path.cmp(&b.path)));
+
+    let yak_id = match yak_id {
+        Some(yak_id) => yak_id,
+        None => {
+            let name = root
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("Imported notes");
+            create_yak(store, name).await?
+        }
+    };
+
+    let mut report = ImportReport {
+        yak_id: yak_id.clone(),
+        ..Default::default()
+    };
+
+    for file in files {
+        let relative = file.path.strip_prefix(root).unwrap_or(&file.path);
+        let content = match tokio::fs::read_to_string(&file.path).await {
+            Ok(content) => content,
+            Err(e) => {
+                report.skipped.push(format!("{}: {e}", relative.display()));
+                continue;
+            }
+        };
+
+        let (fields, body) = frontmatter::split(&content);
+        let tags = fields.as_ref().map(frontmatter::tags).unwrap_or_default();
+        let (fields, properties) = properties::from_frontmatter(fields);
+        let title = file
+            .path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        let meta = serde_json::json!({
+            "title": title,
+            "source_path": relative.to_string_lossy(),
+            "mtime": file.mtime,
+            "frontmatter": fields,
+        });
+        let note = add_note(store, &yak_id, body, meta).await?;
+        let note_id = note.id.to_string();
+        report.notes += 1;
+
+        for tag in tags {
+            add_tag(store, &yak_id, &note_id, &tag).await?;
+            report.tags += 1;
+        }
+        for (key, value) in properties {
+            append_property(store, &yak_id, &note_id, &key, value).await?;
+        }
+
+        let note_dir = file.path.parent().unwrap_or(root);
+        for link in embedded_links(body) {
+            let Some(path) = resolve_link(&link, note_dir, &canonical_root, &by_name) else {
+                report
+                    .skipped
+                    .push(format!("{}: missing attachment {link}", relative.display()));
+                continue;
+            };
+            let bytes = tokio::fs::read(&path)
+                .await
+                .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(&link);
+            add_attachment(
+                store,
+                &yak_id,
+                &note_id,
+                name,
+                &bytes,
+                serde_json::json!({ "link": link }),
+            )
+            .await?;
+            report.attachments += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Imports a folder of markdown files (such as an Obsidian vault) into a yak, creating a new
+/// yak named after the folder unless `yak_id` is given.
+#[tauri::command]
+pub async fn import_markdown_dir(
+    store: State<'_, Store>,
+    app: AppHandle,
+    path: String,
+    yak_id: Option<String>,
+) -> Result<ImportReport, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    import_dir(&store, Path::new(&path), yak_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection::Projection;
+    use crate::read_all_frames;
+    use crate::testing;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_embedded_links() {
+        let content = "![cat](img/cat.png) ![[diagram.svg|200]] ![remote](https://x.y/z.png)";
+        assert_eq!(embedded_links(content), vec!["img/cat.png", "diagram.svg"]);
+    }
+
+    #[tokio::test]
+    async fn test_import_markdown_dir() {
+        let vault = tempdir().unwrap();
+        std::fs::create_dir(vault.path().join("img")).unwrap();
+        std::fs::write(vault.path().join("img/cat.png"), b"png").unwrap();
+        std::fs::write(
+            vault.path().join("Cats.md"),
+            "---\ntags: [pets]\n---\n# Cats\n![cat](img/cat.png)\n",
+        )
+        .unwrap();
+
+        let (_dir, store) = testing::store();
+        let report = import_dir(&store, vault.path(), None).await.unwrap();
+        assert_eq!(report.notes, 1);
+        assert_eq!(report.tags, 1);
+        assert_eq!(report.attachments, 1);
+        assert!(report.skipped.is_empty());
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let notes = projection.current_notes(&report.yak_id);
+        assert_eq!(notes.len(), 1);
+        assert!(notes[0].tags.contains("pets"));
+        assert_eq!(notes[0].attachments[0].mime.as_deref(), Some("image/png"));
+    }
+
+    #[tokio::test]
+    async fn test_links_outside_the_vault_are_skipped() {
+        let outside = tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), b"secret").unwrap();
+        let vault = tempdir().unwrap();
+        let escape = outside.path().join("secret.txt");
+        std::fs::write(
+            vault.path().join("Note.md"),
+            format!(
+                "![up](../{}/secret.txt)\n![abs]({})\n![[secret.txt]]\n![link](linked.txt)\n",
+                outside.path().file_name().unwrap().to_str().unwrap(),
+                escape.display()
+            ),
+        )
+        .unwrap();
+        // Symlinked dirs aren't walked, whether they lead out of the vault or back into it
+        std::fs::write(outside.path().join("Elsewhere.md"), b"elsewhere").unwrap();
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&escape, vault.path().join("linked.txt")).unwrap();
+            std::os::unix::fs::symlink(outside.path(), vault.path().join("Outside")).unwrap();
+            std::os::unix::fs::symlink(vault.path(), vault.path().join("Loop")).unwrap();
+        }
+
+        let (_dir, store) = testing::store();
+        let report = import_dir(&store, vault.path(), None).await.unwrap();
+        assert_eq!(report.notes, 1);
+        assert_eq!(report.attachments, 0);
+        assert_eq!(report.skipped.len(), 4);
+    }
+}
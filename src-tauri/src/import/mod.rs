@@ -0,0 +1,293 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+use xs::store::{Frame, Store};
+
+use crate::{append_frame, mime};
+
+mod bookmarks;
+mod document;
+mod enex;
+mod keep;
+pub(crate) mod markdown;
+mod notion;
+mod simplenote;
+mod standard_notes;
+
+pub use bookmarks::import_bookmarks;
+pub use document::import_document;
+pub use enex::import_enex;
+pub use keep::import_keep_takeout;
+pub use markdown::import_markdown_dir;
+pub use notion::import_notion_zip;
+pub use simplenote::import_simplenote;
+pub use standard_notes::import_standard_notes;
+
+/// What an importer did, returned to the frontend once it finishes.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub yak_id: String,
+    pub notes: usize,
+    pub attachments: usize,
+    pub tags: usize,
+    /// Sources that could not be imported, with the reason
+    pub skipped: Vec<String>,
+}
+
+/// A note read from another app's JSON export, before anything is written.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct PlannedNote {
+    /// The note's id in the source app
+    pub source_id: String,
+    pub title: Option<String>,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub pinned: bool,
+    pub archived: bool,
+    pub trashed: bool,
+    /// RFC 3339, as the export has them
+    pub created: Option<String>,
+    pub updated: Option<String>,
+}
+
+/// What importing planned notes would create, or did; `report` is only set once written.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub notes: usize,
+    pub pinned: usize,
+    pub archived: usize,
+    /// Imported and then deleted, so they sit in the trash and can be restored
+    pub trashed: usize,
+    /// Tag -> notes carrying it
+    pub tags: BTreeMap<String, usize>,
+    pub report: Option<ImportReport>,
+}
+
+/// Writes `notes` into `yak_id`, or a new yak named `yak_name`, keeping their tags and
+/// pinned, archived and trashed state. With `dry_run`, only counts what would be written.
+pub(crate) async fn import_planned(
+    store: &Store,
+    notes: Vec<PlannedNote>,
+    yak_id: Option<String>,
+    yak_name: &str,
+    source: &str,
+    dry_run: bool,
+) -> Result<ImportSummary, String> {
+    let mut summary = ImportSummary {
+        notes: notes.len(),
+        ..Default::default()
+    };
+    for note in &notes {
+        summary.pinned += usize::from(note.pinned);
+        summary.archived += usize::from(note.archived);
+        summary.trashed += usize::from(note.trashed);
+        for tag in &note.tags {
+            *summary.tags.entry(tag.clone()).or_default() += 1;
+        }
+    }
+    if dry_run {
+        return Ok(summary);
+    }
+
+    let yak_id = match yak_id {
+        Some(yak_id) => yak_id,
+        None => create_yak(store, yak_name).await?,
+    };
+    let mut report = ImportReport {
+        yak_id: yak_id.clone(),
+        ..Default::default()
+    };
+    for note in notes {
+        let meta = serde_json::json!({
+            "title": note.title,
+            "source": source,
+            "source_id": note.source_id,
+            "created": note.created,
+            "updated": note.updated,
+        });
+        let frame = add_note(store, &yak_id, &note.content, meta).await?;
+        let note_id = frame.id.to_string();
+        report.notes += 1;
+        if note.pinned {
+            set_flag(store, "note.pin", &yak_id, &note_id, true).await?;
+        }
+        if note.archived {
+            set_flag(store, "note.archive", &yak_id, &note_id, true).await?;
+        }
+        for tag in &note.tags {
+            add_tag(store, &yak_id, &note_id, tag).await?;
+            report.tags += 1;
+        }
+        if note.trashed {
+            let meta = serde_json::json!({ "yak_id": yak_id, "note_id": note_id });
+            append_frame(store, "note.delete", None, Some(meta)).await?;
+        }
+    }
+    summary.report = Some(report);
+    Ok(summary)
+}
+
+/// Reads every file in a zip archive into memory, keyed by its path inside the archive.
+pub(crate) fn read_zip(path: &Path) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    let file =
+        std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("Invalid zip: {e}"))?;
+    let mut entries = BTreeMap::new();
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| format!("Invalid zip entry: {e}"))?;
+        if entry.is_dir() || entry.name().starts_with("__MACOSX") {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        entry
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Failed to read {}: {e}", entry.name()))?;
+        entries.insert(entry.name().to_string(), bytes);
+    }
+    Ok(entries)
+}
+
+/// Like `read_zip`, for a directory: every file below `root`, keyed by `/`-separated path.
+pub(crate) fn read_dir(root: &Path) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    fn walk(
+        root: &Path,
+        dir: &Path,
+        entries: &mut BTreeMap<String, Vec<u8>>,
+    ) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                walk(root, &path, entries)?;
+            } else {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                let key = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                entries.insert(key, std::fs::read(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    let mut entries = BTreeMap::new();
+    walk(root, root, &mut entries)
+        .map_err(|e| format!("Failed to read {}: {e}", root.display()))?;
+    Ok(entries)
+}
+
+/// Decodes `%XX` escapes in a link target.
+pub(crate) fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = if bytes[i] == b'%' {
+            s.get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match hex {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Creates the yak that an import writes into, named after its source.
+pub(crate) async fn create_yak(store: &Store, name: &str) -> Result<String, String> {
+    let frame = append_frame(
+        store,
+        "yak.create",
+        None,
+        Some(serde_json::json!({ "name": name, "imported": true })),
+    )
+    .await?;
+    Ok(frame.id.to_string())
+}
+
+pub(crate) async fn add_note(
+    store: &Store,
+    yak_id: &str,
+    content: &str,
+    meta: serde_json::Value,
+) -> Result<Frame, String> {
+    let mut fields = match meta {
+        serde_json::Value::Object(fields) => fields,
+        _ => serde_json::Map::new(),
+    };
+    fields.insert("yak_id".to_string(), yak_id.into());
+    let content = (!content.is_empty()).then_some(content.as_bytes());
+    append_frame(store, "note.create", content, Some(fields.into())).await
+}
+
+/// Sets a note's pinned (`note.pin`) or archived (`note.archive`) flag.
+pub(crate) async fn set_flag(
+    store: &Store,
+    topic: &str,
+    yak_id: &str,
+    note_id: &str,
+    value: bool,
+) -> Result<Frame, String> {
+    let key = if topic == "note.pin" {
+        "pinned"
+    } else {
+        "archived"
+    };
+    let meta = serde_json::json!({ "yak_id": yak_id, "note_id": note_id, key: value });
+    append_frame(store, topic, None, Some(meta)).await
+}
+
+pub(crate) async fn add_task(
+    store: &Store,
+    yak_id: &str,
+    note_id: Option<&str>,
+    text: &str,
+    done: bool,
+) -> Result<Frame, String> {
+    let meta = serde_json::json!({ "yak_id": yak_id, "note_id": note_id, "done": done });
+    append_frame(store, "task.create", Some(text.as_bytes()), Some(meta)).await
+}
+
+pub(crate) async fn add_tag(
+    store: &Store,
+    yak_id: &str,
+    note_id: &str,
+    tag: &str,
+) -> Result<Frame, String> {
+    let meta = serde_json::json!({ "yak_id": yak_id, "note_id": note_id, "tag": tag });
+    append_frame(store, "tag.add", None, Some(meta)).await
+}
+
+pub(crate) async fn add_attachment(
+    store: &Store,
+    yak_id: &str,
+    note_id: &str,
+    name: &str,
+    content: &[u8],
+    extra: serde_json::Value,
+) -> Result<Frame, String> {
+    let mut meta = serde_json::json!({
+        "yak_id": yak_id,
+        "note_id": note_id,
+        "name": name,
+        "mime": mime::from_name(name),
+        "size": content.len(),
+    });
+    if let (Some(meta), serde_json::Value::Object(extra)) = (meta.as_object_mut(), extra) {
+        meta.extend(extra);
+    }
+    append_frame(store, "attachment.add", Some(content), Some(meta)).await
+}
@@ -0,0 +1,304 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::OnceLock;
+use tauri::{AppHandle, State};
+use xs::store::Store;
+
+use super::{
+    add_attachment, add_note, add_tag, create_yak, percent_decode, read_zip, ImportReport,
+};
+use crate::html;
+
+/// What an import would create, returned instead of writing anything on a dry run.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct NotionSummary {
+    pub pages: usize,
+    pub attachments: usize,
+    /// Database name -> number of row pages, each imported as a note tagged with the name
+    pub databases: BTreeMap<String, usize>,
+    pub report: Option<ImportReport>,
+}
+
+/// Notion suffixes exported file names with a 32 character hex id: `Page 0123abcd….md`.
+pub(crate) fn clean_title(stem: &str) -> String {
+    static ID_SUFFIX: OnceLock<Regex> = OnceLock::new();
+    let id_suffix = ID_SUFFIX.get_or_init(|| Regex::new(r"\s+[0-9a-f]{32}$").unwrap());
+    id_suffix.replace(stem, "").trim().to_string()
+}
+
+fn stem(path: &str) -> &str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.rsplit_once('.').map_or(name, |(stem, _)| stem)
+}
+
+fn is_page(path: &str) -> bool {
+    path.ends_with(".md") || path.ends_with(".html")
+}
+
+/// Resolves a link relative to the page at `from`, normalizing `.` and `..`.
+fn resolve(from: &str, link: &str) -> String {
+    let mut parts: Vec<&str> = from.split('/').collect();
+    parts.pop();
+    for part in link.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+struct Plan {
+    /// Pages ordered deepest first, so sub-pages exist before the pages linking to them
+    pages: Vec<String>,
+    /// Folder prefix of each database's row pages -> database name
+    databases: HashMap<String, String>,
+}
+
+fn plan(entries: &BTreeMap<String, Vec<u8>>) -> Plan {
+    let databases = entries
+        .keys()
+        .filter(|path| path.ends_with(".csv") && !path.ends_with("_all.csv"))
+        .map(|path| {
+            let folder = path.trim_end_matches(".csv").to_string() + "/";
+            (folder, clean_title(stem(path)))
+        })
+        .collect();
+    let mut pages: Vec<String> = entries
+        .keys()
+        .filter(|path| is_page(path))
+        .cloned()
+        .collect();
+    pages.sort_by_key(|path| std::cmp::Reverse(path.matches('/').count()));
+    Plan { pages, databases }
+}
+
+fn database_of<'a>(plan: &'a Plan, page: &str) -> Option<&'a String> {
+    plan.databases
+        .iter()
+        .find(|(folder, _)| {
+            page.starts_with(folder.as_str()) && !page[folder.len()..].contains('/')
+        })
+        .map(|(_, name)| name)
+}
+
+struct Link {
+    text: String,
+    label: String,
+    target: String,
+    image: bool,
+}
+
+fn links(content: &str) -> Vec<Link> {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    let link = LINK.get_or_init(|| Regex::new(r"(!?)\[([^\]]*)\]\(([^)\s]+)\)").unwrap());
+    link.captures_iter(content)
+        .map(|caps| Link {
+            text: caps[0].to_string(),
+            label: caps[2].to_string(),
+            target: caps[3].to_string(),
+            image: &caps[1] == "!",
+        })
+        .collect()
+}
+
+pub(crate) async fn import_archive(
+    store: &Store,
+    path: &Path,
+    yak_id: Option<String>,
+    dry_run: bool,
+) -> Result<NotionSummary, String> {
+    let entries = read_zip(path)?;
+    let plan = plan(&entries);
+
+    let mut summary = NotionSummary {
+        pages: plan.pages.len(),
+        ..Default::default()
+    };
+    for page in &plan.pages {
+        if let Some(database) = database_of(&plan, page) {
+            *summary.databases.entry(database.clone()).or_default() += 1;
+        }
+        let content = String::from_utf8_lossy(&entries[page]);
+        summary.attachments += links(&content)
+            .iter()
+            .filter(|link| {
+                link.image && entries.contains_key(&resolve(page, &percent_decode(&link.target)))
+            })
+            .count();
+    }
+    if dry_run {
+        return Ok(summary);
+    }
+
+    let yak_id = match yak_id {
+        Some(yak_id) => yak_id,
+        None => {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("Notion");
+            create_yak(store, name).await?
+        }
+    };
+    let mut report = ImportReport {
+        yak_id: yak_id.clone(),
+        ..Default::default()
+    };
+    let mut note_ids: HashMap<String, String> = HashMap::new();
+
+    for page in &plan.pages {
+        let raw = String::from_utf8_lossy(&entries[page]);
+        let mut content = if page.ends_with(".html") {
+            html::to_text(&raw)
+        } else {
+            raw.into_owned()
+        };
+
+        // Links to sub-pages become wiki links to the notes created for them
+        let mut images = Vec::new();
+        for link in links(&content) {
+            let resolved = resolve(page, &percent_decode(&link.target));
+            if link.image {
+                if entries.contains_key(&resolved) {
+                    images.push((link.target, resolved));
+                }
+            } else if let Some(note_id) = note_ids.get(&resolved) {
+                content = content.replace(&link.text, &format!("[[{note_id}|{}]]", link.label));
+            }
+        }
+
+        let title = clean_title(stem(page));
+        let parent = page
+            .rsplit_once('/')
+            .map(|(folder, _)| clean_title(folder.rsplit('/').next().unwrap_or(folder)));
+        let meta = serde_json::json!({
+            "title": title,
+            "source": "notion",
+            "source_path": page,
+            "parent_title": parent,
+        });
+        let note = add_note(store, &yak_id, &content, meta).await?;
+        let note_id = note.id.to_string();
+        note_ids.insert(page.clone(), note_id.clone());
+        report.notes += 1;
+
+        if let Some(database) = database_of(&plan, page) {
+            add_tag(store, &yak_id, &note_id, database).await?;
+            report.tags += 1;
+        }
+
+        for (link, resolved) in images {
+            let name = resolved.rsplit('/').next().unwrap_or(&resolved);
+            add_attachment(
+                store,
+                &yak_id,
+                &note_id,
+                name,
+                &entries[&resolved],
+                serde_json::json!({ "link": link }),
+            )
+            .await?;
+            report.attachments += 1;
+        }
+    }
+
+    summary.report = Some(report);
+    Ok(summary)
+}
+
+/// Imports a Notion "Markdown & CSV" or "HTML" export. With `dry_run`, only reports what
+/// would be created.
+#[tauri::command]
+pub async fn import_notion_zip(
+    store: State<'_, Store>,
+    app: AppHandle,
+    path: String,
+    yak_id: Option<String>,
+    dry_run: Option<bool>,
+) -> Result<NotionSummary, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    import_archive(&store, Path::new(&path), yak_id, dry_run.unwrap_or(false)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection::Projection;
+    use crate::read_all_frames;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    const ID: &str = "0123456789abcdef0123456789abcdef";
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        for (name, bytes) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(bytes).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_clean_title_and_resolve() {
+        assert_eq!(clean_title(&format!("Project Notes {ID}")), "Project Notes");
+        assert_eq!(resolve("a/b/page.md", "../img/x.png"), "a/img/x.png");
+    }
+
+    #[tokio::test]
+    async fn test_import_notion_zip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("notion.zip");
+        let parent = format!("Home {ID}.md");
+        let child = format!("Home {ID}/Child {ID}.md");
+        let parent_content = format!("# Home\n[Child](Home%20{ID}/Child%20{ID}.md)\n");
+        let child_content = "# Child\n![](pic.png)\n".to_string();
+        let image = format!("Home {ID}/pic.png");
+        let row = format!("Tasks {ID}/Row {ID}.md");
+        write_zip(
+            &path,
+            &[
+                (&parent, parent_content.as_bytes()),
+                (&child, child_content.as_bytes()),
+                (&image, b"png"),
+                (&format!("Tasks {ID}.csv"), b"Name\nRow\n"),
+                (&row, b"# Row\n"),
+            ],
+        );
+
+        let store = Store::new(dir.path().join("store"));
+        let summary = import_archive(&store, &path, None, true).await.unwrap();
+        assert_eq!(summary.pages, 3);
+        assert_eq!(summary.attachments, 1);
+        assert_eq!(summary.databases.get("Tasks"), Some(&1));
+        assert!(summary.report.is_none());
+        assert!(read_all_frames(&store).await.is_empty());
+
+        let summary = import_archive(&store, &path, None, false).await.unwrap();
+        let report = summary.report.unwrap();
+        assert_eq!(report.notes, 3);
+        assert_eq!(report.attachments, 1);
+        assert_eq!(report.tags, 1);
+
+        let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+        projection.resolve_content(&store).await;
+        let home = projection
+            .current_notes(&report.yak_id)
+            .into_iter()
+            .find(|note| {
+                note.content
+                    .as_deref()
+                    .unwrap_or_default()
+                    .starts_with("# Home")
+            })
+            .unwrap();
+        assert!(home.content.as_deref().unwrap().contains("[["));
+    }
+}
@@ -0,0 +1,146 @@
+use serde::Deserialize;
+use std::path::Path;
+use tauri::{AppHandle, State};
+use xs::store::Store;
+
+use super::{import_planned, read_zip, ImportSummary, PlannedNote};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Export {
+    active_notes: Vec<SimpleNote>,
+    trashed_notes: Vec<SimpleNote>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct SimpleNote {
+    id: String,
+    content: String,
+    creation_date: Option<String>,
+    last_modified: Option<String>,
+    tags: Vec<String>,
+    pinned: bool,
+    /// Older exports flag pinned notes here rather than with `pinned`
+    system_tags: Vec<String>,
+}
+
+/// The notes in a Simplenote `notes.json`, active then trashed. Simplenote has no titles
+/// of its own; the first line of the content stands in for one.
+fn parse(json: &[u8]) -> Result<Vec<PlannedNote>, String> {
+    let export: Export =
+        serde_json::from_slice(json).map_err(|e| format!("Invalid Simplenote export: {e}"))?;
+    let active = export.active_notes.into_iter().map(|note| (note, false));
+    let trashed = export.trashed_notes.into_iter().map(|note| (note, true));
+    Ok(active
+        .chain(trashed)
+        .map(|(note, trashed)| PlannedNote {
+            pinned: note.pinned || note.system_tags.iter().any(|tag| tag == "pinned"),
+            source_id: note.id,
+            title: None,
+            content: note.content.replace("\r\n", "\n"),
+            tags: note
+                .tags
+                .into_iter()
+                .filter(|tag| !tag.trim().is_empty())
+                .collect(),
+            archived: false,
+            trashed,
+            created: note.creation_date,
+            updated: note.last_modified,
+        })
+        .collect())
+}
+
+/// The export's `notes.json`, whether `path` is that file or the zip Simplenote exports.
+fn read_export(path: &Path) -> Result<Vec<u8>, String> {
+    if !path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+    {
+        return std::fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()));
+    }
+    read_zip(path)?
+        .into_iter()
+        .find(|(name, _)| name == "notes.json" || name.ends_with("/notes.json"))
+        .map(|(_, bytes)| bytes)
+        .ok_or_else(|| "No notes.json in the Simplenote export".to_string())
+}
+
+/// Imports a Simplenote export (the zip, or `notes.json` from it) with tags and pinned
+/// state; trashed notes are imported into the trash. With `dry_run`, only reports what
+/// would be created.
+#[tauri::command]
+pub async fn import_simplenote(
+    store: State<'_, Store>,
+    app: AppHandle,
+    path: String,
+    yak_id: Option<String>,
+    dry_run: Option<bool>,
+) -> Result<ImportSummary, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let notes = parse(&read_export(Path::new(&path))?)?;
+    let dry_run = dry_run.unwrap_or(false);
+    import_planned(&store, notes, yak_id, "Simplenote", "simplenote", dry_run).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection::Projection;
+    use crate::read_all_frames;
+    use tempfile::tempdir;
+
+    const EXPORT: &str = r#"{
+        "activeNotes": [
+            {
+                "id": "a1",
+                "content": "Groceries\r\nmilk",
+                "creationDate": "2021-03-04T10:11:12.345Z",
+                "lastModified": "2021-03-05T10:11:12.345Z",
+                "tags": ["home", " "],
+                "pinned": true
+            },
+            { "id": "a2", "content": "Ideas", "systemTags": ["pinned", "markdown"] },
+            { "id": "a3", "content": "Plain" }
+        ],
+        "trashedNotes": [{ "id": "t1", "content": "Old list", "tags": ["home"] }]
+    }"#;
+
+    #[tokio::test]
+    async fn test_import_simplenote() {
+        let notes = parse(EXPORT.as_bytes()).unwrap();
+        assert_eq!(notes.len(), 4);
+        assert_eq!(notes[0].content, "Groceries\nmilk");
+        assert_eq!(notes[0].tags, vec!["home"]);
+        assert!(notes[1].pinned && !notes[2].pinned);
+        assert!(notes[3].trashed);
+
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let dry = import_planned(
+            &store,
+            notes.clone(),
+            None,
+            "Simplenote",
+            "simplenote",
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!((dry.notes, dry.pinned, dry.trashed), (4, 2, 1));
+        assert_eq!(dry.tags["home"], 2);
+        assert!(dry.report.is_none());
+        assert!(read_all_frames(&store).await.is_empty());
+
+        let summary = import_planned(&store, notes, None, "Simplenote", "simplenote", false)
+            .await
+            .unwrap();
+        let report = summary.report.unwrap();
+        assert_eq!((report.notes, report.tags), (4, 2));
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let current = projection.current_notes(&report.yak_id);
+        assert_eq!(current.len(), 3);
+        assert!(current[0].pinned && current[0].tags.contains("home"));
+    }
+}
@@ -0,0 +1,172 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use tauri::{AppHandle, State};
+use xs::store::Store;
+
+use super::{import_planned, ImportSummary, PlannedNote};
+
+/// Where older Standard Notes versions keep `pinned` and `archived`.
+const APP_DATA: &str = "org.standardnotes.sn";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Backup {
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Item {
+    uuid: String,
+    content_type: String,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+    deleted: bool,
+    /// An object in decrypted backups, a `004:…` string in encrypted ones
+    content: Value,
+}
+
+fn flag(content: &Value, key: &str) -> bool {
+    content
+        .get(key)
+        .or_else(|| content.get("appData")?.get(APP_DATA)?.get(key))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+fn text<'a>(content: &'a Value, key: &str) -> &'a str {
+    content.get(key).and_then(Value::as_str).unwrap_or_default()
+}
+
+/// The notes in a decrypted Standard Notes backup, tagged by the tags that reference them.
+fn parse(json: &[u8]) -> Result<Vec<PlannedNote>, String> {
+    let backup: Backup =
+        serde_json::from_slice(json).map_err(|e| format!("Invalid Standard Notes backup: {e}"))?;
+    let items: Vec<&Item> = backup.items.iter().filter(|item| !item.deleted).collect();
+    if items.iter().any(|item| item.content.is_string()) {
+        return Err(
+            "This backup is encrypted; export a decrypted backup from Standard Notes".to_string(),
+        );
+    }
+
+    let mut tags: HashMap<&str, Vec<String>> = HashMap::new();
+    for tag in items.iter().filter(|item| item.content_type == "Tag") {
+        let title = text(&tag.content, "title").trim();
+        if title.is_empty() {
+            continue;
+        }
+        let references = tag.content.get("references").and_then(Value::as_array);
+        for reference in references.into_iter().flatten() {
+            if let Some(uuid) = reference.get("uuid").and_then(Value::as_str) {
+                tags.entry(uuid).or_default().push(title.to_string());
+            }
+        }
+    }
+
+    Ok(items
+        .iter()
+        .filter(|item| item.content_type == "Note")
+        .map(|item| {
+            let title = text(&item.content, "title").trim();
+            let body = text(&item.content, "text");
+            let content = match title {
+                "" => body.to_string(),
+                title => format!("# {title}\n\n{body}"),
+            };
+            PlannedNote {
+                source_id: item.uuid.clone(),
+                title: (!title.is_empty()).then(|| title.to_string()),
+                content: content.trim_end().to_string(),
+                tags: tags.remove(item.uuid.as_str()).unwrap_or_default(),
+                pinned: flag(&item.content, "pinned"),
+                archived: flag(&item.content, "archived"),
+                trashed: flag(&item.content, "trashed"),
+                created: item.created_at.clone(),
+                updated: item.updated_at.clone(),
+            }
+        })
+        .collect())
+}
+
+/// Imports a decrypted Standard Notes backup (`.txt` or `.json`) with tags and pinned,
+/// archived and trashed state; trashed notes are imported into the trash. With `dry_run`,
+/// only reports what would be created.
+#[tauri::command]
+pub async fn import_standard_notes(
+    store: State<'_, Store>,
+    app: AppHandle,
+    path: String,
+    yak_id: Option<String>,
+    dry_run: Option<bool>,
+) -> Result<ImportSummary, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let json = std::fs::read(&path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+    let notes = parse(&json)?;
+    let dry_run = dry_run.unwrap_or(false);
+    import_planned(
+        &store,
+        notes,
+        yak_id,
+        "Standard Notes",
+        "standard-notes",
+        dry_run,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let backup = r#"{
+            "version": "004",
+            "items": [
+                {
+                    "uuid": "n1",
+                    "content_type": "Note",
+                    "created_at": "2022-01-01T00:00:00.000Z",
+                    "content": { "title": "Trip", "text": "Pack bags\n", "pinned": true }
+                },
+                {
+                    "uuid": "n2",
+                    "content_type": "Note",
+                    "content": {
+                        "title": "",
+                        "text": "Scratch",
+                        "trashed": true,
+                        "appData": { "org.standardnotes.sn": { "archived": true } }
+                    }
+                },
+                { "uuid": "n3", "content_type": "Note", "deleted": true, "content": null },
+                {
+                    "uuid": "t1",
+                    "content_type": "Tag",
+                    "content": { "title": "travel", "references": [{ "uuid": "n1" }] }
+                },
+                { "uuid": "k1", "content_type": "SN|ItemsKey", "content": {} }
+            ]
+        }"#;
+        let notes = parse(backup.as_bytes()).unwrap();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].content, "# Trip\n\nPack bags");
+        assert_eq!(notes[0].title.as_deref(), Some("Trip"));
+        assert_eq!(notes[0].tags, vec!["travel"]);
+        assert_eq!(
+            notes[0].created.as_deref(),
+            Some("2022-01-01T00:00:00.000Z")
+        );
+        assert!(notes[0].pinned && !notes[0].trashed);
+        assert_eq!(notes[1].content, "Scratch");
+        assert!(notes[1].archived && notes[1].trashed);
+
+        let encrypted =
+            r#"{ "items": [{ "uuid": "n1", "content_type": "Note", "content": "004:abc" }] }"#;
+        assert!(parse(encrypted.as_bytes())
+            .unwrap_err()
+            .contains("encrypted"));
+    }
+}
@@ -0,0 +1,312 @@
+use chrono::{DateTime, Duration, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::export::note_title;
+use crate::import::add_task;
+use crate::organize::note_moves;
+use crate::projection::Projection;
+use crate::settings::{load_setting, save_setting};
+use crate::shutdown::begin_write;
+use crate::time::from_id;
+use crate::windows::{emit_frame, emit_frames};
+use crate::{append_batch_to_store, append_frame, read_all_frames};
+
+const CONFIG_TOPIC: &str = "capture.inbox";
+const TRIAGE_TOPIC: &str = "inbox.triage";
+
+/// One yak that every capture (mail, the watched folder) files into, to be triaged from
+/// there, instead of each capture's own.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct InboxConfig {
+    pub enabled: bool,
+    /// Created the first time the inbox is enabled
+    pub yak_id: Option<String>,
+}
+
+/// What to do with an inbox note. Each action takes the note out of the inbox queue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum TriageAction {
+    /// File it in another yak
+    Move { yak_id: String },
+    /// Tag it and leave it in the inbox
+    Tag { tag: String },
+    /// Make a task of it, named after the note and linked to it
+    Task,
+    /// Delete it
+    Trash,
+}
+
+impl TriageAction {
+    fn name(&self) -> &'static str {
+        match self {
+            TriageAction::Move { .. } => "move",
+            TriageAction::Tag { .. } => "tag",
+            TriageAction::Task => "task",
+            TriageAction::Trash => "trash",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TriageResult {
+    /// The next note waiting, oldest first, so the next keypress has something to act on
+    pub next: Option<String>,
+    pub remaining: usize,
+}
+
+/// How fast the inbox is being worked through.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TriageStats {
+    pub pending: usize,
+    pub today: usize,
+    pub last_7_days: usize,
+    pub by_action: BTreeMap<String, usize>,
+    /// Notes triaged per day over the last 7
+    pub daily_average: f64,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+/// The yak a capture should file into: the inbox if it's enabled, else the capture's own.
+pub(crate) async fn capture_yak(store: &Store, own: &str) -> String {
+    let config: InboxConfig = load_setting(store, CONFIG_TOPIC).await;
+    match config.yak_id.filter(|_| config.enabled) {
+        Some(yak_id) => yak_id,
+        None => own.to_string(),
+    }
+}
+
+/// Notes in the inbox yak not yet triaged, oldest first.
+fn pending(projection: &Projection, frames: &[Frame], inbox: &str) -> Vec<String> {
+    let triaged: HashSet<String> = frames
+        .iter()
+        .filter(|frame| frame.topic == TRIAGE_TOPIC)
+        .filter_map(|frame| meta_str(frame, "note_id"))
+        .map(|note_id| projection.resolve(note_id))
+        .collect();
+    projection
+        .notes_by_yak
+        .get(inbox)
+        .into_iter()
+        .flatten()
+        .filter(|note_id| !triaged.contains(*note_id))
+        .cloned()
+        .collect()
+}
+
+fn stats(frames: &[Frame], pending: usize, now: DateTime<Utc>) -> TriageStats {
+    let today = now.with_timezone(&Local).date_naive();
+    let week_ago = now - Duration::days(7);
+    let mut stats = TriageStats {
+        pending,
+        ..Default::default()
+    };
+    for frame in frames.iter().filter(|frame| frame.topic == TRIAGE_TOPIC) {
+        let Some(time) = from_id(&frame.id.to_string()) else {
+            continue;
+        };
+        if time.with_timezone(&Local).date_naive() == today {
+            stats.today += 1;
+        }
+        if time > week_ago {
+            stats.last_7_days += 1;
+            let action = meta_str(frame, "action").unwrap_or("unknown");
+            *stats.by_action.entry(action.to_string()).or_default() += 1;
+        }
+    }
+    stats.daily_average = stats.last_7_days as f64 / 7.0;
+    stats
+}
+
+fn inbox_yak(config: &InboxConfig) -> Result<&str, String> {
+    config
+        .yak_id
+        .as_deref()
+        .filter(|_| config.enabled)
+        .ok_or_else(|| "The inbox isn't enabled".to_string())
+}
+
+/// Applies `action` to an inbox note and records it as triaged, returning every frame
+/// appended.
+async fn apply(
+    store: &Store,
+    projection: &Projection,
+    note_id: &str,
+    action: &TriageAction,
+) -> Result<Vec<Frame>, String> {
+    let note = projection
+        .notes
+        .get(note_id)
+        .ok_or_else(|| format!("Note not found: {note_id}"))?;
+    let yak_id = note.yak_id.as_str();
+    let mut frames = match action {
+        TriageAction::Move { yak_id: target } => {
+            let requests = note_moves(projection, &[note_id.to_string()], target)?;
+            append_batch_to_store(store, requests).await?
+        }
+        TriageAction::Tag { tag } => {
+            let tag = tag.trim().trim_start_matches('#');
+            if tag.is_empty() {
+                return Err("Tags can't be empty".to_string());
+            }
+            let meta = serde_json::json!({ "yak_id": yak_id, "note_id": note_id, "tag": tag });
+            vec![append_frame(store, "tag.add", None, Some(meta)).await?]
+        }
+        TriageAction::Task => {
+            let mut note = note.clone();
+            if let Some(hash) = &note.hash {
+                note.content = store
+                    .cas_read(hash)
+                    .await
+                    .ok()
+                    .and_then(|bytes| String::from_utf8(bytes).ok());
+            }
+            let text = note_title(&note);
+            vec![add_task(store, yak_id, Some(note_id), &text, false).await?]
+        }
+        TriageAction::Trash => {
+            let meta = serde_json::json!({ "yak_id": yak_id, "note_id": note_id });
+            vec![append_frame(store, "note.delete", None, Some(meta)).await?]
+        }
+    };
+    let meta = serde_json::json!({
+        "yak_id": yak_id,
+        "note_id": note_id,
+        "action": action.name(),
+    });
+    frames.push(append_frame(store, TRIAGE_TOPIC, None, Some(meta)).await?);
+    Ok(frames)
+}
+
+/// Turns the inbox on or off. While it's on, mail and the watched folder file into an
+/// "Inbox" yak, created the first time, rather than their own yaks.
+#[tauri::command]
+pub async fn configure_inbox(
+    app: AppHandle,
+    store: State<'_, Store>,
+    enabled: bool,
+) -> Result<InboxConfig, String> {
+    let mut config: InboxConfig = load_setting(&store, CONFIG_TOPIC).await;
+    if enabled && config.yak_id.is_none() {
+        let meta = serde_json::json!({ "name": "Inbox" });
+        let yak = append_frame(&store, "yak.create", None, Some(meta)).await?;
+        let _ = emit_frame(&app, &yak);
+        config.yak_id = Some(yak.id.to_string());
+    }
+    config.enabled = enabled;
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(config)
+}
+
+#[tauri::command]
+pub async fn get_inbox_count(store: State<'_, Store>) -> Result<usize, String> {
+    let config: InboxConfig = load_setting(&store, CONFIG_TOPIC).await;
+    let Some(inbox) = config.yak_id.filter(|_| config.enabled) else {
+        return Ok(0);
+    };
+    let frames = read_all_frames(&store).await;
+    let projection = Projection::from_frames(&frames);
+    Ok(pending(&projection, &frames, &inbox).len())
+}
+
+/// Moves, tags, turns into a task or trashes an inbox note, and hands back the next one
+/// waiting, for working through the inbox from the keyboard.
+#[tauri::command]
+pub async fn triage(
+    app: AppHandle,
+    store: State<'_, Store>,
+    frame_id: String,
+    action: TriageAction,
+) -> Result<TriageResult, String> {
+    let config: InboxConfig = load_setting(&store, CONFIG_TOPIC).await;
+    let inbox = inbox_yak(&config)?;
+    let _guard = begin_write(&app)?;
+    let frames = read_all_frames(&store).await;
+    let projection = Projection::from_frames(&frames);
+    let note_id = projection.resolve(&frame_id);
+    if !pending(&projection, &frames, inbox).contains(&note_id) {
+        return Err(format!("Not waiting in the inbox: {frame_id}"));
+    }
+    let appended = apply(&store, &projection, &note_id, &action).await?;
+    let _ = emit_frames(&app, &appended);
+
+    let frames = read_all_frames(&store).await;
+    let projection = Projection::from_frames(&frames);
+    let waiting = pending(&projection, &frames, inbox);
+    Ok(TriageResult {
+        next: waiting.first().cloned(),
+        remaining: waiting.len(),
+    })
+}
+
+/// Notes triaged today and over the last week, by action, with how many are waiting.
+#[tauri::command]
+pub async fn get_triage_stats(store: State<'_, Store>) -> Result<TriageStats, String> {
+    let config: InboxConfig = load_setting(&store, CONFIG_TOPIC).await;
+    let frames = read_all_frames(&store).await;
+    let waiting = match config.yak_id.filter(|_| config.enabled) {
+        Some(inbox) => pending(&Projection::from_frames(&frames), &frames, &inbox).len(),
+        None => 0,
+    };
+    Ok(stats(&frames, waiting, Utc::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{add_note, create_yak};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_triage() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let inbox = create_yak(&store, "Inbox").await.unwrap();
+        let work = create_yak(&store, "Work").await.unwrap();
+        let mut notes = Vec::new();
+        for content in ["# Call Sam", "# Invoice", "# Spam", "# Idea"] {
+            let note = add_note(&store, &inbox, content, json!({})).await.unwrap();
+            notes.push(note.id.to_string());
+        }
+        let actions = [
+            TriageAction::Task,
+            TriageAction::Move {
+                yak_id: work.clone(),
+            },
+            TriageAction::Trash,
+        ];
+        for (note_id, action) in notes.iter().zip(&actions) {
+            let frames = read_all_frames(&store).await;
+            let projection = Projection::from_frames(&frames);
+            apply(&store, &projection, note_id, action).await.unwrap();
+        }
+
+        let frames = read_all_frames(&store).await;
+        let projection = Projection::from_frames(&frames);
+        assert_eq!(
+            pending(&projection, &frames, &inbox),
+            vec![notes[3].clone()]
+        );
+        assert_eq!(projection.notes[&notes[1]].yak_id, work);
+        let task = projection.tasks.values().next().unwrap();
+        assert_eq!(task.note_id.as_deref(), Some(notes[0].as_str()));
+
+        let stats = stats(&frames, 1, Utc::now());
+        assert_eq!((stats.pending, stats.today, stats.last_7_days), (1, 3, 3));
+        assert_eq!(stats.by_action["trash"], 1);
+        assert_eq!(
+            serde_json::from_value::<TriageAction>(json!({ "action": "tag", "tag": "later" }))
+                .unwrap(),
+            TriageAction::Tag {
+                tag: "later".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+use xs::store::Store;
+
+use crate::read_model::{self, ReadModel};
+use crate::semantic::SemanticState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexKind {
+    /// The read model's full-text table
+    FullText,
+    /// Note embeddings
+    Semantic,
+    /// The SQLite copy of the projection, full-text table included
+    ReadModel,
+}
+
+const ALL: [IndexKind; 3] = [
+    IndexKind::FullText,
+    IndexKind::Semantic,
+    IndexKind::ReadModel,
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexProgress {
+    pub kind: IndexKind,
+    pub percent: u8,
+    /// Set on the last event of a rebuild, once the new index is in use
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+/// The rebuilds under way, so asking again doesn't start a second one.
+#[derive(Default)]
+pub struct IndexState {
+    running: Mutex<HashSet<IndexKind>>,
+}
+
+/// Emits "index-progress" as a rebuild moves on a whole percent.
+#[derive(Clone)]
+pub(crate) struct Progress {
+    app: AppHandle,
+    kind: IndexKind,
+    last: Arc<AtomicU8>,
+}
+
+impl Progress {
+    fn new(app: AppHandle, kind: IndexKind) -> Self {
+        Self {
+            app,
+            kind,
+            last: Arc::new(AtomicU8::new(u8::MAX)),
+        }
+    }
+
+    fn emit(&self, progress: IndexProgress) {
+        if let Err(e) = self.app.emit("index-progress", &progress) {
+            eprintln!("Failed to emit index progress: {e}");
+        }
+    }
+
+    /// Reports `done` of `total` steps. 100% is only reported once the new index is in use.
+    pub(crate) fn report(&self, done: usize, total: usize) {
+        let percent = match total {
+            0 => 0,
+            _ => (done * 100 / total).min(99) as u8,
+        };
+        if self.last.swap(percent, Ordering::Relaxed) != percent {
+            self.emit(IndexProgress {
+                kind: self.kind,
+                percent,
+                done: false,
+                error: None,
+            });
+        }
+    }
+
+    fn finish(&self, result: Result<(), String>) {
+        let error = result.err();
+        self.emit(IndexProgress {
+            kind: self.kind,
+            percent: if error.is_none() { 100 } else { 0 },
+            done: true,
+            error,
+        });
+    }
+}
+
+async fn rebuild(app: &AppHandle, store: &Store, progress: &Progress) -> Result<(), String> {
+    match progress.kind {
+        IndexKind::FullText => app.state::<ReadModel>().rebuild_full_text(progress).await,
+        IndexKind::Semantic => app.state::<SemanticState>().rebuild(store, progress).await,
+        IndexKind::ReadModel => read_model::rebuild_database(app, store, progress).await,
+    }
+}
+
+/// Rebuilds an index from the log in the background, or all of them when `kind` is `None`.
+/// Reads go to the old index until the new one replaces it; progress arrives as
+/// "index-progress" events. Returns the rebuilds started, leaving out any already running.
+#[tauri::command]
+pub async fn rebuild_indexes(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, IndexState>,
+    kind: Option<IndexKind>,
+) -> Result<Vec<IndexKind>, String> {
+    let kinds = kind.map_or(ALL.to_vec(), |kind| vec![kind]);
+    let mut started = Vec::new();
+    for kind in kinds {
+        if !state.running.lock().unwrap().insert(kind) {
+            continue;
+        }
+        started.push(kind);
+        let app = app.clone();
+        let store = store.inner().clone();
+        tokio::spawn(async move {
+            let progress = Progress::new(app.clone(), kind);
+            progress.report(0, 1);
+            let result = rebuild(&app, &store, &progress).await;
+            if let Err(e) = &result {
+                eprintln!("Failed to rebuild {kind:?} index: {e}");
+            }
+            app.state::<IndexState>()
+                .running
+                .lock()
+                .unwrap()
+                .remove(&kind);
+            progress.finish(result);
+        });
+    }
+    Ok(started)
+}
@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::projection::Projection;
+use crate::settings::{latest_setting, save_setting};
+use crate::time::{self, TimeRange};
+use crate::{append_frame, provenance, read_all_frames};
+
+const CONFIG_TOPIC: &str = "usage.insights";
+/// Something done in the app that leaves no other frame: a search run or a note opened.
+/// Only appended while insights are on, and never synced.
+const EVENT_TOPIC: &str = "usage.event";
+const MOST_OPENED: usize = 10;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InsightsConfig {
+    pub enabled: bool,
+}
+
+#[derive(Default)]
+pub struct InsightsState {
+    config: Mutex<InsightsConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OpenedNote {
+    /// The note's latest revision
+    pub note_id: String,
+    pub yak_id: Option<String>,
+    pub opens: usize,
+}
+
+/// How the app has been used, counted from the log on this device.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct UsageInsights {
+    /// New notes by where they came from: the capture source (`mail`, `browser`, ...) when
+    /// there is one, otherwise what appended them (`ui`, `cli`, `sync`, ...)
+    pub captures: BTreeMap<String, usize>,
+    pub searches: usize,
+    /// Searches by kind: `notes`, `semantic`
+    pub searches_by_kind: BTreeMap<String, usize>,
+    pub most_opened: Vec<OpenedNote>,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+/// Where a new note came from.
+fn capture_source(frame: &Frame) -> String {
+    if let Some(source) = meta_str(frame, "source") {
+        return source.to_string();
+    }
+    provenance::of(frame)
+        .stamp
+        .and_then(|stamp| serde_json::to_value(stamp.source).ok())
+        .and_then(|source| source.as_str().map(str::to_string))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Tallies the frames written in `range`. Opens of any revision count toward the note's
+/// latest one.
+fn insights(frames: &[Frame], range: &TimeRange) -> UsageInsights {
+    let mut usage = UsageInsights::default();
+    let mut opens: HashMap<String, usize> = HashMap::new();
+    for frame in frames {
+        let in_range = time::from_id(&frame.id.to_string()).map_or(true, |at| range.contains(at));
+        if !in_range {
+            continue;
+        }
+        match frame.topic.as_str() {
+            "note.create" => *usage.captures.entry(capture_source(frame)).or_default() += 1,
+            EVENT_TOPIC => match meta_str(frame, "event") {
+                Some("search") => {
+                    usage.searches += 1;
+                    let kind = meta_str(frame, "kind").unwrap_or("notes").to_string();
+                    *usage.searches_by_kind.entry(kind).or_default() += 1;
+                }
+                Some("open") => {
+                    if let Some(note_id) = meta_str(frame, "note_id") {
+                        *opens.entry(note_id.to_string()).or_default() += 1;
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    let projection = Projection::from_frames(frames);
+    let mut by_note: HashMap<String, usize> = HashMap::new();
+    for (note_id, count) in opens {
+        *by_note.entry(projection.resolve(&note_id)).or_default() += count;
+    }
+    let mut most_opened: Vec<OpenedNote> = by_note
+        .into_iter()
+        .map(|(note_id, opens)| OpenedNote {
+            yak_id: projection
+                .notes
+                .get(&note_id)
+                .map(|note| note.yak_id.clone()),
+            note_id,
+            opens,
+        })
+        .collect();
+    most_opened.sort_by(|a, b| b.opens.cmp(&a.opens).then(a.note_id.cmp(&b.note_id)));
+    most_opened.truncate(MOST_OPENED);
+    usage.most_opened = most_opened;
+    usage
+}
+
+fn enabled(app: &AppHandle) -> bool {
+    app.try_state::<InsightsState>()
+        .is_some_and(|state| state.config.lock().unwrap().enabled)
+}
+
+/// Notes a search run, if insights are on. What was searched for isn't kept.
+pub(crate) async fn record_search(app: &AppHandle, kind: &str) {
+    record(app, json!({ "event": "search", "kind": kind })).await;
+}
+
+async fn record(app: &AppHandle, meta: Value) {
+    if !enabled(app) {
+        return;
+    }
+    let Some(store) = app.try_state::<Store>() else {
+        return;
+    };
+    if let Err(e) = append_frame(&store, EVENT_TOPIC, None, Some(meta)).await {
+        eprintln!("Failed to record usage: {e}");
+    }
+}
+
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let config: InsightsConfig = latest_setting(frames, CONFIG_TOPIC);
+    *app.state::<InsightsState>().config.lock().unwrap() = config;
+}
+
+#[tauri::command]
+pub async fn get_insights_config(
+    state: State<'_, InsightsState>,
+) -> Result<InsightsConfig, String> {
+    Ok(state.config.lock().unwrap().clone())
+}
+
+/// Turns usage insights on or off. Off, nothing more is recorded; what was is kept.
+#[tauri::command]
+pub async fn configure_insights(
+    store: State<'_, Store>,
+    state: State<'_, InsightsState>,
+    config: InsightsConfig,
+) -> Result<(), String> {
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    *state.config.lock().unwrap() = config;
+    Ok(())
+}
+
+/// Notes a note being opened, if insights are on.
+#[tauri::command]
+pub async fn record_note_open(app: AppHandle, note_id: String) -> Result<(), String> {
+    record(&app, json!({ "event": "open", "note_id": note_id })).await;
+    Ok(())
+}
+
+/// Captures per source, searches run and the most-opened notes over `range`, or all time.
+/// `None` while insights are off.
+#[tauri::command]
+pub async fn get_usage_insights(
+    store: State<'_, Store>,
+    state: State<'_, InsightsState>,
+    range: Option<TimeRange>,
+) -> Result<Option<UsageInsights>, String> {
+    if !state.config.lock().unwrap().enabled {
+        return Ok(None);
+    }
+    let frames = read_all_frames(&store).await;
+    Ok(Some(insights(&frames, &range.unwrap_or_default())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_insights() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak = append_frame(&store, "yak.create", None, Some(json!({ "name": "Inbox" })))
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let mut notes = Vec::new();
+        for source in [Some("mail"), Some("mail"), None] {
+            let mut meta = json!({ "yak_id": yak_id });
+            if let Some(source) = source {
+                meta["source"] = json!(source);
+            }
+            let note = append_frame(&store, "note.create", Some(b"hi"), Some(meta))
+                .await
+                .unwrap();
+            notes.push(note.id.to_string());
+        }
+        let edit = json!({ "yak_id": yak_id, "note_id": notes[0] });
+        let edited = append_frame(&store, "note.edit", Some(b"hello"), Some(edit))
+            .await
+            .unwrap();
+        let events = [
+            json!({ "event": "search", "kind": "notes" }),
+            json!({ "event": "search", "kind": "semantic" }),
+            json!({ "event": "open", "note_id": notes[0] }),
+            json!({ "event": "open", "note_id": edited.id.to_string() }),
+            json!({ "event": "open", "note_id": notes[1] }),
+        ];
+        for meta in events {
+            append_frame(&store, EVENT_TOPIC, None, Some(meta))
+                .await
+                .unwrap();
+        }
+
+        let usage = insights(&read_all_frames(&store).await, &TimeRange::default());
+        assert_eq!(usage.captures["mail"], 2);
+        assert_eq!(usage.captures.values().sum::<usize>(), 3);
+        assert_eq!(usage.searches, 2);
+        assert_eq!(usage.searches_by_kind["semantic"], 1);
+        assert_eq!(
+            usage.most_opened[0],
+            OpenedNote {
+                note_id: edited.id.to_string(),
+                yak_id: Some(yak_id.clone()),
+                opens: 2,
+            }
+        );
+        assert_eq!(usage.most_opened[1].opens, 1);
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use xs::store::{Frame, Store};
+
+use crate::locks::LockState;
+use crate::{read_all_frames, topics};
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InspectOptions {
+    /// Only return frames after this frame id (exclusive)
+    pub after: Option<String>,
+    /// Only return frames whose topic starts with this prefix
+    pub topic: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FramePage {
+    pub frames: Vec<Frame>,
+    /// Pass as `after` to fetch the next page; `None` when there are no more frames
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FrameDetail {
+    pub frame: Frame,
+    /// CAS content decoded as UTF-8, if the frame has content and it is valid text
+    pub content: Option<String>,
+    pub content_size: Option<usize>,
+}
+
+pub(crate) fn paginate(frames: Vec<Frame>, options: &InspectOptions) -> Result<FramePage, String> {
+    let after = options
+        .after
+        .as_deref()
+        .map(|id| {
+            id.parse::<scru128::Scru128Id>()
+                .map_err(|e| format!("Invalid frame id: {e}"))
+        })
+        .transpose()?;
+    let limit = options.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+
+    let mut matching = frames
+        .into_iter()
+        .filter(|frame| after.map_or(true, |after| frame.id > after))
+        .filter(|frame| {
+            options
+                .topic
+                .as_deref()
+                .map_or(true, |prefix| frame.topic.starts_with(prefix))
+        });
+
+    let page: Vec<Frame> = matching.by_ref().take(limit).collect();
+    let next = match matching.next() {
+        Some(_) => page.last().map(|frame| frame.id.to_string()),
+        None => None,
+    };
+
+    Ok(FramePage { frames: page, next })
+}
+
+#[tauri::command]
+pub async fn inspect_frames(
+    store: State<'_, Store>,
+    options: Option<InspectOptions>,
+) -> Result<FramePage, String> {
+    let frames = read_all_frames(&store).await;
+    paginate(frames, &options.unwrap_or_default())
+}
+
+/// Frames for user-facing views: like `inspect_frames`, but system frames (settings, sync
+/// and automation bookkeeping) are left out unless `include_system` is set.
+#[tauri::command]
+pub async fn read_frames(
+    store: State<'_, Store>,
+    options: Option<InspectOptions>,
+    include_system: Option<bool>,
+) -> Result<FramePage, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let mut frames = read_all_frames(&store).await;
+    if !include_system.unwrap_or(false) {
+        frames.retain(|frame| !topics::is_system(&frame.topic));
+    }
+    paginate(frames, &options.unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn inspect_frame(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    id: String,
+) -> Result<FrameDetail, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let id = id
+        .parse::<scru128::Scru128Id>()
+        .map_err(|e| format!("Invalid frame id: {e}"))?;
+
+    let frame = store
+        .get(&id)
+        .ok_or_else(|| format!("Frame not found: {id}"))?;
+
+    let (content, content_size) = match &frame.hash {
+        Some(hash) => {
+            let bytes = store
+                .cas_read(hash)
+                .await
+                .map_err(|e| format!("Failed to read content: {e}"))?;
+            let size = bytes.len();
+            // Content of a locked yak is left out unless it's unlocked
+            let content = String::from_utf8(bytes)
+                .ok()
+                .and_then(|content| locks.open(&hash.to_string(), &content).ok());
+            (content, Some(size))
+        }
+        None => (None, None),
+    };
+
+    Ok(FrameDetail {
+        frame,
+        content,
+        content_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+
+    #[tokio::test]
+    async fn test_paginate() {
+        let (_dir, store) = testing::store();
+        for topic in ["yak.create", "note.create", "note.edit", "note.create"] {
+            append(&store, topic, None);
+        }
+        let frames = read_all_frames(&store).await;
+
+        let options = InspectOptions {
+            topic: Some("note.".to_string()),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = paginate(frames.clone(), &options).unwrap();
+        assert_eq!(page.frames.len(), 2);
+        assert_eq!(page.next, Some(frames[2].id.to_string()));
+
+        let options = InspectOptions {
+            after: page.next,
+            ..options
+        };
+        let page = paginate(frames.clone(), &options).unwrap();
+        assert_eq!(page.frames.len(), 1);
+        assert_eq!(page.frames[0].id, frames[3].id);
+        assert_eq!(page.next, None);
+    }
+}
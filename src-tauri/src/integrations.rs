@@ -0,0 +1,421 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::export::{content_title, note_title};
+use crate::projection::Projection;
+use crate::settings::{latest_setting, save_setting};
+use crate::{append_frame, health, locks, read_all_frames};
+
+const CONFIG_TOPIC: &str = "integrations.chat";
+const DELIVERY_TOPIC: &str = "integration.delivery";
+const MAX_ATTEMPTS: u32 = 3;
+/// Shared notes are cut to this many characters in the message.
+const EXCERPT_CHARS: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatService {
+    Slack,
+    Discord,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn default_share_tag() -> String {
+    "share".to_string()
+}
+
+/// Where a yak's news goes: a Slack or Discord channel's incoming webhook.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatIntegration {
+    pub service: ChatService,
+    pub webhook_url: String,
+    /// Post tasks when they're checked off
+    #[serde(default = "enabled")]
+    pub tasks: bool,
+    /// Post notes when they're given this tag; empty to post none
+    #[serde(default = "default_share_tag")]
+    pub share_tag: String,
+}
+
+/// Chat integrations, by yak id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatConfig {
+    #[serde(default)]
+    pub yaks: HashMap<String, ChatIntegration>,
+}
+
+#[derive(Default)]
+pub struct ChatState {
+    config: Mutex<ChatConfig>,
+}
+
+/// A post attempt, recorded by an `integration.delivery` frame.
+#[derive(Debug, Clone, Serialize)]
+pub struct Delivery {
+    pub id: String,
+    pub yak_id: String,
+    /// The frame that led to the post
+    pub frame_id: String,
+    pub ok: bool,
+    pub attempts: u32,
+    pub error: Option<String>,
+}
+
+/// What happened that a yak's channel hears about.
+#[derive(Debug, Clone, PartialEq)]
+enum Trigger {
+    TaskDone { yak_id: String, task_id: String },
+    NoteShared { yak_id: String, note_id: String },
+}
+
+impl Trigger {
+    fn yak_id(&self) -> &str {
+        match self {
+            Trigger::TaskDone { yak_id, .. } | Trigger::NoteShared { yak_id, .. } => yak_id,
+        }
+    }
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+/// What `frame` triggers, judged against `projection` as it was before the frame: a task
+/// going from open to done, or a note getting its yak's share tag.
+fn trigger(projection: &Projection, frame: &Frame, config: &ChatConfig) -> Option<Trigger> {
+    match frame.topic.as_str() {
+        "task.update" => {
+            let done = frame.meta.as_ref()?.get("done")?.as_bool()?;
+            let task = projection.tasks.get(meta_str(frame, "task_id")?)?;
+            let integration = config.yaks.get(&task.yak_id)?;
+            (done && !task.done && integration.tasks).then(|| Trigger::TaskDone {
+                yak_id: task.yak_id.clone(),
+                task_id: task.id.clone(),
+            })
+        }
+        "tag.add" => {
+            let tag = normalize_tag(meta_str(frame, "tag")?);
+            let note = projection
+                .notes
+                .get(&projection.resolve(meta_str(frame, "note_id")?))?;
+            let share_tag = normalize_tag(&config.yaks.get(&note.yak_id)?.share_tag);
+            let already = note.tags.iter().any(|tag| normalize_tag(tag) == share_tag);
+            (!share_tag.is_empty() && tag == share_tag && !already).then(|| Trigger::NoteShared {
+                yak_id: note.yak_id.clone(),
+                note_id: note.id.clone(),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn excerpt(content: &str) -> String {
+    let content = content.trim();
+    match content.char_indices().nth(EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}…", content[..end].trim_end()),
+        None => content.to_string(),
+    }
+}
+
+/// The webhook body for a message, in the service's own markdown flavour.
+fn message(service: ChatService, title: &str, body: &str) -> serde_json::Value {
+    let title = match service {
+        ChatService::Slack => format!("*{title}*"),
+        ChatService::Discord => format!("**{title}**"),
+    };
+    let text = if body.is_empty() {
+        title
+    } else {
+        format!("{title}\n{body}")
+    };
+    match service {
+        ChatService::Slack => serde_json::json!({ "text": text }),
+        ChatService::Discord => serde_json::json!({ "content": text }),
+    }
+}
+
+/// The readable text behind a hash, or `None` for missing or sealed content.
+async fn read_text(store: &Store, hash: Option<&ssri::Integrity>) -> Option<String> {
+    let bytes = store.cas_read(hash?).await.ok()?;
+    let text = String::from_utf8(bytes).ok()?;
+    (!locks::is_sealed(&text)).then_some(text)
+}
+
+/// The message to post about a trigger, from `projection` as it is after the triggering
+/// frame. Sealed content isn't posted.
+async fn compose(
+    store: &Store,
+    projection: &Projection,
+    trigger: &Trigger,
+    service: ChatService,
+) -> Option<serde_json::Value> {
+    let yak_name = |yak_id: &str| {
+        projection
+            .yaks
+            .get(yak_id)
+            .and_then(|yak| yak.name.clone())
+            .unwrap_or_else(|| "a yak".to_string())
+    };
+    match trigger {
+        Trigger::TaskDone { task_id, .. } => {
+            let task = projection.tasks.get(task_id)?;
+            let text = read_text(store, task.hash.as_ref()).await?;
+            let title = format!("✅ Done in {}", yak_name(&task.yak_id));
+            Some(message(service, &title, &content_title(&text)))
+        }
+        Trigger::NoteShared { note_id, .. } => {
+            let mut note = projection.notes.get(note_id)?.clone();
+            let text = read_text(store, note.hash.as_ref()).await?;
+            note.content = Some(text.clone());
+            let title = format!("📝 {} (from {})", note_title(&note), yak_name(&note.yak_id));
+            Some(message(service, &title, &excerpt(&text)))
+        }
+    }
+}
+
+/// Posts `message`, retrying with backoff, and records how it went as a delivery frame.
+async fn deliver(
+    app: AppHandle,
+    store: Store,
+    integration: ChatIntegration,
+    yak_id: String,
+    frame_id: String,
+    message: serde_json::Value,
+) {
+    let client = crate::web::client();
+    let mut error = None;
+    let mut attempts = 0;
+    while attempts < MAX_ATTEMPTS {
+        attempts += 1;
+        let result = client
+            .post(&integration.webhook_url)
+            .json(&message)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match result {
+            Ok(_) => {
+                error = None;
+                break;
+            }
+            Err(e) => error = Some(e.to_string()),
+        }
+        if attempts < MAX_ATTEMPTS {
+            tokio::time::sleep(Duration::from_secs(2u64.pow(attempts))).await;
+        }
+    }
+    match &error {
+        Some(e) => health::error(&app, "integrations", e),
+        None => health::ok(&app, "integrations"),
+    }
+    let meta = serde_json::json!({
+        "yak_id": yak_id,
+        "frame_id": frame_id,
+        "service": integration.service,
+        "ok": error.is_none(),
+        "attempts": attempts,
+        "error": error,
+    });
+    if let Err(e) = append_frame(&store, DELIVERY_TOPIC, None, Some(meta)).await {
+        eprintln!("Failed to record delivery: {e}");
+    }
+}
+
+/// Posts finished tasks and shared notes to the chat channels their yaks are set up with.
+/// Changes copied in by sync are left to the device they were made on.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(options).await;
+    let mut projection = Projection::default();
+    let mut live = false;
+    while let Some(frame) = rx.recv().await {
+        if frame.topic == "xs.threshold" {
+            live = true;
+            continue;
+        }
+        let config = app.state::<ChatState>().config.lock().unwrap().clone();
+        let fired = (live && crate::sync::origin(&frame).is_none())
+            .then(|| trigger(&projection, &frame, &config))
+            .flatten();
+        projection.apply(&frame);
+        let Some(fired) = fired else {
+            continue;
+        };
+        let Some(integration) = config.yaks.get(fired.yak_id()).cloned() else {
+            continue;
+        };
+        let Some(message) = compose(&store, &projection, &fired, integration.service).await else {
+            continue;
+        };
+        tokio::spawn(deliver(
+            app.clone(),
+            store.clone(),
+            integration,
+            fired.yak_id().to_string(),
+            frame.id.to_string(),
+            message,
+        ));
+    }
+}
+
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let config: ChatConfig = latest_setting(frames, CONFIG_TOPIC);
+    *app.state::<ChatState>().config.lock().unwrap() = config;
+}
+
+/// Sets up (or with `None`, removes) the Slack or Discord channel a yak posts its finished
+/// tasks and shared notes to.
+#[tauri::command]
+pub async fn configure_chat_integration(
+    store: State<'_, Store>,
+    state: State<'_, ChatState>,
+    yak_id: String,
+    integration: Option<ChatIntegration>,
+) -> Result<ChatConfig, String> {
+    let mut config = state.config.lock().unwrap().clone();
+    match integration {
+        Some(integration) => {
+            let url = reqwest::Url::parse(&integration.webhook_url)
+                .map_err(|e| format!("Invalid webhook url: {e}"))?;
+            if url.scheme() != "https" {
+                return Err("Chat webhooks need an https url".to_string());
+            }
+            config.yaks.insert(yak_id, integration);
+        }
+        None => {
+            config.yaks.remove(&yak_id);
+        }
+    }
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    *state.config.lock().unwrap() = config.clone();
+    Ok(config)
+}
+
+#[tauri::command]
+pub async fn get_chat_integrations(state: State<'_, ChatState>) -> Result<ChatConfig, String> {
+    Ok(state.config.lock().unwrap().clone())
+}
+
+/// A yak's recent chat posts, newest first, with whether they went through.
+#[tauri::command]
+pub async fn list_chat_deliveries(
+    store: State<'_, Store>,
+    yak_id: String,
+    limit: Option<usize>,
+) -> Result<Vec<Delivery>, String> {
+    let deliveries = read_all_frames(&store)
+        .await
+        .iter()
+        .rev()
+        .filter(|frame| frame.topic == DELIVERY_TOPIC)
+        .filter(|frame| meta_str(frame, "yak_id") == Some(yak_id.as_str()))
+        .filter_map(|frame| {
+            let meta = frame.meta.as_ref()?;
+            Some(Delivery {
+                id: frame.id.to_string(),
+                yak_id: yak_id.clone(),
+                frame_id: meta.get("frame_id")?.as_str()?.to_string(),
+                ok: meta.get("ok")?.as_bool()?,
+                attempts: meta.get("attempts")?.as_u64()? as u32,
+                error: meta.get("error").and_then(|e| e.as_str()).map(String::from),
+            })
+        })
+        .take(limit.unwrap_or(50))
+        .collect();
+    Ok(deliveries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{add_note, add_tag, add_task, create_yak};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_trigger() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Launch").await.unwrap();
+        let other = create_yak(&store, "Home").await.unwrap();
+        let task = add_task(&store, &yak_id, None, "Ship it", false)
+            .await
+            .unwrap();
+        let chores = add_task(&store, &other, None, "Water plants", false)
+            .await
+            .unwrap();
+        let note = add_note(&store, &yak_id, "# Release notes\nAll new", json!({}))
+            .await
+            .unwrap();
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+
+        let mut config = ChatConfig::default();
+        config.yaks.insert(
+            yak_id.clone(),
+            ChatIntegration {
+                service: ChatService::Slack,
+                webhook_url: "https://hooks.example.com/1".to_string(),
+                tasks: true,
+                share_tag: "#Share".to_string(),
+            },
+        );
+        let done = |task_id: String| {
+            let meta = json!({ "task_id": task_id, "done": true });
+            append_frame(&store, "task.update", None, Some(meta))
+        };
+        let frame = done(task.id.to_string()).await.unwrap();
+        assert_eq!(
+            trigger(&projection, &frame, &config),
+            Some(Trigger::TaskDone {
+                yak_id: yak_id.clone(),
+                task_id: task.id.to_string()
+            })
+        );
+        let frame = done(chores.id.to_string()).await.unwrap();
+        assert_eq!(trigger(&projection, &frame, &config), None);
+
+        let frame = add_tag(&store, &yak_id, &note.id.to_string(), "share")
+            .await
+            .unwrap();
+        assert_eq!(
+            trigger(&projection, &frame, &config),
+            Some(Trigger::NoteShared {
+                yak_id: yak_id.clone(),
+                note_id: note.id.to_string()
+            })
+        );
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let trigger = Trigger::NoteShared {
+            yak_id,
+            note_id: note.id.to_string(),
+        };
+        let message = compose(&store, &projection, &trigger, ChatService::Discord)
+            .await
+            .unwrap();
+        assert_eq!(
+            message,
+            json!({ "content": "**📝 Release notes (from Launch)**\n# Release notes\nAll new" })
+        );
+    }
+
+    #[test]
+    fn test_excerpt() {
+        let long = "word ".repeat(200);
+        let cut = excerpt(&long);
+        assert!(cut.ends_with("word…"));
+        assert_eq!(cut.chars().count(), EXCERPT_CHARS);
+        assert_eq!(excerpt("  short  "), "short");
+    }
+}
@@ -0,0 +1,152 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+use xs::store::Store;
+
+use crate::append_frame;
+
+pub(crate) const CORRUPTION_TOPIC: &str = "store.corruption";
+/// How the CAS words its own failed check, with the hashes on the lines after.
+const CAS_CHECK_FAILED: &str = "Integrity check failed";
+
+/// Content whose bytes don't hash to the integrity its frames refer to it by.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IntegrityMismatch {
+    pub hash: String,
+    /// What the bytes read hash to, when known
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for IntegrityMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Content is corrupted: it doesn't match {}", self.hash)
+    }
+}
+
+/// Why content couldn't be read; commands return it as `{"integrity_mismatch": {...}}` or
+/// `{"failed": "..."}` so the frontend can tell corruption apart.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ReadError {
+    IntegrityMismatch(IntegrityMismatch),
+    Failed(String),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::IntegrityMismatch(mismatch) => write!(f, "{mismatch}"),
+            ReadError::Failed(e) => f.write_str(e),
+        }
+    }
+}
+
+impl From<String> for ReadError {
+    fn from(e: String) -> Self {
+        ReadError::Failed(e)
+    }
+}
+
+impl From<ReadError> for String {
+    fn from(e: ReadError) -> Self {
+        e.to_string()
+    }
+}
+
+fn verify(hash: &ssri::Integrity, bytes: &[u8]) -> Result<(), IntegrityMismatch> {
+    hash.check(bytes)
+        .map(|_| ())
+        .map_err(|_| IntegrityMismatch {
+            hash: hash.to_string(),
+            actual: Some(ssri::Integrity::from(bytes).to_string()),
+        })
+}
+
+/// A CAS read error that is the CAS's own integrity check failing, as a mismatch.
+fn mismatch_from(hash: &ssri::Integrity, error: &str) -> Option<IntegrityMismatch> {
+    if !error.contains(CAS_CHECK_FAILED) {
+        return None;
+    }
+    let actual = error
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Actual:"))
+        .map(|actual| actual.trim().to_string());
+    Some(IntegrityMismatch {
+        hash: hash.to_string(),
+        actual,
+    })
+}
+
+/// Records a mismatch as a `store.corruption` frame, once per hash per run, so it turns
+/// up in the log rather than only in whichever view tripped over it.
+async fn report(store: &Store, mismatch: &IntegrityMismatch) {
+    static REPORTED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    let reported = REPORTED.get_or_init(Default::default);
+    if !reported.lock().unwrap().insert(mismatch.hash.clone()) {
+        return;
+    }
+    eprintln!("{mismatch}");
+    let meta = serde_json::json!({ "hash": mismatch.hash, "actual": mismatch.actual });
+    if let Err(e) = append_frame(store, CORRUPTION_TOPIC, None, Some(meta)).await {
+        eprintln!("Failed to record corrupted content: {e}");
+    }
+}
+
+/// Reads content from the CAS, checking the bytes against `hash` before handing them back.
+/// Damaged content is an `IntegrityMismatch`, and recorded as a `store.corruption` frame.
+pub(crate) async fn read(store: &Store, hash: &ssri::Integrity) -> Result<Vec<u8>, ReadError> {
+    let result = match store.cas_read(hash).await {
+        Ok(bytes) => verify(hash, &bytes).map(|()| bytes),
+        Err(e) => match mismatch_from(hash, &e.to_string()) {
+            Some(mismatch) => Err(mismatch),
+            None => return Err(ReadError::Failed(format!("Failed to read content: {e}"))),
+        },
+    };
+    match result {
+        Ok(bytes) => Ok(bytes),
+        Err(mismatch) => {
+            report(store, &mismatch).await;
+            Err(ReadError::IntegrityMismatch(mismatch))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let hash = ssri::Integrity::from(b"intact");
+        assert!(verify(&hash, b"intact").is_ok());
+        let mismatch = verify(&hash, b"intakt").unwrap_err();
+        assert_eq!(mismatch.hash, hash.to_string());
+        assert_eq!(
+            mismatch.actual,
+            Some(ssri::Integrity::from(b"intakt").to_string())
+        );
+
+        let error = format!("{CAS_CHECK_FAILED}.\n\tWanted: {hash}\n\tActual: sha256-abc");
+        let mismatch = mismatch_from(&hash, &error).unwrap();
+        assert_eq!(mismatch.actual.as_deref(), Some("sha256-abc"));
+        assert!(mismatch_from(&hash, "No such file or directory").is_none());
+
+        // Commands hand a mismatch to the frontend as its own kind of error
+        let error = serde_json::to_value(ReadError::IntegrityMismatch(mismatch)).unwrap();
+        assert_eq!(error["integrity_mismatch"]["actual"], "sha256-abc");
+    }
+
+    #[tokio::test]
+    async fn test_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let hash = store.cas_insert(b"intact").await.unwrap();
+        assert_eq!(read(&store, &hash).await.unwrap(), b"intact");
+        let missing = ssri::Integrity::from(b"lost");
+        assert!(matches!(
+            read(&store, &missing).await,
+            Err(ReadError::Failed(_))
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+use serde_json::json;
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::projection::{Label, Projection};
+use crate::shutdown::begin_write;
+use crate::windows::emit_frame;
+use crate::{append_frame, read_all_frames};
+
+const TOPIC: &str = "label.set";
+/// Colors the frontend has swatches for; anything else is given as `#rrggbb`.
+const COLORS: &[&str] = &[
+    "red", "orange", "yellow", "green", "teal", "blue", "purple", "pink", "gray",
+];
+/// Icons are an emoji or a short icon name.
+const MAX_ICON_CHARS: usize = 32;
+
+fn validate_color(color: &str) -> Result<(), String> {
+    let hex = color
+        .strip_prefix('#')
+        .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+    if !hex && !COLORS.contains(&color) {
+        return Err(format!(
+            "Invalid color {color:?}: use #rrggbb or one of {}",
+            COLORS.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+fn validate_icon(icon: &str) -> Result<(), String> {
+    if icon.chars().count() > MAX_ICON_CHARS || icon.chars().any(char::is_whitespace) {
+        return Err(format!("Invalid icon {icon:?}"));
+    }
+    Ok(())
+}
+
+/// The label with blank parts left out, checked.
+fn label(color: Option<String>, icon: Option<String>) -> Result<Label, String> {
+    let color = color
+        .map(|color| color.trim().to_lowercase())
+        .filter(|color| !color.is_empty());
+    let icon = icon
+        .map(|icon| icon.trim().to_string())
+        .filter(|icon| !icon.is_empty());
+    if let Some(color) = &color {
+        validate_color(color)?;
+    }
+    if let Some(icon) = &icon {
+        validate_icon(icon)?;
+    }
+    Ok(Label { color, icon })
+}
+
+/// Gives a yak or note a color and icon, shown in lists and the snapshot and matched by
+/// `label:` in search. Without either, the label comes off. A note's label stays through
+/// later edits.
+#[tauri::command]
+pub async fn set_label(
+    app: AppHandle,
+    store: State<'_, Store>,
+    target_id: String,
+    color: Option<String>,
+    icon: Option<String>,
+) -> Result<Frame, String> {
+    let label = label(color, icon)?;
+    let _guard = begin_write(&app)?;
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let (target_id, yak_id) = match projection.yaks.get(&target_id) {
+        Some(yak) => (yak.id.clone(), yak.id.clone()),
+        None => {
+            let note_id = projection.resolve(&target_id);
+            let note = projection
+                .notes
+                .get(&note_id)
+                .ok_or_else(|| format!("No yak or note {target_id}"))?;
+            (note_id, note.yak_id.clone())
+        }
+    };
+    let meta = json!({
+        "target_id": target_id,
+        "yak_id": yak_id,
+        "color": label.color,
+        "icon": label.icon,
+    });
+    let frame = append_frame(&store, TOPIC, None, Some(meta)).await?;
+    let _ = emit_frame(&app, &frame);
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{add_note, create_yak};
+    use tempfile::tempdir;
+
+    fn set(target: &str, color: Option<&str>, icon: Option<&str>) -> serde_json::Value {
+        json!({ "target_id": target, "color": color, "icon": icon })
+    }
+
+    #[tokio::test]
+    async fn test_labels() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Garden").await.unwrap();
+        let note = add_note(&store, &yak_id, "# Tomatoes", json!({}))
+            .await
+            .unwrap();
+        let note_id = note.id.to_string();
+        for meta in [
+            set(&yak_id, Some("green"), Some("🌱")),
+            set(&note_id, Some("#ff0000"), None),
+        ] {
+            append_frame(&store, TOPIC, None, Some(meta)).await.unwrap();
+        }
+        let edit = json!({ "yak_id": yak_id, "note_id": note_id });
+        let edit = append_frame(
+            &store,
+            "note.edit",
+            Some("# Cherry tomatoes".as_bytes()),
+            Some(edit),
+        )
+        .await
+        .unwrap();
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let yak_label = projection.yaks[&yak_id].label.clone().unwrap();
+        assert_eq!(yak_label.icon.as_deref(), Some("🌱"));
+        // The note's label followed the edit
+        let note_label = projection.notes[&edit.id.to_string()].label.clone();
+        assert_eq!(note_label.unwrap().color.as_deref(), Some("#ff0000"));
+
+        append_frame(&store, TOPIC, None, Some(set(&yak_id, None, None)))
+            .await
+            .unwrap();
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        assert_eq!(projection.yaks[&yak_id].label, None);
+
+        assert_eq!(
+            label(Some(" Blue ".to_string()), Some(String::new())).unwrap(),
+            Label {
+                color: Some("blue".to_string()),
+                icon: None,
+            }
+        );
+        assert!(label(Some("#12345".to_string()), None).is_err());
+        assert!(label(Some("mauve".to_string()), None).is_err());
+        assert!(label(None, Some("two words".to_string())).is_err());
+    }
+}
@@ -1,75 +1,457 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tauri::{AppHandle, Emitter, Manager, State};
+use tauri::{AppHandle, Manager, State};
 use xs::store::{FollowOption, Frame, ReadOptions, Store, ZERO_CONTEXT};
 
-#[derive(Debug, Serialize, Deserialize)]
+mod actions;
+mod ai;
+mod app_lock;
+mod archives;
+mod audio;
+mod autocomplete;
+mod autostart;
+#[cfg(feature = "bench")]
+pub mod bench;
+mod blobs;
+mod board;
+mod bulk;
+mod cache;
+mod checkboxes;
+mod checkpoints;
+pub mod cli;
+mod clipboard;
+mod clips;
+mod cold_archive;
+mod compaction;
+mod conflicts;
+mod crypto;
+mod demo;
+mod diagnostics;
+mod diff;
+mod digests;
+mod disk;
+mod drafts;
+mod duplicates;
+mod editor;
+mod emission;
+mod enrich;
+mod export;
+mod external;
+mod extract;
+mod feeds;
+mod focus;
+mod folder;
+mod frontmatter;
+mod geo;
+mod handlers;
+mod health;
+mod history;
+mod html;
+mod import;
+mod inbox;
+mod indexes;
+mod insights;
+mod inspect;
+mod integrations;
+mod integrity;
+mod labels;
+mod limits;
+mod links;
+mod locale;
+mod location;
+mod locks;
+mod mail;
+pub mod mcp;
+mod meetings;
+mod mentions;
+mod merge;
+mod metrics;
+mod migrations;
+mod mime;
+mod open_with;
+mod organize;
+mod outbox;
+mod outline;
+mod permissions;
+mod plugins;
+mod presence;
+mod presentation;
+mod profiles;
+mod projection;
+mod projects;
+mod properties;
+mod provenance;
+mod publish;
+mod rates;
+mod read_model;
+mod recovery;
+mod recurrence;
+mod retention;
+mod reviews;
+mod rules;
+mod schema;
+mod search;
+mod secrets;
+mod semantic;
+mod settings;
+mod setup;
+mod share;
+mod share_links;
+mod shortcuts;
+mod shutdown;
+#[cfg(test)]
+mod simulation;
+mod snapshot;
+mod snippets;
+mod speech;
+mod stats;
+mod store_lock;
+mod subscriptions;
+mod sync;
+mod task_sync;
+mod templates;
+#[cfg(test)]
+mod testing;
+mod time;
+mod titles;
+mod topics;
+mod undo;
+mod unread;
+mod video;
+mod web;
+mod webhooks;
+mod when;
+mod windows;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppendRequest {
     pub topic: String,
     pub content: String,
     pub meta: Option<HashMap<String, serde_json::Value>>,
 }
 
-#[tauri::command]
-async fn append_event(
-    store: State<'_, Store>,
-    app: AppHandle,
-    request: AppendRequest,
-) -> Result<String, String> {
+/// Why an append failed: the request itself, which retrying won't change, or the store.
+/// Sent to the frontend as a message, or as the `SchemaError` itself for a schema violation.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub(crate) enum AppendError {
+    /// Refused before reaching the store: permissions, read-only mode, shutdown
+    Rejected(String),
+    /// Meta that doesn't match the topic's schema
+    Schema(schema::SchemaError),
+    /// The store or CAS failed; the same request may well go through later
+    Store(String),
+}
+
+impl std::fmt::Display for AppendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppendError::Rejected(e) | AppendError::Store(e) => f.write_str(e),
+            AppendError::Schema(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+/// The checks an append command runs before appending refuse with a message.
+impl From<String> for AppendError {
+    fn from(e: String) -> Self {
+        AppendError::Rejected(e)
+    }
+}
+
+impl AppendError {
+    /// Prefixes a store failure with what was being written; refusals read fine alone.
+    pub(crate) fn context(self, what: &str) -> Self {
+        match self {
+            AppendError::Store(e) => AppendError::Store(format!("{what}: {e}")),
+            e => e,
+        }
+    }
+}
+
+impl From<AppendError> for String {
+    fn from(e: AppendError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Everything that can refuse a frame, short of the store failing to take it.
+fn check_append(frame: &Frame) -> Result<(), AppendError> {
+    store_lock::ensure_writable().map_err(AppendError::Rejected)?;
+    shutdown::ensure_open().map_err(AppendError::Rejected)?;
+    schema::validate(&frame.topic, frame.meta.as_ref()).map_err(AppendError::Schema)
+}
+
+/// Writes a frame to the store. Every append goes through here, so none gets past read-only
+/// mode or its topic's schema, or lands after the store is flushed for exit.
+pub(crate) fn append_to_store(store: &Store, frame: Frame) -> Result<Frame, AppendError> {
+    check_append(&frame)?;
+    store
+        .append(frame)
+        .map_err(|e| AppendError::Store(e.to_string()))
+}
+
+fn request_meta(request: &AppendRequest) -> Option<serde_json::Value> {
+    request
+        .meta
+        .clone()
+        .map(|m| serde_json::Value::Object(m.into_iter().collect()))
+}
+
+async fn prepare_frame(store: &Store, request: AppendRequest) -> Result<Frame, AppendError> {
+    // Whoever wrote it, a note in a locked yak is only ever stored sealed
+    let request = locks::seal_append(request).map_err(AppendError::Rejected)?;
+    let mut meta = request_meta(&request);
+
+    // Sealed content is opaque, and an explicit content_type from the caller wins
+    if !request.content.is_empty() && !locks::is_sealed(&request.content) {
+        let fields = meta.get_or_insert_with(|| serde_json::json!({}));
+        if let Some(fields) = fields.as_object_mut() {
+            fields
+                .entry("content_type")
+                .or_insert_with(|| mime::sniff(&request.content).into());
+            if mentions::TOPICS.contains(&request.topic.as_str()) {
+                fields.insert(
+                    "mentions".to_string(),
+                    mentions::extract(&request.content).into(),
+                );
+            }
+        }
+    }
+
+    permissions::check_append(&request.topic, meta.as_ref()).map_err(AppendError::Rejected)?;
+    provenance::stamp(&mut meta, provenance::Source::Ui);
+
     // Insert content into CAS if provided
     let hash = if !request.content.is_empty() {
+        metrics::record_cas_write(request.content.len());
         Some(
             store
                 .cas_insert(&request.content.into_bytes())
                 .await
-                .map_err(|e| format!("Failed to insert content: {e}"))?,
+                .map_err(|e| AppendError::Store(format!("Failed to insert content: {e}")))?,
         )
     } else {
         None
     };
 
     let context_id = ZERO_CONTEXT; // Use system context for now
-    let frame_id = scru128::new();
 
-    let frame = Frame {
-        id: frame_id,
+    Ok(Frame {
+        id: scru128::new(),
         context_id,
-        topic: request.topic.clone(),
+        topic: request.topic,
         hash,
-        meta: request
-            .meta
-            .map(|m| serde_json::Value::Object(m.into_iter().collect())),
+        meta,
         ttl: None,
-    };
+    })
+}
 
-    let appended_frame = store
-        .append(frame)
-        .map_err(|e| format!("Failed to append frame: {e}"))?;
+#[tauri::command]
+async fn append_event(
+    store: State<'_, Store>,
+    app: AppHandle,
+    mut request: AppendRequest,
+) -> Result<String, AppendError> {
+    let _guard = shutdown::begin_write(&app)?;
+    topics::check_user(&request.topic)?;
+    request.content = secrets::check(&app, &request.topic, request.content);
+    app.state::<meetings::MeetingState>().stamp(&mut request);
+    let mut request = app.state::<locks::LockState>().seal_request(request)?;
+    enrich::enrich(&app, &mut request);
+    let overflow = limits::check(&app, &mut request)?;
+
+    // A store failure queues the request rather than lose it; a rejected one is just refused
+    let appended_frame = match prepare_frame(&store, request.clone()).await {
+        Ok(frame) => append_to_store(&store, frame)
+            .inspect(|_| metrics::record_appends(1))
+            .map_err(|e| e.context("Failed to append frame")),
+        Err(e) => Err(e),
+    }
+    .map_err(|e| match e {
+        AppendError::Store(e) => {
+            // Queued whole; the retry isn't cut short
+            if let Some(full) = overflow.clone() {
+                request.content = full;
+            }
+            let id = outbox::enqueue(&app, vec![request], &e);
+            AppendError::Store(format!("{e}; queued for retry as {id}"))
+        }
+        e => e,
+    })?;
 
     // Emit the frame to frontend via Tauri events
-    app.emit("frame", &appended_frame)
-        .map_err(|e| format!("Failed to emit frame: {e}"))?;
+    windows::emit_frame(&app, &appended_frame).map_err(|e| format!("Failed to emit frame: {e}"))?;
+    if let Some(full) = overflow {
+        let attachment = limits::attach_overflow(&app, &store, &appended_frame, full).await?;
+        windows::emit_frame(&app, &attachment).map_err(|e| format!("Failed to emit frame: {e}"))?;
+    }
 
-    Ok(frame_id.to_string())
+    Ok(appended_frame.id.to_string())
 }
 
-#[tauri::command]
-async fn get_cas_content(store: State<'_, Store>, hash: String) -> Result<String, String> {
-    let integrity = hash
-        .parse::<ssri::Integrity>()
-        .map_err(|e| format!("Invalid hash format: {e}"))?;
+/// Appends a group of frames as a unit, as far as the store allows. Every frame is checked
+/// and its CAS content inserted before any is written, so a refused frame leaves the log
+/// untouched. The store has no transactions, though: if it fails partway, the frames already
+/// written are removed again, but anything following the store may have seen them by then.
+async fn append_batch_to_store(
+    store: &Store,
+    requests: Vec<AppendRequest>,
+) -> Result<Vec<Frame>, String> {
+    Ok(try_append_batch(store, requests).await?)
+}
+
+/// `append_batch_to_store`, telling a rejected batch from one the store failed to take.
+async fn try_append_batch(
+    store: &Store,
+    requests: Vec<AppendRequest>,
+) -> Result<Vec<Frame>, AppendError> {
+    store_lock::ensure_writable().map_err(AppendError::Rejected)?;
+    let mut frames = Vec::with_capacity(requests.len());
+    for request in requests {
+        frames.push(prepare_frame(store, request).await?);
+    }
+    // Refused before the first write, rather than rolled back after
+    frames.iter().try_for_each(check_append)?;
+
+    let mut appended: Vec<Frame> = Vec::with_capacity(frames.len());
+    for frame in frames {
+        match append_to_store(store, frame) {
+            Ok(frame) => appended.push(frame),
+            Err(e) => {
+                for frame in &appended {
+                    if let Err(e) = store.remove(&frame.id) {
+                        eprintln!("Failed to roll back frame {}: {e}", frame.id);
+                    }
+                }
+                return Err(e.context("Failed to append batch"));
+            }
+        }
+    }
+    metrics::record_appends(appended.len() as u64);
+    appended.iter().for_each(permissions::record_append);
 
-    let content = store
-        .cas_read(&integrity)
+    Ok(appended)
+}
+
+#[tauri::command]
+async fn append_batch(
+    store: State<'_, Store>,
+    app: AppHandle,
+    requests: Vec<AppendRequest>,
+) -> Result<Vec<String>, AppendError> {
+    let _guard = shutdown::begin_write(&app)?;
+    for request in &requests {
+        topics::check_user(&request.topic)?;
+    }
+    let locks = app.state::<locks::LockState>();
+    let meetings = app.state::<meetings::MeetingState>();
+    let mut overflows = Vec::with_capacity(requests.len());
+    let requests = requests
+        .into_iter()
+        .map(|mut request| {
+            request.content = secrets::check(&app, &request.topic, request.content);
+            meetings.stamp(&mut request);
+            let mut request = locks.seal_request(request)?;
+            enrich::enrich(&app, &mut request);
+            overflows.push(limits::check(&app, &mut request)?);
+            Ok(request)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut appended = try_append_batch(&store, requests.clone())
         .await
-        .map_err(|e| format!("Failed to read content: {e}"))?;
+        .map_err(|e| match e {
+            AppendError::Store(e) => {
+                let mut requests = requests;
+                for (request, full) in requests.iter_mut().zip(&overflows) {
+                    if let Some(full) = full {
+                        request.content = full.clone();
+                    }
+                }
+                let id = outbox::enqueue(&app, requests, &e);
+                AppendError::Store(format!("{e}; queued for retry as {id}"))
+            }
+            e => e,
+        })?;
+    let ids: Vec<String> = appended.iter().map(|frame| frame.id.to_string()).collect();
+    for (i, full) in overflows.into_iter().enumerate() {
+        if let Some(full) = full {
+            let frame = appended[i].clone();
+            appended.push(limits::attach_overflow(&app, &store, &frame, full).await?);
+        }
+    }
+
+    // Emit the whole batch at once so the frontend can apply it in a single update
+    windows::emit_frames(&app, &appended).map_err(|e| format!("Failed to emit frames: {e}"))?;
+
+    Ok(ids)
+}
+
+#[tauri::command]
+async fn get_cas_content(
+    store: State<'_, Store>,
+    cache: State<'_, std::sync::Arc<cache::CasCache>>,
+    locks: State<'_, locks::LockState>,
+    hash: String,
+) -> Result<String, integrity::ReadError> {
+    app_lock::ensure_unlocked()?;
+    let content = cache.read_verified(&store, &hash).await?;
+    Ok(locks.open(&hash, &content)?)
+}
+
+/// Inserts `content` (if any) into the CAS and appends a frame referencing it.
+pub(crate) async fn append_frame(
+    store: &Store,
+    topic: &str,
+    content: Option<&[u8]>,
+    mut meta: Option<serde_json::Value>,
+) -> Result<Frame, String> {
+    store_lock::ensure_writable()?;
+    let content = locks::seal_frame(topic, content, &mut meta)?;
+    permissions::check_append(topic, meta.as_ref())?;
+    if let Some(content) = &content {
+        metrics::record_cas_write(content.len());
+    }
+    let hash = match content {
+        Some(content) => Some(
+            store
+                .cas_insert(&content)
+                .await
+                .map_err(|e| format!("Failed to insert content: {e}"))?,
+        ),
+        None => None,
+    };
 
-    String::from_utf8(content).map_err(|e| format!("Invalid UTF-8 content: {e}"))
+    provenance::stamp(&mut meta, provenance::Source::App);
+    let frame = Frame {
+        id: scru128::new(),
+        context_id: ZERO_CONTEXT,
+        topic: topic.to_string(),
+        hash,
+        meta,
+        ttl: None,
+    };
+    let frame = append_to_store(store, frame).map_err(|e| e.context("Failed to append frame"))?;
+    metrics::record_appends(1);
+    permissions::record_append(&frame);
+    Ok(frame)
+}
+
+/// Reads every historical frame in the store, in order, without following.
+pub(crate) async fn read_all_frames(store: &Store) -> Vec<Frame> {
+    let read_options = ReadOptions::builder().follow(FollowOption::Off).build();
+    let mut rx = store.read(read_options).await;
+    let mut frames = Vec::new();
+    while let Some(frame) = rx.recv().await {
+        frames.push(frame);
+    }
+    frames
 }
 
 #[tauri::command]
 fn log_message(level: String, message: String) {
+    diagnostics::record_log(&level, &message);
     match level.as_str() {
         "error" => eprintln!("[FRONTEND ERROR] {message}"),
         "warn" => eprintln!("[FRONTEND WARN] {message}"),
@@ -77,14 +459,61 @@ fn log_message(level: String, message: String) {
     }
 }
 
+/// Starts the background tasks that follow the store, under the health check.
+fn start_watchers(app: &AppHandle, store: &Store) {
+    health::supervise(app, store, "ics", |_, store| export::watch_ics(store));
+    health::supervise(app, store, "spotlight", export::watch_spotlight);
+    health::supervise(app, store, "git-mirror", export::watch_git_mirrors);
+    health::supervise(app, store, "archive", web::watch_archive);
+    health::supervise(app, store, "unfurl", web::watch_unfurl);
+    health::supervise(app, store, "feeds", feeds::watch);
+    health::supervise(app, store, "webhooks", webhooks::watch);
+    health::supervise(app, store, "integrations", integrations::watch);
+    health::supervise(app, store, "mail", mail::watch);
+    health::supervise(app, store, "folder", folder::watch);
+    health::supervise(app, store, "handlers", handlers::watch);
+    health::supervise(app, store, "rules", rules::watch);
+    health::supervise(app, store, "ocr", extract::watch_ocr);
+    health::supervise(app, store, "pdf", extract::watch_pdf);
+    health::supervise(app, store, "transcription", extract::watch_transcription);
+    health::supervise(app, store, "tag_suggestions", ai::watch_tag_suggestions);
+    health::supervise(app, store, "recurrence", recurrence::watch);
+    health::supervise(app, store, "compaction", compaction::watch);
+    health::supervise(app, store, "retention", retention::watch);
+    health::supervise(app, store, "checkboxes", checkboxes::watch);
+    health::supervise(app, store, "meetings", meetings::watch);
+    health::supervise(app, store, "presence", presence::watch);
+    health::supervise(app, store, "merge", merge::watch);
+    health::supervise(app, store, "rates", rates::watch);
+    health::supervise(app, store, "disk", |app, _| disk::watch(app));
+    health::supervise(app, store, "autocomplete", autocomplete::watch);
+    health::supervise(app, store, "digests", digests::watch);
+    health::supervise(app, store, "external", external::watch);
+    health::supervise(app, store, "permissions", permissions::watch);
+    health::supervise(app, store, "task_sync", task_sync::watch);
+}
+
 async fn initialize_store(app: &AppHandle) -> Result<Store> {
-    let app_data_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| anyhow::anyhow!("Failed to get app data dir: {}", e))?;
+    let data_dir = profiles::data_dir(app).map_err(|e| anyhow::anyhow!(e))?;
+
+    tokio::fs::create_dir_all(&data_dir).await?;
 
-    tokio::fs::create_dir_all(&app_data_dir).await?;
-    let store_path = app_data_dir.join("store");
+    if std::env::args().any(|arg| arg == demo::FLAG) {
+        let store_path = demo::store_path();
+        println!(
+            "Demo mode, using a seeded store at {}",
+            store_path.display()
+        );
+        app.manage(location::StoreLocation(store_path.clone()));
+        return demo::demo_store(&store_path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to seed demo store: {}", e));
+    }
+
+    let store_path = location::resolve(&data_dir);
+    app.manage(location::StoreLocation(store_path.clone()));
+    tokio::fs::create_dir_all(&store_path).await?;
+    let writable = store_lock::initialize(&app.state::<store_lock::StoreLockState>(), &store_path);
 
     let store = Store::new(store_path);
 
@@ -102,7 +531,7 @@ async fn initialize_store(app: &AppHandle) -> Result<Store> {
         }
     }
 
-    if !has_yak {
+    if !has_yak && writable {
         println!("No yak found, creating default yak...");
         // Create default yak
         let yak_frame = Frame {
@@ -115,14 +544,13 @@ async fn initialize_store(app: &AppHandle) -> Result<Store> {
         };
 
         println!("Creating yak frame: {yak_frame:?}");
-        let appended_yak = store
-            .append(yak_frame)
-            .map_err(|e| anyhow::anyhow!("Failed to append yak: {}", e))?;
+        let appended_yak = append_to_store(&store, yak_frame)
+            .map_err(|e| anyhow::anyhow!("{}", e.context("Failed to append yak")))?;
 
         println!("Yak appended successfully: {appended_yak:?}");
 
         // Emit to frontend
-        app.emit("frame", &appended_yak).unwrap_or_else(|e| {
+        windows::emit_frame(app, &appended_yak).unwrap_or_else(|e| {
             eprintln!("Failed to emit initial yak frame: {e}");
         });
 
@@ -135,41 +563,162 @@ async fn initialize_store(app: &AppHandle) -> Result<Store> {
 }
 
 #[tauri::command]
-async fn subscribe_to_events(store: State<'_, Store>, app: AppHandle) -> Result<(), String> {
-    println!("Starting event subscription...");
+async fn subscribe_to_events(
+    store: State<'_, Store>,
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    include_system: Option<bool>,
+) -> Result<(), String> {
+    println!("Starting event subscription for {}...", window.label());
 
     // Create read options to get all frames (historical + new ones) with follow enabled
     let read_options = ReadOptions::builder().follow(FollowOption::On).build();
 
     println!("Reading frames from store with follow enabled...");
-    let mut rx = store.read(read_options).await;
-    let mut count = 0;
-
-    // Spawn task to handle the continuous stream
-    tokio::spawn(async move {
-        while let Some(frame) = rx.recv().await {
-            count += 1;
-            println!("Streaming frame {count}: {frame:?}");
-            if let Err(e) = app.emit("frame", &frame) {
-                eprintln!("Failed to emit frame: {e}");
-                break;
-            }
-        }
-        println!("Event stream ended after {count} frames");
-    });
+    let rx = store.read(read_options).await;
+
+    // Stream to the subscribing window only, so opening another window doesn't replay
+    // history into this one
+    windows::forward_to_window(
+        app,
+        window.label().to_string(),
+        None,
+        rx,
+        windows::Delivery::Events,
+        include_system.unwrap_or(false),
+    );
 
     Ok(())
 }
 
 pub fn run() {
     tauri::Builder::default()
+        // Registered first: a second launch hands its files and links to this instance
+        .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+            windows::focus_main(app);
+            let paths = open_with::paths_from_args(&argv, std::path::Path::new(&cwd));
+            open_with::handle(app, paths);
+        }))
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            None,
+        ))
+        // Attachments for the webview's media elements, which need range requests to seek
+        .register_asynchronous_uri_scheme_protocol("cas", |ctx, request, responder| {
+            let Some(store) = ctx.app_handle().try_state::<Store>() else {
+                let response = tauri::http::Response::builder()
+                    .status(tauri::http::StatusCode::SERVICE_UNAVAILABLE)
+                    .body(Vec::new())
+                    .unwrap_or_default();
+                responder.respond(response);
+                return;
+            };
+            let store = store.inner().clone();
+            tauri::async_runtime::spawn(async move {
+                responder.respond(video::serve(&store, &request).await);
+            });
+        })
         .setup(|app| {
+            provenance::initialize(app.handle());
+            profiles::initialize(app.handle());
+            app.manage(sync::SyncState::default());
+            app.manage(sync::P2pState::default());
+            app.manage(sync::S3State::default());
+            app.manage(clipboard::ClipboardState::default());
+            app.manage(web::PreviewCache::default());
+            app.manage(plugins::PluginState::default());
+            app.manage(mcp::McpState::default());
+            app.manage(meetings::MeetingState::default());
+            app.manage(focus::FocusState::default());
+            app.manage(semantic::SemanticState::default());
+            app.manage(publish::PublishState::default());
+            app.manage(windows::WindowState::default());
+            app.manage(subscriptions::SubscriptionState::default());
+            app.manage(drafts::DraftState::default());
+            app.manage(geo::GeoState::default());
+            app.manage(shutdown::ShutdownState::default());
+            app.manage(health::HealthState::default());
+            app.manage(outbox::OutboxState::default());
+            let locks = locks::LockState::default();
+            locks.activate();
+            app.manage(locks);
+            app.manage(app_lock::AppLockState::default());
+            app.manage(presence::PresenceState::default());
+            app.manage(presentation::PresentationState::default());
+            app.manage(speech::SpeechState::default());
+            app.manage(open_with::OpenWithState::default());
+            app.manage(editor::EditorState::default());
+            app.manage(secrets::SecretsState::default());
+            app.manage(limits::LimitsState::default());
+            app.manage(emission::EmissionState::default());
+            app.manage(insights::InsightsState::default());
+            app.manage(rates::RatesState::default());
+            app.manage(disk::DiskState::default());
+            app.manage(autocomplete::AutocompleteState::default());
+            app.manage(enrich::EnrichState::default());
+            app.manage(integrations::ChatState::default());
+            app.manage(locale::LocaleState::default());
+            app.manage(stats::StatsState::default());
+            app.manage(read_model::ReadModel::default());
+            app.manage(indexes::IndexState::default());
+            app.manage(store_lock::StoreLockState::default());
+            app.manage(std::sync::Arc::new(cache::CasCache::default()));
+
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
                 match initialize_store(&app_handle).await {
                     Ok(store) => {
-                        app_handle.manage(store);
+                        app_handle.manage(store.clone());
+                        // Read-only, nothing that writes on its own is started
+                        let writable = store_lock::ensure_writable().is_ok();
+                        if writable {
+                            recovery::initialize(&app_handle, &store).await;
+                        }
+                        read_model::initialize(&app_handle, &store).await;
+                        // Reading locked yaks depends on it, so it runs read-only too
+                        health::supervise(&app_handle, &store, "locks", locks::watch);
+                        if writable {
+                            start_watchers(&app_handle, &store);
+                        }
+                        windows::initialize(&app_handle);
+                        if writable {
+                            migrations::initialize(&app_handle, &store).await;
+                        }
+                        // One scan of the log for every setting and session picked up below
+                        let frames = read_all_frames(&store).await;
+                        app_lock::initialize(&app_handle, &frames).await;
+                        locale::initialize(&app_handle, &frames);
+                        secrets::initialize(&app_handle, &frames);
+                        limits::initialize(&app_handle, &frames);
+                        emission::initialize(&app_handle, &frames);
+                        insights::initialize(&app_handle, &frames);
+                        rates::initialize(&app_handle, &frames);
+                        disk::initialize(&app_handle, &frames);
+                        permissions::initialize(&frames);
+                        enrich::initialize(&app_handle, &frames);
+                        integrations::initialize(&app_handle, &frames);
+                        outbox::initialize(&app_handle, &store).await;
+                        clipboard::initialize(&app_handle, &store, &frames).await;
+                        geo::initialize(&app_handle, &frames);
+                        plugins::initialize(&app_handle, &store).await;
+                        mcp::initialize(&app_handle, &store, &frames).await;
+                        semantic::initialize(&app_handle, &store).await;
+                        publish::initialize(&app_handle, &store, &frames).await;
+                        drafts::initialize(&app_handle, &frames);
+                        meetings::initialize(&app_handle, &frames);
+                        focus::initialize(&app_handle, &store, &frames).await;
+                        shortcuts::initialize(&app_handle, &frames);
+                        autostart::initialize(&app_handle, &frames);
+                        links::initialize(&app_handle, &store);
+                        open_with::initialize(&app_handle, &store);
+                        if writable {
+                            cli::initialize(&app_handle, &store);
+                            sync::initialize(&app_handle, &store, &frames).await;
+                        }
                     }
                     Err(e) => {
                         eprintln!("Failed to initialize store: {e}");
@@ -180,13 +729,294 @@ pub fn run() {
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
+            actions::invoke_action,
+            actions::list_actions,
+            ai::accept_suggestion,
+            ai::ask_notes,
+            ai::configure_ai,
+            ai::dismiss_suggestion,
+            ai::enable_tag_suggestions,
+            ai::list_suggestions,
+            ai::summarize_note,
+            ai::summarize_yak,
+            app_lock::configure_app_lock,
+            app_lock::get_app_lock,
+            app_lock::lock_app,
+            app_lock::report_activity,
+            app_lock::unlock_app,
+            archives::extract_archive_entry,
+            archives::list_archive_entries,
             append_event,
+            append_batch,
+            audio::add_audio_note,
+            autocomplete::suggest_links,
+            autostart::get_autostart,
+            autostart::set_autostart,
+            blobs::collect_garbage,
+            blobs::get_blob_stats,
+            board::get_board,
+            board::set_note_order,
+            bulk::bulk_apply,
+            cache::cas_cache_stats,
+            cache::clear_cas_cache,
+            cache::get_cas_batch,
+            cache::get_cas_packed,
+            checkboxes::extract_tasks,
+            checkpoints::create_checkpoint,
+            checkpoints::diff_checkpoint,
+            checkpoints::list_checkpoints,
+            clipboard::clipboard_capture_status,
+            clipboard::enable_clipboard_capture,
+            clipboard::pause_clipboard_capture,
+            clips::query_clip,
+            clips::render_clip,
+            cold_archive::configure_cold_archive,
+            cold_archive::get_cold_archive_config,
+            cold_archive::search_cold_archive,
+            compaction::compact_store,
+            compaction::configure_compaction,
+            compaction::get_compaction_config,
+            conflicts::list_conflicts,
+            conflicts::resolve_conflict,
+            diagnostics::export_diagnostics,
+            diff::diff_revisions,
+            digests::configure_digests,
+            digests::generate_digest,
+            digests::get_digest_config,
+            disk::configure_disk_watchdog,
+            disk::get_disk_status,
+            drafts::commit_draft,
+            drafts::configure_drafts,
+            drafts::draft_update,
+            duplicates::find_duplicates,
+            duplicates::merge_duplicates,
+            editor::edit_externally,
+            editor::stop_editing_externally,
+            emission::configure_emit_policy,
+            emission::get_emit_policy,
+            emission::get_frame_meta,
+            enrich::configure_enrichers,
+            export::configure_git_mirror,
+            export::configure_secure_copy,
+            export::configure_spotlight,
+            export::copy_notes,
+            export::copy_secure,
+            export::export_audit_log,
+            export::export_ics,
+            export::export_json_feed,
+            export::export_note_pdf,
+            export::export_org,
+            export::export_sqlite,
+            export::export_tasks_csv,
+            export::export_yak_markdown,
+            export::get_git_mirrors,
+            export::publish_yak_html,
+            export::verify_audit_log,
+            extract::configure_ocr,
+            extract::configure_transcription,
+            extract::get_pdf_page,
+            extract::ocr_attachment,
+            extract::transcribe_attachment,
+            feeds::add_feed,
+            feeds::list_feeds,
+            feeds::remove_feed,
+            focus::get_focus,
+            focus::get_time_report,
+            focus::start_focus,
+            focus::stop_focus,
+            folder::configure_folder_import,
+            folder::import_folder_now,
+            geo::configure_location,
+            geo::list_notes_near,
+            geo::report_location,
             get_cas_content,
+            handlers::handler_logs,
+            handlers::list_handlers,
+            handlers::register_handler,
+            handlers::unregister_handler,
+            health::get_health,
+            health::restart_subsystem,
+            history::get_replay_limit,
+            history::load_older,
+            history::set_replay_limit,
+            import::import_bookmarks,
+            import::import_document,
+            import::import_enex,
+            import::import_keep_takeout,
+            import::import_markdown_dir,
+            import::import_notion_zip,
+            import::import_simplenote,
+            import::import_standard_notes,
+            inbox::configure_inbox,
+            inbox::get_inbox_count,
+            inbox::get_triage_stats,
+            inbox::triage,
+            indexes::rebuild_indexes,
+            insights::configure_insights,
+            insights::get_insights_config,
+            insights::get_usage_insights,
+            insights::record_note_open,
+            integrations::configure_chat_integration,
+            integrations::get_chat_integrations,
+            integrations::list_chat_deliveries,
+            inspect::inspect_frame,
+            inspect::inspect_frames,
+            inspect::read_frames,
+            labels::set_label,
+            limits::configure_content_limit,
+            limits::get_content_limit,
+            location::get_store_path,
+            location::set_store_path,
+            locale::get_locale,
+            locale::set_locale,
+            locks::configure_locks,
+            locks::lock_yak,
+            locks::unlock_yak,
             log_message,
-            subscribe_to_events
+            mail::check_mail_now,
+            mail::configure_mail,
+            mcp::enable_mcp_server,
+            meetings::end_meeting,
+            meetings::get_meeting,
+            meetings::start_meeting,
+            mentions::list_mentions,
+            mentions::list_people,
+            metrics::get_metrics,
+            migrations::migration_status,
+            organize::merge_yaks,
+            organize::move_notes,
+            outbox::get_pending_appends,
+            outbox::discard_pending_append,
+            outline::get_outline,
+            outline::set_parent,
+            permissions::get_integration_audit,
+            permissions::get_integration_scopes,
+            permissions::set_integration_scope,
+            plugins::enable_plugin,
+            plugins::list_plugins,
+            presence::get_presence,
+            presence::set_presence,
+            presentation::get_presentation_mode,
+            presentation::presentation_mode,
+            presentation::set_yak_private,
+            profiles::create_profile,
+            profiles::discard_fork,
+            profiles::fork_store,
+            profiles::list_profiles,
+            profiles::promote_fork,
+            profiles::set_profile_picker,
+            profiles::switch_profile,
+            projects::create_project,
+            projects::list_project_templates,
+            projects::save_project_template,
+            properties::set_note_meta,
+            provenance::get_frame_provenance,
+            publish::configure_publish,
+            publish::get_ingest_usage,
+            publish::publish_status,
+            rates::configure_rate_alerts,
+            rates::get_paused_ingesters,
+            rates::get_topic_rates,
+            rates::resume_ingester,
+            read_model::get_counts,
+            read_model::get_snapshot,
+            read_model::search_notes,
+            recurrence::list_recurring,
+            recurrence::pause_recurrence,
+            recurrence::set_recurrence,
+            retention::list_retention,
+            retention::prune_expired,
+            retention::set_retention,
+            reviews::get_due_reviews,
+            reviews::grade_review,
+            reviews::mark_for_review,
+            rules::add_rule,
+            rules::list_rules,
+            rules::remove_rule,
+            rules::test_rule,
+            schema::get_topic_schemas,
+            secrets::configure_secret_scanning,
+            secrets::scan_for_secrets,
+            semantic::get_related,
+            semantic::semantic_search,
+            setup::export_config,
+            setup::import_config,
+            share::export_share_bundle,
+            share::import_share_bundle,
+            share_links::create_share_link,
+            share_links::list_share_links,
+            share_links::revoke_share_link,
+            shortcuts::list_shortcuts,
+            shortcuts::rebind_shortcut,
+            shortcuts::reset_shortcuts,
+            snapshot::get_snapshot_at,
+            snippets::add_snippet,
+            snippets::search_snippets,
+            speech::configure_speech,
+            speech::get_reading,
+            speech::get_speech_config,
+            speech::pause_reading,
+            speech::read_aloud,
+            speech::resume_reading,
+            speech::stop_reading,
+            stats::get_writing_stats,
+            store_lock::get_store_lock_status,
+            sync::configure_s3_sync,
+            sync::configure_sync,
+            sync::enable_p2p,
+            sync::list_devices,
+            sync::p2p_ticket,
+            sync::pair_peer,
+            sync::revoke_device,
+            sync::s3_restore,
+            sync::s3_sync_now,
+            sync::set_yak_sync,
+            sync::sync_peers_now,
+            sync::trust_device,
+            sync::unpair_peer,
+            sync::sync_now,
+            sync::sync_status,
+            subscribe_to_events,
+            subscriptions::subscribe,
+            subscriptions::unsubscribe,
+            task_sync::configure_task_sync,
+            task_sync::get_task_sync,
+            task_sync::sync_tasks_now,
+            templates::list_templates,
+            templates::new_from_template,
+            templates::save_template,
+            titles::rename_note,
+            undo::redo,
+            undo::undo,
+            undo::undo_status,
+            unread::mark_read,
+            video::add_video_attachment,
+            web::archive_url,
+            web::fetch_link_preview,
+            webhooks::add_webhook,
+            webhooks::list_webhooks,
+            webhooks::remove_webhook,
+            when::parse_when_text,
+            when::quick_capture,
+            when::set_reminder,
+            windows::open_yak_window,
+            windows::resume_from,
+            windows::stream_frames,
+            windows::sync_from
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::ExitRequested { api, .. } = &event {
+                shutdown::on_exit_requested(app, api);
+            }
+            // macOS delivers "Open with" and file associations as an event, not arguments
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            if let tauri::RunEvent::Opened { urls } = &event {
+                let paths = urls.iter().filter_map(|url| url.to_file_path().ok()).collect();
+                open_with::handle(app, paths);
+            }
+        });
 }
 
 #[cfg(test)]
@@ -230,4 +1060,75 @@ mod tests {
         assert_eq!(appended.topic, "test.topic");
         assert!(appended.hash.is_some());
     }
+
+    #[tokio::test]
+    async fn test_append_batch() {
+        let temp_dir = tempdir().unwrap();
+        let store = Store::new(temp_dir.path().to_path_buf());
+
+        let requests = vec![
+            AppendRequest {
+                topic: "note.create".to_string(),
+                content: "batched note".to_string(),
+                meta: Some(HashMap::from([("yak_id".to_string(), "yak".into())])),
+            },
+            AppendRequest {
+                topic: "note.tag".to_string(),
+                content: String::new(),
+                meta: None,
+            },
+        ];
+
+        let appended = append_batch_to_store(&store, requests).await.unwrap();
+        assert_eq!(appended.len(), 2);
+        assert_eq!(appended[0].topic, "note.create");
+        assert!(appended[0].hash.is_some());
+        assert!(appended[1].hash.is_none());
+        assert!(appended[0].id < appended[1].id);
+    }
+
+    #[tokio::test]
+    async fn test_failing_batch_writes_nothing() {
+        let temp_dir = tempdir().unwrap();
+        let store = Store::new(temp_dir.path().to_path_buf());
+
+        let note = |meta: Option<HashMap<String, serde_json::Value>>| AppendRequest {
+            topic: "note.create".to_string(),
+            content: "batched note".to_string(),
+            meta,
+        };
+        let yak = || Some(HashMap::from([("yak_id".to_string(), "yak".into())]));
+        // The middle frame fails its append after the first was prepared
+        let requests = vec![note(yak()), note(None), note(yak())];
+        let e = append_batch_to_store(&store, requests).await.unwrap_err();
+        assert!(e.starts_with("Invalid note.create frame"));
+        assert!(read_all_frames(&store).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_appends_are_checked_against_schemas() {
+        let temp_dir = tempdir().unwrap();
+        let store = Store::new(temp_dir.path().to_path_buf());
+
+        let meta = serde_json::json!({ "yak_id": "yak", "tag": 5 });
+        let e = append_frame(&store, "tag.add", None, Some(meta)).await;
+        assert_eq!(
+            e.unwrap_err(),
+            "Invalid tag.add frame: note_id is required, tag must be a string"
+        );
+
+        let requests = vec![AppendRequest {
+            topic: "note.create".to_string(),
+            content: "no yak".to_string(),
+            meta: None,
+        }];
+        let Err(AppendError::Schema(e)) = try_append_batch(&store, requests).await else {
+            panic!("expected a schema error");
+        };
+        assert_eq!(e.violations[0].field, "yak_id");
+        // Sent to the frontend with its violations intact
+        let json = serde_json::to_value(AppendError::Schema(e)).unwrap();
+        assert_eq!(json["violations"][0]["problem"], "is required");
+        assert!(read_all_frames(&store).await.is_empty());
+    }
 }
@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::import::add_attachment;
+use crate::locks::is_sealed;
+use crate::settings::{latest_setting, save_setting};
+use crate::AppendRequest;
+
+const CONFIG_TOPIC: &str = "limits.content";
+/// Topics whose content is held to the limit; anything else, attachments included, isn't.
+const TOPICS: &[&str] = &["note.create", "note.edit"];
+const DEFAULT_MAX_BYTES: usize = 1024 * 1024;
+const MIN_MAX_BYTES: usize = 1024;
+/// Meta key on a cut-short note with the size of the content it was appended with.
+const FULL_SIZE_KEY: &str = "full_size";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    /// Keep what fits in the note and attach the whole content to it
+    #[default]
+    Attach,
+    /// Refuse the append
+    Reject,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentLimitConfig {
+    /// Largest note content, in bytes, stored as is
+    pub max_bytes: usize,
+    pub overflow: Overflow,
+}
+
+impl Default for ContentLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_BYTES,
+            overflow: Overflow::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LimitsState {
+    config: Mutex<ContentLimitConfig>,
+}
+
+/// What happened to content over the limit, as told to windows with "content-limited".
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentLimited {
+    pub frame_id: String,
+    pub attachment_id: String,
+    /// The content's full size, all of which is in the attachment
+    pub size: usize,
+}
+
+/// The longest start of `content` within `max_bytes`, cut after a line where one is near.
+fn excerpt(content: &str, max_bytes: usize) -> &str {
+    let mut end = max_bytes.min(content.len());
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    match content[..end].rfind('\n') {
+        Some(line) if line >= end / 2 => &content[..=line],
+        _ => &content[..end],
+    }
+}
+
+/// Holds an append to the limit: oversized note content is cut short, and the full content
+/// returned so it can be attached once the note is appended, or the append is refused.
+/// Sealed content is left alone, so a locked yak's content is never attached in the clear.
+pub(crate) fn split(
+    request: &mut AppendRequest,
+    config: &ContentLimitConfig,
+) -> Result<Option<String>, String> {
+    let size = request.content.len();
+    if size <= config.max_bytes
+        || !TOPICS.contains(&request.topic.as_str())
+        || is_sealed(&request.content)
+    {
+        return Ok(None);
+    }
+    if config.overflow == Overflow::Reject {
+        return Err(format!(
+            "Content is {size} bytes, over the limit of {} bytes",
+            config.max_bytes
+        ));
+    }
+    let kept = excerpt(&request.content, config.max_bytes).to_string();
+    let full = std::mem::replace(&mut request.content, kept);
+    request
+        .meta
+        .get_or_insert_with(Default::default)
+        .insert(FULL_SIZE_KEY.to_string(), size.into());
+    Ok(Some(full))
+}
+
+/// `split` with the configured limit.
+pub(crate) fn check(
+    app: &AppHandle,
+    request: &mut AppendRequest,
+) -> Result<Option<String>, String> {
+    let config = app.state::<LimitsState>().config.lock().unwrap().clone();
+    split(request, &config)
+}
+
+/// Attaches the full content of a note `split` cut short, telling windows what happened.
+pub(crate) async fn attach_overflow(
+    app: &AppHandle,
+    store: &Store,
+    frame: &Frame,
+    content: String,
+) -> Result<Frame, String> {
+    let meta = frame.meta.as_ref();
+    let yak_id = meta
+        .and_then(|meta| meta.get("yak_id"))
+        .and_then(|yak_id| yak_id.as_str())
+        .unwrap_or_default();
+    let markdown = meta
+        .and_then(|meta| meta.get("content_type"))
+        .is_some_and(|content_type| content_type == "text/markdown");
+    let name = if markdown {
+        "content.md"
+    } else {
+        "content.txt"
+    };
+    let note_id = frame.id.to_string();
+    let attachment = add_attachment(
+        store,
+        yak_id,
+        &note_id,
+        name,
+        content.as_bytes(),
+        json!({ "overflow": true }),
+    )
+    .await
+    .map_err(|e| format!("Failed to attach the rest of the content: {e}"))?;
+
+    let limited = ContentLimited {
+        frame_id: note_id,
+        attachment_id: attachment.id.to_string(),
+        size: content.len(),
+    };
+    if let Err(e) = app.emit("content-limited", &limited) {
+        eprintln!("Failed to emit content limited: {e}");
+    }
+    Ok(attachment)
+}
+
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let config: ContentLimitConfig = latest_setting(frames, CONFIG_TOPIC);
+    *app.state::<LimitsState>().config.lock().unwrap() = config;
+}
+
+/// Sets the largest note content appended as is. Past it, the note keeps what fits and the
+/// full content is attached to it, with windows hearing "content-limited", or with
+/// `reject` the append fails.
+#[tauri::command]
+pub async fn configure_content_limit(
+    store: State<'_, Store>,
+    state: State<'_, LimitsState>,
+    max_bytes: Option<usize>,
+    overflow: Option<Overflow>,
+) -> Result<ContentLimitConfig, String> {
+    let mut config = state.config.lock().unwrap().clone();
+    if let Some(max_bytes) = max_bytes {
+        if max_bytes < MIN_MAX_BYTES {
+            return Err(format!("The limit must be at least {MIN_MAX_BYTES} bytes"));
+        }
+        config.max_bytes = max_bytes;
+    }
+    if let Some(overflow) = overflow {
+        config.overflow = overflow;
+    }
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    *state.config.lock().unwrap() = config.clone();
+    Ok(config)
+}
+
+#[tauri::command]
+pub async fn get_content_limit(
+    state: State<'_, LimitsState>,
+) -> Result<ContentLimitConfig, String> {
+    Ok(state.config.lock().unwrap().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(topic: &str, content: &str) -> AppendRequest {
+        AppendRequest {
+            topic: topic.to_string(),
+            content: content.to_string(),
+            meta: None,
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        let config = ContentLimitConfig {
+            max_bytes: 40,
+            overflow: Overflow::Attach,
+        };
+        let content = "# Paste\nfirst line of it\nsecond line, which goes on\n";
+        let mut note = request("note.create", content);
+        assert_eq!(split(&mut note, &config).unwrap().as_deref(), Some(content));
+        // Cut after the last line that fits
+        assert_eq!(note.content, "# Paste\nfirst line of it\n");
+        assert_eq!(note.meta.unwrap()[FULL_SIZE_KEY], content.len());
+
+        // Never mid-character
+        let mut wide = request("note.edit", &"é".repeat(30));
+        split(&mut wide, &config).unwrap();
+        assert_eq!(wide.content, "é".repeat(20));
+
+        let mut small = request("note.create", "short");
+        assert_eq!(split(&mut small, &config).unwrap(), None);
+        let mut attachment = request("attachment.add", content);
+        assert_eq!(split(&mut attachment, &config).unwrap(), None);
+
+        let reject = ContentLimitConfig {
+            overflow: Overflow::Reject,
+            ..config
+        };
+        assert!(split(&mut request("note.create", content), &reject).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+use xs::store::Store;
+
+use crate::projection::Projection;
+use crate::read_all_frames;
+use crate::windows::focus_main;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenNote {
+    pub note_id: String,
+    pub yak_id: String,
+}
+
+/// The note a `yaks://note/<id>` link points at.
+fn note_id(url: &str) -> Option<&str> {
+    let id = url.strip_prefix("yaks://note/")?.trim_end_matches('/');
+    (!id.is_empty()).then_some(id)
+}
+
+/// Shows the note behind a link, following edits made since the link was written.
+async fn open(app: AppHandle, store: Store, note_id: String) {
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let note_id = projection.resolve(&note_id);
+    let Some(yak_id) = projection
+        .notes
+        .get(&note_id)
+        .map(|note| note.yak_id.clone())
+    else {
+        eprintln!("Link to unknown note {note_id}");
+        return;
+    };
+    if let Some(window) = focus_main(&app) {
+        if let Err(e) = window.emit("open-note", OpenNote { note_id, yak_id }) {
+            eprintln!("Failed to emit open-note: {e}");
+        }
+    }
+}
+
+fn handle(app: &AppHandle, store: &Store, urls: impl IntoIterator<Item = Url>) {
+    for url in urls {
+        if let Some(id) = note_id(url.as_str()) {
+            tauri::async_runtime::spawn(open(app.clone(), store.clone(), id.to_string()));
+        }
+    }
+}
+
+/// Handles `yaks://` links, including the one the app was launched with.
+pub(crate) fn initialize(app: &AppHandle, store: &Store) {
+    let deep_link = app.deep_link();
+    // macOS registers the scheme from the bundle; elsewhere it's registered at runtime
+    #[cfg(any(windows, target_os = "linux"))]
+    if let Err(e) = deep_link.register_all() {
+        eprintln!("Failed to register yaks:// links: {e}");
+    }
+    if let Ok(Some(urls)) = deep_link.get_current() {
+        handle(app, store, urls);
+    }
+    let (handler_app, handler_store) = (app.clone(), store.clone());
+    deep_link.on_open_url(move |event| handle(&handler_app, &handler_store, event.urls()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_id() {
+        assert_eq!(note_id("yaks://note/abc"), Some("abc"));
+        assert_eq!(note_id("yaks://note/abc/"), Some("abc"));
+        assert_eq!(note_id("yaks://note/"), None);
+        assert_eq!(note_id("https://note/abc"), None);
+    }
+}
@@ -0,0 +1,185 @@
+use chrono::{NaiveDate, Weekday};
+use icu::calendar::types::IsoWeekday;
+use icu::calendar::week::WeekCalculator;
+use icu::collator::{Collator, CollatorOptions};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::settings::{latest_setting, save_setting};
+
+const CONFIG_TOPIC: &str = "locale.config";
+/// Used when neither the setting nor the environment names a locale.
+const FALLBACK_LOCALE: &str = "en-US";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct LocaleConfig {
+    /// A BCP 47 tag such as "de-CH"; the system's when unset
+    pub locale: Option<String>,
+    /// Overrides the locale's first day of the week
+    pub week_start: Option<Weekday>,
+}
+
+/// The locale dates are grouped and lists are sorted by.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Locale {
+    pub tag: String,
+    pub week_start: Weekday,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::resolve(&LocaleConfig::default()).unwrap_or(Locale {
+            tag: FALLBACK_LOCALE.to_string(),
+            week_start: Weekday::Sun,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct LocaleState {
+    locale: Mutex<Locale>,
+}
+
+impl LocaleState {
+    pub(crate) fn get(&self) -> Locale {
+        self.locale.lock().unwrap().clone()
+    }
+}
+
+fn parse(tag: &str) -> Result<icu::locid::Locale, String> {
+    tag.parse()
+        .map_err(|e| format!("Failed to parse locale {tag:?}: {e}"))
+}
+
+/// The locale from `LC_ALL`, `LC_COLLATE` or `LANG`, as in "de_CH.UTF-8".
+fn system_tag() -> String {
+    ["LC_ALL", "LC_COLLATE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .map(|value| {
+            value
+                .split(['.', '@'])
+                .next()
+                .unwrap_or("")
+                .replace('_', "-")
+        })
+        .find(|tag| !tag.is_empty() && tag != "C" && tag != "POSIX" && parse(tag).is_ok())
+        .unwrap_or_else(|| FALLBACK_LOCALE.to_string())
+}
+
+fn weekday(day: IsoWeekday) -> Weekday {
+    match day {
+        IsoWeekday::Monday => Weekday::Mon,
+        IsoWeekday::Tuesday => Weekday::Tue,
+        IsoWeekday::Wednesday => Weekday::Wed,
+        IsoWeekday::Thursday => Weekday::Thu,
+        IsoWeekday::Friday => Weekday::Fri,
+        IsoWeekday::Saturday => Weekday::Sat,
+        IsoWeekday::Sunday => Weekday::Sun,
+    }
+}
+
+impl Locale {
+    /// The locale `config` names, with the first day of the week CLDR gives its region
+    /// (Sunday in the US, Monday in most of Europe, Saturday in parts of the Middle East).
+    fn resolve(config: &LocaleConfig) -> Result<Locale, String> {
+        let tag = config.locale.clone().unwrap_or_else(system_tag);
+        let locale = parse(&tag)?;
+        let week_start = config.week_start.unwrap_or_else(|| {
+            WeekCalculator::try_new(&(&locale).into())
+                .map(|week| weekday(week.first_weekday))
+                .unwrap_or(Weekday::Mon)
+        });
+        Ok(Locale {
+            tag: locale.to_string(),
+            week_start,
+        })
+    }
+
+    /// The first day of the week `date` falls in.
+    pub(crate) fn week_of(&self, date: NaiveDate) -> NaiveDate {
+        date.week(self.week_start).first_day()
+    }
+
+    /// Sorts `items` alphabetically by `key` the way the locale does, so accented and
+    /// differently cased names sit with their neighbours rather than after "z".
+    pub(crate) fn sort_by_key<T>(&self, items: &mut [T], key: impl Fn(&T) -> &str) {
+        let collator = parse(&self.tag)
+            .ok()
+            .and_then(|locale| Collator::try_new(&(&locale).into(), CollatorOptions::new()).ok());
+        match collator {
+            Some(collator) => items.sort_by(|a, b| collator.compare(key(a), key(b))),
+            None => items.sort_by(|a, b| key(a).cmp(key(b))),
+        }
+    }
+}
+
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let config: LocaleConfig = latest_setting(frames, CONFIG_TOPIC);
+    match Locale::resolve(&config) {
+        Ok(locale) => *app.state::<LocaleState>().locale.lock().unwrap() = locale,
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+/// Sets the locale used to group dates into weeks and to sort names, tags and people.
+/// Without `locale` the system's is used; `week_start` overrides the day weeks start on.
+#[tauri::command]
+pub async fn set_locale(
+    store: State<'_, Store>,
+    state: State<'_, LocaleState>,
+    locale: Option<String>,
+    week_start: Option<Weekday>,
+) -> Result<Locale, String> {
+    let config = LocaleConfig {
+        locale: locale.filter(|tag| !tag.trim().is_empty()),
+        week_start,
+    };
+    let resolved = Locale::resolve(&config)?;
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    *state.locale.lock().unwrap() = resolved.clone();
+    Ok(resolved)
+}
+
+#[tauri::command]
+pub fn get_locale(state: State<'_, LocaleState>) -> Locale {
+    state.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locale(tag: &str) -> Locale {
+        Locale::resolve(&LocaleConfig {
+            locale: Some(tag.to_string()),
+            week_start: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_locale() {
+        let us = locale("en-US");
+        let de = locale("de-DE");
+        assert_eq!((us.week_start, de.week_start), (Weekday::Sun, Weekday::Mon));
+        let wednesday = "2025-03-05".parse::<NaiveDate>().unwrap();
+        assert_eq!(us.week_of(wednesday).to_string(), "2025-03-02");
+        assert_eq!(de.week_of(wednesday).to_string(), "2025-03-03");
+
+        let mut tags = vec!["zebra", "Äpfel", "apple", "Zoo"];
+        de.sort_by_key(&mut tags, |tag| *tag);
+        assert_eq!(tags, vec!["Äpfel", "apple", "zebra", "Zoo"]);
+        let mut tags = vec!["zebra", "öl", "ost"];
+        locale("sv-SE").sort_by_key(&mut tags, |tag| *tag);
+        assert_eq!(tags, vec!["ost", "zebra", "öl"]);
+
+        assert!(Locale::resolve(&LocaleConfig {
+            locale: Some("not a locale!".to_string()),
+            week_start: None,
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use xs::store::Store;
+
+use crate::profiles;
+use crate::read_all_frames;
+use crate::settings::save_setting;
+
+/// File in the app data dir holding the path of a store that has been moved elsewhere.
+const POINTER: &str = "store-path";
+const SETTING_TOPIC: &str = "store.location";
+/// Where the CAS keeps content, under its `cacache` dir.
+const CONTENT_DIR: &str = "content-v2";
+
+/// Where the running store lives.
+pub struct StoreLocation(pub PathBuf);
+
+#[derive(Debug, Clone, Serialize)]
+struct LocationSetting {
+    path: PathBuf,
+    from: PathBuf,
+}
+
+/// The store's path: the one recorded by `set_store_path`, if any, or `store` in the app
+/// data dir.
+pub(crate) fn resolve(app_data_dir: &Path) -> PathBuf {
+    std::fs::read_to_string(app_data_dir.join(POINTER))
+        .ok()
+        .map(|path| PathBuf::from(path.trim()))
+        .filter(|path| !path.as_os_str().is_empty())
+        .unwrap_or_else(|| app_data_dir.join("store"))
+}
+
+/// Makes the profile in `dir` open the store at `target`, replacing the pointer file in one
+/// rename.
+pub(crate) fn point(dir: &Path, target: &Path) -> Result<(), String> {
+    let pointer = dir.join(POINTER);
+    let staged_pointer = dir.join(format!("{POINTER}.new"));
+    std::fs::write(&staged_pointer, target.to_string_lossy().as_bytes())
+        .map_err(|e| format!("Failed to record store path: {e}"))?;
+    std::fs::rename(&staged_pointer, &pointer)
+        .map_err(|e| format!("Failed to record store path: {e}"))
+}
+
+/// Copies `from` into `to`. With `share_content`, the CAS's content files, which never change
+/// once written, are hard-linked instead where the filesystem allows it.
+fn copy_dir(from: &Path, to: &Path, share_content: bool) -> Result<(), String> {
+    std::fs::create_dir_all(to).map_err(|e| format!("Failed to create {}: {e}", to.display()))?;
+    let entries =
+        std::fs::read_dir(from).map_err(|e| format!("Failed to read {}: {e}", from.display()))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {e}", from.display()))?;
+        let target = to.join(entry.file_name());
+        let file_type = entry
+            .file_type()
+            .map_err(|e| format!("Failed to read {}: {e}", entry.path().display()))?;
+        let content = share_content
+            && entry
+                .path()
+                .components()
+                .any(|component| component.as_os_str() == CONTENT_DIR);
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target, share_content)?;
+        } else if content && std::fs::hard_link(entry.path(), &target).is_ok() {
+            continue;
+        } else {
+            std::fs::copy(entry.path(), &target)
+                .map_err(|e| format!("Failed to copy {}: {e}", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks a copied store holds every frame `original` had when the copy started, and that
+/// all of its content reads back intact.
+async fn verify(original: &Store, copy: &Store) -> Result<usize, String> {
+    let copied = read_all_frames(copy).await;
+    let copied_ids: std::collections::HashSet<String> =
+        copied.iter().map(|frame| frame.id.to_string()).collect();
+    let last = copied.last().map(|frame| frame.id);
+    let missing = read_all_frames(original)
+        .await
+        .into_iter()
+        .filter(|frame| last.map_or(true, |last| frame.id <= last))
+        .filter(|frame| !copied_ids.contains(&frame.id.to_string()))
+        .count();
+    if missing > 0 {
+        return Err(format!("Copy is missing {missing} frames"));
+    }
+    for hash in copied.iter().filter_map(|frame| frame.hash.as_ref()) {
+        // The CAS checks content against its hash on read
+        copy.cas_read(hash)
+            .await
+            .map_err(|e| format!("Copied content {hash} is unreadable: {e}"))?;
+    }
+    Ok(copied.len())
+}
+
+/// Copies the store to `target` via a staging dir beside it, verifies the copy, then moves
+/// it into place with a rename so `target` is either absent or complete. `share_content`
+/// hard-links content rather than copying it, for a copy on the same disk.
+pub(crate) async fn copy_store(
+    store: &Store,
+    from: &Path,
+    target: &Path,
+    share_content: bool,
+) -> Result<Store, String> {
+    if target.exists()
+        && std::fs::read_dir(target)
+            .map(|mut entries| entries.next().is_some())
+            .unwrap_or(true)
+    {
+        return Err(format!(
+            "{} already exists and isn't empty",
+            target.display()
+        ));
+    }
+    let name = target
+        .file_name()
+        .ok_or_else(|| format!("Invalid store path {}", target.display()))?
+        .to_string_lossy();
+    let staging = target.with_file_name(format!(".{name}.partial"));
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)
+            .map_err(|e| format!("Failed to clear {}: {e}", staging.display()))?;
+    }
+
+    copy_dir(from, &staging, share_content)?;
+    let copy = Store::new(staging.clone());
+    let verified = verify(store, &copy).await;
+    drop(copy);
+    if let Err(e) = verified {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    if target.exists() {
+        std::fs::remove_dir(target)
+            .map_err(|e| format!("Failed to replace {}: {e}", target.display()))?;
+    }
+    std::fs::rename(&staging, target)
+        .map_err(|e| format!("Failed to move store into {}: {e}", target.display()))?;
+    Ok(Store::new(target.to_path_buf()))
+}
+
+#[tauri::command]
+pub fn get_store_path(location: State<'_, StoreLocation>) -> String {
+    location.0.to_string_lossy().into_owned()
+}
+
+/// Moves the store to `new_path` (e.g. another disk or a synced folder) and restarts onto
+/// it. The old store is left in place untouched.
+#[tauri::command]
+pub async fn set_store_path(
+    app: AppHandle,
+    store: State<'_, Store>,
+    location: State<'_, StoreLocation>,
+    new_path: String,
+) -> Result<String, String> {
+    let target = PathBuf::from(&new_path);
+    if !target.is_absolute() {
+        return Err("The store path must be absolute".to_string());
+    }
+    if target == location.0 {
+        return Ok(new_path);
+    }
+
+    let moved = copy_store(&store, &location.0, &target, false).await?;
+    let setting = LocationSetting {
+        path: target.clone(),
+        from: location.0.clone(),
+    };
+    save_setting(&moved, SETTING_TOPIC, &setting)?;
+    drop(moved);
+
+    point(&profiles::data_dir(&app)?, &target)?;
+
+    // Give the response a moment to reach the window before restarting onto the new store
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        app.restart();
+    });
+    Ok(new_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_resolve() {
+        let dir = tempdir().unwrap();
+        assert_eq!(resolve(dir.path()), dir.path().join("store"));
+        std::fs::write(dir.path().join(POINTER), "/elsewhere/store\n").unwrap();
+        assert_eq!(resolve(dir.path()), PathBuf::from("/elsewhere/store"));
+    }
+
+    #[tokio::test]
+    async fn test_copy_store() {
+        let dir = tempdir().unwrap();
+        let from = dir.path().join("store");
+        let store = Store::new(from.clone());
+        let frame = append_frame(
+            &store,
+            "note.create",
+            Some(b"hello".as_slice()),
+            Some(serde_json::json!({ "yak_id": "y" })),
+        )
+        .await
+        .unwrap();
+
+        let target = dir.path().join("moved");
+        let moved = copy_store(&store, &from, &target, false).await.unwrap();
+        let frames = read_all_frames(&moved).await;
+        assert_eq!(frames[0].id, frame.id);
+        assert_eq!(
+            moved.cas_read(frame.hash.as_ref().unwrap()).await.unwrap(),
+            b"hello"
+        );
+        assert!(!dir.path().join(".moved.partial").exists());
+        assert!(copy_store(&store, &from, &target, false).await.is_err());
+
+        let forked = copy_store(&store, &from, &dir.path().join("fork"), true)
+            .await
+            .unwrap();
+        let content = forked.cas_read(frame.hash.as_ref().unwrap()).await.unwrap();
+        assert_eq!(content, b"hello");
+    }
+}
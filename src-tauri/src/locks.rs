@@ -0,0 +1,508 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{AppHandle, Manager, State};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::crypto::{self, KEY_LEN};
+use crate::export::content_title;
+use crate::projection::Projection;
+use crate::settings::{load_setting, save_setting};
+use crate::{append_batch_to_store, read_all_frames, shutdown, windows, AppendRequest};
+
+const LOCK_TOPIC: &str = "yak.lock";
+const CONFIG_TOPIC: &str = "lock.config";
+/// Sealed note content is stored as `yaks-locked:v1:<yak id>:<base64>` so any read can tell
+/// which yak's key opens it.
+const ENVELOPE_PREFIX: &str = "yaks-locked:v1:";
+/// Encrypted with the yak's key when it's locked, to check passphrases against.
+const VERIFIER: &[u8] = b"yaks";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct LockConfig {
+    /// Leave locked yaks out of snapshots entirely, rather than showing note titles
+    pub hide_locked: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Lock {
+    yak_id: String,
+    salt: String,
+    verifier: String,
+}
+
+impl Lock {
+    /// The yak's key, if `passphrase` is the one it was locked with.
+    fn key(&self, passphrase: &str) -> Result<[u8; KEY_LEN], String> {
+        let key = crypto::derive_key(passphrase, &crypto::from_hex(&self.salt)?)?;
+        crypto::decrypt(&key, &crypto::from_hex(&self.verifier)?)
+            .map_err(|_| "Wrong passphrase".to_string())?;
+        Ok(key)
+    }
+}
+
+/// Locked yaks, and the keys of those unlocked this session. Keys are never persisted.
+/// Clones share the same locks and keys.
+#[derive(Default, Clone)]
+pub struct LockState {
+    locks: Arc<Mutex<HashMap<String, Lock>>>,
+    keys: Arc<Mutex<HashMap<String, [u8; KEY_LEN]>>>,
+    /// Content hash of every note revision -> the yaks it was written in, so content read by
+    /// hash can be refused for locked yaks, revisions from before the lock included
+    revisions: Arc<Mutex<HashMap<String, HashSet<String>>>>,
+}
+
+/// The app's `LockState`, for the append primitives, which only have the store: every note
+/// written into a locked yak is sealed however it's written (see `seal_append`).
+static ACTIVE: OnceLock<LockState> = OnceLock::new();
+
+fn lock_from(frame: &Frame) -> Option<Lock> {
+    serde_json::from_value(frame.meta.clone()?).ok()
+}
+
+fn seal(key: &[u8; KEY_LEN], yak_id: &str, plaintext: &str) -> Result<String, String> {
+    let sealed = crypto::encrypt(key, plaintext.as_bytes())?;
+    Ok(format!(
+        "{ENVELOPE_PREFIX}{yak_id}:{}",
+        base64::engine::general_purpose::STANDARD.encode(sealed)
+    ))
+}
+
+/// The yak whose key opens `content`, and the sealed payload, if it is sealed at all.
+fn envelope(content: &str) -> Option<(&str, &str)> {
+    content.strip_prefix(ENVELOPE_PREFIX)?.split_once(':')
+}
+
+pub(crate) fn is_sealed(content: &str) -> bool {
+    envelope(content).is_some()
+}
+
+impl LockState {
+    /// Makes this the state notes are sealed with on every append.
+    pub(crate) fn activate(&self) {
+        let _ = ACTIVE.set(self.clone());
+    }
+
+    pub(crate) fn is_locked(&self, yak_id: &str) -> bool {
+        self.locks.lock().unwrap().contains_key(yak_id)
+    }
+
+    /// Drops every key unlocked this session, locking those yaks again.
+    pub(crate) fn forget_keys(&self) {
+        self.keys.lock().unwrap().clear();
+    }
+
+    fn is_unlocked(&self, yak_id: &str) -> bool {
+        self.keys.lock().unwrap().contains_key(yak_id)
+    }
+
+    /// Locked and not unlocked this session.
+    fn is_closed(&self, yak_id: &str) -> bool {
+        self.is_locked(yak_id) && !self.is_unlocked(yak_id)
+    }
+
+    /// Notes which yak a note revision's content belongs to.
+    fn track(&self, frame: &Frame) {
+        if !matches!(frame.topic.as_str(), "note.create" | "note.edit") {
+            return;
+        }
+        let yak_id = frame
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.get("yak_id"))
+            .and_then(|yak_id| yak_id.as_str());
+        if let (Some(hash), Some(yak_id)) = (&frame.hash, yak_id) {
+            self.revisions
+                .lock()
+                .unwrap()
+                .entry(hash.to_string())
+                .or_default()
+                .insert(yak_id.to_string());
+        }
+    }
+
+    /// Content read by hash, as readers may see it: refused when it's only ever been written
+    /// in yaks that are locked now, and opened if sealed.
+    pub(crate) fn open(&self, hash: &str, content: &str) -> Result<String, String> {
+        let owners = self.revisions.lock().unwrap().get(hash).cloned();
+        let closed = owners.is_some_and(|owners| {
+            !owners.is_empty() && owners.iter().all(|yak_id| self.is_closed(yak_id))
+        });
+        if closed {
+            return Err("This content belongs to a locked yak".to_string());
+        }
+        self.reveal(content)
+    }
+
+    /// Opens sealed content with this session's keys; anything else passes through.
+    pub(crate) fn reveal(&self, content: &str) -> Result<String, String> {
+        let Some((yak_id, payload)) = envelope(content) else {
+            return Ok(content.to_string());
+        };
+        let key = self
+            .keys
+            .lock()
+            .unwrap()
+            .get(yak_id)
+            .copied()
+            .ok_or_else(|| format!("Yak {yak_id} is locked"))?;
+        let sealed = base64::engine::general_purpose::STANDARD
+            .decode(payload)
+            .map_err(|e| format!("Invalid sealed content: {e}"))?;
+        let plaintext = crypto::decrypt(&key, &sealed)?;
+        String::from_utf8(plaintext).map_err(|e| format!("Invalid UTF-8 content: {e}"))
+    }
+
+    /// The sealed content and plaintext title of a note written into a locked yak, refusing
+    /// it until the yak is unlocked; `None` when the content needn't be sealed.
+    fn seal_note(
+        &self,
+        topic: &str,
+        yak_id: Option<&str>,
+        content: &str,
+    ) -> Result<Option<(String, String)>, String> {
+        if !matches!(topic, "note.create" | "note.edit") || content.is_empty() || is_sealed(content)
+        {
+            return Ok(None);
+        }
+        let Some(yak_id) = yak_id.filter(|yak_id| self.is_locked(yak_id)) else {
+            return Ok(None);
+        };
+        let key = self
+            .keys
+            .lock()
+            .unwrap()
+            .get(yak_id)
+            .copied()
+            .ok_or_else(|| format!("Yak {yak_id} is locked; unlock it first"))?;
+        Ok(Some((seal(&key, yak_id, content)?, content_title(content))))
+    }
+
+    /// Seals the content of notes written into a locked yak, refusing them until it is
+    /// unlocked. The plaintext title stays in meta, so locked yaks can still list notes.
+    pub(crate) fn seal_request(&self, mut request: AppendRequest) -> Result<AppendRequest, String> {
+        let yak_id = request
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.get("yak_id"))
+            .and_then(|yak_id| yak_id.as_str());
+        let Some((sealed, title)) = self.seal_note(&request.topic, yak_id, &request.content)?
+        else {
+            return Ok(request);
+        };
+        request.content = sealed;
+        let meta = request.meta.get_or_insert_with(HashMap::new);
+        meta.insert("encrypted".to_string(), true.into());
+        meta.insert("title".to_string(), title.into());
+        Ok(request)
+    }
+
+    /// Opens locked notes that are unlocked and blanks the rest, leaving their titles. Notes
+    /// of yaks locked now are blanked even in a projection of the log from before the lock.
+    /// With `hide`, yaks that are still locked are removed instead.
+    pub(crate) fn redact(&self, projection: &mut Projection, hide: bool) {
+        let locked: Vec<String> = projection
+            .yaks
+            .values()
+            .filter(|yak| (yak.locked || self.is_locked(&yak.id)) && !self.is_unlocked(&yak.id))
+            .map(|yak| yak.id.clone())
+            .collect();
+        for note in projection.notes.values_mut() {
+            if locked.contains(&note.yak_id) || self.is_closed(&note.yak_id) {
+                note.content = None;
+            } else if let Some(sealed) = &note.sealed {
+                note.content = self.reveal(sealed).ok();
+            }
+        }
+        if hide {
+            for yak_id in &locked {
+                projection.yaks.remove(yak_id);
+                projection.notes_by_yak.remove(yak_id);
+            }
+            projection
+                .notes
+                .retain(|_, note| !locked.contains(&note.yak_id));
+            projection
+                .tasks
+                .retain(|_, task| !locked.contains(&task.yak_id));
+        }
+    }
+}
+
+/// Seals a request with the app's lock state, if there is one.
+pub(crate) fn seal_append(request: AppendRequest) -> Result<AppendRequest, String> {
+    match ACTIVE.get() {
+        Some(state) => state.seal_request(request),
+        None => Ok(request),
+    }
+}
+
+/// Seals the content of a frame about to be appended, like `seal_append` does a request,
+/// returning the content to write.
+pub(crate) fn seal_frame(
+    topic: &str,
+    content: Option<&[u8]>,
+    meta: &mut Option<serde_json::Value>,
+) -> Result<Option<Vec<u8>>, String> {
+    let (Some(state), Some(text)) = (
+        ACTIVE.get(),
+        content.and_then(|c| std::str::from_utf8(c).ok()),
+    ) else {
+        return Ok(content.map(<[u8]>::to_vec));
+    };
+    let yak_id = meta
+        .as_ref()
+        .and_then(|meta| meta.get("yak_id"))
+        .and_then(|yak_id| yak_id.as_str());
+    let Some((sealed, title)) = state.seal_note(topic, yak_id, text)? else {
+        return Ok(content.map(<[u8]>::to_vec));
+    };
+    if let Some(fields) = meta.get_or_insert_with(|| json!({})).as_object_mut() {
+        fields.insert("encrypted".to_string(), true.into());
+        fields.insert("title".to_string(), title.into());
+    }
+    Ok(Some(sealed.into_bytes()))
+}
+
+/// Blanks locked notes without opening any, for readers that mustn't show locked yaks even
+/// while they're unlocked, like the publish server. With `hide`, locked yaks are removed.
+pub(crate) fn withhold(projection: &mut Projection, hide: bool) {
+    LockState::default().redact(projection, hide);
+}
+
+pub(crate) async fn load_config(store: &Store) -> LockConfig {
+    load_setting(store, CONFIG_TOPIC).await
+}
+
+/// Tracks `yak.lock` frames, including ones arriving through sync, and which yak each note
+/// revision's content belongs to. Runs read-only too, since reading needs it.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    while let Some(frame) = rx.recv().await {
+        let state = app.state::<LockState>();
+        state.track(&frame);
+        if frame.topic != LOCK_TOPIC {
+            continue;
+        }
+        if let Some(lock) = lock_from(&frame) {
+            state
+                .locks
+                .lock()
+                .unwrap()
+                .insert(lock.yak_id.clone(), lock);
+        }
+    }
+}
+
+/// The frames that lock a yak: the lock itself, then every current note rewritten with its
+/// content sealed.
+async fn lock_requests(
+    store: &Store,
+    yak_id: &str,
+    lock: &Lock,
+    key: &[u8; KEY_LEN],
+) -> Result<Vec<AppendRequest>, String> {
+    let mut projection = Projection::from_frames(&read_all_frames(store).await);
+    if !projection.yaks.contains_key(yak_id) {
+        return Err(format!("Yak not found: {yak_id}"));
+    }
+    projection.resolve_content(store).await;
+    let lock_meta = serde_json::to_value(lock).map_err(|e| format!("Invalid lock: {e}"))?;
+    let mut requests = vec![AppendRequest {
+        topic: LOCK_TOPIC.to_string(),
+        content: String::new(),
+        meta: serde_json::from_value(lock_meta).ok(),
+    }];
+    for note in projection.current_notes(yak_id) {
+        let Some(content) = note
+            .content
+            .as_deref()
+            .filter(|content| !is_sealed(content))
+        else {
+            continue;
+        };
+        let meta = json!({
+            "yak_id": yak_id,
+            "note_id": note.id,
+            "encrypted": true,
+            "title": crate::export::note_title(note),
+        });
+        requests.push(AppendRequest {
+            topic: "note.edit".to_string(),
+            content: seal(key, yak_id, content)?,
+            meta: serde_json::from_value(meta).ok(),
+        });
+    }
+    Ok(requests)
+}
+
+/// Locks a yak with a passphrase: its notes are rewritten with their content encrypted, and
+/// reading them needs `unlock_yak` first. Revisions written before the lock stay in the log
+/// in the clear, but nothing reads them while the yak is locked: projections withhold them
+/// and content asked for by hash is refused (see `LockState::open`). Locking a yak that is
+/// already locked just forgets its key for this session.
+#[tauri::command]
+pub async fn lock_yak(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, LockState>,
+    yak_id: String,
+    passphrase: String,
+) -> Result<(), String> {
+    let existing = state.locks.lock().unwrap().get(&yak_id).cloned();
+    if let Some(lock) = existing {
+        lock.key(&passphrase)?;
+        state.keys.lock().unwrap().remove(&yak_id);
+        return Ok(());
+    }
+    if passphrase.is_empty() {
+        return Err("Passphrase can't be empty".to_string());
+    }
+
+    let _guard = shutdown::begin_write(&app)?;
+    let salt = crypto::random_salt();
+    let key = crypto::derive_key(&passphrase, &salt)?;
+    let lock = Lock {
+        yak_id: yak_id.clone(),
+        salt: crypto::to_hex(&salt),
+        verifier: crypto::to_hex(&crypto::encrypt(&key, VERIFIER)?),
+    };
+    let requests = lock_requests(&store, &yak_id, &lock, &key).await?;
+    let frames = append_batch_to_store(&store, requests).await?;
+    state.locks.lock().unwrap().insert(yak_id, lock);
+    windows::emit_frames(&app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))?;
+    Ok(())
+}
+
+/// Unlocks a yak for the rest of this session.
+#[tauri::command]
+pub fn unlock_yak(
+    state: State<'_, LockState>,
+    yak_id: String,
+    passphrase: String,
+) -> Result<(), String> {
+    let lock = state
+        .locks
+        .lock()
+        .unwrap()
+        .get(&yak_id)
+        .cloned()
+        .ok_or_else(|| format!("Yak {yak_id} isn't locked"))?;
+    let key = lock.key(&passphrase)?;
+    state.keys.lock().unwrap().insert(yak_id, key);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn configure_locks(
+    store: State<'_, Store>,
+    hide_locked: bool,
+) -> Result<LockConfig, String> {
+    let config = LockConfig { hide_locked };
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_lock_seals_notes_until_unlocked() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let plain = append_frame(
+            &store,
+            "note.create",
+            Some(b"# Diary\ndear diary".as_slice()),
+            Some(json!({ "yak_id": yak_id })),
+        )
+        .await
+        .unwrap();
+        let before = Projection::from_frames(&read_all_frames(&store).await);
+
+        let salt = crypto::random_salt();
+        let key = crypto::derive_key("hunter2", &salt).unwrap();
+        let lock = Lock {
+            yak_id: yak_id.clone(),
+            salt: crypto::to_hex(&salt),
+            verifier: crypto::to_hex(&crypto::encrypt(&key, VERIFIER).unwrap()),
+        };
+        let requests = lock_requests(&store, &yak_id, &lock, &key).await.unwrap();
+        assert_eq!(requests.len(), 2);
+        append_batch_to_store(&store, requests).await.unwrap();
+
+        let state = LockState::default();
+        state
+            .locks
+            .lock()
+            .unwrap()
+            .insert(yak_id.clone(), lock.clone());
+        assert!(lock.key("wrong").is_err());
+
+        let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+        assert!(projection.yaks[&yak_id].locked);
+        projection.resolve_content(&store).await;
+        let mut locked = projection.clone();
+        state.redact(&mut locked, false);
+        let note = locked.current_notes(&yak_id)[0];
+        assert_eq!(note.content, None);
+        assert_eq!(crate::export::note_title(note), "Diary");
+
+        let mut hidden = projection.clone();
+        state.redact(&mut hidden, true);
+        assert!(hidden.yaks.is_empty() && hidden.notes.is_empty());
+
+        // The revision from before the lock is still in the log, but isn't given out
+        for frame in read_all_frames(&store).await {
+            state.track(&frame);
+        }
+        let hash = plain.hash.clone().unwrap().to_string();
+        assert!(state.open(&hash, "# Diary\ndear diary").is_err());
+        let mut before = before;
+        before.resolve_content(&store).await;
+        state.redact(&mut before, false);
+        assert_eq!(before.notes[&plain.id.to_string()].content, None);
+
+        // New notes can't be written in the clear by any writer, and are sealed once unlocked
+        state.activate();
+        let request = AppendRequest {
+            topic: "note.create".to_string(),
+            content: "more".to_string(),
+            meta: serde_json::from_value(json!({ "yak_id": yak_id })).ok(),
+        };
+        assert!(state.seal_request(request.clone()).is_err());
+        let meta = json!({ "yak_id": yak_id });
+        let plain_write = append_frame(&store, "note.create", Some(b"more"), Some(meta.clone()));
+        assert!(plain_write.await.is_err());
+        state
+            .keys
+            .lock()
+            .unwrap()
+            .insert(yak_id.clone(), lock.key("hunter2").unwrap());
+        let sealed = state.seal_request(request).unwrap();
+        assert!(is_sealed(&sealed.content));
+        assert_eq!(state.reveal(&sealed.content).unwrap(), "more");
+        let written = append_frame(&store, "note.create", Some(b"more"), Some(meta))
+            .await
+            .unwrap();
+        let content = store.cas_read(&written.hash.unwrap()).await.unwrap();
+        assert!(is_sealed(&String::from_utf8(content).unwrap()));
+
+        state.redact(&mut projection, false);
+        assert_eq!(
+            projection.current_notes(&yak_id)[0].content.as_deref(),
+            Some("# Diary\ndear diary")
+        );
+        assert!(state.open(&hash, "# Diary\ndear diary").is_ok());
+    }
+}
@@ -0,0 +1,382 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::import::{add_attachment, add_note};
+use crate::profiles;
+use crate::rates::RatesState;
+use crate::settings::{latest_setting, load_setting, save_setting};
+use crate::shutdown::begin_write;
+use crate::windows::emit_frame;
+use crate::{append_frame, health, read_all_frames};
+
+const CONFIG_TOPIC: &str = "capture.mail";
+/// Holds the account password, in the profile's dir rather than the store.
+const SECRETS_FILE: &str = "mail-secrets.json";
+
+fn default_port() -> u16 {
+    993
+}
+
+fn default_folder() -> String {
+    "INBOX".to_string()
+}
+
+fn default_poll_minutes() -> u64 {
+    5
+}
+
+/// An IMAP mailbox whose unread messages become notes. Stored locally only; the account
+/// password is kept in `SECRETS_FILE`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MailConfig {
+    pub enabled: bool,
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub username: String,
+    /// Kept in `SECRETS_FILE`, never in the settings frame
+    #[serde(default, skip_serializing)]
+    pub password: String,
+    #[serde(default = "default_folder")]
+    pub folder: String,
+    /// The yak messages are filed into; a "Mail" yak is created if unset
+    pub yak_id: Option<String>,
+    #[serde(default = "default_poll_minutes")]
+    pub poll_minutes: u64,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: default_port(),
+            username: String::new(),
+            password: String::new(),
+            folder: default_folder(),
+            yak_id: None,
+            poll_minutes: default_poll_minutes(),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct MailNote {
+    message_id: Option<String>,
+    subject: String,
+    from: Option<String>,
+    date: Option<String>,
+    body: String,
+    attachments: Vec<(String, Vec<u8>)>,
+}
+
+fn parse_message(raw: &[u8]) -> Option<MailNote> {
+    let message = mail_parser::MessageParser::default().parse(raw)?;
+    let from = message.from().and_then(|from| from.first()).map(|addr| {
+        match (addr.name(), addr.address()) {
+            (Some(name), Some(address)) => format!("{name} <{address}>"),
+            (name, address) => name.or(address).unwrap_or_default().to_string(),
+        }
+    });
+    let attachments = message
+        .attachments()
+        .map(|part| {
+            let name = part.attachment_name().unwrap_or("attachment").to_string();
+            (name, part.contents().to_vec())
+        })
+        .collect();
+    Some(MailNote {
+        message_id: message.message_id().map(String::from),
+        subject: message.subject().unwrap_or("(no subject)").to_string(),
+        from,
+        date: message.date().map(|date| date.to_rfc3339()),
+        body: message
+            .body_text(0)
+            .map(|body| body.trim().to_string())
+            .unwrap_or_default(),
+        attachments,
+    })
+}
+
+type ImapSession = imap::Session<native_tls::TlsStream<std::net::TcpStream>>;
+
+fn connect(config: &MailConfig) -> Result<ImapSession, String> {
+    let tls = native_tls::TlsConnector::new().map_err(|e| format!("TLS error: {e}"))?;
+    let client = imap::connect((config.host.as_str(), config.port), &config.host, &tls)
+        .map_err(|e| format!("Failed to connect to {}: {e}", config.host))?;
+    let mut session = client
+        .login(&config.username, &config.password)
+        .map_err(|(e, _)| format!("Failed to log in: {e}"))?;
+    session
+        .select(&config.folder)
+        .map_err(|e| format!("Failed to open {}: {e}", config.folder))?;
+    Ok(session)
+}
+
+/// Fetches the unread messages in the configured folder, by UID. Fetching with `BODY.PEEK`
+/// leaves them unread until they've been stored.
+fn fetch_unseen(config: &MailConfig) -> Result<Vec<(u32, Vec<u8>)>, String> {
+    let mut session = connect(config)?;
+    let uids = session
+        .uid_search("UNSEEN")
+        .map_err(|e| format!("Failed to search mailbox: {e}"))?;
+    let mut messages = Vec::new();
+    if !uids.is_empty() {
+        let set = uids
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let fetches = session
+            .uid_fetch(&set, "(UID BODY.PEEK[])")
+            .map_err(|e| format!("Failed to fetch messages: {e}"))?;
+        for fetch in fetches.iter() {
+            if let (Some(uid), Some(body)) = (fetch.uid, fetch.body()) {
+                messages.push((uid, body.to_vec()));
+            }
+        }
+    }
+    let _ = session.logout();
+    messages.sort_by_key(|(uid, _)| *uid);
+    Ok(messages)
+}
+
+fn mark_processed(config: &MailConfig, uids: &[u32]) -> Result<(), String> {
+    if uids.is_empty() {
+        return Ok(());
+    }
+    let mut session = connect(config)?;
+    let set = uids
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+    session
+        .uid_store(&set, "+FLAGS (\\Seen)")
+        .map_err(|e| format!("Failed to mark messages read: {e}"))?;
+    let _ = session.logout();
+    Ok(())
+}
+
+/// Message-IDs already filed as notes, in case marking them read failed last time.
+async fn stored_message_ids(store: &Store) -> HashSet<String> {
+    read_all_frames(store)
+        .await
+        .iter()
+        .filter(|frame| frame.topic == "note.create")
+        .filter_map(|frame| frame.meta.as_ref()?.get("message_id")?.as_str())
+        .map(String::from)
+        .collect()
+}
+
+/// Files unread messages as notes (with their attachments) and marks them read.
+async fn check(app: &AppHandle, store: &Store, config: &MailConfig) -> Result<usize, String> {
+    let yak_id = config
+        .yak_id
+        .as_deref()
+        .ok_or_else(|| "Mail capture has no yak configured".to_string())?;
+    let yak_id = crate::inbox::capture_yak(store, yak_id).await;
+    let fetch_config = config.clone();
+    let messages = tokio::task::spawn_blocking(move || fetch_unseen(&fetch_config))
+        .await
+        .map_err(|e| format!("Mail check failed: {e}"))??;
+
+    let _guard = begin_write(app)?;
+    let mut seen = stored_message_ids(store).await;
+    let mut processed = Vec::new();
+    let mut added = 0;
+    for (uid, raw) in messages {
+        processed.push(uid);
+        let Some(mail) = parse_message(&raw) else {
+            continue;
+        };
+        if let Some(id) = &mail.message_id {
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+        }
+
+        let content = format!("# {}\n\n{}", mail.subject, mail.body);
+        let meta = serde_json::json!({
+            "source": "mail",
+            "message_id": mail.message_id,
+            "from": mail.from,
+            "subject": mail.subject,
+            "date": mail.date,
+        });
+        let note = add_note(store, &yak_id, &content, meta).await?;
+        let _ = emit_frame(app, &note);
+        let note_id = note.id.to_string();
+        for (name, bytes) in &mail.attachments {
+            let frame =
+                add_attachment(store, &yak_id, &note_id, name, bytes, serde_json::json!({}))
+                    .await?;
+            let _ = emit_frame(app, &frame);
+        }
+        added += 1;
+    }
+
+    let mark_config = config.clone();
+    tokio::task::spawn_blocking(move || mark_processed(&mark_config, &processed))
+        .await
+        .map_err(|e| format!("Mail check failed: {e}"))??;
+    Ok(added)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MailSecrets {
+    password: String,
+}
+
+fn secrets_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(profiles::data_dir(app)?.join(SECRETS_FILE))
+}
+
+fn load_secrets(path: &Path) -> MailSecrets {
+    std::fs::read(path)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_secrets(path: &Path, config: &MailConfig) -> Result<(), String> {
+    let secrets = MailSecrets {
+        password: config.password.clone(),
+    };
+    let json = serde_json::to_vec(&secrets).map_err(|e| e.to_string())?;
+    profiles::write_private(path, &json).map_err(|e| format!("Failed to save mail password: {e}"))
+}
+
+/// The mailbox config with its password. A password saved in the frame by an earlier version
+/// is moved out, and the frame saved again without it.
+async fn load_config(app: &AppHandle, store: &Store) -> Result<MailConfig, String> {
+    let mut config: MailConfig = load_setting(store, CONFIG_TOPIC).await;
+    let path = secrets_path(app)?;
+    if config.password.is_empty() {
+        config.password = load_secrets(&path).password;
+    } else {
+        save_secrets(&path, &config)?;
+        save_setting(store, CONFIG_TOPIC, &config)?;
+    }
+    Ok(config)
+}
+
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    loop {
+        let config = match load_config(&app, &store).await {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Failed to load mail config: {e}");
+                MailConfig::default()
+            }
+        };
+        if config.enabled && !app.state::<RatesState>().is_paused("mail") {
+            match check(&app, &store, &config).await {
+                Ok(_) => health::ok(&app, "mail"),
+                Err(e) => {
+                    health::error(&app, "mail", &e);
+                    eprintln!("Failed to check mail: {e}");
+                }
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(config.poll_minutes.max(1) * 60)).await;
+    }
+}
+
+/// Stops a forked store checking this mailbox too.
+pub(crate) fn detach(store: &Store, frames: &[Frame]) -> Result<(), String> {
+    let config: MailConfig = latest_setting(frames, CONFIG_TOPIC);
+    if config.enabled {
+        let config = MailConfig {
+            enabled: false,
+            ..config
+        };
+        save_setting(store, CONFIG_TOPIC, &config)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn configure_mail(
+    store: State<'_, Store>,
+    app: AppHandle,
+    mut config: MailConfig,
+) -> Result<MailConfig, String> {
+    if config.yak_id.is_none() {
+        let yak = append_frame(
+            &store,
+            "yak.create",
+            None,
+            Some(serde_json::json!({ "name": "Mail" })),
+        )
+        .await?;
+        config.yak_id = Some(yak.id.to_string());
+    }
+    save_secrets(&secrets_path(&app)?, &config)?;
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(config)
+}
+
+/// Checks the mailbox immediately, returning how many messages were filed.
+#[tauri::command]
+pub async fn check_mail_now(store: State<'_, Store>, app: AppHandle) -> Result<usize, String> {
+    let config = load_config(&app, &store).await?;
+    if !config.enabled {
+        return Err("Mail capture is not enabled".to_string());
+    }
+    check(&app, &store, &config).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message() {
+        let raw = b"From: Ada <ada@example.com>\r\n\
+Subject: Read this\r\n\
+Message-ID: <1@example.com>\r\n\
+Date: Tue, 10 Jun 2025 04:00:00 +0000\r\n\
+Content-Type: multipart/mixed; boundary=\"b\"\r\n\
+\r\n\
+--b\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+Hello there\r\n\
+--b\r\n\
+Content-Type: text/plain\r\n\
+Content-Disposition: attachment; filename=\"notes.txt\"\r\n\
+\r\n\
+attached\r\n\
+--b--\r\n";
+        let mail = parse_message(raw).unwrap();
+        assert_eq!(mail.subject, "Read this");
+        assert_eq!(mail.from.as_deref(), Some("Ada <ada@example.com>"));
+        assert_eq!(mail.message_id.as_deref(), Some("1@example.com"));
+        assert_eq!(mail.body, "Hello there");
+        assert_eq!(mail.attachments.len(), 1);
+        assert_eq!(mail.attachments[0].0, "notes.txt");
+    }
+
+    #[tokio::test]
+    async fn test_password_stays_out_of_the_store() {
+        let (dir, store) = crate::testing::store();
+        let config = MailConfig {
+            username: "ada".to_string(),
+            password: "hunter2".to_string(),
+            ..Default::default()
+        };
+        save_setting(&store, CONFIG_TOPIC, &config).unwrap();
+        let path = dir.path().join(SECRETS_FILE);
+        save_secrets(&path, &config).unwrap();
+
+        let saved: MailConfig = load_setting(&store, CONFIG_TOPIC).await;
+        assert_eq!(saved.username, "ada");
+        assert!(saved.password.is_empty());
+        assert_eq!(load_secrets(&path).password, "hunter2");
+    }
+}
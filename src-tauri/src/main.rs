@@ -2,5 +2,19 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    if std::env::args().any(|arg| arg == "--mcp") {
+        if let Err(e) = yaks_lib::mcp::run_stdio_bridge() {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if std::env::args().nth(1).as_deref() == Some("append") {
+        if let Err(e) = yaks_lib::cli::run_append(std::env::args().skip(2).collect()) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
     yaks_lib::run()
 }
@@ -0,0 +1,436 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use xs::store::{Frame, Store};
+
+use crate::export::note_title;
+use crate::import::add_note;
+use crate::locks::LockState;
+use crate::permissions::{self, Integration};
+use crate::profiles;
+use crate::projection::Projection;
+use crate::provenance::{self, Source};
+use crate::read_all_frames;
+use crate::search::{keyword_search, Match};
+use crate::settings::{latest_setting, save_setting};
+use crate::shutdown;
+use crate::windows::emit_frame;
+
+const CONFIG_TOPIC: &str = "mcp.config";
+const PROTOCOL_VERSION: &str = "2024-11-05";
+const SOCKET_NAME: &str = "mcp.sock";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct McpConfig {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct McpStatus {
+    pub enabled: bool,
+    /// The local socket clients connect to, directly or through `yaks --mcp`
+    pub socket: String,
+}
+
+#[derive(Default)]
+pub struct McpState {
+    server: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// Where the server listens: in the running profile's data dir, so each profile has its own.
+fn socket_path(profile_dir: &Path) -> PathBuf {
+    profile_dir.join(SOCKET_NAME)
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "list_yaks",
+            "description": "List the yaks (notebooks) with their ids and note counts.",
+            "inputSchema": { "type": "object", "properties": {} }
+        },
+        {
+            "name": "search_notes",
+            "description": "Find notes containing all of the given words, best matches first.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "yak_id": { "type": "string", "description": "Only search this yak" },
+                    "limit": { "type": "integer", "default": 20 }
+                },
+                "required": ["query"]
+            }
+        },
+        {
+            "name": "read_note",
+            "description": "Read the current content and tags of a note.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "note_id": { "type": "string" } },
+                "required": ["note_id"]
+            }
+        },
+        {
+            "name": "append_note",
+            "description": "Add a new markdown note to a yak.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "yak_id": { "type": "string" },
+                    "content": { "type": "string" }
+                },
+                "required": ["yak_id", "content"]
+            }
+        }
+    ])
+}
+
+struct Server {
+    store: Store,
+    app: Option<AppHandle>,
+}
+
+fn snippet(content: &str, word: &str) -> String {
+    // Lowercasing can shift byte offsets, so fall back to the start if the match isn't on a
+    // character boundary of the original
+    let start = content
+        .to_lowercase()
+        .find(word)
+        .filter(|&i| content.is_char_boundary(i))
+        .unwrap_or(0);
+    let start = content[..start]
+        .char_indices()
+        .rev()
+        .nth(40)
+        .map_or(0, |(i, _)| i);
+    let snippet: String = content[start..].chars().take(160).collect();
+    snippet.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn string_arg<'a>(arguments: &'a Value, key: &str) -> Result<&'a str, String> {
+    arguments
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("Missing argument: {key}"))
+}
+
+impl Server {
+    /// The current notes, without yaks that are locked this session.
+    async fn projection(&self) -> Projection {
+        let frames = read_all_frames(&self.store).await;
+        let mut projection = Projection::from_frames(&frames).into_current();
+        projection.resolve_content(&self.store).await;
+        match &self.app {
+            Some(app) => app.state::<LockState>().redact(&mut projection, true),
+            None => LockState::default().redact(&mut projection, true),
+        }
+        projection
+    }
+
+    async fn call_tool(&self, name: &str, arguments: &Value) -> Result<Value, String> {
+        match name {
+            "list_yaks" => {
+                crate::app_lock::ensure_unlocked()?;
+                permissions::check_read(Integration::Mcp, None, None)?;
+                let projection = self.projection().await;
+                Ok(projection
+                    .yaks
+                    .values()
+                    .filter(|yak| permissions::allows_yak(Integration::Mcp, &yak.id))
+                    .map(|yak| {
+                        json!({
+                            "id": yak.id,
+                            "name": yak.name,
+                            "notes": projection.current_notes(&yak.id).len(),
+                        })
+                    })
+                    .collect())
+            }
+            "search_notes" => {
+                let query = string_arg(arguments, "query")?.to_lowercase();
+                let yak_id = arguments.get("yak_id").and_then(Value::as_str);
+                let limit = arguments.get("limit").and_then(Value::as_u64).unwrap_or(20);
+                let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+                crate::app_lock::ensure_unlocked()?;
+                permissions::check_read(Integration::Mcp, yak_id, None)?;
+                let mut projection = self.projection().await;
+                projection
+                    .notes_by_yak
+                    .retain(|yak_id, _| permissions::allows_yak(Integration::Mcp, yak_id));
+                let first_word = query.split_whitespace().next().unwrap_or_default();
+                Ok(
+                    keyword_search(&projection, &query, yak_id, Match::All, limit)
+                        .into_iter()
+                        .map(|note| {
+                            let content = note.content.as_deref().unwrap_or_default();
+                            json!({
+                                "id": note.id,
+                                "yak_id": note.yak_id,
+                                "title": note_title(note),
+                                "snippet": snippet(content, first_word),
+                            })
+                        })
+                        .collect(),
+                )
+            }
+            "read_note" => {
+                let note_id = string_arg(arguments, "note_id")?;
+                crate::app_lock::ensure_unlocked()?;
+                let projection = self.projection().await;
+                let note = projection
+                    .notes
+                    .get(&projection.resolve(note_id))
+                    .ok_or_else(|| format!("Note not found: {note_id}"))?;
+                permissions::check_read(Integration::Mcp, Some(&note.yak_id), Some(&note.id))?;
+                Ok(json!({
+                    "id": note.id,
+                    "yak_id": note.yak_id,
+                    "title": note_title(note),
+                    "tags": note.tags,
+                    "content": note.content,
+                }))
+            }
+            "append_note" => {
+                let yak_id = string_arg(arguments, "yak_id")?;
+                let content = string_arg(arguments, "content")?;
+                let frames = read_all_frames(&self.store).await;
+                if !frames
+                    .iter()
+                    .any(|frame| frame.topic == "yak.create" && frame.id.to_string() == yak_id)
+                {
+                    return Err(format!("Yak not found: {yak_id}"));
+                }
+                let _guard = self.app.as_ref().map(shutdown::begin_write).transpose()?;
+                let note = add_note(&self.store, yak_id, content, json!({ "source": "mcp" }));
+                let frame = provenance::scope(Source::Cli, note).await?;
+                if let Some(app) = &self.app {
+                    let _ = emit_frame(app, &frame);
+                }
+                Ok(json!({ "id": frame.id.to_string() }))
+            }
+            _ => Err(format!("Unknown tool: {name}")),
+        }
+    }
+
+    /// Handles one JSON-RPC message, returning the response (notifications get none).
+    async fn handle(&self, message: &Value) -> Option<Value> {
+        let id = message.get("id")?.clone();
+        let method = message
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "yaks", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "ping" => json!({}),
+            "tools/list" => json!({ "tools": tools() }),
+            "tools/call" => {
+                let name = params
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+                let called = permissions::scope(Integration::Mcp, self.call_tool(name, &arguments));
+                match called.await {
+                    Ok(value) => json!({
+                        "content": [{ "type": "text", "text": value.to_string() }],
+                        "isError": false,
+                    }),
+                    Err(e) => json!({
+                        "content": [{ "type": "text", "text": e }],
+                        "isError": true,
+                    }),
+                }
+            }
+            _ => {
+                return Some(json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": format!("Method not found: {method}") },
+                }))
+            }
+        };
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+}
+
+/// Serves newline-delimited JSON-RPC on a Unix socket.
+#[cfg(unix)]
+async fn serve(server: std::sync::Arc<Server>, path: PathBuf) -> Result<(), String> {
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path)
+        .map_err(|e| format!("Failed to listen on {}: {e}", path.display()))?;
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| format!("MCP accept failed: {e}"))?;
+        let server = server.clone();
+        tokio::spawn(async move {
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let response = match serde_json::from_str::<Value>(&line) {
+                    Ok(message) => server.handle(&message).await,
+                    Err(e) => Some(json!({
+                        "jsonrpc": "2.0",
+                        "id": null,
+                        "error": { "code": -32700, "message": format!("Parse error: {e}") },
+                    })),
+                };
+                if let Some(response) = response {
+                    let mut out = response.to_string();
+                    out.push('\n');
+                    if write.write_all(out.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+async fn serve(_server: std::sync::Arc<Server>, _path: PathBuf) -> Result<(), String> {
+    Err("The MCP server is only available on macOS and Linux".to_string())
+}
+
+async fn apply_config(app: &AppHandle, store: &Store, state: &McpState, config: &McpConfig) {
+    let mut server = state.server.lock().await;
+    if let Some(handle) = server.take() {
+        handle.abort();
+    }
+    let path = match profiles::data_dir(app) {
+        Ok(dir) => socket_path(&dir),
+        Err(e) => {
+            eprintln!("MCP server disabled: {e}");
+            return;
+        }
+    };
+    if config.enabled {
+        let mcp = std::sync::Arc::new(Server {
+            store: store.clone(),
+            app: Some(app.clone()),
+        });
+        *server = Some(tokio::spawn(async move {
+            if let Err(e) = serve(mcp, path).await {
+                eprintln!("{e}");
+            }
+        }));
+    } else {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+pub(crate) async fn initialize(app: &AppHandle, store: &Store, frames: &[Frame]) {
+    let config: McpConfig = latest_setting(frames, CONFIG_TOPIC);
+    apply_config(app, store, &app.state::<McpState>(), &config).await;
+}
+
+/// Starts or stops the MCP server, which gives MCP clients tools to list yaks, search and
+/// read notes, and append new ones.
+#[tauri::command]
+pub async fn enable_mcp_server(
+    store: State<'_, Store>,
+    state: State<'_, McpState>,
+    app: AppHandle,
+    enabled: bool,
+) -> Result<McpStatus, String> {
+    let config = McpConfig { enabled };
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    apply_config(&app, &store, &state, &config).await;
+    Ok(McpStatus {
+        enabled,
+        socket: socket_path(&profiles::data_dir(&app)?)
+            .to_string_lossy()
+            .into_owned(),
+    })
+}
+
+/// Relays stdin/stdout to the running app's MCP socket, for clients that only speak stdio:
+/// configure them to launch `yaks --mcp`, with `--profile <name>` for a profile other than
+/// the active one.
+pub fn run_stdio_bridge() -> Result<(), String> {
+    // The same app data dir Tauri derives, as there's no `AppHandle` here
+    let app_data_dir = dirs::data_dir()
+        .ok_or("No data directory")?
+        .join("stream.cross.yaks");
+    let path = socket_path(&profiles::resolve_dir(&app_data_dir, std::env::args()));
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    runtime.block_on(bridge(&path))
+}
+
+#[cfg(unix)]
+async fn bridge(path: &Path) -> Result<(), String> {
+    let stream = tokio::net::UnixStream::connect(path)
+        .await
+        .map_err(|e| format!("Is yaks running with the MCP server enabled? {e}"))?;
+    let (mut read, mut write) = stream.into_split();
+    let mut stdin = tokio::io::stdin();
+    let mut stdout = tokio::io::stdout();
+    tokio::select! {
+        result = tokio::io::copy(&mut stdin, &mut write) => result.map(|_| ()),
+        result = tokio::io::copy(&mut read, &mut stdout) => result.map(|_| ()),
+    }
+    .map_err(|e| format!("MCP bridge failed: {e}"))
+}
+
+#[cfg(not(unix))]
+async fn bridge(_path: &Path) -> Result<(), String> {
+    Err("The MCP server is only available on macOS and Linux".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::create_yak;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn test_search_and_append() {
+        let _gate = crate::app_lock::TEST_GATE.lock().await;
+        let (_dir, store) = testing::store();
+        let yak_id = create_yak(&store, "Work").await.unwrap();
+        let server = Server {
+            store: store.clone(),
+            app: None,
+        };
+
+        let call = |name: &str, arguments: Value| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": arguments },
+            })
+        };
+        let response = server
+            .handle(&call(
+                "append_note",
+                json!({ "yak_id": yak_id, "content": "# Plan\nShip the MCP server" }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response["result"]["isError"], false);
+
+        let response = server
+            .handle(&call("search_notes", json!({ "query": "mcp ship" })))
+            .await
+            .unwrap();
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        let results: Value = serde_json::from_str(text).unwrap();
+        assert_eq!(results[0]["title"], "Plan");
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert!(server.handle(&notification).await.is_none());
+    }
+}
@@ -0,0 +1,384 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::audio::{append_audio, prepare};
+use crate::extract::Segment;
+use crate::import::add_note;
+use crate::shutdown::begin_write;
+use crate::windows::emit_frame;
+use crate::{append_frame, read_all_frames, AppendRequest};
+
+const START_TOPIC: &str = "meeting.start";
+const END_TOPIC: &str = "meeting.end";
+/// Written once a meeting's recording is transcribed, pairing its notes with what was said.
+const LINKS_TOPIC: &str = "meeting.links";
+/// Meta of notes written during a meeting: which meeting, and how far into it.
+const MEETING_KEY: &str = "meeting_id";
+const OFFSET_KEY: &str = "meeting_offset_ms";
+
+#[derive(Debug, Clone)]
+struct Active {
+    id: String,
+    yak_id: String,
+    note_id: String,
+    started_ms: u64,
+}
+
+/// The meeting under way, if any.
+#[derive(Default)]
+pub struct MeetingState {
+    active: Mutex<Option<Active>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MeetingStarted {
+    pub meeting_id: String,
+    pub note_id: String,
+    /// Whether the window should start recording
+    pub record: bool,
+}
+
+/// A recording made in the frontend, base64 encoded.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Recording {
+    pub data: String,
+    pub mime: String,
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeetingNote {
+    pub note_id: String,
+    pub offset_ms: u64,
+    /// What was said while the note was being written
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Meeting {
+    pub id: String,
+    pub title: String,
+    pub yak_id: String,
+    /// The note the meeting's recording is attached to
+    pub note_id: String,
+    pub ended: Option<String>,
+    pub recording_id: Option<String>,
+    pub notes: Vec<MeetingNote>,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+impl MeetingState {
+    /// Stamps a note written during a meeting with the meeting and its offset into it.
+    pub(crate) fn stamp(&self, request: &mut AppendRequest) {
+        if request.topic != "note.create" {
+            return;
+        }
+        let Some(active) = self.active.lock().unwrap().clone() else {
+            return;
+        };
+        let offset = (Utc::now().timestamp_millis() as u64).saturating_sub(active.started_ms);
+        let meta = request.meta.get_or_insert_with(HashMap::new);
+        meta.insert(MEETING_KEY.to_string(), active.id.into());
+        meta.insert(OFFSET_KEY.to_string(), offset.into());
+    }
+}
+
+/// Pairs each transcript segment with the first note written at or after it began, so a
+/// note collects what was said while it was being typed. Segments after the last note are
+/// left out.
+fn link(notes: &[(String, u64)], segments: &[Segment]) -> Vec<MeetingNote> {
+    let mut notes: Vec<MeetingNote> = notes
+        .iter()
+        .map(|(note_id, offset_ms)| MeetingNote {
+            note_id: note_id.clone(),
+            offset_ms: *offset_ms,
+            segments: Vec::new(),
+        })
+        .collect();
+    notes.sort_by_key(|note| note.offset_ms);
+    for segment in segments {
+        if let Some(note) = notes
+            .iter_mut()
+            .find(|note| note.offset_ms >= segment.start_ms)
+        {
+            note.segments.push(segment.clone());
+        }
+    }
+    notes
+}
+
+/// Notes stamped with `meeting_id`, and their offsets.
+fn meeting_notes(frames: &[Frame], meeting_id: &str) -> Vec<(String, u64)> {
+    frames
+        .iter()
+        .filter(|frame| {
+            frame.topic == "note.create" && meta_str(frame, MEETING_KEY) == Some(meeting_id)
+        })
+        .filter_map(|frame| {
+            let offset = frame.meta.as_ref()?.get(OFFSET_KEY)?.as_u64()?;
+            Some((frame.id.to_string(), offset))
+        })
+        .collect()
+}
+
+/// Links the notes of meetings whose recordings have been transcribed since.
+async fn link_transcripts(app: &AppHandle, store: &Store) -> Result<(), String> {
+    let frames = read_all_frames(store).await;
+    let linked: Vec<&str> = frames
+        .iter()
+        .filter(|frame| frame.topic == LINKS_TOPIC)
+        .filter_map(|frame| meta_str(frame, "attachment_id"))
+        .collect();
+    let recordings = frames.iter().filter(|frame| {
+        frame.topic == "attachment.audio" && meta_str(frame, MEETING_KEY).is_some()
+    });
+    for recording in recordings {
+        let attachment_id = recording.id.to_string();
+        if linked.contains(&attachment_id.as_str()) {
+            continue;
+        }
+        let Some(segments) = frames
+            .iter()
+            .filter(|frame| frame.topic == "text.extract")
+            .filter(|frame| meta_str(frame, "kind") == Some("transcript"))
+            .find(|frame| meta_str(frame, "attachment_id") == Some(attachment_id.as_str()))
+            .and_then(|frame| frame.meta.as_ref()?.get("segments").cloned())
+            .and_then(|segments| serde_json::from_value::<Vec<Segment>>(segments).ok())
+        else {
+            continue;
+        };
+        let meeting_id = meta_str(recording, MEETING_KEY).unwrap_or_default();
+        let links = link(&meeting_notes(&frames, meeting_id), &segments);
+        let _guard = begin_write(app)?;
+        let meta = json!({
+            "meeting_id": meeting_id,
+            "attachment_id": attachment_id,
+            "links": links,
+        });
+        let frame = append_frame(store, LINKS_TOPIC, None, Some(meta)).await?;
+        let _ = emit_frame(app, &frame);
+    }
+    Ok(())
+}
+
+/// Resumes a meeting left running when the app last quit.
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let Some(start) = frames.iter().rev().find(|frame| frame.topic == START_TOPIC) else {
+        return;
+    };
+    let id = start.id.to_string();
+    let ended = frames
+        .iter()
+        .any(|frame| frame.topic == END_TOPIC && meta_str(frame, MEETING_KEY) == Some(id.as_str()));
+    if ended {
+        return;
+    }
+    *app.state::<MeetingState>().active.lock().unwrap() = Some(Active {
+        yak_id: meta_str(start, "yak_id").unwrap_or_default().to_string(),
+        note_id: meta_str(start, "note_id").unwrap_or_default().to_string(),
+        started_ms: start.id.timestamp(),
+        id,
+    });
+}
+
+/// Links meeting notes to transcripts as they arrive.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    let mut caught_up = false;
+    while let Some(frame) = rx.recv().await {
+        if frame.topic == "xs.threshold" {
+            caught_up = true;
+        } else if !caught_up || frame.topic != "text.extract" {
+            continue;
+        }
+        if let Err(e) = link_transcripts(&app, &store).await {
+            eprintln!("Failed to link meeting transcripts: {e}");
+        }
+    }
+}
+
+/// Starts a meeting in `yak_id` with a note titled `title`. Notes created until
+/// `end_meeting` are stamped with how far into the meeting they were written. With
+/// `record`, windows are asked to start recording through "meeting-started".
+#[tauri::command]
+pub async fn start_meeting(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, MeetingState>,
+    yak_id: String,
+    title: String,
+    record: Option<bool>,
+) -> Result<MeetingStarted, String> {
+    if state.active.lock().unwrap().is_some() {
+        return Err("A meeting is already under way".to_string());
+    }
+    let _guard = begin_write(&app)?;
+    let record = record.unwrap_or(false);
+    let note = add_note(&store, &yak_id, &format!("# {title}\n"), json!({})).await?;
+    let _ = emit_frame(&app, &note);
+    let meta = json!({
+        "yak_id": yak_id,
+        "note_id": note.id.to_string(),
+        "title": title,
+        "record": record,
+    });
+    let frame = append_frame(&store, START_TOPIC, None, Some(meta)).await?;
+    let _ = emit_frame(&app, &frame);
+
+    *state.active.lock().unwrap() = Some(Active {
+        id: frame.id.to_string(),
+        yak_id,
+        note_id: note.id.to_string(),
+        started_ms: frame.id.timestamp(),
+    });
+    let started = MeetingStarted {
+        meeting_id: frame.id.to_string(),
+        note_id: note.id.to_string(),
+        record,
+    };
+    if let Err(e) = app.emit("meeting-started", &started) {
+        eprintln!("Failed to emit meeting start: {e}");
+    }
+    Ok(started)
+}
+
+/// Ends the meeting under way, attaching its `recording` to the meeting's note. The
+/// recording is transcribed like any other and its notes linked to the transcript.
+#[tauri::command]
+pub async fn end_meeting(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, MeetingState>,
+    recording: Option<Recording>,
+) -> Result<String, String> {
+    let Some(active) = state.active.lock().unwrap().clone() else {
+        return Err("No meeting is under way".to_string());
+    };
+    let encoded = match recording {
+        Some(recording) => {
+            Some(prepare(&recording.data, recording.mime, recording.duration_ms).await?)
+        }
+        None => None,
+    };
+    let _guard = begin_write(&app)?;
+    if let Some(encoded) = encoded {
+        let extra = json!({ MEETING_KEY: active.id });
+        let frame = append_audio(&store, &active.yak_id, &active.note_id, encoded, extra).await?;
+        let _ = emit_frame(&app, &frame);
+    }
+    let frame = append_frame(
+        &store,
+        END_TOPIC,
+        None,
+        Some(json!({ MEETING_KEY: active.id })),
+    )
+    .await?;
+    let _ = emit_frame(&app, &frame);
+    *state.active.lock().unwrap() = None;
+    if let Err(e) = app.emit("meeting-ended", &active.id) {
+        eprintln!("Failed to emit meeting end: {e}");
+    }
+    Ok(active.id)
+}
+
+/// A meeting with its notes in the order they were written, each with the transcript
+/// segments linked to it once the recording has been transcribed.
+#[tauri::command]
+pub async fn get_meeting(store: State<'_, Store>, meeting_id: String) -> Result<Meeting, String> {
+    let frames = read_all_frames(&store).await;
+    let start = frames
+        .iter()
+        .find(|frame| frame.topic == START_TOPIC && frame.id.to_string() == meeting_id)
+        .ok_or_else(|| format!("Meeting not found: {meeting_id}"))?;
+    let of_meeting = |frame: &&Frame| meta_str(frame, MEETING_KEY) == Some(meeting_id.as_str());
+    let ended = frames
+        .iter()
+        .filter(of_meeting)
+        .find(|frame| frame.topic == END_TOPIC)
+        .map(|frame| frame.id.to_string());
+    let recording_id = frames
+        .iter()
+        .filter(of_meeting)
+        .find(|frame| frame.topic == "attachment.audio")
+        .map(|frame| frame.id.to_string());
+    let linked = frames
+        .iter()
+        .rev()
+        .filter(of_meeting)
+        .find(|frame| frame.topic == LINKS_TOPIC)
+        .and_then(|frame| frame.meta.as_ref()?.get("links").cloned())
+        .and_then(|links| serde_json::from_value(links).ok());
+    let notes = linked.unwrap_or_else(|| link(&meeting_notes(&frames, &meeting_id), &[]));
+    Ok(Meeting {
+        id: meeting_id.clone(),
+        title: meta_str(start, "title").unwrap_or_default().to_string(),
+        yak_id: meta_str(start, "yak_id").unwrap_or_default().to_string(),
+        note_id: meta_str(start, "note_id").unwrap_or_default().to_string(),
+        ended,
+        recording_id,
+        notes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start_ms: u64, end_ms: u64, text: &str) -> Segment {
+        Segment {
+            start_ms,
+            end_ms,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_link() {
+        let notes = vec![("b".to_string(), 30_000), ("a".to_string(), 10_000)];
+        let segments = vec![
+            segment(0, 4_000, "welcome"),
+            segment(4_000, 12_000, "budget is tight"),
+            segment(12_000, 25_000, "ship friday"),
+            segment(40_000, 45_000, "thanks all"),
+        ];
+        let linked = link(&notes, &segments);
+        assert_eq!(linked[0].note_id, "a");
+        let texts = |note: &MeetingNote| -> Vec<String> {
+            note.segments.iter().map(|s| s.text.clone()).collect()
+        };
+        assert_eq!(texts(&linked[0]), vec!["welcome", "budget is tight"]);
+        assert_eq!(texts(&linked[1]), vec!["ship friday"]);
+    }
+
+    #[test]
+    fn test_stamp() {
+        let state = MeetingState::default();
+        let mut request = AppendRequest {
+            topic: "note.create".to_string(),
+            content: "hi".to_string(),
+            meta: None,
+        };
+        state.stamp(&mut request);
+        assert!(request.meta.is_none());
+
+        *state.active.lock().unwrap() = Some(Active {
+            id: "m".to_string(),
+            yak_id: "y".to_string(),
+            note_id: "n".to_string(),
+            started_ms: Utc::now().timestamp_millis() as u64 - 5_000,
+        });
+        state.stamp(&mut request);
+        let meta = request.meta.unwrap();
+        assert_eq!(meta[MEETING_KEY], "m");
+        assert!(meta[OFFSET_KEY].as_u64().unwrap() >= 5_000);
+    }
+}
@@ -0,0 +1,216 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, OnceLock};
+use tauri::State;
+use xs::store::Store;
+
+use crate::cache::CasCache;
+use crate::export::note_title;
+use crate::locale::LocaleState;
+use crate::projection::Projection;
+use crate::read_all_frames;
+
+/// Topics whose content is parsed for `@mentions` when appended.
+pub(crate) const TOPICS: &[&str] = &["note.create", "note.edit", "task.create", "task.update"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Person {
+    pub name: String,
+    pub notes: usize,
+    pub tasks: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MentionedNote {
+    pub note_id: String,
+    pub yak_id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MentionedTask {
+    pub task_id: String,
+    pub yak_id: String,
+    pub note_id: Option<String>,
+    pub text: String,
+    pub done: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Mentions {
+    pub notes: Vec<MentionedNote>,
+    pub tasks: Vec<MentionedTask>,
+}
+
+/// The people `@mentioned` in some text, lowercased and deduplicated. Email addresses and
+/// handles inside words don't count.
+pub(crate) fn extract(content: &str) -> Vec<String> {
+    static MENTION: OnceLock<Regex> = OnceLock::new();
+    let mention = MENTION.get_or_init(|| Regex::new(r"(?:^|[^\w@./])@([A-Za-z][\w.-]*)").unwrap());
+    let people: BTreeSet<String> = mention
+        .captures_iter(content)
+        .map(|captures| captures[1].trim_end_matches(['.', '-']).to_lowercase())
+        .collect();
+    people.into_iter().collect()
+}
+
+/// Mentions recorded in meta at append time; frames from before that (or from imports)
+/// are parsed from their content instead.
+fn recorded(meta: Option<&serde_json::Value>) -> Option<Vec<String>> {
+    let mentions = meta?.get("mentions")?.as_array()?;
+    Some(
+        mentions
+            .iter()
+            .filter_map(|m| m.as_str().map(String::from))
+            .collect(),
+    )
+}
+
+async fn read_text(store: &Store, cache: &CasCache, hash: Option<&ssri::Integrity>) -> String {
+    let Some(hash) = hash else {
+        return String::new();
+    };
+    match cache.read(store, &hash.to_string()).await {
+        Ok(content) => content.to_string(),
+        Err(e) => {
+            eprintln!("Failed to read {hash}: {e}");
+            String::new()
+        }
+    }
+}
+
+/// Who each current note and task mentions, by person.
+async fn index(store: &Store, cache: &CasCache) -> BTreeMap<String, Mentions> {
+    let projection = Projection::from_frames(&read_all_frames(store).await).into_current();
+    let mut people: BTreeMap<String, Mentions> = BTreeMap::new();
+    for note in projection.notes.values() {
+        let mut note = note.clone();
+        let mentions = match recorded(note.meta.as_ref()) {
+            Some(mentions) => mentions,
+            None => {
+                let text = read_text(store, cache, note.hash.as_ref()).await;
+                let mentions = extract(&text);
+                note.content = Some(text);
+                mentions
+            }
+        };
+        if mentions.is_empty() {
+            continue;
+        }
+        if note.content.is_none() {
+            // For the title
+            note.content = Some(read_text(store, cache, note.hash.as_ref()).await);
+        }
+        for person in mentions {
+            people.entry(person).or_default().notes.push(MentionedNote {
+                note_id: note.id.clone(),
+                yak_id: note.yak_id.clone(),
+                title: note_title(&note),
+            });
+        }
+    }
+    for task in projection.tasks.values() {
+        let text = read_text(store, cache, task.hash.as_ref()).await;
+        let mentions = recorded(task.meta.as_ref()).unwrap_or_else(|| extract(&text));
+        for person in mentions {
+            people.entry(person).or_default().tasks.push(MentionedTask {
+                task_id: task.id.clone(),
+                yak_id: task.yak_id.clone(),
+                note_id: task.note_id.clone(),
+                text: text.clone(),
+                done: task.done,
+            });
+        }
+    }
+    people
+}
+
+/// Everyone mentioned in current notes and tasks, most mentioned first, then alphabetically.
+#[tauri::command]
+pub async fn list_people(
+    store: State<'_, Store>,
+    cache: State<'_, Arc<CasCache>>,
+    locale: State<'_, LocaleState>,
+) -> Result<Vec<Person>, String> {
+    let people = index(&store, &cache).await;
+    let mut people: Vec<Person> = people
+        .into_iter()
+        .map(|(name, mentions)| Person {
+            name,
+            notes: mentions.notes.len(),
+            tasks: mentions.tasks.len(),
+        })
+        .collect();
+    locale
+        .get()
+        .sort_by_key(&mut people, |person| person.name.as_str());
+    people.sort_by(|a, b| (b.notes + b.tasks).cmp(&(a.notes + a.tasks)));
+    Ok(people)
+}
+
+/// The notes and tasks that mention `person`, with or without the leading `@`.
+#[tauri::command]
+pub async fn list_mentions(
+    store: State<'_, Store>,
+    cache: State<'_, Arc<CasCache>>,
+    person: String,
+) -> Result<Mentions, String> {
+    let person = person.trim().trim_start_matches('@').to_lowercase();
+    let mut people = index(&store, &cache).await;
+    let mut mentions = people.remove(&person).unwrap_or_default();
+    // Most recently written first
+    mentions.notes.sort_by(|a, b| b.note_id.cmp(&a.note_id));
+    mentions.tasks.sort_by(|a, b| b.task_id.cmp(&a.task_id));
+    Ok(mentions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_extract() {
+        assert_eq!(
+            extract("Sync with @Ana and @bo.li. Mail ana@example.com, cc @ana"),
+            vec!["ana", "bo.li"]
+        );
+        assert!(extract("no mentions, just user@host").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mentions_from_content() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let cache = CasCache::new(1024 * 1024);
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        // Imported without mentions in meta, so they're parsed from the content
+        append_frame(
+            &store,
+            "note.create",
+            Some(b"# 1:1\nAsk @ana about the budget".as_slice()),
+            Some(json!({ "yak_id": yak_id })),
+        )
+        .await
+        .unwrap();
+        append_frame(
+            &store,
+            "task.create",
+            Some(b"Send @ana the draft".as_slice()),
+            Some(json!({ "yak_id": yak_id, "mentions": ["ana"] })),
+        )
+        .await
+        .unwrap();
+
+        let people = index(&store, &cache).await;
+        let ana = &people["ana"];
+        assert_eq!(ana.notes[0].title, "1:1");
+        assert_eq!(ana.tasks[0].text, "Send @ana the draft");
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::AppHandle;
+use tokio::sync::mpsc;
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+use yrs::updates::decoder::Decode;
+use yrs::{Doc, GetString, OffsetKind, Options, ReadTxn, StateVector, Text, Transact, Update};
+
+use crate::conflicts::{self, detect_conflicts};
+use crate::diff::{diff_tokens, tokens, Op};
+use crate::locks::is_sealed;
+use crate::shutdown::begin_write;
+use crate::windows::emit_frames;
+use crate::{prepare_frame, read_all_frames, sync};
+
+/// Meta key marking an edit appended by merging concurrent edits on its own.
+pub(crate) const MERGED_KEY: &str = "merged";
+/// Waited after synced edits arrive, so a whole pull is merged at once.
+const SETTLE: Duration = Duration::from_secs(2);
+/// The shared text in each merge document.
+const TEXT: &str = "content";
+
+/// One branch's change to the base: the base bytes `start..end` replaced with `text`.
+#[derive(Debug, Clone, PartialEq)]
+struct Change {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+impl Change {
+    /// Whether two different changes touch the same words, or insert at the same spot, so
+    /// there's no telling which should win.
+    fn overlaps(&self, other: &Change) -> bool {
+        self != other
+            && ((self.start < other.end && other.start < self.end) || self.start == other.start)
+    }
+}
+
+/// The changes turning `base` into `branch`, word by word.
+fn changes(base: &str, branch: &str) -> Vec<Change> {
+    let (old, new) = (tokens(base), tokens(branch));
+    let mut changes = Vec::new();
+    let mut open: Option<Change> = None;
+    let mut offset = 0;
+    for (op, token) in diff_tokens(&old, &new) {
+        let change = || Change {
+            start: offset,
+            end: offset,
+            text: String::new(),
+        };
+        match op {
+            Op::Equal => {
+                changes.extend(open.take());
+                offset += token.len();
+            }
+            Op::Delete => {
+                open.get_or_insert_with(change).end += token.len();
+                offset += token.len();
+            }
+            Op::Insert => open.get_or_insert_with(change).text.push_str(token),
+        }
+    }
+    changes.extend(open);
+    changes
+}
+
+fn doc(client_id: u64) -> Doc {
+    Doc::with_options(Options {
+        client_id,
+        offset_kind: OffsetKind::Bytes,
+        ..Options::default()
+    })
+}
+
+fn apply_update(doc: &Doc, update: &[u8]) -> Result<(), String> {
+    let update = Update::decode_v1(update).map_err(|e| format!("Failed to decode update: {e}"))?;
+    doc.transact_mut()
+        .apply_update(update)
+        .map_err(|e| format!("Failed to apply update: {e}"))
+}
+
+/// Replays each branch's changes on its own replica of the base and merges the replicas,
+/// as the devices would have had they shared the text as it was typed.
+fn replay(base: &str, branches: &[Vec<Change>]) -> Result<String, String> {
+    let merged = doc(1);
+    let text = merged.get_or_insert_text(TEXT);
+    text.insert(&mut merged.transact_mut(), 0, base);
+    let (base_state, base_update) = {
+        let txn = merged.transact();
+        let update = txn.encode_state_as_update_v1(&StateVector::default());
+        (txn.state_vector(), update)
+    };
+
+    let mut updates = Vec::new();
+    for (i, changes) in branches.iter().enumerate() {
+        let replica = doc(2 + i as u64);
+        let text = replica.get_or_insert_text(TEXT);
+        apply_update(&replica, &base_update)?;
+        let mut txn = replica.transact_mut();
+        // From the end, so the base offsets of earlier changes still hold
+        for change in changes.iter().rev() {
+            if change.end > change.start {
+                let len = (change.end - change.start) as u32;
+                text.remove_range(&mut txn, change.start as u32, len);
+            }
+            if !change.text.is_empty() {
+                text.insert(&mut txn, change.start as u32, &change.text);
+            }
+        }
+        updates.push(txn.encode_diff_v1(&base_state));
+    }
+    for update in &updates {
+        apply_update(&merged, update)?;
+    }
+    let txn = merged.transact();
+    Ok(text.get_string(&txn))
+}
+
+/// Merges branches edited concurrently from `base`, or `None` when two of them changed the
+/// same words differently, which is left for the user to settle.
+pub(crate) fn merge(base: &str, branches: &[&str]) -> Result<Option<String>, String> {
+    let mut seen: Vec<Change> = Vec::new();
+    let mut own: Vec<Vec<Change>> = Vec::new();
+    for branch in branches {
+        let mut changes = changes(base, branch);
+        // A change made the same way on several branches is made once
+        changes.retain(|change| !seen.contains(change));
+        if changes
+            .iter()
+            .any(|change| seen.iter().any(|other| change.overlaps(other)))
+        {
+            return Ok(None);
+        }
+        seen.extend(changes.iter().cloned());
+        own.push(changes);
+    }
+    replay(base, &own).map(Some)
+}
+
+async fn text(store: &Store, hash: Option<&ssri::Integrity>) -> Option<String> {
+    let bytes = store.cas_read(hash?).await.ok()?;
+    String::from_utf8(bytes)
+        .ok()
+        .filter(|text| !is_sealed(text))
+}
+
+/// Settles every conflict whose branches merge cleanly with an edit holding the merged text,
+/// returning the edits appended. Locked notes and true conflicts are left to
+/// `resolve_conflict`.
+pub(crate) async fn merge_conflicts(store: &Store) -> Result<Vec<Frame>, String> {
+    let frames = read_all_frames(store).await;
+    let hashes: HashMap<String, Option<&ssri::Integrity>> = frames
+        .iter()
+        .filter(|frame| frame.topic.starts_with("note."))
+        .map(|frame| (frame.id.to_string(), frame.hash.as_ref()))
+        .collect();
+
+    let mut appended = Vec::new();
+    'conflicts: for conflict in detect_conflicts(&frames) {
+        let Some(base) = text(store, hashes.get(&conflict.note_id).copied().flatten()).await else {
+            continue;
+        };
+        let mut heads = Vec::new();
+        for version in &conflict.versions {
+            let Some(head) = text(store, version.hash.as_ref()).await else {
+                continue 'conflicts;
+            };
+            heads.push(head);
+        }
+        let heads: Vec<&str> = heads.iter().map(String::as_str).collect();
+        let Some(merged) = merge(&base, &heads)? else {
+            continue;
+        };
+
+        let mut request = conflicts::resolution(&frames, &conflict, merged);
+        if let Some(meta) = request.meta.as_mut() {
+            meta.insert(MERGED_KEY.to_string(), true.into());
+        }
+        let frame = prepare_frame(store, request).await?;
+        let frame = crate::append_to_store(store, frame)
+            .map_err(|e| e.context("Failed to append frame"))?;
+        appended.push(frame);
+    }
+    Ok(appended)
+}
+
+/// Merges notes edited on more than one device as their edits sync in, and on startup for
+/// any that arrived while the app was closed.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let (kick, mut kicked) = mpsc::channel::<()>(1);
+    let follower_store = store.clone();
+    tokio::spawn(async move {
+        let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+        let mut rx = follower_store.read(read_options).await;
+        while let Some(frame) = rx.recv().await {
+            let synced_edit = frame.topic == "note.edit" && sync::origin(&frame).is_some();
+            if frame.topic == "xs.threshold" || synced_edit {
+                let _ = kick.try_send(());
+            }
+        }
+    });
+
+    while kicked.recv().await.is_some() {
+        tokio::time::sleep(SETTLE).await;
+        let _ = kicked.try_recv();
+        let Ok(_guard) = begin_write(&app) else {
+            continue;
+        };
+        match merge_conflicts(&store).await {
+            Ok(frames) if !frames.is_empty() => {
+                let _ = emit_frames(&app, &frames);
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Failed to merge concurrent edits: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let base = "# Groceries\nmilk, eggs\n\nCall the vet on Monday";
+        let laptop = "# Groceries\nmilk, eggs, bread\n\nCall the vet on Monday";
+        let phone = "# Shopping\nmilk, eggs\n\nCall the vet on Tuesday";
+        assert_eq!(
+            merge(base, &[laptop, phone]).unwrap().as_deref(),
+            Some("# Shopping\nmilk, eggs, bread\n\nCall the vet on Tuesday")
+        );
+
+        // The same change on both sides is made once
+        let both = merge(base, &[laptop, laptop]).unwrap();
+        assert_eq!(both.as_deref(), Some(laptop));
+
+        // Different changes to the same words are a true conflict
+        let desktop = "# Groceries\nmilk, eggs\n\nCall the vet on Friday";
+        assert_eq!(merge(base, &[phone, desktop]).unwrap(), None);
+    }
+}
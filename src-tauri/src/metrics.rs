@@ -0,0 +1,226 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Window over which `appends_per_sec` is averaged.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Process-wide counters, cheap enough to bump from any write or read path.
+struct Metrics {
+    started: Instant,
+    appends: AtomicU64,
+    recent_appends: Mutex<VecDeque<(Instant, u64)>>,
+    cas_bytes_written: AtomicU64,
+    cas_bytes_read: AtomicU64,
+    cas_reads: AtomicU64,
+    cas_read_micros: AtomicU64,
+    ipc_events: AtomicU64,
+    /// When the search index first fell behind the log, if it has
+    index_behind_since: Mutex<Option<Instant>>,
+}
+
+fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics {
+        started: Instant::now(),
+        appends: AtomicU64::new(0),
+        recent_appends: Mutex::new(VecDeque::new()),
+        cas_bytes_written: AtomicU64::new(0),
+        cas_bytes_read: AtomicU64::new(0),
+        cas_reads: AtomicU64::new(0),
+        cas_read_micros: AtomicU64::new(0),
+        ipc_events: AtomicU64::new(0),
+        index_behind_since: Mutex::new(None),
+    })
+}
+
+pub(crate) fn record_appends(count: u64) {
+    let metrics = metrics();
+    metrics.appends.fetch_add(count, Ordering::Relaxed);
+    let now = Instant::now();
+    let mut recent = metrics.recent_appends.lock().unwrap();
+    recent.push_back((now, count));
+    while recent
+        .front()
+        .is_some_and(|(at, _)| now.duration_since(*at) > RATE_WINDOW)
+    {
+        recent.pop_front();
+    }
+}
+
+pub(crate) fn record_cas_write(bytes: usize) {
+    metrics()
+        .cas_bytes_written
+        .fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+pub(crate) fn record_cas_read(bytes: usize, took: Duration) {
+    let metrics = metrics();
+    metrics.cas_reads.fetch_add(1, Ordering::Relaxed);
+    metrics
+        .cas_bytes_read
+        .fetch_add(bytes as u64, Ordering::Relaxed);
+    metrics
+        .cas_read_micros
+        .fetch_add(took.as_micros() as u64, Ordering::Relaxed);
+}
+
+pub(crate) fn record_ipc_events(count: u64) {
+    metrics().ipc_events.fetch_add(count, Ordering::Relaxed);
+}
+
+/// The search index has frames it hasn't caught up with.
+pub(crate) fn index_behind() {
+    metrics()
+        .index_behind_since
+        .lock()
+        .unwrap()
+        .get_or_insert_with(Instant::now);
+}
+
+pub(crate) fn index_caught_up() {
+    *metrics().index_behind_since.lock().unwrap() = None;
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub uptime_seconds: f64,
+    pub appends_total: u64,
+    pub appends_per_sec: f64,
+    pub cas_reads_total: u64,
+    pub cas_read_seconds_total: f64,
+    pub cas_read_latency_avg_ms: f64,
+    pub cas_bytes_read_total: u64,
+    pub cas_bytes_written_total: u64,
+    pub ipc_events_total: u64,
+    pub index_lag_seconds: f64,
+}
+
+pub(crate) fn snapshot() -> MetricsSnapshot {
+    let metrics = metrics();
+    let now = Instant::now();
+    let uptime = now.duration_since(metrics.started);
+    let recent: u64 = metrics
+        .recent_appends
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(at, _)| now.duration_since(*at) <= RATE_WINDOW)
+        .map(|(_, count)| count)
+        .sum();
+    let window = uptime.min(RATE_WINDOW).as_secs_f64().max(1.0);
+    let reads = metrics.cas_reads.load(Ordering::Relaxed);
+    let read_seconds = metrics.cas_read_micros.load(Ordering::Relaxed) as f64 / 1e6;
+    MetricsSnapshot {
+        uptime_seconds: uptime.as_secs_f64(),
+        appends_total: metrics.appends.load(Ordering::Relaxed),
+        appends_per_sec: recent as f64 / window,
+        cas_reads_total: reads,
+        cas_read_seconds_total: read_seconds,
+        cas_read_latency_avg_ms: if reads == 0 {
+            0.0
+        } else {
+            read_seconds * 1000.0 / reads as f64
+        },
+        cas_bytes_read_total: metrics.cas_bytes_read.load(Ordering::Relaxed),
+        cas_bytes_written_total: metrics.cas_bytes_written.load(Ordering::Relaxed),
+        ipc_events_total: metrics.ipc_events.load(Ordering::Relaxed),
+        index_lag_seconds: metrics
+            .index_behind_since
+            .lock()
+            .unwrap()
+            .map_or(0.0, |since| now.duration_since(since).as_secs_f64()),
+    }
+}
+
+/// Renders a snapshot in the Prometheus text exposition format.
+pub(crate) fn render_prometheus(snapshot: &MetricsSnapshot) -> String {
+    let series: [(&str, &str, &str, f64); 9] = [
+        (
+            "yaks_uptime_seconds",
+            "gauge",
+            "Seconds since start",
+            snapshot.uptime_seconds,
+        ),
+        (
+            "yaks_appends_total",
+            "counter",
+            "Frames appended",
+            snapshot.appends_total as f64,
+        ),
+        (
+            "yaks_cas_reads_total",
+            "counter",
+            "CAS reads that went to disk",
+            snapshot.cas_reads_total as f64,
+        ),
+        (
+            "yaks_cas_read_seconds_total",
+            "counter",
+            "Time spent in CAS reads",
+            snapshot.cas_read_seconds_total,
+        ),
+        (
+            "yaks_cas_read_bytes_total",
+            "counter",
+            "Bytes read from the CAS",
+            snapshot.cas_bytes_read_total as f64,
+        ),
+        (
+            "yaks_cas_written_bytes_total",
+            "counter",
+            "Bytes written to the CAS",
+            snapshot.cas_bytes_written_total as f64,
+        ),
+        (
+            "yaks_ipc_events_total",
+            "counter",
+            "Frame events emitted to windows",
+            snapshot.ipc_events_total as f64,
+        ),
+        (
+            "yaks_index_lag_seconds",
+            "gauge",
+            "How long the search index has been behind the log",
+            snapshot.index_lag_seconds,
+        ),
+        (
+            "yaks_appends_per_second",
+            "gauge",
+            "Appends per second over the last minute",
+            snapshot.appends_per_sec,
+        ),
+    ];
+    series
+        .iter()
+        .map(|(name, kind, help, value)| {
+            format!("# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n")
+        })
+        .collect()
+}
+
+/// Internal counters, as also served at `/metrics` by the publish server.
+#[tauri::command]
+pub fn get_metrics() -> MetricsSnapshot {
+    snapshot()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus() {
+        record_appends(3);
+        record_cas_read(10, Duration::from_millis(2));
+        let text = render_prometheus(&snapshot());
+        assert!(text.contains("# TYPE yaks_appends_total counter\n"));
+        let appends = text
+            .lines()
+            .find_map(|line| line.strip_prefix("yaks_appends_total "))
+            .unwrap();
+        assert!(appends.parse::<f64>().unwrap() >= 3.0);
+    }
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, State};
+use xs::store::{Frame, Store};
+
+use crate::projection::Projection;
+use crate::read_all_frames;
+use crate::settings::{latest_setting, save_setting};
+
+/// Records the schema version frames after it are written in.
+pub(crate) const VERSION_TOPIC: &str = "schema.version";
+
+/// One change to a topic's meta shape. History is never rewritten: frames written before
+/// the step was recorded are upgraded as they're projected.
+struct Step {
+    version: u64,
+    description: &'static str,
+    /// Upgrades a legacy frame in place given the projection so far; returns whether it
+    /// changed anything
+    upgrade: fn(&Projection, &mut Frame) -> bool,
+}
+
+const STEPS: &[Step] = &[Step {
+    version: 1,
+    description: "note.edit frames name their yak",
+    upgrade: edit_yak_id,
+}];
+
+fn edit_yak_id(projection: &Projection, frame: &mut Frame) -> bool {
+    if frame.topic != "note.edit" {
+        return false;
+    }
+    let meta = frame.meta.get_or_insert_with(|| serde_json::json!({}));
+    if meta.get("yak_id").is_some_and(|yak_id| yak_id.is_string()) {
+        return false;
+    }
+    let Some(note) = meta
+        .get("note_id")
+        .and_then(|note_id| note_id.as_str())
+        .and_then(|note_id| projection.notes.get(note_id))
+    else {
+        return false;
+    };
+    meta["yak_id"] = note.yak_id.clone().into();
+    true
+}
+
+pub(crate) fn latest_version() -> u64 {
+    STEPS.last().map_or(0, |step| step.version)
+}
+
+/// `frame` as the current projection code expects it, if it was written under an older
+/// schema version and needs upgrading.
+pub(crate) fn upgrade(projection: &Projection, frame: &Frame, written_at: u64) -> Option<Frame> {
+    let mut upgraded: Option<Frame> = None;
+    for step in STEPS.iter().filter(|step| step.version > written_at) {
+        let mut candidate = upgraded.clone().unwrap_or_else(|| frame.clone());
+        if (step.upgrade)(projection, &mut candidate) {
+            upgraded = Some(candidate);
+        }
+    }
+    upgraded
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct SchemaVersion {
+    version: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationProgress {
+    pub version: u64,
+    pub description: String,
+    pub processed: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub version: u64,
+    pub latest: u64,
+    /// Steps not yet recorded, oldest first
+    pub pending: Vec<String>,
+}
+
+pub(crate) fn status(frames: &[Frame]) -> MigrationStatus {
+    let version = latest_setting::<SchemaVersion>(frames, VERSION_TOPIC).version;
+    MigrationStatus {
+        version,
+        latest: latest_version(),
+        pending: STEPS
+            .iter()
+            .filter(|step| step.version > version)
+            .map(|step| step.description.to_string())
+            .collect(),
+    }
+}
+
+/// Runs pending steps over the log, reporting progress, then records the latest version so
+/// frames written from now on are taken as already current.
+async fn run(
+    store: &Store,
+    progress: impl Fn(MigrationProgress),
+) -> Result<MigrationStatus, String> {
+    let frames = read_all_frames(store).await;
+    let recorded = status(&frames).version;
+    if recorded >= latest_version() {
+        return Ok(status(&frames));
+    }
+
+    for step in STEPS.iter().filter(|step| step.version > recorded) {
+        let mut projection = Projection::default();
+        let mut upgraded = 0;
+        for (i, frame) in frames.iter().enumerate() {
+            let mut candidate = frame.clone();
+            if (step.upgrade)(&projection, &mut candidate) {
+                upgraded += 1;
+            }
+            projection.apply(frame);
+            if i % 1000 == 0 || i + 1 == frames.len() {
+                progress(MigrationProgress {
+                    version: step.version,
+                    description: step.description.to_string(),
+                    processed: i + 1,
+                    total: frames.len(),
+                });
+            }
+        }
+        println!(
+            "Schema migration {} ({}): {upgraded} legacy frames",
+            step.version, step.description
+        );
+    }
+
+    save_setting(
+        store,
+        VERSION_TOPIC,
+        &SchemaVersion {
+            version: latest_version(),
+        },
+    )?;
+    Ok(status(&read_all_frames(store).await))
+}
+
+pub(crate) async fn initialize(app: &AppHandle, store: &Store) {
+    let result = run(store, |progress| {
+        if let Err(e) = app.emit("migration-progress", &progress) {
+            eprintln!("Failed to emit migration progress: {e}");
+        }
+    })
+    .await;
+    if let Err(e) = result {
+        eprintln!("Schema migration failed: {e}");
+    }
+}
+
+#[tauri::command]
+pub async fn migration_status(store: State<'_, Store>) -> Result<MigrationStatus, String> {
+    Ok(status(&read_all_frames(&store).await))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use crate::testing;
+    use serde_json::json;
+
+    /// An edit as older versions wrote it, without a `yak_id`, which appends now refuse.
+    async fn legacy_edit(store: &Store, content: &[u8], note_id: String) -> Frame {
+        let hash = store.cas_insert(content).await.unwrap();
+        store
+            .append(Frame {
+                id: scru128::new(),
+                context_id: xs::store::ZERO_CONTEXT,
+                topic: "note.edit".to_string(),
+                hash: Some(hash),
+                meta: Some(json!({ "note_id": note_id })),
+                ttl: None,
+            })
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_legacy_frames_are_upgraded_until_recorded() {
+        let (_dir, store) = testing::store();
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let note = append_frame(
+            &store,
+            "note.create",
+            Some(b"one".as_slice()),
+            Some(json!({ "yak_id": yak_id })),
+        )
+        .await
+        .unwrap();
+        let legacy = legacy_edit(&store, b"two", note.id.to_string()).await;
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        assert_eq!(
+            projection.current_notes(&yak_id)[0].id,
+            legacy.id.to_string()
+        );
+
+        let status = run(&store, |_| {}).await.unwrap();
+        assert_eq!(status.version, latest_version());
+        assert!(status.pending.is_empty());
+
+        // Frames written after the version is recorded are taken as they are
+        legacy_edit(&store, b"three", legacy.id.to_string()).await;
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        assert_eq!(
+            projection.current_notes(&yak_id)[0].id,
+            legacy.id.to_string()
+        );
+    }
+}
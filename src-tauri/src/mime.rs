@@ -0,0 +1,137 @@
+use std::path::Path;
+
+/// Guesses a MIME type from a file name's extension.
+pub(crate) fn from_name(name: &str) -> &'static str {
+    let extension = Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("md" | "markdown") => "text/markdown",
+        Some("txt") => "text/plain",
+        Some("html" | "htm") => "text/html",
+        Some("json") => "application/json",
+        Some("pdf") => "application/pdf",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("mp3") => "audio/mpeg",
+        Some("m4a") => "audio/mp4",
+        Some("wav") => "audio/wav",
+        Some("ogg" | "oga") => "audio/ogg",
+        Some("webm") => "video/webm",
+        Some("mp4" | "m4v") => "video/mp4",
+        Some("mov") => "video/quicktime",
+        Some("mkv") => "video/x-matroska",
+        Some("zip") => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Text that opens a fenced code block.
+const FENCE: &str = "```";
+
+/// Starts of lines that only show up in source code.
+const CODE_STARTS: &[&str] = &[
+    "fn ",
+    "pub fn ",
+    "def ",
+    "function ",
+    "const ",
+    "let ",
+    "var ",
+    "import ",
+    "from ",
+    "#include",
+    "class ",
+    "return ",
+    "if (",
+    "for (",
+    "while (",
+    "use ",
+    "package ",
+    "func ",
+    "SELECT ",
+    "select ",
+];
+
+fn is_markdown_line(line: &str) -> bool {
+    let line = line.trim_start();
+    let heading = line.trim_start_matches('#');
+    (heading.len() < line.len() && line.len() - heading.len() <= 6 && heading.starts_with(' '))
+        || ["- ", "* ", "+ ", "> ", "- [ ]", "- [x]"]
+            .iter()
+            .any(|marker| line.starts_with(marker))
+        || line
+            .split_once(". ")
+            .is_some_and(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        || (line.contains("](") && line.contains('['))
+        || line.contains("**")
+}
+
+fn is_code_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    CODE_STARTS.iter().any(|start| trimmed.starts_with(start))
+        || trimmed.ends_with(';')
+        || trimmed.ends_with('{')
+        || trimmed == "}"
+        || trimmed.contains(" => ")
+        || trimmed.contains("();")
+}
+
+/// Sniffs what kind of content a note or clip holds: `binary`, `json`, `markdown`, `code`
+/// or plain `text`. Recorded as `content_type` in frame meta when content is appended, so
+/// views and search can treat a JSON clip, a code snippet and prose differently.
+pub(crate) fn sniff(content: &str) -> &'static str {
+    let controls = content
+        .chars()
+        .filter(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+        .count();
+    if content.contains('\0') || controls * 10 > content.chars().count() {
+        return "binary";
+    }
+    let trimmed = content.trim();
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(trimmed).is_ok()
+    {
+        return "json";
+    }
+    if trimmed.starts_with(FENCE) && trimmed.ends_with(FENCE) && trimmed.len() > 2 * FENCE.len() {
+        return "code";
+    }
+
+    let lines: Vec<&str> = trimmed.lines().filter(|l| !l.trim().is_empty()).collect();
+    let markdown = lines.iter().filter(|line| is_markdown_line(line)).count();
+    let code = lines.iter().filter(|line| is_code_line(line)).count();
+    if trimmed.contains(FENCE) || markdown > code {
+        "markdown"
+    } else if code >= 2 && code * 3 >= lines.len() {
+        "code"
+    } else if markdown > 0 {
+        "markdown"
+    } else {
+        "text"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(r#"{"status": "ok", "items": [1, 2]}"#), "json");
+        assert_eq!(sniff("{ not json"), "text");
+        assert_eq!(sniff("# Plan\n\n- milk\n- eggs\n"), "markdown");
+        assert_eq!(sniff("Notes\n\n```rust\nlet x = 1;\n```\n"), "markdown");
+        assert_eq!(sniff("fn main() {\n    println!(\"hi\");\n}\n"), "code");
+        assert_eq!(
+            sniff("import os\n\ndef run():\n    return os.getcwd()\n"),
+            "code"
+        );
+        assert_eq!(sniff("Call Bo about the lease tomorrow."), "text");
+        assert_eq!(sniff("PK\u{3}\u{4}\u{14}\0\0"), "binary");
+    }
+}
@@ -0,0 +1,214 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+use xs::store::{Frame, Store};
+
+use crate::frontmatter;
+use crate::import::markdown::embedded_links;
+use crate::import::{add_attachment, add_note, add_tag, create_yak};
+use crate::links::OpenNote;
+use crate::projection::Projection;
+use crate::properties::{self, append_property};
+use crate::read_all_frames;
+use crate::windows::{emit_frames, focus_main};
+
+/// Where opened files go unless their frontmatter names a yak.
+const YAK_NAME: &str = "Opened files";
+/// Extensions registered as file associations in `tauri.conf.json`.
+const EXTENSIONS: &[&str] = &["yaknote", "md", "markdown"];
+
+/// Files the OS handed over before the store was ready.
+#[derive(Default)]
+pub struct OpenWithState {
+    pending: Mutex<Vec<PathBuf>>,
+}
+
+fn is_openable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+/// The files in a launch's arguments (after the program itself), resolved against `cwd`.
+/// Flags and `yaks://` links are left to their own handlers.
+pub(crate) fn paths_from_args(args: &[String], cwd: &Path) -> Vec<PathBuf> {
+    args.iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-') && !arg.contains("://"))
+        .map(|arg| cwd.join(arg))
+        .filter(|path| is_openable(path) && path.is_file())
+        .collect()
+}
+
+/// The yak named in the file's frontmatter (`yak: Work`), else the "Opened files" yak,
+/// created the first time it's needed.
+async fn target_yak(store: &Store, wanted: Option<&str>) -> Result<String, String> {
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    let by_name = |name: &str| {
+        projection
+            .yaks
+            .values()
+            .filter(|yak| yak.name.as_deref() == Some(name))
+            .map(|yak| yak.id.clone())
+            .min()
+    };
+    if let Some(yak_id) = wanted.and_then(by_name) {
+        return Ok(yak_id);
+    }
+    match by_name(YAK_NAME) {
+        Some(yak_id) => Ok(yak_id),
+        None => create_yak(store, YAK_NAME).await,
+    }
+}
+
+/// Imports one file as a note, with its frontmatter tags and any images it embeds. Returns
+/// every frame appended, the note first.
+pub(crate) async fn import_file(store: &Store, path: &Path) -> Result<Vec<Frame>, String> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let (fields, body) = frontmatter::split(&content);
+    let wanted = fields
+        .as_ref()
+        .and_then(|fields| fields.get("yak"))
+        .and_then(|yak| yak.as_str());
+    let yak_id = target_yak(store, wanted).await?;
+    let tags = fields.as_ref().map(frontmatter::tags).unwrap_or_default();
+    let (fields, properties) = properties::from_frontmatter(fields);
+    let title = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let meta = serde_json::json!({
+        "title": title,
+        "source_path": path.to_string_lossy(),
+        "frontmatter": fields,
+        "opened": true,
+    });
+    let note = add_note(store, &yak_id, body, meta).await?;
+    let note_id = note.id.to_string();
+    let mut frames = vec![note];
+
+    for tag in tags {
+        frames.push(add_tag(store, &yak_id, &note_id, &tag).await?);
+    }
+    for (key, value) in properties {
+        frames.push(append_property(store, &yak_id, &note_id, &key, value).await?);
+    }
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for link in embedded_links(body) {
+        let linked = dir.join(crate::import::percent_decode(&link));
+        let Ok(bytes) = tokio::fs::read(&linked).await else {
+            eprintln!("Skipping missing attachment {link} of {}", path.display());
+            continue;
+        };
+        let name = linked
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&link);
+        let extra = serde_json::json!({ "link": link });
+        frames.push(add_attachment(store, &yak_id, &note_id, name, &bytes, extra).await?);
+    }
+    Ok(frames)
+}
+
+/// Imports the files and shows the last of them.
+async fn open(app: AppHandle, store: Store, paths: Vec<PathBuf>) {
+    let mut last = None;
+    for path in paths {
+        match import_file(&store, &path).await {
+            Ok(frames) => {
+                let _ = emit_frames(&app, &frames);
+                last = frames.into_iter().next();
+            }
+            Err(e) => eprintln!("Failed to open {}: {e}", path.display()),
+        }
+    }
+    let Some(note) = last else {
+        return;
+    };
+    let yak_id = crate::windows::frame_yak_id(&note).unwrap_or_default();
+    let note_id = note.id.to_string();
+    if let Some(window) = focus_main(&app) {
+        if let Err(e) = window.emit("open-note", OpenNote { note_id, yak_id }) {
+            eprintln!("Failed to emit open-note: {e}");
+        }
+    }
+}
+
+/// Handles files from "Open with", a file association, or a second launch. Files arriving
+/// before the store is ready are kept until `initialize`.
+pub(crate) fn handle(app: &AppHandle, paths: Vec<PathBuf>) {
+    let paths: Vec<PathBuf> = paths.into_iter().filter(|p| is_openable(p)).collect();
+    if paths.is_empty() {
+        return;
+    }
+    match app.try_state::<Store>() {
+        Some(store) => {
+            tauri::async_runtime::spawn(open(app.clone(), store.inner().clone(), paths));
+        }
+        None => app
+            .state::<OpenWithState>()
+            .pending
+            .lock()
+            .unwrap()
+            .extend(paths),
+    }
+}
+
+/// Opens the files the app was launched with, and any handed over while it started.
+pub(crate) fn initialize(app: &AppHandle, store: &Store) {
+    let args: Vec<String> = std::env::args().collect();
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut paths = std::mem::take(&mut *app.state::<OpenWithState>().pending.lock().unwrap());
+    paths.extend(paths_from_args(&args, &cwd));
+    if !paths.is_empty() {
+        tauri::async_runtime::spawn(open(app.clone(), store.clone(), paths));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_paths_from_args() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("todo.md"), "# Todo").unwrap();
+        std::fs::write(dir.path().join("photo.png"), b"png").unwrap();
+        let args: Vec<String> = ["yaks", "--flag", "todo.md", "photo.png", "yaks://note/x"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(
+            paths_from_args(&args, dir.path()),
+            vec![dir.path().join("todo.md")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("Groceries.yaknote");
+        std::fs::write(&path, "---\ntags: [home]\n---\nmilk, eggs\n").unwrap();
+        let store_dir = tempdir().unwrap();
+        let store = Store::new(store_dir.path().to_path_buf());
+
+        let frames = import_file(&store, &path).await.unwrap();
+        assert_eq!(frames.len(), 2);
+        // A second file lands in the same yak
+        let again = import_file(&store, &path).await.unwrap();
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let yak_id = crate::windows::frame_yak_id(&frames[0]).unwrap();
+        assert_eq!(projection.yaks[&yak_id].name.as_deref(), Some(YAK_NAME));
+        assert_eq!(
+            crate::windows::frame_yak_id(&again[0]),
+            Some(yak_id.clone())
+        );
+        let note = &projection.notes[&frames[0].id.to_string()];
+        assert!(note.tags.contains("home"));
+        assert_eq!(crate::export::note_title(note), "Groceries");
+    }
+}
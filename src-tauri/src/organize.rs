@@ -0,0 +1,207 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::projection::Projection;
+use crate::windows::emit_frames;
+use crate::{append_batch_to_store, read_all_frames, AppendRequest};
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct MoveReport {
+    pub notes: usize,
+    pub tasks: usize,
+}
+
+fn request(topic: &str, meta: [(&str, String); 3]) -> AppendRequest {
+    AppendRequest {
+        topic: topic.to_string(),
+        content: String::new(),
+        meta: Some(HashMap::from(
+            meta.map(|(key, value)| (key.to_string(), value.into())),
+        )),
+    }
+}
+
+/// `note.move` requests re-homing the given notes (any revision id) into `target`. Notes
+/// already there are skipped.
+pub(crate) fn note_moves(
+    projection: &Projection,
+    frame_ids: &[String],
+    target: &str,
+) -> Result<Vec<AppendRequest>, String> {
+    if !projection.yaks.contains_key(target) {
+        return Err(format!("Unknown yak {target}"));
+    }
+    let mut requests = Vec::new();
+    for frame_id in frame_ids {
+        let note_id = projection.resolve(frame_id);
+        let note = projection
+            .notes
+            .get(&note_id)
+            .ok_or_else(|| format!("Unknown note {frame_id}"))?;
+        if note.yak_id != target {
+            requests.push(request(
+                "note.move",
+                [
+                    ("note_id", note_id),
+                    ("yak_id", target.to_string()),
+                    ("from_yak_id", note.yak_id.clone()),
+                ],
+            ));
+        }
+    }
+    Ok(requests)
+}
+
+async fn merge(
+    store: &Store,
+    source: &str,
+    target: &str,
+) -> Result<(Vec<Frame>, MoveReport), String> {
+    if source == target {
+        return Err("Can't merge a yak into itself".to_string());
+    }
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    if !projection.yaks.contains_key(source) {
+        return Err(format!("Unknown yak {source}"));
+    }
+
+    let note_ids: Vec<String> = projection
+        .current_notes(source)
+        .iter()
+        .map(|note| note.id.clone())
+        .collect();
+    let mut requests = note_moves(&projection, &note_ids, target)?;
+    let mut report = MoveReport {
+        notes: requests.len(),
+        tasks: 0,
+    };
+    // Tasks attached to a note follow it; the rest are moved on their own
+    for task in projection.yak_tasks(source) {
+        if task.note_id.is_none() {
+            requests.push(request(
+                "task.move",
+                [
+                    ("task_id", task.id.clone()),
+                    ("yak_id", target.to_string()),
+                    ("from_yak_id", source.to_string()),
+                ],
+            ));
+            report.tasks += 1;
+        }
+    }
+    requests.push(request(
+        "yak.merge",
+        [
+            ("yak_id", source.to_string()),
+            ("into", target.to_string()),
+            (
+                "name",
+                projection.yaks[source].name.clone().unwrap_or_default(),
+            ),
+        ],
+    ));
+
+    let frames = append_batch_to_store(store, requests).await?;
+    Ok((frames, report))
+}
+
+/// Moves notes (with their tasks, tags and attachments) into another yak, e.g. to split a
+/// yak in two.
+#[tauri::command]
+pub async fn move_notes(
+    store: State<'_, Store>,
+    app: AppHandle,
+    frame_ids: Vec<String>,
+    target_yak: String,
+) -> Result<MoveReport, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let requests = note_moves(&projection, &frame_ids, &target_yak)?;
+    let report = MoveReport {
+        notes: requests.len(),
+        tasks: 0,
+    };
+    let frames = append_batch_to_store(&store, requests).await?;
+    emit_frames(&app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))?;
+    Ok(report)
+}
+
+/// Moves everything in `source` into `target`, then retires `source`.
+#[tauri::command]
+pub async fn merge_yaks(
+    store: State<'_, Store>,
+    app: AppHandle,
+    source: String,
+    target: String,
+) -> Result<MoveReport, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let (frames, report) = merge(&store, &source, &target).await?;
+    emit_frames(&app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_merge_yaks() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let source = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let target = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let (source, target) = (source.id.to_string(), target.id.to_string());
+        let note = append_frame(
+            &store,
+            "note.create",
+            Some(b"hello".as_slice()),
+            Some(json!({ "yak_id": source })),
+        )
+        .await
+        .unwrap();
+        let note_id = note.id.to_string();
+        append_frame(
+            &store,
+            "task.create",
+            Some(b"follow up".as_slice()),
+            Some(json!({ "yak_id": source, "note_id": note_id })),
+        )
+        .await
+        .unwrap();
+        append_frame(
+            &store,
+            "task.create",
+            Some(b"loose end".as_slice()),
+            Some(json!({ "yak_id": source })),
+        )
+        .await
+        .unwrap();
+
+        let (_, report) = merge(&store, &source, &target).await.unwrap();
+        assert_eq!((report.notes, report.tasks), (1, 1));
+
+        // An editor still holding the old yak id doesn't pull the note back
+        append_frame(
+            &store,
+            "note.edit",
+            Some(b"hello again".as_slice()),
+            Some(json!({ "yak_id": source, "note_id": note_id })),
+        )
+        .await
+        .unwrap();
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        assert!(!projection.yaks.contains_key(&source));
+        assert_eq!(projection.current_notes(&target).len(), 1);
+        assert_eq!(projection.yak_tasks(&target).len(), 2);
+    }
+}
@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Notify;
+use xs::store::{Frame, Store};
+
+use crate::profiles;
+use crate::windows::emit_frames;
+use crate::{try_append_batch, AppendError, AppendRequest};
+
+const OUTBOX_FILE: &str = "outbox.json";
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// Attempts after which an append is given up on, and kept only to be looked at or discarded.
+const MAX_ATTEMPTS: u32 = 12;
+
+/// Appends that failed on a store error, kept (in a file beside the store) until a retry
+/// gets them in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingAppend {
+    pub id: String,
+    /// Appended together, as by `append_batch`
+    pub requests: Vec<AppendRequest>,
+    pub attempts: u32,
+    pub last_error: String,
+    /// Given up on: failed `MAX_ATTEMPTS` times, or rejected on retry. No longer retried
+    #[serde(default)]
+    pub dead: bool,
+}
+
+#[derive(Default)]
+pub struct OutboxState {
+    path: Mutex<Option<PathBuf>>,
+    pending: Mutex<Vec<PendingAppend>>,
+    kick: Notify,
+}
+
+impl OutboxState {
+    fn save(&self) {
+        let Some(path) = self.path.lock().unwrap().clone() else {
+            return;
+        };
+        let pending = self.pending.lock().unwrap().clone();
+        let result = serde_json::to_vec(&pending)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                let staged = path.with_extension("json.new");
+                std::fs::write(&staged, json).map_err(|e| e.to_string())?;
+                std::fs::rename(&staged, &path).map_err(|e| e.to_string())
+            });
+        // The queue is still held in memory if the disk is what's failing
+        if let Err(e) = result {
+            eprintln!("Failed to save outbox: {e}");
+        }
+    }
+
+    fn discard(&self, id: &str) -> Result<(), String> {
+        {
+            let mut pending = self.pending.lock().unwrap();
+            let before = pending.len();
+            pending.retain(|entry| entry.id != id);
+            if pending.len() == before {
+                return Err(format!("No queued append {id}"));
+            }
+        }
+        self.save();
+        Ok(())
+    }
+}
+
+fn backoff(attempts: u32) -> Duration {
+    Duration::from_secs(1u64 << attempts.min(9)).min(MAX_BACKOFF)
+}
+
+/// Queues requests whose append failed so they're retried in the background.
+pub(crate) fn enqueue(app: &AppHandle, requests: Vec<AppendRequest>, error: &str) -> String {
+    let state = app.state::<OutboxState>();
+    let id = scru128::new().to_string();
+    state.pending.lock().unwrap().push(PendingAppend {
+        id: id.clone(),
+        requests,
+        attempts: 1,
+        last_error: error.to_string(),
+        dead: false,
+    });
+    state.save();
+    state.kick.notify_one();
+    id
+}
+
+/// Tries every pending append once, oldest first but each on its own, so one that keeps
+/// failing doesn't hold up the rest. Returns the frames written.
+async fn retry_pending(state: &OutboxState, store: &Store) -> Vec<Frame> {
+    let entries: Vec<PendingAppend> = state
+        .pending
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|entry| !entry.dead)
+        .cloned()
+        .collect();
+    let mut written = Vec::new();
+    for entry in entries {
+        let result = try_append_batch(store, entry.requests).await;
+        let mut pending = state.pending.lock().unwrap();
+        match result {
+            Ok(frames) => {
+                pending.retain(|p| p.id != entry.id);
+                written.extend(frames);
+            }
+            Err(e) => {
+                if let Some(p) = pending.iter_mut().find(|p| p.id == entry.id) {
+                    p.attempts += 1;
+                    p.last_error = e.to_string();
+                    p.dead = p.attempts >= MAX_ATTEMPTS || !matches!(e, AppendError::Store(_));
+                    if p.dead {
+                        eprintln!("Giving up on queued append {}: {e}", p.id);
+                    } else {
+                        eprintln!("Retrying queued append {} later: {e}", p.id);
+                    }
+                }
+            }
+        }
+    }
+    state.save();
+    written
+}
+
+/// Retries queued appends, backing off while the store keeps failing.
+async fn watch(app: AppHandle, store: Store) {
+    let state = app.state::<OutboxState>();
+    loop {
+        let wait = {
+            let pending = state.pending.lock().unwrap();
+            pending
+                .iter()
+                .filter(|entry| !entry.dead)
+                .map(|entry| backoff(entry.attempts))
+                .min()
+        };
+        match wait {
+            Some(wait) => {
+                let _ = tokio::time::timeout(wait, state.kick.notified()).await;
+            }
+            None => state.kick.notified().await,
+        }
+
+        let frames = retry_pending(&state, &store).await;
+        if !frames.is_empty() {
+            if let Err(e) = emit_frames(&app, &frames) {
+                eprintln!("Failed to emit frames: {e}");
+            }
+        }
+    }
+}
+
+pub(crate) async fn initialize(app: &AppHandle, store: &Store) {
+    let state = app.state::<OutboxState>();
+    match profiles::data_dir(app) {
+        Ok(dir) => {
+            let path = dir.join(OUTBOX_FILE);
+            if let Ok(json) = tokio::fs::read(&path).await {
+                match serde_json::from_slice::<Vec<PendingAppend>>(&json) {
+                    Ok(pending) => *state.pending.lock().unwrap() = pending,
+                    Err(e) => eprintln!("Ignoring unreadable outbox: {e}"),
+                }
+            }
+            *state.path.lock().unwrap() = Some(path);
+        }
+        Err(e) => eprintln!("Outbox won't persist: {e}"),
+    }
+    crate::shutdown::track(app, tokio::spawn(watch(app.clone(), store.clone())));
+    state.kick.notify_one();
+}
+
+/// Appends still waiting to be retried, and those given up on.
+#[tauri::command]
+pub fn get_pending_appends(state: State<'_, OutboxState>) -> Vec<PendingAppend> {
+    state.pending.lock().unwrap().clone()
+}
+
+/// Drops a queued append, e.g. one given up on, without writing it.
+#[tauri::command]
+pub fn discard_pending_append(state: State<'_, OutboxState>, id: String) -> Result<(), String> {
+    state.discard(&id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), Duration::from_secs(2));
+        assert_eq!(backoff(4), Duration::from_secs(16));
+        assert_eq!(backoff(30), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn test_retry_pending() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let state = OutboxState::default();
+        *state.path.lock().unwrap() = Some(dir.path().join(OUTBOX_FILE));
+        let pending = |id: &str, meta: Option<HashMap<String, serde_json::Value>>| PendingAppend {
+            id: id.to_string(),
+            requests: vec![AppendRequest {
+                topic: "note.create".to_string(),
+                content: "captured while the disk was full".to_string(),
+                meta,
+            }],
+            attempts: 1,
+            last_error: "No space left on device".to_string(),
+            dead: false,
+        };
+        // Queued first, but rejected: it mustn't hold up the one behind it
+        state.pending.lock().unwrap().extend([
+            pending("bad", None),
+            pending("good", Some([("yak_id".to_string(), "yak".into())].into())),
+        ]);
+
+        let frames = retry_pending(&state, &store).await;
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].topic, "note.create");
+        let left = state.pending.lock().unwrap().clone();
+        assert_eq!(left.len(), 1);
+        assert!(left[0].dead && left[0].last_error.contains("yak_id"));
+        assert!(retry_pending(&state, &store).await.is_empty());
+
+        state.discard("bad").unwrap();
+        let saved = std::fs::read(dir.path().join(OUTBOX_FILE)).unwrap();
+        assert_eq!(saved, b"[]");
+    }
+}
@@ -0,0 +1,231 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+use xs::store::Store;
+
+use crate::cache::CasCache;
+use crate::export::note_title;
+use crate::locks::LockState;
+use crate::projection::Projection;
+use crate::windows::emit_frames;
+use crate::{append_batch_to_store, read_all_frames, shutdown, AppendRequest};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutlineNode {
+    pub note_id: String,
+    pub title: String,
+    pub children: Vec<OutlineNode>,
+}
+
+/// A yak's notes as a tree, siblings in the yak's note order. Notes nested under a note that
+/// has since been deleted or moved to another yak come up to the top level.
+fn outline(
+    projection: &Projection,
+    yak_id: &str,
+    titles: &HashMap<String, String>,
+) -> Vec<OutlineNode> {
+    let ids = projection
+        .notes_by_yak
+        .get(yak_id)
+        .cloned()
+        .unwrap_or_default();
+    let current: HashSet<&String> = ids.iter().collect();
+    let mut children: HashMap<String, Vec<String>> = HashMap::new();
+    let mut roots = Vec::new();
+    for id in &ids {
+        match projection
+            .parent(id)
+            .filter(|parent| current.contains(parent))
+        {
+            Some(parent) => children.entry(parent).or_default().push(id.clone()),
+            None => roots.push(id.clone()),
+        }
+    }
+
+    fn build(
+        id: &str,
+        children: &HashMap<String, Vec<String>>,
+        titles: &HashMap<String, String>,
+    ) -> OutlineNode {
+        OutlineNode {
+            note_id: id.to_string(),
+            title: titles.get(id).cloned().unwrap_or_default(),
+            children: children
+                .get(id)
+                .map(|ids| ids.iter().map(|id| build(id, children, titles)).collect())
+                .unwrap_or_default(),
+        }
+    }
+    roots
+        .iter()
+        .map(|id| build(id, &children, &titles))
+        .collect()
+}
+
+fn parent_request(
+    projection: &Projection,
+    frame_id: &str,
+    parent_id: Option<&str>,
+) -> Result<AppendRequest, String> {
+    let note_id = projection.resolve(frame_id);
+    let note = projection
+        .notes
+        .get(&note_id)
+        .ok_or_else(|| format!("Note not found: {frame_id}"))?;
+    let parent = match parent_id {
+        Some(parent_id) => {
+            let parent_id = projection.resolve(parent_id);
+            let parent = projection
+                .notes
+                .get(&parent_id)
+                .ok_or_else(|| format!("Note not found: {parent_id}"))?;
+            if parent.yak_id != note.yak_id {
+                return Err("A note can only be nested under a note in the same yak".to_string());
+            }
+            if projection.is_ancestor(&note_id, &parent_id) {
+                return Err(format!(
+                    "Can't nest {note_id} under {parent_id}: it would contain itself"
+                ));
+            }
+            Some(parent_id)
+        }
+        None => None,
+    };
+    Ok(AppendRequest {
+        topic: "note.parent".to_string(),
+        content: String::new(),
+        meta: Some(HashMap::from([
+            ("yak_id".to_string(), note.yak_id.clone().into()),
+            ("note_id".to_string(), note_id.into()),
+            ("parent_id".to_string(), parent.into()),
+        ])),
+    })
+}
+
+/// Nests a note under `parent_id`, or back at the top level when it is null.
+#[tauri::command]
+pub async fn set_parent(
+    store: State<'_, Store>,
+    app: AppHandle,
+    frame_id: String,
+    parent_id: Option<String>,
+) -> Result<(), String> {
+    let _guard = shutdown::begin_write(&app)?;
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let request = parent_request(&projection, &frame_id, parent_id.as_deref())?;
+    let frames = append_batch_to_store(&store, vec![request]).await?;
+    emit_frames(&app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_outline(
+    app: AppHandle,
+    store: State<'_, Store>,
+    cache: State<'_, Arc<CasCache>>,
+    yak_id: String,
+) -> Result<Vec<OutlineNode>, String> {
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    if !projection.yaks.contains_key(&yak_id) {
+        return Err(format!("Unknown yak {yak_id}"));
+    }
+    let mut titles = HashMap::new();
+    for note in projection.current_notes(&yak_id) {
+        let mut note = note.clone();
+        if let Some(hash) = &note.hash {
+            note.content = cache
+                .read(&store, &hash.to_string())
+                .await
+                .ok()
+                // Sealed notes fall back to their meta title, if any
+                .and_then(|content| app.state::<LockState>().reveal(&content).ok());
+        }
+        titles.insert(note.id.clone(), note_title(&note));
+    }
+    Ok(outline(&projection, &yak_id, &titles))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn shape(nodes: &[OutlineNode]) -> String {
+        nodes
+            .iter()
+            .map(|node| {
+                if node.children.is_empty() {
+                    node.title.clone()
+                } else {
+                    format!("{}({})", node.title, shape(&node.children))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[tokio::test]
+    async fn test_outline() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let mut ids = Vec::new();
+        let mut titles = HashMap::new();
+        for title in ["a", "b", "c"] {
+            let note = append_frame(
+                &store,
+                "note.create",
+                Some(title.as_bytes()),
+                Some(json!({ "yak_id": yak_id })),
+            )
+            .await
+            .unwrap();
+            ids.push(note.id.to_string());
+            titles.insert(note.id.to_string(), title.to_string());
+        }
+        let set = |child: usize, parent: usize| {
+            let store = store.clone();
+            let (child, parent) = (ids[child].clone(), ids[parent].clone());
+            async move {
+                let projection = Projection::from_frames(&read_all_frames(&store).await);
+                let request = parent_request(&projection, &child, Some(&parent))?;
+                append_batch_to_store(&store, vec![request]).await
+            }
+        };
+        set(1, 0).await.unwrap();
+        set(2, 1).await.unwrap();
+        // a > b > c, so a can't go under c
+        assert!(set(0, 2).await.is_err());
+
+        // Nesting survives an edit of the parent
+        let edit = append_frame(
+            &store,
+            "note.edit",
+            Some(b"b2".as_slice()),
+            Some(json!({ "yak_id": yak_id, "note_id": ids[1] })),
+        )
+        .await
+        .unwrap();
+        titles.insert(edit.id.to_string(), "b2".to_string());
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        assert_eq!(shape(&outline(&projection, &yak_id, &titles)), "a(b2(c))");
+
+        // Deleting b2 brings c up to the top level
+        append_frame(
+            &store,
+            "note.delete",
+            None,
+            Some(json!({ "yak_id": yak_id, "note_id": edit.id.to_string() })),
+        )
+        .await
+        .unwrap();
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        assert_eq!(shape(&outline(&projection, &yak_id, &titles)), "a c");
+    }
+}
@@ -0,0 +1,326 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::settings::{latest_setting, save_setting};
+use crate::windows::emit_frames;
+use crate::{append_frame, read_all_frames};
+
+const CONFIG_TOPIC: &str = "integration.scopes";
+const AUDIT_TOPIC: &str = "integration.audit";
+/// How often recorded actions are written to the audit log.
+const FLUSH: Duration = Duration::from_secs(1);
+const DEFAULT_AUDIT_LIMIT: usize = 200;
+
+/// A way into the store from outside the app's own windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Integration {
+    /// The publish server's capture endpoint
+    Http,
+    Mcp,
+    Plugins,
+    Handlers,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    #[default]
+    Full,
+    ReadOnly,
+    /// Can add frames but not read notes
+    AppendOnly,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scope {
+    pub access: Access,
+    /// The yaks it may touch; every yak when empty
+    pub yaks: Vec<String>,
+}
+
+impl Scope {
+    fn allows_yak(&self, yak_id: Option<&str>) -> bool {
+        self.yaks.is_empty() || yak_id.is_some_and(|yak_id| self.yaks.iter().any(|id| id == yak_id))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Read,
+    Append,
+}
+
+/// One thing an integration did or was refused, kept as an `integration.audit` frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub integration: Integration,
+    pub action: Action,
+    pub allowed: bool,
+    pub topic: Option<String>,
+    pub yak_id: Option<String>,
+    /// The frame appended, or the note read
+    pub frame_id: Option<String>,
+    /// The audit frame's id, set when the log is read back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<String>,
+}
+
+/// Checked on every append; `append_frame` has no app handle to reach managed state with.
+static SCOPES: RwLock<BTreeMap<Integration, Scope>> = RwLock::new(BTreeMap::new());
+/// Actions waiting to be written to the audit log.
+static PENDING: Mutex<Vec<AuditEntry>> = Mutex::new(Vec::new());
+
+tokio::task_local! {
+    static INTEGRATION: Integration;
+}
+
+/// Runs `future` with every frame it appends held to `integration`'s scope and audited.
+pub(crate) async fn scope<F: Future>(integration: Integration, future: F) -> F::Output {
+    INTEGRATION.scope(integration, future).await
+}
+
+fn current() -> Option<Integration> {
+    INTEGRATION.try_with(|integration| *integration).ok()
+}
+
+fn scope_of(integration: Integration) -> Scope {
+    SCOPES
+        .read()
+        .unwrap()
+        .get(&integration)
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn record(entry: AuditEntry) {
+    PENDING.lock().unwrap().push(entry);
+}
+
+fn meta_yak(meta: Option<&Value>) -> Option<&str> {
+    meta?.get("yak_id")?.as_str()
+}
+
+fn refused(integration: Integration, action: Action, yak_id: Option<&str>) -> String {
+    let action = match action {
+        Action::Read => "read",
+        Action::Append => "append",
+    };
+    let name = serde_json::to_value(integration)
+        .ok()
+        .and_then(|name| name.as_str().map(String::from))
+        .unwrap_or_default();
+    match yak_id {
+        Some(yak_id) => format!("The {name} integration may not {action} in yak {yak_id}"),
+        None => format!("The {name} integration may not {action} here"),
+    }
+}
+
+/// A read without a yak is one across yaks, narrowed to the allowed ones with `allows_yak`;
+/// an append without one is of a frame outside any yak.
+fn allowed(scope: &Scope, action: Action, yak_id: Option<&str>) -> bool {
+    match action {
+        Action::Read => {
+            scope.access != Access::AppendOnly && (yak_id.is_none() || scope.allows_yak(yak_id))
+        }
+        Action::Append => scope.access != Access::ReadOnly && scope.allows_yak(yak_id),
+    }
+}
+
+/// Whether `integration` may read in `yak_id`, without auditing it.
+pub(crate) fn can_read(integration: Integration, yak_id: Option<&str>) -> bool {
+    allowed(&scope_of(integration), Action::Read, yak_id)
+}
+
+/// Whether a plugin or handler may be run on `frame`: one in a yak it can read, and not
+/// from the audit log, which its own appends would only add to.
+pub(crate) fn triggers(integration: Integration, frame: &Frame) -> bool {
+    frame.topic != AUDIT_TOPIC && can_read(integration, meta_yak(frame.meta.as_ref()))
+}
+
+/// Whether `integration` may read `frame_id` in `yak_id`, auditing the read either way.
+pub(crate) fn check_read(
+    integration: Integration,
+    yak_id: Option<&str>,
+    frame_id: Option<&str>,
+) -> Result<(), String> {
+    let allowed = allowed(&scope_of(integration), Action::Read, yak_id);
+    record(AuditEntry {
+        integration,
+        action: Action::Read,
+        allowed,
+        topic: None,
+        yak_id: yak_id.map(String::from),
+        frame_id: frame_id.map(String::from),
+        at: None,
+    });
+    if !allowed {
+        return Err(refused(integration, Action::Read, yak_id));
+    }
+    Ok(())
+}
+
+/// Whether `integration` may list or search yak `yak_id`; reads across yaks are narrowed to
+/// the ones this allows.
+pub(crate) fn allows_yak(integration: Integration, yak_id: &str) -> bool {
+    scope_of(integration).allows_yak(Some(yak_id))
+}
+
+/// Refuses an append the current integration's scope doesn't allow, auditing the refusal.
+/// Appends outside an integration pass.
+pub(crate) fn check_append(topic: &str, meta: Option<&Value>) -> Result<(), String> {
+    let Some(integration) = current() else {
+        return Ok(());
+    };
+    let yak_id = meta_yak(meta);
+    if allowed(&scope_of(integration), Action::Append, yak_id) {
+        return Ok(());
+    }
+    record(AuditEntry {
+        integration,
+        action: Action::Append,
+        allowed: false,
+        topic: Some(topic.to_string()),
+        yak_id: yak_id.map(String::from),
+        frame_id: None,
+        at: None,
+    });
+    Err(refused(integration, Action::Append, yak_id))
+}
+
+/// Audits a frame the current integration appended.
+pub(crate) fn record_append(frame: &Frame) {
+    let Some(integration) = current() else {
+        return;
+    };
+    record(AuditEntry {
+        integration,
+        action: Action::Append,
+        allowed: true,
+        topic: Some(frame.topic.clone()),
+        yak_id: meta_yak(frame.meta.as_ref()).map(String::from),
+        frame_id: Some(frame.id.to_string()),
+        at: None,
+    });
+}
+
+pub(crate) fn initialize(frames: &[Frame]) {
+    let scopes: BTreeMap<Integration, Scope> = latest_setting(frames, CONFIG_TOPIC);
+    *SCOPES.write().unwrap() = scopes;
+}
+
+/// Writes recorded actions to the audit log as `integration.audit` frames.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    loop {
+        tokio::time::sleep(FLUSH).await;
+        let pending = std::mem::take(&mut *PENDING.lock().unwrap());
+        let mut appended = Vec::new();
+        for entry in pending {
+            match append_frame(&store, AUDIT_TOPIC, None, Some(serde_json::json!(entry))).await {
+                Ok(frame) => appended.push(frame),
+                Err(e) => eprintln!("Failed to audit integration action: {e}"),
+            }
+        }
+        if !appended.is_empty() {
+            let _ = emit_frames(&app, &appended);
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_integration_scopes() -> Result<BTreeMap<Integration, Scope>, String> {
+    Ok(SCOPES.read().unwrap().clone())
+}
+
+/// Limits what an integration can do: read only, append only, or only in some yaks. No scope
+/// gives it full access again.
+#[tauri::command]
+pub async fn set_integration_scope(
+    store: State<'_, Store>,
+    integration: Integration,
+    scope: Option<Scope>,
+) -> Result<BTreeMap<Integration, Scope>, String> {
+    let mut scopes = SCOPES.read().unwrap().clone();
+    match scope.filter(|scope| *scope != Scope::default()) {
+        Some(scope) => scopes.insert(integration, scope),
+        None => scopes.remove(&integration),
+    };
+    save_setting(&store, CONFIG_TOPIC, &scopes)?;
+    *SCOPES.write().unwrap() = scopes.clone();
+    Ok(scopes)
+}
+
+/// What integrations read and appended, and what they were refused, newest first.
+#[tauri::command]
+pub async fn get_integration_audit(
+    store: State<'_, Store>,
+    integration: Option<Integration>,
+    limit: Option<usize>,
+) -> Result<Vec<AuditEntry>, String> {
+    let frames = read_all_frames(&store).await;
+    Ok(frames
+        .iter()
+        .rev()
+        .filter(|frame| frame.topic == AUDIT_TOPIC)
+        .filter_map(|frame| {
+            let mut entry: AuditEntry = serde_json::from_value(frame.meta.clone()?).ok()?;
+            entry.at = Some(frame.id.to_string());
+            Some(entry)
+        })
+        .filter(|entry| integration.map_or(true, |integration| entry.integration == integration))
+        .take(limit.unwrap_or(DEFAULT_AUDIT_LIMIT))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_scopes() {
+        let full = Scope::default();
+        assert!(allowed(&full, Action::Read, None));
+        assert!(allowed(&full, Action::Append, Some("work")));
+
+        let read_only = Scope {
+            access: Access::ReadOnly,
+            yaks: Vec::new(),
+        };
+        assert!(allowed(&read_only, Action::Read, Some("work")));
+        assert!(!allowed(&read_only, Action::Append, Some("work")));
+
+        let inbox_only = Scope {
+            access: Access::AppendOnly,
+            yaks: vec!["inbox".to_string()],
+        };
+        assert!(allowed(&inbox_only, Action::Append, Some("inbox")));
+        assert!(!allowed(&inbox_only, Action::Append, Some("work")));
+        // Frames outside any yak are out of a yak-limited scope
+        assert!(!allowed(&inbox_only, Action::Append, None));
+        assert!(!allowed(&inbox_only, Action::Read, Some("inbox")));
+        let work_only = Scope {
+            access: Access::Full,
+            yaks: vec!["work".to_string()],
+        };
+        assert!(allowed(&work_only, Action::Read, None));
+        assert!(!allowed(&work_only, Action::Read, Some("home")));
+
+        assert_eq!(
+            serde_json::to_value(&inbox_only).unwrap(),
+            json!({ "access": "append_only", "yaks": ["inbox"] })
+        );
+        assert_eq!(
+            refused(Integration::Mcp, Action::Append, Some("work")),
+            "The mcp integration may not append in yak work"
+        );
+    }
+}
@@ -0,0 +1,408 @@
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Mutex;
+use wasmtime::{Caller, Config, Engine, Linker, Module};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::append_frame;
+use crate::permissions::{self, Integration};
+use crate::profiles;
+use crate::settings::{load_setting, save_setting};
+use crate::shutdown;
+use crate::windows::emit_frame;
+
+const CONFIG_TOPIC: &str = "plugin.config";
+/// Instructions a plugin may execute per frame before it's stopped
+const FUEL_PER_FRAME: u64 = 50_000_000;
+
+/// Which plugins are enabled on this device; plugins are off until enabled.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct PluginConfig {
+    enabled: BTreeSet<String>,
+}
+
+/// Optional `<name>.json` beside a plugin's `<name>.wasm`.
+#[derive(Debug, Default, Deserialize)]
+struct Manifest {
+    /// Topic prefixes the plugin wants; every frame if empty
+    #[serde(default)]
+    topics: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginInfo {
+    pub name: String,
+    pub path: String,
+    pub topics: Vec<String>,
+    pub enabled: bool,
+    /// Why the module couldn't be loaded, if it couldn't
+    pub error: Option<String>,
+}
+
+struct Plugin {
+    info: PluginInfo,
+    module: Option<Module>,
+}
+
+pub struct PluginState {
+    engine: Engine,
+    dir: Mutex<Option<PathBuf>>,
+    plugins: Mutex<Vec<Plugin>>,
+}
+
+impl Default for PluginState {
+    fn default() -> Self {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Self {
+            engine: Engine::new(&config).expect("wasm engine configuration is valid"),
+            dir: Mutex::new(None),
+            plugins: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+/// What a running plugin can reach: the store, through the `yaks` host functions.
+struct Host {
+    store: Store,
+    /// Appends are registered with shutdown through it; there's none in tests
+    app: Option<AppHandle>,
+    runtime: tokio::runtime::Handle,
+    plugin: String,
+    appended: Vec<Frame>,
+}
+
+/// A frame a plugin asks to append through `yaks.append`.
+#[derive(Deserialize)]
+struct AppendRequest {
+    topic: String,
+    content: Option<String>,
+    meta: Option<serde_json::Value>,
+}
+
+fn read_guest(caller: &mut Caller<'_, Host>, ptr: i32, len: i32) -> anyhow::Result<Vec<u8>> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| anyhow!("plugin does not export memory"))?;
+    let mut bytes = vec![0; usize::try_from(len)?];
+    memory.read(&caller, usize::try_from(ptr)?, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Host functions, imported by plugins from the `yaks` module:
+///
+/// - `log(ptr, len)` writes a message to the app log
+/// - `cas_read(hash_ptr, hash_len, out_ptr, out_len) -> i32` copies content into the guest
+///   buffer if it fits and returns its length (so a short buffer can be retried), or -1
+/// - `append(ptr, len) -> i32` appends the frame described by a JSON
+///   `{"topic", "content"?, "meta"?}` and returns 0, or -1 on failure
+fn linker(engine: &Engine) -> anyhow::Result<Linker<Host>> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap(
+        "yaks",
+        "log",
+        |mut caller: Caller<'_, Host>, ptr: i32, len: i32| -> anyhow::Result<()> {
+            let message = read_guest(&mut caller, ptr, len)?;
+            let plugin = &caller.data().plugin;
+            eprintln!("[plugin {plugin}] {}", String::from_utf8_lossy(&message));
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "yaks",
+        "cas_read",
+        |mut caller: Caller<'_, Host>,
+         hash_ptr: i32,
+         hash_len: i32,
+         out_ptr: i32,
+         out_len: i32|
+         -> anyhow::Result<i32> {
+            let hash = String::from_utf8(read_guest(&mut caller, hash_ptr, hash_len)?)?;
+            let Ok(hash) = hash.parse::<ssri::Integrity>() else {
+                return Ok(-1);
+            };
+            if !permissions::can_read(Integration::Plugins, None) {
+                return Ok(-1);
+            }
+            let host = caller.data();
+            let Ok(content) = host.runtime.block_on(host.store.cas_read(&hash)) else {
+                return Ok(-1);
+            };
+            let len = i32::try_from(content.len())?;
+            if len <= out_len {
+                let memory = caller
+                    .get_export("memory")
+                    .and_then(|export| export.into_memory())
+                    .ok_or_else(|| anyhow!("plugin does not export memory"))?;
+                memory.write(&mut caller, usize::try_from(out_ptr)?, &content)?;
+            }
+            Ok(len)
+        },
+    )?;
+    linker.func_wrap(
+        "yaks",
+        "append",
+        |mut caller: Caller<'_, Host>, ptr: i32, len: i32| -> anyhow::Result<i32> {
+            let request = read_guest(&mut caller, ptr, len)?;
+            let Ok(request) = serde_json::from_slice::<AppendRequest>(&request) else {
+                return Ok(-1);
+            };
+            let host = caller.data_mut();
+            let guard = host.app.as_ref().map(shutdown::begin_write).transpose();
+            let _guard = match guard {
+                Ok(guard) => guard,
+                Err(e) => {
+                    eprintln!("[plugin {}] {e}", host.plugin);
+                    return Ok(-1);
+                }
+            };
+            let mut meta = match request.meta {
+                Some(serde_json::Value::Object(meta)) => meta,
+                _ => serde_json::Map::new(),
+            };
+            meta.insert("plugin".into(), host.plugin.clone().into());
+            let content = request.content.as_deref().map(str::as_bytes);
+            let append = append_frame(&host.store, &request.topic, content, Some(meta.into()));
+            let appended = host
+                .runtime
+                .block_on(permissions::scope(Integration::Plugins, append));
+            match appended {
+                Ok(frame) => {
+                    host.appended.push(frame);
+                    Ok(0)
+                }
+                Err(e) => {
+                    eprintln!("[plugin {}] {e}", host.plugin);
+                    Ok(-1)
+                }
+            }
+        },
+    )?;
+    Ok(linker)
+}
+
+/// Calls the plugin's `on_frame(ptr, len)` export with the frame as JSON, placed in a buffer
+/// from its `alloc(len) -> ptr` export. Each call gets a fresh instance.
+fn call(engine: &Engine, module: &Module, host: Host, frame: &Frame) -> anyhow::Result<Vec<Frame>> {
+    let linker = linker(engine)?;
+    let mut store = wasmtime::Store::new(engine, host);
+    store.set_fuel(FUEL_PER_FRAME)?;
+    let instance = linker.instantiate(&mut store, module)?;
+    let memory = instance
+        .get_memory(&mut store, "memory")
+        .context("plugin does not export memory")?;
+    let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+    let on_frame = instance.get_typed_func::<(i32, i32), ()>(&mut store, "on_frame")?;
+
+    let input = serde_json::to_vec(frame)?;
+    let len = i32::try_from(input.len())?;
+    let ptr = alloc.call(&mut store, len)?;
+    memory.write(&mut store, usize::try_from(ptr)?, &input)?;
+    on_frame.call(&mut store, (ptr, len))?;
+    Ok(store.into_data().appended)
+}
+
+fn wants(info: &PluginInfo, frame: &Frame) -> bool {
+    let own = frame
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.get("plugin"))
+        .and_then(|plugin| plugin.as_str())
+        == Some(info.name.as_str());
+    info.enabled
+        && !own
+        && !frame.topic.starts_with("xs.")
+        && (info.topics.is_empty()
+            || info
+                .topics
+                .iter()
+                .any(|topic| frame.topic.starts_with(topic.as_str())))
+}
+
+fn load_plugins(engine: &Engine, dir: &Path, config: &PluginConfig) -> Vec<Plugin> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let manifest: Manifest = std::fs::read(path.with_extension("json"))
+                .ok()
+                .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+                .unwrap_or_default();
+            let module = Module::from_file(engine, &path);
+            Plugin {
+                info: PluginInfo {
+                    enabled: config.enabled.contains(&name),
+                    name,
+                    path: path.to_string_lossy().into_owned(),
+                    topics: manifest.topics,
+                    error: module.as_ref().err().map(|e| format!("{e:#}")),
+                },
+                module: module.ok(),
+            }
+        })
+        .collect()
+}
+
+async fn reload(state: &PluginState, store: &Store) {
+    let Some(dir) = state.dir.lock().await.clone() else {
+        return;
+    };
+    let config: PluginConfig = load_setting(store, CONFIG_TOPIC).await;
+    let engine = state.engine.clone();
+    let plugins = tokio::task::spawn_blocking(move || load_plugins(&engine, &dir, &config))
+        .await
+        .unwrap_or_default();
+    *state.plugins.lock().await = plugins;
+}
+
+/// Runs enabled plugins on each new frame, once history has been read.
+async fn watch(app: AppHandle, store: Store) {
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    let mut caught_up = false;
+    while let Some(frame) = rx.recv().await {
+        if frame.topic == "xs.threshold" {
+            caught_up = true;
+            continue;
+        }
+        if !caught_up {
+            continue;
+        }
+        let state = app.state::<PluginState>();
+        for plugin in state.plugins.lock().await.iter() {
+            let Some(module) = plugin
+                .module
+                .clone()
+                .filter(|_| wants(&plugin.info, &frame))
+                .filter(|_| permissions::triggers(Integration::Plugins, &frame))
+            else {
+                continue;
+            };
+            let host = Host {
+                store: store.clone(),
+                app: Some(app.clone()),
+                runtime: tokio::runtime::Handle::current(),
+                plugin: plugin.info.name.clone(),
+                appended: Vec::new(),
+            };
+            let (engine, frame, app) = (state.engine.clone(), frame.clone(), app.clone());
+            tokio::task::spawn_blocking(move || {
+                let name = host.plugin.clone();
+                match call(&engine, &module, host, &frame) {
+                    Ok(appended) => {
+                        for frame in appended {
+                            let _ = emit_frame(&app, &frame);
+                        }
+                    }
+                    Err(e) => eprintln!("[plugin {name}] failed on {}: {e:#}", frame.id),
+                }
+            });
+        }
+    }
+}
+
+pub(crate) async fn initialize(app: &AppHandle, store: &Store) {
+    let dir = match profiles::data_dir(app) {
+        Ok(dir) => dir.join("plugins"),
+        Err(e) => {
+            eprintln!("Plugins disabled: {e}");
+            return;
+        }
+    };
+    let state = app.state::<PluginState>();
+    *state.dir.lock().await = Some(dir);
+    reload(&state, store).await;
+    tokio::spawn(watch(app.clone(), store.clone()));
+}
+
+/// The plugins found in the plugins directory, rescanned on every call.
+#[tauri::command]
+pub async fn list_plugins(
+    store: State<'_, Store>,
+    state: State<'_, PluginState>,
+) -> Result<Vec<PluginInfo>, String> {
+    reload(&state, &store).await;
+    let plugins = state.plugins.lock().await;
+    Ok(plugins.iter().map(|plugin| plugin.info.clone()).collect())
+}
+
+#[tauri::command]
+pub async fn enable_plugin(
+    store: State<'_, Store>,
+    state: State<'_, PluginState>,
+    name: String,
+    enabled: bool,
+) -> Result<Vec<PluginInfo>, String> {
+    let mut config: PluginConfig = load_setting(&store, CONFIG_TOPIC).await;
+    if enabled {
+        config.enabled.insert(name);
+    } else {
+        config.enabled.remove(&name);
+    }
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    list_plugins(store, state).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    // A plugin that appends `{"topic":"seen"}` for every frame it's given
+    const PLUGIN: &str = r#"(module
+        (import "yaks" "append" (func $append (param i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "{\"topic\":\"seen\"}")
+        (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+        (func (export "on_frame") (param i32 i32)
+            (drop (call $append (i32.const 0) (i32.const 16)))))"#;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_plugin_appends_frames() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let state = PluginState::default();
+        let module = Module::new(&state.engine, PLUGIN).unwrap();
+        let frame = append_frame(
+            &store,
+            "note.create",
+            None,
+            Some(serde_json::json!({ "yak_id": "y" })),
+        )
+        .await
+        .unwrap();
+
+        let host = Host {
+            store: store.clone(),
+            app: None,
+            runtime: tokio::runtime::Handle::current(),
+            plugin: "echo".to_string(),
+            appended: Vec::new(),
+        };
+        let engine = state.engine.clone();
+        let appended = tokio::task::spawn_blocking(move || call(&engine, &module, host, &frame))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(appended.len(), 1);
+        assert_eq!(appended[0].topic, "seen");
+        assert_eq!(appended[0].meta.as_ref().unwrap()["plugin"], "echo");
+    }
+}
@@ -0,0 +1,224 @@
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use xs::store::{FollowOption, Frame, ReadOptions, Store, TTL, ZERO_CONTEXT};
+
+use crate::provenance::{self, Source};
+
+pub(crate) const TOPIC: &str = "presence.update";
+/// How long an update counts, and how long the store keeps it; windows renew theirs well
+/// within it while they stay on a note.
+pub(crate) const LIFETIME: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Presence {
+    /// The device, as stamped on its frames, and the window on it
+    pub device: String,
+    pub window: String,
+    pub this_device: bool,
+    pub yak_id: String,
+    pub note_id: Option<String>,
+    pub editing: bool,
+    /// The latest update's frame id
+    pub frame_id: String,
+}
+
+/// Who is where, by device and window, with the time (unix ms) each was last heard from.
+#[derive(Default)]
+pub struct PresenceState {
+    entries: Mutex<HashMap<(String, String), (u64, Option<Presence>)>>,
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+/// The TTL presence frames are appended with, here or when synced in.
+pub(crate) fn ttl(topic: &str) -> Option<TTL> {
+    (topic == TOPIC).then_some(TTL::Time(LIFETIME))
+}
+
+/// A presence update too old to count any more, which sync leaves behind.
+pub(crate) fn is_stale(frame: &Frame) -> bool {
+    frame.topic == TOPIC
+        && now_ms().saturating_sub(frame.id.timestamp()) > LIFETIME.as_millis() as u64
+}
+
+fn meta_str(frame: &Frame, key: &str) -> Option<String> {
+    frame.meta.as_ref()?.get(key)?.as_str().map(String::from)
+}
+
+/// The window a presence frame is from, and where it is; nowhere once it has left.
+fn presence_from(frame: &Frame) -> Option<((String, String), Option<Presence>)> {
+    let (device, this_device) = provenance::device(frame)?;
+    let window = meta_str(frame, "window")?;
+    let presence = meta_str(frame, "yak_id").map(|yak_id| Presence {
+        device: device.clone(),
+        window: window.clone(),
+        this_device,
+        yak_id,
+        note_id: meta_str(frame, "note_id"),
+        editing: frame
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.get("editing"))
+            .and_then(|editing| editing.as_bool())
+            .unwrap_or(false),
+        frame_id: frame.id.to_string(),
+    });
+    Some(((device, window), presence))
+}
+
+impl PresenceState {
+    /// Records a presence frame, returning the yaks whose presence changed.
+    fn presence(&self, frame: &Frame) -> Vec<String> {
+        let Some((key, presence)) = presence_from(frame) else {
+            return Vec::new();
+        };
+        let mut entries = self.entries.lock().unwrap();
+        let previous = entries.insert(key, (frame.id.timestamp(), presence.clone()));
+        let mut changed: Vec<String> = [previous.and_then(|(_, presence)| presence), presence]
+            .into_iter()
+            .flatten()
+            .map(|presence| presence.yak_id)
+            .collect();
+        changed.dedup();
+        changed
+    }
+
+    /// Everyone on the yak as of `now` (unix ms).
+    fn on_yak(&self, yak_id: &str, now: u64) -> Vec<Presence> {
+        let lifetime = LIFETIME.as_millis() as u64;
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (seen, _)| now.saturating_sub(*seen) <= lifetime);
+        let mut found: Vec<Presence> = entries
+            .values()
+            .filter_map(|(_, presence)| presence.clone())
+            .filter(|presence| presence.yak_id == yak_id)
+            .collect();
+        found.sort_by(|a, b| (&a.device, &a.window).cmp(&(&b.device, &b.window)));
+        found
+    }
+}
+
+/// Follows presence updates from this device's windows and synced peers, telling windows
+/// who is on each yak as it changes.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    while let Some(frame) = rx.recv().await {
+        if frame.topic != TOPIC || is_stale(&frame) {
+            continue;
+        }
+        let state = app.state::<PresenceState>();
+        for yak_id in state.update(&frame) {
+            let present = state.on_yak(&yak_id, now_ms());
+            let payload = json!({ "yak_id": yak_id, "present": present });
+            if let Err(e) = app.emit("presence", payload) {
+                eprintln!("Failed to emit presence: {e}");
+            }
+        }
+    }
+}
+
+/// Tells other windows and synced peers where this window is: viewing a yak, on a note, or
+/// editing it. Windows send this as the user moves around and every ~10s while they stay,
+/// and with no `yak_id` when they leave. Updates expire on their own and are only kept
+/// briefly; windows hear "presence" with the yak's list as it changes.
+#[tauri::command]
+pub async fn set_presence(
+    store: State<'_, Store>,
+    window: tauri::WebviewWindow,
+    yak_id: Option<String>,
+    note_id: Option<String>,
+    editing: Option<bool>,
+) -> Result<(), String> {
+    let mut meta = Some(json!({
+        "window": window.label(),
+        "yak_id": yak_id,
+        "note_id": note_id,
+        "editing": editing.unwrap_or(false),
+    }));
+    provenance::stamp(&mut meta, Source::Ui);
+    let frame = Frame {
+        id: scru128::new(),
+        context_id: ZERO_CONTEXT,
+        topic: TOPIC.to_string(),
+        hash: None,
+        meta,
+        ttl: ttl(TOPIC),
+    };
+    crate::append_to_store(&store, frame).map_err(|e| e.context("Failed to append presence"))?;
+    Ok(())
+}
+
+/// Who is viewing or editing what in a yak, across windows and synced devices.
+#[tauri::command]
+pub async fn get_presence(
+    state: State<'_, PresenceState>,
+    yak_id: String,
+) -> Result<Vec<Presence>, String> {
+    Ok(state.on_yak(&yak_id, now_ms()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+
+    fn presence(
+        store: &Store,
+        device: &str,
+        window: &str,
+        yak_id: Option<&str>,
+        editing: bool,
+    ) -> Frame {
+        let mut meta = json!({
+            "window": window,
+            "yak_id": yak_id,
+            "note_id": "n1",
+            "editing": editing,
+        });
+        meta[provenance::KEY] = json!({ "device": device, "version": "1.0.0", "source": "ui" });
+        append(store, TOPIC, Some(meta))
+    }
+
+    #[tokio::test]
+    async fn test_presence() {
+        let (_dir, store) = testing::store();
+        let frame =
+            |device, window, yak_id, editing| presence(&store, device, window, yak_id, editing);
+        let state = PresenceState::default();
+        assert_eq!(
+            state.update(&frame("laptop", "main", Some("y1"), false)),
+            vec!["y1"]
+        );
+        state.update(&frame("phone", "main", Some("y1"), true));
+        state.update(&frame("laptop", "note-2", Some("y2"), true));
+        let now = now_ms();
+        let present = state.on_yak("y1", now);
+        let who: Vec<(&str, bool)> = present
+            .iter()
+            .map(|presence| (presence.device.as_str(), presence.editing))
+            .collect();
+        assert_eq!(who, vec![("laptop", false), ("phone", true)]);
+
+        // Moving on, or leaving, takes a window off the yak it was on
+        let moved = state.update(&frame("laptop", "main", Some("y2"), false));
+        assert_eq!(moved, vec!["y1", "y2"]);
+        assert_eq!(
+            state.update(&frame("phone", "main", None, false)),
+            vec!["y1"]
+        );
+        assert!(state.on_yak("y1", now).is_empty());
+        assert_eq!(state.on_yak("y2", now).len(), 2);
+
+        // Updates not renewed expire
+        let later = now + LIFETIME.as_millis() as u64 + 1;
+        assert!(state.on_yak("y2", later).is_empty());
+        assert!(!is_stale(&frame("laptop", "main", Some("y2"), false)));
+    }
+}
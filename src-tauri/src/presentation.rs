@@ -0,0 +1,176 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::append_frame;
+use crate::projection::{Note, Projection};
+use crate::shutdown::begin_write;
+use crate::windows::emit_frame;
+
+const PRIVATE_TOPIC: &str = "yak.private";
+/// The shortcut action that flips presentation mode (see `shortcuts`).
+pub(crate) const TOGGLE_ACTION: &str = "presentation.toggle";
+
+/// Whether the screen is being shared. It starts off with each launch.
+#[derive(Default)]
+pub struct PresentationState {
+    enabled: AtomicBool,
+}
+
+impl PresentationState {
+    /// Yaks to keep off screen: the private ones, while presenting.
+    fn hidden(&self, projection: &Projection) -> HashSet<String> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return HashSet::new();
+        }
+        projection
+            .yaks
+            .values()
+            .filter(|yak| yak.private)
+            .map(|yak| yak.id.clone())
+            .collect()
+    }
+
+    /// Removes private yaks, their notes and their tasks while presenting.
+    pub(crate) fn redact(&self, projection: &mut Projection) {
+        let hidden = self.hidden(projection);
+        if hidden.is_empty() {
+            return;
+        }
+        projection.yaks.retain(|id, _| !hidden.contains(id));
+        projection.notes_by_yak.retain(|id, _| !hidden.contains(id));
+        projection
+            .notes
+            .retain(|_, note| !hidden.contains(&note.yak_id));
+        projection
+            .tasks
+            .retain(|_, task| !hidden.contains(&task.yak_id));
+    }
+
+    /// Keeps hits from private yaks in the results while presenting, with everything they
+    /// would show withheld.
+    pub(crate) fn blur(&self, projection: &Projection, notes: &mut [Note]) {
+        let hidden = self.hidden(projection);
+        for note in notes
+            .iter_mut()
+            .filter(|note| hidden.contains(&note.yak_id))
+        {
+            note.content = None;
+            note.meta = None;
+            note.tags.clear();
+            note.attachments.clear();
+            note.blurred = true;
+        }
+    }
+}
+
+/// Applies the latest `yak.private` marks in `frames` to a projection folded from fewer of
+/// them, so a yak made private later is hidden in views of its past too.
+pub(crate) fn mark_private(frames: &[Frame], projection: &mut Projection) {
+    for frame in frames.iter().filter(|frame| frame.topic == PRIVATE_TOPIC) {
+        let Some(meta) = frame.meta.as_ref() else {
+            continue;
+        };
+        let yak_id = meta.get("yak_id").and_then(|v| v.as_str());
+        let private = meta.get("private").and_then(|v| v.as_bool());
+        if let (Some(yak_id), Some(private)) = (yak_id, private) {
+            if let Some(yak) = projection.yaks.get_mut(yak_id) {
+                yak.private = private;
+            }
+        }
+    }
+}
+
+fn set(app: &AppHandle, enabled: bool) {
+    app.state::<PresentationState>()
+        .enabled
+        .store(enabled, Ordering::Relaxed);
+    if let Err(e) = app.emit("presentation-mode", enabled) {
+        eprintln!("Failed to emit presentation mode: {e}");
+    }
+}
+
+pub(crate) fn toggle(app: &AppHandle) {
+    let enabled = app
+        .state::<PresentationState>()
+        .enabled
+        .load(Ordering::Relaxed);
+    set(app, !enabled);
+}
+
+/// Turns presentation mode on or off. While it's on, yaks marked private are left out of
+/// snapshots and search hits from them are blurred. Windows hear "presentation-mode".
+#[tauri::command]
+pub async fn presentation_mode(app: AppHandle, enabled: bool) -> Result<bool, String> {
+    set(&app, enabled);
+    Ok(enabled)
+}
+
+#[tauri::command]
+pub async fn get_presentation_mode(state: State<'_, PresentationState>) -> Result<bool, String> {
+    Ok(state.enabled.load(Ordering::Relaxed))
+}
+
+/// Marks a yak as private, to be hidden in presentation mode, or clears the mark.
+#[tauri::command]
+pub async fn set_yak_private(
+    app: AppHandle,
+    store: State<'_, Store>,
+    yak_id: String,
+    private: bool,
+) -> Result<(), String> {
+    let _guard = begin_write(&app)?;
+    let meta = serde_json::json!({ "yak_id": yak_id, "private": private });
+    let frame = append_frame(&store, PRIVATE_TOPIC, None, Some(meta)).await?;
+    let _ = emit_frame(&app, &frame);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_all_frames;
+    use crate::testing::{self, append};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_presentation_hides_private_yaks() {
+        let (_dir, store) = testing::store();
+        let work = append(&store, "yak.create", Some(json!({ "name": "work" })));
+        let home = append(&store, "yak.create", Some(json!({ "name": "home" })));
+        let (work_id, home_id) = (work.id.to_string(), home.id.to_string());
+        append(&store, "note.create", Some(json!({ "yak_id": work_id })));
+        append(&store, "note.create", Some(json!({ "yak_id": home_id })));
+        let private = json!({ "yak_id": home_id, "private": true });
+        append(&store, PRIVATE_TOPIC, Some(private));
+        let frames = read_all_frames(&store).await;
+        let projection = Projection::from_frames(&frames);
+        assert!(projection.yaks[&home_id].private);
+
+        let state = PresentationState::default();
+        let mut snapshot = projection.clone();
+        state.redact(&mut snapshot);
+        assert_eq!(snapshot.yaks.len(), 2);
+
+        state.enabled.store(true, Ordering::Relaxed);
+        let mut snapshot = projection.clone();
+        state.redact(&mut snapshot);
+        assert_eq!(snapshot.yaks.keys().collect::<Vec<_>>(), vec![&work_id]);
+        assert!(snapshot.notes.values().all(|note| note.yak_id == work_id));
+
+        let mut notes: Vec<Note> = projection.notes.values().cloned().collect();
+        state.blur(&projection, &mut notes);
+        for note in &notes {
+            assert_eq!(note.blurred, note.yak_id == home_id);
+            assert_eq!(note.meta.is_none(), note.yak_id == home_id);
+        }
+
+        // A view of the log from before the yak was made private hides it too
+        let mut past = Projection::from_frames(&frames[..4]);
+        assert!(!past.yaks[&home_id].private);
+        mark_private(&frames, &mut past);
+        state.redact(&mut past);
+        assert!(!past.yaks.contains_key(&home_id));
+    }
+}
@@ -0,0 +1,497 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use xs::store::Store;
+
+use crate::location::{self, StoreLocation};
+use crate::{mail, read_all_frames, sync, task_sync, webhooks};
+
+/// File in the app data dir listing the profiles and which one opens.
+const PROFILES_FILE: &str = "profiles.json";
+/// The profile that predates profiles, kept in the app data dir itself.
+const DEFAULT: &str = "default";
+/// Opens a profile for this run without changing the active one, e.g. `--profile work`.
+const FLAG: &str = "--profile";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Profiles {
+    active: String,
+    names: Vec<String>,
+    ask_on_launch: bool,
+    /// Set by `switch_profile` so the restart onto the chosen profile doesn't ask again
+    #[serde(default)]
+    chosen: bool,
+    /// Sandbox profiles made by `fork_store`, with the profile each was forked from
+    #[serde(default)]
+    forks: BTreeMap<String, String>,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT.to_string(),
+            names: vec![DEFAULT.to_string()],
+            ask_on_launch: false,
+            chosen: false,
+            forks: BTreeMap::new(),
+        }
+    }
+}
+
+/// The profile this run uses. Its dir holds the store, or the pointer to a moved one, and
+/// everything kept beside it: indexes, the outbox, plugins and window cursors. Settings,
+/// sync config and lock keys live in the store, so each profile has its own.
+pub struct ActiveProfile {
+    pub name: String,
+    pub dir: PathBuf,
+    /// Whether the launch picker has been answered, or wasn't needed
+    picked: AtomicBool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileInfo {
+    pub name: String,
+    pub dir: PathBuf,
+    pub active: bool,
+    /// The profile this one is a fork of
+    pub fork_of: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileList {
+    pub profiles: Vec<ProfileInfo>,
+    pub ask_on_launch: bool,
+    /// Whether the window should show the picker now
+    pub pick: bool,
+}
+
+fn load(app_data_dir: &Path) -> Profiles {
+    std::fs::read(app_data_dir.join(PROFILES_FILE))
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save(app_data_dir: &Path, profiles: &Profiles) -> Result<(), String> {
+    let json = serde_json::to_vec_pretty(profiles)
+        .map_err(|e| format!("Failed to serialize profiles: {e}"))?;
+    let path = app_data_dir.join(PROFILES_FILE);
+    let staged = app_data_dir.join(format!("{PROFILES_FILE}.new"));
+    std::fs::write(&staged, json).map_err(|e| format!("Failed to save profiles: {e}"))?;
+    std::fs::rename(&staged, &path).map_err(|e| format!("Failed to save profiles: {e}"))
+}
+
+/// Where a profile keeps its data: the app data dir for the default profile, as before
+/// profiles existed, and `profiles/<name>` in it for the rest.
+fn profile_dir(app_data_dir: &Path, name: &str) -> PathBuf {
+    match name {
+        DEFAULT => app_data_dir.to_path_buf(),
+        name => app_data_dir.join("profiles").join(name),
+    }
+}
+
+/// The profile named by `--profile`, if any.
+fn flagged(args: impl IntoIterator<Item = String>) -> Option<String> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == FLAG {
+            return args.next();
+        }
+        if let Some(name) = arg.strip_prefix(&format!("{FLAG}=")) {
+            return Some(name.to_string());
+        }
+    }
+    None
+}
+
+/// The data dir of the profile named by `--profile` in `args`, else of the active one, for
+/// the command line, which has no `AppHandle`.
+pub(crate) fn resolve_dir(app_data_dir: &Path, args: impl IntoIterator<Item = String>) -> PathBuf {
+    let name = flagged(args).unwrap_or_else(|| load(app_data_dir).active);
+    profile_dir(app_data_dir, &name)
+}
+
+fn validate(name: &str) -> Result<(), String> {
+    let valid = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if name.is_empty() || name.len() > 64 || !valid {
+        return Err("Profile names use letters, digits, - and _".to_string());
+    }
+    Ok(())
+}
+
+fn app_data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {e}"))
+}
+
+/// Picks this run's profile: the one named by `--profile`, else the active one.
+pub(crate) fn initialize(app: &AppHandle) {
+    let app_data_dir = match app_data_dir(app) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Profiles disabled: {e}");
+            return;
+        }
+    };
+    let mut profiles = load(&app_data_dir);
+    let flagged = flagged(std::env::args()).filter(|name| {
+        let known = profiles.names.contains(name);
+        if !known {
+            eprintln!("Unknown profile {name}, opening {}", profiles.active);
+        }
+        known
+    });
+    // A profile named on the command line or picked before a restart isn't asked about again
+    let picked = flagged.is_some() || profiles.chosen || !profiles.ask_on_launch;
+    // A restart keeps the command line it launched with, so a switch outranks `--profile`
+    let name = match flagged {
+        Some(name) if !profiles.chosen => name,
+        _ => profiles.active.clone(),
+    };
+    if profiles.chosen {
+        profiles.chosen = false;
+        if let Err(e) = save(&app_data_dir, &profiles) {
+            eprintln!("{e}");
+        }
+    }
+    let dir = profile_dir(&app_data_dir, &name);
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        eprintln!("Failed to create profile dir {}: {e}", dir.display());
+    }
+    println!("Using profile {name}");
+    app.manage(ActiveProfile {
+        name,
+        dir,
+        picked: AtomicBool::new(picked),
+    });
+}
+
+/// The running profile's data dir, in place of the app data dir.
+pub(crate) fn data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    match app.try_state::<ActiveProfile>() {
+        Some(profile) => Ok(profile.dir.clone()),
+        None => app_data_dir(app),
+    }
+}
+
+/// Writes a file in a profile's dir that only this user can read, for credentials kept out
+/// of the store.
+pub(crate) fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(bytes)
+}
+
+#[tauri::command]
+pub async fn list_profiles(
+    app: AppHandle,
+    active: State<'_, ActiveProfile>,
+) -> Result<ProfileList, String> {
+    let app_data_dir = app_data_dir(&app)?;
+    let profiles = load(&app_data_dir);
+    Ok(ProfileList {
+        profiles: profiles
+            .names
+            .iter()
+            .map(|name| ProfileInfo {
+                name: name.clone(),
+                dir: profile_dir(&app_data_dir, name),
+                active: *name == active.name,
+                fork_of: profiles.forks.get(name).cloned(),
+            })
+            .collect(),
+        ask_on_launch: profiles.ask_on_launch,
+        pick: profiles.names.len() > 1 && !active.picked.load(Ordering::Relaxed),
+    })
+}
+
+/// Whether a profile's store lives in `dir`, as a promoted fork's does in the dir the fork
+/// had, which a new profile of the same name mustn't take over.
+fn in_use(app_data_dir: &Path, profiles: &Profiles, dir: &Path) -> bool {
+    profiles
+        .names
+        .iter()
+        .any(|name| location::resolve(&profile_dir(app_data_dir, name)).starts_with(dir))
+}
+
+/// Turns off everything in a forked store that reaches outside it: sync, with the node and
+/// device keys, mail, task sync and webhooks. Otherwise the fork would push its experiments
+/// to the same remotes, as the same device.
+async fn detach(fork: &Store) -> Result<(), String> {
+    let frames = read_all_frames(fork).await;
+    sync::detach(fork, &frames)?;
+    mail::detach(fork, &frames)?;
+    task_sync::detach(fork, &frames)?;
+    webhooks::detach(fork, &frames).await
+}
+
+/// Adds an empty profile. Its store is created the first time it's opened.
+#[tauri::command]
+pub async fn create_profile(app: AppHandle, name: String) -> Result<ProfileInfo, String> {
+    validate(&name)?;
+    let app_data_dir = app_data_dir(&app)?;
+    let mut profiles = load(&app_data_dir);
+    if profiles.names.contains(&name) {
+        return Err(format!("Profile {name} already exists"));
+    }
+    let dir = profile_dir(&app_data_dir, &name);
+    if in_use(&app_data_dir, &profiles, &dir) {
+        return Err(format!("{name} still holds a promoted fork's store"));
+    }
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    profiles.names.push(name.clone());
+    save(&app_data_dir, &profiles)?;
+    Ok(ProfileInfo {
+        name,
+        dir,
+        active: false,
+        fork_of: None,
+    })
+}
+
+/// Makes `name` the profile that opens, restarting onto it unless it's already running.
+/// Returns whether a restart is coming. Also answers the launch picker.
+#[tauri::command]
+pub async fn switch_profile(
+    app: AppHandle,
+    active: State<'_, ActiveProfile>,
+    name: String,
+) -> Result<bool, String> {
+    let app_data_dir = app_data_dir(&app)?;
+    let mut profiles = load(&app_data_dir);
+    if !profiles.names.contains(&name) {
+        return Err(format!("No profile named {name}"));
+    }
+    active.picked.store(true, Ordering::Relaxed);
+    let restart = active.name != name;
+    if profiles.active != name || restart {
+        profiles.active = name;
+        profiles.chosen = restart;
+        save(&app_data_dir, &profiles)?;
+    }
+    if !restart {
+        return Ok(false);
+    }
+
+    restart_soon(app);
+    Ok(true)
+}
+
+/// Restarts onto the profile that opens, after giving the response a moment to reach the
+/// window.
+fn restart_soon(app: AppHandle) {
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        app.restart();
+    });
+}
+
+/// Copies the running store into a new sandbox profile `name` to try destructive things in,
+/// such as imports, compaction or migrations. Content is hard-linked where the filesystem
+/// allows, so a fork costs little more than its frames. Open it with `switch_profile`; the
+/// original is untouched until the fork is promoted. Sync and the other integrations that
+/// reach outside the store start off in the fork.
+#[tauri::command]
+pub async fn fork_store(
+    app: AppHandle,
+    store: State<'_, Store>,
+    location: State<'_, StoreLocation>,
+    active: State<'_, ActiveProfile>,
+    name: String,
+) -> Result<ProfileInfo, String> {
+    validate(&name)?;
+    let app_data_dir = app_data_dir(&app)?;
+    let mut profiles = load(&app_data_dir);
+    if profiles.names.contains(&name) {
+        return Err(format!("Profile {name} already exists"));
+    }
+    let dir = profile_dir(&app_data_dir, &name);
+    if in_use(&app_data_dir, &profiles, &dir) {
+        return Err(format!("{name} still holds a promoted fork's store"));
+    }
+    let forked = match location::copy_store(&store, &location.0, &dir.join("store"), true).await {
+        Ok(fork) => detach(&fork).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = forked {
+        let _ = std::fs::remove_dir_all(&dir);
+        return Err(e);
+    }
+    profiles.names.push(name.clone());
+    profiles.forks.insert(name.clone(), active.name.clone());
+    save(&app_data_dir, &profiles)?;
+    Ok(ProfileInfo {
+        name,
+        dir,
+        active: false,
+        fork_of: Some(active.name.clone()),
+    })
+}
+
+/// Deletes fork `name` and everything in it. A running fork has to be switched away from
+/// first.
+#[tauri::command]
+pub async fn discard_fork(
+    app: AppHandle,
+    active: State<'_, ActiveProfile>,
+    name: String,
+) -> Result<(), String> {
+    let app_data_dir = app_data_dir(&app)?;
+    let mut profiles = load(&app_data_dir);
+    let parent = profiles
+        .forks
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| format!("{name} isn't a fork"))?;
+    if active.name == name {
+        return Err("Switch to another profile before discarding this fork".to_string());
+    }
+    let dir = profile_dir(&app_data_dir, &name);
+    std::fs::remove_dir_all(&dir)
+        .map_err(|e| format!("Failed to remove {}: {e}", dir.display()))?;
+    profiles.names.retain(|profile| *profile != name);
+    profiles.forks.remove(&name);
+    // Forks of this fork now stem from its parent
+    for fork_of in profiles.forks.values_mut() {
+        if *fork_of == name {
+            *fork_of = parent.clone();
+        }
+    }
+    if profiles.active == name {
+        profiles.active = parent;
+    }
+    save(&app_data_dir, &profiles)
+}
+
+/// Makes fork `name`'s store the one its parent profile opens, then restarts onto the
+/// parent. The fork stops being a profile of its own. The parent's old store stays on disk
+/// where it was, so nothing is lost if the experiment turns out badly after all. The fork's
+/// store stays in the fork's dir, so its name can't be used again while the parent opens it.
+#[tauri::command]
+pub async fn promote_fork(app: AppHandle, name: String) -> Result<(), String> {
+    let app_data_dir = app_data_dir(&app)?;
+    let mut profiles = load(&app_data_dir);
+    let parent = profiles
+        .forks
+        .remove(&name)
+        .ok_or_else(|| format!("{name} isn't a fork"))?;
+    if !profiles.names.contains(&parent) {
+        return Err(format!(
+            "{name} was forked from {parent}, which no longer exists"
+        ));
+    }
+    let store = location::resolve(&profile_dir(&app_data_dir, &name));
+    location::point(&profile_dir(&app_data_dir, &parent), &store)?;
+    profiles.names.retain(|profile| *profile != name);
+    for fork_of in profiles.forks.values_mut() {
+        if *fork_of == name {
+            *fork_of = parent.clone();
+        }
+    }
+    profiles.active = parent;
+    profiles.chosen = true;
+    save(&app_data_dir, &profiles)?;
+    restart_soon(app);
+    Ok(())
+}
+
+/// Sets whether launching asks which profile to open.
+#[tauri::command]
+pub async fn set_profile_picker(app: AppHandle, enabled: bool) -> Result<(), String> {
+    let app_data_dir = app_data_dir(&app)?;
+    let mut profiles = load(&app_data_dir);
+    profiles.ask_on_launch = enabled;
+    save(&app_data_dir, &profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_profiles() {
+        let dir = tempdir().unwrap();
+        assert_eq!(load(dir.path()), Profiles::default());
+        assert_eq!(profile_dir(dir.path(), DEFAULT), dir.path());
+        assert_eq!(
+            profile_dir(dir.path(), "work"),
+            dir.path().join("profiles").join("work")
+        );
+
+        let mut profiles = Profiles::default();
+        profiles.names.push("work".to_string());
+        profiles.active = "work".to_string();
+        profiles
+            .forks
+            .insert("work".to_string(), DEFAULT.to_string());
+        save(dir.path(), &profiles).unwrap();
+        assert_eq!(load(dir.path()), profiles);
+
+        // Profiles saved before forks existed still load
+        let old = r#"{"active":"default","names":["default"],"ask_on_launch":true}"#;
+        let old: Profiles = serde_json::from_str(old).unwrap();
+        assert!(old.forks.is_empty());
+
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            flagged(args(&["yaks", "--profile", "work"])).unwrap(),
+            "work"
+        );
+        assert_eq!(flagged(args(&["yaks", "--profile=home"])).unwrap(), "home");
+        assert!(flagged(args(&["yaks", "--demo"])).is_none());
+
+        assert!(validate("work-2").is_ok());
+        assert!(validate("../work").is_err());
+        assert!(validate("").is_err());
+
+        // A fork promoted into its parent keeps its dir from being handed out again
+        let work = profile_dir(dir.path(), "work");
+        std::fs::create_dir_all(&work).unwrap();
+        location::point(&work, &profile_dir(dir.path(), "trial").join("store")).unwrap();
+        assert!(in_use(
+            dir.path(),
+            &profiles,
+            &profile_dir(dir.path(), "trial")
+        ));
+        assert!(!in_use(
+            dir.path(),
+            &profiles,
+            &profile_dir(dir.path(), "other")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_detach_fork() {
+        let (_dir, fork) = crate::testing::store();
+        let config = sync::SyncConfig {
+            enabled: true,
+            url: Some("http://127.0.0.1:3021".to_string()),
+            follow: true,
+        };
+        crate::settings::save_setting(&fork, "sync.config", &config).unwrap();
+        let meta = serde_json::json!({ "url": "https://example.com", "secret": "s" });
+        let webhook = crate::testing::append(&fork, "webhook.add", Some(meta));
+
+        detach(&fork).await.unwrap();
+        let frames = read_all_frames(&fork).await;
+        let config: sync::SyncConfig = crate::settings::latest_setting(&frames, "sync.config");
+        assert!(!config.enabled && !config.follow);
+        let removed = frames.last().unwrap();
+        assert_eq!(removed.topic, "webhook.remove");
+        assert_eq!(
+            removed.meta.as_ref().unwrap()["webhook_id"],
+            webhook.id.to_string()
+        );
+    }
+}
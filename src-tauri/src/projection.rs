@@ -0,0 +1,927 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use xs::store::{Frame, Store};
+
+use crate::{compaction, locks, migrations, recovery, unread};
+
+/// Backend mirror of the frontend's yak/note projection (see `src/store/index.ts`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Yak {
+    pub id: String,
+    /// Optional display name from the `yak.create` meta; the frontend falls back to the id's time
+    pub name: Option<String>,
+    /// Id of the most recent frame touching this yak
+    pub last_activity: String,
+    /// Set by `yak.lock`: note content in this yak is sealed (see `locks`)
+    #[serde(default)]
+    pub locked: bool,
+    /// Set by `yak.private`: kept off screen in presentation mode (see `presentation`)
+    #[serde(default)]
+    pub private: bool,
+    /// Current notes still unread; only counted by `count_unread`
+    #[serde(default)]
+    pub unread: usize,
+    /// Set by `label.set`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<Label>,
+}
+
+/// A color and icon picked for a yak or note (see `labels`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Note {
+    pub id: String,
+    pub yak_id: String,
+    pub hash: Option<ssri::Integrity>,
+    /// If this note is an edit, the id of the note it replaced
+    pub edited_note_id: Option<String>,
+    /// Resolved CAS content; only populated by `resolve_content`. Left unset for notes of
+    /// locked yaks, which only `LockState::redact` opens
+    pub content: Option<String>,
+    /// Sealed content of a note in a locked yak, as `resolve_content` found it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealed: Option<String>,
+    /// Meta of the frame that created this revision
+    pub meta: Option<serde_json::Value>,
+    /// Tags, attachments and flags carry over from revision to revision
+    pub tags: BTreeSet<String>,
+    pub attachments: Vec<Attachment>,
+    pub pinned: bool,
+    pub archived: bool,
+    /// RFC 3339 time set by `reminder.set`, cleared by `reminder.clear`
+    pub reminder: Option<String>,
+    /// Set on search hits whose content was withheld in presentation mode
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub blurred: bool,
+    /// Typed key/value properties set by `note.property`; they carry over like tags
+    #[serde(default)]
+    pub properties: BTreeMap<String, serde_json::Value>,
+    /// Set on ingested notes until a `note.read` (see `unread`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unread: bool,
+    /// Set by `note.rename`; it carries over edits, so the name stays put when the first
+    /// line changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Set by `label.set`; it carries over edits like tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<Label>,
+}
+
+/// A board column and the notes in it, top to bottom (see `note.order`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BoardColumn {
+    pub name: String,
+    pub note_ids: Vec<String>,
+}
+
+/// A checklist item, created by `task.create` and changed by `task.update` / `task.delete`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Task {
+    pub id: String,
+    pub yak_id: String,
+    /// The note this task belongs to, if any
+    pub note_id: Option<String>,
+    pub hash: Option<ssri::Integrity>,
+    pub done: bool,
+    pub reminder: Option<String>,
+    pub meta: Option<serde_json::Value>,
+    /// Resolved CAS content; only populated by `resolve_content`
+    pub content: Option<String>,
+}
+
+/// A file attached to a note via an `attachment.add` frame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub hash: Option<ssri::Integrity>,
+    pub name: Option<String>,
+    pub mime: Option<String>,
+    pub meta: Option<serde_json::Value>,
+    /// Text derived from the file by background workers
+    pub extracts: Vec<Extract>,
+}
+
+/// Text derived from an attachment via a `text.extract` frame, e.g. a transcript.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Extract {
+    pub id: String,
+    pub kind: String,
+    /// For documents extracted page by page, the 1-based page number
+    pub page: Option<u64>,
+    pub hash: Option<ssri::Integrity>,
+    /// Resolved CAS content; only populated by `resolve_content`
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Projection {
+    pub yaks: BTreeMap<String, Yak>,
+    pub notes: HashMap<String, Note>,
+    /// yak id -> current note ids, in creation order
+    pub notes_by_yak: HashMap<String, Vec<String>>,
+    /// note id -> id of the edit that replaced it
+    pub replaced_by: HashMap<String, String>,
+    /// task id -> task, in creation order
+    pub tasks: BTreeMap<String, Task>,
+    /// Schema version in effect at this point of the log (see `migrations`)
+    #[serde(default)]
+    pub schema_version: u64,
+    /// yak id -> board columns, in the order they were first used
+    #[serde(default)]
+    pub boards: HashMap<String, Vec<BoardColumn>>,
+    /// note id -> the note it's nested under (any revision), set by `note.parent`
+    #[serde(default)]
+    pub parents: HashMap<String, String>,
+    /// Pruned revision id -> the revision it was folded into (see `compaction`)
+    #[serde(default)]
+    pub folded: HashMap<String, String>,
+    /// yak id -> how many older notes `truncate` left out, to fetch with `load_older`
+    #[serde(default)]
+    pub older: HashMap<String, usize>,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+impl Projection {
+    pub fn from_frames<'a>(frames: impl IntoIterator<Item = &'a Frame>) -> Self {
+        let frames: Vec<&Frame> = frames.into_iter().collect();
+        // Frames whose content was found damaged after a crash are left out
+        let quarantined = recovery::quarantined(frames.iter().copied());
+        let mut projection = Self::default();
+        // Revisions compaction folded away stand for the ones they were folded into, even if
+        // a run was cut short before removing them
+        projection.fold(compaction::folded(frames.iter().copied()));
+        for frame in frames {
+            let id = frame.id.to_string();
+            if !quarantined.contains(&id) && !projection.folded.contains_key(&id) {
+                projection.apply(frame);
+            }
+        }
+        projection
+    }
+
+    pub fn apply(&mut self, frame: &Frame) {
+        if frame.topic == migrations::VERSION_TOPIC {
+            if let Some(version) = frame
+                .meta
+                .as_ref()
+                .and_then(|meta| meta.get("version"))
+                .and_then(|version| version.as_u64())
+            {
+                self.schema_version = version;
+            }
+            return;
+        }
+        let upgraded = migrations::upgrade(self, frame, self.schema_version);
+        let frame = upgraded.as_ref().unwrap_or(frame);
+
+        match frame.topic.as_str() {
+            "yak.create" => {
+                let id = frame.id.to_string();
+                self.yaks.insert(
+                    id.clone(),
+                    Yak {
+                        id: id.clone(),
+                        name: meta_str(frame, "name").map(String::from),
+                        last_activity: id.clone(),
+                        locked: false,
+                        private: false,
+                        unread: 0,
+                        label: None,
+                    },
+                );
+                self.notes_by_yak.entry(id).or_default();
+            }
+            // Captured clipboard text and feed items are notes in their yak
+            "note.create" | "clip" | "feed.item" => {
+                let Some(yak_id) = meta_str(frame, "yak_id") else {
+                    return;
+                };
+                let id = frame.id.to_string();
+                self.notes.insert(
+                    id.clone(),
+                    Note {
+                        id: id.clone(),
+                        yak_id: yak_id.to_string(),
+                        hash: frame.hash.clone(),
+                        edited_note_id: None,
+                        content: None,
+                        sealed: None,
+                        meta: frame.meta.clone(),
+                        tags: BTreeSet::new(),
+                        attachments: Vec::new(),
+                        pinned: false,
+                        archived: false,
+                        reminder: None,
+                        blurred: false,
+                        properties: BTreeMap::new(),
+                        unread: unread::ingested(frame),
+                        title: None,
+                        label: None,
+                    },
+                );
+                self.notes_by_yak
+                    .entry(yak_id.to_string())
+                    .or_default()
+                    .push(id.clone());
+                self.touch(yak_id, id);
+            }
+            "note.edit" => {
+                let (Some(yak_id), Some(original_id)) =
+                    (meta_str(frame, "yak_id"), meta_str(frame, "note_id"))
+                else {
+                    return;
+                };
+                let original_id = match self.folded.get(original_id) {
+                    Some(kept) if !self.notes.contains_key(original_id) => kept.clone(),
+                    _ => original_id.to_string(),
+                };
+                let original_id = original_id.as_str();
+                // A note moved since the editor opened stays in the yak it was moved to
+                let moved_to = self
+                    .notes
+                    .get(&self.resolve(original_id))
+                    .map(|note| note.yak_id.clone());
+                let yak_id = moved_to.as_deref().unwrap_or(yak_id);
+                let id = frame.id.to_string();
+                let (tags, attachments, pinned, archived, reminder, properties, unread, title) =
+                    self.notes
+                        .get(original_id)
+                        .map(|note| {
+                            (
+                                note.tags.clone(),
+                                note.attachments.clone(),
+                                note.pinned,
+                                note.archived,
+                                note.reminder.clone(),
+                                note.properties.clone(),
+                                note.unread,
+                                note.title.clone(),
+                            )
+                        })
+                        .unwrap_or_default();
+                let label = self
+                    .notes
+                    .get(original_id)
+                    .and_then(|note| note.label.clone());
+                self.notes.insert(
+                    id.clone(),
+                    Note {
+                        id: id.clone(),
+                        yak_id: yak_id.to_string(),
+                        hash: frame.hash.clone(),
+                        edited_note_id: Some(original_id.to_string()),
+                        content: None,
+                        sealed: None,
+                        meta: frame.meta.clone(),
+                        tags,
+                        attachments,
+                        pinned,
+                        archived,
+                        reminder,
+                        blurred: false,
+                        properties,
+                        unread,
+                        title,
+                        label,
+                    },
+                );
+                self.replaced_by.insert(original_id.to_string(), id.clone());
+                if let Some(parent) = self.parents.remove(original_id) {
+                    self.parents.insert(id.clone(), parent);
+                }
+                if let Some(ids) = self.notes_by_yak.get_mut(yak_id) {
+                    for note_id in ids.iter_mut() {
+                        if note_id == original_id {
+                            *note_id = id.clone();
+                        }
+                    }
+                }
+                self.touch(yak_id, id);
+            }
+            "tag.add" | "tag.remove" => {
+                let (Some(note_id), Some(tag)) =
+                    (meta_str(frame, "note_id"), meta_str(frame, "tag"))
+                else {
+                    return;
+                };
+                let note_id = self.resolve(note_id);
+                if let Some(note) = self.notes.get_mut(&note_id) {
+                    if frame.topic == "tag.add" {
+                        note.tags.insert(tag.to_string());
+                    } else {
+                        note.tags.remove(tag);
+                    }
+                }
+            }
+            unread::READ_TOPIC => {
+                let ids = frame
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get("note_ids"))
+                    .and_then(|ids| ids.as_array());
+                for id in ids.into_iter().flatten().filter_map(|id| id.as_str()) {
+                    let note_id = self.resolve(id);
+                    if let Some(note) = self.notes.get_mut(&note_id) {
+                        note.unread = false;
+                    }
+                }
+            }
+            // A null value removes the property
+            "note.property" => {
+                let (Some(note_id), Some(key)) =
+                    (meta_str(frame, "note_id"), meta_str(frame, "key"))
+                else {
+                    return;
+                };
+                let value = frame.meta.as_ref().and_then(|meta| meta.get("value"));
+                let note_id = self.resolve(note_id);
+                if let Some(note) = self.notes.get_mut(&note_id) {
+                    match value {
+                        Some(value) if !value.is_null() => {
+                            note.properties.insert(key.to_string(), value.clone());
+                        }
+                        _ => {
+                            note.properties.remove(key);
+                        }
+                    }
+                }
+            }
+            // A missing or blank title goes back to the one derived from the content
+            "note.rename" => {
+                let Some(note_id) = meta_str(frame, "note_id") else {
+                    return;
+                };
+                let title = meta_str(frame, "title")
+                    .map(str::trim)
+                    .filter(|title| !title.is_empty());
+                let note_id = self.resolve(note_id);
+                if let Some(note) = self.notes.get_mut(&note_id) {
+                    note.title = title.map(String::from);
+                }
+            }
+            // A yak's or a note's; neither a color nor an icon takes the label off
+            "label.set" => {
+                let Some(target_id) = meta_str(frame, "target_id") else {
+                    return;
+                };
+                let label = Label {
+                    color: meta_str(frame, "color").map(String::from),
+                    icon: meta_str(frame, "icon").map(String::from),
+                };
+                let label = (label != Label::default()).then_some(label);
+                if let Some(yak) = self.yaks.get_mut(target_id) {
+                    yak.label = label;
+                } else {
+                    let note_id = self.resolve(target_id);
+                    if let Some(note) = self.notes.get_mut(&note_id) {
+                        note.label = label;
+                    }
+                }
+            }
+            "attachment.add" | "attachment.audio" | "attachment.video" => {
+                let Some(note_id) = meta_str(frame, "note_id") else {
+                    return;
+                };
+                let note_id = self.resolve(note_id);
+                if let Some(note) = self.notes.get_mut(&note_id) {
+                    note.attachments.push(Attachment {
+                        id: frame.id.to_string(),
+                        hash: frame.hash.clone(),
+                        name: meta_str(frame, "name").map(String::from),
+                        mime: meta_str(frame, "mime").map(String::from),
+                        meta: frame.meta.clone(),
+                        extracts: Vec::new(),
+                    });
+                }
+            }
+            "text.extract" => {
+                let (Some(note_id), Some(attachment_id), Some(kind)) = (
+                    meta_str(frame, "note_id"),
+                    meta_str(frame, "attachment_id"),
+                    meta_str(frame, "kind"),
+                ) else {
+                    return;
+                };
+                let note_id = self.resolve(note_id);
+                let attachment = self.notes.get_mut(&note_id).and_then(|note| {
+                    note.attachments
+                        .iter_mut()
+                        .find(|attachment| attachment.id == attachment_id)
+                });
+                if let Some(attachment) = attachment {
+                    attachment.extracts.push(Extract {
+                        id: frame.id.to_string(),
+                        kind: kind.to_string(),
+                        page: frame
+                            .meta
+                            .as_ref()
+                            .and_then(|meta| meta.get("page"))
+                            .and_then(|page| page.as_u64()),
+                        hash: frame.hash.clone(),
+                        content: None,
+                    });
+                }
+            }
+            "note.pin" | "note.archive" => {
+                let Some(note_id) = meta_str(frame, "note_id") else {
+                    return;
+                };
+                let key = if frame.topic == "note.pin" {
+                    "pinned"
+                } else {
+                    "archived"
+                };
+                let value = frame
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get(key))
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                let note_id = self.resolve(note_id);
+                if let Some(note) = self.notes.get_mut(&note_id) {
+                    if frame.topic == "note.pin" {
+                        note.pinned = value;
+                    } else {
+                        note.archived = value;
+                    }
+                }
+            }
+            "task.create" => {
+                let Some(yak_id) = meta_str(frame, "yak_id") else {
+                    return;
+                };
+                let id = frame.id.to_string();
+                let done = frame
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get("done"))
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                self.tasks.insert(
+                    id.clone(),
+                    Task {
+                        id: id.clone(),
+                        yak_id: yak_id.to_string(),
+                        note_id: meta_str(frame, "note_id").map(String::from),
+                        hash: frame.hash.clone(),
+                        done,
+                        // Recurring tasks are created with their reminder already set
+                        reminder: meta_str(frame, "reminder").map(String::from),
+                        meta: frame.meta.clone(),
+                        content: None,
+                    },
+                );
+                self.touch(yak_id, id);
+            }
+            "task.update" => {
+                let Some(task) = meta_str(frame, "task_id").and_then(|id| self.tasks.get_mut(id))
+                else {
+                    return;
+                };
+                if let Some(done) = frame
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get("done"))
+                    .and_then(|v| v.as_bool())
+                {
+                    task.done = done;
+                }
+                if frame.hash.is_some() {
+                    task.hash = frame.hash.clone();
+                }
+                // Mentions are parsed from the text at append time, so follow its changes
+                if let (Some(mentions), Some(serde_json::Value::Object(meta))) = (
+                    frame.meta.as_ref().and_then(|meta| meta.get("mentions")),
+                    task.meta.as_mut(),
+                ) {
+                    meta.insert("mentions".to_string(), mentions.clone());
+                }
+            }
+            "reminder.set" | "reminder.clear" => {
+                let at = if frame.topic == "reminder.set" {
+                    match meta_str(frame, "at") {
+                        Some(at) => Some(at.to_string()),
+                        None => return,
+                    }
+                } else {
+                    None
+                };
+                if let Some(task) = meta_str(frame, "task_id").and_then(|id| self.tasks.get_mut(id))
+                {
+                    task.reminder = at;
+                } else if let Some(note_id) = meta_str(frame, "note_id") {
+                    let note_id = self.resolve(note_id);
+                    if let Some(note) = self.notes.get_mut(&note_id) {
+                        note.reminder = at;
+                    }
+                }
+            }
+            "note.move" => {
+                let (Some(note_id), Some(yak_id)) =
+                    (meta_str(frame, "note_id"), meta_str(frame, "yak_id"))
+                else {
+                    return;
+                };
+                let note_id = self.resolve(note_id);
+                let Some(note) = self.notes.get_mut(&note_id) else {
+                    return;
+                };
+                let from = std::mem::replace(&mut note.yak_id, yak_id.to_string());
+                if from == yak_id {
+                    return;
+                }
+                if let Some(ids) = self.notes_by_yak.get_mut(&from) {
+                    ids.retain(|id| id != &note_id);
+                }
+                self.notes_by_yak
+                    .entry(yak_id.to_string())
+                    .or_default()
+                    .push(note_id.clone());
+                // The note's tasks go with it
+                let task_ids: Vec<String> = self
+                    .tasks
+                    .values()
+                    .filter(|task| {
+                        task.note_id
+                            .as_deref()
+                            .is_some_and(|id| self.resolve(id) == note_id)
+                    })
+                    .map(|task| task.id.clone())
+                    .collect();
+                for task_id in task_ids {
+                    if let Some(task) = self.tasks.get_mut(&task_id) {
+                        task.yak_id = yak_id.to_string();
+                    }
+                }
+                self.touch(&from, frame.id.to_string());
+                self.touch(yak_id, frame.id.to_string());
+            }
+            // Deleted notes leave their yak but stay in `notes` so they can be restored
+            "note.delete" | "note.restore" => {
+                let Some(note_id) = meta_str(frame, "note_id") else {
+                    return;
+                };
+                let note_id = self.resolve(note_id);
+                let Some(yak_id) = self.notes.get(&note_id).map(|note| note.yak_id.clone()) else {
+                    return;
+                };
+                let ids = self.notes_by_yak.entry(yak_id.clone()).or_default();
+                if frame.topic == "note.delete" {
+                    ids.retain(|id| id != &note_id);
+                } else if !ids.contains(&note_id) {
+                    ids.push(note_id);
+                }
+                self.touch(&yak_id, frame.id.to_string());
+            }
+            "task.move" => {
+                let (Some(task_id), Some(yak_id)) =
+                    (meta_str(frame, "task_id"), meta_str(frame, "yak_id"))
+                else {
+                    return;
+                };
+                if let Some(task) = self.tasks.get_mut(task_id) {
+                    task.yak_id = yak_id.to_string();
+                    self.touch(yak_id, frame.id.to_string());
+                }
+            }
+            // Sets a board column's notes, taking them out of any other column
+            "note.order" => {
+                let (Some(yak_id), Some(column)) =
+                    (meta_str(frame, "yak_id"), meta_str(frame, "column"))
+                else {
+                    return;
+                };
+                let ids: Vec<String> = frame
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get("note_ids"))
+                    .and_then(|ids| ids.as_array())
+                    .map(|ids| {
+                        ids.iter()
+                            .filter_map(|id| id.as_str())
+                            .map(|id| self.resolve(id))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let mut columns = self.boards.remove(yak_id).unwrap_or_default();
+                for existing in &mut columns {
+                    for id in &mut existing.note_ids {
+                        *id = self.resolve(id);
+                    }
+                    existing.note_ids.retain(|id| !ids.contains(id));
+                }
+                match columns.iter_mut().find(|existing| existing.name == column) {
+                    Some(existing) => existing.note_ids = ids,
+                    None => columns.push(BoardColumn {
+                        name: column.to_string(),
+                        note_ids: ids,
+                    }),
+                }
+                self.boards.insert(yak_id.to_string(), columns);
+                self.touch(yak_id, frame.id.to_string());
+            }
+            "note.parent" => {
+                let Some(note_id) = meta_str(frame, "note_id") else {
+                    return;
+                };
+                let note_id = self.resolve(note_id);
+                match meta_str(frame, "parent_id") {
+                    // Two devices nesting notes under each other can still meet in the log;
+                    // whichever comes second is dropped
+                    Some(parent_id) if !self.is_ancestor(&note_id, parent_id) => {
+                        self.parents.insert(note_id, parent_id.to_string());
+                    }
+                    Some(_) => {}
+                    None => {
+                        self.parents.remove(&note_id);
+                    }
+                }
+            }
+            "yak.lock" => {
+                if let Some(yak) = meta_str(frame, "yak_id").and_then(|id| self.yaks.get_mut(id)) {
+                    yak.locked = true;
+                }
+            }
+            "yak.private" => {
+                let private = frame
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get("private"))
+                    .and_then(|private| private.as_bool());
+                if let (Some(yak), Some(private)) = (
+                    meta_str(frame, "yak_id").and_then(|id| self.yaks.get_mut(id)),
+                    private,
+                ) {
+                    yak.private = private;
+                }
+            }
+            // Written after a merge has moved everything out of `yak_id` into `into`
+            "yak.merge" => {
+                let Some(yak_id) = meta_str(frame, "yak_id") else {
+                    return;
+                };
+                if self
+                    .notes_by_yak
+                    .get(yak_id)
+                    .map_or(true, |ids| ids.is_empty())
+                {
+                    self.yaks.remove(yak_id);
+                    self.notes_by_yak.remove(yak_id);
+                }
+            }
+            "task.delete" => {
+                if let Some(task_id) = meta_str(frame, "task_id") {
+                    self.tasks.remove(task_id);
+                }
+            }
+            compaction::CHECKPOINT_TOPIC => {
+                if let Some(folded) = compaction::checkpoint_folds(frame) {
+                    self.fold(folded);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Points references to pruned revisions at the revisions they were folded into. Where
+    /// the pruned revisions were already applied, their own edits are left to resolve them.
+    fn fold(&mut self, folded: HashMap<String, String>) {
+        for (pruned, kept) in folded {
+            self.replaced_by
+                .entry(pruned.clone())
+                .or_insert_with(|| kept.clone());
+            self.folded.insert(pruned, kept);
+        }
+    }
+
+    /// Tasks belonging to a yak, in creation order.
+    pub fn yak_tasks(&self, yak_id: &str) -> Vec<&Task> {
+        self.tasks
+            .values()
+            .filter(|task| task.yak_id == yak_id)
+            .collect()
+    }
+
+    /// Follows edits from any revision of a note to its latest revision.
+    pub fn resolve(&self, note_id: &str) -> String {
+        let mut id = note_id;
+        while let Some(next) = self.replaced_by.get(id) {
+            id = next;
+        }
+        id.to_string()
+    }
+
+    fn touch(&mut self, yak_id: &str, frame_id: String) {
+        if let Some(yak) = self.yaks.get_mut(yak_id) {
+            yak.last_activity = frame_id;
+        }
+    }
+
+    /// The latest revision of the note `note_id` is nested under, if any.
+    pub fn parent(&self, note_id: &str) -> Option<String> {
+        self.parents
+            .get(&self.resolve(note_id))
+            .map(|parent| self.resolve(parent))
+    }
+
+    /// Whether `ancestor` is `note_id` or one of the notes it is nested under.
+    pub fn is_ancestor(&self, ancestor: &str, note_id: &str) -> bool {
+        let ancestor = self.resolve(ancestor);
+        let mut current = Some(self.resolve(note_id));
+        let mut seen = HashSet::new();
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            if !seen.insert(id.clone()) {
+                break;
+            }
+            current = self.parent(&id);
+        }
+        false
+    }
+
+    /// A yak's board with every note at its latest revision, leaving out notes since
+    /// deleted or moved to another yak.
+    pub fn board(&self, yak_id: &str) -> Vec<BoardColumn> {
+        let current = self.notes_by_yak.get(yak_id);
+        let mut placed = HashSet::new();
+        self.boards
+            .get(yak_id)
+            .map(|columns| {
+                columns
+                    .iter()
+                    .map(|column| BoardColumn {
+                        name: column.name.clone(),
+                        note_ids: column
+                            .note_ids
+                            .iter()
+                            .map(|id| self.resolve(id))
+                            .filter(|id| current.is_some_and(|ids| ids.contains(id)))
+                            .filter(|id| placed.insert(id.clone()))
+                            .collect(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The current (latest edit) notes of a yak, in order.
+    pub fn current_notes(&self, yak_id: &str) -> Vec<&Note> {
+        self.notes_by_yak
+            .get(yak_id)
+            .map(|ids| ids.iter().filter_map(|id| self.notes.get(id)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Sets each yak's count of current notes still unread.
+    pub fn count_unread(&mut self) {
+        for yak in self.yaks.values_mut() {
+            yak.unread = self
+                .notes_by_yak
+                .get(&yak.id)
+                .map(|ids| {
+                    ids.iter()
+                        .filter(|id| self.notes.get(*id).is_some_and(|note| note.unread))
+                        .count()
+                })
+                .unwrap_or_default();
+        }
+    }
+
+    /// Keeps only the latest `limit` current notes of each yak, counting the rest in `older`.
+    pub fn truncate(&mut self, limit: usize) {
+        for (yak_id, ids) in self.notes_by_yak.iter_mut() {
+            if ids.len() <= limit {
+                continue;
+            }
+            let dropped: Vec<String> = ids.drain(..ids.len() - limit).collect();
+            for id in &dropped {
+                self.notes.remove(id);
+            }
+            self.older.insert(yak_id.clone(), dropped.len());
+        }
+    }
+
+    /// Drops superseded edits, keeping only the notes currently visible in a yak.
+    pub fn into_current(mut self) -> Self {
+        let current: HashSet<String> = self.notes_by_yak.values().flatten().cloned().collect();
+        self.notes.retain(|id, _| current.contains(id));
+        self
+    }
+
+    /// Moves sealed note content out of `content`, and drops revisions of locked yaks written
+    /// in the clear before the lock, so nothing reads a locked note without `LockState`.
+    pub fn withhold_locked(&mut self) {
+        for note in self.notes.values_mut() {
+            let locked = self.yaks.get(&note.yak_id).is_some_and(|yak| yak.locked);
+            match note.content.take() {
+                Some(content) if locks::is_sealed(&content) => note.sealed = Some(content),
+                Some(content) if !locked => note.content = Some(content),
+                _ => {}
+            }
+        }
+    }
+
+    /// Fills in `content` for every note (and its extracted text) from the CAS, leaving out
+    /// that of locked yaks (see `withhold_locked`).
+    pub async fn resolve_content(&mut self, store: &Store) {
+        for task in self.tasks.values_mut() {
+            if let Some(hash) = &task.hash {
+                task.content = store
+                    .cas_read(hash)
+                    .await
+                    .ok()
+                    .and_then(|bytes| String::from_utf8(bytes).ok());
+            }
+        }
+        for note in self.notes.values_mut() {
+            if let Some(hash) = &note.hash {
+                match store.cas_read(hash).await {
+                    Ok(bytes) => note.content = String::from_utf8(bytes).ok(),
+                    Err(e) => eprintln!("Failed to read content for note {}: {e}", note.id),
+                }
+            }
+            let extracts = note
+                .attachments
+                .iter_mut()
+                .flat_map(|attachment| attachment.extracts.iter_mut());
+            for extract in extracts {
+                if let Some(hash) = &extract.hash {
+                    extract.content = store
+                        .cas_read(hash)
+                        .await
+                        .ok()
+                        .and_then(|bytes| String::from_utf8(bytes).ok());
+                }
+            }
+        }
+        self.withhold_locked();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_all_frames;
+    use crate::testing::{self, append};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_projection_edits_replace_notes() {
+        let (_dir, store) = testing::store();
+        let yak = append(&store, "yak.create", None);
+        let yak_id = yak.id.to_string();
+        let note = append(&store, "note.create", Some(json!({ "yak_id": yak_id })));
+        let edit = json!({ "yak_id": yak_id, "note_id": note.id.to_string() });
+        let edit = append(&store, "note.edit", Some(edit));
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let current = projection.current_notes(&yak_id);
+        assert_eq!(current.len(), 1);
+        assert_eq!(current[0].id, edit.id.to_string());
+        assert_eq!(projection.yaks[&yak_id].last_activity, edit.id.to_string());
+
+        let projection = projection.into_current();
+        assert_eq!(projection.notes.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_tags_follow_edits() {
+        let (_dir, store) = testing::store();
+        let yak = append(&store, "yak.create", None);
+        let yak_id = yak.id.to_string();
+        let note = append(&store, "note.create", Some(json!({ "yak_id": yak_id })));
+        let note_id = note.id.to_string();
+        append(
+            &store,
+            "tag.add",
+            Some(json!({ "note_id": note_id, "tag": "rust" })),
+        );
+        let edit = json!({ "yak_id": yak_id, "note_id": note_id });
+        let edit = append(&store, "note.edit", Some(edit));
+        append(
+            &store,
+            "tag.add",
+            Some(json!({ "note_id": note_id, "tag": "tauri" })),
+        );
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let current = &projection.notes[&edit.id.to_string()];
+        assert_eq!(
+            current.tags.iter().collect::<Vec<_>>(),
+            vec!["rust", "tauri"]
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::import::{add_note, add_tag, add_task};
+use crate::shutdown::begin_write;
+use crate::templates::expand;
+use crate::windows::emit_frames;
+use crate::{append_frame, read_all_frames};
+
+const SAVE_TOPIC: &str = "project.template";
+
+/// A note a project starts with. Its content is expanded like a note template, with
+/// `{{project}}` set to the project's name, so notes can link each other by title with
+/// `[[{{project}} Kickoff]]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectNote {
+    pub content: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Open tasks on the note
+    #[serde(default)]
+    pub tasks: Vec<String>,
+}
+
+/// The structure a new project is set up with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectTemplate {
+    pub name: String,
+    pub notes: Vec<ProjectNote>,
+    /// Tasks on the yak rather than a note
+    #[serde(default)]
+    pub tasks: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Project {
+    pub yak_id: String,
+    pub note_ids: Vec<String>,
+    pub tasks: usize,
+}
+
+/// The latest saved version of each project template, by name.
+fn project_templates(frames: &[Frame]) -> BTreeMap<String, ProjectTemplate> {
+    frames
+        .iter()
+        .filter(|frame| frame.topic == SAVE_TOPIC)
+        .filter_map(|frame| serde_json::from_value::<ProjectTemplate>(frame.meta.clone()?).ok())
+        .map(|template| (template.name.clone(), template))
+        .collect()
+}
+
+/// The template's notes with their variables expanded for project `name`.
+fn expand_notes(template: &ProjectTemplate, name: &str, now: DateTime<Local>) -> Vec<ProjectNote> {
+    let substitutions = HashMap::from([("project".to_string(), name.to_string())]);
+    let fill = |text: &str| expand(text, now, &substitutions).content;
+    template
+        .notes
+        .iter()
+        .map(|note| ProjectNote {
+            content: fill(&note.content),
+            tags: note.tags.iter().map(|tag| fill(tag)).collect(),
+            tasks: note.tasks.iter().map(|task| fill(task)).collect(),
+        })
+        .collect()
+}
+
+/// Saves a project template, replacing any earlier one of the same name.
+#[tauri::command]
+pub async fn save_project_template(
+    store: State<'_, Store>,
+    app: AppHandle,
+    template: ProjectTemplate,
+) -> Result<ProjectTemplate, String> {
+    let _guard = begin_write(&app)?;
+    let mut template = template;
+    template.name = template.name.trim().to_string();
+    if template.name.is_empty() {
+        return Err("Template name can't be empty".to_string());
+    }
+    if template.notes.is_empty() && template.tasks.is_empty() {
+        return Err("A project template needs at least one note or task".to_string());
+    }
+    let meta = serde_json::to_value(&template)
+        .map_err(|e| format!("Failed to serialize template: {e}"))?;
+    append_frame(&store, SAVE_TOPIC, None, Some(meta)).await?;
+    Ok(template)
+}
+
+#[tauri::command]
+pub async fn list_project_templates(
+    store: State<'_, Store>,
+) -> Result<Vec<ProjectTemplate>, String> {
+    Ok(project_templates(&read_all_frames(&store).await)
+        .into_values()
+        .collect())
+}
+
+/// Starts a project: a new yak named `name`, set up with the notes, tags and tasks of
+/// project template `template`.
+#[tauri::command]
+pub async fn create_project(
+    app: AppHandle,
+    store: State<'_, Store>,
+    name: String,
+    template: String,
+) -> Result<Project, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("A project needs a name".to_string());
+    }
+    let template = project_templates(&read_all_frames(&store).await)
+        .remove(&template)
+        .ok_or_else(|| format!("Project template not found: {template}"))?;
+    let _guard = begin_write(&app)?;
+
+    let meta = serde_json::json!({ "name": name, "project_template": template.name });
+    let yak = append_frame(&store, "yak.create", None, Some(meta)).await?;
+    let yak_id = yak.id.to_string();
+    let mut frames = vec![yak];
+    let mut note_ids = Vec::new();
+    let mut tasks = 0;
+    for note in expand_notes(&template, &name, Local::now()) {
+        let meta = serde_json::json!({ "project_template": template.name });
+        let created = add_note(&store, &yak_id, &note.content, meta).await?;
+        let note_id = created.id.to_string();
+        frames.push(created);
+        for tag in &note.tags {
+            frames.push(add_tag(&store, &yak_id, &note_id, tag).await?);
+        }
+        for task in &note.tasks {
+            frames.push(add_task(&store, &yak_id, Some(&note_id), task, false).await?);
+            tasks += 1;
+        }
+        note_ids.push(note_id);
+    }
+    for task in &template.tasks {
+        frames.push(add_task(&store, &yak_id, None, task, false).await?);
+        tasks += 1;
+    }
+    emit_frames(&app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))?;
+    Ok(Project {
+        yak_id,
+        note_ids,
+        tasks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_project_templates() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        for notes in [1, 2] {
+            let template = ProjectTemplate {
+                name: "client".to_string(),
+                notes: vec![
+                    ProjectNote {
+                        content: "# {{project}} Overview\nSee [[{{project}} Kickoff]]".to_string(),
+                        tags: vec!["{{project}}".to_string(), "client".to_string()],
+                        tasks: vec!["Send {{project}} the contract".to_string()],
+                    };
+                    notes
+                ],
+                tasks: Vec::new(),
+            };
+            let meta = serde_json::to_value(&template).unwrap();
+            append_frame(&store, SAVE_TOPIC, None, Some(meta))
+                .await
+                .unwrap();
+        }
+        let templates = project_templates(&read_all_frames(&store).await);
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates["client"].notes.len(), 2);
+
+        let now = Local.with_ymd_and_hms(2024, 3, 1, 9, 30, 0).unwrap();
+        let notes = expand_notes(&templates["client"], "Acme", now);
+        assert_eq!(notes[0].content, "# Acme Overview\nSee [[Acme Kickoff]]");
+        assert_eq!(notes[0].tags, vec!["Acme", "client"]);
+        assert_eq!(notes[0].tasks, vec!["Send Acme the contract"]);
+    }
+}
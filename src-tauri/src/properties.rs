@@ -0,0 +1,163 @@
+use serde_json::Value;
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::projection::Projection;
+use crate::shutdown::begin_write;
+use crate::windows::emit_frame;
+use crate::{append_frame, read_all_frames};
+
+const TOPIC: &str = "note.property";
+/// Front-matter fields with a meaning of their own, kept out of properties on import.
+const RESERVED: &[&str] = &["id", "title", "tags", "label", "yak", "created", "updated"];
+
+fn validate(key: &str) -> Result<(), String> {
+    let valid = key.chars().next().is_some_and(|c| c.is_alphabetic())
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    if !valid || key.len() > 64 {
+        return Err(format!(
+            "Invalid property {key:?}: use letters, digits, - and _"
+        ));
+    }
+    // `has:` is how search asks for a property to be present
+    if key == "has" || RESERVED.contains(&key) {
+        return Err(format!("{key} can't be used as a property"));
+    }
+    Ok(())
+}
+
+/// Sets a property on a note, or removes it when `value` is null.
+pub(crate) async fn append_property(
+    store: &Store,
+    yak_id: &str,
+    note_id: &str,
+    key: &str,
+    value: Value,
+) -> Result<Frame, String> {
+    let meta = serde_json::json!({
+        "yak_id": yak_id,
+        "note_id": note_id,
+        "key": key,
+        "value": value,
+    });
+    append_frame(store, TOPIC, None, Some(meta)).await
+}
+
+/// Splits imported front-matter into the fields kept in the note's meta and the ones that
+/// become properties, which is every field without a meaning of its own.
+pub(crate) fn from_frontmatter(fields: Option<Value>) -> (Option<Value>, Vec<(String, Value)>) {
+    let Some(Value::Object(mut fields)) = fields else {
+        return (fields, Vec::new());
+    };
+    let keys: Vec<String> = fields
+        .keys()
+        .filter(|key| validate(key).is_ok())
+        .cloned()
+        .collect();
+    let properties = keys
+        .into_iter()
+        .filter_map(|key| {
+            let value = fields.remove(&key)?;
+            (!value.is_null()).then_some((key, value))
+        })
+        .collect();
+    let kept = (!fields.is_empty()).then_some(Value::Object(fields));
+    (kept, properties)
+}
+
+/// Sets a typed property on a note (a string, number, bool, list or object), or removes it
+/// when `value` is null. Properties carry over edits, show in the snapshot, are written to
+/// exported front-matter, and can be searched with `key:value` filters.
+#[tauri::command]
+pub async fn set_note_meta(
+    app: AppHandle,
+    store: State<'_, Store>,
+    frame_id: String,
+    key: String,
+    value: Value,
+) -> Result<Frame, String> {
+    validate(&key)?;
+    let _guard = begin_write(&app)?;
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let note_id = projection.resolve(&frame_id);
+    let note = projection
+        .notes
+        .get(&note_id)
+        .ok_or_else(|| format!("Note not found: {frame_id}"))?;
+    let frame = append_property(&store, &note.yak_id, &note_id, &key, value).await?;
+    let _ = emit_frame(&app, &frame);
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_from_frontmatter() {
+        let fields = json!({
+            "title": "Trip",
+            "tags": ["travel"],
+            "status": "booked",
+            "budget": 1200,
+            "visited": null,
+            "two words": true,
+        });
+        let (kept, properties) = from_frontmatter(Some(fields));
+        assert_eq!(
+            kept.unwrap(),
+            json!({ "title": "Trip", "tags": ["travel"], "two words": true })
+        );
+        let properties: std::collections::BTreeMap<_, _> = properties.into_iter().collect();
+        assert_eq!(properties.len(), 2);
+        assert_eq!(properties["budget"], json!(1200));
+        assert_eq!(properties["status"], json!("booked"));
+        assert!(validate("has").is_err());
+        assert!(validate("2nd").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_properties_follow_edits() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let note = append_frame(
+            &store,
+            "note.create",
+            Some(b"draft".as_slice()),
+            Some(json!({ "yak_id": yak_id })),
+        )
+        .await
+        .unwrap();
+        let note_id = note.id.to_string();
+        append_property(&store, &yak_id, &note_id, "priority", json!(2))
+            .await
+            .unwrap();
+        append_property(&store, &yak_id, &note_id, "status", json!("open"))
+            .await
+            .unwrap();
+        let edit = append_frame(
+            &store,
+            "note.edit",
+            Some(b"final".as_slice()),
+            Some(json!({ "yak_id": yak_id, "note_id": note_id })),
+        )
+        .await
+        .unwrap();
+        append_property(&store, &yak_id, &note_id, "status", Value::Null)
+            .await
+            .unwrap();
+
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        let note = &projection.notes[&edit.id.to_string()];
+        assert_eq!(note.properties.len(), 1);
+        assert_eq!(note.properties["priority"], json!(2));
+    }
+}
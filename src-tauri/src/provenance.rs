@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::future::Future;
+use std::path::Path;
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager, State};
+use xs::store::Store;
+
+use crate::sync::ORIGIN_KEY;
+
+/// The meta field every appended frame is stamped with.
+pub(crate) const KEY: &str = "provenance";
+/// Kept beside the store pointer, so a store copied to another machine doesn't bring the
+/// old machine's identity with it.
+const DEVICE_FILE: &str = "device-id";
+
+/// What asked for a frame to be appended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// A command from one of the app's windows
+    Ui,
+    /// The `--mcp` bridge, as used by command-line agents
+    Cli,
+    /// The publish server's capture endpoint
+    Http,
+    /// Copied in from another device
+    Sync,
+    /// The app's own background work: captures, feeds, extraction, migrations
+    App,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub device: String,
+    pub version: String,
+    pub source: Source,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Provenance {
+    pub frame_id: String,
+    pub topic: String,
+    /// `None` for frames appended before stamping, or by a version without it
+    pub stamp: Option<Stamp>,
+    /// Whether the stamp's device is this one
+    pub this_device: bool,
+    /// The id of the frame this was copied from, if sync brought it here
+    pub synced_from: Option<String>,
+}
+
+struct Identity {
+    device: String,
+    version: String,
+}
+
+static IDENTITY: OnceLock<Identity> = OnceLock::new();
+
+tokio::task_local! {
+    static SOURCE: Source;
+}
+
+/// Runs `future` with every frame it appends attributed to `source`.
+pub(crate) async fn scope<F: Future>(source: Source, future: F) -> F::Output {
+    SOURCE.scope(source, future).await
+}
+
+/// This device's id, created the first time it's asked for.
+fn device_id(app_data_dir: &Path) -> Result<String, String> {
+    let path = app_data_dir.join(DEVICE_FILE);
+    if let Ok(id) = std::fs::read_to_string(&path) {
+        if !id.trim().is_empty() {
+            return Ok(id.trim().to_string());
+        }
+    }
+    let id = scru128::new().to_string();
+    std::fs::create_dir_all(app_data_dir)
+        .and_then(|_| std::fs::write(&path, &id))
+        .map_err(|e| format!("Failed to save device id: {e}"))?;
+    Ok(id)
+}
+
+/// Adds this device, the app version and where the append came from to `meta`, replacing
+/// any stamp copied from an earlier frame. `fallback` is the source when no `scope` says
+/// otherwise.
+pub(crate) fn stamp(meta: &mut Option<Value>, fallback: Source) {
+    if let Some(identity) = IDENTITY.get() {
+        stamp_as(identity, meta, fallback);
+    }
+}
+
+fn stamp_as(identity: &Identity, meta: &mut Option<Value>, fallback: Source) {
+    let fields = meta.get_or_insert_with(|| serde_json::json!({}));
+    let Some(fields) = fields.as_object_mut() else {
+        return;
+    };
+    let stamp = Stamp {
+        device: identity.device.clone(),
+        version: identity.version.clone(),
+        source: SOURCE.try_with(|source| *source).unwrap_or(fallback),
+    };
+    fields.insert(KEY.to_string(), serde_json::json!(stamp));
+}
+
+fn provenance(frame: &xs::store::Frame, identity: Option<&Identity>) -> Provenance {
+    let meta = frame.meta.as_ref();
+    let stamp: Option<Stamp> = meta
+        .and_then(|meta| meta.get(KEY))
+        .and_then(|stamp| serde_json::from_value(stamp.clone()).ok());
+    let this_device = match (&stamp, identity) {
+        (Some(stamp), Some(identity)) => stamp.device == identity.device,
+        _ => false,
+    };
+    Provenance {
+        frame_id: frame.id.to_string(),
+        topic: frame.topic.clone(),
+        stamp,
+        this_device,
+        synced_from: meta
+            .and_then(|meta| meta.get(ORIGIN_KEY))
+            .and_then(|id| id.as_str())
+            .map(String::from),
+    }
+}
+
+/// Where `frame` came from, as `get_frame_provenance` gives it.
+pub(crate) fn of(frame: &xs::store::Frame) -> Provenance {
+    provenance(frame, IDENTITY.get())
+}
+
+/// The device whose stamp `frame` carries, and whether it's this one.
+pub(crate) fn device(frame: &xs::store::Frame) -> Option<(String, bool)> {
+    let found = of(frame);
+    Some((found.stamp?.device, found.this_device))
+}
+
+/// Sets up this device's identity; frames appended before this aren't stamped.
+pub(crate) fn initialize(app: &AppHandle) {
+    let device = match app.path().app_data_dir() {
+        Ok(dir) => device_id(&dir),
+        Err(e) => Err(format!("Failed to get app data dir: {e}")),
+    };
+    match device {
+        Ok(device) => {
+            let version = app.package_info().version.to_string();
+            let _ = IDENTITY.set(Identity { device, version });
+        }
+        Err(e) => eprintln!("Frames won't record their provenance: {e}"),
+    }
+}
+
+/// Which device, app version and kind of request appended a frame.
+#[tauri::command]
+pub async fn get_frame_provenance(
+    store: State<'_, Store>,
+    id: String,
+) -> Result<Provenance, String> {
+    let id = id
+        .parse::<scru128::Scru128Id>()
+        .map_err(|e| format!("Invalid frame id: {e}"))?;
+    let frame = store
+        .get(&id)
+        .ok_or_else(|| format!("Frame not found: {id}"))?;
+    Ok(provenance(&frame, IDENTITY.get()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_device_id_is_kept() {
+        let dir = tempdir().unwrap();
+        let id = device_id(dir.path()).unwrap();
+        assert_eq!(device_id(dir.path()).unwrap(), id);
+    }
+
+    #[tokio::test]
+    async fn test_stamp() {
+        let laptop = Identity {
+            device: "laptop".to_string(),
+            version: "1.2.3".to_string(),
+        };
+        let mut meta = None;
+        stamp_as(&laptop, &mut meta, Source::Ui);
+        let mut scoped = Some(serde_json::json!({ "yak_id": "y" }));
+        scope(Source::Http, async {
+            stamp_as(&laptop, &mut scoped, Source::Ui)
+        })
+        .await;
+
+        let frame = xs::store::Frame {
+            id: scru128::new(),
+            context_id: xs::store::ZERO_CONTEXT,
+            topic: "note.create".to_string(),
+            hash: None,
+            meta: scoped,
+            ttl: None,
+        };
+        let found = provenance(&frame, Some(&laptop));
+        assert_eq!(found.stamp.unwrap().source, Source::Http);
+        assert!(found.this_device);
+        assert_eq!(meta.unwrap()[KEY]["source"], "ui");
+
+        // A synced copy names the device that wrote it
+        let synced = xs::store::Frame {
+            meta: Some(serde_json::json!({
+                KEY: { "device": "phone", "version": "1.0.0", "source": "ui" },
+                ORIGIN_KEY: "remote-id",
+            })),
+            ..frame
+        };
+        let found = provenance(&synced, Some(&laptop));
+        assert!(!found.this_device);
+        assert_eq!(found.synced_from.as_deref(), Some("remote-id"));
+    }
+}
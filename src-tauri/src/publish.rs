@@ -0,0 +1,913 @@
+use axum::extract::{Path, Query, Request, State as AxumState};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use xs::store::{Frame, Store};
+
+use crate::export::html::{inline_attachments, link_notes, page, render_markdown};
+use crate::export::{json_feed, note_title};
+use crate::html::escape;
+use crate::locks::{self, LockState};
+use crate::permissions::{self, Integration};
+use crate::projection::{Note, Projection};
+use crate::provenance::{self, Source};
+use crate::search::{keyword_search, Match};
+use crate::settings::{latest_setting, load_setting, save_setting};
+use crate::share_links::{self, shared_note, Shares};
+use crate::windows::{emit_frame, emit_frames};
+use crate::{append_batch_to_store, append_frame, crypto, read_all_frames, secrets, AppendRequest};
+
+pub(crate) const CONFIG_TOPIC: &str = "publish.config";
+const COOKIE: &str = "yaks_token";
+
+fn default_port() -> u16 {
+    8421
+}
+
+fn default_rate_limit() -> u32 {
+    30
+}
+
+/// Room for a full-page screenshot
+fn default_max_body_bytes() -> usize {
+    25 * 1024 * 1024
+}
+
+fn default_daily_quota() -> u32 {
+    1000
+}
+
+/// Serving a yak read-only on the local network. Visitors need the token, given once as
+/// `?token=` (then remembered in a cookie) or as a bearer token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishConfig {
+    pub enabled: bool,
+    pub yak_id: Option<String>,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Generated when publishing is first enabled
+    pub token: Option<String>,
+    #[serde(default)]
+    pub capture: CaptureConfig,
+    /// Signs share links. Generated when the first one is made
+    #[serde(default)]
+    pub share_key: Option<String>,
+}
+
+/// The `/capture` endpoint for the browser extension, served alongside (or without) a
+/// published yak. It has its own token, since it can write.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureConfig {
+    pub enabled: bool,
+    /// Where clips go
+    pub yak_id: Option<String>,
+    /// Generated when capture is first enabled
+    pub token: Option<String>,
+    /// Extension origins allowed to call it, e.g. `chrome-extension://<id>`
+    #[serde(default)]
+    pub origins: Vec<String>,
+    /// Requests a minute each token may make; 0 for no limit
+    #[serde(default = "default_rate_limit")]
+    pub rate_limit: u32,
+    /// Requests with a larger body are turned away
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// Requests let through per day (UTC); 0 for no limit
+    #[serde(default = "default_daily_quota")]
+    pub daily_quota: u32,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            yak_id: None,
+            token: None,
+            origins: Vec::new(),
+            rate_limit: default_rate_limit(),
+            max_body_bytes: default_max_body_bytes(),
+            daily_quota: default_daily_quota(),
+        }
+    }
+}
+
+impl Default for PublishConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            yak_id: None,
+            port: default_port(),
+            token: None,
+            capture: CaptureConfig::default(),
+            share_key: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PublishStatus {
+    pub config: PublishConfig,
+    /// The address to share, including the token
+    pub url: Option<String>,
+}
+
+/// How much `/capture` has taken in today, and what it turned away.
+#[derive(Debug, Clone, Serialize)]
+pub struct IngestUsage {
+    /// The UTC day counted, e.g. `2024-05-01`
+    pub day: String,
+    /// Requests let through, whether or not they made a note
+    pub requests: u32,
+    pub daily_quota: u32,
+    pub bytes: u64,
+    pub rate_limited: u32,
+    pub too_large: u32,
+    pub over_quota: u32,
+}
+
+/// Requests to `/capture` on the current day. It outlives the server being reconfigured,
+/// but not the app restarting.
+#[derive(Debug, Default)]
+struct Usage {
+    day: Option<NaiveDate>,
+    requests: u32,
+    bytes: u64,
+    rate_limited: u32,
+    too_large: u32,
+    over_quota: u32,
+    /// When each token's requests in the last minute came in
+    recent: HashMap<String, VecDeque<DateTime<Utc>>>,
+}
+
+/// Why a request was turned away, and how many seconds until it's worth trying again.
+#[derive(Debug, PartialEq)]
+enum Refusal {
+    RateLimited(i64),
+    OverQuota(i64),
+}
+
+impl Usage {
+    fn roll(&mut self, now: DateTime<Utc>) {
+        let today = now.date_naive();
+        if self.day != Some(today) {
+            *self = Usage {
+                day: Some(today),
+                recent: std::mem::take(&mut self.recent),
+                ..Usage::default()
+            };
+        }
+    }
+
+    /// Counts a request made with `token`, unless it's over the daily quota or the token's
+    /// rate limit.
+    fn admit(
+        &mut self,
+        token: &str,
+        config: &CaptureConfig,
+        now: DateTime<Utc>,
+    ) -> Result<(), Refusal> {
+        self.roll(now);
+        if config.daily_quota > 0 && self.requests >= config.daily_quota {
+            self.over_quota += 1;
+            let tomorrow = now.date_naive().succ_opt().unwrap_or(now.date_naive());
+            let midnight = tomorrow.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+            return Err(Refusal::OverQuota((midnight - now).num_seconds().max(1)));
+        }
+        let recent = self.recent.entry(token.to_string()).or_default();
+        let minute = chrono::Duration::seconds(60);
+        while recent.front().is_some_and(|at| *at + minute <= now) {
+            recent.pop_front();
+        }
+        if config.rate_limit > 0 && recent.len() >= config.rate_limit as usize {
+            self.rate_limited += 1;
+            let oldest = recent.front().copied().unwrap_or(now);
+            return Err(Refusal::RateLimited(
+                (oldest + minute - now).num_seconds().max(1),
+            ));
+        }
+        recent.push_back(now);
+        self.requests += 1;
+        Ok(())
+    }
+
+    fn report(&mut self, config: &CaptureConfig, now: DateTime<Utc>) -> IngestUsage {
+        self.roll(now);
+        IngestUsage {
+            day: now.date_naive().to_string(),
+            requests: self.requests,
+            daily_quota: config.daily_quota,
+            bytes: self.bytes,
+            rate_limited: self.rate_limited,
+            too_large: self.too_large,
+            over_quota: self.over_quota,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PublishState {
+    server: Mutex<Option<JoinHandle<()>>>,
+    usage: Arc<std::sync::Mutex<Usage>>,
+}
+
+struct Site {
+    store: Store,
+    yak_id: String,
+    token: String,
+}
+
+type SharedSite = Arc<Site>;
+
+struct Capture {
+    app: AppHandle,
+    store: Store,
+    yak_id: String,
+    token: String,
+    origins: Vec<String>,
+    limits: CaptureConfig,
+    usage: Arc<std::sync::Mutex<Usage>>,
+}
+
+type SharedCapture = Arc<Capture>;
+
+impl Site {
+    async fn notes(&self) -> (String, Vec<Note>) {
+        let frames = read_all_frames(&self.store).await;
+        let mut projection = Projection::from_frames(&frames);
+        projection.resolve_content(&self.store).await;
+        locks::withhold(&mut projection, true);
+        let name = projection
+            .yaks
+            .get(&self.yak_id)
+            .and_then(|yak| yak.name.clone())
+            .unwrap_or_else(|| "Notes".to_string());
+        let notes = projection
+            .current_notes(&self.yak_id)
+            .into_iter()
+            .filter(|note| !note.archived)
+            .cloned()
+            .collect();
+        (name, notes)
+    }
+
+    fn nav(&self, name: &str) -> String {
+        format!(
+            "<nav><a href=\"/\">{}</a> · <form action=\"/search\" style=\"display:inline\">\
+             <input name=\"q\" placeholder=\"Search\"></form></nav>",
+            escape(name)
+        )
+    }
+}
+
+fn note_list(notes: &[&Note]) -> String {
+    let items: String = notes
+        .iter()
+        .map(|note| {
+            format!(
+                "<li><a href=\"/note/{}\">{}</a></li>",
+                note.id,
+                escape(&note_title(note))
+            )
+        })
+        .collect();
+    format!("<ul>{items}</ul>")
+}
+
+fn tag_links(tags: &BTreeSet<String>) -> String {
+    let links: String = tags
+        .iter()
+        .map(|tag| {
+            let href = tag
+                .replace('%', "%25")
+                .replace('/', "%2F")
+                .replace(' ', "%20");
+            format!("<a href=\"/tag/{href}\">{}</a> ", escape(tag))
+        })
+        .collect();
+    format!("<p class=\"tags\">{links}</p>")
+}
+
+async fn index(AxumState(site): AxumState<SharedSite>) -> Html<String> {
+    let (name, notes) = site.notes().await;
+    let pinned: Vec<&Note> = notes.iter().filter(|note| note.pinned).collect();
+    let rest: Vec<&Note> = notes.iter().filter(|note| !note.pinned).collect();
+    let tags: BTreeSet<String> = notes.iter().flat_map(|note| note.tags.clone()).collect();
+    let mut body = format!("<h1>{}</h1>", escape(&name));
+    if !pinned.is_empty() {
+        body.push_str(&format!("<h2>Pinned</h2>{}", note_list(&pinned)));
+    }
+    body.push_str(&note_list(&rest));
+    if !tags.is_empty() {
+        body.push_str(&format!("<h2>Tags</h2>{}", tag_links(&tags)));
+    }
+    Html(page(&name, &site.nav(&name), &body))
+}
+
+async fn note(
+    AxumState(site): AxumState<SharedSite>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let (name, notes) = site.notes().await;
+    let note = notes
+        .iter()
+        .find(|note| note.id == id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let pages: HashMap<String, String> = notes
+        .iter()
+        .flat_map(|note| {
+            let href = format!("/note/{}", note.id);
+            [
+                (note_title(note).to_lowercase(), href.clone()),
+                (note.id.clone(), href),
+            ]
+        })
+        .collect();
+    let markdown = link_notes(note.content.as_deref().unwrap_or_default(), &pages);
+    let markdown = inline_attachments(&site.store, note, markdown)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut body = render_markdown(&markdown);
+    if !note.tags.is_empty() {
+        body.push_str(&tag_links(&note.tags));
+    }
+    Ok(Html(page(&note_title(note), &site.nav(&name), &body)))
+}
+
+async fn tag(AxumState(site): AxumState<SharedSite>, Path(tag): Path<String>) -> Html<String> {
+    let (name, notes) = site.notes().await;
+    let tagged: Vec<&Note> = notes
+        .iter()
+        .filter(|note| note.tags.contains(&tag))
+        .collect();
+    let body = format!("<h1>#{}</h1>{}", escape(&tag), note_list(&tagged));
+    Html(page(&tag, &site.nav(&name), &body))
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+async fn search(
+    AxumState(site): AxumState<SharedSite>,
+    Query(query): Query<SearchQuery>,
+) -> Html<String> {
+    let frames = read_all_frames(&site.store).await;
+    let mut projection = Projection::from_frames(&frames).into_current();
+    projection.resolve_content(&site.store).await;
+    locks::withhold(&mut projection, true);
+    let name = projection
+        .yaks
+        .get(&site.yak_id)
+        .and_then(|yak| yak.name.clone())
+        .unwrap_or_else(|| "Notes".to_string());
+    let hits: Vec<&Note> =
+        keyword_search(&projection, &query.q, Some(&site.yak_id), Match::All, 50)
+            .into_iter()
+            .filter(|note| !note.archived)
+            .collect();
+    let body = format!("<h1>Search: {}</h1>{}", escape(&query.q), note_list(&hits));
+    Html(page("Search", &site.nav(&name), &body))
+}
+
+/// The published yak as a JSON Feed, linking to its pages here. Readers subscribe with the
+/// token in the URL, as `/feed.json?token=`.
+async fn feed(AxumState(site): AxumState<SharedSite>, headers: HeaderMap) -> Response {
+    let frames = read_all_frames(&site.store).await;
+    let mut projection = Projection::from_frames(&frames);
+    projection.resolve_content(&site.store).await;
+    locks::withhold(&mut projection, true);
+    let notes: Vec<Note> = projection
+        .current_notes(&site.yak_id)
+        .into_iter()
+        .cloned()
+        .collect();
+    let base_url = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(|host| format!("http://{host}"));
+    let feed = json_feed::render(&projection, &site.yak_id, &notes, base_url.as_deref());
+    (
+        [(header::CONTENT_TYPE, json_feed::CONTENT_TYPE)],
+        feed.to_string(),
+    )
+        .into_response()
+}
+
+async fn metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        crate::metrics::render_prometheus(&crate::metrics::snapshot()),
+    )
+}
+
+fn token_from(request: &Request) -> Option<String> {
+    let headers = request.headers();
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let cookie = headers
+        .get(header::COOKIE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|cookies| {
+            cookies
+                .split(';')
+                .find_map(|cookie| cookie.trim().strip_prefix(&format!("{COOKIE}=")))
+        });
+    let query = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+    });
+    bearer.or(cookie).or(query).map(String::from)
+}
+
+async fn auth(AxumState(site): AxumState<SharedSite>, request: Request, next: Next) -> Response {
+    let token = token_from(&request);
+    if token.as_deref() != Some(site.token.as_str()) {
+        return (StatusCode::UNAUTHORIZED, "A valid token is required").into_response();
+    }
+    if crate::app_lock::ensure_unlocked().is_err() {
+        return (StatusCode::LOCKED, "Yaks is locked").into_response();
+    }
+    let mut response = next.run(request).await;
+    let cookie = format!("{COOKIE}={}; Path=/; HttpOnly; SameSite=Strict", site.token);
+    if let Ok(cookie) = cookie.parse() {
+        response.headers_mut().insert(header::SET_COOKIE, cookie);
+    }
+    response
+}
+
+/// What the browser extension posts to `/capture`.
+#[derive(Debug, Deserialize)]
+struct CapturePayload {
+    url: String,
+    title: Option<String>,
+    /// Text selected on the page, if any
+    selection: Option<String>,
+    /// A PNG or JPEG as a data URL or bare base64
+    screenshot: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Captured {
+    note_id: String,
+    attachment_id: Option<String>,
+}
+
+/// The clip's note: its title, the selection quoted, then the link.
+fn clip_markdown(payload: &CapturePayload) -> String {
+    let title = payload
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .unwrap_or(&payload.url);
+    let mut markdown = format!("# {title}\n\n");
+    if let Some(selection) = payload
+        .selection
+        .as_deref()
+        .filter(|selection| !selection.trim().is_empty())
+    {
+        for line in selection.trim().lines() {
+            markdown.push_str(&format!("> {line}\n"));
+        }
+        markdown.push('\n');
+    }
+    markdown.push_str(&format!("<{}>\n", payload.url));
+    markdown
+}
+
+/// Decodes a screenshot, returning its bytes and MIME type.
+fn decode_screenshot(screenshot: &str) -> Result<(Vec<u8>, String), String> {
+    let (mime, data) = match screenshot.strip_prefix("data:") {
+        Some(rest) => {
+            let (mime, data) = rest
+                .split_once(";base64,")
+                .ok_or("Screenshots must be base64 data URLs")?;
+            (mime.to_string(), data)
+        }
+        None => ("image/png".to_string(), screenshot),
+    };
+    if !mime.starts_with("image/") {
+        return Err(format!("Unsupported screenshot type {mime}"));
+    }
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| format!("Invalid screenshot: {e}"))?;
+    Ok((bytes, mime))
+}
+
+/// The CORS headers for an allowed `Origin`, or `Err` if the request came from a page or
+/// extension that isn't on the list. Requests without an origin don't come from a browser
+/// and only need the token.
+fn cors(capture: &Capture, headers: &HeaderMap) -> Result<HeaderMap, StatusCode> {
+    let mut cors = HeaderMap::new();
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return Ok(cors);
+    };
+    let allowed = origin
+        .to_str()
+        .is_ok_and(|origin| capture.origins.iter().any(|allowed| allowed == origin));
+    if !allowed {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    cors.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+    cors.insert(
+        header::ACCESS_CONTROL_ALLOW_METHODS,
+        HeaderValue::from_static("POST, OPTIONS"),
+    );
+    cors.insert(
+        header::ACCESS_CONTROL_ALLOW_HEADERS,
+        HeaderValue::from_static("authorization, content-type"),
+    );
+    cors.insert(header::VARY, HeaderValue::from_static("Origin"));
+    Ok(cors)
+}
+
+async fn capture_preflight(
+    AxumState(capture): AxumState<SharedCapture>,
+    headers: HeaderMap,
+) -> Response {
+    match cors(&capture, &headers) {
+        Ok(cors) => (StatusCode::NO_CONTENT, cors).into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+async fn save_clip(capture: &Capture, payload: &CapturePayload) -> Result<Captured, String> {
+    let _guard = crate::shutdown::begin_write(&capture.app)?;
+    if !payload.url.starts_with("http://") && !payload.url.starts_with("https://") {
+        return Err(format!("Not a web page: {}", payload.url));
+    }
+    // Decoded first so a bad screenshot doesn't leave a clip without it
+    let screenshot = payload
+        .screenshot
+        .as_deref()
+        .map(decode_screenshot)
+        .transpose()?;
+
+    let content = secrets::check(&capture.app, "note.create", clip_markdown(payload));
+    let meta = HashMap::from([
+        ("yak_id".to_string(), capture.yak_id.clone().into()),
+        ("source".to_string(), "browser".into()),
+        ("url".to_string(), payload.url.clone().into()),
+        ("title".to_string(), payload.title.clone().into()),
+    ]);
+    let request = AppendRequest {
+        topic: "note.create".to_string(),
+        content,
+        meta: Some(meta),
+    };
+    let request = capture.app.state::<LockState>().seal_request(request)?;
+    let frames = append_batch_to_store(&capture.store, vec![request]).await?;
+    emit_frames(&capture.app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))?;
+    let note_id = frames[0].id.to_string();
+
+    let attachment_id = match screenshot {
+        Some((bytes, mime)) => {
+            let extension = mime.strip_prefix("image/").unwrap_or("png");
+            let meta = serde_json::json!({
+                "yak_id": capture.yak_id,
+                "note_id": note_id,
+                "name": format!("screenshot.{extension}"),
+                "mime": mime,
+                "size": bytes.len(),
+            });
+            let frame =
+                append_frame(&capture.store, "attachment.add", Some(&bytes), Some(meta)).await?;
+            emit_frame(&capture.app, &frame).map_err(|e| format!("Failed to emit frame: {e}"))?;
+            Some(frame.id.to_string())
+        }
+        None => None,
+    };
+    Ok(Captured {
+        note_id,
+        attachment_id,
+    })
+}
+
+fn refuse(refusal: Refusal, mut cors: HeaderMap) -> Response {
+    let (retry_after, message) = match refusal {
+        Refusal::RateLimited(seconds) => (seconds, "Too many captures, slow down"),
+        Refusal::OverQuota(seconds) => (seconds, "Today's capture quota is used up"),
+    };
+    if let Ok(value) = HeaderValue::from_str(&retry_after.to_string()) {
+        cors.insert(header::RETRY_AFTER, value);
+    }
+    (StatusCode::TOO_MANY_REQUESTS, cors, message).into_response()
+}
+
+/// Appends a web clip from the browser extension: a note with the page's title, selection
+/// and link, plus the screenshot as an attachment. The token and limits are checked before
+/// the body is read.
+async fn capture_clip(AxumState(capture): AxumState<SharedCapture>, request: Request) -> Response {
+    let headers = request.headers();
+    let cors = match cors(&capture, headers) {
+        Ok(cors) => cors,
+        Err(status) => return status.into_response(),
+    };
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if bearer != Some(capture.token.as_str()) {
+        return (StatusCode::UNAUTHORIZED, cors, "A valid token is required").into_response();
+    }
+    let admitted = capture
+        .usage
+        .lock()
+        .unwrap()
+        .admit(&capture.token, &capture.limits, Utc::now());
+    if let Err(refusal) = admitted {
+        return refuse(refusal, cors);
+    }
+    let limit = capture.limits.max_body_bytes;
+    let body = match axum::body::to_bytes(request.into_body(), limit).await {
+        Ok(body) => body,
+        Err(_) => {
+            capture.usage.lock().unwrap().too_large += 1;
+            let message = format!("Captures are limited to {limit} bytes");
+            return (StatusCode::PAYLOAD_TOO_LARGE, cors, message).into_response();
+        }
+    };
+    capture.usage.lock().unwrap().bytes += body.len() as u64;
+    let payload: CapturePayload = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                cors,
+                format!("Invalid capture: {e}"),
+            )
+                .into_response()
+        }
+    };
+    let saved = provenance::scope(Source::Http, save_clip(&capture, &payload));
+    match permissions::scope(Integration::Http, saved).await {
+        Ok(captured) => (StatusCode::CREATED, cors, Json(captured)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, cors, e).into_response(),
+    }
+}
+
+fn router(
+    site: Option<SharedSite>,
+    capture: Option<SharedCapture>,
+    shares: Option<Arc<Shares>>,
+) -> Router {
+    let mut router = Router::new();
+    if let Some(site) = site {
+        router = router.merge(
+            Router::new()
+                .route("/", get(index))
+                .route("/note/:id", get(note))
+                .route("/tag/:tag", get(tag))
+                .route("/search", get(search))
+                .route("/feed.json", get(feed))
+                .route("/metrics", get(metrics))
+                .layer(middleware::from_fn_with_state(site.clone(), auth))
+                .with_state(site),
+        );
+    }
+    if let Some(capture) = capture {
+        router = router.merge(
+            Router::new()
+                .route("/capture", post(capture_clip).options(capture_preflight))
+                .with_state(capture),
+        );
+    }
+    if let Some(shares) = shares {
+        // The signed link is the credential, so no token
+        router = router.merge(
+            Router::new()
+                .route("/shared/:link_id", get(shared_note))
+                .with_state(shares),
+        );
+    }
+    router
+}
+
+/// Restarts the server for `config`, or leaves it stopped when there's nothing to serve.
+pub(crate) async fn apply_config(
+    app: &AppHandle,
+    store: &Store,
+    state: &PublishState,
+    config: &PublishConfig,
+) {
+    let mut server = state.server.lock().await;
+    if let Some(handle) = server.take() {
+        handle.abort();
+    }
+    let site = match (config.enabled, &config.yak_id, &config.token) {
+        (true, Some(yak_id), Some(token)) => Some(Arc::new(Site {
+            store: store.clone(),
+            yak_id: yak_id.clone(),
+            token: token.clone(),
+        })),
+        _ => None,
+    };
+    let capture = &config.capture;
+    let capture = match (capture.enabled, &capture.yak_id, &capture.token) {
+        (true, Some(yak_id), Some(token)) => Some(Arc::new(Capture {
+            app: app.clone(),
+            store: store.clone(),
+            yak_id: yak_id.clone(),
+            token: token.clone(),
+            origins: capture.origins.clone(),
+            limits: capture.clone(),
+            usage: state.usage.clone(),
+        })),
+        _ => None,
+    };
+    let shares = share_links::serving(store, config).await;
+    if site.is_none() && capture.is_none() && shares.is_none() {
+        return;
+    }
+    let port = config.port;
+    *server = Some(tokio::spawn(async move {
+        let listener = match tokio::net::TcpListener::bind(("0.0.0.0", port)).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Failed to publish on port {port}: {e}");
+                return;
+            }
+        };
+        if let Err(e) = axum::serve(listener, router(site, capture, shares)).await {
+            eprintln!("Publish server stopped: {e}");
+        }
+    }));
+}
+
+/// Where the server can be reached from elsewhere on the network.
+pub(crate) fn base_url(port: u16) -> String {
+    let host = local_ip_address::local_ip()
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|_| "localhost".to_string());
+    format!("http://{host}:{port}")
+}
+
+fn status(config: PublishConfig) -> PublishStatus {
+    let url = match (&config.enabled, &config.token) {
+        (true, Some(token)) => Some(format!("{}/?token={token}", base_url(config.port))),
+        _ => None,
+    };
+    PublishStatus { config, url }
+}
+
+pub(crate) async fn initialize(app: &AppHandle, store: &Store, frames: &[Frame]) {
+    let config: PublishConfig = latest_setting(frames, CONFIG_TOPIC);
+    apply_config(app, store, &app.state::<PublishState>(), &config).await;
+}
+
+/// Starts, stops or reconfigures serving a yak read-only on the local network, and the
+/// browser extension's `/capture` endpoint.
+#[tauri::command]
+pub async fn configure_publish(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, PublishState>,
+    mut config: PublishConfig,
+) -> Result<PublishStatus, String> {
+    if config.enabled && config.yak_id.is_none() {
+        return Err("Choose a yak to publish".to_string());
+    }
+    if config.capture.enabled && config.capture.yak_id.is_none() {
+        return Err("Choose a yak for captured pages".to_string());
+    }
+    if config.capture.max_body_bytes == 0 {
+        return Err("Captures need room for a body".to_string());
+    }
+    if config.token.is_none() {
+        config.token = Some(crypto::to_hex(&crypto::random_salt()));
+    }
+    if config.capture.token.is_none() {
+        config.capture.token = Some(crypto::to_hex(&crypto::random_salt()));
+    }
+    if config.share_key.is_none() {
+        let saved: PublishConfig = load_setting(&store, CONFIG_TOPIC).await;
+        config.share_key = saved.share_key;
+    }
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    apply_config(&app, &store, &state, &config).await;
+    Ok(status(config))
+}
+
+#[tauri::command]
+pub async fn publish_status(store: State<'_, Store>) -> Result<PublishStatus, String> {
+    let config: PublishConfig = load_setting(&store, CONFIG_TOPIC).await;
+    Ok(status(config))
+}
+
+/// What `/capture` has let through and turned away today, against its daily quota.
+#[tauri::command]
+pub async fn get_ingest_usage(
+    store: State<'_, Store>,
+    state: State<'_, PublishState>,
+) -> Result<IngestUsage, String> {
+    let config: PublishConfig = load_setting(&store, CONFIG_TOPIC).await;
+    let mut usage = state.usage.lock().unwrap();
+    Ok(usage.report(&config.capture, Utc::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_from() {
+        let request = |builder: axum::http::request::Builder| {
+            builder.body(axum::body::Body::empty()).unwrap()
+        };
+        let from_query = request(Request::builder().uri("/note/x?a=1&token=abc"));
+        assert_eq!(token_from(&from_query).as_deref(), Some("abc"));
+        let from_cookie = request(
+            Request::builder()
+                .uri("/")
+                .header(header::COOKIE, "theme=dark; yaks_token=def"),
+        );
+        assert_eq!(token_from(&from_cookie).as_deref(), Some("def"));
+        assert_eq!(token_from(&request(Request::builder().uri("/"))), None);
+    }
+
+    #[test]
+    fn test_clip_markdown() {
+        let payload = CapturePayload {
+            url: "https://example.com/post".to_string(),
+            title: Some("A post".to_string()),
+            selection: Some("first line\nsecond line".to_string()),
+            screenshot: None,
+        };
+        assert_eq!(
+            clip_markdown(&payload),
+            "# A post\n\n> first line\n> second line\n\n<https://example.com/post>\n"
+        );
+    }
+
+    #[test]
+    fn test_decode_screenshot() {
+        let (bytes, mime) = decode_screenshot("data:image/jpeg;base64,/9j/").unwrap();
+        assert_eq!(mime, "image/jpeg");
+        assert_eq!(bytes, vec![0xff, 0xd8, 0xff]);
+        assert_eq!(decode_screenshot("iVBORw==").unwrap().1, "image/png");
+        assert!(decode_screenshot("data:text/html;base64,PGI+").is_err());
+    }
+
+    #[test]
+    fn test_admit() {
+        let config = CaptureConfig {
+            rate_limit: 2,
+            daily_quota: 3,
+            ..CaptureConfig::default()
+        };
+        let at = |time: &str| {
+            DateTime::parse_from_rfc3339(time)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let mut usage = Usage::default();
+        assert!(usage
+            .admit("a", &config, at("2024-05-01T23:58:00Z"))
+            .is_ok());
+        assert!(usage
+            .admit("a", &config, at("2024-05-01T23:58:20Z"))
+            .is_ok());
+        assert_eq!(
+            usage.admit("a", &config, at("2024-05-01T23:58:30Z")),
+            Err(Refusal::RateLimited(30))
+        );
+        // Other tokens have their own limit, but share the quota
+        assert!(usage
+            .admit("b", &config, at("2024-05-01T23:58:30Z"))
+            .is_ok());
+        assert_eq!(
+            usage.admit("a", &config, at("2024-05-01T23:59:00Z")),
+            Err(Refusal::OverQuota(60))
+        );
+        let report = usage.report(&config, at("2024-05-01T23:59:00Z"));
+        assert_eq!(
+            (report.requests, report.rate_limited, report.over_quota),
+            (3, 1, 1)
+        );
+
+        // A new day starts a new quota
+        assert!(usage
+            .admit("a", &config, at("2024-05-02T00:00:00Z"))
+            .is_ok());
+        assert_eq!(
+            usage.report(&config, at("2024-05-02T00:00:00Z")).requests,
+            1
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::settings::{latest_setting, save_setting};
+
+const CONFIG_TOPIC: &str = "rates.alerts";
+/// Window rates are counted over.
+const WINDOW: Duration = Duration::from_secs(60);
+const DEFAULT_PER_MINUTE: usize = 300;
+/// Every background capture that can be paused.
+pub(crate) const INGESTERS: &[&str] = &["clipboard", "feeds", "folder", "mail"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateAlertConfig {
+    /// Appends to one topic within a minute that count as runaway
+    pub per_minute: usize,
+    /// Pause the ingester behind a runaway topic until it's resumed
+    pub auto_pause: bool,
+}
+
+impl Default for RateAlertConfig {
+    fn default() -> Self {
+        Self {
+            per_minute: DEFAULT_PER_MINUTE,
+            auto_pause: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TopicRate {
+    pub topic: String,
+    /// Appends over the last minute
+    pub per_minute: usize,
+    /// Since the app started
+    pub total: u64,
+    /// The ingester its latest frame came from, if any
+    pub ingester: Option<&'static str>,
+}
+
+/// Sent as "topic-rate-warning" when a topic goes over the configured rate.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RateWarning {
+    pub topic: String,
+    pub per_minute: usize,
+    pub ingester: Option<&'static str>,
+    /// Whether the ingester was paused for it
+    pub paused: bool,
+}
+
+#[derive(Debug, Default)]
+struct TopicCounter {
+    recent: VecDeque<Instant>,
+    total: u64,
+    ingester: Option<&'static str>,
+    /// Set while over the rate, so a burst is warned about once
+    alerted: bool,
+}
+
+#[derive(Default)]
+pub struct RatesState {
+    config: Mutex<RateAlertConfig>,
+    topics: Mutex<HashMap<String, TopicCounter>>,
+    paused: Mutex<BTreeSet<String>>,
+}
+
+/// The background capture behind a frame, which can be paused when it runs away.
+fn ingester(frame: &Frame) -> Option<&'static str> {
+    match frame.topic.as_str() {
+        "feed.item" => return Some("feeds"),
+        "clip" => return Some("clipboard"),
+        _ => {}
+    }
+    let source = frame.meta.as_ref()?.get("source")?.as_str()?;
+    ["mail", "folder"]
+        .into_iter()
+        .find(|ingester| *ingester == source)
+}
+
+impl RatesState {
+    /// Counts an append at `now`, returning a warning when it takes its topic over the rate.
+    fn record(&self, frame: &Frame, now: Instant) -> Option<RateWarning> {
+        let config = self.config.lock().unwrap().clone();
+        let mut topics = self.topics.lock().unwrap();
+        let counter = topics.entry(frame.topic.clone()).or_default();
+        counter.recent.push_back(now);
+        counter.total += 1;
+        counter.ingester = ingester(frame).or(counter.ingester);
+        while counter
+            .recent
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > WINDOW)
+        {
+            counter.recent.pop_front();
+        }
+
+        let per_minute = counter.recent.len();
+        if per_minute <= config.per_minute {
+            counter.alerted = false;
+            return None;
+        }
+        if counter.alerted {
+            return None;
+        }
+        counter.alerted = true;
+        let ingester = counter.ingester;
+        let paused = match ingester.filter(|_| config.auto_pause) {
+            Some(ingester) => self.pause(ingester),
+            None => false,
+        };
+        Some(RateWarning {
+            topic: frame.topic.clone(),
+            per_minute,
+            ingester,
+            paused,
+        })
+    }
+
+    fn rates(&self, now: Instant) -> Vec<TopicRate> {
+        let topics = self.topics.lock().unwrap();
+        let mut rates: Vec<TopicRate> = topics
+            .iter()
+            .map(|(topic, counter)| TopicRate {
+                topic: topic.clone(),
+                per_minute: counter
+                    .recent
+                    .iter()
+                    .filter(|at| now.duration_since(**at) <= WINDOW)
+                    .count(),
+                total: counter.total,
+                ingester: counter.ingester,
+            })
+            .collect();
+        rates.sort_by(|a, b| b.per_minute.cmp(&a.per_minute).then(a.topic.cmp(&b.topic)));
+        rates
+    }
+
+    /// Whether an ingester has been paused for running away; ingesters check this before
+    /// capturing more.
+    pub(crate) fn is_paused(&self, ingester: &str) -> bool {
+        self.paused.lock().unwrap().contains(ingester)
+    }
+
+    /// Pauses an ingester, returning whether it was running.
+    pub(crate) fn pause(&self, ingester: &str) -> bool {
+        self.paused.lock().unwrap().insert(ingester.to_string())
+    }
+
+    /// Lets a paused ingester capture again, returning whether it was paused.
+    pub(crate) fn resume(&self, ingester: &str) -> bool {
+        self.paused.lock().unwrap().remove(ingester)
+    }
+}
+
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let config: RateAlertConfig = latest_setting(frames, CONFIG_TOPIC);
+    *app.state::<RatesState>().config.lock().unwrap() = config;
+}
+
+/// Counts appends per topic as they happen, warning windows when one runs away.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    let mut caught_up = false;
+    while let Some(frame) = rx.recv().await {
+        // Only appends made while the app runs count towards rates
+        if frame.topic == "xs.threshold" {
+            caught_up = true;
+            continue;
+        }
+        if !caught_up {
+            continue;
+        }
+        let Some(warning) = app.state::<RatesState>().record(&frame, Instant::now()) else {
+            continue;
+        };
+        eprintln!(
+            "Topic {} is being appended {} times a minute",
+            warning.topic, warning.per_minute
+        );
+        if let Err(e) = app.emit("topic-rate-warning", &warning) {
+            eprintln!("Failed to emit topic rate warning: {e}");
+        }
+    }
+}
+
+/// Appends per topic over the last minute and since the app started, busiest first.
+#[tauri::command]
+pub async fn get_topic_rates(state: State<'_, RatesState>) -> Result<Vec<TopicRate>, String> {
+    Ok(state.rates(Instant::now()))
+}
+
+/// Sets the per-topic rate windows are warned at with "topic-rate-warning", and whether the
+/// feed, mail, folder or clipboard ingester behind the topic is paused as well.
+#[tauri::command]
+pub async fn configure_rate_alerts(
+    store: State<'_, Store>,
+    state: State<'_, RatesState>,
+    per_minute: usize,
+    auto_pause: bool,
+) -> Result<RateAlertConfig, String> {
+    if per_minute == 0 {
+        return Err("The rate must be at least one append a minute".to_string());
+    }
+    let config = RateAlertConfig {
+        per_minute,
+        auto_pause,
+    };
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    *state.config.lock().unwrap() = config.clone();
+    Ok(config)
+}
+
+/// The ingesters paused for running away.
+#[tauri::command]
+pub async fn get_paused_ingesters(state: State<'_, RatesState>) -> Result<Vec<String>, String> {
+    Ok(state.paused.lock().unwrap().iter().cloned().collect())
+}
+
+#[tauri::command]
+pub async fn resume_ingester(state: State<'_, RatesState>, ingester: String) -> Result<(), String> {
+    if !state.resume(&ingester) {
+        return Err(format!("{ingester} isn't paused"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_rates() {
+        let (_dir, store) = testing::store();
+        let frame = |topic: &str| append(&store, topic, Some(json!({ "source": "mail" })));
+        let state = RatesState::default();
+        *state.config.lock().unwrap() = RateAlertConfig {
+            per_minute: 3,
+            auto_pause: true,
+        };
+        let start = Instant::now();
+        let mut warnings = Vec::new();
+        for i in 0..6 {
+            let at = start + Duration::from_secs(i);
+            warnings.extend(state.record(&frame("feed.item"), at));
+            state.record(&frame("note.create"), at + Duration::from_secs(30));
+        }
+        // Warned once per burst, with the feed ingester paused
+        assert_eq!(
+            warnings,
+            vec![RateWarning {
+                topic: "feed.item".to_string(),
+                per_minute: 4,
+                ingester: Some("feeds"),
+                paused: true,
+            }]
+        );
+        assert!(state.is_paused("feeds"));
+        // The mail notes in the same window were warned about too, pausing mail
+        assert!(state.is_paused("mail"));
+
+        let rates = state.rates(start + Duration::from_secs(40));
+        let feed = rates.iter().find(|rate| rate.topic == "feed.item").unwrap();
+        assert_eq!((feed.per_minute, feed.total), (6, 6));
+
+        // Once the burst is over, the rate falls back
+        let later = start + Duration::from_secs(200);
+        assert!(state.record(&frame("feed.item"), later).is_none());
+        assert_eq!(state.rates(later)[0].per_minute, 1);
+    }
+}
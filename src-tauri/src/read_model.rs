@@ -0,0 +1,1282 @@
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::RwLock;
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::export::note_title;
+use crate::indexes::Progress;
+use crate::locale::LocaleState;
+use crate::location::StoreLocation;
+use crate::locks::{self, LockState};
+use crate::presentation::PresentationState;
+use crate::profiles;
+use crate::projection::{Note, Projection, Task, Yak};
+use crate::search::{
+    filter_notes, keyword_search, note_status, parse_query, searchable_text, Match, NoteStatus,
+    SearchScope,
+};
+use crate::semantic::wiki_targets;
+use crate::{compaction, health, history, insights, read_all_frames, recovery, retention};
+
+const DB_FILE: &str = "read-model.sqlite";
+/// Bumped when the tables change; an older database is emptied and rebuilt from the log.
+const FORMAT: &str = "3";
+/// Frames applied between writes while catching up with the log.
+const BATCH: usize = 1000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS state (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS yaks (
+    id TEXT PRIMARY KEY,
+    name TEXT,
+    data TEXT NOT NULL,
+    note_ids TEXT NOT NULL,
+    board TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS notes (
+    id TEXT PRIMARY KEY,
+    yak_id TEXT NOT NULL,
+    current INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    content TEXT,
+    parent_id TEXT,
+    data TEXT NOT NULL,
+    status TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS notes_by_yak ON notes (yak_id, current);
+CREATE INDEX IF NOT EXISTS notes_by_status ON notes (status, yak_id);
+CREATE TABLE IF NOT EXISTS tags (
+    note_id TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (note_id, tag)
+);
+CREATE INDEX IF NOT EXISTS tags_by_tag ON tags (tag);
+CREATE TABLE IF NOT EXISTS tasks (
+    id TEXT PRIMARY KEY,
+    yak_id TEXT NOT NULL,
+    note_id TEXT,
+    done INTEGER NOT NULL,
+    content TEXT,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS tasks_by_yak ON tasks (yak_id, done);
+CREATE TABLE IF NOT EXISTS links (
+    note_id TEXT NOT NULL,
+    target TEXT NOT NULL,
+    PRIMARY KEY (note_id, target)
+);
+CREATE INDEX IF NOT EXISTS links_by_target ON links (target);
+CREATE VIRTUAL TABLE IF NOT EXISTS notes_fts USING fts5(note_id UNINDEXED, title, content);
+";
+
+/// One row of the read model, with the tags and links that hang off a note.
+#[derive(Debug)]
+enum Row {
+    Yak {
+        id: String,
+        name: Option<String>,
+        data: String,
+        note_ids: String,
+        board: String,
+    },
+    Note {
+        id: String,
+        yak_id: String,
+        current: bool,
+        title: String,
+        content: Option<String>,
+        parent_id: Option<String>,
+        data: String,
+        status: NoteStatus,
+        tags: Vec<String>,
+        links: Vec<String>,
+        /// What the full-text table holds for a note that isn't an old revision
+        text: Option<String>,
+    },
+    Task {
+        id: String,
+        yak_id: String,
+        note_id: Option<String>,
+        done: bool,
+        content: Option<String>,
+        data: String,
+    },
+}
+
+/// What to write to bring the database level with the projection.
+#[derive(Debug, Default)]
+struct Changes {
+    rows: Vec<Row>,
+    deleted: Vec<(&'static str, String)>,
+    cursor: Option<String>,
+    schema_version: u64,
+}
+
+/// The projection with its content resolved, and what was last written of it.
+#[derive(Default)]
+struct Model {
+    projection: Projection,
+    cursor: Option<scru128::Scru128Id>,
+    /// Fingerprints of the rows last written, by table and id
+    written: HashMap<(&'static str, String), u64>,
+}
+
+impl Model {
+    /// Folds in a frame from the log, unless it was folded in before.
+    fn apply(&mut self, frame: &Frame) -> bool {
+        if self.cursor.is_some_and(|cursor| frame.id <= cursor) {
+            return false;
+        }
+        self.projection.apply(frame);
+        self.cursor = Some(frame.id);
+        true
+    }
+
+    /// Reads the content of notes, tasks and extracts that arrived since the last call,
+    /// reporting each note done.
+    async fn resolve_content(&mut self, store: &Store, mut report: impl FnMut(usize, usize)) {
+        async fn read(store: &Store, hash: &ssri::Integrity) -> Option<String> {
+            let bytes = store.cas_read(hash).await.ok()?;
+            String::from_utf8(bytes).ok()
+        }
+        let total = self.projection.notes.len();
+        let yaks = &self.projection.yaks;
+        for (i, note) in self.projection.notes.values_mut().enumerate() {
+            report(i, total);
+            // Revisions of a locked yak written in the clear are withheld, so not read again
+            let withheld = yaks.get(&note.yak_id).is_some_and(|yak| yak.locked)
+                && note
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get("encrypted"))
+                    .is_none();
+            if let (None, None, Some(hash), false) =
+                (&note.content, &note.sealed, &note.hash, withheld)
+            {
+                note.content = read(store, hash).await;
+            }
+            let extracts = note
+                .attachments
+                .iter_mut()
+                .flat_map(|attachment| attachment.extracts.iter_mut());
+            for extract in extracts {
+                if let (None, Some(hash)) = (&extract.content, &extract.hash) {
+                    extract.content = read(store, hash).await;
+                }
+            }
+        }
+        for task in self.projection.tasks.values_mut() {
+            if let (None, Some(hash)) = (&task.content, &task.hash) {
+                task.content = read(store, hash).await;
+            }
+        }
+        // Also drops content read before its yak was locked, so it leaves the index
+        self.projection.withhold_locked();
+    }
+
+    /// The rows that changed since the last call, and those that are gone. Rows are compared
+    /// by fingerprint, so unchanged content isn't copied.
+    fn changes(&mut self) -> Changes {
+        let projection = &mut self.projection;
+        let current: HashSet<String> = projection
+            .notes_by_yak
+            .values()
+            .flatten()
+            .cloned()
+            .collect();
+        let mut seen = HashSet::new();
+        let mut rows = Vec::new();
+        let mut changed = |key: (&'static str, String), fingerprint: u64| {
+            seen.insert(key.clone());
+            self.written.insert(key, fingerprint) != Some(fingerprint)
+        };
+
+        for yak in projection.yaks.values() {
+            let data = to_json(yak);
+            let note_ids = to_json(&projection.notes_by_yak.get(&yak.id));
+            let board = to_json(&projection.boards.get(&yak.id));
+            if changed(
+                ("yaks", yak.id.clone()),
+                fingerprint(&(&data, &note_ids, &board)),
+            ) {
+                rows.push(Row::Yak {
+                    id: yak.id.clone(),
+                    name: yak.name.clone(),
+                    data,
+                    note_ids,
+                    board,
+                });
+            }
+        }
+        let statuses: HashMap<String, NoteStatus> = projection
+            .notes
+            .values()
+            .map(|note| (note.id.clone(), note_status(projection, &current, note)))
+            .collect();
+        for note in projection.notes.values_mut() {
+            // Content has a column of its own
+            let content = note.content.take();
+            let data = to_json(&*note);
+            note.content = content;
+            let is_current = current.contains(&note.id);
+            let status = statuses[&note.id];
+            let parent_id = projection.parents.get(&note.id);
+            let print = fingerprint(&(&data, status, parent_id, &note.content));
+            if !changed(("notes", note.id.clone()), print) {
+                continue;
+            }
+            rows.push(Row::Note {
+                id: note.id.clone(),
+                yak_id: note.yak_id.clone(),
+                current: is_current,
+                title: note_title(note),
+                content: note.content.clone(),
+                parent_id: parent_id.cloned(),
+                data,
+                status,
+                tags: note.tags.iter().cloned().collect(),
+                links: note
+                    .content
+                    .as_deref()
+                    .map(|content| wiki_targets(content).collect())
+                    .unwrap_or_default(),
+                text: (status != NoteStatus::Revision).then(|| searchable_text(note)),
+            });
+        }
+        for task in projection.tasks.values_mut() {
+            let content = task.content.take();
+            let data = to_json(&*task);
+            task.content = content;
+            if !changed(
+                ("tasks", task.id.clone()),
+                fingerprint(&(&data, &task.content)),
+            ) {
+                continue;
+            }
+            rows.push(Row::Task {
+                id: task.id.clone(),
+                yak_id: task.yak_id.clone(),
+                note_id: task.note_id.clone(),
+                done: task.done,
+                content: task.content.clone(),
+                data,
+            });
+        }
+
+        let deleted: Vec<(&'static str, String)> = self
+            .written
+            .keys()
+            .filter(|key| !seen.contains(*key))
+            .cloned()
+            .collect();
+        for key in &deleted {
+            self.written.remove(key);
+        }
+        Changes {
+            rows,
+            deleted,
+            cursor: self.cursor.map(|cursor| cursor.to_string()),
+            schema_version: self.projection.schema_version,
+        }
+    }
+}
+
+fn fingerprint<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn get_state(conn: &Connection, key: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT value FROM state WHERE key = ?1", [key], |row| {
+        row.get(0)
+    })
+    .optional()
+}
+
+fn set_state(tx: &Transaction, key: &str, value: &str) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT OR REPLACE INTO state (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+fn clear(conn: &mut Connection, store_path: &str) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for table in [
+        "state",
+        "yaks",
+        "notes",
+        "tags",
+        "tasks",
+        "links",
+        "notes_fts",
+    ] {
+        tx.execute(&format!("DELETE FROM {table}"), [])?;
+    }
+    set_state(&tx, "format", FORMAT)?;
+    set_state(&tx, "store", store_path)?;
+    tx.commit()
+}
+
+/// Opens the database for the store at `store_path`, emptying it if it was built for another
+/// store or an older format.
+fn open(path: &Path, store_path: &str) -> rusqlite::Result<Connection> {
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+    let format = get_state(&conn, "format")?;
+    let store = get_state(&conn, "store")?;
+    if format.as_deref() != Some(FORMAT) || store.as_deref() != Some(store_path) {
+        clear(&mut conn, store_path)?;
+    }
+    Ok(conn)
+}
+
+/// Writes `changes` in one transaction, reporting each row done.
+fn write(
+    conn: &mut Connection,
+    changes: &Changes,
+    mut report: impl FnMut(usize, usize),
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for (i, row) in changes.rows.iter().enumerate() {
+        report(i, changes.rows.len());
+        match row {
+            Row::Yak {
+                id,
+                name,
+                data,
+                note_ids,
+                board,
+            } => {
+                tx.prepare_cached(
+                    "INSERT OR REPLACE INTO yaks (id, name, data, note_ids, board)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                )?
+                .execute(params![id, name, data, note_ids, board])?;
+            }
+            Row::Note {
+                id,
+                yak_id,
+                current,
+                title,
+                content,
+                parent_id,
+                data,
+                status,
+                tags,
+                links,
+                text,
+            } => {
+                tx.prepare_cached(
+                    "INSERT OR REPLACE INTO notes
+                     (id, yak_id, current, title, content, parent_id, data, status)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                )?
+                .execute(params![
+                    id,
+                    yak_id,
+                    current,
+                    title,
+                    content,
+                    parent_id,
+                    data,
+                    status.as_str()
+                ])?;
+                tx.prepare_cached("DELETE FROM tags WHERE note_id = ?1")?
+                    .execute([id])?;
+                for tag in tags {
+                    tx.prepare_cached("INSERT OR IGNORE INTO tags (note_id, tag) VALUES (?1, ?2)")?
+                        .execute(params![id, tag])?;
+                }
+                tx.prepare_cached("DELETE FROM links WHERE note_id = ?1")?
+                    .execute([id])?;
+                for target in links {
+                    tx.prepare_cached(
+                        "INSERT OR IGNORE INTO links (note_id, target) VALUES (?1, ?2)",
+                    )?
+                    .execute(params![id, target])?;
+                }
+                let text = text.as_deref().map(|text| (title.as_str(), text));
+                index_text(&tx, id, text)?;
+            }
+            Row::Task {
+                id,
+                yak_id,
+                note_id,
+                done,
+                content,
+                data,
+            } => {
+                tx.prepare_cached(
+                    "INSERT OR REPLACE INTO tasks (id, yak_id, note_id, done, content, data)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )?
+                .execute(params![id, yak_id, note_id, done, content, data])?;
+            }
+        }
+    }
+    for (table, id) in &changes.deleted {
+        tx.execute(&format!("DELETE FROM {table} WHERE id = ?1"), [id])?;
+        if *table == "notes" {
+            tx.execute("DELETE FROM tags WHERE note_id = ?1", [id])?;
+            tx.execute("DELETE FROM links WHERE note_id = ?1", [id])?;
+            index_text(&tx, id, None)?;
+        }
+    }
+    if let Some(cursor) = &changes.cursor {
+        set_state(&tx, "cursor", cursor)?;
+    }
+    set_state(&tx, "schema_version", &changes.schema_version.to_string())?;
+    tx.commit()
+}
+
+/// Puts a note's title and text in the full-text table, or takes it out.
+fn index_text(tx: &Transaction, note_id: &str, text: Option<(&str, &str)>) -> rusqlite::Result<()> {
+    tx.prepare_cached("DELETE FROM notes_fts WHERE note_id = ?1")?
+        .execute([note_id])?;
+    if let Some((title, content)) = text {
+        tx.prepare_cached("INSERT INTO notes_fts (note_id, title, content) VALUES (?1, ?2, ?3)")?
+            .execute(params![note_id, title, content])?;
+    }
+    Ok(())
+}
+
+/// Replaces the full-text table's rows with `notes`' ids, titles and text.
+fn refill_text(
+    conn: &mut Connection,
+    notes: &[(String, String, String)],
+    mut report: impl FnMut(usize, usize),
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM notes_fts", [])?;
+    for (i, (id, title, text)) in notes.iter().enumerate() {
+        report(i, notes.len());
+        index_text(&tx, id, Some((title.as_str(), text.as_str())))?;
+    }
+    tx.commit()
+}
+
+/// Every word of `query`, quoted so FTS syntax in it is taken literally.
+fn fts_query(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{word}\""))
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Ids of notes in `scope` matching every word of `query`, best first. The scope is applied
+/// by the query itself, so the limit counts only notes in it.
+fn fts_search(
+    conn: &Connection,
+    query: &str,
+    scope: &SearchScope,
+    limit: usize,
+) -> rusqlite::Result<Vec<String>> {
+    let Some(query) = fts_query(query) else {
+        return Ok(Vec::new());
+    };
+    let yak_ids = scope.yak_ids().map(|ids| to_json(&ids));
+    let statuses = to_json(&scope.statuses());
+    let mut stmt = conn.prepare_cached(
+        "SELECT notes_fts.note_id FROM notes_fts JOIN notes ON notes.id = notes_fts.note_id
+         WHERE notes_fts MATCH ?1
+           AND (?2 IS NULL OR notes.yak_id IN (SELECT value FROM json_each(?2)))
+           AND notes.status IN (SELECT value FROM json_each(?3))
+         ORDER BY notes_fts.rank LIMIT ?4",
+    )?;
+    let ids = stmt.query_map(params![query, yak_ids, statuses, limit], |row| row.get(0))?;
+    ids.collect()
+}
+
+/// Rebuilds the model last written to the database.
+fn load(conn: &Connection) -> rusqlite::Result<Model> {
+    let mut projection = Projection::default();
+    let mut stmt = conn.prepare("SELECT data, note_ids, board FROM yaks")?;
+    let yaks = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+    for yak in yaks {
+        let (data, note_ids, board) = yak?;
+        let Ok(yak) = serde_json::from_str::<Yak>(&data) else {
+            continue;
+        };
+        if let Ok(Some(note_ids)) = serde_json::from_str(&note_ids) {
+            projection.notes_by_yak.insert(yak.id.clone(), note_ids);
+        }
+        if let Ok(Some(board)) = serde_json::from_str(&board) {
+            projection.boards.insert(yak.id.clone(), board);
+        }
+        projection.yaks.insert(yak.id.clone(), yak);
+    }
+
+    let mut stmt = conn.prepare("SELECT data, content, parent_id FROM notes ORDER BY id")?;
+    let notes = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<String>>(2)?,
+        ))
+    })?;
+    for note in notes {
+        let (data, content, parent_id) = note?;
+        let Ok(mut note) = serde_json::from_str::<Note>(&data) else {
+            continue;
+        };
+        note.content = content;
+        if let Some(original) = &note.edited_note_id {
+            projection
+                .replaced_by
+                .insert(original.clone(), note.id.clone());
+        }
+        if let Some(parent_id) = parent_id {
+            projection.parents.insert(note.id.clone(), parent_id);
+        }
+        projection.notes.insert(note.id.clone(), note);
+    }
+
+    let mut stmt = conn.prepare("SELECT data, content FROM tasks")?;
+    let tasks = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+    })?;
+    for task in tasks {
+        let (data, content) = task?;
+        let Ok(mut task) = serde_json::from_str::<Task>(&data) else {
+            continue;
+        };
+        task.content = content;
+        projection.tasks.insert(task.id.clone(), task);
+    }
+
+    projection.schema_version = get_state(conn, "schema_version")?
+        .and_then(|version| version.parse().ok())
+        .unwrap_or_default();
+    let cursor = get_state(conn, "cursor")?.and_then(|cursor| cursor.parse().ok());
+    let mut model = Model {
+        projection,
+        cursor,
+        written: HashMap::new(),
+    };
+    // Everything loaded is already written
+    model.changes();
+    Ok(model)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct YakCounts {
+    pub notes: usize,
+    pub open_tasks: usize,
+    pub done_tasks: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Counts {
+    pub yaks: BTreeMap<String, YakCounts>,
+    /// tag -> current notes carrying it
+    pub tags: BTreeMap<String, usize>,
+    /// The tags in the locale's alphabetical order, for listing them
+    pub tag_order: Vec<String>,
+}
+
+fn counts(conn: &Connection) -> rusqlite::Result<Counts> {
+    let mut counts = Counts::default();
+    let mut stmt = conn.prepare("SELECT id FROM yaks")?;
+    for id in stmt.query_map([], |row| row.get::<_, String>(0))? {
+        counts.yaks.insert(id?, YakCounts::default());
+    }
+    let mut stmt =
+        conn.prepare("SELECT yak_id, COUNT(*) FROM notes WHERE current = 1 GROUP BY yak_id")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, usize>(1)?))
+    })?;
+    for row in rows {
+        let (yak_id, notes) = row?;
+        counts.yaks.entry(yak_id).or_default().notes = notes;
+    }
+    let mut stmt =
+        conn.prepare("SELECT yak_id, SUM(done = 0), SUM(done = 1) FROM tasks GROUP BY yak_id")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, usize>(1)?,
+            row.get::<_, usize>(2)?,
+        ))
+    })?;
+    for row in rows {
+        let (yak_id, open, done) = row?;
+        let yak = counts.yaks.entry(yak_id).or_default();
+        yak.open_tasks = open;
+        yak.done_tasks = done;
+    }
+    let mut stmt = conn.prepare(
+        "SELECT tag, COUNT(*) FROM tags JOIN notes ON notes.id = tags.note_id
+         WHERE notes.current = 1 GROUP BY tag",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, usize>(1)?))
+    })?;
+    for row in rows {
+        let (tag, notes) = row?;
+        counts.tags.insert(tag, notes);
+    }
+    Ok(counts)
+}
+
+/// A SQLite copy of the projection, kept level with the log from a stored cursor so a cold
+/// start doesn't have to refold every frame or read every note from the CAS.
+#[derive(Default)]
+pub struct ReadModel {
+    model: RwLock<Model>,
+    db: Arc<Mutex<Option<Connection>>>,
+    /// Whether `model` holds anything yet: loaded from disk, or caught up with the log
+    ready: AtomicBool,
+}
+
+impl ReadModel {
+    /// Resolves new content and writes whatever changed.
+    async fn persist(&self, store: &Store) -> Result<(), String> {
+        let changes = {
+            let mut model = self.model.write().await;
+            model.resolve_content(store, |_, _| {}).await;
+            model.changes()
+        };
+        let db = self.db.clone();
+        let written = tokio::task::spawn_blocking(move || match db.lock().unwrap().as_mut() {
+            Some(conn) => write(conn, &changes, |_, _| {}),
+            None => Ok(()),
+        })
+        .await;
+        let result = match written {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(format!("Failed to write read model: {e}")),
+            Err(e) => Err(format!("Failed to write read model: {e}")),
+        };
+        if let Err(e) = &result {
+            eprintln!("{e}");
+        }
+        result
+    }
+
+    /// Refolds the whole log, e.g. after a recovery pass set frames aside or compaction or
+    /// retention removed them.
+    async fn rebuild(&self, store: &Store) -> Result<(), String> {
+        let frames = read_all_frames(store).await;
+        {
+            let mut model = self.model.write().await;
+            model.projection = Projection::from_frames(&frames);
+            model.cursor = frames.last().map(|frame| frame.id);
+        }
+        self.persist(store).await
+    }
+
+    /// Refills the full-text table from the current notes in one transaction, so searches
+    /// use the old rows until it commits.
+    pub(crate) async fn rebuild_full_text(&self, progress: &Progress) -> Result<(), String> {
+        if !self.ready.load(Ordering::Acquire) {
+            return Err("The read model isn't ready yet".to_string());
+        }
+        // Held throughout, so a write of newer notes can't land between the read and commit
+        let model = self.model.read().await;
+        let projection = &model.projection;
+        let current: HashSet<String> = projection
+            .notes_by_yak
+            .values()
+            .flatten()
+            .cloned()
+            .collect();
+        let notes: Vec<(String, String, String)> = projection
+            .notes
+            .values()
+            .filter(|note| note_status(projection, &current, note) != NoteStatus::Revision)
+            .map(|note| (note.id.clone(), note_title(note), searchable_text(note)))
+            .collect();
+        let db = self.db.clone();
+        let progress = progress.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut db = db.lock().unwrap();
+            let conn = db.as_mut().ok_or("The read model isn't available")?;
+            refill_text(conn, &notes, |done, total| progress.report(done, total))
+                .map_err(|e| format!("Failed to rebuild full-text index: {e}"))
+        })
+        .await
+        .map_err(|e| format!("Failed to rebuild full-text index: {e}"))?
+    }
+
+    /// Current notes matching every word of `query`, best first, if the database is open.
+    async fn full_text(
+        &self,
+        query: String,
+        scope: SearchScope,
+        limit: usize,
+    ) -> Option<Vec<String>> {
+        let db = self.db.clone();
+        let found = tokio::task::spawn_blocking(move || {
+            let db = db.lock().unwrap();
+            Some(fts_search(db.as_ref()?, &query, &scope, limit))
+        })
+        .await;
+        match found {
+            Ok(Some(Ok(ids))) => Some(ids),
+            Ok(None) => None,
+            Ok(Some(Err(e))) => {
+                eprintln!("Full-text search failed: {e}");
+                None
+            }
+            Err(e) => {
+                eprintln!("Full-text search failed: {e}");
+                None
+            }
+        }
+    }
+
+    /// Folds in frames another process appended, which never come through this store's
+    /// follow stream; `store` is one opened since, that has them. Returns how many were new.
+    pub(crate) async fn apply_external(
+        &self,
+        store: &Store,
+        frames: &[Frame],
+    ) -> Result<usize, String> {
+        let (applied, removed) = {
+            let mut model = self.model.write().await;
+            let applied: Vec<&Frame> = frames.iter().filter(|frame| model.apply(frame)).collect();
+            let removed = applied
+                .iter()
+                .any(|frame| REMOVED_TOPICS.contains(&frame.topic.as_str()));
+            (applied.len(), removed)
+        };
+        if removed {
+            self.rebuild(store).await?;
+        } else if applied > 0 {
+            self.persist(store).await?;
+        }
+        Ok(applied)
+    }
+
+    /// The current notes, tasks and yaks, if the model is ready.
+    async fn current(&self) -> Option<Projection> {
+        Some(self.projection().await?.into_current())
+    }
+
+    /// Every note revision, trashed ones included, once the model is ready.
+    async fn projection(&self) -> Option<Projection> {
+        if !self.ready.load(Ordering::Acquire) {
+            return None;
+        }
+        Some(self.model.read().await.projection.clone())
+    }
+
+    /// Each current note's id, yak and title, leaving out sealed notes: from the model once
+    /// it's ready, else from a fold of the log.
+    pub(crate) async fn titles(&self, store: &Store) -> Vec<(String, String, String)> {
+        let titles = |projection: &Projection| {
+            projection
+                .notes_by_yak
+                .values()
+                .flatten()
+                .filter_map(|id| projection.notes.get(id))
+                .filter(|note| !note.content.as_deref().is_some_and(locks::is_sealed))
+                .map(|note| (note.id.clone(), note.yak_id.clone(), note_title(note)))
+                .collect()
+        };
+        if self.ready.load(Ordering::Acquire) {
+            return titles(&self.model.read().await.projection);
+        }
+        let mut projection = Projection::from_frames(&read_all_frames(store).await).into_current();
+        projection.resolve_content(store).await;
+        titles(&projection)
+    }
+
+    /// The current notes, tasks and yaks with content: from the model once it's ready, else
+    /// from a fold of the log.
+    pub(crate) async fn snapshot(&self, store: &Store) -> Projection {
+        if let Some(snapshot) = self.current().await {
+            return snapshot;
+        }
+        let mut snapshot = Projection::from_frames(&read_all_frames(store).await).into_current();
+        snapshot.resolve_content(store).await;
+        snapshot
+    }
+}
+
+/// Frames that set others aside or remove them, so their rows go too.
+const REMOVED_TOPICS: [&str; 3] = [
+    recovery::QUARANTINE_TOPIC,
+    compaction::CHECKPOINT_TOPIC,
+    retention::TOMBSTONE_TOPIC,
+];
+
+/// Reports a write of the read model to the health check.
+fn report(app: &AppHandle, written: Result<(), String>) {
+    match written {
+        Ok(()) => health::ok(app, "indexer"),
+        Err(e) => health::error(app, "indexer", &e),
+    }
+}
+
+async fn watch(app: AppHandle, store: Store) {
+    let state = app.state::<ReadModel>();
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    let mut caught_up = false;
+    let mut pending = 0;
+    while let Some(frame) = rx.recv().await {
+        if frame.topic == "xs.threshold" {
+            caught_up = true;
+            report(&app, state.persist(&store).await);
+            state.ready.store(true, Ordering::Release);
+            pending = 0;
+            continue;
+        }
+        let applied = state.model.write().await.apply(&frame);
+        if !applied {
+            continue;
+        }
+        if REMOVED_TOPICS.contains(&frame.topic.as_str()) {
+            report(&app, state.rebuild(&store).await);
+            continue;
+        }
+        pending += 1;
+        if caught_up || pending >= BATCH {
+            report(&app, state.persist(&store).await);
+            pending = 0;
+        }
+    }
+}
+
+/// Builds a new database from the whole log beside the current one, then moves it into
+/// place. Frames appended meanwhile are applied before the new one is used.
+pub(crate) async fn rebuild_database(
+    app: &AppHandle,
+    store: &Store,
+    progress: &Progress,
+) -> Result<(), String> {
+    let dir = profiles::data_dir(app)?;
+    let path = dir.join(DB_FILE);
+    let partial = dir.join(format!("{DB_FILE}.new"));
+    let store_path = app.state::<StoreLocation>().0.to_string_lossy().to_string();
+
+    // Reading content is the first half of the work, writing rows the second
+    let mut model = Model::default();
+    for frame in read_all_frames(store).await {
+        model.apply(&frame);
+    }
+    model
+        .resolve_content(store, |done, total| progress.report(done, total * 2))
+        .await;
+    let changes = model.changes();
+    {
+        let (partial, store_path) = (partial.clone(), store_path.clone());
+        let progress = progress.clone();
+        tokio::task::spawn_blocking(move || {
+            let _ = std::fs::remove_file(&partial);
+            let mut conn = open(&partial, &store_path)?;
+            write(&mut conn, &changes, |done, total| {
+                progress.report(total + done, total * 2)
+            })
+        })
+        .await
+        .map_err(|e| format!("Failed to rebuild read model: {e}"))?
+        .map_err(|e| format!("Failed to rebuild read model: {e}"))?;
+    }
+
+    // The watcher waits on the model while the files are swapped and it catches up
+    let state = app.state::<ReadModel>();
+    let mut current = state.model.write().await;
+    for frame in read_all_frames(store).await {
+        model.apply(&frame);
+    }
+    model.resolve_content(store, |_, _| {}).await;
+    let changes = model.changes();
+    let db = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut db = db.lock().unwrap();
+        // Closed before it's replaced
+        *db = None;
+        std::fs::rename(&partial, &path)
+            .map_err(|e| format!("Failed to replace {}: {e}", path.display()))?;
+        let mut conn = open(&path, &store_path)
+            .map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+        write(&mut conn, &changes, |_, _| {})
+            .map_err(|e| format!("Failed to write read model: {e}"))?;
+        *db = Some(conn);
+        Ok::<_, String>(())
+    })
+    .await
+    .map_err(|e| format!("Failed to rebuild read model: {e}"))??;
+    *current = model;
+    state.ready.store(true, Ordering::Release);
+    Ok(())
+}
+
+/// Builds a read model database at `path` from the whole log in one go, for the benchmark
+/// harness to search.
+#[cfg(feature = "bench")]
+pub(crate) async fn build_database(store: &Store, path: &Path) -> Result<Connection, String> {
+    let mut model = Model::default();
+    for frame in read_all_frames(store).await {
+        model.apply(&frame);
+    }
+    model.resolve_content(store, |_, _| {}).await;
+    let changes = model.changes();
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = open(&path, "bench")?;
+        write(&mut conn, &changes, |_, _| {})?;
+        Ok::<_, rusqlite::Error>(conn)
+    })
+    .await
+    .map_err(|e| format!("Failed to build read model: {e}"))?
+    .map_err(|e| format!("Failed to build read model: {e}"))
+}
+
+/// A full-text search as `search_notes` makes once the read model is ready.
+#[cfg(feature = "bench")]
+pub(crate) fn search_database(
+    conn: &Connection,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<String>, String> {
+    fts_search(conn, query, &SearchScope::unscoped(None), limit)
+        .map_err(|e| format!("Full-text search failed: {e}"))
+}
+
+pub(crate) async fn initialize(app: &AppHandle, store: &Store) {
+    let path = match profiles::data_dir(app) {
+        Ok(dir) => dir.join(DB_FILE),
+        Err(e) => {
+            eprintln!("Read model disabled: {e}");
+            return;
+        }
+    };
+    let store_path = app.state::<StoreLocation>().0.to_string_lossy().to_string();
+    let opened = tokio::task::spawn_blocking(move || {
+        let conn = open(&path, &store_path)?;
+        let model = load(&conn)?;
+        Ok::<_, rusqlite::Error>((conn, model))
+    })
+    .await;
+    let state = app.state::<ReadModel>();
+    match opened {
+        Ok(Ok((conn, model))) => {
+            let loaded = model.cursor.is_some();
+            *state.model.write().await = model;
+            *state.db.lock().unwrap() = Some(conn);
+            state.ready.store(loaded, Ordering::Release);
+        }
+        Ok(Err(e)) => eprintln!("Failed to open read model, rebuilding in memory: {e}"),
+        Err(e) => eprintln!("Failed to open read model, rebuilding in memory: {e}"),
+    }
+    tokio::spawn(watch(app.clone(), store.clone()));
+}
+
+/// The current yaks, notes (content included) and tasks, from the read model rather than a
+/// fold of the whole log. `yak_id` narrows it to one yak. With a replay limit set, only each
+/// yak's latest notes are included; `older` counts the rest.
+#[tauri::command]
+pub async fn get_snapshot(
+    store: State<'_, Store>,
+    state: State<'_, ReadModel>,
+    locks: State<'_, LockState>,
+    presentation: State<'_, PresentationState>,
+    yak_id: Option<String>,
+) -> Result<Projection, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let mut snapshot = state.snapshot(&store).await;
+    if let Some(yak_id) = yak_id {
+        snapshot.yaks.retain(|id, _| *id == yak_id);
+        snapshot.notes.retain(|_, note| note.yak_id == yak_id);
+        snapshot.notes_by_yak.retain(|id, _| *id == yak_id);
+        snapshot.tasks.retain(|_, task| task.yak_id == yak_id);
+    }
+    snapshot.count_unread();
+    if let Some(limit) = history::load_config(&store).await.note_limit {
+        snapshot.truncate(limit);
+    }
+    let config = locks::load_config(&store).await;
+    locks.redact(&mut snapshot, config.hide_locked);
+    presentation.redact(&mut snapshot);
+    Ok(snapshot)
+}
+
+/// The notes of one yak and status among a search's hits, in the order they were found.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchGroup {
+    pub yak_id: String,
+    pub status: NoteStatus,
+    pub note_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResults {
+    pub notes: Vec<Note>,
+    /// The same notes by yak and status, best group first
+    pub groups: Vec<SearchGroup>,
+}
+
+fn group(notes: Vec<Note>, statuses: &HashMap<String, NoteStatus>) -> SearchResults {
+    let mut groups: Vec<SearchGroup> = Vec::new();
+    for note in &notes {
+        let status = statuses
+            .get(&note.id)
+            .copied()
+            .unwrap_or(NoteStatus::Active);
+        match groups
+            .iter_mut()
+            .find(|group| group.yak_id == note.yak_id && group.status == status)
+        {
+            Some(group) => group.note_ids.push(note.id.clone()),
+            None => groups.push(SearchGroup {
+                yak_id: note.yak_id.clone(),
+                status,
+                note_ids: vec![note.id.clone()],
+            }),
+        }
+    }
+    SearchResults { notes, groups }
+}
+
+/// Keyword search over notes, best matches first: through the full-text table once the read
+/// model is ready, else over a fold of the log. Property filters in the query
+/// (`status:done`, `priority:>2`, `has:due`) narrow the hits; a query of only filters lists
+/// every match, newest first. `scope` picks the yaks searched and whether archived and
+/// trashed notes are; without it, `yak_id` or every yak is searched, archived notes
+/// included. Hits come back grouped by yak and status too.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn search_notes(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, ReadModel>,
+    locks: State<'_, LockState>,
+    presentation: State<'_, PresentationState>,
+    query: String,
+    yak_id: Option<String>,
+    scope: Option<SearchScope>,
+    limit: Option<usize>,
+) -> Result<SearchResults, String> {
+    crate::app_lock::ensure_unlocked()?;
+    insights::record_search(&app, "notes").await;
+    let scope = scope.unwrap_or_else(|| SearchScope::unscoped(yak_id));
+    let found = state.projection().await;
+    let from_model = found.is_some();
+    let mut projection = match found {
+        Some(projection) => projection,
+        None => {
+            let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+            projection.resolve_content(&store).await;
+            projection
+        }
+    };
+    // Already scoped, so the searches below look in every yak left
+    let statuses = scope.retain(&mut projection);
+    locks.redact(&mut projection, true);
+    let limit = limit.unwrap_or(50);
+    let (query, filters) = parse_query(&query);
+    if !filters.is_empty() {
+        projection
+            .notes
+            .retain(|_, note| filters.iter().all(|filter| filter.matches(note)));
+    }
+    if query.is_empty() && !filters.is_empty() {
+        let mut notes: Vec<Note> = filter_notes(&projection, &filters, None, limit)
+            .into_iter()
+            .cloned()
+            .collect();
+        presentation.blur(&projection, &mut notes);
+        return Ok(group(notes, &statuses));
+    }
+    // The full-text index doesn't know properties, so filtered queries search the projection
+    let full_text = match from_model && filters.is_empty() {
+        true => state.full_text(query.clone(), scope, limit).await,
+        false => None,
+    };
+    let Some(ids) = full_text else {
+        let hits = keyword_search(&projection, &query, None, Match::All, limit);
+        let mut notes: Vec<Note> = hits.into_iter().cloned().collect();
+        presentation.blur(&projection, &mut notes);
+        return Ok(group(notes, &statuses));
+    };
+    let mut notes: Vec<Note> = ids
+        .iter()
+        .filter_map(|id| projection.notes.get(id))
+        .filter(|note| !locks.is_locked(&note.yak_id))
+        .cloned()
+        .collect();
+    // Sealed content is stored as written, so unlocked yaks are searched once revealed
+    projection
+        .notes
+        .retain(|_, note| locks.is_locked(&note.yak_id));
+    let sealed = keyword_search(&projection, &query, None, Match::All, limit);
+    notes.extend(sealed.into_iter().cloned());
+    notes.truncate(limit);
+    presentation.blur(&projection, &mut notes);
+    Ok(group(notes, &statuses))
+}
+
+/// Notes and open/done tasks per yak, and notes per tag.
+#[tauri::command]
+pub async fn get_counts(
+    state: State<'_, ReadModel>,
+    locale: State<'_, LocaleState>,
+) -> Result<Counts, String> {
+    let db = state.db.clone();
+    let mut counts = tokio::task::spawn_blocking(move || match db.lock().unwrap().as_ref() {
+        Some(conn) => counts(conn).map_err(|e| format!("Failed to count: {e}")),
+        None => Err("The read model isn't available".to_string()),
+    })
+    .await
+    .map_err(|e| format!("Failed to count: {e}"))??;
+    counts.tag_order = counts.tags.keys().cloned().collect();
+    locale
+        .get()
+        .sort_by_key(&mut counts.tag_order, |tag| tag.as_str());
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::YakScope;
+    use crate::testing::{self, append};
+    use serde_json::{json, Value};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_write_and_load() {
+        let (_store_dir, store) = testing::store();
+        let frame = |topic: &str, meta: Value| append(&store, topic, Some(meta));
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(DB_FILE);
+        let mut conn = open(&path, "/store").unwrap();
+
+        let yak = frame("yak.create", json!({ "name": "Home" }));
+        let yak_id = yak.id.to_string();
+        let note = frame("note.create", json!({ "yak_id": yak_id }));
+        let note_id = note.id.to_string();
+        let tag = frame("tag.add", json!({ "note_id": note_id, "tag": "chores" }));
+        let task = frame("task.create", json!({ "yak_id": yak_id }));
+
+        let mut model = Model::default();
+        for frame in [&yak, &note, &tag, &task] {
+            assert!(model.apply(frame));
+        }
+        model.projection.notes.get_mut(&note_id).unwrap().content =
+            Some("# Laundry\nSee [[Groceries]]".to_string());
+        write(&mut conn, &model.changes(), |_, _| {}).unwrap();
+
+        // An edit rewrites the note's row and adds the new revision's
+        let edit = frame("note.edit", json!({ "yak_id": yak_id, "note_id": note_id }));
+        assert!(model.apply(&edit));
+        assert!(!model.apply(&edit));
+        let changes = model.changes();
+        assert_eq!(changes.rows.len(), 3);
+        write(&mut conn, &changes, |_, _| {}).unwrap();
+
+        let loaded = load(&conn).unwrap();
+        assert_eq!(loaded.cursor, Some(edit.id));
+        assert_eq!(loaded.projection.resolve(&note_id), edit.id.to_string());
+        let current = loaded.projection.clone().into_current();
+        assert_eq!(current.notes.len(), 1);
+        assert!(current.notes[&edit.id.to_string()].tags.contains("chores"));
+        let original = &loaded.projection.notes[&note_id];
+        assert_eq!(note_title(original), "Laundry");
+
+        let counts = counts(&conn).unwrap();
+        assert_eq!(counts.yaks[&yak_id].notes, 1);
+        assert_eq!(counts.yaks[&yak_id].open_tasks, 1);
+        assert_eq!(counts.tags["chores"], 1);
+        let target: String = conn
+            .query_row("SELECT target FROM links", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(target, "groceries");
+
+        // Another store's database starts over
+        drop(conn);
+        let conn = open(&path, "/elsewhere").unwrap();
+        assert!(load(&conn).unwrap().cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_full_text() {
+        let (_store_dir, store) = testing::store();
+        let frame = |topic: &str, meta: Value| append(&store, topic, Some(meta));
+        let dir = tempdir().unwrap();
+        let mut conn = open(&dir.path().join(DB_FILE), "/store").unwrap();
+        let yak = frame("yak.create", json!({}));
+        let yak_id = yak.id.to_string();
+        let note = frame("note.create", json!({ "yak_id": yak_id }));
+        let note_id = note.id.to_string();
+        let mut model = Model::default();
+        model.apply(&yak);
+        model.apply(&note);
+        model.projection.notes.get_mut(&note_id).unwrap().content =
+            Some("Buy laundry detergent".to_string());
+        write(&mut conn, &model.changes(), |_, _| {}).unwrap();
+        let all = SearchScope::unscoped(None);
+        let search = |conn: &Connection, query: &str| fts_search(conn, query, &all, 10).unwrap();
+        assert_eq!(search(&conn, "Laundry (detergent"), vec![note_id.clone()]);
+        let other = SearchScope::unscoped(Some("other".to_string()));
+        assert!(fts_search(&conn, "laundry", &other, 10).unwrap().is_empty());
+
+        // Only the current revision is searchable
+        let edit = frame("note.edit", json!({ "yak_id": yak_id, "note_id": note_id }));
+        model.apply(&edit);
+        let edit_id = edit.id.to_string();
+        model.projection.notes.get_mut(&edit_id).unwrap().content =
+            Some("Wash the dishes".to_string());
+        write(&mut conn, &model.changes(), |_, _| {}).unwrap();
+        assert!(search(&conn, "laundry").is_empty());
+        assert_eq!(search(&conn, "dishes"), vec![edit_id.clone()]);
+
+        // Trashed notes are only searched when the scope asks for them
+        let delete = frame(
+            "note.delete",
+            json!({ "yak_id": yak_id, "note_id": edit_id }),
+        );
+        model.apply(&delete);
+        write(&mut conn, &model.changes(), |_, _| {}).unwrap();
+        assert!(search(&conn, "dishes").is_empty());
+        let trash = SearchScope {
+            yaks: YakScope::Selected {
+                yak_ids: vec![yak_id.clone()],
+            },
+            include_archived: false,
+            include_trashed: true,
+        };
+        assert_eq!(
+            fts_search(&conn, "dishes", &trash, 10).unwrap(),
+            vec![edit_id.clone()]
+        );
+        let restore = frame(
+            "note.restore",
+            json!({ "yak_id": yak_id, "note_id": edit_id }),
+        );
+        model.apply(&restore);
+        write(&mut conn, &model.changes(), |_, _| {}).unwrap();
+        assert_eq!(search(&conn, "dishes"), vec![edit_id.clone()]);
+
+        let notes = vec![(edit_id.clone(), "Chores".to_string(), "sweep".to_string())];
+        refill_text(&mut conn, &notes, |_, _| {}).unwrap();
+        assert!(search(&conn, "dishes").is_empty());
+        assert_eq!(search(&conn, "chores sweep"), vec![edit_id]);
+        assert!(search(&conn, "\"*").is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+use xs::store::{Frame, Store};
+
+use crate::cache::CasCache;
+use crate::profiles;
+use crate::{append_frame, read_all_frames};
+
+/// Present in the app data dir while Yaks runs; finding it at launch means the last run
+/// didn't shut down cleanly.
+const SENTINEL: &str = "running";
+/// How many of the most recent frames are checked after an unclean shutdown.
+pub(crate) const TAIL: usize = 500;
+pub(crate) const QUARANTINE_TOPIC: &str = "recovery.quarantine";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedFrame {
+    pub frame_id: String,
+    pub topic: String,
+    pub hash: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RecoveryReport {
+    pub checked: usize,
+    pub quarantined: Vec<QuarantinedFrame>,
+}
+
+fn sentinel(app: &AppHandle) -> Option<PathBuf> {
+    profiles::data_dir(app).ok().map(|dir| dir.join(SENTINEL))
+}
+
+/// Marks this run as in progress, returning whether the previous one ended uncleanly.
+fn begin(path: &Path) -> bool {
+    let unclean = path.exists();
+    if let Err(e) = std::fs::write(path, std::process::id().to_string()) {
+        eprintln!("Failed to write {}: {e}", path.display());
+    }
+    unclean
+}
+
+/// Marks a clean shutdown.
+pub(crate) fn end(app: &AppHandle) {
+    if let Some(path) = sentinel(app) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Ids of frames set aside by an earlier recovery pass.
+pub(crate) fn quarantined<'a>(frames: impl IntoIterator<Item = &'a Frame>) -> HashSet<String> {
+    frames
+        .into_iter()
+        .filter(|frame| frame.topic == QUARANTINE_TOPIC)
+        .filter_map(|frame| {
+            frame
+                .meta
+                .as_ref()?
+                .get("frame_id")?
+                .as_str()
+                .map(String::from)
+        })
+        .collect()
+}
+
+/// Checks that the content of the most recent frames is present and intact, without
+/// quarantining anything.
+pub(crate) async fn scan(store: &Store, frames: &[Frame], tail: usize) -> RecoveryReport {
+    let already = quarantined(frames);
+    let mut report = RecoveryReport::default();
+    for frame in frames.iter().rev().take(tail) {
+        report.checked += 1;
+        let Some(hash) = &frame.hash else {
+            continue;
+        };
+        if already.contains(&frame.id.to_string()) {
+            continue;
+        }
+        // The CAS verifies content against its hash on read, catching truncated writes
+        if let Err(e) = store.cas_read(hash).await {
+            report.quarantined.push(QuarantinedFrame {
+                frame_id: frame.id.to_string(),
+                topic: frame.topic.clone(),
+                hash: hash.to_string(),
+                reason: e.to_string(),
+            });
+        }
+    }
+    report
+}
+
+/// Like `scan`, quarantining frames whose content can't be read so projections skip them.
+async fn check(store: &Store, tail: usize) -> Result<RecoveryReport, String> {
+    let frames = read_all_frames(store).await;
+    let report = scan(store, &frames, tail).await;
+    for entry in &report.quarantined {
+        let meta = serde_json::to_value(entry).map_err(|e| format!("Invalid report: {e}"))?;
+        append_frame(store, QUARANTINE_TOPIC, None, Some(meta)).await?;
+    }
+    Ok(report)
+}
+
+/// Runs the integrity pass if the previous run crashed, emitting `recovery-report`.
+pub(crate) async fn initialize(app: &AppHandle, store: &Store) {
+    let Some(path) = sentinel(app) else {
+        return;
+    };
+    if !begin(&path) {
+        return;
+    }
+    println!("Previous run didn't shut down cleanly, checking recent frames...");
+    match check(store, TAIL).await {
+        Ok(report) => {
+            if !report.quarantined.is_empty() {
+                eprintln!("Quarantined {} damaged frames", report.quarantined.len());
+            }
+            let cache = app.state::<Arc<CasCache>>();
+            for entry in &report.quarantined {
+                cache.invalidate(&entry.hash);
+            }
+            if let Err(e) = app.emit("recovery-report", &report) {
+                eprintln!("Failed to emit recovery report: {e}");
+            }
+        }
+        Err(e) => eprintln!("Recovery check failed: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sentinel() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(SENTINEL);
+        assert!(!begin(&path));
+        assert!(begin(&path));
+    }
+
+    #[tokio::test]
+    async fn test_check_quarantines_missing_content() {
+        let (_dir, store) = testing::store();
+        append_frame(
+            &store,
+            "note.create",
+            Some(b"intact".as_slice()),
+            Some(serde_json::json!({ "yak_id": "y" })),
+        )
+        .await
+        .unwrap();
+        // Content that never made it into the CAS, as after a crash mid-write
+        let missing = ssri::Integrity::from(b"lost");
+        let broken = store
+            .append(Frame {
+                id: scru128::new(),
+                context_id: xs::store::ZERO_CONTEXT,
+                topic: "note.create".to_string(),
+                hash: Some(missing),
+                meta: None,
+                ttl: None,
+            })
+            .unwrap();
+
+        let report = check(&store, TAIL).await.unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.quarantined.len(), 1);
+        assert_eq!(report.quarantined[0].frame_id, broken.id.to_string());
+
+        // Already quarantined frames aren't reported again
+        assert!(check(&store, TAIL).await.unwrap().quarantined.is_empty());
+        let frames = read_all_frames(&store).await;
+        assert!(quarantined(&frames).contains(&broken.id.to_string()));
+    }
+}
@@ -0,0 +1,645 @@
+use chrono::{DateTime, Datelike, Duration, Local, Months, NaiveDate, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Notify;
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::locks::LockState;
+use crate::projection::Projection;
+use crate::{
+    append_batch_to_store, append_frame, health, read_all_frames, shutdown, templates, windows,
+};
+
+const SET_TOPIC: &str = "recurrence.set";
+const PAUSE_TOPIC: &str = "recurrence.pause";
+/// How often the scheduler checks for occurrences whose time has come.
+const TICK: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A subset of RFC 5545 RRULEs: `FREQ`, `INTERVAL`, `BYDAY` (weekly only), `COUNT` and
+/// `UNTIL`, e.g. `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH`.
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    freq: Frequency,
+    interval: u32,
+    by_day: Vec<Weekday>,
+    count: Option<usize>,
+    until: Option<NaiveDate>,
+}
+
+fn weekday(code: &str) -> Result<Weekday, String> {
+    Ok(match code {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return Err(format!("Invalid BYDAY value: {code}")),
+    })
+}
+
+impl Rule {
+    fn parse(rule: &str) -> Result<Self, String> {
+        let rule = rule.trim();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+        let mut freq = None;
+        let mut parsed = Rule {
+            freq: Frequency::Daily,
+            interval: 1,
+            by_day: Vec::new(),
+            count: None,
+            until: None,
+        };
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid rule part: {part}"))?;
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    freq = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(format!("Unsupported FREQ: {value}")),
+                    })
+                }
+                "INTERVAL" => {
+                    parsed.interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| *interval > 0)
+                        .ok_or_else(|| format!("Invalid INTERVAL: {value}"))?;
+                }
+                "BYDAY" => {
+                    parsed.by_day = value
+                        .split(',')
+                        .map(|code| weekday(&code.to_ascii_uppercase()))
+                        .collect::<Result<_, _>>()?;
+                }
+                "COUNT" => {
+                    parsed.count = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid COUNT: {value}"))?,
+                    );
+                }
+                "UNTIL" => {
+                    let date = value.get(..8).unwrap_or(value);
+                    parsed.until = Some(
+                        NaiveDate::parse_from_str(date, "%Y%m%d")
+                            .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d"))
+                            .map_err(|_| format!("Invalid UNTIL: {value}"))?,
+                    );
+                }
+                _ => return Err(format!("Unsupported rule part: {key}")),
+            }
+        }
+        parsed.freq = freq.ok_or("Rule needs a FREQ")?;
+        if !parsed.by_day.is_empty() && parsed.freq != Frequency::Weekly {
+            return Err("BYDAY is only supported with FREQ=WEEKLY".to_string());
+        }
+        Ok(parsed)
+    }
+
+    /// The first occurrence after `at`, at the same time of day.
+    fn next_after(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let months = |n: u32| at.checked_add_months(Months::new(n)).unwrap_or(at);
+        match self.freq {
+            Frequency::Daily => at + Duration::days(self.interval.into()),
+            Frequency::Weekly if self.by_day.is_empty() => {
+                at + Duration::weeks(self.interval.into())
+            }
+            Frequency::Weekly => {
+                let week_start = |day: DateTime<Utc>| {
+                    day.date_naive() - Duration::days(day.weekday().num_days_from_monday().into())
+                };
+                let interval = i64::from(self.interval);
+                (1..=7 * (interval + 1))
+                    .map(|days| at + Duration::days(days))
+                    .find(|candidate| {
+                        let weeks = (week_start(*candidate) - week_start(at)).num_weeks();
+                        weeks % interval == 0 && self.by_day.contains(&candidate.weekday())
+                    })
+                    .unwrap_or(at + Duration::weeks(interval))
+            }
+            Frequency::Monthly => months(self.interval),
+            Frequency::Yearly => months(12 * self.interval),
+        }
+    }
+
+    /// Whether an occurrence at `at` is within the rule's `COUNT` and `UNTIL`, given how many
+    /// there have been.
+    fn allows(&self, at: DateTime<Utc>, occurrences: usize) -> bool {
+        self.count.map_or(true, |count| occurrences < count)
+            && self.until.map_or(true, |until| at.date_naive() <= until)
+    }
+}
+
+/// What each occurrence of a series is: a copy of a task, or a note from a template.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecurrenceSource {
+    Task { task_id: String },
+    Template { name: String, yak_id: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Occurrence {
+    pub at: String,
+    pub item_id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Series {
+    pub id: String,
+    pub rule: String,
+    pub source: RecurrenceSource,
+    pub start: String,
+    pub paused: bool,
+    pub occurrences: Vec<Occurrence>,
+    /// When the next occurrence falls, unless the series is paused or finished
+    pub next: Option<String>,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+fn parse_time(at: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(at)
+        .ok()
+        .map(|at| at.with_timezone(&Utc))
+}
+
+/// Every series in the log with its occurrences so far. Occurrences are the tasks and notes
+/// written with a `recurrence_id`; a task series starts with the task it was set on.
+fn series(frames: &[Frame]) -> BTreeMap<String, Series> {
+    let mut series = BTreeMap::new();
+    for frame in frames {
+        match frame.topic.as_str() {
+            SET_TOPIC => {
+                let meta = frame.meta.clone().unwrap_or_default();
+                let (Some(rule), Some(start), Ok(source)) = (
+                    meta_str(frame, "rule"),
+                    meta_str(frame, "start"),
+                    serde_json::from_value::<RecurrenceSource>(
+                        meta.get("source").cloned().unwrap_or_default(),
+                    ),
+                ) else {
+                    continue;
+                };
+                let occurrences = match &source {
+                    RecurrenceSource::Task { task_id } => vec![Occurrence {
+                        at: start.to_string(),
+                        item_id: task_id.clone(),
+                    }],
+                    RecurrenceSource::Template { .. } => Vec::new(),
+                };
+                series.insert(
+                    frame.id.to_string(),
+                    Series {
+                        id: frame.id.to_string(),
+                        rule: rule.to_string(),
+                        source,
+                        start: start.to_string(),
+                        paused: false,
+                        occurrences,
+                        next: None,
+                    },
+                );
+            }
+            PAUSE_TOPIC => {
+                let paused = frame
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get("paused"))
+                    .and_then(|paused| paused.as_bool())
+                    .unwrap_or(true);
+                if let Some(entry) =
+                    meta_str(frame, "recurrence_id").and_then(|id| series.get_mut(id))
+                {
+                    entry.paused = paused;
+                }
+            }
+            "task.create" | "note.create" => {
+                let (Some(id), Some(at)) = (
+                    meta_str(frame, "recurrence_id"),
+                    meta_str(frame, "occurrence_at"),
+                ) else {
+                    continue;
+                };
+                if let Some(entry) = series.get_mut(id) {
+                    entry.occurrences.push(Occurrence {
+                        at: at.to_string(),
+                        item_id: frame.id.to_string(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    series
+}
+
+/// The occurrence to materialize now, if any. A task's next occurrence is created once the
+/// current one is done or its time has passed, and lands on the first time still ahead. A
+/// template's is created when its time arrives; missed ones collapse into the latest.
+fn due(
+    series: &Series,
+    rule: &Rule,
+    projection: &Projection,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    if series.paused {
+        return None;
+    }
+    let mut next = match series.occurrences.last() {
+        None => parse_time(&series.start)?,
+        Some(last) => {
+            let at = parse_time(&last.at)?;
+            if matches!(series.source, RecurrenceSource::Task { .. }) {
+                // A deleted occurrence counts as dealt with
+                let done = projection
+                    .tasks
+                    .get(&last.item_id)
+                    .map_or(true, |task| task.done);
+                if !done && now < at {
+                    return None;
+                }
+            }
+            rule.next_after(at)
+        }
+    };
+    match series.source {
+        RecurrenceSource::Task { .. } => {
+            while next <= now {
+                next = rule.next_after(next);
+            }
+        }
+        RecurrenceSource::Template { .. } => {
+            if next > now {
+                return None;
+            }
+            loop {
+                let after = rule.next_after(next);
+                if after > now {
+                    break;
+                }
+                next = after;
+            }
+        }
+    }
+    rule.allows(next, series.occurrences.len()).then_some(next)
+}
+
+/// When the series' next occurrence falls, for listing.
+fn upcoming(series: &Series, rule: &Rule, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if series.paused {
+        return None;
+    }
+    let mut next = match series.occurrences.last() {
+        None => parse_time(&series.start)?,
+        Some(last) => rule.next_after(parse_time(&last.at)?),
+    };
+    while next <= now {
+        next = rule.next_after(next);
+    }
+    rule.allows(next, series.occurrences.len()).then_some(next)
+}
+
+/// Writes the occurrence of `series` at `at`.
+async fn materialize(
+    app: &AppHandle,
+    store: &Store,
+    projection: &Projection,
+    series: &Series,
+    at: DateTime<Utc>,
+) -> Result<Vec<Frame>, String> {
+    let _guard = shutdown::begin_write(app)?;
+    let occurrence_at = at.to_rfc3339();
+    match &series.source {
+        RecurrenceSource::Task { .. } => {
+            let task = series
+                .occurrences
+                .iter()
+                .rev()
+                .find_map(|occurrence| projection.tasks.get(&occurrence.item_id))
+                .ok_or_else(|| format!("Every task of series {} is gone", series.id))?;
+            let content = match &task.hash {
+                Some(hash) => Some(
+                    store
+                        .cas_read(hash)
+                        .await
+                        .map_err(|e| format!("Failed to read task: {e}"))?,
+                ),
+                None => None,
+            };
+            let meta = json!({
+                "yak_id": task.yak_id,
+                "note_id": task.note_id,
+                "done": false,
+                "reminder": occurrence_at,
+                "recurrence_id": series.id,
+                "occurrence_at": occurrence_at,
+            });
+            let frame = append_frame(store, "task.create", content.as_deref(), Some(meta)).await?;
+            Ok(vec![frame])
+        }
+        RecurrenceSource::Template { name, yak_id } => {
+            let (mut request, _) = templates::note_request(
+                store,
+                name,
+                yak_id,
+                &HashMap::new(),
+                at.with_timezone(&Local),
+            )
+            .await?;
+            let meta = request.meta.get_or_insert_with(HashMap::new);
+            meta.insert("recurrence_id".to_string(), series.id.clone().into());
+            meta.insert("occurrence_at".to_string(), occurrence_at.into());
+            let request = app.state::<LockState>().seal_request(request)?;
+            append_batch_to_store(store, vec![request]).await
+        }
+    }
+}
+
+async fn run(app: &AppHandle, store: &Store) {
+    let frames = read_all_frames(store).await;
+    let projection = Projection::from_frames(&frames);
+    let now = Utc::now();
+    let mut failed = false;
+    for series in series(&frames).values() {
+        let Ok(rule) = Rule::parse(&series.rule) else {
+            continue;
+        };
+        let Some(at) = due(series, &rule, &projection, now) else {
+            continue;
+        };
+        match materialize(app, store, &projection, series, at).await {
+            Ok(frames) => {
+                let _ = windows::emit_frames(app, &frames);
+            }
+            Err(e) => {
+                health::error(app, "recurrence", &format!("{}: {e}", series.id));
+                eprintln!("Failed to create occurrence of {}: {e}", series.id);
+                failed = true;
+            }
+        }
+    }
+    if !failed {
+        health::ok(app, "recurrence");
+    }
+}
+
+/// Materializes occurrences as tasks are completed and as their times come around.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let kick = Arc::new(Notify::new());
+    let follower_kick = kick.clone();
+    let follower_store = store.clone();
+    tokio::spawn(async move {
+        let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+        let mut rx = follower_store.read(read_options).await;
+        let mut caught_up = false;
+        while let Some(frame) = rx.recv().await {
+            if frame.topic == "xs.threshold" {
+                caught_up = true;
+                follower_kick.notify_one();
+            } else if caught_up
+                && (frame.topic == "task.update" || frame.topic.starts_with("recurrence."))
+            {
+                follower_kick.notify_one();
+            }
+        }
+    });
+
+    loop {
+        tokio::select! {
+            _ = kick.notified() => {}
+            _ = tokio::time::sleep(TICK) => {}
+        }
+        run(&app, &store).await;
+    }
+}
+
+fn listed(mut series: Series, now: DateTime<Utc>) -> Series {
+    series.next = Rule::parse(&series.rule)
+        .ok()
+        .and_then(|rule| upcoming(&series, &rule, now))
+        .map(|next| next.to_rfc3339());
+    series
+}
+
+/// Makes a task or a template recur by `rule`, e.g. `FREQ=WEEKLY;BYDAY=MO`. A task's series
+/// starts at its reminder unless `start` (RFC 3339) is given; a template's starts now.
+#[tauri::command]
+pub async fn set_recurrence(
+    store: State<'_, Store>,
+    app: AppHandle,
+    rule: String,
+    task_id: Option<String>,
+    template: Option<String>,
+    yak_id: Option<String>,
+    start: Option<String>,
+) -> Result<Series, String> {
+    let _guard = shutdown::begin_write(&app)?;
+    Rule::parse(&rule)?;
+    if let Some(start) = &start {
+        parse_time(start).ok_or_else(|| format!("Invalid start time: {start}"))?;
+    }
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let (source, default_start) = match (task_id, template, yak_id) {
+        (Some(task_id), None, _) => {
+            let task = projection
+                .tasks
+                .get(&task_id)
+                .ok_or_else(|| format!("Task not found: {task_id}"))?;
+            let reminder = task.reminder.clone();
+            (RecurrenceSource::Task { task_id }, reminder)
+        }
+        (None, Some(name), Some(yak_id)) => {
+            if !projection.yaks.contains_key(&yak_id) {
+                return Err(format!("Yak not found: {yak_id}"));
+            }
+            (RecurrenceSource::Template { name, yak_id }, None)
+        }
+        _ => return Err("Give either a task_id, or a template and yak_id".to_string()),
+    };
+    let start = start
+        .or(default_start)
+        .unwrap_or_else(|| Utc::now().to_rfc3339());
+    let meta = json!({ "rule": rule, "source": source, "start": start });
+    let frame = append_frame(&store, SET_TOPIC, None, Some(meta)).await?;
+
+    let frames = read_all_frames(&store).await;
+    series(&frames)
+        .remove(&frame.id.to_string())
+        .map(|series| listed(series, Utc::now()))
+        .ok_or_else(|| "Failed to read back the new series".to_string())
+}
+
+#[tauri::command]
+pub async fn list_recurring(store: State<'_, Store>) -> Result<Vec<Series>, String> {
+    let now = Utc::now();
+    Ok(series(&read_all_frames(&store).await)
+        .into_values()
+        .map(|series| listed(series, now))
+        .collect())
+}
+
+/// Stops (or resumes) creating occurrences of a series. Existing ones are left alone.
+#[tauri::command]
+pub async fn pause_recurrence(
+    store: State<'_, Store>,
+    app: AppHandle,
+    recurrence_id: String,
+    paused: bool,
+) -> Result<(), String> {
+    let _guard = shutdown::begin_write(&app)?;
+    if !series(&read_all_frames(&store).await).contains_key(&recurrence_id) {
+        return Err(format!("Recurrence not found: {recurrence_id}"));
+    }
+    let meta = json!({ "recurrence_id": recurrence_id, "paused": paused });
+    append_frame(&store, PAUSE_TOPIC, None, Some(meta)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    fn at(y: i32, m: u32, d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, 9, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_next_after() {
+        let daily = Rule::parse("RRULE:FREQ=DAILY;INTERVAL=2").unwrap();
+        assert_eq!(daily.next_after(at(2024, 2, 28)), at(2024, 3, 1));
+
+        // 2024-03-04 is a Monday
+        let weekly = Rule::parse("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH").unwrap();
+        assert_eq!(weekly.next_after(at(2024, 3, 4)), at(2024, 3, 7));
+        assert_eq!(weekly.next_after(at(2024, 3, 7)), at(2024, 3, 18));
+
+        let monthly = Rule::parse("FREQ=MONTHLY").unwrap();
+        assert_eq!(monthly.next_after(at(2024, 1, 31)), at(2024, 2, 29));
+
+        let limited = Rule::parse("FREQ=DAILY;COUNT=2;UNTIL=20240305").unwrap();
+        assert!(limited.allows(at(2024, 3, 5), 1));
+        assert!(!limited.allows(at(2024, 3, 5), 2));
+        assert!(!limited.allows(at(2024, 3, 6), 0));
+
+        assert!(Rule::parse("INTERVAL=2").is_err());
+        assert!(Rule::parse("FREQ=DAILY;BYDAY=MO").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_task_series() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let task = append_frame(
+            &store,
+            "task.create",
+            Some(b"water plants".as_slice()),
+            Some(json!({ "yak_id": yak_id, "reminder": at(2024, 3, 4).to_rfc3339() })),
+        )
+        .await
+        .unwrap();
+        let source = RecurrenceSource::Task {
+            task_id: task.id.to_string(),
+        };
+        append_frame(
+            &store,
+            SET_TOPIC,
+            None,
+            Some(json!({ "rule": "FREQ=DAILY", "source": source, "start": at(2024, 3, 4).to_rfc3339() })),
+        )
+        .await
+        .unwrap();
+
+        let frames = read_all_frames(&store).await;
+        let projection = Projection::from_frames(&frames);
+        let all = series(&frames);
+        let entry = all.values().next().unwrap();
+        let rule = Rule::parse(&entry.rule).unwrap();
+
+        // Not yet due while the task is open and its time hasn't come
+        assert_eq!(due(entry, &rule, &projection, at(2024, 3, 3)), None);
+        // Once it has passed, the next lands on the first day still ahead
+        assert_eq!(
+            due(
+                entry,
+                &rule,
+                &projection,
+                at(2024, 3, 6) + Duration::hours(1)
+            ),
+            Some(at(2024, 3, 7))
+        );
+
+        // Completing it early brings the next one forward
+        append_frame(
+            &store,
+            "task.update",
+            None,
+            Some(json!({ "task_id": task.id.to_string(), "done": true })),
+        )
+        .await
+        .unwrap();
+        let projection = Projection::from_frames(&read_all_frames(&store).await);
+        assert_eq!(
+            due(entry, &rule, &projection, at(2024, 3, 3)),
+            Some(at(2024, 3, 5))
+        );
+
+        let mut paused = entry.clone();
+        paused.paused = true;
+        assert_eq!(due(&paused, &rule, &projection, at(2024, 3, 6)), None);
+    }
+
+    #[test]
+    fn test_template_series_collapses_missed_occurrences() {
+        let entry = Series {
+            id: "r".to_string(),
+            rule: "FREQ=DAILY".to_string(),
+            source: RecurrenceSource::Template {
+                name: "daily".to_string(),
+                yak_id: "y".to_string(),
+            },
+            start: at(2024, 3, 1).to_rfc3339(),
+            paused: false,
+            occurrences: Vec::new(),
+            next: None,
+        };
+        let rule = Rule::parse(&entry.rule).unwrap();
+        let projection = Projection::default();
+        assert_eq!(due(&entry, &rule, &projection, at(2024, 2, 29)), None);
+        assert_eq!(
+            due(
+                &entry,
+                &rule,
+                &projection,
+                at(2024, 3, 4) + Duration::hours(1)
+            ),
+            Some(at(2024, 3, 4))
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::settings::{latest_setting, load_setting, save_setting};
+use crate::shutdown::begin_write;
+use crate::sync::ORIGIN_KEY;
+use crate::windows::emit_frame;
+use crate::{append_frame, blobs, cold_archive, read_all_frames};
+
+const CONFIG_TOPIC: &str = "retention.policies";
+/// Written before each topic's expired frames are removed, listing them.
+pub(crate) const TOMBSTONE_TOPIC: &str = "retention.tombstone";
+/// Topics the projection and the app's own bookkeeping are built from, which can't expire.
+const PROTECTED: &[&str] = &[
+    "xs.",
+    "yak.",
+    "note.",
+    "task.",
+    "tag.",
+    "attachment.",
+    "text.",
+    "reminder.",
+    "recurrence.",
+    "schema.",
+    "recovery.",
+    "compaction.",
+    "retention.",
+    "store.",
+    "sync.",
+];
+const TICK: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RetentionPolicy {
+    KeepForever,
+    /// The `count` most recent frames
+    KeepLast {
+        count: usize,
+    },
+    /// Frames younger than `days`
+    KeepDays {
+        days: u32,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PruneReport {
+    pub topic: String,
+    pub tombstone_id: String,
+    pub pruned: usize,
+}
+
+/// Frames each policy lets go, by topic. `now` is in ms since the epoch. Copies made by
+/// sync are left alone, as the next sync would bring them back.
+fn expired(
+    frames: &[Frame],
+    policies: &BTreeMap<String, RetentionPolicy>,
+    now: u64,
+) -> BTreeMap<String, Vec<scru128::Scru128Id>> {
+    let synced = |frame: &Frame| {
+        frame
+            .meta
+            .as_ref()
+            .is_some_and(|m| m.get(ORIGIN_KEY).is_some())
+    };
+    let mut expired = BTreeMap::new();
+    for (topic, policy) in policies {
+        let on_topic: Vec<&Frame> = frames
+            .iter()
+            .filter(|frame| frame.topic == *topic && !synced(frame))
+            .collect();
+        let ids: Vec<scru128::Scru128Id> = match policy {
+            RetentionPolicy::KeepForever => continue,
+            RetentionPolicy::KeepLast { count } => on_topic
+                .iter()
+                .rev()
+                .skip(*count)
+                .map(|frame| frame.id)
+                .collect(),
+            RetentionPolicy::KeepDays { days } => {
+                let cutoff = now.saturating_sub(u64::from(*days) * 24 * 60 * 60 * 1000);
+                on_topic
+                    .iter()
+                    .filter(|frame| frame.id.timestamp() < cutoff)
+                    .map(|frame| frame.id)
+                    .collect()
+            }
+        };
+        if !ids.is_empty() {
+            expired.insert(topic.clone(), ids);
+        }
+    }
+    expired
+}
+
+/// Tombstones each topic's expired frames, copies them to the cold archive if it's on, then
+/// removes them. A run cut short leaves frames that are still expired, so the next one
+/// finishes the job.
+async fn prune(app: &AppHandle, store: &Store) -> Result<Vec<PruneReport>, String> {
+    let _guard = begin_write(app)?;
+    let frames = read_all_frames(store).await;
+    let policies: BTreeMap<String, RetentionPolicy> = latest_setting(&frames, CONFIG_TOPIC);
+    let expired = expired(&frames, &policies, Utc::now().timestamp_millis() as u64);
+
+    let mut reports = Vec::new();
+    for (topic, ids) in expired {
+        let removing: HashSet<&scru128::Scru128Id> = ids.iter().collect();
+        let removing: Vec<&Frame> = frames
+            .iter()
+            .filter(|frame| removing.contains(&frame.id))
+            .collect();
+        cold_archive::preserve(app, store, &removing, "retention").await?;
+        let meta = serde_json::json!({
+            "topic": topic,
+            "policy": policies[&topic],
+            "frame_ids": ids,
+        });
+        let tombstone = append_frame(store, TOMBSTONE_TOPIC, None, Some(meta)).await?;
+        let mut pruned = 0;
+        for id in &ids {
+            match store.remove(id) {
+                Ok(_) => pruned += 1,
+                Err(e) => eprintln!("Failed to remove expired frame {id}: {e}"),
+            }
+        }
+        let _ = emit_frame(app, &tombstone);
+        reports.push(PruneReport {
+            topic,
+            tombstone_id: tombstone.id.to_string(),
+            pruned,
+        });
+    }
+    if reports.iter().any(|report| report.pruned > 0) {
+        if let Err(e) = blobs::sweep(app, store).await {
+            eprintln!("{e}");
+        }
+    }
+    Ok(reports)
+}
+
+/// Applies the retention policies every hour.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    loop {
+        match prune(&app, &store).await {
+            Ok(reports) => {
+                for report in reports {
+                    println!(
+                        "Retention removed {} {} frames",
+                        report.pruned, report.topic
+                    );
+                }
+            }
+            Err(e) => eprintln!("Retention pruning failed: {e}"),
+        }
+        tokio::time::sleep(TICK).await;
+    }
+}
+
+/// Sets how long frames on `topic` are kept. `keep_forever` clears the topic's policy.
+#[tauri::command]
+pub async fn set_retention(
+    store: State<'_, Store>,
+    topic: String,
+    policy: RetentionPolicy,
+) -> Result<(), String> {
+    if topic.is_empty() || PROTECTED.iter().any(|prefix| topic.starts_with(prefix)) {
+        return Err(format!("{topic} frames are always kept"));
+    }
+    if policy == (RetentionPolicy::KeepDays { days: 0 }) {
+        return Err("Frames must be kept at least a day".to_string());
+    }
+    let mut policies: BTreeMap<String, RetentionPolicy> = load_setting(&store, CONFIG_TOPIC).await;
+    match policy {
+        RetentionPolicy::KeepForever => policies.remove(&topic),
+        policy => policies.insert(topic, policy),
+    };
+    save_setting(&store, CONFIG_TOPIC, &policies)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_retention(
+    store: State<'_, Store>,
+) -> Result<BTreeMap<String, RetentionPolicy>, String> {
+    Ok(load_setting(&store, CONFIG_TOPIC).await)
+}
+
+/// Applies the retention policies now rather than at the next hourly pass.
+#[tauri::command]
+pub async fn prune_expired(
+    app: AppHandle,
+    store: State<'_, Store>,
+) -> Result<Vec<PruneReport>, String> {
+    prune(&app, &store).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xs::store::ZERO_CONTEXT;
+
+    const DAY: u64 = 24 * 60 * 60 * 1000;
+
+    fn frame(time: u64, topic: &str) -> Frame {
+        Frame {
+            id: scru128::Scru128Id::from_fields(time, 0, 0, 0),
+            context_id: ZERO_CONTEXT,
+            topic: topic.to_string(),
+            hash: None,
+            meta: None,
+            ttl: None,
+        }
+    }
+
+    #[test]
+    fn test_expired() {
+        let frames: Vec<Frame> = (1..=5)
+            .flat_map(|day| {
+                [
+                    frame(day * DAY, "handler.output"),
+                    frame(day * DAY + 1, "plugin.log"),
+                ]
+            })
+            .collect();
+        let policies = BTreeMap::from([
+            (
+                "handler.output".to_string(),
+                RetentionPolicy::KeepLast { count: 2 },
+            ),
+            (
+                "plugin.log".to_string(),
+                RetentionPolicy::KeepDays { days: 2 },
+            ),
+        ]);
+        let expired = expired(&frames, &policies, 6 * DAY);
+
+        let days = |topic: &str| -> Vec<u64> {
+            let mut days: Vec<u64> = expired[topic]
+                .iter()
+                .map(|id| id.timestamp() / DAY)
+                .collect();
+            days.sort();
+            days
+        };
+        assert_eq!(days("handler.output"), vec![1, 2, 3]);
+        // Day 4's frame is two days old, less a millisecond
+        assert_eq!(days("plugin.log"), vec![1, 2, 3]);
+
+        let kept = BTreeMap::from([(
+            "plugin.log".to_string(),
+            RetentionPolicy::KeepLast { count: 10 },
+        )]);
+        assert!(expired(&frames, &kept, 6 * DAY).is_empty());
+    }
+}
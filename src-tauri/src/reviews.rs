@@ -0,0 +1,321 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::export::note_title;
+use crate::projection::Projection;
+use crate::shutdown::begin_write;
+use crate::time::from_id;
+use crate::windows::emit_frame;
+use crate::{append_frame, locks, read_all_frames};
+
+const MARK_TOPIC: &str = "review.mark";
+const GRADE_TOPIC: &str = "review.grade";
+/// SM-2's starting ease, and the floor it never drops below.
+const INITIAL_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+
+/// A note's place in the review queue.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReviewCard {
+    pub note_id: String,
+    pub yak_id: String,
+    pub title: String,
+    /// Successful reviews in a row
+    pub repetitions: u32,
+    pub interval_days: u32,
+    pub ease: f64,
+    pub due: DateTime<Utc>,
+    pub last_reviewed: Option<DateTime<Utc>>,
+}
+
+/// The scheduling state carried from one grade to the next.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Schedule {
+    repetitions: u32,
+    interval_days: u32,
+    ease: f64,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            repetitions: 0,
+            interval_days: 0,
+            ease: INITIAL_EASE,
+        }
+    }
+}
+
+impl Schedule {
+    /// SM-2: a grade of 3 or more (out of 5) was recalled, and stretches the interval from
+    /// a day to six to the last one times the ease; less starts over from a day. The ease
+    /// moves with every grade.
+    fn grade(self, grade: u8) -> Schedule {
+        let grade = grade.min(5);
+        let miss = f64::from(5 - grade);
+        let ease = (self.ease + 0.1 - miss * (0.08 + miss * 0.02)).max(MIN_EASE);
+        if grade < 3 {
+            return Schedule {
+                repetitions: 0,
+                interval_days: 1,
+                ease,
+            };
+        }
+        let interval_days = match self.repetitions {
+            0 => 1,
+            1 => 6,
+            _ => (f64::from(self.interval_days) * self.ease).round() as u32,
+        };
+        Schedule {
+            repetitions: self.repetitions + 1,
+            interval_days,
+            ease,
+        }
+    }
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+/// The review cards of notes marked for review, by current note revision, replaying
+/// grades in order. Notes that have been deleted since are left out, and sealed ones go
+/// by their meta title or none.
+fn cards(projection: &Projection, frames: &[Frame]) -> HashMap<String, ReviewCard> {
+    let mut cards: HashMap<String, (Schedule, ReviewCard)> = HashMap::new();
+    for frame in frames {
+        let Some(note_id) = meta_str(frame, "note_id").map(|id| projection.resolve(id)) else {
+            continue;
+        };
+        let Some(time) = from_id(&frame.id.to_string()) else {
+            continue;
+        };
+        match frame.topic.as_str() {
+            MARK_TOPIC => {
+                let review = frame
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get("review"))
+                    .and_then(|review| review.as_bool())
+                    .unwrap_or(true);
+                if !review {
+                    cards.remove(&note_id);
+                    continue;
+                }
+                cards.entry(note_id.clone()).or_insert_with(|| {
+                    let card = ReviewCard {
+                        note_id,
+                        yak_id: String::new(),
+                        title: String::new(),
+                        repetitions: 0,
+                        interval_days: 0,
+                        ease: INITIAL_EASE,
+                        due: time,
+                        last_reviewed: None,
+                    };
+                    (Schedule::default(), card)
+                });
+            }
+            GRADE_TOPIC => {
+                let Some((schedule, card)) = cards.get_mut(&note_id) else {
+                    continue;
+                };
+                let Some(grade) = frame
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get("grade"))
+                    .and_then(|grade| grade.as_u64())
+                else {
+                    continue;
+                };
+                *schedule = schedule.grade(grade.min(5) as u8);
+                card.repetitions = schedule.repetitions;
+                card.interval_days = schedule.interval_days;
+                card.ease = schedule.ease;
+                card.due = time + Duration::days(i64::from(schedule.interval_days));
+                card.last_reviewed = Some(time);
+            }
+            _ => {}
+        }
+    }
+    cards
+        .into_iter()
+        .filter_map(|(note_id, (_, mut card))| {
+            let note = projection.notes.get(&note_id)?;
+            let live = projection
+                .notes_by_yak
+                .get(&note.yak_id)
+                .is_some_and(|ids| ids.contains(&note_id));
+            if !live {
+                return None;
+            }
+            let mut note = note.clone();
+            if note.content.as_deref().is_some_and(locks::is_sealed) {
+                note.content = None;
+            }
+            card.yak_id = note.yak_id.clone();
+            card.title = note_title(&note);
+            Some((note_id, card))
+        })
+        .collect()
+}
+
+async fn load(store: &Store) -> (Projection, HashMap<String, ReviewCard>) {
+    let frames = read_all_frames(store).await;
+    let mut projection = Projection::from_frames(&frames);
+    projection.resolve_content(store).await;
+    let cards = cards(&projection, &frames);
+    (projection, cards)
+}
+
+/// Adds a note to the review queue, due right away, or with `review` false takes it out.
+/// Taking a note out forgets its schedule.
+#[tauri::command]
+pub async fn mark_for_review(
+    app: AppHandle,
+    store: State<'_, Store>,
+    frame_id: String,
+    review: bool,
+) -> Result<Frame, String> {
+    let _guard = begin_write(&app)?;
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let note_id = projection.resolve(&frame_id);
+    let note = projection
+        .notes
+        .get(&note_id)
+        .ok_or_else(|| format!("Note not found: {frame_id}"))?;
+    let meta = json!({ "yak_id": note.yak_id, "note_id": note_id, "review": review });
+    let frame = append_frame(&store, MARK_TOPIC, None, Some(meta)).await?;
+    let _ = emit_frame(&app, &frame);
+    Ok(frame)
+}
+
+/// Notes due for review, optionally in one yak, most overdue first.
+#[tauri::command]
+pub async fn get_due_reviews(
+    store: State<'_, Store>,
+    yak_id: Option<String>,
+) -> Result<Vec<ReviewCard>, String> {
+    let (_, cards) = load(&store).await;
+    let now = Utc::now();
+    let mut due: Vec<ReviewCard> = cards
+        .into_values()
+        .filter(|card| card.due <= now)
+        .filter(|card| {
+            yak_id
+                .as_ref()
+                .map_or(true, |yak_id| &card.yak_id == yak_id)
+        })
+        .collect();
+    due.sort_by_key(|card| card.due);
+    Ok(due)
+}
+
+/// Grades how well a note was recalled, from 0 (blank) to 5 (perfect), and reschedules it
+/// SM-2 style. Returns the card with its next due date.
+#[tauri::command]
+pub async fn grade_review(
+    app: AppHandle,
+    store: State<'_, Store>,
+    frame_id: String,
+    grade: u8,
+) -> Result<ReviewCard, String> {
+    if grade > 5 {
+        return Err(format!("Grades run from 0 to 5, not {grade}"));
+    }
+    let (projection, cards) = load(&store).await;
+    let note_id = projection.resolve(&frame_id);
+    let card = cards
+        .get(&note_id)
+        .ok_or_else(|| format!("Note isn't up for review: {frame_id}"))?;
+    let meta = json!({ "yak_id": card.yak_id, "note_id": note_id, "grade": grade });
+    let frame = {
+        let _guard = begin_write(&app)?;
+        append_frame(&store, GRADE_TOPIC, None, Some(meta)).await?
+    };
+    let _ = emit_frame(&app, &frame);
+    let (_, mut cards) = load(&store).await;
+    cards
+        .remove(&note_id)
+        .ok_or_else(|| format!("Note isn't up for review: {frame_id}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{add_note, create_yak};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_schedule() {
+        let first = Schedule::default().grade(4);
+        assert_eq!((first.repetitions, first.interval_days), (1, 1));
+        assert_eq!(first.ease, INITIAL_EASE);
+        let second = first.grade(5);
+        assert_eq!(second.interval_days, 6);
+        assert!((second.ease - 2.6).abs() < 1e-9);
+        let third = second.grade(3);
+        assert_eq!(third.interval_days, 16);
+        assert!(third.ease < second.ease);
+
+        let missed = third.grade(1);
+        assert_eq!((missed.repetitions, missed.interval_days), (0, 1));
+        let mut hard = Schedule::default();
+        for _ in 0..10 {
+            hard = hard.grade(0);
+        }
+        assert_eq!(hard.ease, MIN_EASE);
+    }
+
+    #[tokio::test]
+    async fn test_cards() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Spanish").await.unwrap();
+        let card = add_note(&store, &yak_id, "# Hola\nhello", json!({}))
+            .await
+            .unwrap();
+        let dropped = add_note(&store, &yak_id, "# Adiós\nbye", json!({}))
+            .await
+            .unwrap();
+        let note_id = card.id.to_string();
+        let dropped_id = dropped.id.to_string();
+        for (id, review) in [(&note_id, true), (&dropped_id, true), (&dropped_id, false)] {
+            let meta = json!({ "yak_id": yak_id, "note_id": id, "review": review });
+            append_frame(&store, MARK_TOPIC, None, Some(meta))
+                .await
+                .unwrap();
+        }
+
+        let (_, cards) = load(&store).await;
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[&note_id].title, "Hola");
+        assert!(cards[&note_id].due <= Utc::now());
+
+        // Grades follow the note through edits
+        let meta = json!({ "yak_id": yak_id, "note_id": note_id });
+        let edit = append_frame(
+            &store,
+            "note.edit",
+            Some(b"# Hola\nhello, hi".as_slice()),
+            Some(meta),
+        )
+        .await
+        .unwrap();
+        let meta = json!({ "yak_id": yak_id, "note_id": note_id, "grade": 4 });
+        append_frame(&store, GRADE_TOPIC, None, Some(meta))
+            .await
+            .unwrap();
+        let (_, cards) = load(&store).await;
+        let card = &cards[&edit.id.to_string()];
+        assert_eq!(card.repetitions, 1);
+        assert_eq!(
+            card.last_reviewed.map(|at| card.due - at),
+            Some(Duration::days(1))
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::locks::is_sealed;
+use crate::projection::Projection;
+use crate::shutdown::begin_write;
+use crate::windows::{emit_frame, emit_frames};
+use crate::{append_frame, read_all_frames, sync};
+
+/// Meta key on the frames a rule appends, naming the rule.
+const RULE_KEY: &str = "rule_id";
+
+/// What a rule looks for in a capture, and where it files the captures that match.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleSpec {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Only captures from this source: `clip`, `feed`, `mail`, `browser` or `folder`
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Text the capture must contain, ignoring case; a rule without it matches everything
+    #[serde(default)]
+    pub contains: Option<String>,
+    /// The yak to move matching captures to
+    #[serde(default)]
+    pub yak_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// A routing rule, added by a `rule.add` frame (the spec is its meta) and dropped by
+/// `rule.remove`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Rule {
+    pub id: String,
+    #[serde(flatten)]
+    pub spec: RuleSpec,
+}
+
+/// Where a capture ends up once every rule has had its say.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Routing {
+    /// The rules that matched, in the order they were added
+    pub rule_ids: Vec<String>,
+    /// The first matching rule's yak
+    pub yak_id: Option<String>,
+    /// The tags of every matching rule
+    pub tags: Vec<String>,
+}
+
+impl RuleSpec {
+    fn matches(&self, source: &str, content: &str) -> bool {
+        self.source
+            .as_deref()
+            .map_or(true, |wanted| wanted == source)
+            && self.contains.as_deref().map_or(true, |text| {
+                content.to_lowercase().contains(&text.to_lowercase())
+            })
+    }
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+fn apply(rules: &mut Vec<Rule>, frame: &Frame) {
+    match frame.topic.as_str() {
+        "rule.add" => {
+            let spec = frame
+                .meta
+                .clone()
+                .and_then(|meta| serde_json::from_value(meta).ok());
+            if let Some(spec) = spec {
+                rules.push(Rule {
+                    id: frame.id.to_string(),
+                    spec,
+                });
+            }
+        }
+        "rule.remove" => {
+            if let Some(rule_id) = meta_str(frame, RULE_KEY) {
+                rules.retain(|rule| rule.id != rule_id);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub(crate) fn rules(frames: &[Frame]) -> Vec<Rule> {
+    let mut rules = Vec::new();
+    for frame in frames {
+        apply(&mut rules, frame);
+    }
+    rules
+}
+
+/// The capture source of a frame: clipboard clips, feed items, and notes filed from mail,
+/// the browser extension or the watched folder. Anything else isn't routed.
+pub(crate) fn source(frame: &Frame) -> Option<&str> {
+    match frame.topic.as_str() {
+        "clip" => Some("clip"),
+        "feed.item" => Some("feed"),
+        "note.create" => {
+            meta_str(frame, "source").filter(|s| matches!(*s, "mail" | "browser" | "folder"))
+        }
+        _ => None,
+    }
+}
+
+fn route(rules: &[Rule], source: &str, content: &str) -> Routing {
+    let mut routing = Routing::default();
+    for rule in rules
+        .iter()
+        .filter(|rule| rule.spec.matches(source, content))
+    {
+        routing.rule_ids.push(rule.id.clone());
+        if routing.yak_id.is_none() {
+            routing.yak_id = rule.spec.yak_id.clone();
+        }
+        for tag in &rule.spec.tags {
+            if !routing.tags.contains(tag) {
+                routing.tags.push(tag.clone());
+            }
+        }
+    }
+    routing
+}
+
+/// Moves and tags a new capture as the rules say.
+async fn file(app: &AppHandle, store: &Store, rules: &[Rule], frame: &Frame) -> Result<(), String> {
+    let (Some(source), Some(hash)) = (source(frame), frame.hash.as_ref()) else {
+        return Ok(());
+    };
+    let content = store
+        .cas_read(hash)
+        .await
+        .map_err(|e| format!("Failed to read capture: {e}"))?;
+    let content = String::from_utf8_lossy(&content);
+    if is_sealed(&content) {
+        return Ok(());
+    }
+    let routing = route(rules, source, &content);
+    let Some(rule_id) = routing.rule_ids.first() else {
+        return Ok(());
+    };
+
+    let _guard = begin_write(app)?;
+    let projection = Projection::from_frames(&read_all_frames(store).await);
+    let note_id = frame.id.to_string();
+    let Some(note) = projection.notes.get(&projection.resolve(&note_id)) else {
+        return Ok(());
+    };
+    let mut yak_id = note.yak_id.clone();
+    let mut frames = Vec::new();
+    if let Some(target) = routing.yak_id.filter(|target| *target != yak_id) {
+        match projection.yaks.get(&target) {
+            // Sealing happens as notes are written, so a plain capture stays out of locked yaks
+            Some(yak) if !yak.locked => {
+                let meta = serde_json::json!({
+                    "note_id": note.id,
+                    "yak_id": target,
+                    "from_yak_id": yak_id,
+                    RULE_KEY: rule_id,
+                });
+                frames.push(append_frame(store, "note.move", None, Some(meta)).await?);
+                yak_id = target;
+            }
+            Some(_) => eprintln!("Rule {rule_id} can't file into locked yak {target}"),
+            None => eprintln!("Rule {rule_id} files into unknown yak {target}"),
+        }
+    }
+    for tag in routing.tags.iter().filter(|tag| !note.tags.contains(*tag)) {
+        let meta = serde_json::json!({
+            "yak_id": yak_id,
+            "note_id": note.id,
+            "tag": tag,
+            RULE_KEY: rule_id,
+        });
+        frames.push(append_frame(store, "tag.add", None, Some(meta)).await?);
+    }
+    let _ = emit_frames(app, &frames);
+    Ok(())
+}
+
+/// Tracks rules as their frames go by, and once history has been read, files each new
+/// capture by them.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let mut rules: Vec<Rule> = Vec::new();
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    let mut caught_up = false;
+    while let Some(frame) = rx.recv().await {
+        match frame.topic.as_str() {
+            "xs.threshold" => caught_up = true,
+            "rule.add" | "rule.remove" => apply(&mut rules, &frame),
+            // Synced captures were filed on the device that made them
+            _ if caught_up && !rules.is_empty() && sync::origin(&frame).is_none() => {
+                if let Err(e) = file(&app, &store, &rules, &frame).await {
+                    eprintln!("Failed to apply rules: {e}");
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Adds a rule filing new captures that match it. Earlier rules pick the yak; every
+/// matching rule adds its tags.
+#[tauri::command]
+pub async fn add_rule(
+    app: AppHandle,
+    store: State<'_, Store>,
+    rule: RuleSpec,
+) -> Result<Rule, String> {
+    if rule.yak_id.is_none() && rule.tags.is_empty() {
+        return Err("A rule needs a yak or tags to file captures with".to_string());
+    }
+    if rule
+        .contains
+        .as_deref()
+        .is_some_and(|text| text.trim().is_empty())
+    {
+        return Err("A rule can't look for empty text".to_string());
+    }
+    let _guard = begin_write(&app)?;
+    let meta = serde_json::to_value(&rule).map_err(|e| format!("Invalid rule: {e}"))?;
+    let frame = append_frame(&store, "rule.add", None, Some(meta)).await?;
+    let _ = emit_frame(&app, &frame);
+    Ok(Rule {
+        id: frame.id.to_string(),
+        spec: rule,
+    })
+}
+
+#[tauri::command]
+pub async fn remove_rule(
+    app: AppHandle,
+    store: State<'_, Store>,
+    rule_id: String,
+) -> Result<(), String> {
+    let _guard = begin_write(&app)?;
+    let meta = serde_json::json!({ RULE_KEY: rule_id });
+    let frame = append_frame(&store, "rule.remove", None, Some(meta)).await?;
+    let _ = emit_frame(&app, &frame);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_rules(store: State<'_, Store>) -> Result<Vec<Rule>, String> {
+    Ok(rules(&read_all_frames(&store).await))
+}
+
+/// How the rules would file `sample` captured from `source` (a clip, if not given),
+/// without appending anything.
+#[tauri::command]
+pub async fn test_rule(
+    store: State<'_, Store>,
+    sample: String,
+    source: Option<String>,
+) -> Result<Routing, String> {
+    let rules = rules(&read_all_frames(&store).await);
+    Ok(route(&rules, source.as_deref().unwrap_or("clip"), &sample))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_route() {
+        let (_dir, store) = testing::store();
+        let frame = |topic: &str, meta: Value| append(&store, topic, Some(meta));
+        frame(
+            "rule.add",
+            json!({ "contains": "todo", "yak_id": "work", "tags": ["todo"] }),
+        );
+        let links = frame("rule.add", json!({ "source": "feed", "yak_id": "reading" }));
+        frame(
+            "rule.add",
+            json!({ "contains": "URGENT", "tags": ["urgent"] }),
+        );
+
+        let rules_before = rules(&read_all_frames(&store).await);
+        let routing = route(&rules_before, "feed", "TODO: urgent reading");
+        assert_eq!(routing.yak_id.as_deref(), Some("work"));
+        assert_eq!(routing.tags, vec!["todo", "urgent"]);
+        assert_eq!(routing.rule_ids.len(), 3);
+
+        let routing = route(&rules_before, "feed", "an article");
+        assert_eq!(routing.yak_id.as_deref(), Some("reading"));
+        assert!(route(&rules_before, "clip", "an article")
+            .rule_ids
+            .is_empty());
+
+        frame("rule.remove", json!({ RULE_KEY: links.id.to_string() }));
+        let rules_after = rules(&read_all_frames(&store).await);
+        assert_eq!(rules_after.len(), 2);
+        assert!(route(&rules_after, "feed", "an article").yak_id.is_none());
+
+        let mail = frame(
+            "note.create",
+            json!({ "yak_id": "inbox", "source": "mail" }),
+        );
+        let typed = frame("note.create", json!({ "yak_id": "inbox" }));
+        assert_eq!(source(&mail), Some("mail"));
+        assert_eq!(source(&typed), None);
+    }
+}
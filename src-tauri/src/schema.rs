@@ -0,0 +1,289 @@
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    String,
+    Bool,
+    Number,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Field {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub kind: FieldType,
+    /// Optional fields may be absent or null
+    pub required: bool,
+}
+
+/// The meta a well-known topic expects. Fields not listed are allowed, so frames can carry
+/// extra context (import sources, sync origins) without a schema change.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TopicSchema {
+    pub topic: &'static str,
+    pub fields: &'static [Field],
+}
+
+const fn required(name: &'static str) -> Field {
+    Field {
+        name,
+        kind: FieldType::String,
+        required: true,
+    }
+}
+
+const fn optional(name: &'static str, kind: FieldType) -> Field {
+    Field {
+        name,
+        kind,
+        required: false,
+    }
+}
+
+const NOTE: &[Field] = &[required("yak_id"), required("note_id")];
+const TAG: &[Field] = &[required("yak_id"), required("note_id"), required("tag")];
+
+const SCHEMAS: &[TopicSchema] = &[
+    TopicSchema {
+        topic: "yak.create",
+        fields: &[optional("name", FieldType::String)],
+    },
+    TopicSchema {
+        topic: "note.create",
+        fields: &[required("yak_id")],
+    },
+    TopicSchema {
+        topic: "note.edit",
+        fields: NOTE,
+    },
+    TopicSchema {
+        topic: "note.pin",
+        fields: &[required("note_id"), optional("pinned", FieldType::Bool)],
+    },
+    TopicSchema {
+        topic: "note.archive",
+        fields: &[required("note_id"), optional("archived", FieldType::Bool)],
+    },
+    TopicSchema {
+        topic: "note.property",
+        fields: &[required("note_id"), required("key")],
+    },
+    TopicSchema {
+        topic: "note.rename",
+        fields: &[required("note_id"), optional("title", FieldType::String)],
+    },
+    TopicSchema {
+        topic: "label.set",
+        fields: &[
+            required("target_id"),
+            optional("color", FieldType::String),
+            optional("icon", FieldType::String),
+        ],
+    },
+    TopicSchema {
+        topic: "note.move",
+        fields: NOTE,
+    },
+    TopicSchema {
+        topic: "note.parent",
+        fields: &[
+            required("yak_id"),
+            required("note_id"),
+            optional("parent_id", FieldType::String),
+        ],
+    },
+    TopicSchema {
+        topic: "note.order",
+        fields: &[required("yak_id"), required("column")],
+    },
+    TopicSchema {
+        topic: "note.delete",
+        fields: &[required("note_id")],
+    },
+    TopicSchema {
+        topic: "note.restore",
+        fields: &[required("note_id")],
+    },
+    TopicSchema {
+        topic: "tag.add",
+        fields: TAG,
+    },
+    TopicSchema {
+        topic: "tag.remove",
+        fields: TAG,
+    },
+    TopicSchema {
+        topic: "attachment.add",
+        fields: &[
+            required("note_id"),
+            optional("name", FieldType::String),
+            optional("mime", FieldType::String),
+        ],
+    },
+    TopicSchema {
+        topic: "task.create",
+        fields: &[
+            required("yak_id"),
+            optional("note_id", FieldType::String),
+            optional("done", FieldType::Bool),
+            optional("reminder", FieldType::String),
+            optional("checkbox", FieldType::String),
+        ],
+    },
+    TopicSchema {
+        topic: "task.update",
+        fields: &[required("task_id"), optional("done", FieldType::Bool)],
+    },
+    TopicSchema {
+        topic: "task.move",
+        fields: &[required("task_id"), required("yak_id")],
+    },
+    TopicSchema {
+        topic: "task.delete",
+        fields: &[required("task_id")],
+    },
+    TopicSchema {
+        topic: "reminder.set",
+        fields: &[
+            required("at"),
+            optional("note_id", FieldType::String),
+            optional("task_id", FieldType::String),
+        ],
+    },
+    TopicSchema {
+        topic: "reminder.clear",
+        fields: &[
+            optional("note_id", FieldType::String),
+            optional("task_id", FieldType::String),
+        ],
+    },
+    TopicSchema {
+        topic: "text.extract",
+        fields: &[
+            required("note_id"),
+            required("attachment_id"),
+            required("kind"),
+            optional("page", FieldType::Number),
+        ],
+    },
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Violation {
+    pub field: &'static str,
+    pub problem: String,
+}
+
+/// Why a frame was rejected: every field that doesn't match its topic's schema.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaError {
+    pub topic: String,
+    pub violations: Vec<Violation>,
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let violations: Vec<String> = self
+            .violations
+            .iter()
+            .map(|violation| format!("{} {}", violation.field, violation.problem))
+            .collect();
+        write!(f, "Invalid {} frame: {}", self.topic, violations.join(", "))
+    }
+}
+
+impl FieldType {
+    fn name(self) -> &'static str {
+        match self {
+            FieldType::String => "string",
+            FieldType::Bool => "bool",
+            FieldType::Number => "number",
+        }
+    }
+
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            FieldType::String => value.is_string(),
+            FieldType::Bool => value.is_boolean(),
+            FieldType::Number => value.is_number(),
+        }
+    }
+}
+
+/// Checks a frame's meta against its topic's schema. Topics without a schema pass.
+pub(crate) fn validate(topic: &str, meta: Option<&Value>) -> Result<(), SchemaError> {
+    let Some(schema) = SCHEMAS.iter().find(|schema| schema.topic == topic) else {
+        return Ok(());
+    };
+    let violations: Vec<Violation> = schema
+        .fields
+        .iter()
+        .filter_map(|field| {
+            let value = meta.and_then(|meta| meta.get(field.name));
+            let problem = match value {
+                None | Some(Value::Null) if field.required => "is required".to_string(),
+                None | Some(Value::Null) => return None,
+                Some(value) if !field.kind.matches(value) => {
+                    format!("must be a {}", field.kind.name())
+                }
+                Some(_) => return None,
+            };
+            Some(Violation {
+                field: field.name,
+                problem,
+            })
+        })
+        .collect();
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(SchemaError {
+            topic: topic.to_string(),
+            violations,
+        })
+    }
+}
+
+/// The meta schemas frames on well-known topics are checked against when appended.
+#[tauri::command]
+pub fn get_topic_schemas() -> Vec<TopicSchema> {
+    SCHEMAS.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("yak.create", None).is_ok());
+        assert!(validate("custom.topic", Some(&json!({ "anything": 1 }))).is_ok());
+        assert!(validate(
+            "task.create",
+            Some(&json!({ "yak_id": "y", "note_id": null, "extra": true }))
+        )
+        .is_ok());
+
+        let error = validate("tag.add", Some(&json!({ "yak_id": "y", "tag": 5 }))).unwrap_err();
+        assert_eq!(
+            error.violations,
+            vec![
+                Violation {
+                    field: "note_id",
+                    problem: "is required".to_string(),
+                },
+                Violation {
+                    field: "tag",
+                    problem: "must be a string".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            "Invalid tag.add frame: note_id is required, tag must be a string"
+        );
+    }
+}
@@ -0,0 +1,386 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use crate::projection::{Note, Projection};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Match {
+    /// Every word must occur, as in a search box
+    All,
+    /// Any word may occur, for natural-language questions
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Has,
+}
+
+/// A condition on a note property in a search query: `status:done`, `status:!done`,
+/// `priority:>2`, `due:<=2025-06-01` or `has:due`. Numbers compare as numbers and strings
+/// in order, so ISO dates work; a list matches `key:value` if any item does. `label:red`
+/// and `has:label` ask about the note's label, by color or icon.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PropertyFilter {
+    key: String,
+    op: Op,
+    value: String,
+}
+
+impl PropertyFilter {
+    fn parse(token: &str) -> Option<Self> {
+        let (key, rest) = token.split_once(':')?;
+        let valid = key.chars().next().is_some_and(|c| c.is_alphabetic())
+            && key
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+        // `//` keeps URLs in the query as words
+        if !valid || rest.is_empty() || rest.starts_with("//") {
+            return None;
+        }
+        if key == "has" {
+            return Some(Self {
+                key: rest.to_string(),
+                op: Op::Has,
+                value: String::new(),
+            });
+        }
+        let (op, value) = [
+            (">=", Op::Ge),
+            ("<=", Op::Le),
+            (">", Op::Gt),
+            ("<", Op::Lt),
+            ("!", Op::Ne),
+        ]
+        .into_iter()
+        .find_map(|(prefix, op)| Some((op, rest.strip_prefix(prefix)?)))
+        .unwrap_or((Op::Eq, rest));
+        Some(Self {
+            key: key.to_string(),
+            op,
+            value: value.to_string(),
+        })
+    }
+
+    pub(crate) fn matches(&self, note: &Note) -> bool {
+        let label = note
+            .label
+            .as_ref()
+            .filter(|_| self.key == "label")
+            .map(|label| Value::from_iter(label.color.iter().chain(&label.icon).cloned()));
+        let property = label.as_ref().or_else(|| note.properties.get(&self.key));
+        match (self.op, property) {
+            (Op::Has, property) => property.is_some(),
+            (Op::Eq, Some(property)) => equals(property, &self.value),
+            (Op::Ne, property) => !property.is_some_and(|p| equals(p, &self.value)),
+            (_, None) => false,
+            (op, Some(property)) => {
+                let ordering = compare(property, &self.value);
+                match op {
+                    Op::Lt => ordering == Some(Ordering::Less),
+                    Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    Op::Gt => ordering == Some(Ordering::Greater),
+                    _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                }
+            }
+        }
+    }
+}
+
+fn equals(property: &Value, wanted: &str) -> bool {
+    match property {
+        Value::Array(items) => items.iter().any(|item| equals(item, wanted)),
+        Value::String(s) => s.eq_ignore_ascii_case(wanted),
+        Value::Bool(b) => wanted.eq_ignore_ascii_case(&b.to_string()),
+        _ => compare(property, wanted) == Some(Ordering::Equal),
+    }
+}
+
+fn compare(property: &Value, wanted: &str) -> Option<Ordering> {
+    match property {
+        Value::Number(n) => n.as_f64()?.partial_cmp(&wanted.parse::<f64>().ok()?),
+        Value::String(s) => Some(s.as_str().cmp(wanted)),
+        _ => None,
+    }
+}
+
+/// Where a note revision stands, as a scoped search sees it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteStatus {
+    Active,
+    Archived,
+    /// Deleted, and restorable
+    Trashed,
+    /// Replaced by a later edit; never searched
+    Revision,
+}
+
+impl NoteStatus {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            NoteStatus::Active => "active",
+            NoteStatus::Archived => "archived",
+            NoteStatus::Trashed => "trashed",
+            NoteStatus::Revision => "revision",
+        }
+    }
+}
+
+/// `note`'s status, given the ids of the current notes.
+pub(crate) fn note_status(
+    projection: &Projection,
+    current: &HashSet<String>,
+    note: &Note,
+) -> NoteStatus {
+    if current.contains(&note.id) {
+        match note.archived {
+            true => NoteStatus::Archived,
+            false => NoteStatus::Active,
+        }
+    } else if projection.replaced_by.contains_key(&note.id) {
+        NoteStatus::Revision
+    } else {
+        NoteStatus::Trashed
+    }
+}
+
+/// Which yaks a search looks in.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum YakScope {
+    /// The yak the window is showing
+    Current {
+        yak_id: String,
+    },
+    Selected {
+        yak_ids: Vec<String>,
+    },
+    #[default]
+    All,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SearchScope {
+    pub yaks: YakScope,
+    pub include_archived: bool,
+    pub include_trashed: bool,
+}
+
+impl SearchScope {
+    /// The scope searches had before they took one: a yak or all of them, archived notes
+    /// included and the trash left out.
+    pub(crate) fn unscoped(yak_id: Option<String>) -> Self {
+        Self {
+            yaks: yak_id.map_or(YakScope::All, |yak_id| YakScope::Current { yak_id }),
+            include_archived: true,
+            include_trashed: false,
+        }
+    }
+
+    /// The yaks searched, or `None` for all of them.
+    pub(crate) fn yak_ids(&self) -> Option<Vec<String>> {
+        match &self.yaks {
+            YakScope::Current { yak_id } => Some(vec![yak_id.clone()]),
+            YakScope::Selected { yak_ids } => Some(yak_ids.clone()),
+            YakScope::All => None,
+        }
+    }
+
+    pub(crate) fn statuses(&self) -> Vec<NoteStatus> {
+        let mut statuses = vec![NoteStatus::Active];
+        if self.include_archived {
+            statuses.push(NoteStatus::Archived);
+        }
+        if self.include_trashed {
+            statuses.push(NoteStatus::Trashed);
+        }
+        statuses
+    }
+
+    /// Keeps only the notes in scope, for searches over a fold of the log. Returns each
+    /// kept note's status by id.
+    pub(crate) fn retain(&self, projection: &mut Projection) -> HashMap<String, NoteStatus> {
+        let current: HashSet<String> = projection
+            .notes_by_yak
+            .values()
+            .flatten()
+            .cloned()
+            .collect();
+        let yak_ids = self.yak_ids();
+        let statuses = self.statuses();
+        let kept: HashMap<String, NoteStatus> = projection
+            .notes
+            .values()
+            .filter(|note| {
+                yak_ids
+                    .as_ref()
+                    .map_or(true, |ids| ids.contains(&note.yak_id))
+            })
+            .map(|note| (note.id.clone(), note_status(projection, &current, note)))
+            .filter(|(_, status)| statuses.contains(status))
+            .collect();
+        projection.notes.retain(|id, _| kept.contains_key(id));
+        kept
+    }
+}
+
+/// Splits a search query into its words and its property filters.
+pub(crate) fn parse_query(query: &str) -> (String, Vec<PropertyFilter>) {
+    let mut words = Vec::new();
+    let mut filters = Vec::new();
+    for token in query.split_whitespace() {
+        match PropertyFilter::parse(token) {
+            Some(filter) => filters.push(filter),
+            None => words.push(token),
+        }
+    }
+    (words.join(" "), filters)
+}
+
+/// The current notes matching every filter, newest first, for queries that are all filters.
+pub(crate) fn filter_notes<'a>(
+    projection: &'a Projection,
+    filters: &[PropertyFilter],
+    yak_id: Option<&str>,
+    limit: usize,
+) -> Vec<&'a Note> {
+    let mut notes: Vec<&Note> = projection
+        .notes
+        .values()
+        .filter(|note| yak_id.map_or(true, |yak_id| note.yak_id == yak_id))
+        .filter(|note| filters.iter().all(|filter| filter.matches(note)))
+        .collect();
+    notes.sort_by(|a, b| b.id.cmp(&a.id));
+    notes.truncate(limit);
+    notes
+}
+
+/// How well a note matches a keyword query: the total number of occurrences of the query's
+/// words, or `None` if it doesn't match.
+pub(crate) fn score(text: &str, words: &[String], mode: Match) -> Option<usize> {
+    let text = text.to_lowercase();
+    let mut total = 0;
+    for word in words {
+        match text.matches(word.as_str()).count() {
+            0 if mode == Match::All => return None,
+            n => total += n,
+        }
+    }
+    (total > 0).then_some(total)
+}
+
+/// A note's content followed by any text extracted from its attachments.
+pub(crate) fn searchable_text(note: &Note) -> String {
+    let mut text = note.content.clone().unwrap_or_default();
+    let extracts = note
+        .attachments
+        .iter()
+        .flat_map(|attachment| &attachment.extracts)
+        .filter_map(|extract| extract.content.as_deref());
+    for extract in extracts {
+        text.push('\n');
+        text.push_str(extract);
+    }
+    text
+}
+
+/// Keyword search over the current notes of a projection (with content resolved), including
+/// text extracted from their attachments; best matches first, ties broken by recency.
+pub(crate) fn keyword_search<'a>(
+    projection: &'a Projection,
+    query: &str,
+    yak_id: Option<&str>,
+    mode: Match,
+    limit: usize,
+) -> Vec<&'a Note> {
+    let words: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        // Short words are mostly noise when any word may match
+        .filter(|word| !word.is_empty() && (mode == Match::All || word.chars().count() > 3))
+        .map(str::to_lowercase)
+        .collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+    let mut hits: Vec<(usize, &Note)> = projection
+        .notes
+        .values()
+        .filter(|note| yak_id.map_or(true, |yak_id| note.yak_id == yak_id))
+        .filter_map(|note| {
+            let score = score(&searchable_text(note), &words, mode)?;
+            Some((score, note))
+        })
+        .collect();
+    hits.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(b.id.cmp(&a.id)));
+    hits.into_iter().take(limit).map(|(_, note)| note).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_score() {
+        let words = vec!["rust".to_string(), "sync".to_string()];
+        assert_eq!(score("Rust sync, rust again", &words, Match::All), Some(3));
+        assert_eq!(score("only rust", &words, Match::All), None);
+        assert_eq!(score("only rust", &words, Match::Any), Some(1));
+        assert_eq!(score("neither", &words, Match::Any), None);
+    }
+
+    #[test]
+    fn test_property_filters() {
+        let (words, filters) = parse_query("trip status:booked budget:>=1000 has:due http://x.io");
+        assert_eq!(words, "trip http://x.io");
+        assert_eq!(filters.len(), 3);
+
+        let mut note: Note = serde_json::from_value(json!({
+            "id": "1",
+            "yak_id": "y",
+            "hash": null,
+            "edited_note_id": null,
+            "content": null,
+            "meta": null,
+            "tags": [],
+            "attachments": [],
+            "pinned": false,
+            "archived": false,
+            "reminder": null,
+        }))
+        .unwrap();
+        note.properties.insert("status".into(), json!("Booked"));
+        note.properties.insert("budget".into(), json!(1200));
+        note.properties.insert("due".into(), json!("2025-06-01"));
+        note.properties
+            .insert("people".into(), json!(["sam", "alex"]));
+        let matches = |query: &str| {
+            let (_, filters) = parse_query(query);
+            filters.iter().all(|filter| filter.matches(&note))
+        };
+        assert!(matches("status:booked budget:>=1000 has:due"));
+        assert!(matches("budget:<1200.5 due:<2025-07-01 people:alex"));
+        assert!(matches("status:!open owner:!sam"));
+        assert!(!matches("budget:>1200"));
+        assert!(!matches("has:owner"));
+        assert!(!matches("due:>2025-06-01"));
+        assert!(!matches("has:label"));
+
+        note.label = Some(crate::projection::Label {
+            color: Some("red".into()),
+            icon: Some("🍅".into()),
+        });
+        let (_, filters) = parse_query("label:red label:🍅 has:label label:!blue");
+        assert!(filters.iter().all(|filter| filter.matches(&note)));
+    }
+}
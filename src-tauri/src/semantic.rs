@@ -0,0 +1,442 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::{mpsc, Mutex, OnceCell};
+use xs::store::{FollowOption, ReadOptions, Store};
+
+use crate::export::note_title;
+use crate::indexes::Progress;
+use crate::profiles;
+use crate::projection::{Note, Projection};
+use crate::{insights, read_all_frames};
+
+const INDEX_FILE: &str = "embeddings.json";
+/// Notes are embedded from their first few thousand characters; the model truncates anyway
+const MAX_EMBED_CHARS: usize = 4000;
+/// Notes embedded between progress reports during a rebuild.
+const REBUILD_CHUNK: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    yak_id: String,
+    title: String,
+    /// The content hash the vector was computed from, so unchanged notes aren't re-embedded
+    hash: Option<String>,
+    vector: Vec<f32>,
+}
+
+/// Note embeddings keyed by current note id, saved as JSON beside the store.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Index {
+    entries: HashMap<String, Entry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SemanticHit {
+    pub note_id: String,
+    pub yak_id: String,
+    pub title: String,
+    pub score: f32,
+}
+
+pub struct SemanticState {
+    path: Mutex<Option<PathBuf>>,
+    model: OnceCell<Arc<fastembed::TextEmbedding>>,
+    index: Mutex<Index>,
+}
+
+impl Default for SemanticState {
+    fn default() -> Self {
+        Self {
+            path: Mutex::new(None),
+            model: OnceCell::new(),
+            index: Mutex::new(Index::default()),
+        }
+    }
+}
+
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+/// Cosine similarity, given normalized vectors.
+fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn embed_text(note: &Note) -> String {
+    note.content
+        .as_deref()
+        .unwrap_or_default()
+        .chars()
+        .take(MAX_EMBED_CHARS)
+        .collect()
+}
+
+impl Index {
+    /// The `k` notes closest to `vector`, best first, skipping any `exclude` rejects.
+    pub(crate) fn nearest(
+        &self,
+        vector: &[f32],
+        k: usize,
+        exclude: impl Fn(&str) -> bool,
+    ) -> Vec<SemanticHit> {
+        let mut hits: Vec<SemanticHit> = self
+            .entries
+            .iter()
+            .filter(|(id, _)| !exclude(id))
+            .map(|(id, entry)| SemanticHit {
+                note_id: id.clone(),
+                yak_id: entry.yak_id.clone(),
+                title: entry.title.clone(),
+                score: similarity(vector, &entry.vector),
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(k);
+        hits
+    }
+
+    pub(crate) fn vector(&self, note_id: &str) -> Option<&[f32]> {
+        self.entries
+            .get(note_id)
+            .map(|entry| entry.vector.as_slice())
+    }
+}
+
+impl SemanticState {
+    async fn model(&self) -> Result<Arc<fastembed::TextEmbedding>, String> {
+        let cache_dir = self
+            .path
+            .lock()
+            .await
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map(|dir| dir.join("models"))
+            .ok_or("Semantic search is not initialized")?;
+        self.model
+            .get_or_try_init(|| async move {
+                tokio::task::spawn_blocking(move || {
+                    let options =
+                        fastembed::InitOptions::new(fastembed::EmbeddingModel::AllMiniLML6V2)
+                            .with_cache_dir(cache_dir)
+                            .with_show_download_progress(false);
+                    fastembed::TextEmbedding::try_new(options)
+                        .map(Arc::new)
+                        .map_err(|e| format!("Failed to load embedding model: {e}"))
+                })
+                .await
+                .map_err(|e| format!("Failed to load embedding model: {e}"))?
+            })
+            .await
+            .cloned()
+    }
+
+    pub(crate) async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        let model = self.model().await?;
+        tokio::task::spawn_blocking(move || model.embed(texts, None))
+            .await
+            .map_err(|e| format!("Embedding failed: {e}"))?
+            .map(|vectors| vectors.into_iter().map(normalize).collect())
+            .map_err(|e| format!("Embedding failed: {e}"))
+    }
+
+    /// Brings the index up to date with the current notes: new and changed notes are
+    /// embedded, superseded and removed ones dropped, and the result saved.
+    pub(crate) async fn refresh(&self, store: &Store) -> Result<(), String> {
+        let frames = read_all_frames(store).await;
+        let mut projection = Projection::from_frames(&frames).into_current();
+        projection.resolve_content(store).await;
+
+        let mut index = self.index.lock().await;
+        index
+            .entries
+            .retain(|id, _| projection.notes.contains_key(id));
+        let stale: Vec<&Note> = projection
+            .notes
+            .values()
+            .filter(|note| {
+                note.content
+                    .as_deref()
+                    .is_some_and(|c| !c.trim().is_empty())
+            })
+            .filter(|note| {
+                let hash = note.hash.as_ref().map(|hash| hash.to_string());
+                index
+                    .entries
+                    .get(&note.id)
+                    .map_or(true, |entry| entry.hash != hash)
+            })
+            .collect();
+        if stale.is_empty() {
+            return Ok(());
+        }
+
+        let vectors = self
+            .embed(stale.iter().map(|note| embed_text(note)).collect())
+            .await?;
+        for (note, vector) in stale.into_iter().zip(vectors) {
+            index.entries.insert(
+                note.id.clone(),
+                Entry {
+                    yak_id: note.yak_id.clone(),
+                    title: note_title(note),
+                    hash: note.hash.as_ref().map(|hash| hash.to_string()),
+                    vector,
+                },
+            );
+        }
+
+        self.save(&index).await
+    }
+
+    /// Writes the index beside the old one, then moves it into place.
+    async fn save(&self, index: &Index) -> Result<(), String> {
+        let Some(path) = self.path.lock().await.clone() else {
+            return Ok(());
+        };
+        let json = serde_json::to_vec(index).map_err(|e| e.to_string())?;
+        let partial = path.with_extension("json.new");
+        tokio::fs::write(&partial, json)
+            .await
+            .map_err(|e| format!("Failed to save {}: {e}", path.display()))?;
+        tokio::fs::rename(&partial, &path)
+            .await
+            .map_err(|e| format!("Failed to save {}: {e}", path.display()))
+    }
+
+    /// Embeds every current note into a fresh index, which replaces the old one once it's
+    /// complete; searches use the old one until then.
+    pub(crate) async fn rebuild(&self, store: &Store, progress: &Progress) -> Result<(), String> {
+        let frames = read_all_frames(store).await;
+        let mut projection = Projection::from_frames(&frames).into_current();
+        projection.resolve_content(store).await;
+        let notes: Vec<&Note> = projection
+            .notes
+            .values()
+            .filter(|note| {
+                note.content
+                    .as_deref()
+                    .is_some_and(|c| !c.trim().is_empty())
+            })
+            .collect();
+
+        let mut index = Index::default();
+        for (i, chunk) in notes.chunks(REBUILD_CHUNK).enumerate() {
+            progress.report(i * REBUILD_CHUNK, notes.len());
+            let vectors = self
+                .embed(chunk.iter().map(|note| embed_text(note)).collect())
+                .await?;
+            for (note, vector) in chunk.iter().zip(vectors) {
+                index.entries.insert(
+                    note.id.clone(),
+                    Entry {
+                        yak_id: note.yak_id.clone(),
+                        title: note_title(note),
+                        hash: note.hash.as_ref().map(|hash| hash.to_string()),
+                        vector,
+                    },
+                );
+            }
+        }
+        // Held while saving, so a refresh can't write the old index over the new one
+        let mut current = self.index.lock().await;
+        self.save(&index).await?;
+        *current = index;
+        Ok(())
+    }
+
+    pub(crate) async fn index(&self) -> tokio::sync::MutexGuard<'_, Index> {
+        self.index.lock().await
+    }
+}
+
+/// Targets of the `[[...]]` wiki links in some content: note ids or titles.
+pub(crate) fn wiki_targets(content: &str) -> impl Iterator<Item = String> + '_ {
+    static WIKI: OnceLock<Regex> = OnceLock::new();
+    let wiki = WIKI.get_or_init(|| Regex::new(r"\[\[([^\]|#]+)").unwrap());
+    wiki.captures_iter(content)
+        .map(|caps| caps[1].trim().to_lowercase())
+}
+
+/// Current ids of the notes a note links to, and of those linking to it.
+fn linked_notes(projection: &Projection, note: &Note) -> HashSet<String> {
+    let by_title: HashMap<String, &str> = projection
+        .notes
+        .values()
+        .map(|other| (note_title(other).to_lowercase(), other.id.as_str()))
+        .collect();
+    let resolve = |target: &str| match by_title.get(target) {
+        Some(id) => id.to_string(),
+        None => projection.resolve(target),
+    };
+
+    let mut linked: HashSet<String> = wiki_targets(note.content.as_deref().unwrap_or_default())
+        .map(|target| resolve(&target))
+        .collect();
+    for other in projection
+        .notes
+        .values()
+        .filter(|other| other.id != note.id)
+    {
+        if wiki_targets(other.content.as_deref().unwrap_or_default())
+            .any(|target| resolve(&target) == note.id)
+        {
+            linked.insert(other.id.clone());
+        }
+    }
+    linked
+}
+
+/// Keeps the index current as notes change.
+async fn watch(app: AppHandle, store: Store) {
+    let (kick, mut kicked) = mpsc::channel::<()>(1);
+    let follower_store = store.clone();
+    tokio::spawn(async move {
+        let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+        let mut rx = follower_store.read(read_options).await;
+        let mut caught_up = false;
+        while let Some(frame) = rx.recv().await {
+            if frame.topic == "xs.threshold" {
+                caught_up = true;
+                crate::metrics::index_behind();
+                let _ = kick.try_send(());
+            } else if caught_up && frame.topic.starts_with("note.") {
+                crate::metrics::index_behind();
+                let _ = kick.try_send(());
+            }
+        }
+    });
+
+    while kicked.recv().await.is_some() {
+        let Ok(_guard) = crate::shutdown::begin_write(&app) else {
+            break;
+        };
+        // Frames arriving during the refresh mark the index behind again
+        crate::metrics::index_caught_up();
+        if let Err(e) = app.state::<SemanticState>().refresh(&store).await {
+            eprintln!("Failed to update semantic index: {e}");
+        }
+    }
+}
+
+pub(crate) async fn initialize(app: &AppHandle, store: &Store) {
+    let path = match profiles::data_dir(app) {
+        Ok(dir) => dir.join(INDEX_FILE),
+        Err(e) => {
+            eprintln!("Semantic search disabled: {e}");
+            return;
+        }
+    };
+    let state = app.state::<SemanticState>();
+    if let Ok(json) = tokio::fs::read(&path).await {
+        match serde_json::from_slice(&json) {
+            Ok(index) => *state.index.lock().await = index,
+            Err(e) => eprintln!("Rebuilding semantic index: {e}"),
+        }
+    }
+    *state.path.lock().await = Some(path);
+    tokio::spawn(watch(app.clone(), store.clone()));
+}
+
+/// The `k` notes closest in meaning to `query`.
+#[tauri::command]
+pub async fn semantic_search(
+    app: AppHandle,
+    state: State<'_, SemanticState>,
+    query: String,
+    k: Option<usize>,
+) -> Result<Vec<SemanticHit>, String> {
+    crate::app_lock::ensure_unlocked()?;
+    insights::record_search(&app, "semantic").await;
+    let vector = state
+        .embed(vec![query])
+        .await?
+        .pop()
+        .ok_or("Embedding failed")?;
+    let index = state.index().await;
+    Ok(index.nearest(&vector, k.unwrap_or(10), |_| false))
+}
+
+/// The `k` notes most similar to a note, leaving out the note itself and notes it links to
+/// or is linked from. Passing the editor's unsaved `content` keeps results current while
+/// writing.
+#[tauri::command]
+pub async fn get_related(
+    store: State<'_, Store>,
+    state: State<'_, SemanticState>,
+    frame_id: String,
+    k: Option<usize>,
+    content: Option<String>,
+) -> Result<Vec<SemanticHit>, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let frames = read_all_frames(&store).await;
+    let mut projection = Projection::from_frames(&frames).into_current();
+    projection.resolve_content(&store).await;
+    let note_id = projection.resolve(&frame_id);
+    let note = projection
+        .notes
+        .get(&note_id)
+        .ok_or_else(|| format!("Note not found: {frame_id}"))?;
+    let exclude = linked_notes(&projection, note);
+
+    let indexed = state.index().await.vector(&note_id).map(<[f32]>::to_vec);
+    let vector = match (content, indexed) {
+        (None, Some(vector)) => vector,
+        (content, _) => {
+            let text = content.unwrap_or_else(|| embed_text(note));
+            state
+                .embed(vec![text])
+                .await?
+                .pop()
+                .ok_or("Embedding failed")?
+        }
+    };
+    let index = state.index().await;
+    Ok(index.nearest(&vector, k.unwrap_or(5), |id| {
+        id == note_id || exclude.contains(id)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(vector: Vec<f32>) -> Entry {
+        Entry {
+            yak_id: "yak".to_string(),
+            title: String::new(),
+            hash: None,
+            vector: normalize(vector),
+        }
+    }
+
+    #[test]
+    fn test_nearest() {
+        let mut index = Index::default();
+        index.entries.insert("a".to_string(), entry(vec![1.0, 0.0]));
+        index.entries.insert("b".to_string(), entry(vec![0.7, 0.7]));
+        index.entries.insert("c".to_string(), entry(vec![0.0, 1.0]));
+
+        let query = normalize(vec![1.0, 0.1]);
+        let hits = index.nearest(&query, 2, |_| false);
+        let ids: Vec<&str> = hits.iter().map(|hit| hit.note_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+
+        let hits = index.nearest(&query, 1, |id| id == "a");
+        assert_eq!(hits[0].note_id, "b");
+    }
+
+    #[test]
+    fn test_wiki_targets() {
+        let targets: Vec<String> = wiki_targets("see [[Cats|cats]] and [[0abc#part]]").collect();
+        assert_eq!(targets, vec!["cats", "0abc"]);
+    }
+}
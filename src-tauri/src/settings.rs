@@ -0,0 +1,54 @@
+use serde::{de::DeserializeOwned, Serialize};
+use xs::store::{Frame, Store, ZERO_CONTEXT};
+
+use crate::provenance::{self, Source};
+use crate::read_all_frames;
+
+/// Settings are stored as frames: the meta of the latest frame on a topic is the current value.
+pub(crate) async fn load_setting<T: DeserializeOwned + Default>(store: &Store, topic: &str) -> T {
+    let frames = read_all_frames(store).await;
+    latest_setting(&frames, topic)
+}
+
+pub(crate) fn latest_setting<T: DeserializeOwned + Default>(frames: &[Frame], topic: &str) -> T {
+    frames
+        .iter()
+        .rev()
+        .find(|frame| frame.topic == topic)
+        .and_then(|frame| frame.meta.clone())
+        .map(|mut meta| {
+            // The stamp isn't part of the value, and would break settings that are maps
+            if let Some(fields) = meta.as_object_mut() {
+                fields.remove(provenance::KEY);
+            }
+            meta
+        })
+        .and_then(|meta| match serde_json::from_value(meta) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                eprintln!("Ignoring invalid {topic} setting: {e}");
+                None
+            }
+        })
+        .unwrap_or_default()
+}
+
+pub(crate) fn save_setting<T: Serialize>(
+    store: &Store,
+    topic: &str,
+    value: &T,
+) -> Result<Frame, String> {
+    let meta = serde_json::to_value(value).map_err(|e| format!("Invalid setting: {e}"))?;
+    let mut meta = Some(meta);
+    provenance::stamp(&mut meta, Source::Ui);
+    let frame = Frame {
+        id: scru128::new(),
+        context_id: ZERO_CONTEXT,
+        topic: topic.to_string(),
+        hash: None,
+        meta,
+        ttl: None,
+    };
+    crate::append_to_store(store, frame)
+        .map_err(|e| e.context(&format!("Failed to save {topic}")).into())
+}
@@ -0,0 +1,314 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::diagnostics::{is_secret, SETTING_TOPICS};
+use crate::rules::{rules, RuleSpec};
+use crate::settings::{latest_setting, save_setting};
+use crate::shutdown::begin_write;
+use crate::windows::emit_frames;
+use crate::{append_frame, handlers, read_all_frames, templates};
+
+const VERSION: u32 = 1;
+
+/// Settings that belong to the machine they were made on, never carried to another one.
+fn portable(topic: &str) -> bool {
+    !topic.starts_with("store.") && !topic.starts_with("sync.")
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SetupTemplate {
+    name: String,
+    content: String,
+    #[serde(default)]
+    meta: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SetupHandler {
+    name: String,
+    topics: Vec<String>,
+    script: String,
+}
+
+/// Everything about how the app is set up, and none of the notes: settings (without their
+/// credentials), routing rules, templates and handlers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Setup {
+    version: u32,
+    #[serde(default)]
+    settings: BTreeMap<String, Value>,
+    #[serde(default)]
+    rules: Vec<RuleSpec>,
+    #[serde(default)]
+    templates: Vec<SetupTemplate>,
+    #[serde(default)]
+    handlers: Vec<SetupHandler>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SetupReport {
+    pub settings: usize,
+    pub rules: usize,
+    pub templates: usize,
+    pub handlers: usize,
+}
+
+fn strip_secrets(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.retain(|key, _| !is_secret(key));
+            fields.values_mut().for_each(strip_secrets);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_secrets),
+        _ => {}
+    }
+}
+
+/// Puts this machine's credentials back into an imported setting that was stripped of them.
+fn keep_secrets(imported: &mut Value, local: &Value) {
+    let (Value::Object(imported), Value::Object(local)) = (imported, local) else {
+        return;
+    };
+    for (key, value) in local {
+        match imported.get_mut(key) {
+            Some(field) => keep_secrets(field, value),
+            None if is_secret(key) => {
+                imported.insert(key.clone(), value.clone());
+            }
+            None => {}
+        }
+    }
+}
+
+fn settings(frames: &[Frame]) -> BTreeMap<String, Value> {
+    SETTING_TOPICS
+        .iter()
+        .filter(|topic| portable(topic))
+        .filter_map(|topic| {
+            let mut value = latest_setting::<Option<Value>>(frames, topic)?;
+            strip_secrets(&mut value);
+            Some((topic.to_string(), value))
+        })
+        .collect()
+}
+
+async fn collect(store: &Store) -> Setup {
+    let frames = read_all_frames(store).await;
+    Setup {
+        version: VERSION,
+        settings: settings(&frames),
+        rules: rules(&frames).into_iter().map(|rule| rule.spec).collect(),
+        templates: templates::templates(store)
+            .await
+            .into_values()
+            .map(|template| SetupTemplate {
+                name: template.name,
+                content: template.content,
+                meta: template.meta,
+            })
+            .collect(),
+        handlers: handlers::handlers(store)
+            .await
+            .into_iter()
+            .map(|handler| SetupHandler {
+                name: handler.name,
+                topics: handler.topics,
+                script: handler.script,
+            })
+            .collect(),
+    }
+}
+
+/// Appends whatever of `setup` this store doesn't have yet, so importing twice adds nothing.
+async fn apply(store: &Store, setup: Setup) -> Result<(SetupReport, Vec<Frame>), String> {
+    let existing = collect(store).await;
+    let frames = read_all_frames(store).await;
+    let mut report = SetupReport::default();
+    let mut appended = Vec::new();
+
+    for (topic, mut value) in setup.settings {
+        if !SETTING_TOPICS.contains(&topic.as_str()) || !portable(&topic) {
+            continue;
+        }
+        if let Some(local) = latest_setting::<Option<Value>>(&frames, &topic) {
+            keep_secrets(&mut value, &local);
+            if value == local {
+                continue;
+            }
+        }
+        appended.push(save_setting(store, &topic, &value)?);
+        report.settings += 1;
+    }
+    for rule in setup.rules {
+        if existing.rules.contains(&rule) {
+            continue;
+        }
+        let meta = serde_json::to_value(&rule).map_err(|e| format!("Invalid rule: {e}"))?;
+        appended.push(append_frame(store, "rule.add", None, Some(meta)).await?);
+        report.rules += 1;
+    }
+    for template in setup.templates {
+        if existing.templates.contains(&template) {
+            continue;
+        }
+        let content = (!template.content.is_empty()).then_some(template.content.as_bytes());
+        let meta = serde_json::json!({ "name": template.name, "meta": template.meta });
+        appended.push(append_frame(store, templates::SAVE_TOPIC, content, Some(meta)).await?);
+        report.templates += 1;
+    }
+    for handler in setup.handlers {
+        if existing.handlers.contains(&handler) {
+            continue;
+        }
+        let meta = serde_json::json!({ "name": handler.name, "topics": handler.topics });
+        let script = Some(handler.script.as_bytes());
+        appended.push(append_frame(store, "handler.register", script, Some(meta)).await?);
+        report.handlers += 1;
+    }
+    Ok((report, appended))
+}
+
+/// Writes this store's setup to `path` as JSON: settings with their credentials left out,
+/// routing rules, templates and handlers. Notes and device-bound settings (store location,
+/// sync) stay behind. Yaks that settings and rules name must exist wherever it's imported.
+#[tauri::command]
+pub async fn export_config(store: State<'_, Store>, path: String) -> Result<SetupReport, String> {
+    let setup = collect(&store).await;
+    let report = SetupReport {
+        settings: setup.settings.len(),
+        rules: setup.rules.len(),
+        templates: setup.templates.len(),
+        handlers: setup.handlers.len(),
+    };
+    let json =
+        serde_json::to_vec_pretty(&setup).map_err(|e| format!("Failed to serialize setup: {e}"))?;
+    tokio::fs::write(Path::new(&path), json)
+        .await
+        .map_err(|e| format!("Failed to write {path}: {e}"))?;
+    Ok(report)
+}
+
+/// Applies a setup written by `export_config`, keeping this machine's credentials. Settings
+/// are replaced; rules, templates and handlers already here are skipped. Returns what was
+/// added or changed.
+#[tauri::command]
+pub async fn import_config(
+    app: AppHandle,
+    store: State<'_, Store>,
+    path: String,
+) -> Result<SetupReport, String> {
+    let json = tokio::fs::read(&path)
+        .await
+        .map_err(|e| format!("Failed to read {path}: {e}"))?;
+    let setup: Setup =
+        serde_json::from_slice(&json).map_err(|e| format!("Invalid setup file: {e}"))?;
+    if setup.version > VERSION {
+        return Err(format!("{path} was exported by a newer version of the app"));
+    }
+    let _guard = begin_write(&app)?;
+    let (report, frames) = apply(&store, setup).await?;
+    let _ = emit_frames(&app, &frames);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_secrets() {
+        let mut setting = json!({
+            "enabled": true,
+            "token": "abc",
+            "capture": { "enabled": true, "token": "def" },
+        });
+        strip_secrets(&mut setting);
+        assert_eq!(
+            setting,
+            json!({ "enabled": true, "capture": { "enabled": true } })
+        );
+
+        let local = json!({ "enabled": false, "token": "xyz", "capture": { "token": "uvw" } });
+        keep_secrets(&mut setting, &local);
+        assert_eq!(
+            setting,
+            json!({
+                "enabled": true,
+                "token": "xyz",
+                "capture": { "enabled": true, "token": "uvw" },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_setup_roundtrip() {
+        let dir = tempdir().unwrap();
+        let laptop = Store::new(dir.path().join("laptop"));
+        let desktop = Store::new(dir.path().join("desktop"));
+        save_setting(
+            &laptop,
+            "publish.config",
+            &json!({ "port": 8080, "token": "abc" }),
+        )
+        .unwrap();
+        save_setting(&laptop, "sync.s3", &json!({ "bucket": "notes" })).unwrap();
+        save_setting(
+            &desktop,
+            "publish.config",
+            &json!({ "port": 9090, "token": "xyz" }),
+        )
+        .unwrap();
+        let rule = json!({ "contains": "todo", "tags": ["todo"] });
+        append_frame(&laptop, "rule.add", None, Some(rule))
+            .await
+            .unwrap();
+        let template = json!({ "name": "Daily", "meta": {} });
+        append_frame(
+            &laptop,
+            templates::SAVE_TOPIC,
+            Some(b"# {{date}}".as_slice()),
+            Some(template),
+        )
+        .await
+        .unwrap();
+        add_note_frame(&laptop).await;
+
+        let setup = collect(&laptop).await;
+        assert_eq!(
+            setup.settings.keys().collect::<Vec<_>>(),
+            vec!["publish.config"]
+        );
+        assert_eq!(setup.settings["publish.config"], json!({ "port": 8080 }));
+
+        let (report, frames) = apply(&desktop, setup.clone()).await.unwrap();
+        assert_eq!((report.settings, report.rules, report.templates), (1, 1, 1));
+        assert!(frames.iter().all(|frame| !frame.topic.starts_with("note.")));
+        let frames = read_all_frames(&desktop).await;
+        let publish = latest_setting::<Value>(&frames, "publish.config");
+        assert_eq!(publish, json!({ "port": 8080, "token": "xyz" }));
+
+        // Importing again changes nothing
+        let (report, frames) = apply(&desktop, setup).await.unwrap();
+        assert_eq!(report.settings + report.rules + report.templates, 0);
+        assert!(frames.is_empty());
+    }
+
+    async fn add_note_frame(store: &Store) {
+        let yak = append_frame(store, "yak.create", None, None).await.unwrap();
+        let meta = json!({ "yak_id": yak.id.to_string() });
+        append_frame(
+            store,
+            "note.create",
+            Some(b"secret plans".as_slice()),
+            Some(meta),
+        )
+        .await
+        .unwrap();
+    }
+}
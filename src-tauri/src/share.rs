@@ -0,0 +1,304 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::projection::Projection;
+use crate::sync::{is_syncable, reconcile, Direction, Remote, ORIGIN_KEY};
+use crate::{crypto, read_all_frames};
+
+/// Bundles start with this, followed by the key salt and the encrypted payload.
+const MAGIC: &[u8] = b"YAKSHARE1";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Bundle {
+    frames: Vec<Frame>,
+    /// Base64 content, keyed by integrity string
+    blobs: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareReport {
+    pub frames: usize,
+    pub blobs: usize,
+}
+
+fn references(frame: &Frame, ids: &HashSet<String>) -> bool {
+    let Some(meta) = frame.meta.as_ref() else {
+        return false;
+    };
+    ["note_id", "task_id", "attachment_id", "suggestion_id"]
+        .iter()
+        .filter_map(|key| meta.get(*key).and_then(|v| v.as_str()))
+        .any(|id| ids.contains(id))
+}
+
+/// The frames to share for the given yak and note ids. A yak brings everything in it; a note
+/// brings its revisions and everything attached to them, plus its yak's `yak.create` so the
+/// recipient has somewhere to put it.
+fn select(frames: &[Frame], ids: &[String]) -> Vec<Frame> {
+    let yaks: HashSet<String> = frames
+        .iter()
+        .filter(|frame| frame.topic == "yak.create")
+        .map(|frame| frame.id.to_string())
+        .filter(|id| ids.contains(id))
+        .collect();
+    let mut selected: HashSet<String> = ids.iter().cloned().collect();
+    // Frames only reference earlier frames, so one pass in order picks up whole chains
+    for frame in frames {
+        let in_yak = frame
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.get("yak_id"))
+            .and_then(|id| id.as_str())
+            .is_some_and(|yak_id| yaks.contains(yak_id));
+        if in_yak || references(frame, &selected) {
+            selected.insert(frame.id.to_string());
+        }
+    }
+    let homes: HashSet<String> = frames
+        .iter()
+        .filter(|frame| selected.contains(&frame.id.to_string()))
+        .filter_map(|frame| {
+            frame
+                .meta
+                .as_ref()?
+                .get("yak_id")?
+                .as_str()
+                .map(String::from)
+        })
+        .collect();
+
+    frames
+        .iter()
+        .filter(|frame| is_syncable(frame))
+        .filter(|frame| {
+            let id = frame.id.to_string();
+            selected.contains(&id) || (frame.topic == "yak.create" && homes.contains(&id))
+        })
+        .cloned()
+        .map(|mut frame| {
+            // Sync provenance from the sharer's devices means nothing to the recipient
+            if let Some(serde_json::Value::Object(meta)) = frame.meta.as_mut() {
+                meta.remove(ORIGIN_KEY);
+            }
+            frame
+        })
+        .collect()
+}
+
+/// Drops every frame of a locked yak: their content from before the lock is in the clear, and
+/// the recipient couldn't open what came after.
+fn without_locked(frames: Vec<Frame>) -> Vec<Frame> {
+    let locked: HashSet<String> = Projection::from_frames(&frames)
+        .yaks
+        .into_values()
+        .filter(|yak| yak.locked)
+        .map(|yak| yak.id)
+        .collect();
+    frames
+        .into_iter()
+        .filter(|frame| {
+            let yak_id = frame
+                .meta
+                .as_ref()
+                .and_then(|meta| meta.get("yak_id"))
+                .and_then(|id| id.as_str());
+            !locked.contains(&frame.id.to_string()) && !yak_id.is_some_and(|id| locked.contains(id))
+        })
+        .collect()
+}
+
+fn seal(passphrase: &str, bundle: &Bundle) -> Result<Vec<u8>, String> {
+    let payload = serde_json::to_vec(bundle).map_err(|e| e.to_string())?;
+    let salt = crypto::random_salt();
+    let key = crypto::derive_key(passphrase, &salt)?;
+    let mut sealed = MAGIC.to_vec();
+    sealed.extend_from_slice(&salt);
+    sealed.extend(crypto::encrypt(&key, &payload)?);
+    Ok(sealed)
+}
+
+fn open(passphrase: &str, sealed: &[u8]) -> Result<Bundle, String> {
+    let rest = sealed
+        .strip_prefix(MAGIC)
+        .ok_or("Not a yaks share bundle")?;
+    if rest.len() < crypto::SALT_LEN {
+        return Err("Share bundle is truncated".to_string());
+    }
+    let (salt, ciphertext) = rest.split_at(crypto::SALT_LEN);
+    let key = crypto::derive_key(passphrase, salt)?;
+    let payload = crypto::decrypt(&key, ciphertext)?;
+    serde_json::from_slice(&payload).map_err(|e| format!("Invalid share bundle: {e}"))
+}
+
+impl Remote for Bundle {
+    async fn frames(&self) -> Result<Vec<Frame>, String> {
+        Ok(self.frames.clone())
+    }
+
+    async fn content(&self, hash: &ssri::Integrity) -> Result<Vec<u8>, String> {
+        let blob = self
+            .blobs
+            .get(&hash.to_string())
+            .ok_or_else(|| format!("Share bundle is missing content {hash}"))?;
+        base64::engine::general_purpose::STANDARD
+            .decode(blob)
+            .map_err(|e| format!("Invalid share bundle: {e}"))
+    }
+
+    async fn append(
+        &self,
+        _topic: &str,
+        _content: Option<Vec<u8>>,
+        _meta: serde_json::Value,
+    ) -> Result<Frame, String> {
+        Err("Share bundles are read-only".to_string())
+    }
+}
+
+pub(crate) async fn export_bundle(
+    store: &Store,
+    ids: &[String],
+    passphrase: &str,
+    path: &Path,
+) -> Result<ShareReport, String> {
+    let frames = without_locked(read_all_frames(store).await);
+    // Ids of anything withheld can't bring in the frames attached to them either
+    let present: HashSet<String> = frames.iter().map(|frame| frame.id.to_string()).collect();
+    let ids: Vec<String> = ids
+        .iter()
+        .filter(|id| present.contains(*id))
+        .cloned()
+        .collect();
+    let frames = select(&frames, &ids);
+    if frames.is_empty() {
+        return Err("Nothing to share".to_string());
+    }
+    let mut bundle = Bundle {
+        frames,
+        ..Default::default()
+    };
+    for hash in bundle.frames.iter().filter_map(|frame| frame.hash.as_ref()) {
+        let content = store
+            .cas_read(hash)
+            .await
+            .map_err(|e| format!("Failed to read content: {e}"))?;
+        bundle.blobs.insert(
+            hash.to_string(),
+            base64::engine::general_purpose::STANDARD.encode(content),
+        );
+    }
+
+    let sealed = seal(passphrase, &bundle)?;
+    tokio::fs::write(path, sealed)
+        .await
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok(ShareReport {
+        frames: bundle.frames.len(),
+        blobs: bundle.blobs.len(),
+    })
+}
+
+/// Writes the given yaks and/or notes, with their content, to a single file encrypted with
+/// `passphrase`.
+#[tauri::command]
+pub async fn export_share_bundle(
+    store: State<'_, Store>,
+    ids: Vec<String>,
+    passphrase: String,
+    path: String,
+) -> Result<ShareReport, String> {
+    crate::app_lock::ensure_unlocked()?;
+    export_bundle(&store, &ids, &passphrase, Path::new(&path)).await
+}
+
+/// Imports a share bundle. Frames already imported from the same bundle are skipped, so
+/// importing an updated bundle only adds what's new.
+#[tauri::command]
+pub async fn import_share_bundle(
+    store: State<'_, Store>,
+    app: AppHandle,
+    path: String,
+    passphrase: String,
+) -> Result<ShareReport, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let sealed = tokio::fs::read(&path)
+        .await
+        .map_err(|e| format!("Failed to read {path}: {e}"))?;
+    let bundle = open(&passphrase, &sealed)?;
+    // Handlers run scripts, so one can't come from someone else's bundle
+    if bundle
+        .frames
+        .iter()
+        .any(|frame| frame.topic.starts_with("handler."))
+    {
+        return Err("Share bundles can't include handlers".to_string());
+    }
+    let (_, pulled) = reconcile(&store, &bundle, Direction::Pull).await?;
+    Ok(ShareReport {
+        frames: pulled,
+        blobs: bundle.blobs.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{add_note, add_tag, create_yak};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_share_note_roundtrip() {
+        let dir = tempdir().unwrap();
+        let sender = Store::new(dir.path().join("sender"));
+        let yak_id = create_yak(&sender, "Project").await.unwrap();
+        let shared = add_note(&sender, &yak_id, "shared", serde_json::json!({}))
+            .await
+            .unwrap();
+        add_tag(&sender, &yak_id, &shared.id.to_string(), "todo")
+            .await
+            .unwrap();
+        add_note(&sender, &yak_id, "private", serde_json::json!({}))
+            .await
+            .unwrap();
+
+        let path = dir.path().join("note.yaks");
+        let report = export_bundle(&sender, &[shared.id.to_string()], "pass", &path)
+            .await
+            .unwrap();
+        assert_eq!(report.frames, 3);
+
+        let sealed = std::fs::read(&path).unwrap();
+        assert!(open("wrong", &sealed).is_err());
+        let bundle = open("pass", &sealed).unwrap();
+
+        let receiver = Store::new(dir.path().join("receiver"));
+        assert_eq!(
+            reconcile(&receiver, &bundle, Direction::Pull)
+                .await
+                .unwrap()
+                .1,
+            3
+        );
+        // A second import adds nothing
+        assert_eq!(
+            reconcile(&receiver, &bundle, Direction::Pull)
+                .await
+                .unwrap()
+                .1,
+            0
+        );
+
+        // Nothing of a locked yak is shared, even what was written before the lock
+        crate::testing::append(
+            &sender,
+            "yak.lock",
+            Some(serde_json::json!({ "yak_id": yak_id })),
+        );
+        let locked = export_bundle(&sender, &[shared.id.to_string()], "pass", &path).await;
+        assert_eq!(locked.unwrap_err(), "Nothing to share");
+    }
+}
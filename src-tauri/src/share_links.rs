@@ -0,0 +1,381 @@
+use axum::extract::{Path, Query, State as AxumState};
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::export::html::{inline_attachments, page, render_markdown};
+use crate::export::note_title;
+use crate::projection::Projection;
+use crate::publish::{self, PublishConfig, PublishState};
+use crate::settings::{load_setting, save_setting};
+use crate::{append_frame, crypto, read_all_frames};
+
+const CREATE_TOPIC: &str = "publish.share.create";
+const REVOKE_TOPIC: &str = "publish.share.revoke";
+const VIEW_TOPIC: &str = "publish.share.view";
+/// A visit turned away: expired, revoked or with a bad signature.
+const REFUSE_TOPIC: &str = "publish.share.refuse";
+const MAX_TTL: i64 = 90 * 24 * 60 * 60;
+
+/// A link that shows one note, and its attachments, to anyone holding it until it expires
+/// or is revoked. Made with a `publish.share.create` frame; visits and revocation are
+/// frames too.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareLink {
+    pub link_id: String,
+    pub frame_id: String,
+    pub expires: DateTime<Utc>,
+    pub revoked: bool,
+    pub views: usize,
+    /// Set for links that still work
+    pub url: Option<String>,
+    /// The signature in the URL
+    #[serde(skip)]
+    sig: String,
+}
+
+impl ShareLink {
+    fn live(&self, now: DateTime<Utc>) -> bool {
+        !self.revoked && now < self.expires
+    }
+}
+
+/// What `/shared` needs to check a link and show its note.
+pub(crate) struct Shares {
+    store: Store,
+    key: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Signed {
+    #[serde(default)]
+    sig: String,
+}
+
+/// The hex HMAC-SHA256 over what a link grants, keyed with the publish config's share key.
+fn sign(key: &str, link_id: &str, frame_id: &str, expires: DateTime<Utc>) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(format!("{link_id}:{frame_id}:{}", expires.timestamp()).as_bytes());
+    crypto::to_hex(&mac.finalize().into_bytes())
+}
+
+fn verify(key: &str, link: &ShareLink, sig: &str) -> bool {
+    if !sig.is_ascii() {
+        return false;
+    }
+    let Ok(sig) = crypto::from_hex(sig) else {
+        return false;
+    };
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any length");
+    let granted = format!(
+        "{}:{}:{}",
+        link.link_id,
+        link.frame_id,
+        link.expires.timestamp()
+    );
+    mac.update(granted.as_bytes());
+    mac.verify_slice(&sig).is_ok()
+}
+
+/// Every share link made, by id, as its frames leave it.
+fn links(frames: &[Frame]) -> BTreeMap<String, ShareLink> {
+    let mut links = BTreeMap::new();
+    for frame in frames {
+        let Some(meta) = frame.meta.as_ref() else {
+            continue;
+        };
+        let field = |key: &str| meta.get(key).and_then(|value| value.as_str());
+        let Some(link_id) = field("link_id") else {
+            continue;
+        };
+        match frame.topic.as_str() {
+            CREATE_TOPIC => {
+                let (Some(frame_id), Some(expires), Some(sig)) =
+                    (field("frame_id"), field("expires"), field("sig"))
+                else {
+                    continue;
+                };
+                let Ok(expires) = expires.parse::<DateTime<Utc>>() else {
+                    continue;
+                };
+                links.insert(
+                    link_id.to_string(),
+                    ShareLink {
+                        link_id: link_id.to_string(),
+                        frame_id: frame_id.to_string(),
+                        expires,
+                        revoked: false,
+                        views: 0,
+                        url: None,
+                        sig: sig.to_string(),
+                    },
+                );
+            }
+            REVOKE_TOPIC => {
+                if let Some(link) = links.get_mut(link_id) {
+                    link.revoked = true;
+                }
+            }
+            VIEW_TOPIC => {
+                if let Some(link) = links.get_mut(link_id) {
+                    link.views += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    links
+}
+
+fn with_url(mut link: ShareLink, port: u16, now: DateTime<Utc>) -> ShareLink {
+    if link.live(now) {
+        link.url = Some(format!(
+            "{}/shared/{}?sig={}",
+            publish::base_url(port),
+            link.link_id,
+            link.sig
+        ));
+    }
+    link
+}
+
+async fn record(store: &Store, topic: &str, meta: serde_json::Value) {
+    if let Err(e) = append_frame(store, topic, None, Some(meta)).await {
+        eprintln!("Failed to record share link event: {e}");
+    }
+}
+
+/// What the publish server needs to serve share links, when any still work.
+pub(crate) async fn serving(store: &Store, config: &PublishConfig) -> Option<Arc<Shares>> {
+    let key = config.share_key.clone()?;
+    let now = Utc::now();
+    let frames = read_all_frames(store).await;
+    links(&frames).values().any(|link| link.live(now)).then(|| {
+        Arc::new(Shares {
+            store: store.clone(),
+            key,
+        })
+    })
+}
+
+fn refuse(status: StatusCode, message: &str) -> Response {
+    (status, message.to_string()).into_response()
+}
+
+/// Shows a share link's note, alone, with its attachments inlined.
+pub(crate) async fn shared_note(
+    AxumState(shares): AxumState<Arc<Shares>>,
+    Path(link_id): Path<String>,
+    Query(signed): Query<Signed>,
+) -> Response {
+    if crate::app_lock::ensure_unlocked().is_err() {
+        return refuse(StatusCode::LOCKED, "Yaks is locked");
+    }
+    let frames = read_all_frames(&shares.store).await;
+    let Some(link) = links(&frames).remove(&link_id) else {
+        return refuse(StatusCode::NOT_FOUND, "No such link");
+    };
+    let now = Utc::now();
+    let refusal = if !verify(&shares.key, &link, &signed.sig) {
+        Some((StatusCode::FORBIDDEN, "signature"))
+    } else if link.revoked {
+        Some((StatusCode::GONE, "revoked"))
+    } else if now >= link.expires {
+        Some((StatusCode::GONE, "expired"))
+    } else {
+        None
+    };
+    if let Some((status, reason)) = refusal {
+        let meta = json!({ "link_id": link_id, "reason": reason });
+        record(&shares.store, REFUSE_TOPIC, meta).await;
+        return refuse(status, &format!("This link is no longer valid ({reason})"));
+    }
+
+    // Edits since the link was made are shown; a deleted note isn't
+    let projection = Projection::from_frames(&frames);
+    let note_id = projection.resolve(&link.frame_id);
+    let mut projection = projection.into_current();
+    projection.resolve_content(&shares.store).await;
+    let Some(note) = projection.notes.get(&note_id) else {
+        return refuse(StatusCode::NOT_FOUND, "The note is gone");
+    };
+    let locked = projection
+        .yaks
+        .get(&note.yak_id)
+        .is_some_and(|yak| yak.locked);
+    if locked || note.sealed.is_some() {
+        return refuse(StatusCode::NOT_FOUND, "The note is locked");
+    }
+    let content = note.content.as_deref().unwrap_or_default();
+    let markdown = match inline_attachments(&shares.store, note, content.to_string()).await {
+        Ok(markdown) => markdown,
+        Err(e) => return refuse(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    };
+    let html = page(&note_title(note), "", &render_markdown(&markdown));
+    let meta = json!({ "link_id": link_id, "frame_id": note_id });
+    record(&shares.store, VIEW_TOPIC, meta).await;
+    (
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            // The URL is the credential, so it mustn't leak to sites the note links to
+            (header::REFERRER_POLICY, "no-referrer"),
+            (header::HeaderName::from_static("x-robots-tag"), "noindex"),
+        ],
+        Html(html),
+    )
+        .into_response()
+}
+
+/// Makes a link to note `frame_id` that works for `ttl` seconds, served by the publish server
+/// without its token. The server is started for it if it isn't running.
+#[tauri::command]
+pub async fn create_share_link(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, PublishState>,
+    frame_id: String,
+    ttl: i64,
+) -> Result<ShareLink, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    if !(1..=MAX_TTL).contains(&ttl) {
+        return Err(format!(
+            "Links last between a second and {} days",
+            MAX_TTL / 86400
+        ));
+    }
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let note_id = projection.resolve(&frame_id);
+    let note = projection
+        .notes
+        .get(&note_id)
+        .ok_or_else(|| format!("Note {frame_id} not found"))?;
+    let mut config: PublishConfig = load_setting(&store, publish::CONFIG_TOPIC).await;
+    let key = match &config.share_key {
+        Some(key) => key.clone(),
+        None => {
+            let key = crypto::to_hex(&crypto::random_salt());
+            config.share_key = Some(key.clone());
+            save_setting(&store, publish::CONFIG_TOPIC, &config)?;
+            key
+        }
+    };
+
+    let now = Utc::now();
+    let link_id = scru128::new().to_string();
+    let expires = now + Duration::seconds(ttl);
+    let sig = sign(&key, &link_id, &note_id, expires);
+    let meta = json!({
+        "link_id": link_id,
+        "frame_id": note_id,
+        "yak_id": note.yak_id,
+        "expires": expires.to_rfc3339(),
+        "sig": sig,
+    });
+    append_frame(&store, CREATE_TOPIC, None, Some(meta)).await?;
+    publish::apply_config(&app, &store, &state, &config).await;
+    Ok(with_url(
+        ShareLink {
+            link_id,
+            frame_id: note_id,
+            expires,
+            revoked: false,
+            views: 0,
+            url: None,
+            sig,
+        },
+        config.port,
+        now,
+    ))
+}
+
+/// Stops a share link working, straight away.
+#[tauri::command]
+pub async fn revoke_share_link(
+    store: State<'_, Store>,
+    app: AppHandle,
+    link_id: String,
+) -> Result<(), String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let frames = read_all_frames(&store).await;
+    let link = links(&frames)
+        .remove(&link_id)
+        .ok_or_else(|| format!("Share link {link_id} not found"))?;
+    if link.revoked {
+        return Ok(());
+    }
+    let meta = json!({ "link_id": link_id, "frame_id": link.frame_id });
+    append_frame(&store, REVOKE_TOPIC, None, Some(meta)).await?;
+    Ok(())
+}
+
+/// Every share link made, newest first, with how often each was opened.
+#[tauri::command]
+pub async fn list_share_links(store: State<'_, Store>) -> Result<Vec<ShareLink>, String> {
+    let config: PublishConfig = load_setting(&store, publish::CONFIG_TOPIC).await;
+    let now = Utc::now();
+    let frames = read_all_frames(&store).await;
+    Ok(links(&frames)
+        .into_values()
+        .rev()
+        .map(|link| with_url(link, config.port, now))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_links() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let expires = Utc::now() + Duration::hours(1);
+        for link_id in ["a", "b"] {
+            let meta = json!({
+                "link_id": link_id,
+                "frame_id": "note",
+                "expires": expires.to_rfc3339(),
+                "sig": sign("key", link_id, "note", expires),
+            });
+            append_frame(&store, CREATE_TOPIC, None, Some(meta))
+                .await
+                .unwrap();
+        }
+        let view = json!({ "link_id": "a", "frame_id": "note" });
+        append_frame(&store, VIEW_TOPIC, None, Some(view))
+            .await
+            .unwrap();
+        let revoke = json!({ "link_id": "b" });
+        append_frame(&store, REVOKE_TOPIC, None, Some(revoke))
+            .await
+            .unwrap();
+
+        let links = links(&read_all_frames(&store).await);
+        let now = Utc::now();
+        assert_eq!(links["a"].views, 1);
+        assert!(links["a"].live(now));
+        assert!(!links["b"].live(now));
+        assert!(!links["a"].live(expires));
+
+        let link = &links["a"];
+        assert!(verify("key", link, &link.sig));
+        assert!(!verify("other key", link, &link.sig));
+        assert!(!verify("key", link, "not hex"));
+        let moved = ShareLink {
+            frame_id: "other note".to_string(),
+            ..link.clone()
+        };
+        assert!(!verify("key", &moved, &link.sig));
+    }
+}
@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tauri::{AppHandle, Emitter, State};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use xs::store::{Frame, Store};
+
+use crate::settings::{latest_setting, load_setting, save_setting};
+use crate::windows::focus_main;
+use crate::{app_lock, presentation};
+
+const CONFIG_TOPIC: &str = "shortcut.bindings";
+
+struct Action {
+    id: &'static str,
+    description: &'static str,
+    default: &'static str,
+    /// Registered with the OS so it works while Yaks is in the background; other
+    /// shortcuts are handled by the frontend while a window is focused
+    global: bool,
+}
+
+const ACTIONS: &[Action] = &[
+    Action {
+        id: "note.new",
+        description: "New note",
+        default: "CmdOrCtrl+N",
+        global: false,
+    },
+    Action {
+        id: "note.edit",
+        description: "Edit the selected note",
+        default: "CmdOrCtrl+Enter",
+        global: false,
+    },
+    Action {
+        id: "app.show",
+        description: "Bring Yaks to the front",
+        default: "CmdOrCtrl+Shift+Y",
+        global: true,
+    },
+    Action {
+        id: "capture.quick",
+        description: "Capture a note from anywhere",
+        default: "CmdOrCtrl+Shift+Space",
+        global: true,
+    },
+    Action {
+        id: app_lock::LOCK_ACTION,
+        description: "Lock Yaks now",
+        default: "CmdOrCtrl+Alt+Shift+L",
+        global: true,
+    },
+    Action {
+        id: presentation::TOGGLE_ACTION,
+        description: "Turn presentation mode on or off",
+        default: "CmdOrCtrl+Alt+Shift+P",
+        global: true,
+    },
+];
+
+/// Bindings that differ from the defaults, keyed by action id. `None` leaves the action
+/// unbound.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ShortcutConfig {
+    pub overrides: BTreeMap<String, Option<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShortcutBinding {
+    pub action: String,
+    pub description: String,
+    pub global: bool,
+    pub default: String,
+    pub accelerator: Option<String>,
+}
+
+fn bindings(config: &ShortcutConfig) -> Vec<ShortcutBinding> {
+    ACTIONS
+        .iter()
+        .map(|action| ShortcutBinding {
+            action: action.id.to_string(),
+            description: action.description.to_string(),
+            global: action.global,
+            default: action.default.to_string(),
+            accelerator: match config.overrides.get(action.id) {
+                Some(accelerator) => accelerator.clone(),
+                None => Some(action.default.to_string()),
+            },
+        })
+        .collect()
+}
+
+fn parse(accelerator: &str) -> Result<Shortcut, String> {
+    accelerator
+        .parse()
+        .map_err(|e| format!("Invalid shortcut {accelerator}: {e}"))
+}
+
+/// Applies a rebinding to `config`, refusing unknown actions, unparseable accelerators and
+/// accelerators already bound to another action.
+fn rebind(
+    config: &mut ShortcutConfig,
+    action: &str,
+    accelerator: Option<String>,
+) -> Result<(), String> {
+    let Some(target) = ACTIONS.iter().find(|candidate| candidate.id == action) else {
+        return Err(format!("Unknown action: {action}"));
+    };
+    if let Some(accelerator) = &accelerator {
+        let shortcut = parse(accelerator)?;
+        for other in bindings(config) {
+            if other.action == action {
+                continue;
+            }
+            let Some(bound) = &other.accelerator else {
+                continue;
+            };
+            if parse(bound).is_ok_and(|bound| bound == shortcut) {
+                return Err(format!(
+                    "{accelerator} is already bound to {}",
+                    other.description
+                ));
+            }
+        }
+    }
+    if accelerator.as_deref() == Some(target.default) {
+        config.overrides.remove(action);
+    } else {
+        config.overrides.insert(action.to_string(), accelerator);
+    }
+    Ok(())
+}
+
+fn trigger(app: &AppHandle, action: &str) {
+    // Handled here rather than by a window, which shouldn't need to come forward for it
+    if action == presentation::TOGGLE_ACTION {
+        presentation::toggle(app);
+        return;
+    }
+    if action == app_lock::LOCK_ACTION {
+        if let Err(e) = app_lock::lock(app) {
+            eprintln!("Failed to lock: {e}");
+        }
+        return;
+    }
+    if let Some(window) = focus_main(app) {
+        if let Err(e) = window.emit("shortcut", action) {
+            eprintln!("Failed to emit shortcut: {e}");
+        }
+    }
+}
+
+/// Replaces the OS-level registrations with the global bindings in `bindings`.
+fn register(app: &AppHandle, bindings: &[ShortcutBinding]) -> Result<(), String> {
+    let global_shortcut = app.global_shortcut();
+    global_shortcut
+        .unregister_all()
+        .map_err(|e| format!("Failed to unregister shortcuts: {e}"))?;
+    for binding in bindings.iter().filter(|binding| binding.global) {
+        let Some(accelerator) = &binding.accelerator else {
+            continue;
+        };
+        let action = binding.action.clone();
+        global_shortcut
+            .on_shortcut(parse(accelerator)?, move |app, _, event| {
+                if event.state == ShortcutState::Pressed {
+                    trigger(app, &action);
+                }
+            })
+            .map_err(|e| format!("Failed to register {accelerator}: {e}"))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn initialize(app: &AppHandle, frames: &[Frame]) {
+    let config: ShortcutConfig = latest_setting(frames, CONFIG_TOPIC);
+    if let Err(e) = register(app, &bindings(&config)) {
+        eprintln!("Failed to register global shortcuts: {e}");
+    }
+}
+
+async fn save(
+    app: &AppHandle,
+    store: &Store,
+    previous: &ShortcutConfig,
+    config: &ShortcutConfig,
+) -> Result<Vec<ShortcutBinding>, String> {
+    let updated = bindings(config);
+    // Another app may own the accelerator; keep the old registrations if so
+    if let Err(e) = register(app, &updated) {
+        let _ = register(app, &bindings(previous));
+        return Err(e);
+    }
+    save_setting(store, CONFIG_TOPIC, config)?;
+    if let Err(e) = app.emit("shortcuts-changed", &updated) {
+        eprintln!("Failed to emit shortcuts: {e}");
+    }
+    Ok(updated)
+}
+
+/// Every action with its current binding.
+#[tauri::command]
+pub async fn list_shortcuts(store: State<'_, Store>) -> Result<Vec<ShortcutBinding>, String> {
+    let config: ShortcutConfig = load_setting(&store, CONFIG_TOPIC).await;
+    Ok(bindings(&config))
+}
+
+/// Binds `action` to `accelerator` (e.g. `CmdOrCtrl+Shift+K`), or unbinds it when
+/// `accelerator` is null. Global shortcuts are re-registered straight away.
+#[tauri::command]
+pub async fn rebind_shortcut(
+    app: AppHandle,
+    store: State<'_, Store>,
+    action: String,
+    accelerator: Option<String>,
+) -> Result<Vec<ShortcutBinding>, String> {
+    let previous: ShortcutConfig = load_setting(&store, CONFIG_TOPIC).await;
+    let mut config = previous.clone();
+    rebind(&mut config, &action, accelerator)?;
+    save(&app, &store, &previous, &config).await
+}
+
+#[tauri::command]
+pub async fn reset_shortcuts(
+    app: AppHandle,
+    store: State<'_, Store>,
+) -> Result<Vec<ShortcutBinding>, String> {
+    let previous: ShortcutConfig = load_setting(&store, CONFIG_TOPIC).await;
+    save(&app, &store, &previous, &ShortcutConfig::default()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accelerator(config: &ShortcutConfig, action: &str) -> Option<String> {
+        bindings(config)
+            .into_iter()
+            .find(|binding| binding.action == action)
+            .unwrap()
+            .accelerator
+    }
+
+    #[test]
+    fn test_rebind() {
+        let mut config = ShortcutConfig::default();
+        assert_eq!(
+            accelerator(&config, "note.new").as_deref(),
+            Some("CmdOrCtrl+N")
+        );
+
+        rebind(&mut config, "note.new", Some("CmdOrCtrl+Shift+N".into())).unwrap();
+        assert_eq!(
+            accelerator(&config, "note.new").as_deref(),
+            Some("CmdOrCtrl+Shift+N")
+        );
+
+        rebind(&mut config, "capture.quick", None).unwrap();
+        assert_eq!(accelerator(&config, "capture.quick"), None);
+
+        // Going back to the default drops the override
+        rebind(&mut config, "note.new", Some("CmdOrCtrl+N".into())).unwrap();
+        assert!(!config.overrides.contains_key("note.new"));
+    }
+
+    #[test]
+    fn test_rebind_rejects_conflicts_and_unknowns() {
+        let mut config = ShortcutConfig::default();
+        let err = rebind(&mut config, "app.show", Some("CmdOrCtrl+N".into())).unwrap_err();
+        assert_eq!(err, "CmdOrCtrl+N is already bound to New note");
+        assert!(rebind(&mut config, "note.fly", Some("CmdOrCtrl+J".into())).is_err());
+        assert!(rebind(&mut config, "note.new", Some("Banana+J".into())).is_err());
+        assert!(config.overrides.is_empty());
+
+        // An unbound action no longer conflicts
+        rebind(&mut config, "note.new", None).unwrap();
+        rebind(&mut config, "app.show", Some("CmdOrCtrl+N".into())).unwrap();
+    }
+}
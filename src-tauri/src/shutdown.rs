@@ -0,0 +1,182 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, ExitRequestApi, Manager};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// How long quitting waits for in-flight writes before giving up on them.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Set once the store has been flushed for exit; checked on every append, which has no app
+/// handle to reach `ShutdownState` with.
+static CLOSED: AtomicBool = AtomicBool::new(false);
+
+/// Tracks writes in progress and long-running background tasks so quitting can let the
+/// former finish and stop the latter cleanly.
+#[derive(Default)]
+pub struct ShutdownState {
+    started: AtomicBool,
+    closing: AtomicBool,
+    finished: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+/// Held for the duration of a store or index write; quitting waits until all are dropped.
+pub(crate) struct WriteGuard(AppHandle);
+
+impl Drop for WriteGuard {
+    fn drop(&mut self) {
+        let state = self.0.state::<ShutdownState>();
+        if state.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            state.idle.notify_waiters();
+        }
+    }
+}
+
+/// Registers a write, refused once shutdown has started or while the store is read-only.
+pub(crate) fn begin_write(app: &AppHandle) -> Result<WriteGuard, String> {
+    crate::store_lock::ensure_writable()?;
+    let state = app.state::<ShutdownState>();
+    state.in_flight.fetch_add(1, Ordering::SeqCst);
+    let guard = WriteGuard(app.clone());
+    if state.closing.load(Ordering::SeqCst) {
+        return Err("Yaks is shutting down".to_string());
+    }
+    Ok(guard)
+}
+
+/// Fails once the store has been flushed for exit, so nothing is written after it.
+pub(crate) fn ensure_open() -> Result<(), String> {
+    if CLOSED.load(Ordering::SeqCst) {
+        return Err("Yaks is shutting down".to_string());
+    }
+    Ok(())
+}
+
+/// Keeps a background task's handle so shutdown can stop it.
+pub(crate) fn track(app: &AppHandle, task: JoinHandle<()>) {
+    app.state::<ShutdownState>()
+        .tasks
+        .lock()
+        .unwrap()
+        .push(task);
+}
+
+async fn drain(state: &ShutdownState) {
+    loop {
+        let idle = state.idle.notified();
+        if state.in_flight.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        idle.await;
+    }
+}
+
+/// Syncs every file under the store directory, and the directories themselves, to disk.
+fn flush_store(path: &Path) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let kind = entry.file_type()?;
+        if kind.is_dir() {
+            flush_store(&entry.path())?;
+        } else if kind.is_file() {
+            std::fs::File::open(entry.path())?.sync_all()?;
+        }
+    }
+    std::fs::File::open(path)?.sync_all()
+}
+
+/// Stops taking writes, commits pending drafts, waits for in-flight writes, stops
+/// background tasks, then flushes the store.
+async fn shutdown(app: &AppHandle) {
+    let state = app.state::<ShutdownState>();
+    // Drafts are committed before the gate closes so their writes are let through
+    crate::drafts::flush_all(app).await;
+    state.closing.store(true, Ordering::SeqCst);
+
+    if tokio::time::timeout(DRAIN_TIMEOUT, drain(&state))
+        .await
+        .is_err()
+    {
+        eprintln!(
+            "Quitting with {} writes still in flight",
+            state.in_flight.load(Ordering::SeqCst)
+        );
+    }
+    for task in state.tasks.lock().unwrap().drain(..) {
+        task.abort();
+    }
+    CLOSED.store(true, Ordering::SeqCst);
+    let path = app.state::<crate::location::StoreLocation>().0.clone();
+    if let Ok(Err(e)) = tokio::task::spawn_blocking(move || flush_store(&path)).await {
+        eprintln!("Failed to flush the store: {e}");
+    }
+    crate::windows::save_cursors(app);
+    crate::recovery::end(app);
+}
+
+/// Called for `RunEvent::ExitRequested` (including the last window closing): holds the
+/// exit until `shutdown` has run, then exits for real.
+pub(crate) fn on_exit_requested(app: &AppHandle, api: &ExitRequestApi) {
+    let state = app.state::<ShutdownState>();
+    if state.finished.load(Ordering::SeqCst) {
+        return;
+    }
+    api.prevent_exit();
+    if state.started.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        shutdown(&app).await;
+        app.state::<ShutdownState>()
+            .finished
+            .store(true, Ordering::SeqCst);
+        app.exit(0);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+    use xs::store::Store;
+
+    #[tokio::test]
+    async fn test_drain_and_flush() {
+        let state = Arc::new(ShutdownState::default());
+        state.in_flight.store(2, Ordering::SeqCst);
+        let waiting = tokio::spawn({
+            let state = state.clone();
+            async move { drain(&state).await }
+        });
+        tokio::task::yield_now().await;
+        for _ in 0..2 {
+            assert!(!waiting.is_finished());
+            // What dropping a WriteGuard does
+            if state.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+                state.idle.notify_waiters();
+            }
+            tokio::task::yield_now().await;
+        }
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("drain should finish once nothing is in flight")
+            .unwrap();
+
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let meta = serde_json::json!({ "yak_id": "y" });
+        let frame = crate::append_frame(&store, "note.create", Some(b"kept"), Some(meta))
+            .await
+            .unwrap();
+        flush_store(dir.path()).unwrap();
+        assert_eq!(store.get(&frame.id).unwrap().hash, frame.hash);
+    }
+}
@@ -0,0 +1,317 @@
+use proptest::prelude::*;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use xs::store::{Frame, ZERO_CONTEXT};
+
+use crate::projection::Projection;
+
+const TAGS: &[&str] = &["work", "home", "urgent", "later"];
+
+/// One step of a simulated session. Indices pick among the yaks and notes that exist when
+/// the step runs, so any sequence of steps makes a valid log.
+#[derive(Debug, Clone)]
+enum Op {
+    CreateYak,
+    Create {
+        yak: usize,
+    },
+    Edit {
+        note: usize,
+    },
+    /// Deletes through any of the note's revisions, as a stale window would
+    Delete {
+        note: usize,
+        revision: usize,
+    },
+    Restore {
+        note: usize,
+        revision: usize,
+    },
+    Tag {
+        note: usize,
+        revision: usize,
+        tag: usize,
+        add: bool,
+    },
+    Move {
+        note: usize,
+        yak: usize,
+    },
+    Parent {
+        note: usize,
+        parent: Option<usize>,
+    },
+}
+
+fn ops() -> impl Strategy<Value = Vec<Op>> {
+    let op = prop_oneof![
+        1 => Just(Op::CreateYak),
+        4 => any::<usize>().prop_map(|yak| Op::Create { yak }),
+        3 => any::<usize>().prop_map(|note| Op::Edit { note }),
+        2 => any::<(usize, usize)>().prop_map(|(note, revision)| Op::Delete { note, revision }),
+        1 => any::<(usize, usize)>().prop_map(|(note, revision)| Op::Restore { note, revision }),
+        3 => any::<(usize, usize, usize, bool)>()
+            .prop_map(|(note, revision, tag, add)| Op::Tag { note, revision, tag, add }),
+        1 => any::<(usize, usize)>().prop_map(|(note, yak)| Op::Move { note, yak }),
+        1 => any::<(usize, Option<usize>)>().prop_map(|(note, parent)| Op::Parent { note, parent }),
+    ];
+    prop::collection::vec(op, 0..120)
+}
+
+/// What the simulated session should add up to, kept in the plainest terms.
+#[derive(Debug, Default)]
+struct ModelNote {
+    /// Ids of the note's revisions, oldest first
+    revisions: Vec<String>,
+    yak: String,
+    deleted: bool,
+    tags: BTreeSet<String>,
+    parent: Option<usize>,
+}
+
+/// Turns ops into frames while tracking the expected outcome.
+#[derive(Debug, Default)]
+struct Simulator {
+    yaks: Vec<String>,
+    notes: Vec<ModelNote>,
+    /// yak id -> its live notes, by index, in order
+    order: HashMap<String, Vec<usize>>,
+    frames: Vec<Frame>,
+}
+
+impl Simulator {
+    fn run(ops: &[Op]) -> Self {
+        let mut simulator = Self::default();
+        simulator.append("yak.create", json!({ "name": "first" }));
+        for op in ops {
+            simulator.step(op);
+        }
+        simulator
+    }
+
+    fn append(&mut self, topic: &str, meta: Value) -> String {
+        let content = format!("{topic} {}", self.frames.len());
+        let frame = Frame {
+            id: scru128::new(),
+            context_id: ZERO_CONTEXT,
+            topic: topic.to_string(),
+            hash: Some(ssri::Integrity::from(content.as_bytes())),
+            meta: Some(meta),
+            ttl: None,
+        };
+        let id = frame.id.to_string();
+        self.frames.push(frame);
+        if topic == "yak.create" {
+            self.yaks.push(id.clone());
+            self.order.insert(id.clone(), Vec::new());
+        }
+        id
+    }
+
+    fn pick<T>(items: &[T], index: usize) -> Option<usize> {
+        (!items.is_empty()).then(|| index % items.len())
+    }
+
+    fn live(&self, index: usize) -> Option<usize> {
+        let live: Vec<usize> = (0..self.notes.len())
+            .filter(|&i| !self.notes[i].deleted)
+            .collect();
+        Self::pick(&live, index).map(|i| live[i])
+    }
+
+    fn current(&self, note: usize) -> &str {
+        self.notes[note].revisions.last().unwrap()
+    }
+
+    /// Whether `ancestor` is `note` or one of the notes it is nested under.
+    fn is_ancestor(&self, ancestor: usize, note: usize) -> bool {
+        let mut current = Some(note);
+        let mut seen = HashSet::new();
+        while let Some(note) = current {
+            if note == ancestor {
+                return true;
+            }
+            if !seen.insert(note) {
+                return false;
+            }
+            current = self.notes[note].parent;
+        }
+        false
+    }
+
+    fn step(&mut self, op: &Op) {
+        match *op {
+            Op::CreateYak => {
+                let name = format!("yak {}", self.yaks.len());
+                self.append("yak.create", json!({ "name": name }));
+            }
+            Op::Create { yak } => {
+                let yak = self.yaks[yak % self.yaks.len()].clone();
+                let id = self.append("note.create", json!({ "yak_id": yak }));
+                self.order.get_mut(&yak).unwrap().push(self.notes.len());
+                self.notes.push(ModelNote {
+                    revisions: vec![id],
+                    yak,
+                    ..Default::default()
+                });
+            }
+            Op::Edit { note } => {
+                let Some(note) = self.live(note) else { return };
+                let meta = json!({ "yak_id": self.notes[note].yak, "note_id": self.current(note) });
+                let id = self.append("note.edit", meta);
+                self.notes[note].revisions.push(id);
+            }
+            Op::Delete { note, revision } | Op::Restore { note, revision } => {
+                let Some(note) = Self::pick(&self.notes, note) else {
+                    return;
+                };
+                let revisions = &self.notes[note].revisions;
+                let note_id = revisions[revision % revisions.len()].clone();
+                let delete = matches!(op, Op::Delete { .. });
+                let topic = if delete {
+                    "note.delete"
+                } else {
+                    "note.restore"
+                };
+                self.append(topic, json!({ "note_id": note_id }));
+                let order = self.order.get_mut(&self.notes[note].yak).unwrap();
+                if delete {
+                    order.retain(|&i| i != note);
+                } else if !order.contains(&note) {
+                    order.push(note);
+                }
+                self.notes[note].deleted = delete;
+            }
+            Op::Tag {
+                note,
+                revision,
+                tag,
+                add,
+            } => {
+                let Some(note) = Self::pick(&self.notes, note) else {
+                    return;
+                };
+                let revisions = &self.notes[note].revisions;
+                let note_id = revisions[revision % revisions.len()].clone();
+                let tag = TAGS[tag % TAGS.len()];
+                let topic = if add { "tag.add" } else { "tag.remove" };
+                self.append(topic, json!({ "note_id": note_id, "tag": tag }));
+                let tags = &mut self.notes[note].tags;
+                if add {
+                    tags.insert(tag.to_string());
+                } else {
+                    tags.remove(tag);
+                }
+            }
+            Op::Move { note, yak } => {
+                let Some(note) = self.live(note) else { return };
+                let to = self.yaks[yak % self.yaks.len()].clone();
+                let meta = json!({ "note_id": self.current(note), "yak_id": to });
+                self.append("note.move", meta);
+                let from = std::mem::replace(&mut self.notes[note].yak, to.clone());
+                if from != to {
+                    self.order.get_mut(&from).unwrap().retain(|&i| i != note);
+                    self.order.get_mut(&to).unwrap().push(note);
+                }
+            }
+            Op::Parent { note, parent } => {
+                let Some(note) = Self::pick(&self.notes, note) else {
+                    return;
+                };
+                let parent = parent.map(|parent| parent % self.notes.len());
+                let parent_id = parent.map(|parent| self.current(parent).to_string());
+                let meta = json!({ "note_id": self.current(note), "parent_id": parent_id });
+                self.append("note.parent", meta);
+                // Nesting that would make a cycle is dropped
+                match parent {
+                    Some(parent) if self.is_ancestor(note, parent) => {}
+                    parent => self.notes[note].parent = parent,
+                }
+            }
+        }
+    }
+
+    /// Asserts the projection's invariants, and that it agrees with the simulation.
+    fn check(&self, projection: &Projection) {
+        // Every yak lists exactly its live notes, by current revision, in a stable order
+        for yak in &self.yaks {
+            assert!(projection.yaks.contains_key(yak));
+            let expected: Vec<&str> = self.order[yak].iter().map(|&i| self.current(i)).collect();
+            assert_eq!(projection.notes_by_yak[yak], expected, "order of yak {yak}");
+        }
+
+        // No dangling or duplicated references
+        let mut listed = HashSet::new();
+        for (yak, ids) in &projection.notes_by_yak {
+            assert!(
+                projection.yaks.contains_key(yak),
+                "notes of missing yak {yak}"
+            );
+            for id in ids {
+                let note = &projection.notes[id];
+                assert_eq!(&note.yak_id, yak);
+                assert!(
+                    !projection.replaced_by.contains_key(id),
+                    "{id} isn't current"
+                );
+                assert!(listed.insert(id.clone()), "{id} listed twice");
+            }
+        }
+        for id in projection.replaced_by.values() {
+            assert!(projection.notes.contains_key(id), "edit {id} missing");
+        }
+
+        for (i, note) in self.notes.iter().enumerate() {
+            let current = self.current(i);
+            // Any revision reaches the latest one
+            for revision in &note.revisions {
+                assert_eq!(projection.resolve(revision), current);
+            }
+            // Deleted notes are hidden but kept, so they can be restored
+            assert_eq!(listed.contains(current), !note.deleted, "deleted {current}");
+            let projected = &projection.notes[current];
+            assert_eq!(projected.yak_id, note.yak);
+            assert_eq!(projected.tags, note.tags, "tags of {current}");
+            let parent = note.parent.map(|parent| self.current(parent).to_string());
+            assert_eq!(projection.parent(current), parent, "parent of {current}");
+
+            // Nesting never loops
+            let mut seen = HashSet::from([current.to_string()]);
+            let mut ancestor = projection.parent(current);
+            while let Some(id) = ancestor {
+                assert!(seen.insert(id.clone()), "{current} is nested in itself");
+                ancestor = projection.parent(&id);
+            }
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_projection_matches_simulation(ops in ops()) {
+        let simulator = Simulator::run(&ops);
+        simulator.check(&Projection::from_frames(&simulator.frames));
+    }
+
+    /// Replaying a prefix and applying the rest as it arrives ends in the same state as a
+    /// full replay, as happens when the app starts and then follows the log.
+    #[test]
+    fn prop_replay_is_stable(ops in ops(), split in any::<usize>()) {
+        let simulator = Simulator::run(&ops);
+        let frames = &simulator.frames;
+        let full = Projection::from_frames(frames);
+        let split = split % (frames.len() + 1);
+        let mut resumed = Projection::from_frames(&frames[..split]);
+        for frame in &frames[split..] {
+            resumed.apply(frame);
+        }
+        simulator.check(&resumed);
+        prop_assert_eq!(&resumed.notes_by_yak, &full.notes_by_yak);
+        prop_assert_eq!(&resumed.replaced_by, &full.replaced_by);
+        prop_assert_eq!(&resumed.parents, &full.parents);
+        let activity = |projection: &Projection| -> Vec<String> {
+            projection.yaks.values().map(|yak| yak.last_activity.clone()).collect()
+        };
+        prop_assert_eq!(activity(&resumed), activity(&full));
+    }
+}
@@ -0,0 +1,42 @@
+use tauri::State;
+use xs::store::{Frame, Store};
+
+use crate::locks::{self, LockState};
+use crate::presentation::{self, PresentationState};
+use crate::projection::Projection;
+use crate::read_all_frames;
+
+/// Folds frames up to and including `frame_id`.
+pub(crate) fn fold_until(frames: &[Frame], frame_id: scru128::Scru128Id) -> Projection {
+    Projection::from_frames(frames.iter().take_while(|frame| frame.id <= frame_id))
+}
+
+#[tauri::command]
+pub async fn get_snapshot_at(
+    store: State<'_, Store>,
+    locks: State<'_, LockState>,
+    presentation: State<'_, PresentationState>,
+    frame_id: String,
+    yak_id: Option<String>,
+) -> Result<Projection, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let frame_id = frame_id
+        .parse::<scru128::Scru128Id>()
+        .map_err(|e| format!("Invalid frame id: {e}"))?;
+
+    let frames = read_all_frames(&store).await;
+    let mut snapshot = fold_until(&frames, frame_id).into_current();
+
+    if let Some(yak_id) = yak_id {
+        snapshot.yaks.retain(|id, _| *id == yak_id);
+        snapshot.notes.retain(|_, note| note.yak_id == yak_id);
+        snapshot.notes_by_yak.retain(|id, _| *id == yak_id);
+    }
+
+    snapshot.resolve_content(&store).await;
+    let config = locks::load_config(&store).await;
+    locks.redact(&mut snapshot, config.hide_locked);
+    presentation::mark_private(&frames, &mut snapshot);
+    presentation.redact(&mut snapshot);
+    Ok(snapshot)
+}
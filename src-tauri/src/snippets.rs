@@ -0,0 +1,340 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager, State};
+use xs::store::Store;
+
+use crate::export::note_title;
+use crate::locks::LockState;
+use crate::projection::{Note, Projection};
+use crate::search::{score, Match};
+use crate::{append_batch_to_store, read_all_frames, secrets, shutdown, windows, AppendRequest};
+
+/// How much more a hit on a declared identifier counts than one elsewhere in the code.
+const IDENTIFIER_WEIGHT: usize = 5;
+
+/// Telltale text for each language, matched against lowercased content. The language with
+/// the most hits wins; ties go to the one listed first.
+const LANGUAGES: &[(&str, &[&str])] = &[
+    (
+        "rust",
+        &[
+            "fn ", "let mut ", "impl ", "pub fn", "::", "-> ", "#[derive", "&self",
+        ],
+    ),
+    (
+        "python",
+        &[
+            "def ", "import ", "self.", "elif ", "print(", "__init__", "none",
+        ],
+    ),
+    ("go", &["func ", "package ", ":= ", "fmt.", "err != nil"]),
+    (
+        "typescript",
+        &[
+            "interface ",
+            ": string",
+            ": number",
+            "export ",
+            "=> ",
+            "const ",
+        ],
+    ),
+    (
+        "javascript",
+        &[
+            "function ",
+            "const ",
+            "=> ",
+            "console.log",
+            "require(",
+            "let ",
+        ],
+    ),
+    ("c", &["#include", "int main", "printf(", "->", "malloc("]),
+    (
+        "sql",
+        &[
+            "select ",
+            " from ",
+            " where ",
+            "insert into",
+            "create table",
+            "join ",
+        ],
+    ),
+    (
+        "shell",
+        &["echo ", "$(", "\nfi", "export ", "sudo ", "| grep"],
+    ),
+    ("html", &["<div", "<html", "</", "<span", "class=\""]),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Snippet {
+    pub note_id: String,
+    pub yak_id: String,
+    pub language: Option<String>,
+    pub title: String,
+    pub identifiers: Vec<String>,
+    pub content: String,
+}
+
+/// Guesses a snippet's language from a fence's info string, a shebang, or telltale text.
+pub(crate) fn detect_language(content: &str) -> Option<&'static str> {
+    let first = content.trim_start().lines().next().unwrap_or_default();
+    let hinted = first
+        .strip_prefix("```")
+        .or_else(|| first.strip_prefix("#!"))
+        .map(|hint| hint.trim().to_lowercase());
+    if let Some(hint) = hinted.filter(|hint| !hint.is_empty()) {
+        let hint = hint.rsplit(['/', ' ']).next().unwrap_or_default();
+        let alias = match hint {
+            "rs" => "rust",
+            "py" | "python3" => "python",
+            "ts" | "tsx" => "typescript",
+            "js" | "jsx" | "node" => "javascript",
+            "sh" | "bash" | "zsh" => "shell",
+            other => other,
+        };
+        if let Some((language, _)) = LANGUAGES.iter().find(|(name, _)| *name == alias) {
+            return Some(language);
+        }
+    }
+
+    let lower = content.to_lowercase();
+    let mut best = None;
+    let mut best_hits = 1;
+    for (language, markers) in LANGUAGES {
+        let hits: usize = markers
+            .iter()
+            .map(|marker| lower.matches(marker).count())
+            .sum();
+        if hits > best_hits {
+            best = Some(*language);
+            best_hits = hits;
+        }
+    }
+    best
+}
+
+/// Names the snippet declares: functions, types, classes and variables.
+pub(crate) fn identifiers(content: &str) -> Vec<String> {
+    static DECLARATION: OnceLock<Regex> = OnceLock::new();
+    let declaration = DECLARATION.get_or_init(|| {
+        Regex::new(
+            r"\b(?:fn|def|function|class|struct|enum|trait|type|interface|const|let|var|func)\s+(?:mut\s+)?([A-Za-z_][A-Za-z0-9_]*)",
+        )
+        .unwrap()
+    });
+    let names: BTreeSet<String> = declaration
+        .captures_iter(content)
+        .map(|captures| captures[1].to_string())
+        .collect();
+    names.into_iter().collect()
+}
+
+fn snippet_language(note: &Note) -> Option<&str> {
+    note.meta.as_ref()?.get("language")?.as_str()
+}
+
+fn to_snippet(note: &Note) -> Snippet {
+    let identifiers = note
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.get("identifiers"))
+        .and_then(|ids| ids.as_array())
+        .map(|ids| {
+            ids.iter()
+                .filter_map(|id| id.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    Snippet {
+        note_id: note.id.clone(),
+        yak_id: note.yak_id.clone(),
+        language: snippet_language(note).map(String::from),
+        title: note_title(note),
+        identifiers,
+        content: note.content.clone().unwrap_or_default(),
+    }
+}
+
+/// Snippets matching every word of `query`, optionally only in `language`. Words naming a
+/// declared identifier count for more than words found elsewhere in the code.
+fn search(projection: &Projection, language: Option<&str>, query: &str) -> Vec<Snippet> {
+    let words: Vec<String> = query
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut hits: Vec<(usize, Snippet)> = projection
+        .notes
+        .values()
+        .filter(|note| {
+            snippet_language(note).is_some_and(|lang| {
+                language.map_or(true, |language| lang.eq_ignore_ascii_case(language))
+            })
+        })
+        .filter_map(|note| {
+            let snippet = to_snippet(note);
+            if words.is_empty() {
+                return Some((0, snippet));
+            }
+            let text = format!("{}\n{}", snippet.title, snippet.content);
+            let text_score = score(&text, &words, Match::All)?;
+            let identifier_score = words
+                .iter()
+                .filter(|word| {
+                    snippet
+                        .identifiers
+                        .iter()
+                        .any(|id| id.to_lowercase().contains(word.as_str()))
+                })
+                .count();
+            Some((text_score + identifier_score * IDENTIFIER_WEIGHT, snippet))
+        })
+        .collect();
+    hits.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(b.note_id.cmp(&a.note_id)));
+    hits.into_iter().map(|(_, snippet)| snippet).collect()
+}
+
+/// The `note.create` request for a snippet, with its language (given or detected) and
+/// declared identifiers in meta.
+fn snippet_request(
+    yak_id: &str,
+    content: String,
+    language: Option<String>,
+    title: Option<String>,
+) -> AppendRequest {
+    let language = language
+        .map(|language| language.trim().to_lowercase())
+        .filter(|language| !language.is_empty())
+        .or_else(|| detect_language(&content).map(String::from));
+    let mut meta = HashMap::from([
+        ("yak_id".to_string(), yak_id.into()),
+        ("content_type".to_string(), "code".into()),
+        (
+            "language".to_string(),
+            language.unwrap_or_else(|| "text".to_string()).into(),
+        ),
+        ("identifiers".to_string(), identifiers(&content).into()),
+    ]);
+    if let Some(title) = title.filter(|title| !title.trim().is_empty()) {
+        meta.insert("title".to_string(), title.into());
+    }
+    AppendRequest {
+        topic: "note.create".to_string(),
+        content,
+        meta: Some(meta),
+    }
+}
+
+/// Saves a code snippet as a note in `yak_id`. The language is detected when not given.
+#[tauri::command]
+pub async fn add_snippet(
+    app: AppHandle,
+    store: State<'_, Store>,
+    yak_id: String,
+    content: String,
+    language: Option<String>,
+    title: Option<String>,
+) -> Result<Snippet, String> {
+    let _guard = shutdown::begin_write(&app)?;
+    if content.trim().is_empty() {
+        return Err("The snippet is empty".to_string());
+    }
+    let content = secrets::check(&app, "note.create", content);
+    let request = snippet_request(&yak_id, content.clone(), language, title);
+    let request = app.state::<LockState>().seal_request(request)?;
+    let frames = append_batch_to_store(&store, vec![request]).await?;
+    windows::emit_frames(&app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))?;
+    let mut projection = Projection::default();
+    projection.apply(&frames[0]);
+    let mut note = projection
+        .notes
+        .remove(&frames[0].id.to_string())
+        .ok_or("Failed to read back the snippet")?;
+    note.content = Some(content);
+    Ok(to_snippet(&note))
+}
+
+/// Searches snippets by identifier and content, optionally only those in `language`. An
+/// empty query lists them all, newest first.
+#[tauri::command]
+pub async fn search_snippets(
+    app: AppHandle,
+    store: State<'_, Store>,
+    language: Option<String>,
+    query: String,
+) -> Result<Vec<Snippet>, String> {
+    crate::app_lock::ensure_unlocked()?;
+    let frames = read_all_frames(&store).await;
+    let mut projection = Projection::from_frames(&frames).into_current();
+    projection.resolve_content(&store).await;
+    app.state::<LockState>().redact(&mut projection, true);
+    Ok(search(&projection, language.as_deref(), &query))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(
+            detect_language("fn main() {\n    let mut x = 1;\n}"),
+            Some("rust")
+        );
+        assert_eq!(
+            detect_language("def greet(name):\n    print(name)\n"),
+            Some("python")
+        );
+        assert_eq!(detect_language("#!/usr/bin/env bash\nls"), Some("shell"));
+        assert_eq!(
+            detect_language("SELECT id FROM notes WHERE yak_id = 1"),
+            Some("sql")
+        );
+        assert_eq!(detect_language("buy milk"), None);
+    }
+
+    #[test]
+    fn test_identifiers() {
+        assert_eq!(
+            identifiers("pub fn parse_rule(s: &str) {}\nstruct Rule;\nlet mut count = 0;"),
+            vec!["Rule", "count", "parse_rule"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_prefers_identifiers() {
+        let (_dir, store) = testing::store();
+        let mut projection = Projection::default();
+        let yak = append(&store, "yak.create", None);
+        projection.apply(&yak);
+        let yak_id = yak.id.to_string();
+        for content in [
+            "fn retry() -> u64 {\n    // calls backoff\n    backoff(3)\n}",
+            "fn backoff(attempt: u32) -> u64 {\n    2u64.pow(attempt)\n}",
+            "import math\n\ndef backoff(n):\n    return 2 ** n",
+        ] {
+            let request = snippet_request(&yak_id, content.to_string(), None, None);
+            let meta = serde_json::to_value(request.meta).unwrap();
+            let frame = append(&store, &request.topic, Some(meta));
+            projection.apply(&frame);
+            projection
+                .notes
+                .get_mut(&frame.id.to_string())
+                .unwrap()
+                .content = Some(content.to_string());
+        }
+
+        let hits = search(&projection, Some("rust"), "backoff");
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].identifiers, vec!["backoff"]);
+        assert_eq!(search(&projection, Some("Python"), "backoff").len(), 1);
+        assert_eq!(search(&projection, None, "").len(), 3);
+    }
+}
@@ -0,0 +1,338 @@
+use pulldown_cmark::{Event, Parser, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::watch;
+use xs::store::Store;
+
+use crate::projection::Projection;
+use crate::settings::{load_setting, save_setting};
+use crate::{app_lock, locks, read_all_frames};
+
+const CONFIG_TOPIC: &str = "speech.config";
+/// Sentences are spoken in runs of about this many characters, so pausing and progress work
+/// at a finer grain than the whole note.
+const MAX_CHUNK: usize = 300;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeechConfig {
+    /// A program and its arguments that speak the text written to its stdin, such as a local
+    /// model; the OS's own speech engine when unset
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Control {
+    Playing,
+    Paused,
+    Stopped,
+    Finished,
+}
+
+/// Sent as "read-aloud" as each part of a note starts, and when reading pauses or ends.
+#[derive(Debug, Clone, Serialize)]
+pub struct SpeechProgress {
+    pub frame_id: String,
+    pub state: Control,
+    /// The part being read, counting from 0
+    pub chunk: usize,
+    pub chunks: usize,
+    pub text: String,
+}
+
+struct Playback {
+    id: u64,
+    frame_id: String,
+    control: watch::Sender<Control>,
+}
+
+/// The note being read aloud, if any. Only one is read at a time.
+#[derive(Default)]
+pub struct SpeechState {
+    current: Mutex<Option<Playback>>,
+    next_id: AtomicU64,
+}
+
+/// What a note says, without its markdown.
+fn spoken_text(markdown: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(chunk) | Event::Code(chunk) => text.push_str(&chunk),
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak
+            | Event::End(
+                TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::CodeBlock,
+            ) => text.push('\n'),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// `text` split into sentences and grouped into chunks of at most `MAX_CHUNK` characters,
+/// unless a sentence is longer. Paragraphs always start a new chunk.
+fn chunks(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    for paragraph in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let mut sentences = Vec::new();
+        let mut start = 0;
+        let mut chars = paragraph.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            let ends = matches!(c, '.' | '!' | '?')
+                && chars.peek().map_or(true, |(_, next)| next.is_whitespace());
+            if ends {
+                sentences.push(paragraph[start..i + c.len_utf8()].trim());
+                start = i + c.len_utf8();
+            }
+        }
+        sentences.push(paragraph[start..].trim());
+
+        let mut current = String::new();
+        for sentence in sentences.into_iter().filter(|s| !s.is_empty()) {
+            if !current.is_empty() && current.len() + 1 + sentence.len() > MAX_CHUNK {
+                chunks.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(sentence);
+        }
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+    }
+    chunks
+}
+
+/// The command that speaks text written to its stdin.
+fn speaker(config: &SpeechConfig) -> (String, Vec<String>) {
+    if let Some(command) = config.command.as_deref() {
+        let mut words = command.split_whitespace().map(String::from);
+        if let Some(program) = words.next() {
+            return (program, words.collect());
+        }
+    }
+    if cfg!(target_os = "macos") {
+        ("say".to_string(), Vec::new())
+    } else if cfg!(target_os = "windows") {
+        let script = concat!(
+            "Add-Type -AssemblyName System.Speech; ",
+            "(New-Object System.Speech.Synthesis.SpeechSynthesizer)",
+            ".Speak([Console]::In.ReadToEnd())",
+        );
+        let args = ["-NoProfile", "-Command", script];
+        ("powershell".to_string(), args.map(String::from).to_vec())
+    } else {
+        ("espeak-ng".to_string(), vec!["--stdin".to_string()])
+    }
+}
+
+async fn speak(config: &SpeechConfig, text: &str) -> Result<tokio::process::Child, String> {
+    let (program, args) = speaker(config);
+    let mut child = tokio::process::Command::new(&program)
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to start {program}: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to send text to {program}: {e}"))?;
+    }
+    Ok(child)
+}
+
+fn emit(app: &AppHandle, progress: SpeechProgress) {
+    if let Err(e) = app.emit("read-aloud", &progress) {
+        eprintln!("Failed to emit read-aloud progress: {e}");
+    }
+}
+
+/// Reads `chunks` one after another until the end or a stop. A pause cuts the current chunk
+/// off; resuming starts it again.
+async fn play(
+    app: AppHandle,
+    id: u64,
+    frame_id: String,
+    chunks: Vec<String>,
+    config: SpeechConfig,
+    mut control: watch::Receiver<Control>,
+) {
+    let progress = |state: Control, chunk: usize| SpeechProgress {
+        frame_id: frame_id.clone(),
+        state,
+        chunk,
+        chunks: chunks.len(),
+        text: chunks.get(chunk).cloned().unwrap_or_default(),
+    };
+    let mut index = 0;
+    let end = loop {
+        if index == chunks.len() {
+            break Control::Finished;
+        }
+        let current = *control.borrow_and_update();
+        match current {
+            Control::Stopped | Control::Finished => break Control::Stopped,
+            Control::Paused => {
+                emit(&app, progress(Control::Paused, index));
+                if control.changed().await.is_err() {
+                    break Control::Stopped;
+                }
+                continue;
+            }
+            Control::Playing => {}
+        }
+        emit(&app, progress(Control::Playing, index));
+        let mut child = match speak(&config, &chunks[index]).await {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("{e}");
+                break Control::Stopped;
+            }
+        };
+        tokio::select! {
+            status = child.wait() => match status {
+                Ok(status) if status.success() => index += 1,
+                Ok(status) => {
+                    eprintln!("Speech engine exited with {status}");
+                    break Control::Stopped;
+                }
+                Err(e) => {
+                    eprintln!("Failed to wait for speech engine: {e}");
+                    break Control::Stopped;
+                }
+            },
+            _ = control.changed() => {
+                let _ = child.kill().await;
+            }
+        }
+    };
+    emit(
+        &app,
+        progress(end, index.min(chunks.len().saturating_sub(1))),
+    );
+    let state = app.state::<SpeechState>();
+    let mut current = state.current.lock().unwrap();
+    if current.as_ref().is_some_and(|playback| playback.id == id) {
+        *current = None;
+    }
+}
+
+fn set_control(state: &SpeechState, control: Control) -> Result<(), String> {
+    let current = state.current.lock().unwrap();
+    let playback = current.as_ref().ok_or("Nothing is being read aloud")?;
+    playback.control.send_replace(control);
+    Ok(())
+}
+
+/// Reads a note aloud with the OS's speech engine, or the command set with
+/// `configure_speech`, stopping whatever was being read before. Progress arrives as
+/// "read-aloud" events; `pause_reading`, `resume_reading` and `stop_reading` control it.
+#[tauri::command]
+pub async fn read_aloud(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, SpeechState>,
+    frame_id: String,
+) -> Result<usize, String> {
+    app_lock::ensure_unlocked()?;
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let note = projection
+        .notes
+        .get(&projection.resolve(&frame_id))
+        .ok_or_else(|| format!("Note {frame_id} not found"))?;
+    let content = match &note.hash {
+        Some(hash) => store
+            .cas_read(hash)
+            .await
+            .map_err(|e| format!("Failed to read note content: {e}"))?,
+        None => Vec::new(),
+    };
+    let content =
+        String::from_utf8(content).map_err(|_| "Only text notes can be read aloud".to_string())?;
+    if locks::is_sealed(&content) {
+        return Err("Unlock the yak to read its notes aloud".to_string());
+    }
+    let chunks = chunks(&spoken_text(&content));
+    if chunks.is_empty() {
+        return Err("The note has nothing to read".to_string());
+    }
+
+    let config: SpeechConfig = load_setting(&store, CONFIG_TOPIC).await;
+    let id = state.next_id.fetch_add(1, Ordering::Relaxed);
+    let (control, receiver) = watch::channel(Control::Playing);
+    let previous = state.current.lock().unwrap().replace(Playback {
+        id,
+        frame_id: frame_id.clone(),
+        control,
+    });
+    if let Some(previous) = previous {
+        previous.control.send_replace(Control::Stopped);
+    }
+    let count = chunks.len();
+    tokio::spawn(play(app, id, frame_id, chunks, config, receiver));
+    Ok(count)
+}
+
+#[tauri::command]
+pub async fn pause_reading(state: State<'_, SpeechState>) -> Result<(), String> {
+    set_control(&state, Control::Paused)
+}
+
+#[tauri::command]
+pub async fn resume_reading(state: State<'_, SpeechState>) -> Result<(), String> {
+    set_control(&state, Control::Playing)
+}
+
+#[tauri::command]
+pub async fn stop_reading(state: State<'_, SpeechState>) -> Result<(), String> {
+    set_control(&state, Control::Stopped)
+}
+
+/// The note being read aloud, if any.
+#[tauri::command]
+pub async fn get_reading(state: State<'_, SpeechState>) -> Result<Option<String>, String> {
+    let current = state.current.lock().unwrap();
+    Ok(current.as_ref().map(|playback| playback.frame_id.clone()))
+}
+
+#[tauri::command]
+pub async fn get_speech_config(store: State<'_, Store>) -> Result<SpeechConfig, String> {
+    Ok(load_setting(&store, CONFIG_TOPIC).await)
+}
+
+#[tauri::command]
+pub async fn configure_speech(store: State<'_, Store>, config: SpeechConfig) -> Result<(), String> {
+    save_setting(&store, CONFIG_TOPIC, &config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks() {
+        let text = spoken_text("# Plan\n\nShip *v2.1* today. Then rest!\n\n- `cargo test`\n");
+        assert_eq!(text, "Plan\nShip v2.1 today. Then rest!\ncargo test\n");
+        assert_eq!(
+            chunks(&text),
+            vec!["Plan", "Ship v2.1 today. Then rest!", "cargo test"]
+        );
+
+        let long = "This sentence is forty characters long. ".repeat(10);
+        let grouped = chunks(&long);
+        assert_eq!(grouped.len(), 2);
+        assert!(grouped.iter().all(|chunk| chunk.len() <= MAX_CHUNK));
+        assert_eq!(grouped.join(" "), long.trim());
+    }
+}
@@ -0,0 +1,292 @@
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use tauri::State;
+use tokio::sync::Mutex;
+use xs::store::Store;
+
+use crate::cache::CasCache;
+use crate::enrich;
+use crate::locale::{Locale, LocaleState};
+use crate::locks::LockState;
+use crate::read_all_frames;
+use crate::time::{from_id, TimeRange};
+
+/// Words written by one `note.create` or `note.edit` frame.
+#[derive(Debug, Clone)]
+struct Entry {
+    time: DateTime<Utc>,
+    yak_id: String,
+    words: usize,
+}
+
+/// What's been counted so far. Each revision's content is read once; later calls only
+/// look at frames appended since.
+#[derive(Debug, Default)]
+struct Tally {
+    seen: HashSet<String>,
+    /// note revision id -> its word count, to diff edits against
+    note_words: HashMap<String, usize>,
+    entries: Vec<Entry>,
+}
+
+#[derive(Default)]
+pub struct StatsState {
+    tally: Mutex<Tally>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DayStats {
+    pub date: NaiveDate,
+    pub words: usize,
+    pub by_yak: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WeekStats {
+    /// The week's first day, per the locale
+    pub start: NaiveDate,
+    pub words: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WritingStats {
+    pub words: usize,
+    pub by_yak: BTreeMap<String, usize>,
+    /// Days with any writing, oldest first
+    pub days: Vec<DayStats>,
+    /// Weeks with any writing, oldest first
+    pub weeks: Vec<WeekStats>,
+    /// Consecutive days written up to today, or up to yesterday if nothing's written yet today
+    pub current_streak: usize,
+    pub longest_streak: usize,
+}
+
+/// Whitespace-separated words with at least one letter or digit, so markdown markers like
+/// `-` or `##` don't count.
+pub(crate) fn count_words(content: &str) -> usize {
+    content
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+impl Tally {
+    async fn update(&mut self, store: &Store, cache: &CasCache, locks: &LockState) {
+        for frame in read_all_frames(store).await {
+            if frame.topic != "note.create" && frame.topic != "note.edit" {
+                continue;
+            }
+            let id = frame.id.to_string();
+            if !self.seen.insert(id.clone()) {
+                continue;
+            }
+            let meta = frame.meta.as_ref();
+            let field = |key: &str| meta.and_then(|meta| meta.get(key)).and_then(|v| v.as_str());
+            let Some(yak_id) = field("yak_id").map(String::from) else {
+                continue;
+            };
+            let stored = meta
+                .and_then(|meta| meta.get(enrich::WORD_COUNT))
+                .and_then(|words| words.as_u64());
+            let words = match (stored, &frame.hash) {
+                (Some(words), _) => words as usize,
+                (None, Some(hash)) => match cache.read(store, &hash.to_string()).await {
+                    // Sealed revisions of locked yaks count as empty
+                    Ok(content) => locks.reveal(&content).map_or(0, |c| count_words(&c)),
+                    Err(e) => {
+                        eprintln!("Failed to read content for {id}: {e}");
+                        0
+                    }
+                },
+                (None, None) => 0,
+            };
+            let previous = match frame.topic.as_str() {
+                "note.edit" => field("note_id")
+                    .and_then(|note_id| self.note_words.get(note_id))
+                    .copied()
+                    .unwrap_or(0),
+                _ => 0,
+            };
+            let time = from_id(&id);
+            self.note_words.insert(id, words);
+            // Deleting text isn't negative writing
+            let written = words.saturating_sub(previous);
+            if let Some(time) = time.filter(|_| written > 0) {
+                self.entries.push(Entry {
+                    time,
+                    yak_id,
+                    words: written,
+                });
+            }
+        }
+    }
+
+    fn stats(&self, range: &TimeRange, today: NaiveDate, locale: &Locale) -> WritingStats {
+        let mut by_day: BTreeMap<NaiveDate, BTreeMap<String, usize>> = BTreeMap::new();
+        for entry in self
+            .entries
+            .iter()
+            .filter(|entry| range.contains(entry.time))
+        {
+            let date = entry.time.with_timezone(&Local).date_naive();
+            *by_day
+                .entry(date)
+                .or_default()
+                .entry(entry.yak_id.clone())
+                .or_default() += entry.words;
+        }
+
+        let mut by_yak = BTreeMap::new();
+        let mut days = Vec::new();
+        let mut weeks: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+        let mut longest_streak = 0;
+        let mut streak = 0;
+        let mut previous: Option<NaiveDate> = None;
+        for (date, yaks) in by_day {
+            for (yak_id, words) in &yaks {
+                *by_yak.entry(yak_id.clone()).or_default() += words;
+            }
+            streak = match previous {
+                Some(previous) if previous.succ_opt() == Some(date) => streak + 1,
+                _ => 1,
+            };
+            longest_streak = longest_streak.max(streak);
+            previous = Some(date);
+            let words = yaks.values().sum();
+            *weeks.entry(locale.week_of(date)).or_default() += words;
+            days.push(DayStats {
+                date,
+                words,
+                by_yak: yaks,
+            });
+        }
+        let current_streak = match previous {
+            Some(last) if last == today || last.succ_opt() == Some(today) => streak,
+            _ => 0,
+        };
+
+        WritingStats {
+            words: by_yak.values().sum(),
+            by_yak,
+            days,
+            weeks: weeks
+                .into_iter()
+                .map(|(start, words)| WeekStats { start, words })
+                .collect(),
+            current_streak,
+            longest_streak,
+        }
+    }
+}
+
+/// Words written per day, per week and per yak within `range`, with writing streaks. An edit
+/// counts the words it added over the revision it replaced. Weeks start on the locale's first
+/// day of the week.
+#[tauri::command]
+pub async fn get_writing_stats(
+    store: State<'_, Store>,
+    cache: State<'_, Arc<CasCache>>,
+    locks: State<'_, LockState>,
+    state: State<'_, StatsState>,
+    locale: State<'_, LocaleState>,
+    range: Option<TimeRange>,
+) -> Result<WritingStats, String> {
+    let mut tally = state.tally.lock().await;
+    tally.update(&store, &cache, &locks).await;
+    let today = Local::now().date_naive();
+    Ok(tally.stats(&range.unwrap_or_default(), today, &locale.get()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_count_words() {
+        assert_eq!(count_words("## Plan\n- buy milk, eggs\n- call Bo"), 6);
+        assert_eq!(count_words("  "), 0);
+    }
+
+    #[test]
+    fn test_streaks() {
+        let date = |s: &str| s.parse::<NaiveDate>().unwrap();
+        let entry = |day: &str, words| Entry {
+            // Midday UTC falls on the same local date in all but the farthest timezones
+            time: format!("{day}T12:00:00Z").parse().unwrap(),
+            yak_id: "yak".to_string(),
+            words,
+        };
+        let tally = Tally {
+            entries: vec![
+                entry("2025-03-01", 10),
+                entry("2025-03-02", 5),
+                entry("2025-03-03", 5),
+                entry("2025-03-05", 7),
+                entry("2025-03-06", 3),
+            ],
+            ..Default::default()
+        };
+        let locale = Locale {
+            tag: "de-DE".to_string(),
+            week_start: chrono::Weekday::Mon,
+        };
+        let stats = tally.stats(&TimeRange::default(), date("2025-03-07"), &locale);
+        assert_eq!(stats.words, 30);
+        assert_eq!(stats.days.len(), 5);
+        let weeks: Vec<_> = stats.weeks.iter().map(|w| (w.start, w.words)).collect();
+        assert_eq!(
+            weeks,
+            vec![(date("2025-02-24"), 15), (date("2025-03-03"), 15)]
+        );
+        assert_eq!(stats.longest_streak, 3);
+        assert_eq!(stats.current_streak, 2);
+
+        let stats = tally.stats(&TimeRange::default(), date("2025-03-08"), &locale);
+        assert_eq!(stats.current_streak, 0);
+    }
+
+    #[tokio::test]
+    async fn test_edits_count_added_words() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let cache = CasCache::new(1024 * 1024);
+        let locks = LockState::default();
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let note = append_frame(
+            &store,
+            "note.create",
+            Some(b"one two three".as_slice()),
+            Some(json!({ "yak_id": yak_id })),
+        )
+        .await
+        .unwrap();
+
+        let mut tally = Tally::default();
+        tally.update(&store, &cache, &locks).await;
+        let today = Local::now().date_naive();
+        let locale = Locale::default();
+        assert_eq!(tally.stats(&TimeRange::default(), today, &locale).words, 3);
+
+        append_frame(
+            &store,
+            "note.edit",
+            Some(b"one two three four five".as_slice()),
+            Some(json!({ "yak_id": yak_id, "note_id": note.id.to_string() })),
+        )
+        .await
+        .unwrap();
+        tally.update(&store, &cache, &locks).await;
+        let stats = tally.stats(&TimeRange::default(), today, &locale);
+        assert_eq!(stats.words, 5);
+        assert_eq!(stats.by_yak[&yak_id], 5);
+        assert_eq!(stats.current_streak, 1);
+    }
+}
@@ -0,0 +1,139 @@
+use fs4::fs_std::FileExt;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::State;
+
+/// Held exclusively by the Yaks process writing to a store, with its pid inside.
+const LOCK_FILE: &str = "yaks.lock";
+/// The socket `xs serve` listens on in the store directory while it's running.
+const XS_SOCKET: &str = "sock";
+
+/// Checked on every append; `append_frame` has no app handle to reach managed state with.
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StoreLockStatus {
+    pub read_only: bool,
+    /// Who else has the store open for writing, when read-only
+    pub holder: Option<String>,
+    pub lock_path: PathBuf,
+}
+
+/// The store's write lock, kept open for as long as the app runs.
+#[derive(Default)]
+pub struct StoreLockState {
+    status: Mutex<StoreLockStatus>,
+    file: Mutex<Option<File>>,
+}
+
+/// Fails with a clear message while the store is open read-only.
+pub(crate) fn ensure_writable() -> Result<(), String> {
+    if READ_ONLY.load(Ordering::SeqCst) {
+        return Err("The store is open read-only while another program writes to it".to_string());
+    }
+    Ok(())
+}
+
+/// Whether another process holds the store, so frames can land in it without this one
+/// appending them.
+pub(crate) fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::SeqCst)
+}
+
+/// Whether `xs serve` is listening on the store, rather than having left a stale socket.
+fn xs_serving(store_path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        std::os::unix::net::UnixStream::connect(store_path.join(XS_SOCKET)).is_ok()
+    }
+    #[cfg(not(unix))]
+    {
+        store_path.join(XS_SOCKET).exists()
+    }
+}
+
+/// Takes the store's write lock. Returns the open lock file, or who holds it instead.
+pub(crate) fn acquire(store_path: &Path) -> Result<File, String> {
+    if xs_serving(store_path) {
+        return Err("xs serve".to_string());
+    }
+    let path = store_path.join(LOCK_FILE);
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(|e| format!("unknown ({e})"))?;
+    if file.try_lock_exclusive().is_err() {
+        let mut pid = String::new();
+        let _ = file.read_to_string(&mut pid);
+        return Err(match pid.trim() {
+            "" => "another Yaks instance".to_string(),
+            pid => format!("another Yaks instance (pid {pid})"),
+        });
+    }
+    let _ = file.set_len(0);
+    let _ = file.rewind();
+    let _ = write!(file, "{}", std::process::id());
+    let _ = file.flush();
+    Ok(file)
+}
+
+/// Takes the write lock on the store at `store_path`, or, if the `xs` CLI or another Yaks
+/// has the store open for writing, puts the app in read-only mode: appends fail with a
+/// clear error, and the status says who holds the lock. Returns whether the app can write.
+pub(crate) fn initialize(state: &StoreLockState, store_path: &Path) -> bool {
+    let lock_path = store_path.join(LOCK_FILE);
+    let status = match acquire(store_path) {
+        Ok(file) => {
+            *state.file.lock().unwrap() = Some(file);
+            StoreLockStatus {
+                read_only: false,
+                holder: None,
+                lock_path,
+            }
+        }
+        Err(holder) => {
+            eprintln!("Store is in use by {holder}; opening read-only");
+            StoreLockStatus {
+                read_only: true,
+                holder: Some(holder),
+                lock_path,
+            }
+        }
+    };
+    READ_ONLY.store(status.read_only, Ordering::SeqCst);
+    let writable = !status.read_only;
+    *state.status.lock().unwrap() = status;
+    writable
+}
+
+/// Whether the app is writing to the store or only reading it, and if so, why.
+#[tauri::command]
+pub fn get_store_lock_status(state: State<'_, StoreLockState>) -> StoreLockStatus {
+    state.status.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_acquire() {
+        let dir = tempdir().unwrap();
+        let held = acquire(dir.path()).unwrap();
+        let holder = acquire(dir.path()).unwrap_err();
+        assert_eq!(
+            holder,
+            format!("another Yaks instance (pid {})", std::process::id())
+        );
+        drop(held);
+        assert!(acquire(dir.path()).is_ok());
+    }
+}
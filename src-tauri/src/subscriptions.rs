@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, EventTarget, State};
+use tokio::task::JoinHandle;
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::projection::Projection;
+use crate::windows::frame_yak_id;
+
+/// What a subscription wants to hear about. Empty lists match everything; a frame must
+/// pass every filter that is set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameFilter {
+    /// Exact topics, or prefixes ending in `*` such as `note.*`
+    #[serde(default)]
+    pub topics: Vec<String>,
+    pub yak_id: Option<String>,
+    /// Frames touching notes carrying any of these tags, including the tag changes themselves
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Also deliver matching history before following new frames
+    #[serde(default)]
+    pub replay: bool,
+}
+
+impl FrameFilter {
+    fn topic_matches(&self, topic: &str) -> bool {
+        self.topics.is_empty()
+            || self
+                .topics
+                .iter()
+                .any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => topic.starts_with(prefix),
+                    None => topic == pattern,
+                })
+    }
+
+    /// `note_tags` are the tags of the note the frame touches, before and after applying it.
+    pub(crate) fn matches(&self, frame: &Frame, note_tags: &BTreeSet<String>) -> bool {
+        self.topic_matches(&frame.topic)
+            && self
+                .yak_id
+                .as_ref()
+                .map_or(true, |yak_id| frame_yak_id(frame).as_ref() == Some(yak_id))
+            && (self.tags.is_empty() || self.tags.iter().any(|tag| note_tags.contains(tag)))
+    }
+}
+
+/// The tags of the note `frame` touches, according to `projection`.
+pub(crate) fn note_tags(projection: &Projection, frame: &Frame) -> BTreeSet<String> {
+    let note_id = frame
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.get("note_id"))
+        .and_then(|note_id| note_id.as_str());
+    [Some(frame.id.to_string().as_str()), note_id]
+        .into_iter()
+        .flatten()
+        .filter_map(|id| projection.notes.get(id))
+        .flat_map(|note| note.tags.iter().cloned())
+        .collect()
+}
+
+#[derive(Default)]
+pub struct SubscriptionState {
+    tasks: Mutex<HashMap<String, JoinHandle<()>>>,
+}
+
+/// Event channel a subscription's frames are emitted on.
+fn channel(id: &str) -> String {
+    format!("subscription:{id}")
+}
+
+/// Starts streaming frames matching `filter` to the calling window on the
+/// `subscription:<id>` event, returning the id. `xs.threshold` is always delivered so the
+/// window can tell when replayed history ends.
+#[tauri::command]
+pub async fn subscribe(
+    store: State<'_, Store>,
+    state: State<'_, SubscriptionState>,
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    filter: FrameFilter,
+) -> Result<String, String> {
+    let id = scru128::new().to_string();
+    let event = channel(&id);
+    let label = window.label().to_string();
+
+    let options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(options).await;
+    let task = tokio::spawn(async move {
+        // Tag filters need the notes' tags, so history is always read, if not delivered
+        let mut projection = Projection::default();
+        let mut live = false;
+        while let Some(frame) = rx.recv().await {
+            let threshold = frame.topic == "xs.threshold";
+            let mut tags = BTreeSet::new();
+            if !filter.tags.is_empty() {
+                tags = note_tags(&projection, &frame);
+                projection.apply(&frame);
+                tags.extend(note_tags(&projection, &frame));
+            }
+            let deliver = threshold || ((live || filter.replay) && filter.matches(&frame, &tags));
+            if threshold {
+                live = true;
+            }
+            if deliver {
+                crate::metrics::record_ipc_events(1);
+                let target = EventTarget::webview_window(label.clone());
+                if let Err(e) = app.emit_to(target, &event, &frame) {
+                    eprintln!("Failed to emit to {event}: {e}");
+                    break;
+                }
+            }
+        }
+    });
+
+    state.tasks.lock().unwrap().insert(id.clone(), task);
+    Ok(id)
+}
+
+/// Stops a subscription started by `subscribe`.
+#[tauri::command]
+pub fn unsubscribe(state: State<'_, SubscriptionState>, id: String) -> Result<(), String> {
+    let task = state
+        .tasks
+        .lock()
+        .unwrap()
+        .remove(&id)
+        .ok_or_else(|| format!("Unknown subscription {id}"))?;
+    task.abort();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_all_frames;
+    use crate::testing::{self, append};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_topic_and_yak_filters() {
+        let (_dir, store) = testing::store();
+        let filter = FrameFilter {
+            topics: vec!["note.*".to_string(), "tag.add".to_string()],
+            yak_id: Some("yak-a".to_string()),
+            ..Default::default()
+        };
+        let none = BTreeSet::new();
+        let in_yak = Some(json!({ "yak_id": "yak-a" }));
+        let other_yak = Some(json!({ "yak_id": "yak-b" }));
+        let matches = |topic: &str, meta: Option<serde_json::Value>| {
+            filter.matches(&append(&store, topic, meta), &none)
+        };
+
+        assert!(matches("note.create", in_yak.clone()));
+        assert!(matches("tag.add", in_yak.clone()));
+        assert!(!matches("tag.remove", in_yak));
+        assert!(!matches("note.edit", other_yak));
+        assert!(!matches("note.create", None));
+    }
+
+    #[tokio::test]
+    async fn test_tag_filter_follows_notes() {
+        let (_dir, store) = testing::store();
+        let filter = FrameFilter {
+            tags: vec!["work".to_string()],
+            ..Default::default()
+        };
+        let yak = append(&store, "yak.create", None);
+        let yak_id = yak.id.to_string();
+        let note = append(&store, "note.create", Some(json!({ "yak_id": yak_id })));
+        let note_id = note.id.to_string();
+        let tag = json!({ "yak_id": yak_id, "note_id": note_id, "tag": "work" });
+        append(&store, "tag.add", Some(tag.clone()));
+        let edit = json!({ "yak_id": yak_id, "note_id": note_id });
+        append(&store, "note.edit", Some(edit));
+        append(&store, "tag.remove", Some(tag));
+
+        let mut projection = Projection::default();
+        let mut matched = Vec::new();
+        for frame in read_all_frames(&store).await {
+            let mut tags = note_tags(&projection, &frame);
+            projection.apply(&frame);
+            tags.extend(note_tags(&projection, &frame));
+            if filter.matches(&frame, &tags) {
+                matched.push(frame.topic);
+            }
+        }
+        assert_eq!(matched, ["tag.add", "note.edit", "tag.remove"]);
+    }
+}
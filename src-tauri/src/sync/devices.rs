@@ -0,0 +1,395 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use super::is_new_change;
+use crate::settings::{latest_setting, save_setting};
+use crate::{append_frame, provenance, read_all_frames, time};
+
+/// This device's signing key; local, like every `sync.` topic.
+const KEY_TOPIC: &str = "sync.device";
+/// Synced, so every device stops taking the revoked device's writes.
+const REVOKE_TOPIC: &str = "device.revoke";
+/// Synced: a device whose writes are taken in. Once any device is trusted, writes signed by
+/// one that isn't are refused.
+const TRUST_TOPIC: &str = "device.trust";
+/// Meta keys on outbound copies: the signing device's public key, and its signature.
+const DEVICE_KEY: &str = "sync_device";
+const SIGNATURE_KEY: &str = "sync_signature";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DeviceKeyConfig {
+    secret_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Device {
+    /// The device's public key
+    pub id: String,
+    pub this_device: bool,
+    /// Frames it wrote that are in this store
+    pub frames: usize,
+    pub last_seen: Option<String>,
+    pub revoked_at: Option<String>,
+    pub trusted: bool,
+}
+
+/// Whose signed writes are taken in, from the trust and revocation frames in a store.
+#[derive(Debug, Default)]
+pub(crate) struct Trust {
+    this_device: Option<String>,
+    /// Devices trusted; `None` until the first is, when any validly signed write is taken in
+    known: Option<HashSet<String>>,
+    /// Revoked devices, with the time (unix ms) each was revoked
+    revoked: HashMap<String, u64>,
+}
+
+impl Trust {
+    pub(crate) fn new(frames: &[Frame]) -> Self {
+        let config: DeviceKeyConfig = latest_setting(frames, KEY_TOPIC);
+        let this_device = config
+            .secret_key
+            .and_then(|secret| decode(&secret, "device key").ok())
+            .map(|secret| device_id(&SigningKey::from_bytes(&secret)));
+        let mut trust = Self {
+            this_device,
+            ..Default::default()
+        };
+        for frame in frames {
+            trust.apply(frame);
+        }
+        trust
+    }
+
+    /// Takes in a trust or revocation, e.g. one just synced.
+    pub(crate) fn apply(&mut self, frame: &Frame) {
+        let Some(device) = meta_str(frame, "device") else {
+            return;
+        };
+        match frame.topic.as_str() {
+            TRUST_TOPIC => {
+                self.known
+                    .get_or_insert_with(HashSet::new)
+                    .insert(device.to_string());
+            }
+            REVOKE_TOPIC => {
+                let at = frame.meta.as_ref().and_then(|meta| meta.get("revoked_at"));
+                if let Some(at) = at.and_then(|at| at.as_u64()) {
+                    self.revoked.insert(device.to_string(), at);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn trusted(&self, device: &str) -> bool {
+        self.known
+            .as_ref()
+            .is_some_and(|known| known.contains(device))
+    }
+
+    /// Whether a device's writes may be taken in, revocations aside.
+    fn knows(&self, device: &str) -> bool {
+        self.known.is_none() || self.trusted(device) || self.this_device.as_deref() == Some(device)
+    }
+}
+
+fn encode(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn decode<const N: usize>(text: &str, what: &str) -> Result<[u8; N], String> {
+    URL_SAFE_NO_PAD
+        .decode(text)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("Invalid {what}"))
+}
+
+pub(crate) fn device_id(key: &SigningKey) -> String {
+    encode(key.verifying_key().as_bytes())
+}
+
+/// This device's key, made and saved the first time it's needed.
+pub(crate) fn device_key(store: &Store, frames: &[Frame]) -> Result<SigningKey, String> {
+    let config: DeviceKeyConfig = latest_setting(frames, KEY_TOPIC);
+    if let Some(secret) = &config.secret_key {
+        return Ok(SigningKey::from_bytes(&decode(secret, "device key")?));
+    }
+    let mut secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    let config = DeviceKeyConfig {
+        secret_key: Some(encode(&secret)),
+    };
+    save_setting(store, KEY_TOPIC, &config)?;
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+/// Forgets this device's key in a forked store; the fork makes its own when it needs one.
+pub(super) fn detach(store: &Store, frames: &[Frame]) -> Result<(), String> {
+    if frames.iter().any(|frame| frame.topic == KEY_TOPIC) {
+        save_setting(store, KEY_TOPIC, &DeviceKeyConfig::default())?;
+    }
+    Ok(())
+}
+
+/// JSON with object keys sorted, so both sides serialize the same meta the same way.
+fn canonical(value: &Value) -> String {
+    match value {
+        Value::Object(fields) => {
+            let mut keys: Vec<&String> = fields.keys().collect();
+            keys.sort();
+            let fields: Vec<String> = keys
+                .into_iter()
+                .map(|key| format!("{}:{}", Value::from(key.as_str()), canonical(&fields[key])))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical).collect();
+            format!("[{}]", items.join(","))
+        }
+        value => value.to_string(),
+    }
+}
+
+/// What's signed: the topic, content hash and meta, less the signature itself and the
+/// provenance stamp a receiving peer may add.
+fn payload(topic: &str, hash: Option<&ssri::Integrity>, meta: &Value) -> Vec<u8> {
+    let mut meta = meta.clone();
+    if let Some(fields) = meta.as_object_mut() {
+        fields.remove(SIGNATURE_KEY);
+        fields.remove(provenance::KEY);
+    }
+    let hash = hash.map(|hash| hash.to_string());
+    canonical(&json!({ "topic": topic, "hash": hash, "meta": meta })).into_bytes()
+}
+
+/// Signs the meta of an outbound copy, adding this device's id and the signature.
+pub(crate) fn sign(
+    key: &SigningKey,
+    topic: &str,
+    hash: Option<&ssri::Integrity>,
+    meta: Value,
+) -> Value {
+    let Value::Object(mut fields) = meta else {
+        return meta;
+    };
+    fields.insert(DEVICE_KEY.to_string(), device_id(key).into());
+    let signature = key.sign(&payload(topic, hash, &Value::Object(fields.clone())));
+    fields.insert(
+        SIGNATURE_KEY.to_string(),
+        encode(&signature.to_bytes()).into(),
+    );
+    Value::Object(fields)
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+/// Checks a frame from the other side of a sync: a signature must be valid and, once any
+/// device is trusted, made by a trusted device, and a revoked device's writes from after its
+/// revocation are refused. Once any device is revoked, `strict` also refuses unsigned writes
+/// from after that, which could otherwise come from the revoked device with its signature
+/// stripped.
+pub(crate) fn verify(frame: &Frame, trust: &Trust, strict: bool) -> Result<(), String> {
+    let revoked = &trust.revoked;
+    let written = frame.id.timestamp();
+    let (Some(device), Some(signature), Some(meta)) = (
+        meta_str(frame, DEVICE_KEY),
+        meta_str(frame, SIGNATURE_KEY),
+        frame.meta.as_ref(),
+    ) else {
+        let enforced = revoked.values().min().is_some_and(|first| written > *first);
+        if strict && enforced {
+            return Err("unsigned, and a device has been revoked".to_string());
+        }
+        return Ok(());
+    };
+    let key = VerifyingKey::from_bytes(&decode(device, "device id")?)
+        .map_err(|e| format!("Invalid device id: {e}"))?;
+    let signature = Signature::from_bytes(&decode(signature, "signature")?);
+    key.verify(
+        &payload(&frame.topic, frame.hash.as_ref(), meta),
+        &signature,
+    )
+    .map_err(|_| "signature doesn't match".to_string())?;
+    if revoked.get(device).is_some_and(|at| written > *at) {
+        return Err(format!("written by revoked device {device}"));
+    }
+    // A revoked device can make itself a new key, but no trusted device vouches for it
+    if !trust.knows(device) {
+        return Err(format!("written by unknown device {device}"));
+    }
+    Ok(())
+}
+
+fn rfc3339(ms: u64) -> Option<String> {
+    chrono::DateTime::from_timestamp_millis(ms as i64).map(|at| at.to_rfc3339())
+}
+
+fn devices(frames: &[Frame], this_device: &str) -> Vec<Device> {
+    let trust = Trust::new(frames);
+    let mut seen: BTreeMap<String, (usize, Option<String>)> = BTreeMap::new();
+    for frame in frames {
+        let device = match meta_str(frame, DEVICE_KEY) {
+            Some(device) => device,
+            None if is_new_change(frame) => this_device,
+            None => continue,
+        };
+        let entry = seen.entry(device.to_string()).or_default();
+        entry.0 += 1;
+        entry.1 = time::from_id(&frame.id.to_string()).map(|at| at.to_rfc3339());
+    }
+    seen.entry(this_device.to_string()).or_default();
+    seen.into_iter()
+        .map(|(id, (frames, last_seen))| Device {
+            this_device: id == this_device,
+            revoked_at: trust.revoked.get(&id).copied().and_then(rfc3339),
+            trusted: trust.trusted(&id),
+            id,
+            frames,
+            last_seen,
+        })
+        .collect()
+}
+
+/// The devices whose writes this store holds, this one included, as identified by the keys
+/// they sign synced frames with.
+#[tauri::command]
+pub async fn list_devices(store: State<'_, Store>) -> Result<Vec<Device>, String> {
+    let frames = read_all_frames(&store).await;
+    let this_device = device_id(&device_key(&store, &frames)?);
+    Ok(devices(&frames, &this_device))
+}
+
+/// Trusts `devices` and this device, writing a trust for each not trusted yet.
+pub(crate) async fn trust_devices(
+    store: &Store,
+    frames: &[Frame],
+    devices: impl IntoIterator<Item = String>,
+) -> Result<(), String> {
+    let this_device = device_id(&device_key(store, frames)?);
+    let mut trust = Trust::new(frames);
+    for device in std::iter::once(this_device).chain(devices) {
+        decode::<32>(&device, "device id")?;
+        if trust.trusted(&device) {
+            continue;
+        }
+        let meta = json!({ "device": device });
+        let frame = append_frame(store, TRUST_TOPIC, None, Some(meta)).await?;
+        trust.apply(&frame);
+    }
+    Ok(())
+}
+
+/// Trusts a device, e.g. a new laptop. Once any device is trusted, writes signed by a device
+/// that isn't are refused.
+#[tauri::command]
+pub async fn trust_device(
+    store: State<'_, Store>,
+    app: AppHandle,
+    device_id: String,
+) -> Result<(), String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let frames = read_all_frames(&store).await;
+    trust_devices(&store, &frames, [device_id]).await
+}
+
+/// Revokes a device, e.g. a lost laptop: whatever it syncs from now on is refused here, and
+/// on every other device once the revocation reaches it. If no device was trusted yet, the
+/// others seen so far are, so the revoked device can't come back with a new key.
+#[tauri::command]
+pub async fn revoke_device(
+    store: State<'_, Store>,
+    app: AppHandle,
+    device_id: String,
+) -> Result<(), String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let frames = read_all_frames(&store).await;
+    let this_device = self::device_id(&device_key(&store, &frames)?);
+    if device_id == this_device {
+        return Err("This device can't revoke itself".to_string());
+    }
+    decode::<32>(&device_id, "device id")?;
+    if Trust::new(&frames).known.is_none() {
+        let others = devices(&frames, &this_device)
+            .into_iter()
+            .filter(|device| device.id != device_id && device.revoked_at.is_none())
+            .map(|device| device.id);
+        trust_devices(&store, &frames, others).await?;
+    }
+    let revoked_at = chrono::Utc::now().timestamp_millis();
+    let meta = json!({ "device": device_id, "revoked_at": revoked_at });
+    append_frame(&store, REVOKE_TOPIC, None, Some(meta)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+
+    #[tokio::test]
+    async fn test_verify() {
+        let (_dir, store) = testing::store();
+        let frame = |meta: Value| append(&store, "note.create", Some(meta));
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let meta = sign(
+            &key,
+            "note.create",
+            None,
+            json!({ "yak_id": "y1", "sync_origin": "n1" }),
+        );
+        let signed = frame(meta.clone());
+        let none = Trust::default();
+        assert!(verify(&signed, &none, true).is_ok());
+
+        let mut tampered = meta.clone();
+        tampered["yak_id"] = "y2".into();
+        assert!(verify(&frame(tampered), &none, true).is_err());
+
+        let device = device_id(&key);
+        let revoked = |at| Trust {
+            revoked: HashMap::from([(device.clone(), at)]),
+            ..Default::default()
+        };
+        let before = revoked(signed.id.timestamp() - 1);
+        assert!(verify(&signed, &before, true)
+            .unwrap_err()
+            .contains("revoked"));
+        let after = revoked(signed.id.timestamp() + 1);
+        assert!(verify(&signed, &after, true).is_ok());
+
+        // Once a device is trusted, a key no trusted device vouched for is refused
+        let mut trust = Trust::default();
+        let other = device_id(&SigningKey::from_bytes(&[8; 32]));
+        trust.apply(&append(
+            &store,
+            TRUST_TOPIC,
+            Some(json!({ "device": other })),
+        ));
+        assert!(verify(&signed, &trust, true)
+            .unwrap_err()
+            .contains("unknown"));
+        trust.apply(&append(
+            &store,
+            TRUST_TOPIC,
+            Some(json!({ "device": device })),
+        ));
+        assert!(verify(&signed, &trust, true).is_ok());
+
+        // Unsigned writes pass until a device is revoked, unless not strict
+        let unsigned = frame(json!({ "yak_id": "y1" }));
+        assert!(verify(&unsigned, &none, true).is_ok());
+        assert!(verify(&unsigned, &before, true).is_err());
+        assert!(verify(&unsigned, &before, false).is_ok());
+    }
+}
@@ -0,0 +1,616 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use xs::store::{Frame, Store, ZERO_CONTEXT};
+
+use crate::provenance::{self, Source};
+use crate::settings::{latest_setting, save_setting};
+use crate::{append_to_store, read_all_frames};
+use crate::{presence, schema};
+
+mod devices;
+mod p2p;
+mod s3;
+mod xs_remote;
+
+pub use devices::{list_devices, revoke_device, trust_device};
+pub use p2p::{enable_p2p, p2p_ticket, pair_peer, sync_peers_now, unpair_peer, P2pState};
+pub use s3::{configure_s3_sync, s3_restore, s3_sync_now, S3State};
+
+const CONFIG_TOPIC: &str = "sync.config";
+const YAK_SYNC_TOPIC: &str = "sync.yak";
+
+/// Meta key recording the id a frame has on the other side of a sync.
+pub(crate) const ORIGIN_KEY: &str = "sync_origin";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncConfig {
+    pub enabled: bool,
+    /// Base url of a remote `xs serve` instance, e.g. `http://127.0.0.1:3021`
+    pub url: Option<String>,
+    /// Keep following the remote stream and sync whenever either side changes
+    #[serde(default)]
+    pub follow: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncStatus {
+    pub enabled: bool,
+    pub url: Option<String>,
+    pub following: bool,
+    pub running: bool,
+    pub last_sync: Option<String>,
+    pub pushed: usize,
+    pub pulled: usize,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct SyncState {
+    config: Mutex<SyncConfig>,
+    status: Mutex<SyncStatus>,
+    follower: Mutex<Option<JoinHandle<()>>>,
+    /// Where the last sync with the configured remote left off
+    cursor: Mutex<Cursor>,
+}
+
+/// Topics that never leave the device: store internals, sync bookkeeping, and settings that
+/// only make sense on the machine they were made on.
+const LOCAL_TOPIC_PREFIXES: &[&str] = &[
+    "xs.",
+    "sync.",
+    "capture.",
+    "plugin.",
+    "mcp.",
+    "ai.",
+    "extract.",
+    "publish.",
+    "draft.",
+    "store.",
+    "schema.",
+    "recovery.",
+    "compaction.",
+    "retention.",
+    "shortcut.",
+    "speech.",
+    "autostart.",
+    "lock.",
+    "webhook.",
+    "integration.",
+    "integrations.",
+    "usage.",
+    "disk.",
+    "handler.",
+];
+
+/// Whether a frame takes part in sync at all.
+pub(crate) fn is_syncable(frame: &Frame) -> bool {
+    !LOCAL_TOPIC_PREFIXES
+        .iter()
+        .any(|prefix| frame.topic.starts_with(prefix))
+}
+
+/// Ids of frames belonging to yaks marked local-only with `set_yak_sync`: the yaks themselves
+/// plus every frame that references them (or references such a frame) through its meta.
+pub(crate) fn local_only_ids(frames: &[Frame]) -> HashSet<String> {
+    let mut yaks: HashMap<String, bool> = HashMap::new();
+    for frame in frames.iter().filter(|frame| frame.topic == YAK_SYNC_TOPIC) {
+        let Some(meta) = frame.meta.as_ref() else {
+            continue;
+        };
+        if let (Some(yak_id), Some(enabled)) = (
+            meta.get("yak_id").and_then(|v| v.as_str()),
+            meta.get("enabled").and_then(|v| v.as_bool()),
+        ) {
+            yaks.insert(yak_id.to_string(), enabled);
+        }
+    }
+
+    let mut ids: HashSet<String> = yaks
+        .into_iter()
+        .filter(|(_, enabled)| !enabled)
+        .map(|(yak_id, _)| yak_id)
+        .collect();
+    if ids.is_empty() {
+        return ids;
+    }
+    for frame in frames {
+        let Some(meta) = frame.meta.as_ref() else {
+            continue;
+        };
+        let private = ["yak_id", "note_id"]
+            .iter()
+            .filter_map(|key| meta.get(*key).and_then(|v| v.as_str()))
+            .any(|id| ids.contains(id));
+        if private {
+            ids.insert(frame.id.to_string());
+        }
+    }
+    ids
+}
+
+/// Whether a local frame may leave this device.
+pub(crate) fn is_outbound(frame: &Frame, local_only: &HashSet<String>) -> bool {
+    is_syncable(frame) && !presence::is_stale(frame) && !local_only.contains(&frame.id.to_string())
+}
+
+pub(crate) fn origin(frame: &Frame) -> Option<&str> {
+    frame.meta.as_ref()?.get(ORIGIN_KEY)?.as_str()
+}
+
+/// The id a frame is known by on every side: that of the frame it was first written as, which
+/// copies carry along however many remotes they're relayed through.
+fn root_id(frame: &Frame) -> String {
+    origin(frame).map_or_else(|| frame.id.to_string(), String::from)
+}
+
+/// A frame written by a user on this side, as opposed to a copy made by sync.
+pub(crate) fn is_new_change(frame: &Frame) -> bool {
+    is_syncable(frame) && origin(frame).is_none()
+}
+
+/// The other side of a sync: anything that can list its frames, serve content and accept appends.
+pub(crate) trait Remote {
+    async fn frames(&self) -> Result<Vec<Frame>, String>;
+    async fn content(&self, hash: &ssri::Integrity) -> Result<Vec<u8>, String>;
+    async fn append(
+        &self,
+        topic: &str,
+        content: Option<Vec<u8>>,
+        meta: serde_json::Value,
+    ) -> Result<Frame, String>;
+}
+
+/// Pairs of (local id, remote id) for copies of the same frame, matched by `root_id`. A copy
+/// pulled from one remote is only paired with a frame of another remote that is a copy of the
+/// same original, so it's still pushed to remotes that don't have it.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct IdMap {
+    pub local_to_remote: HashMap<String, String>,
+    pub remote_to_local: HashMap<String, String>,
+}
+
+impl IdMap {
+    pub fn build(local: &[Frame], remote: &[Frame]) -> Self {
+        let roots: HashMap<String, String> = local
+            .iter()
+            .map(|frame| (root_id(frame), frame.id.to_string()))
+            .collect();
+        let mut map = Self::default();
+        for frame in remote {
+            if let Some(local_id) = roots.get(&root_id(frame)) {
+                map.insert(local_id.clone(), frame.id.to_string());
+            }
+        }
+        map
+    }
+
+    pub fn insert(&mut self, local_id: String, remote_id: String) {
+        self.local_to_remote
+            .insert(local_id.clone(), remote_id.clone());
+        self.remote_to_local.insert(remote_id, local_id);
+    }
+}
+
+/// Rewrites any string in `meta` that is a known frame id to its counterpart, so references
+/// such as `yak_id` and `note_id` stay valid on the other side.
+pub(crate) fn translate_meta(
+    meta: &serde_json::Value,
+    ids: &HashMap<String, String>,
+) -> serde_json::Value {
+    match meta {
+        serde_json::Value::String(s) => match ids.get(s) {
+            Some(id) => serde_json::Value::String(id.clone()),
+            None => meta.clone(),
+        },
+        serde_json::Value::Array(items) => {
+            items.iter().map(|item| translate_meta(item, ids)).collect()
+        }
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| (key.clone(), translate_meta(value, ids)))
+            .collect(),
+        _ => meta.clone(),
+    }
+}
+
+/// Meta for a copy of `frame` on the other side: references translated and the original's id
+/// recorded as its origin.
+pub(crate) fn outbound_meta(frame: &Frame, ids: &HashMap<String, String>) -> serde_json::Value {
+    let mut meta = match frame.meta.as_ref().map(|meta| translate_meta(meta, ids)) {
+        Some(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    meta.insert(ORIGIN_KEY.to_string(), root_id(frame).into());
+    serde_json::Value::Object(meta)
+}
+
+/// Whether a frame from another device, pulled or pushed, may be taken in: its device
+/// signature must check out for a trusted device that isn't revoked (see `devices::verify`),
+/// and its meta must match its topic's schema.
+pub(crate) fn admit(frame: &Frame, trust: &devices::Trust, strict: bool) -> Result<(), String> {
+    devices::verify(frame, trust, strict)?;
+    schema::validate(&frame.topic, frame.meta.as_ref()).map_err(|e| e.to_string())
+}
+
+/// Appends a frame received from the other side of a sync, inserting its content first.
+pub(crate) async fn append_with_content(
+    store: &Store,
+    topic: &str,
+    content: Option<Vec<u8>>,
+    meta: serde_json::Value,
+) -> Result<Frame, String> {
+    // Copies keep the stamp of the device that wrote them; older ones are stamped on arrival
+    let mut meta = Some(meta);
+    if meta
+        .as_ref()
+        .and_then(|meta| meta.get(provenance::KEY))
+        .is_none()
+    {
+        provenance::stamp(&mut meta, Source::Sync);
+    }
+    let hash = match content {
+        Some(content) => Some(
+            store
+                .cas_insert(&content)
+                .await
+                .map_err(|e| format!("Failed to insert content: {e}"))?,
+        ),
+        None => None,
+    };
+    let frame = Frame {
+        id: scru128::new(),
+        context_id: ZERO_CONTEXT,
+        topic: topic.to_string(),
+        hash,
+        meta,
+        ttl: presence::ttl(topic),
+    };
+    append_to_store(store, frame).map_err(|e| e.context("Failed to append synced frame").into())
+}
+
+/// What a sync remembers of a remote between runs, so each run only fetches the remote
+/// frames that are new since the last.
+#[derive(Debug, Default)]
+pub(crate) struct Cursor {
+    /// The newest remote frame seen
+    pub last_id: Option<String>,
+    /// Every pairing seen so far, including those only the skipped remote frames record
+    ids: IdMap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Both,
+    /// Only bring remote frames in, e.g. when restoring from a backup
+    Pull,
+}
+
+/// Pushes local frames the remote hasn't seen, then pulls remote frames we haven't seen,
+/// reconciling by frame id. Returns the number of frames pushed and pulled.
+pub(crate) async fn reconcile<R: Remote>(
+    store: &Store,
+    remote: &R,
+    direction: Direction,
+) -> Result<(usize, usize), String> {
+    reconcile_from(store, remote, direction, &mut Cursor::default()).await
+}
+
+/// `reconcile` against a remote whose `frames` only lists those after `cursor.last_id`,
+/// moving the cursor on once the sync succeeds.
+pub(crate) async fn reconcile_from<R: Remote>(
+    store: &Store,
+    remote: &R,
+    direction: Direction,
+    cursor: &mut Cursor,
+) -> Result<(usize, usize), String> {
+    let remote_frames = remote.frames().await?;
+    let local = read_all_frames(store).await;
+    let mut ids = cursor.ids.clone();
+    let seen = IdMap::build(&local, &remote_frames);
+    for (local_id, remote_id) in seen.local_to_remote {
+        ids.insert(local_id, remote_id);
+    }
+    // Copies going out are signed, and those coming in checked, by device key
+    let key = match direction {
+        Direction::Both => Some(devices::device_key(store, &local)?),
+        Direction::Pull => None,
+    };
+    let mut trust = devices::Trust::new(&local);
+
+    let mut pushed = 0;
+    let local_only = local_only_ids(&local);
+    let outbound = local
+        .iter()
+        .filter(|frame| direction == Direction::Both && is_outbound(frame, &local_only));
+    for frame in outbound {
+        let id = frame.id.to_string();
+        if ids.local_to_remote.contains_key(&id) {
+            continue;
+        }
+        let content = match &frame.hash {
+            Some(hash) => Some(
+                store
+                    .cas_read(hash)
+                    .await
+                    .map_err(|e| format!("Failed to read content: {e}"))?,
+            ),
+            None => None,
+        };
+        let mut meta = outbound_meta(frame, &ids.local_to_remote);
+        if let Some(key) = &key {
+            meta = devices::sign(key, &frame.topic, frame.hash.as_ref(), meta);
+        }
+        let remote_frame = remote.append(&frame.topic, content, meta).await?;
+        ids.insert(id, remote_frame.id.to_string());
+        pushed += 1;
+    }
+
+    let mut pulled = 0;
+    let inbound = remote_frames
+        .iter()
+        .filter(|frame| is_syncable(frame) && !presence::is_stale(frame));
+    for frame in inbound {
+        let id = frame.id.to_string();
+        if ids.remote_to_local.contains_key(&id) {
+            continue;
+        }
+        if let Err(e) = admit(frame, &trust, key.is_some()) {
+            eprintln!("Rejected synced frame {id}: {e}");
+            continue;
+        }
+        let content = match &frame.hash {
+            Some(hash) => Some(remote.content(hash).await?),
+            None => None,
+        };
+        let local_frame = append_with_content(
+            store,
+            &frame.topic,
+            content,
+            outbound_meta(frame, &ids.remote_to_local),
+        )
+        .await?;
+        // A device trusted earlier in this pull has its later frames taken in
+        trust.apply(&local_frame);
+        ids.insert(local_frame.id.to_string(), id);
+        pulled += 1;
+    }
+
+    if let Some(last) = remote_frames.iter().map(|frame| frame.id).max() {
+        cursor.last_id = Some(last.to_string());
+    }
+    cursor.ids = ids;
+    Ok((pushed, pulled))
+}
+
+async fn run_sync(store: &Store, state: &SyncState) -> Result<(), String> {
+    let config = state.config.lock().await.clone();
+    let url = match (config.enabled, config.url) {
+        (true, Some(url)) => url,
+        _ => return Err("Sync is not enabled".to_string()),
+    };
+
+    {
+        let mut status = state.status.lock().await;
+        if status.running {
+            return Ok(());
+        }
+        status.running = true;
+    }
+
+    let result = {
+        let mut cursor = state.cursor.lock().await;
+        let remote = xs_remote::XsRemote::new(&url).after(cursor.last_id.clone());
+        reconcile_from(store, &remote, Direction::Both, &mut cursor).await
+    };
+
+    let mut status = state.status.lock().await;
+    status.running = false;
+    match result {
+        Ok((pushed, pulled)) => {
+            status.pushed += pushed;
+            status.pulled += pulled;
+            status.last_sync = Some(scru128::new().to_string());
+            status.last_error = None;
+            Ok(())
+        }
+        Err(e) => {
+            status.last_error = Some(e.clone());
+            Err(e)
+        }
+    }
+}
+
+async fn restart_follower(app: &AppHandle, state: &SyncState) {
+    if let Some(handle) = state.follower.lock().await.take() {
+        handle.abort();
+    }
+
+    let config = state.config.lock().await.clone();
+    let following = config.enabled && config.follow && config.url.is_some();
+    state.status.lock().await.following = following;
+
+    if let (true, Some(url)) = (following, config.url) {
+        let handle = tokio::spawn(xs_remote::follow(app.clone(), url));
+        *state.follower.lock().await = Some(handle);
+    }
+}
+
+async fn apply_config(app: &AppHandle, state: &SyncState, config: SyncConfig) {
+    {
+        let mut status = state.status.lock().await;
+        status.enabled = config.enabled;
+        status.url = config.url.clone();
+    }
+    // A different remote starts from the beginning
+    if state.config.lock().await.url != config.url {
+        *state.cursor.lock().await = Cursor::default();
+    }
+    *state.config.lock().await = config;
+    restart_follower(app, state).await;
+}
+
+/// Stops and restarts following the remote, for when it's stuck.
+pub(crate) async fn restart(app: &AppHandle) {
+    restart_follower(app, &app.state::<SyncState>()).await;
+}
+
+/// Loads the persisted sync config once the store is ready. Sync stays off unless configured.
+pub(crate) async fn initialize(app: &AppHandle, store: &Store, frames: &[Frame]) {
+    let config: SyncConfig = latest_setting(frames, CONFIG_TOPIC);
+    let state = app.state::<SyncState>();
+    apply_config(app, &state, config).await;
+
+    p2p::initialize(app, store, frames).await;
+    s3::initialize(app, store, frames).await;
+}
+
+/// Turns every kind of sync off in a store forked from this one, and gives it a device key of
+/// its own, so the fork can't push to the same remotes or pose as this device.
+pub(crate) fn detach(store: &Store, frames: &[Frame]) -> Result<(), String> {
+    let config: SyncConfig = latest_setting(frames, CONFIG_TOPIC);
+    if config.enabled {
+        let config = SyncConfig {
+            enabled: false,
+            follow: false,
+            ..config
+        };
+        save_setting(store, CONFIG_TOPIC, &config)?;
+    }
+    p2p::detach(store, frames)?;
+    s3::detach(store, frames)?;
+    devices::detach(store, frames)
+}
+
+#[tauri::command]
+pub async fn configure_sync(
+    store: State<'_, Store>,
+    state: State<'_, SyncState>,
+    app: AppHandle,
+    config: SyncConfig,
+) -> Result<SyncStatus, String> {
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    apply_config(&app, &state, config).await;
+    Ok(state.status.lock().await.clone())
+}
+
+#[tauri::command]
+pub async fn sync_now(
+    store: State<'_, Store>,
+    app: AppHandle,
+    state: State<'_, SyncState>,
+) -> Result<SyncStatus, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    run_sync(&store, &state).await?;
+    Ok(state.status.lock().await.clone())
+}
+
+/// Marks a yak as synced (the default) or local-only. Local-only yaks, their notes and their
+/// content are never sent to a sync target.
+#[tauri::command]
+pub async fn set_yak_sync(
+    store: State<'_, Store>,
+    app: AppHandle,
+    yak_id: String,
+    enabled: bool,
+) -> Result<(), String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let mut meta = Some(serde_json::json!({ "yak_id": yak_id, "enabled": enabled }));
+    provenance::stamp(&mut meta, Source::Ui);
+    let frame = Frame {
+        id: scru128::new(),
+        context_id: ZERO_CONTEXT,
+        topic: YAK_SYNC_TOPIC.to_string(),
+        hash: None,
+        meta,
+        ttl: None,
+    };
+    append_to_store(&store, frame).map_err(|e| e.context("Failed to save yak sync setting"))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn sync_status(state: State<'_, SyncState>) -> Result<SyncStatus, String> {
+    Ok(state.status.lock().await.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_outbound_meta_translates_references() {
+        let (_dir, store) = testing::store();
+        let (_remote_dir, remote) = testing::store();
+        let yak = append(&store, "yak.create", None);
+        let origin = json!({ "sync_origin": yak.id.to_string() });
+        let remote_yak = append(&remote, "yak.create", Some(origin));
+        let yak_ref = json!({ "yak_id": yak.id.to_string() });
+        let note = append(&store, "note.create", Some(yak_ref));
+
+        let ids = IdMap::build(
+            &read_all_frames(&store).await,
+            &read_all_frames(&remote).await,
+        );
+        assert!(ids.local_to_remote.contains_key(&yak.id.to_string()));
+        assert!(!ids.local_to_remote.contains_key(&note.id.to_string()));
+
+        let meta = outbound_meta(&note, &ids.local_to_remote);
+        assert_eq!(meta["yak_id"], remote_yak.id.to_string());
+        assert_eq!(meta[ORIGIN_KEY], note.id.to_string());
+
+        // A copy pulled from a peer is pushed on to a remote that doesn't have it, as a copy
+        // of the peer's original, and paired with that remote's copy once it has one
+        let original = scru128::new().to_string();
+        let relayed = append(
+            &store,
+            "yak.create",
+            Some(json!({ "sync_origin": original })),
+        );
+        let ids = IdMap::build(
+            &read_all_frames(&store).await,
+            &read_all_frames(&remote).await,
+        );
+        assert!(!ids.local_to_remote.contains_key(&relayed.id.to_string()));
+        assert_eq!(
+            outbound_meta(&relayed, &ids.local_to_remote)[ORIGIN_KEY],
+            original
+        );
+        let remote_copy = append(
+            &remote,
+            "yak.create",
+            Some(json!({ "sync_origin": original })),
+        );
+        let ids = IdMap::build(
+            &read_all_frames(&store).await,
+            &read_all_frames(&remote).await,
+        );
+        assert_eq!(
+            ids.local_to_remote[&relayed.id.to_string()],
+            remote_copy.id.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_only_yaks_stay_local() {
+        let (_dir, store) = testing::store();
+        let public = append(&store, "yak.create", None);
+        let private = append(&store, "yak.create", None);
+        let private_id = private.id.to_string();
+        let note = append(&store, "note.create", Some(json!({ "yak_id": private_id })));
+        let edit = json!({ "note_id": note.id.to_string() });
+        let edit = append(&store, "note.edit", Some(edit));
+        let setting = json!({ "yak_id": private_id, "enabled": false });
+        append(&store, YAK_SYNC_TOPIC, Some(setting));
+
+        let local_only = local_only_ids(&read_all_frames(&store).await);
+        assert!(is_outbound(&public, &local_only));
+        assert!(!is_outbound(&private, &local_only));
+        assert!(!is_outbound(&note, &local_only));
+        assert!(!is_outbound(&edit, &local_only));
+    }
+}
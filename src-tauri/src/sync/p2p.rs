@@ -0,0 +1,611 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use xs::store::{Frame, Store, ZERO_CONTEXT};
+
+use super::{
+    admit, append_with_content, devices, is_outbound, is_syncable, local_only_ids, reconcile,
+    Direction, Remote,
+};
+use crate::crypto::{from_hex, to_hex};
+use crate::profiles;
+use crate::settings::{latest_setting, save_setting};
+use crate::{read_all_frames, shutdown};
+
+const CONFIG_TOPIC: &str = "sync.p2p";
+/// Holds the node secret, in the profile's dir rather than the store.
+const SECRETS_FILE: &str = "p2p-secrets.json";
+const ALPN: &[u8] = b"yaks/sync/0";
+const MAX_MESSAGE_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct P2pConfig {
+    pub enabled: bool,
+    /// Hex-encoded node secret, generated on first enable so the node id stays stable. Kept
+    /// in `SECRETS_FILE`, never in the settings frame
+    #[serde(default, skip_serializing)]
+    pub secret_key: Option<String>,
+    /// Tickets of paired peers; only these may connect to us
+    #[serde(default)]
+    pub peers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerSyncResult {
+    pub node_id: String,
+    pub pushed: usize,
+    pub pulled: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct P2pState {
+    config: Mutex<P2pConfig>,
+    endpoint: Mutex<Option<iroh::Endpoint>>,
+    acceptor: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    Frames,
+    Content {
+        hash: String,
+    },
+    Append {
+        topic: String,
+        meta: serde_json::Value,
+    },
+}
+
+/// What pairing with a device takes: where to reach it, and the key it signs writes with.
+#[derive(Debug, Serialize, Deserialize)]
+struct Ticket {
+    addr: iroh::NodeAddr,
+    /// Trusted on pairing; missing from tickets made before devices were trusted
+    device: Option<String>,
+}
+
+fn encode_ticket(ticket: &Ticket) -> Result<String, String> {
+    let json = serde_json::to_vec(ticket).map_err(|e| format!("Failed to encode ticket: {e}"))?;
+    Ok(to_hex(&json))
+}
+
+fn decode_ticket(ticket: &str) -> Result<Ticket, String> {
+    let json = from_hex(ticket.trim())?;
+    serde_json::from_slice(&json)
+        .or_else(|_| serde_json::from_slice(&json).map(|addr| Ticket { addr, device: None }))
+        .map_err(|e| format!("Invalid ticket: {e}"))
+}
+
+/// Messages are a single JSON header line followed by raw bytes (content, if any).
+fn encode_message<T: Serialize>(header: &T, body: &[u8]) -> Result<Vec<u8>, String> {
+    let mut message =
+        serde_json::to_vec(header).map_err(|e| format!("Failed to encode message: {e}"))?;
+    message.push(b'\n');
+    message.extend_from_slice(body);
+    Ok(message)
+}
+
+fn decode_message<T: for<'de> Deserialize<'de>>(message: &[u8]) -> Result<(T, &[u8]), String> {
+    let split = message
+        .iter()
+        .position(|b| *b == b'\n')
+        .ok_or_else(|| "Malformed message".to_string())?;
+    let header = serde_json::from_slice(&message[..split])
+        .map_err(|e| format!("Malformed message header: {e}"))?;
+    Ok((header, &message[split + 1..]))
+}
+
+/// A paired peer reached over an iroh connection.
+struct PeerRemote {
+    connection: iroh::endpoint::Connection,
+}
+
+impl PeerRemote {
+    async fn call(
+        &self,
+        request: &Request,
+        body: &[u8],
+    ) -> Result<(serde_json::Value, Vec<u8>), String> {
+        let (mut send, mut recv) = self
+            .connection
+            .open_bi()
+            .await
+            .map_err(|e| format!("Failed to open stream: {e}"))?;
+        send.write_all(&encode_message(request, body)?)
+            .await
+            .map_err(|e| format!("Failed to send request: {e}"))?;
+        send.finish()
+            .map_err(|e| format!("Failed to send request: {e}"))?;
+
+        let response = recv
+            .read_to_end(MAX_MESSAGE_SIZE)
+            .await
+            .map_err(|e| format!("Failed to read response: {e}"))?;
+        let (header, body): (Result<serde_json::Value, String>, &[u8]) = decode_message(&response)?;
+        Ok((header?, body.to_vec()))
+    }
+}
+
+impl Remote for PeerRemote {
+    async fn frames(&self) -> Result<Vec<Frame>, String> {
+        let (frames, _) = self.call(&Request::Frames, &[]).await?;
+        serde_json::from_value(frames).map_err(|e| format!("Invalid frames from peer: {e}"))
+    }
+
+    async fn content(&self, hash: &ssri::Integrity) -> Result<Vec<u8>, String> {
+        let request = Request::Content {
+            hash: hash.to_string(),
+        };
+        let (_, content) = self.call(&request, &[]).await?;
+        Ok(content)
+    }
+
+    async fn append(
+        &self,
+        topic: &str,
+        content: Option<Vec<u8>>,
+        meta: serde_json::Value,
+    ) -> Result<Frame, String> {
+        let request = Request::Append {
+            topic: topic.to_string(),
+            meta,
+        };
+        let body = content.unwrap_or_default();
+        let (frame, _) = self.call(&request, &body).await?;
+        serde_json::from_value(frame).map_err(|e| format!("Invalid frame from peer: {e}"))
+    }
+}
+
+/// Frames this device shares with peers.
+fn outbound(frames: &[Frame]) -> Vec<&Frame> {
+    let local_only = local_only_ids(frames);
+    frames
+        .iter()
+        .filter(|frame| is_outbound(frame, &local_only))
+        .collect()
+}
+
+/// Appends a frame a peer pushed, once it's admitted like pulled frames are.
+async fn receive(
+    store: &Store,
+    topic: String,
+    body: &[u8],
+    meta: serde_json::Value,
+) -> Result<Frame, String> {
+    let content = (!body.is_empty()).then(|| body.to_vec());
+    // Checked as written now: a revoked device can't push older frames it kept back
+    let pushed = Frame {
+        id: scru128::new(),
+        context_id: ZERO_CONTEXT,
+        topic,
+        hash: content.as_deref().map(ssri::Integrity::from),
+        meta: Some(meta),
+        ttl: None,
+    };
+    // Pulls never take local-only frames, so a peer can't push them either
+    if !is_syncable(&pushed) {
+        return Err(format!("{} frames aren't synced", pushed.topic));
+    }
+    let trust = devices::Trust::new(&read_all_frames(store).await);
+    admit(&pushed, &trust, true)?;
+    let meta = pushed.meta.unwrap_or_default();
+    append_with_content(store, &pushed.topic, content, meta).await
+}
+
+async fn handle_request(
+    app: &AppHandle,
+    store: &Store,
+    message: &[u8],
+) -> Result<(serde_json::Value, Vec<u8>), String> {
+    let (request, body): (Request, &[u8]) = decode_message(message)?;
+    match request {
+        Request::Frames => {
+            let frames = read_all_frames(store).await;
+            let frames = serde_json::to_value(outbound(&frames)).map_err(|e| e.to_string())?;
+            Ok((frames, Vec::new()))
+        }
+        Request::Content { hash } => {
+            let hash = hash
+                .parse::<ssri::Integrity>()
+                .map_err(|e| format!("Invalid hash format: {e}"))?;
+            // Only content peers can see a frame for, never that of local-only yaks
+            let frames = read_all_frames(store).await;
+            if !outbound(&frames)
+                .iter()
+                .any(|frame| frame.hash.as_ref() == Some(&hash))
+            {
+                return Err(format!("Content {hash} isn't shared"));
+            }
+            let content = store
+                .cas_read(&hash)
+                .await
+                .map_err(|e| format!("Failed to read content: {e}"))?;
+            Ok((serde_json::Value::Null, content))
+        }
+        Request::Append { topic, meta } => {
+            let _guard = shutdown::begin_write(app)?;
+            let frame = receive(store, topic, body, meta).await?;
+            let frame = serde_json::to_value(frame).map_err(|e| e.to_string())?;
+            Ok((frame, Vec::new()))
+        }
+    }
+}
+
+async fn serve_connection(
+    app: AppHandle,
+    node_id: iroh::NodeId,
+    connection: iroh::endpoint::Connection,
+) {
+    let store = app.state::<Store>().inner().clone();
+    while let Ok((mut send, mut recv)) = connection.accept_bi().await {
+        let message = match recv.read_to_end(MAX_MESSAGE_SIZE).await {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("Failed to read peer request: {e}");
+                break;
+            }
+        };
+        // A peer unpaired while connected is cut off at its next request
+        let paired = paired_node_ids(&*app.state::<P2pState>().config.lock().await);
+        if !paired.contains(&node_id) {
+            connection.close(0u32.into(), b"not paired");
+            break;
+        }
+        let response = match handle_request(&app, &store, &message).await {
+            Ok((header, body)) => encode_message(&Ok::<_, String>(header), &body),
+            Err(e) => encode_message(&Err::<serde_json::Value, _>(e), &[]),
+        };
+        let sent = match response {
+            Ok(response) => send.write_all(&response).await.is_ok() && send.finish().is_ok(),
+            Err(_) => false,
+        };
+        if !sent {
+            break;
+        }
+    }
+}
+
+fn paired_node_ids(config: &P2pConfig) -> Vec<iroh::NodeId> {
+    config
+        .peers
+        .iter()
+        .filter_map(|ticket| decode_ticket(ticket).ok())
+        .map(|ticket| ticket.addr.node_id)
+        .collect()
+}
+
+async fn accept_loop(app: AppHandle, endpoint: iroh::Endpoint) {
+    while let Some(incoming) = endpoint.accept().await {
+        let connection = match incoming.accept() {
+            Ok(connecting) => match connecting.await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    eprintln!("Peer connection failed: {e}");
+                    continue;
+                }
+            },
+            Err(e) => {
+                eprintln!("Peer connection failed: {e}");
+                continue;
+            }
+        };
+
+        let state = app.state::<P2pState>();
+        let paired = paired_node_ids(&*state.config.lock().await);
+        match connection.remote_node_id() {
+            Ok(node_id) if paired.contains(&node_id) => {
+                tokio::spawn(serve_connection(app.clone(), node_id, connection));
+            }
+            _ => {
+                connection.close(0u32.into(), b"not paired");
+            }
+        }
+    }
+}
+
+/// Drops the node secret and paired peers from a forked store, so the fork starts as a node
+/// of its own.
+pub(super) fn detach(store: &Store, frames: &[Frame]) -> Result<(), String> {
+    if frames.iter().any(|frame| frame.topic == CONFIG_TOPIC) {
+        save_setting(store, CONFIG_TOPIC, &P2pConfig::default())?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct P2pSecrets {
+    secret_key: Option<String>,
+}
+
+fn secrets_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(profiles::data_dir(app)?.join(SECRETS_FILE))
+}
+
+fn load_secrets(path: &Path) -> P2pSecrets {
+    std::fs::read(path)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_secrets(path: &Path, config: &P2pConfig) -> Result<(), String> {
+    let secrets = P2pSecrets {
+        secret_key: config.secret_key.clone(),
+    };
+    let json = serde_json::to_vec(&secrets).map_err(|e| e.to_string())?;
+    profiles::write_private(path, &json).map_err(|e| format!("Failed to save node secret: {e}"))
+}
+
+fn secret_key(path: &Path, config: &mut P2pConfig) -> Result<iroh::SecretKey, String> {
+    if let Some(hex) = &config.secret_key {
+        let bytes: [u8; 32] = from_hex(hex)?
+            .try_into()
+            .map_err(|_| "Invalid node secret".to_string())?;
+        return Ok(iroh::SecretKey::from_bytes(&bytes));
+    }
+
+    let secret = iroh::SecretKey::generate(rand::rngs::OsRng);
+    config.secret_key = Some(to_hex(&secret.to_bytes()));
+    save_secrets(path, config)?;
+    Ok(secret)
+}
+
+async fn stop(state: &P2pState) {
+    if let Some(handle) = state.acceptor.lock().await.take() {
+        handle.abort();
+    }
+    if let Some(endpoint) = state.endpoint.lock().await.take() {
+        endpoint.close().await;
+    }
+}
+
+async fn start(app: &AppHandle, state: &P2pState) -> Result<(), String> {
+    stop(state).await;
+
+    let mut config = state.config.lock().await;
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let secret = secret_key(&secrets_path(app)?, &mut config)?;
+    let endpoint = iroh::Endpoint::builder()
+        .secret_key(secret)
+        .alpns(vec![ALPN.to_vec()])
+        .discovery_n0()
+        .discovery_local_network()
+        .bind()
+        .await
+        .map_err(|e| format!("Failed to start peer endpoint: {e}"))?;
+
+    println!("P2P sync listening as {}", endpoint.node_id());
+    let handle = tokio::spawn(accept_loop(app.clone(), endpoint.clone()));
+    *state.acceptor.lock().await = Some(handle);
+    *state.endpoint.lock().await = Some(endpoint);
+    Ok(())
+}
+
+pub(super) async fn initialize(app: &AppHandle, store: &Store, frames: &[Frame]) {
+    let state = app.state::<P2pState>();
+    let mut config: P2pConfig = latest_setting(frames, CONFIG_TOPIC);
+    match secrets_path(app) {
+        // Saved in the frame by an earlier version: moved out, and the frame saved without it
+        Ok(path) if config.secret_key.is_some() => {
+            let moved = save_secrets(&path, &config)
+                .and_then(|_| save_setting(store, CONFIG_TOPIC, &config).map(|_| ()));
+            if let Err(e) = moved {
+                eprintln!("Failed to move the node secret out of the store: {e}");
+            }
+        }
+        Ok(path) => config.secret_key = load_secrets(&path).secret_key,
+        Err(e) => eprintln!("The node secret can't be loaded: {e}"),
+    }
+    *state.config.lock().await = config;
+    if let Err(e) = start(app, &state).await {
+        eprintln!("{e}");
+    }
+}
+
+#[tauri::command]
+pub async fn enable_p2p(
+    store: State<'_, Store>,
+    state: State<'_, P2pState>,
+    app: AppHandle,
+    enabled: bool,
+) -> Result<(), String> {
+    {
+        let mut config = state.config.lock().await;
+        config.enabled = enabled;
+        save_setting(&store, CONFIG_TOPIC, &*config)?;
+    }
+    start(&app, &state).await
+}
+
+/// A ticket other devices can use to pair with this one; suitable for rendering as a QR code.
+#[tauri::command]
+pub async fn p2p_ticket(
+    store: State<'_, Store>,
+    state: State<'_, P2pState>,
+) -> Result<String, String> {
+    let endpoint = state.endpoint.lock().await;
+    let endpoint = endpoint
+        .as_ref()
+        .ok_or_else(|| "P2P sync is not enabled".to_string())?;
+    let addr = endpoint
+        .node_addr()
+        .await
+        .map_err(|e| format!("Failed to get node address: {e}"))?;
+    let frames = read_all_frames(&store).await;
+    let device = devices::device_id(&devices::device_key(&store, &frames)?);
+    encode_ticket(&Ticket {
+        addr,
+        device: Some(device),
+    })
+}
+
+/// Pairs with a peer from its ticket, trusting the device it names, and returns the peer's
+/// node id.
+#[tauri::command]
+pub async fn pair_peer(
+    store: State<'_, Store>,
+    state: State<'_, P2pState>,
+    app: AppHandle,
+    ticket: String,
+) -> Result<String, String> {
+    let _guard = shutdown::begin_write(&app)?;
+    let Ticket { addr, device } = decode_ticket(&ticket)?;
+    let device = device.ok_or("This ticket is from an older version; make a new one")?;
+    let frames = read_all_frames(&store).await;
+    devices::trust_devices(&store, &frames, [device]).await?;
+    let mut config = state.config.lock().await;
+    config.peers.retain(|peer| {
+        decode_ticket(peer)
+            .ok()
+            .is_none_or(|peer| peer.addr.node_id != addr.node_id)
+    });
+    config.peers.push(ticket.trim().to_string());
+    save_setting(&store, CONFIG_TOPIC, &*config)?;
+    Ok(addr.node_id.to_string())
+}
+
+#[tauri::command]
+pub async fn unpair_peer(
+    store: State<'_, Store>,
+    state: State<'_, P2pState>,
+    node_id: String,
+) -> Result<(), String> {
+    let mut config = state.config.lock().await;
+    config.peers.retain(|peer| {
+        decode_ticket(peer)
+            .ok()
+            .is_none_or(|peer| peer.addr.node_id.to_string() != node_id)
+    });
+    save_setting(&store, CONFIG_TOPIC, &*config)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn sync_peers_now(
+    store: State<'_, Store>,
+    app: AppHandle,
+    state: State<'_, P2pState>,
+) -> Result<Vec<PeerSyncResult>, String> {
+    let _guard = shutdown::begin_write(&app)?;
+    let endpoint = state
+        .endpoint
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| "P2P sync is not enabled".to_string())?;
+    let peers = state.config.lock().await.peers.clone();
+
+    let mut results = Vec::new();
+    for ticket in peers {
+        let addr = decode_ticket(&ticket)?.addr;
+        let node_id = addr.node_id.to_string();
+        let result = match endpoint.connect(addr, ALPN).await {
+            Ok(connection) => reconcile(&store, &PeerRemote { connection }, Direction::Both).await,
+            Err(e) => Err(format!("Failed to connect: {e}")),
+        };
+        results.push(match result {
+            Ok((pushed, pulled)) => PeerSyncResult {
+                node_id,
+                pushed,
+                pulled,
+                error: None,
+            },
+            Err(e) => PeerSyncResult {
+                node_id,
+                pushed: 0,
+                pulled: 0,
+                error: Some(e),
+            },
+        });
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use ed25519_dalek::SigningKey;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_revoked_device_push_is_refused() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let hash = ssri::Integrity::from(b"hello");
+        let push = |key: &SigningKey| {
+            let meta =
+                serde_json::json!({ "yak_id": "y1", "sync_origin": scru128::new().to_string() });
+            devices::sign(key, "note.create", Some(&hash), meta)
+        };
+        let lost = SigningKey::from_bytes(&[7; 32]);
+        let kept = SigningKey::from_bytes(&[8; 32]);
+        let revoked_at = chrono::Utc::now().timestamp_millis() - 1000;
+        let revoke = serde_json::json!({
+            "device": push(&lost)["sync_device"],
+            "revoked_at": revoked_at,
+        });
+        append_frame(&store, "device.revoke", None, Some(revoke))
+            .await
+            .unwrap();
+
+        let refused = receive(&store, "note.create".to_string(), b"hello", push(&lost)).await;
+        assert!(refused.unwrap_err().contains("revoked"));
+        let unsigned = serde_json::json!({ "yak_id": "y1" });
+        assert!(
+            receive(&store, "note.create".to_string(), b"hello", unsigned)
+                .await
+                .is_err()
+        );
+        let mut tampered = push(&kept);
+        tampered["yak_id"] = "y2".into();
+        assert!(
+            receive(&store, "note.create".to_string(), b"hello", tampered)
+                .await
+                .is_err()
+        );
+        let frame = receive(&store, "note.create".to_string(), b"hello", push(&kept))
+            .await
+            .unwrap();
+        assert_eq!(frame.hash, Some(hash));
+    }
+
+    #[test]
+    fn test_node_secret_stays_out_of_the_store() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(SECRETS_FILE);
+        let mut config = P2pConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let secret = secret_key(&path, &mut config).unwrap();
+        let meta = serde_json::to_value(&config).unwrap();
+        assert!(meta.get("secret_key").is_none());
+
+        let mut reloaded = P2pConfig {
+            secret_key: load_secrets(&path).secret_key,
+            ..Default::default()
+        };
+        let again = secret_key(&path, &mut reloaded).unwrap();
+        assert_eq!(again.to_bytes(), secret.to_bytes());
+    }
+
+    #[test]
+    fn test_message_roundtrip() {
+        let request = Request::Append {
+            topic: "note.create".to_string(),
+            meta: serde_json::json!({ "yak_id": "abc" }),
+        };
+        let message = encode_message(&request, b"hello\nworld").unwrap();
+        let (decoded, body): (Request, &[u8]) = decode_message(&message).unwrap();
+        assert!(matches!(decoded, Request::Append { topic, .. } if topic == "note.create"));
+        assert_eq!(body, b"hello\nworld");
+    }
+}
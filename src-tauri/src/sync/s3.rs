@@ -0,0 +1,468 @@
+use rusty_s3::{Bucket, Credentials, S3Action, UrlStyle};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Mutex;
+use xs::store::{Frame, Store, ZERO_CONTEXT};
+
+use super::{reconcile_from, Cursor, Direction, Remote};
+use crate::crypto::{self, KEY_LEN};
+use crate::profiles;
+use crate::settings::{latest_setting, save_setting};
+
+const CONFIG_TOPIC: &str = "sync.s3";
+/// The secret access key and bucket key, kept beside the store (readable only by the user)
+/// rather than in the `sync.s3` frame, where every export and backup of the store would
+/// carry them.
+const SECRETS_FILE: &str = "s3-secrets.json";
+const SIGN_DURATION: Duration = Duration::from_secs(15 * 60);
+/// Encrypted marker used to check a passphrase against an existing bucket.
+const CHECK_KEY: &str = "check";
+const CHECK_PLAINTEXT: &[u8] = b"yaks";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct S3Config {
+    pub enabled: bool,
+    /// e.g. `https://s3.us-west-004.backblazeb2.com`
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    /// Kept in `SECRETS_FILE`, never in the settings frame
+    #[serde(default, skip_serializing)]
+    pub secret_key: String,
+    /// Key prefix inside the bucket, so one bucket can hold several stores
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub path_style: bool,
+    /// Hex-encoded key derived from the passphrase, kept in `SECRETS_FILE`; the passphrase
+    /// itself is never stored
+    #[serde(default, skip_serializing)]
+    pub key: Option<String>,
+}
+
+impl S3Config {
+    /// Whether `other` points at a different place in a bucket, which syncs from scratch.
+    fn same_bucket(&self, other: &S3Config) -> bool {
+        (&self.endpoint, &self.bucket, &self.prefix)
+            == (&other.endpoint, &other.bucket, &other.prefix)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct S3Secrets {
+    secret_key: String,
+    key: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3SyncResult {
+    pub pushed: usize,
+    pub pulled: usize,
+}
+
+#[derive(Default)]
+pub struct S3State {
+    config: Mutex<S3Config>,
+    /// Where the last sync with the bucket left off
+    cursor: Mutex<Cursor>,
+}
+
+/// An S3-compatible bucket holding client-side encrypted frames (`frames/<id>`) and
+/// content (`cas/<keyed hash>`). Object keys never reveal content hashes.
+struct S3Remote {
+    client: reqwest::Client,
+    bucket: Bucket,
+    credentials: Credentials,
+    prefix: String,
+    key: [u8; KEY_LEN],
+    /// Only frames uploaded after this one are listed and fetched
+    after: Option<String>,
+}
+
+impl S3Remote {
+    fn new(config: &S3Config, key: [u8; KEY_LEN]) -> Result<Self, String> {
+        let endpoint = config
+            .endpoint
+            .parse()
+            .map_err(|e| format!("Invalid S3 endpoint: {e}"))?;
+        let style = if config.path_style {
+            UrlStyle::Path
+        } else {
+            UrlStyle::VirtualHost
+        };
+        let bucket = Bucket::new(
+            endpoint,
+            style,
+            config.bucket.clone(),
+            config.region.clone(),
+        )
+        .map_err(|e| format!("Invalid S3 bucket: {e}"))?;
+        Ok(Self {
+            client: reqwest::Client::new(),
+            bucket,
+            credentials: Credentials::new(config.access_key.clone(), config.secret_key.clone()),
+            prefix: config.prefix.trim_matches('/').to_string(),
+            key,
+            after: None,
+        })
+    }
+
+    fn after(mut self, last_id: Option<String>) -> Self {
+        self.after = last_id;
+        self
+    }
+
+    fn object_key(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{name}", self.prefix)
+        }
+    }
+
+    fn content_key(&self, hash: &ssri::Integrity) -> String {
+        let mut keyed = self.key.to_vec();
+        keyed.extend_from_slice(hash.to_string().as_bytes());
+        let (_, hex) = ssri::Integrity::from(&keyed).to_hex();
+        self.object_key(&format!("cas/{hex}"))
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        let url = self
+            .bucket
+            .get_object(Some(&self.credentials), key)
+            .sign(SIGN_DURATION);
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch {key}: {e}"))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let bytes = response
+            .error_for_status()
+            .map_err(|e| format!("Failed to fetch {key}: {e}"))?
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to fetch {key}: {e}"))?;
+        Ok(Some(bytes.to_vec()))
+    }
+
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), String> {
+        let url = self
+            .bucket
+            .put_object(Some(&self.credentials), key)
+            .sign(SIGN_DURATION);
+        self.client
+            .put(url)
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Failed to upload {key}: {e}"))?;
+        Ok(())
+    }
+
+    /// Keys under `prefix`, in order, starting after the key `start_after` if given.
+    async fn list(&self, prefix: &str, start_after: Option<&str>) -> Result<Vec<String>, String> {
+        let prefix = self.object_key(prefix);
+        let mut keys = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut action = self.bucket.list_objects_v2(Some(&self.credentials));
+            action.with_prefix(prefix.as_str());
+            if let Some(start_after) = start_after {
+                action.with_start_after(start_after);
+            }
+            if let Some(token) = &token {
+                action.with_continuation_token(token.as_str());
+            }
+            let body = self
+                .client
+                .get(action.sign(SIGN_DURATION))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| format!("Failed to list bucket: {e}"))?
+                .text()
+                .await
+                .map_err(|e| format!("Failed to list bucket: {e}"))?;
+            let page = rusty_s3::actions::ListObjectsV2::parse_response(&body)
+                .map_err(|e| format!("Invalid bucket listing: {e}"))?;
+            keys.extend(page.contents.into_iter().map(|object| object.key));
+            match page.next_continuation_token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn get_decrypted(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        match self.get(key).await? {
+            Some(sealed) => crypto::decrypt(&self.key, &sealed).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn put_encrypted(&self, key: &str, plaintext: &[u8]) -> Result<(), String> {
+        self.put(key, crypto::encrypt(&self.key, plaintext)?).await
+    }
+}
+
+impl Remote for S3Remote {
+    /// Frame keys are their ids, which sort by time, so the bucket lists only those uploaded
+    /// since the cursor.
+    async fn frames(&self) -> Result<Vec<Frame>, String> {
+        let after = self
+            .after
+            .as_ref()
+            .map(|id| self.object_key(&format!("frames/{id}")));
+        let mut frames = Vec::new();
+        for key in self.list("frames/", after.as_deref()).await? {
+            let Some(json) = self.get_decrypted(&key).await? else {
+                continue;
+            };
+            let frame = serde_json::from_slice(&json)
+                .map_err(|e| format!("Invalid frame in bucket: {e}"))?;
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+
+    async fn content(&self, hash: &ssri::Integrity) -> Result<Vec<u8>, String> {
+        self.get_decrypted(&self.content_key(hash))
+            .await?
+            .ok_or_else(|| format!("Content missing from bucket: {hash}"))
+    }
+
+    async fn append(
+        &self,
+        topic: &str,
+        content: Option<Vec<u8>>,
+        meta: serde_json::Value,
+    ) -> Result<Frame, String> {
+        let hash = match content {
+            Some(content) => {
+                let hash = ssri::Integrity::from(&content);
+                self.put_encrypted(&self.content_key(&hash), &content)
+                    .await?;
+                Some(hash)
+            }
+            None => None,
+        };
+        let frame = Frame {
+            id: scru128::new(),
+            context_id: ZERO_CONTEXT,
+            topic: topic.to_string(),
+            hash,
+            meta: Some(meta),
+            ttl: None,
+        };
+        let json = serde_json::to_vec(&frame).map_err(|e| e.to_string())?;
+        self.put_encrypted(&self.object_key(&format!("frames/{}", frame.id)), &json)
+            .await?;
+        Ok(frame)
+    }
+}
+
+/// Derives the bucket key from a passphrase, creating the salt and check marker on first use
+/// and rejecting a passphrase that doesn't match an already-initialized bucket.
+async fn unlock(config: &S3Config, passphrase: &str) -> Result<[u8; KEY_LEN], String> {
+    let probe = S3Remote::new(config, [0; KEY_LEN])?;
+    let salt = match probe.get(&probe.object_key("salt")).await? {
+        Some(salt) => salt,
+        None => {
+            let salt = crypto::random_salt().to_vec();
+            probe.put(&probe.object_key("salt"), salt.clone()).await?;
+            salt
+        }
+    };
+
+    let key = crypto::derive_key(passphrase, &salt)?;
+    let remote = S3Remote::new(config, key)?;
+    let check_key = remote.object_key(CHECK_KEY);
+    match remote.get(&check_key).await? {
+        Some(sealed) => {
+            if crypto::decrypt(&key, &sealed)? != CHECK_PLAINTEXT {
+                return Err("Passphrase does not match this bucket".to_string());
+            }
+        }
+        None => remote.put_encrypted(&check_key, CHECK_PLAINTEXT).await?,
+    }
+    Ok(key)
+}
+
+async fn remote(state: &S3State) -> Result<S3Remote, String> {
+    let config = state.config.lock().await.clone();
+    if !config.enabled {
+        return Err("S3 sync is not enabled".to_string());
+    }
+    let key: [u8; KEY_LEN] = config
+        .key
+        .as_deref()
+        .ok_or_else(|| "S3 sync has no passphrase configured".to_string())
+        .and_then(crypto::from_hex)?
+        .try_into()
+        .map_err(|_| "Invalid S3 sync key".to_string())?;
+    S3Remote::new(&config, key)
+}
+
+async fn sync(
+    store: &Store,
+    state: &S3State,
+    direction: Direction,
+) -> Result<S3SyncResult, String> {
+    let mut cursor = state.cursor.lock().await;
+    let remote = remote(state).await?.after(cursor.last_id.clone());
+    let (pushed, pulled) = reconcile_from(store, &remote, direction, &mut cursor).await?;
+    Ok(S3SyncResult { pushed, pulled })
+}
+
+fn secrets_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(profiles::data_dir(app)?.join(SECRETS_FILE))
+}
+
+fn load_secrets(path: &Path) -> S3Secrets {
+    std::fs::read(path)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_secrets(path: &Path, config: &S3Config) -> Result<(), String> {
+    let secrets = S3Secrets {
+        secret_key: config.secret_key.clone(),
+        key: config.key.clone(),
+    };
+    let json = serde_json::to_vec(&secrets).map_err(|e| e.to_string())?;
+    profiles::write_private(path, &json).map_err(|e| format!("Failed to save S3 credentials: {e}"))
+}
+
+/// Turns S3 sync off in a forked store. Its credentials stay behind in this profile's dir.
+pub(super) fn detach(store: &Store, frames: &[Frame]) -> Result<(), String> {
+    let config: S3Config = latest_setting(frames, CONFIG_TOPIC);
+    if config.enabled {
+        let config = S3Config {
+            enabled: false,
+            ..config
+        };
+        save_setting(store, CONFIG_TOPIC, &config)?;
+    }
+    Ok(())
+}
+
+pub(super) async fn initialize(app: &AppHandle, store: &Store, frames: &[Frame]) {
+    let mut config: S3Config = latest_setting(frames, CONFIG_TOPIC);
+    match secrets_path(app) {
+        // Saved in the frame by an earlier version: moved out, and the frame saved without them
+        Ok(path) if !config.secret_key.is_empty() || config.key.is_some() => {
+            let moved = save_secrets(&path, &config)
+                .and_then(|_| save_setting(store, CONFIG_TOPIC, &config).map(|_| ()));
+            if let Err(e) = moved {
+                eprintln!("Failed to move S3 credentials out of the store: {e}");
+            }
+        }
+        Ok(path) => {
+            let secrets = load_secrets(&path);
+            config.secret_key = secrets.secret_key;
+            config.key = secrets.key;
+        }
+        Err(e) => eprintln!("S3 credentials can't be loaded: {e}"),
+    }
+    *app.state::<S3State>().config.lock().await = config;
+}
+
+/// Saves the bucket settings. A passphrase is required the first time (or to change it).
+#[tauri::command]
+pub async fn configure_s3_sync(
+    store: State<'_, Store>,
+    app: AppHandle,
+    state: State<'_, S3State>,
+    mut config: S3Config,
+    passphrase: Option<String>,
+) -> Result<(), String> {
+    config.key = match passphrase {
+        Some(passphrase) => Some(crypto::to_hex(&unlock(&config, &passphrase).await?)),
+        None => state.config.lock().await.key.clone(),
+    };
+    if config.enabled && config.key.is_none() {
+        return Err("A passphrase is required to enable S3 sync".to_string());
+    }
+    save_secrets(&secrets_path(&app)?, &config)?;
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    let mut current = state.config.lock().await;
+    if !current.same_bucket(&config) {
+        *state.cursor.lock().await = Cursor::default();
+    }
+    *current = config;
+    Ok(())
+}
+
+/// Uploads frames and content the bucket doesn't have yet and pulls in any it has that we don't.
+#[tauri::command]
+pub async fn s3_sync_now(
+    store: State<'_, Store>,
+    app: AppHandle,
+    state: State<'_, S3State>,
+) -> Result<S3SyncResult, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    sync(&store, &state, Direction::Both).await
+}
+
+/// Restores from the bucket without uploading anything, e.g. onto a fresh install.
+#[tauri::command]
+pub async fn s3_restore(
+    store: State<'_, Store>,
+    app: AppHandle,
+    state: State<'_, S3State>,
+) -> Result<S3SyncResult, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    sync(&store, &state, Direction::Pull).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_all_frames;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_credentials_stay_out_of_the_store() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().join("store"));
+        let config = S3Config {
+            enabled: true,
+            bucket: "notes".to_string(),
+            access_key: "AKIA".to_string(),
+            secret_key: "hunter2".to_string(),
+            key: Some("00ff".to_string()),
+            ..Default::default()
+        };
+        save_setting(&store, CONFIG_TOPIC, &config).unwrap();
+        let path = dir.path().join(SECRETS_FILE);
+        save_secrets(&path, &config).unwrap();
+
+        let frames = read_all_frames(&store).await;
+        let meta = frames[0].meta.as_ref().unwrap();
+        assert_eq!(meta["access_key"], "AKIA");
+        assert!(meta.get("secret_key").is_none());
+        assert!(meta.get("key").is_none());
+
+        let secrets = load_secrets(&path);
+        assert_eq!(secrets.secret_key, "hunter2");
+        assert_eq!(secrets.key.as_deref(), Some("00ff"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
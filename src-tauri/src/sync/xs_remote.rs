@@ -0,0 +1,215 @@
+use tauri::{AppHandle, Manager};
+use tokio::sync::mpsc;
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use super::{is_new_change, is_syncable, origin, run_sync, Remote, SyncState};
+use crate::{health, presence};
+
+fn endpoint(base: &str, segments: &[&str]) -> Result<reqwest::Url, String> {
+    let mut url = reqwest::Url::parse(base).map_err(|e| format!("Invalid sync url: {e}"))?;
+    url.path_segments_mut()
+        .map_err(|_| "Invalid sync url: cannot be a base".to_string())?
+        .pop_if_empty()
+        .extend(segments);
+    Ok(url)
+}
+
+fn parse_frames(body: &str) -> Result<Vec<Frame>, String> {
+    body.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(|e| format!("Invalid remote frame: {e}")))
+        .collect()
+}
+
+/// A remote `xs serve` instance, spoken to over its HTTP API.
+pub(super) struct XsRemote {
+    client: reqwest::Client,
+    base: String,
+    /// List only the frames after this one
+    after: Option<String>,
+}
+
+impl XsRemote {
+    pub fn new(base: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base: base.to_string(),
+            after: None,
+        }
+    }
+
+    pub fn after(mut self, last_id: Option<String>) -> Self {
+        self.after = last_id;
+        self
+    }
+}
+
+impl Remote for XsRemote {
+    async fn frames(&self) -> Result<Vec<Frame>, String> {
+        let mut url = endpoint(&self.base, &[])?;
+        if let Some(last_id) = &self.after {
+            url.query_pairs_mut().append_pair("last-id", last_id);
+        }
+        let body = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Failed to read remote stream: {e}"))?
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read remote stream: {e}"))?;
+        parse_frames(&body)
+    }
+
+    async fn content(&self, hash: &ssri::Integrity) -> Result<Vec<u8>, String> {
+        let bytes = self
+            .client
+            .get(endpoint(&self.base, &["cas", &hash.to_string()])?)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Failed to fetch remote content {hash}: {e}"))?
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to fetch remote content {hash}: {e}"))?;
+        Ok(bytes.to_vec())
+    }
+
+    async fn append(
+        &self,
+        topic: &str,
+        content: Option<Vec<u8>>,
+        meta: serde_json::Value,
+    ) -> Result<Frame, String> {
+        let mut url = endpoint(&self.base, &[topic])?;
+        // Presence updates expire on the remote too
+        if topic == presence::TOPIC {
+            let ttl = format!("time:{}", presence::LIFETIME.as_millis());
+            url.query_pairs_mut().append_pair("ttl", &ttl);
+        }
+        self.client
+            .post(url)
+            .header("xs-meta", meta.to_string())
+            .body(content.unwrap_or_default())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Failed to push frame: {e}"))?
+            .json::<Frame>()
+            .await
+            .map_err(|e| format!("Invalid response to push: {e}"))
+    }
+}
+
+/// Whether a remote frame is news here, rather than the copy of a frame this device pushed.
+fn is_remote_change(store: &Store, frame: &Frame) -> bool {
+    is_syncable(frame)
+        && origin(frame)
+            .and_then(|id| id.parse::<scru128::Scru128Id>().ok())
+            .is_none_or(|id| store.get(&id).is_none())
+}
+
+async fn watch_remote(store: &Store, base: &str, kick: mpsc::Sender<()>) -> Result<(), String> {
+    let mut url = endpoint(base, &[])?;
+    url.query_pairs_mut().append_pair("follow", "true");
+    let mut response = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to follow remote stream: {e}"))?;
+
+    let mut buffer = Vec::new();
+    let mut caught_up = false;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Remote stream failed: {e}"))?
+    {
+        buffer.extend_from_slice(&chunk);
+        while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            let Ok(frame) = serde_json::from_slice::<Frame>(&line) else {
+                continue;
+            };
+            if frame.topic == "xs.threshold" {
+                caught_up = true;
+            } else if caught_up && is_remote_change(store, &frame) {
+                let _ = kick.try_send(());
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn watch_local(store: Store, kick: mpsc::Sender<()>) {
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    let mut caught_up = false;
+    while let Some(frame) = rx.recv().await {
+        if frame.topic == "xs.threshold" {
+            caught_up = true;
+        } else if caught_up && is_new_change(&frame) {
+            let _ = kick.try_send(());
+        }
+    }
+}
+
+/// Runs until aborted: syncs once up front, then again whenever either stream gets a new change.
+pub(super) async fn follow(app: AppHandle, base: String) {
+    let (kick, mut kicked) = mpsc::channel::<()>(1);
+    let _ = kick.try_send(());
+
+    let store = app.state::<Store>().inner().clone();
+    let remote_kick = kick.clone();
+
+    let syncer = async {
+        while kicked.recv().await.is_some() {
+            let state = app.state::<SyncState>();
+            let store = app.state::<Store>();
+            match run_sync(&store, &state).await {
+                Ok(()) => health::ok(&app, "sync"),
+                Err(e) => {
+                    health::error(&app, "sync", &e);
+                    eprintln!("Sync failed: {e}");
+                }
+            }
+        }
+    };
+
+    tokio::select! {
+        result = watch_remote(&store, &base, remote_kick) => {
+            if let Err(e) = result {
+                health::error(&app, "sync", &e);
+                eprintln!("{e}");
+            }
+            println!("Remote sync stream ended");
+        }
+        _ = watch_local(store.clone(), kick) => {}
+        _ = syncer => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use crate::testing::{self, append};
+
+    #[tokio::test]
+    async fn test_is_remote_change() {
+        let (_dir, store) = testing::store();
+        let (_remote_dir, remote_store) = testing::store();
+        let remote = |meta| append(&remote_store, "note.create", Some(meta));
+        let local = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+
+        // Our own push coming back isn't news; another device's frame is, even once synced
+        let echo = remote(serde_json::json!({ "sync_origin": local.id.to_string() }));
+        assert!(!is_remote_change(&store, &echo));
+        let relayed = remote(serde_json::json!({ "sync_origin": scru128::new().to_string() }));
+        assert!(is_remote_change(&store, &relayed));
+        assert!(is_remote_change(&store, &remote(serde_json::json!({}))));
+    }
+}
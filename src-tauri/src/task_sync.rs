@@ -0,0 +1,654 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::locks::is_sealed;
+use crate::profiles;
+use crate::projection::{Projection, Task};
+use crate::settings::{latest_setting, load_setting, save_setting};
+use crate::shutdown::begin_write;
+use crate::windows::emit_frames;
+use crate::{append_frame, health, read_all_frames};
+
+const CONFIG_TOPIC: &str = "integrations.tasks";
+/// Holds each yak's API token, in the profile's dir rather than the store.
+const SECRETS_FILE: &str = "task-sync-secrets.json";
+/// Links a task to the issue or task it's kept in step with, and records the state both
+/// last agreed on.
+const LINK_TOPIC: &str = "task.external";
+/// Set on the `task.update` frames that bring in remote changes, so they aren't pushed back.
+const SOURCE: &str = "task_sync";
+const POLL: Duration = Duration::from_secs(5 * 60);
+const GITHUB_API: &str = "https://api.github.com";
+const TODOIST_API: &str = "https://api.todoist.com/rest/v2";
+
+/// Only one sync runs at a time, so a push and a poll never race to link the same task.
+static SYNCING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tracker {
+    GitHub,
+    Todoist,
+}
+
+/// Which side wins when a task and its remote copy both changed since they last agreed, and
+/// differently.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Prefer {
+    #[default]
+    Local,
+    Remote,
+}
+
+/// Where a yak's tasks are kept in step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackerSync {
+    pub tracker: Tracker,
+    /// Kept in `SECRETS_FILE`, never in the settings frame
+    #[serde(default, skip_serializing)]
+    pub token: String,
+    /// `owner/repo` for GitHub; a project id for Todoist, or empty for the inbox
+    #[serde(default)]
+    pub target: String,
+    #[serde(default)]
+    pub prefer: Prefer,
+    /// Tasks created before this frame id were there before syncing started, and aren't pushed
+    #[serde(default)]
+    pub since: Option<String>,
+}
+
+/// Task sync, by yak id. Yaks not listed aren't synced.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TaskSyncConfig {
+    #[serde(default)]
+    pub yaks: HashMap<String, TrackerSync>,
+}
+
+/// What both sides of a linked task say.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Side {
+    pub done: bool,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Link {
+    task_id: String,
+    external_id: String,
+    url: Option<String>,
+    agreed: Side,
+}
+
+/// What reconciling a linked task calls for.
+#[derive(Debug, PartialEq)]
+struct Plan {
+    agreed: Side,
+    push: bool,
+    pull: bool,
+    conflict: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TaskSyncReport {
+    pub pushed: usize,
+    pub pulled: usize,
+    pub conflicts: usize,
+}
+
+/// Sent as "task-sync-conflict" when both sides changed a task differently.
+#[derive(Debug, Clone, Serialize)]
+pub struct Conflict {
+    pub task_id: String,
+    pub local: Side,
+    pub remote: Side,
+    pub kept: Side,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+/// A three-way merge of each field: a side that changed since `agreed` wins over one that
+/// didn't, and `prefer` settles fields both changed differently.
+fn reconcile(agreed: &Side, local: &Side, remote: &Side, prefer: Prefer) -> Plan {
+    let mut conflict = false;
+    let mut merge = |agreed: &Value, local: &Value, remote: &Value| {
+        if local == agreed || local == remote {
+            remote.clone()
+        } else if remote == agreed {
+            local.clone()
+        } else {
+            conflict = true;
+            match prefer {
+                Prefer::Local => local.clone(),
+                Prefer::Remote => remote.clone(),
+            }
+        }
+    };
+    let done = merge(&agreed.done.into(), &local.done.into(), &remote.done.into());
+    let title = merge(
+        &agreed.title.as_str().into(),
+        &local.title.as_str().into(),
+        &remote.title.as_str().into(),
+    );
+    let merged = Side {
+        done: done.as_bool().unwrap_or(local.done),
+        title: title.as_str().unwrap_or(&local.title).to_string(),
+    };
+    Plan {
+        push: merged != *remote,
+        pull: merged != *local,
+        conflict,
+        agreed: merged,
+    }
+}
+
+/// The latest link of each task.
+fn links(frames: &[Frame]) -> HashMap<String, Link> {
+    frames
+        .iter()
+        .filter(|frame| frame.topic == LINK_TOPIC)
+        .filter_map(|frame| serde_json::from_value::<Link>(frame.meta.clone()?).ok())
+        .map(|link| (link.task_id.clone(), link))
+        .collect()
+}
+
+fn authorize(request: reqwest::RequestBuilder, sync: &TrackerSync) -> reqwest::RequestBuilder {
+    let request = request.bearer_auth(&sync.token);
+    match sync.tracker {
+        Tracker::GitHub => request.header("Accept", "application/vnd.github+json"),
+        Tracker::Todoist => request,
+    }
+}
+
+async fn send(request: reqwest::RequestBuilder, sync: &TrackerSync) -> Result<Value, String> {
+    let response = authorize(request, sync)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to reach {:?}: {e}", sync.tracker))?;
+    // Todoist answers some calls with no content
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read {:?} response: {e}", sync.tracker))?;
+    if body.is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_str(&body).map_err(|e| format!("Invalid {:?} response: {e}", sync.tracker))
+}
+
+/// Creates the remote copy of a task, returning its id and url.
+async fn create(sync: &TrackerSync, side: &Side) -> Result<(String, Option<String>), String> {
+    let client = crate::web::client();
+    let created = match sync.tracker {
+        Tracker::GitHub => {
+            let url = format!("{GITHUB_API}/repos/{}/issues", sync.target);
+            send(client.post(url).json(&json!({ "title": side.title })), sync).await?
+        }
+        Tracker::Todoist => {
+            let mut body = json!({ "content": side.title });
+            if !sync.target.is_empty() {
+                body["project_id"] = sync.target.clone().into();
+            }
+            send(
+                client.post(format!("{TODOIST_API}/tasks")).json(&body),
+                sync,
+            )
+            .await?
+        }
+    };
+    let id = match sync.tracker {
+        Tracker::GitHub => created["number"].as_u64().map(|number| number.to_string()),
+        Tracker::Todoist => created["id"].as_str().map(String::from),
+    }
+    .ok_or_else(|| format!("{:?} returned no id", sync.tracker))?;
+    let url = created["html_url"]
+        .as_str()
+        .or(created["url"].as_str())
+        .map(String::from);
+    if side.done {
+        update(sync, &id, side).await?;
+    }
+    Ok((id, url))
+}
+
+async fn update(sync: &TrackerSync, id: &str, side: &Side) -> Result<(), String> {
+    let client = crate::web::client();
+    match sync.tracker {
+        Tracker::GitHub => {
+            let url = format!("{GITHUB_API}/repos/{}/issues/{id}", sync.target);
+            let state = if side.done { "closed" } else { "open" };
+            let body = json!({ "title": side.title, "state": state });
+            send(client.patch(url).json(&body), sync).await?;
+        }
+        Tracker::Todoist => {
+            let url = format!("{TODOIST_API}/tasks/{id}");
+            let body = json!({ "content": side.title });
+            send(client.post(&url).json(&body), sync).await?;
+            let action = if side.done { "close" } else { "reopen" };
+            send(client.post(format!("{url}/{action}")), sync).await?;
+        }
+    }
+    Ok(())
+}
+
+/// The remote copy's state. Todoist only serves open tasks, so one it no longer has is
+/// taken as done with the title last agreed on.
+async fn fetch(sync: &TrackerSync, id: &str, agreed: &Side) -> Result<Side, String> {
+    let client = crate::web::client();
+    match sync.tracker {
+        Tracker::GitHub => {
+            let url = format!("{GITHUB_API}/repos/{}/issues/{id}", sync.target);
+            let issue = send(client.get(url), sync).await?;
+            Ok(Side {
+                done: issue["state"] == "closed",
+                title: issue["title"].as_str().unwrap_or_default().to_string(),
+            })
+        }
+        Tracker::Todoist => {
+            let request = authorize(client.get(format!("{TODOIST_API}/tasks/{id}")), sync);
+            let response = request
+                .send()
+                .await
+                .map_err(|e| format!("Failed to reach Todoist: {e}"))?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(Side {
+                    done: true,
+                    title: agreed.title.clone(),
+                });
+            }
+            let task: Value = response
+                .error_for_status()
+                .map_err(|e| format!("Failed to reach Todoist: {e}"))?
+                .json()
+                .await
+                .map_err(|e| format!("Invalid Todoist response: {e}"))?;
+            Ok(Side {
+                done: task["is_completed"].as_bool().unwrap_or(false),
+                title: task["content"].as_str().unwrap_or_default().to_string(),
+            })
+        }
+    }
+}
+
+fn local_side(task: &Task) -> Option<Side> {
+    let text = task.content.as_deref().unwrap_or_default();
+    (!is_sealed(text)).then(|| Side {
+        done: task.done,
+        title: text.trim().to_string(),
+    })
+}
+
+async fn record_link(
+    store: &Store,
+    task: &Task,
+    sync: &TrackerSync,
+    external_id: &str,
+    url: Option<&str>,
+    agreed: &Side,
+) -> Result<Frame, String> {
+    let meta = json!({
+        "yak_id": task.yak_id,
+        "task_id": task.id,
+        "tracker": sync.tracker,
+        "external_id": external_id,
+        "url": url,
+        "agreed": agreed,
+    });
+    append_frame(store, LINK_TOPIC, None, Some(meta)).await
+}
+
+/// Pushes new tasks in `yak_id` to its tracker, then reconciles every linked task with its
+/// remote copy. Only tasks created on this device are pushed, so two synced devices don't
+/// both create a copy.
+async fn sync_yak(
+    app: &AppHandle,
+    store: &Store,
+    yak_id: &str,
+    sync: &TrackerSync,
+) -> Result<TaskSyncReport, String> {
+    let _syncing = SYNCING.lock().await;
+    let frames = read_all_frames(store).await;
+    let mut projection = Projection::from_frames(&frames);
+    projection.resolve_content(store).await;
+    let links = links(&frames);
+    let synced: HashSet<String> = frames
+        .iter()
+        .filter(|frame| frame.topic == "task.create" && crate::sync::origin(frame).is_some())
+        .map(|frame| frame.id.to_string())
+        .collect();
+    drop(frames);
+
+    let mut report = TaskSyncReport::default();
+    let mut appended = Vec::new();
+    let tasks = projection
+        .tasks
+        .values()
+        .filter(|task| task.yak_id == yak_id);
+    for task in tasks {
+        let Some(local) = local_side(task) else {
+            continue;
+        };
+        let Some(link) = links.get(&task.id) else {
+            let before = sync.since.as_ref().is_some_and(|since| task.id < *since);
+            if before || synced.contains(&task.id) || local.title.is_empty() {
+                continue;
+            }
+            let (external_id, url) = create(sync, &local).await?;
+            let _guard = begin_write(app)?;
+            appended
+                .push(record_link(store, task, sync, &external_id, url.as_deref(), &local).await?);
+            report.pushed += 1;
+            continue;
+        };
+
+        let remote = fetch(sync, &link.external_id, &link.agreed).await?;
+        let plan = reconcile(&link.agreed, &local, &remote, sync.prefer);
+        if plan.push {
+            update(sync, &link.external_id, &plan.agreed).await?;
+            report.pushed += 1;
+        }
+        let _guard = begin_write(app)?;
+        if plan.pull {
+            let mut meta = json!({ "yak_id": yak_id, "task_id": task.id, "source": SOURCE });
+            if plan.agreed.done != local.done {
+                meta["done"] = plan.agreed.done.into();
+            }
+            let content =
+                (plan.agreed.title != local.title).then_some(plan.agreed.title.as_bytes());
+            appended.push(append_frame(store, "task.update", content, Some(meta)).await?);
+            report.pulled += 1;
+        }
+        if plan.conflict {
+            report.conflicts += 1;
+            let conflict = Conflict {
+                task_id: task.id.clone(),
+                local: local.clone(),
+                remote: remote.clone(),
+                kept: plan.agreed.clone(),
+            };
+            if let Err(e) = app.emit("task-sync-conflict", &conflict) {
+                eprintln!("Failed to emit task sync conflict: {e}");
+            }
+        }
+        if plan.agreed != link.agreed {
+            let url = link.url.as_deref();
+            appended
+                .push(record_link(store, task, sync, &link.external_id, url, &plan.agreed).await?);
+        }
+    }
+    let _ = emit_frames(app, &appended);
+    Ok(report)
+}
+
+fn secrets_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(profiles::data_dir(app)?.join(SECRETS_FILE))
+}
+
+/// API tokens by yak id.
+fn load_secrets(path: &Path) -> HashMap<String, String> {
+    std::fs::read(path)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_secrets(path: &Path, config: &TaskSyncConfig) -> Result<(), String> {
+    let tokens: HashMap<&String, &String> = config
+        .yaks
+        .iter()
+        .map(|(yak_id, sync)| (yak_id, &sync.token))
+        .collect();
+    let json = serde_json::to_vec(&tokens).map_err(|e| e.to_string())?;
+    profiles::write_private(path, &json)
+        .map_err(|e| format!("Failed to save task sync tokens: {e}"))
+}
+
+/// `config` with its tokens. Tokens saved in the frame by an earlier version are moved out,
+/// and the frame saved again without them.
+fn with_tokens(
+    path: &Path,
+    store: &Store,
+    mut config: TaskSyncConfig,
+) -> Result<TaskSyncConfig, String> {
+    if config.yaks.values().any(|sync| !sync.token.is_empty()) {
+        save_secrets(path, &config)?;
+        save_setting(store, CONFIG_TOPIC, &config)?;
+        return Ok(config);
+    }
+    let mut tokens = load_secrets(path);
+    for (yak_id, sync) in config.yaks.iter_mut() {
+        sync.token = tokens.remove(yak_id).unwrap_or_default();
+    }
+    Ok(config)
+}
+
+fn tokens_for(app: &AppHandle, store: &Store, config: TaskSyncConfig) -> TaskSyncConfig {
+    match secrets_path(app).and_then(|path| with_tokens(&path, store, config)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load task sync tokens: {e}");
+            TaskSyncConfig::default()
+        }
+    }
+}
+
+async fn load_config(path: &Path, store: &Store) -> Result<TaskSyncConfig, String> {
+    with_tokens(path, store, load_setting(store, CONFIG_TOPIC).await)
+}
+
+async fn sync_all(app: &AppHandle, store: &Store, config: &TaskSyncConfig) {
+    for (yak_id, sync) in &config.yaks {
+        match sync_yak(app, store, yak_id, sync).await {
+            Ok(_) => health::ok(app, "task_sync"),
+            Err(e) => {
+                eprintln!("Task sync for yak {yak_id} failed: {e}");
+                health::error(app, "task_sync", &e);
+            }
+        }
+    }
+}
+
+/// Syncs a yak as soon as one of its tasks is created or changed here, and every yak set up
+/// for task sync every few minutes to bring in remote changes.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(options).await;
+    let mut config = TaskSyncConfig::default();
+    let mut tasks: HashMap<String, String> = HashMap::new();
+    let mut live = false;
+    let mut poll = tokio::time::interval(POLL);
+    loop {
+        let frame = tokio::select! {
+            frame = rx.recv() => match frame {
+                Some(frame) => frame,
+                None => break,
+            },
+            _ = poll.tick(), if live => {
+                sync_all(&app, &store, &config).await;
+                continue;
+            }
+        };
+        match frame.topic.as_str() {
+            // Tokens are only looked up for the config that's current, not each one replayed
+            "xs.threshold" => {
+                live = true;
+                config = tokens_for(&app, &store, config);
+            }
+            CONFIG_TOPIC => {
+                config = latest_setting(std::slice::from_ref(&frame), CONFIG_TOPIC);
+                if live {
+                    config = tokens_for(&app, &store, config);
+                }
+            }
+            "task.create" => {
+                if let Some(yak_id) = meta_str(&frame, "yak_id") {
+                    tasks.insert(frame.id.to_string(), yak_id.to_string());
+                }
+            }
+            _ => {}
+        }
+        let changed = matches!(frame.topic.as_str(), "task.create" | "task.update");
+        let ours = meta_str(&frame, "source") == Some(SOURCE);
+        if !live || !changed || ours || crate::sync::origin(&frame).is_some() {
+            continue;
+        }
+        let yak_id = meta_str(&frame, "yak_id").map(String::from).or_else(|| {
+            let task_id = meta_str(&frame, "task_id")?;
+            tasks.get(task_id).cloned()
+        });
+        let Some((yak_id, sync)) =
+            yak_id.and_then(|yak_id| Some((yak_id.clone(), config.yaks.get(&yak_id)?.clone())))
+        else {
+            continue;
+        };
+        let (app, store) = (app.clone(), store.clone());
+        tokio::spawn(async move {
+            if let Err(e) = sync_yak(&app, &store, &yak_id, &sync).await {
+                eprintln!("Task sync for yak {yak_id} failed: {e}");
+                health::error(&app, "task_sync", &e);
+            }
+        });
+    }
+}
+
+/// Unlinks every yak from its tracker in a forked store, so the fork doesn't push tasks too.
+pub(crate) fn detach(store: &Store, frames: &[Frame]) -> Result<(), String> {
+    let config: TaskSyncConfig = latest_setting(frames, CONFIG_TOPIC);
+    if !config.yaks.is_empty() {
+        save_setting(store, CONFIG_TOPIC, &TaskSyncConfig::default())?;
+    }
+    Ok(())
+}
+
+/// Keeps a yak's tasks in step with GitHub issues or Todoist tasks, or with `None` stops.
+/// Tasks already in the yak stay local; new ones are pushed, and done/open and title
+/// changes on either side are brought over. An empty token keeps the one saved before.
+#[tauri::command]
+pub async fn configure_task_sync(
+    store: State<'_, Store>,
+    app: AppHandle,
+    yak_id: String,
+    sync: Option<TrackerSync>,
+) -> Result<TaskSyncConfig, String> {
+    let path = secrets_path(&app)?;
+    let mut config = load_config(&path, &store).await?;
+    match sync {
+        Some(mut sync) => {
+            if sync.token.trim().is_empty() {
+                sync.token = config
+                    .yaks
+                    .get(&yak_id)
+                    .map(|sync| sync.token.clone())
+                    .filter(|token| !token.is_empty())
+                    .ok_or("Task sync needs an API token")?;
+            }
+            if sync.tracker == Tracker::GitHub && sync.target.split('/').count() != 2 {
+                return Err("GitHub task sync needs a repository as owner/repo".to_string());
+            }
+            let previous = config.yaks.get(&yak_id).and_then(|sync| sync.since.clone());
+            sync.since = previous.or_else(|| Some(scru128::new().to_string()));
+            config.yaks.insert(yak_id, sync);
+        }
+        None => {
+            config.yaks.remove(&yak_id);
+        }
+    }
+    save_secrets(&path, &config)?;
+    save_setting(&store, CONFIG_TOPIC, &config)?;
+    Ok(config)
+}
+
+#[tauri::command]
+pub async fn get_task_sync(store: State<'_, Store>) -> Result<TaskSyncConfig, String> {
+    Ok(load_setting(&store, CONFIG_TOPIC).await)
+}
+
+/// Syncs a yak's tasks now rather than at the next poll.
+#[tauri::command]
+pub async fn sync_tasks_now(
+    app: AppHandle,
+    store: State<'_, Store>,
+    yak_id: String,
+) -> Result<TaskSyncReport, String> {
+    let config = load_config(&secrets_path(&app)?, &store).await?;
+    let sync = config
+        .yaks
+        .get(&yak_id)
+        .ok_or("Task sync isn't set up for this yak")?;
+    sync_yak(&app, &store, &yak_id, sync).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_all_frames;
+
+    fn side(done: bool, title: &str) -> Side {
+        Side {
+            done,
+            title: title.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let agreed = side(false, "Call Ana");
+
+        let same = reconcile(&agreed, &agreed, &agreed, Prefer::Local);
+        assert!(!same.push && !same.pull && !same.conflict);
+
+        // Checked off here: push it
+        let done_here = reconcile(&agreed, &side(true, "Call Ana"), &agreed, Prefer::Local);
+        assert_eq!(done_here.agreed, side(true, "Call Ana"));
+        assert!(done_here.push && !done_here.pull);
+
+        // Closed remotely while renamed here: both changes are kept
+        let both = reconcile(
+            &agreed,
+            &side(false, "Call Ana today"),
+            &side(true, "Call Ana"),
+            Prefer::Local,
+        );
+        assert_eq!(both.agreed, side(true, "Call Ana today"));
+        assert!(both.push && both.pull && !both.conflict);
+
+        // Renamed differently on both sides: the preferred side wins
+        let local = side(false, "Call Ana at 3");
+        let remote = side(false, "Email Ana");
+        let conflict = reconcile(&agreed, &local, &remote, Prefer::Remote);
+        assert!(conflict.conflict);
+        assert_eq!(conflict.agreed, remote);
+        assert!(!conflict.push && conflict.pull);
+    }
+
+    #[tokio::test]
+    async fn test_tokens_move_out_of_the_store() {
+        let (dir, store) = crate::testing::store();
+        // As an earlier version saved it, token and all
+        let sync = json!({ "tracker": "github", "token": "ghp_x", "target": "ada/notes" });
+        crate::testing::append(
+            &store,
+            CONFIG_TOPIC,
+            Some(json!({ "yaks": { "yak": sync } })),
+        );
+        let path = dir.path().join(SECRETS_FILE);
+
+        let config = load_config(&path, &store).await.unwrap();
+        assert_eq!(config.yaks["yak"].token, "ghp_x");
+        let frames = read_all_frames(&store).await;
+        let meta = frames.last().unwrap().meta.as_ref().unwrap();
+        assert!(meta["yaks"]["yak"].get("token").is_none());
+
+        let config = load_config(&path, &store).await.unwrap();
+        assert_eq!(config.yaks["yak"].token, "ghp_x");
+        assert_eq!(read_all_frames(&store).await.len(), frames.len());
+    }
+}
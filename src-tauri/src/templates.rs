@@ -0,0 +1,260 @@
+use chrono::{DateTime, Local};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager, State};
+use xs::store::{Frame, Store};
+
+use crate::locks::LockState;
+use crate::{
+    append_batch_to_store, append_frame, read_all_frames, shutdown, windows, AppendRequest,
+};
+
+pub(crate) const SAVE_TOPIC: &str = "template.save";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Template {
+    pub name: String,
+    pub content: String,
+    /// Merged into the meta of notes made from the template
+    pub meta: serde_json::Value,
+    /// Id of the frame that saved this version
+    pub id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Expanded {
+    pub content: String,
+    /// Where `{{cursor}}` was, as a UTF-16 offset like the editor's selection uses
+    pub cursor: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewNote {
+    pub note_id: String,
+    pub cursor: Option<usize>,
+}
+
+fn variable() -> &'static Regex {
+    static VARIABLE: OnceLock<Regex> = OnceLock::new();
+    VARIABLE.get_or_init(|| Regex::new(r"\{\{\s*(\w+)(?::([^}]*))?\s*\}\}").unwrap())
+}
+
+/// Expands `{{name}}` variables. Substitutions win over the built-ins: `date`, `time`,
+/// `datetime`, `weekday` and `year`, which also take a chrono format, as in
+/// `{{date:%d %B}}`. `{{cursor}}` is removed and its position reported. Unknown variables
+/// are left as written.
+pub(crate) fn expand(
+    template: &str,
+    now: DateTime<Local>,
+    substitutions: &HashMap<String, String>,
+) -> Expanded {
+    let mut content = String::with_capacity(template.len());
+    let mut cursor = None;
+    let mut last = 0;
+    for captures in variable().captures_iter(template) {
+        let whole = captures.get(0).unwrap();
+        content.push_str(&template[last..whole.start()]);
+        last = whole.end();
+        let name = &captures[1];
+        if let Some(value) = substitutions.get(name) {
+            content.push_str(value);
+            continue;
+        }
+        let default_format = match name {
+            "cursor" => {
+                cursor.get_or_insert_with(|| content.encode_utf16().count());
+                continue;
+            }
+            "date" => "%Y-%m-%d",
+            "time" => "%H:%M",
+            "datetime" => "%Y-%m-%d %H:%M",
+            "weekday" => "%A",
+            "year" => "%Y",
+            _ => {
+                content.push_str(whole.as_str());
+                continue;
+            }
+        };
+        let format = captures
+            .get(2)
+            .map_or(default_format, |format| format.as_str().trim());
+        content.push_str(&now.format(format).to_string());
+    }
+    content.push_str(&template[last..]);
+    Expanded { content, cursor }
+}
+
+/// The latest saved version of each template, by name.
+pub(crate) async fn templates(store: &Store) -> BTreeMap<String, Template> {
+    let frames: Vec<Frame> = read_all_frames(store)
+        .await
+        .into_iter()
+        .filter(|frame| frame.topic == SAVE_TOPIC)
+        .collect();
+    let mut templates = BTreeMap::new();
+    for frame in frames {
+        let Some(meta) = frame.meta.clone() else {
+            continue;
+        };
+        let Some(name) = meta.get("name").and_then(|name| name.as_str()) else {
+            continue;
+        };
+        let content = match &frame.hash {
+            Some(hash) => match store.cas_read(hash).await {
+                Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                Err(e) => {
+                    eprintln!("Failed to read template {name}: {e}");
+                    continue;
+                }
+            },
+            None => String::new(),
+        };
+        templates.insert(
+            name.to_string(),
+            Template {
+                name: name.to_string(),
+                content,
+                meta: meta.get("meta").cloned().unwrap_or_default(),
+                id: frame.id.to_string(),
+            },
+        );
+    }
+    templates
+}
+
+/// Saves a template, replacing any earlier one of the same name.
+#[tauri::command]
+pub async fn save_template(
+    store: State<'_, Store>,
+    app: AppHandle,
+    name: String,
+    content: String,
+    meta: Option<serde_json::Value>,
+) -> Result<Template, String> {
+    let _guard = shutdown::begin_write(&app)?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Template name can't be empty".to_string());
+    }
+    let meta = meta.unwrap_or_default();
+    let frame = append_frame(
+        &store,
+        SAVE_TOPIC,
+        (!content.is_empty()).then_some(content.as_bytes()),
+        Some(serde_json::json!({ "name": name, "meta": meta })),
+    )
+    .await?;
+    Ok(Template {
+        name,
+        content,
+        meta,
+        id: frame.id.to_string(),
+    })
+}
+
+#[tauri::command]
+pub async fn list_templates(store: State<'_, Store>) -> Result<Vec<Template>, String> {
+    Ok(templates(&store).await.into_values().collect())
+}
+
+/// The `note.create` request for a note made from template `name` at `now`, with where the
+/// editor's cursor should go.
+pub(crate) async fn note_request(
+    store: &Store,
+    name: &str,
+    yak_id: &str,
+    substitutions: &HashMap<String, String>,
+    now: DateTime<Local>,
+) -> Result<(AppendRequest, Option<usize>), String> {
+    let template = templates(store)
+        .await
+        .remove(name)
+        .ok_or_else(|| format!("Template not found: {name}"))?;
+    let expanded = expand(&template.content, now, substitutions);
+
+    let mut meta: HashMap<String, serde_json::Value> = match template.meta {
+        serde_json::Value::Object(fields) => fields.into_iter().collect(),
+        _ => HashMap::new(),
+    };
+    meta.insert("yak_id".to_string(), yak_id.into());
+    meta.insert("template".to_string(), name.into());
+    let request = AppendRequest {
+        topic: "note.create".to_string(),
+        content: expanded.content,
+        meta: Some(meta),
+    };
+    Ok((request, expanded.cursor))
+}
+
+/// Creates a note in `yak_id` from a template, e.g. a daily or meeting note. Returns the
+/// note and where the editor's cursor should go.
+#[tauri::command]
+pub async fn new_from_template(
+    app: AppHandle,
+    store: State<'_, Store>,
+    name: String,
+    yak_id: String,
+    substitutions: Option<HashMap<String, String>>,
+) -> Result<NewNote, String> {
+    let _guard = shutdown::begin_write(&app)?;
+    let (request, cursor) = note_request(
+        &store,
+        &name,
+        &yak_id,
+        &substitutions.unwrap_or_default(),
+        Local::now(),
+    )
+    .await?;
+    let request = app.state::<LockState>().seal_request(request)?;
+    let frames = append_batch_to_store(&store, vec![request]).await?;
+    windows::emit_frames(&app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))?;
+    Ok(NewNote {
+        note_id: frames[0].id.to_string(),
+        cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_expand() {
+        let now = Local.with_ymd_and_hms(2024, 3, 1, 9, 30, 0).unwrap();
+        let substitutions = HashMap::from([("attendees".to_string(), "Ana, Bo".to_string())]);
+        let expanded = expand(
+            "# {{ date }} ({{weekday}}, {{date:%d %B}})\nWith: {{attendees}}\n- é {{cursor}}\n{{unknown}}",
+            now,
+            &substitutions,
+        );
+        assert_eq!(
+            expanded.content,
+            "# 2024-03-01 (Friday, 01 March)\nWith: Ana, Bo\n- é \n{{unknown}}"
+        );
+        assert_eq!(expanded.cursor, Some(50));
+    }
+
+    #[tokio::test]
+    async fn test_latest_template_wins() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        for content in ["v1", "v2"] {
+            append_frame(
+                &store,
+                SAVE_TOPIC,
+                Some(content.as_bytes()),
+                Some(serde_json::json!({ "name": "daily", "meta": { "tags": ["daily"] } })),
+            )
+            .await
+            .unwrap();
+        }
+        let templates = templates(&store).await;
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates["daily"].content, "v2");
+        assert_eq!(templates["daily"].meta["tags"][0], "daily");
+    }
+}
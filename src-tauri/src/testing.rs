@@ -0,0 +1,27 @@
+//! Fixtures shared by the unit tests.
+
+use serde_json::Value;
+use tempfile::TempDir;
+use xs::store::{Frame, Store, ZERO_CONTEXT};
+
+/// A store in a new temp dir, removed when the returned dir is dropped.
+pub(crate) fn store() -> (TempDir, Store) {
+    let dir = tempfile::tempdir().unwrap();
+    let store = Store::new(dir.path().to_path_buf());
+    (dir, store)
+}
+
+/// Appends a frame with no content straight to `store`, without the checks `append_frame`
+/// makes, so tests can set up whatever log the code under test reads.
+pub(crate) fn append(store: &Store, topic: &str, meta: Option<Value>) -> Frame {
+    store
+        .append(Frame {
+            id: scru128::new(),
+            context_id: ZERO_CONTEXT,
+            topic: topic.to_string(),
+            hash: None,
+            meta,
+            ttl: None,
+        })
+        .unwrap()
+}
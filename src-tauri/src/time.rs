@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Limits a query by time, e.g. which notes `summarize_yak` covers; both ends are optional.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    pub(crate) fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.from.map_or(true, |from| time >= from) && self.to.map_or(true, |to| time < to)
+    }
+}
+
+/// The creation time embedded in a SCRU128 frame id.
+pub(crate) fn from_id(id: &str) -> Option<DateTime<Utc>> {
+    let id = id.parse::<scru128::Scru128Id>().ok()?;
+    DateTime::from_timestamp_millis(i64::try_from(id.timestamp()).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_id() {
+        let before = Utc::now().timestamp_millis();
+        let time = from_id(&scru128::new().to_string()).unwrap();
+        assert!(time.timestamp_millis() >= before);
+        assert!(from_id("not an id").is_none());
+    }
+
+    #[test]
+    fn test_time_range() {
+        let time = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let range = TimeRange {
+            from: Some(time("2025-01-01T00:00:00Z")),
+            to: None,
+        };
+        assert!(range.contains(time("2025-06-01T00:00:00Z")));
+        assert!(!range.contains(time("2024-12-31T00:00:00Z")));
+        assert!(TimeRange::default().contains(time("1999-01-01T00:00:00Z")));
+    }
+}
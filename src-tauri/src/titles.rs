@@ -0,0 +1,92 @@
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::projection::Projection;
+use crate::shutdown::begin_write;
+use crate::windows::emit_frame;
+use crate::{append_frame, read_all_frames};
+
+const TOPIC: &str = "note.rename";
+/// Longer titles are cut, like derived ones.
+const MAX_CHARS: usize = 200;
+
+pub(crate) async fn append_rename(
+    store: &Store,
+    yak_id: &str,
+    note_id: &str,
+    title: &str,
+) -> Result<Frame, String> {
+    let meta = serde_json::json!({ "yak_id": yak_id, "note_id": note_id, "title": title });
+    append_frame(store, TOPIC, None, Some(meta)).await
+}
+
+/// Gives a note a title of its own, which lists, search and exports use in place of the
+/// one derived from its first line, and which stays through later edits. A blank title
+/// goes back to the derived one.
+#[tauri::command]
+pub async fn rename_note(
+    app: AppHandle,
+    store: State<'_, Store>,
+    frame_id: String,
+    title: String,
+) -> Result<Frame, String> {
+    let title: String = title.trim().chars().take(MAX_CHARS).collect();
+    if title.contains(['\n', '\r']) {
+        return Err("A title has to fit on one line".to_string());
+    }
+    let _guard = begin_write(&app)?;
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let note_id = projection.resolve(&frame_id);
+    let note = projection
+        .notes
+        .get(&note_id)
+        .ok_or_else(|| format!("Note not found: {frame_id}"))?;
+    let frame = append_rename(&store, &note.yak_id, &note_id, &title).await?;
+    let _ = emit_frame(&app, &frame);
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::note_title;
+    use crate::import::{add_note, create_yak};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_rename_follows_edits() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak_id = create_yak(&store, "Work").await.unwrap();
+        let note = add_note(&store, &yak_id, "# Draft\nplans", json!({}))
+            .await
+            .unwrap();
+        let note_id = note.id.to_string();
+        append_rename(&store, &yak_id, &note_id, "Roadmap")
+            .await
+            .unwrap();
+        let meta = json!({ "yak_id": yak_id, "note_id": note_id });
+        let edit = append_frame(
+            &store,
+            "note.edit",
+            Some(b"# Q3\nplans".as_slice()),
+            Some(meta),
+        )
+        .await
+        .unwrap();
+
+        let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+        projection.resolve_content(&store).await;
+        let edited = &projection.notes[&edit.id.to_string()];
+        assert_eq!(edited.title.as_deref(), Some("Roadmap"));
+        assert_eq!(note_title(edited), "Roadmap");
+
+        append_rename(&store, &yak_id, &note_id, " ").await.unwrap();
+        let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+        projection.resolve_content(&store).await;
+        let edited = &projection.notes[&edit.id.to_string()];
+        assert_eq!(edited.title, None);
+        assert_eq!(note_title(edited), "Q3");
+    }
+}
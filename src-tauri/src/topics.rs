@@ -0,0 +1,66 @@
+/// Topic namespaces the app writes for itself: store internals, settings, and the
+/// bookkeeping of sync, handlers, schedulers and other automation. Everything else (yaks,
+/// notes, tasks, tags, attachments and the like) is user content.
+const SYSTEM_TOPIC_PREFIXES: &[&str] = &[
+    "xs.",
+    "sync.",
+    "device.",
+    "handler.",
+    "capture.",
+    "plugin.",
+    "mcp.",
+    "ai.",
+    "extract.",
+    "enrich.",
+    "export.",
+    "publish.",
+    "draft.",
+    "store.",
+    "schema.",
+    "recovery.",
+    "compaction.",
+    "retention.",
+    "shortcut.",
+    "autostart.",
+    "locale.",
+    "lock.",
+    "webhook.",
+    "integration.",
+    "integrations.",
+    "inbox.",
+    "presence.",
+];
+
+/// Whether `topic` belongs to the app rather than to what someone wrote.
+pub(crate) fn is_system(topic: &str) -> bool {
+    SYSTEM_TOPIC_PREFIXES
+        .iter()
+        .any(|prefix| topic.starts_with(prefix))
+}
+
+/// Rejects system topics in appends made on someone's behalf, from the UI or `yaks append`;
+/// the app writes those itself through its own commands.
+pub(crate) fn check_user(topic: &str) -> Result<(), String> {
+    if is_system(topic) {
+        return Err(format!("Topic {topic} is reserved for the app"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_system() {
+        assert!(is_system("xs.threshold"));
+        assert!(is_system("handler.log"));
+        assert!(is_system("sync.config"));
+        assert!(!is_system("note.create"));
+        assert!(!is_system("yak.lock"));
+        // Only whole namespaces count
+        assert!(!is_system("syncopation.note"));
+        assert!(check_user("retention.tombstone").is_err());
+        assert!(check_user("task.create").is_ok());
+    }
+}
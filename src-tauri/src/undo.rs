@@ -0,0 +1,362 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store, ZERO_CONTEXT};
+
+use crate::projection::Projection;
+use crate::provenance::{self, Source};
+use crate::read_all_frames;
+use crate::windows::emit_frame;
+
+/// Meta key on a compensating frame naming the frame it undoes.
+const UNDO_OF: &str = "undo_of";
+/// Meta key on a frame re-applying a change, naming the undo frame it reverses.
+const REDO_OF: &str = "redo_of";
+
+/// The frame that reverses a change, computed from the state just before the change.
+#[derive(Debug, Clone)]
+struct Inverse {
+    topic: String,
+    hash: Option<ssri::Integrity>,
+    meta: Value,
+}
+
+#[derive(Debug)]
+struct Entry {
+    frame_id: String,
+    inverse: Inverse,
+}
+
+#[derive(Debug, Default)]
+struct Stacks {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UndoStatus {
+    pub undo_depth: usize,
+    pub redo_depth: usize,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+fn meta_bool(frame: &Frame, key: &str) -> Option<bool> {
+    frame.meta.as_ref()?.get(key)?.as_bool()
+}
+
+/// The yak a frame's change belongs to and how to reverse it, or `None` for frames that
+/// aren't user edits (or change nothing) and so can't be undone.
+fn inverse(projection: &Projection, frame: &Frame) -> Option<(String, Inverse)> {
+    let note = |key: &str| {
+        meta_str(frame, key)
+            .map(|id| projection.resolve(id))
+            .and_then(|id| projection.notes.get(&id))
+    };
+    let task = || meta_str(frame, "task_id").and_then(|id| projection.tasks.get(id));
+    let inverse = |topic: &str, hash: Option<ssri::Integrity>, meta: Value| Inverse {
+        topic: topic.to_string(),
+        hash,
+        meta,
+    };
+
+    match frame.topic.as_str() {
+        "note.create" => {
+            let yak_id = meta_str(frame, "yak_id")?.to_string();
+            let meta =
+                json!({ "yak_id": yak_id, "note_id": frame.id.to_string(), "archived": true });
+            Some((yak_id, inverse("note.archive", None, meta)))
+        }
+        "note.edit" => {
+            // The revision this edit replaced, not the head, so an undo restores its content
+            let previous = projection.notes.get(meta_str(frame, "note_id")?)?;
+            let meta = json!({ "yak_id": previous.yak_id, "note_id": frame.id.to_string() });
+            Some((
+                previous.yak_id.clone(),
+                inverse("note.edit", previous.hash.clone(), meta),
+            ))
+        }
+        "tag.add" | "tag.remove" => {
+            let note = note("note_id")?;
+            let tag = meta_str(frame, "tag")?;
+            let adding = frame.topic == "tag.add";
+            if note.tags.contains(tag) == adding {
+                return None;
+            }
+            let topic = if adding { "tag.remove" } else { "tag.add" };
+            let meta = json!({ "yak_id": note.yak_id, "note_id": note.id, "tag": tag });
+            Some((note.yak_id.clone(), inverse(topic, None, meta)))
+        }
+        "note.pin" | "note.archive" => {
+            let note = note("note_id")?;
+            let (key, previous) = if frame.topic == "note.pin" {
+                ("pinned", note.pinned)
+            } else {
+                ("archived", note.archived)
+            };
+            if meta_bool(frame, key).unwrap_or(true) == previous {
+                return None;
+            }
+            let meta = json!({ "yak_id": note.yak_id, "note_id": note.id, key: previous });
+            Some((note.yak_id.clone(), inverse(&frame.topic, None, meta)))
+        }
+        "note.property" => {
+            let note = note("note_id")?;
+            let key = meta_str(frame, "key")?;
+            let previous = note.properties.get(key).cloned().unwrap_or(Value::Null);
+            let value = frame.meta.as_ref()?.get("value").unwrap_or(&Value::Null);
+            if *value == previous {
+                return None;
+            }
+            let meta = json!({
+                "yak_id": note.yak_id,
+                "note_id": note.id,
+                "key": key,
+                "value": previous,
+            });
+            Some((note.yak_id.clone(), inverse("note.property", None, meta)))
+        }
+        "note.rename" => {
+            let note = note("note_id")?;
+            let title = meta_str(frame, "title").map(str::trim).unwrap_or_default();
+            if title == note.title.as_deref().unwrap_or_default() {
+                return None;
+            }
+            let meta = json!({ "yak_id": note.yak_id, "note_id": note.id, "title": note.title });
+            Some((note.yak_id.clone(), inverse("note.rename", None, meta)))
+        }
+        "note.delete" | "note.restore" => {
+            let note = note("note_id")?;
+            let topic = if frame.topic == "note.delete" {
+                "note.restore"
+            } else {
+                "note.delete"
+            };
+            let meta = json!({ "yak_id": note.yak_id, "note_id": note.id });
+            Some((note.yak_id.clone(), inverse(topic, None, meta)))
+        }
+        "task.create" => {
+            let yak_id = meta_str(frame, "yak_id")?.to_string();
+            let meta = json!({ "yak_id": yak_id, "task_id": frame.id.to_string() });
+            Some((yak_id, inverse("task.delete", None, meta)))
+        }
+        "task.update" => {
+            let task = task()?;
+            let meta = json!({ "yak_id": task.yak_id, "task_id": task.id, "done": task.done });
+            let hash = frame.hash.as_ref().and(task.hash.clone());
+            Some((task.yak_id.clone(), inverse("task.update", hash, meta)))
+        }
+        "task.delete" => {
+            // Restored as a new task; later undo entries refer to it by the new id
+            let task = task()?;
+            let meta = json!({ "yak_id": task.yak_id, "note_id": task.note_id, "done": task.done });
+            Some((
+                task.yak_id.clone(),
+                inverse("task.create", task.hash.clone(), meta),
+            ))
+        }
+        "reminder.set" | "reminder.clear" => {
+            let (yak_id, target, previous) = match (task(), note("note_id")) {
+                (Some(task), _) => (&task.yak_id, json!({ "task_id": task.id }), &task.reminder),
+                (None, Some(note)) => (&note.yak_id, json!({ "note_id": note.id }), &note.reminder),
+                (None, None) => return None,
+            };
+            let mut meta = target;
+            meta["yak_id"] = yak_id.clone().into();
+            let topic = match previous {
+                Some(at) => {
+                    meta["at"] = at.clone().into();
+                    "reminder.set"
+                }
+                None => "reminder.clear",
+            };
+            Some((yak_id.clone(), inverse(topic, None, meta)))
+        }
+        _ => None,
+    }
+}
+
+/// Replays the log to find a yak's undo and redo stacks. Frames copied in by sync aren't
+/// this user's changes and are left off.
+fn stacks(frames: &[Frame], yak_id: &str) -> Stacks {
+    let mut projection = Projection::default();
+    let mut stacks = Stacks::default();
+    for frame in frames {
+        if crate::sync::origin(frame).is_none() {
+            if let Some((frame_yak_id, inverse)) = inverse(&projection, frame) {
+                if frame_yak_id == yak_id {
+                    let entry = Entry {
+                        frame_id: frame.id.to_string(),
+                        inverse,
+                    };
+                    if meta_str(frame, UNDO_OF).is_some() {
+                        stacks.undo.pop();
+                        stacks.redo.push(entry);
+                    } else if meta_str(frame, REDO_OF).is_some() {
+                        stacks.redo.pop();
+                        stacks.undo.push(entry);
+                    } else {
+                        stacks.undo.push(entry);
+                        stacks.redo.clear();
+                    }
+                }
+            }
+        }
+        projection.apply(frame);
+    }
+    stacks
+}
+
+impl Stacks {
+    fn status(&self) -> UndoStatus {
+        UndoStatus {
+            undo_depth: self.undo.len(),
+            redo_depth: self.redo.len(),
+        }
+    }
+}
+
+/// Appends the compensating frame for `entry`, tagged with `key` so replay can tell undos
+/// and redos from fresh changes.
+fn apply(store: &Store, entry: &Entry, key: &str) -> Result<Frame, String> {
+    let mut meta = entry.inverse.meta.clone();
+    meta[key] = entry.frame_id.clone().into();
+    let mut meta = Some(meta);
+    provenance::stamp(&mut meta, Source::Ui);
+    let frame = Frame {
+        id: scru128::new(),
+        context_id: ZERO_CONTEXT,
+        topic: entry.inverse.topic.clone(),
+        hash: entry.inverse.hash.clone(),
+        meta,
+        ttl: None,
+    };
+    crate::append_to_store(store, frame).map_err(|e| e.context("Failed to append frame").into())
+}
+
+async fn step(store: &Store, yak_id: &str, redo: bool) -> Result<(Frame, UndoStatus), String> {
+    let mut stacks = stacks(&read_all_frames(store).await, yak_id);
+    let (entry, key) = if redo {
+        (stacks.redo.pop().ok_or("Nothing to redo")?, REDO_OF)
+    } else {
+        (stacks.undo.pop().ok_or("Nothing to undo")?, UNDO_OF)
+    };
+    let frame = apply(store, &entry, key)?;
+    let status = if redo {
+        UndoStatus {
+            undo_depth: stacks.undo.len() + 1,
+            redo_depth: stacks.redo.len(),
+        }
+    } else {
+        UndoStatus {
+            undo_depth: stacks.undo.len(),
+            redo_depth: stacks.redo.len() + 1,
+        }
+    };
+    Ok((frame, status))
+}
+
+/// Reverses the most recent change in a yak by appending a compensating frame.
+#[tauri::command]
+pub async fn undo(
+    store: State<'_, Store>,
+    app: AppHandle,
+    yak_id: String,
+) -> Result<UndoStatus, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let (frame, status) = step(&store, &yak_id, false).await?;
+    emit_frame(&app, &frame).map_err(|e| format!("Failed to emit frame: {e}"))?;
+    Ok(status)
+}
+
+/// Re-applies the most recently undone change in a yak.
+#[tauri::command]
+pub async fn redo(
+    store: State<'_, Store>,
+    app: AppHandle,
+    yak_id: String,
+) -> Result<UndoStatus, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let (frame, status) = step(&store, &yak_id, true).await?;
+    emit_frame(&app, &frame).map_err(|e| format!("Failed to emit frame: {e}"))?;
+    Ok(status)
+}
+
+#[tauri::command]
+pub async fn undo_status(store: State<'_, Store>, yak_id: String) -> Result<UndoStatus, String> {
+    Ok(stacks(&read_all_frames(&store).await, &yak_id).status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_frame;
+    use tempfile::tempdir;
+
+    async fn current(store: &Store, yak_id: &str) -> (Option<String>, Vec<String>) {
+        let mut projection = Projection::from_frames(&read_all_frames(store).await);
+        projection.resolve_content(store).await;
+        let note = projection.current_notes(yak_id)[0].clone();
+        (note.content, note.tags.into_iter().collect())
+    }
+
+    #[tokio::test]
+    async fn test_undo_redo_edit_and_tag() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let yak = append_frame(&store, "yak.create", None, None)
+            .await
+            .unwrap();
+        let yak_id = yak.id.to_string();
+        let note = append_frame(
+            &store,
+            "note.create",
+            Some(b"one".as_slice()),
+            Some(json!({ "yak_id": yak_id })),
+        )
+        .await
+        .unwrap();
+        let note_id = note.id.to_string();
+        append_frame(
+            &store,
+            "note.edit",
+            Some(b"two".as_slice()),
+            Some(json!({ "yak_id": yak_id, "note_id": note_id })),
+        )
+        .await
+        .unwrap();
+        append_frame(
+            &store,
+            "tag.add",
+            None,
+            Some(json!({ "yak_id": yak_id, "note_id": note_id, "tag": "idea" })),
+        )
+        .await
+        .unwrap();
+
+        let status = step(&store, &yak_id, false).await.unwrap().1;
+        assert_eq!((status.undo_depth, status.redo_depth), (2, 1));
+        assert_eq!(current(&store, &yak_id).await, (Some("two".into()), vec![]));
+
+        step(&store, &yak_id, false).await.unwrap();
+        assert_eq!(current(&store, &yak_id).await, (Some("one".into()), vec![]));
+
+        let status = step(&store, &yak_id, true).await.unwrap().1;
+        assert_eq!((status.undo_depth, status.redo_depth), (2, 1));
+        assert_eq!(current(&store, &yak_id).await, (Some("two".into()), vec![]));
+
+        // A fresh change discards what's left to redo
+        append_frame(
+            &store,
+            "note.pin",
+            None,
+            Some(json!({ "yak_id": yak_id, "note_id": note_id, "pinned": true })),
+        )
+        .await
+        .unwrap();
+        let status = stacks(&read_all_frames(&store).await, &yak_id).status();
+        assert_eq!((status.undo_depth, status.redo_depth), (3, 0));
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashSet;
+use tauri::{AppHandle, State};
+use xs::store::{Frame, Store};
+
+use crate::projection::Projection;
+use crate::shutdown::begin_write;
+use crate::windows::emit_frame;
+use crate::{append_frame, read_all_frames, rules, sync};
+
+/// Marks the notes listed in its `note_ids` as read.
+pub(crate) const READ_TOPIC: &str = "note.read";
+
+/// Whether a new note came in from outside rather than being written here: captures (see
+/// `rules::source`) and notes synced in, including those of shared yaks. These start unread.
+pub(crate) fn ingested(frame: &Frame) -> bool {
+    rules::source(frame).is_some() || sync::origin(frame).is_some()
+}
+
+/// Marks notes as read, by any revision id. Returns how many were unread.
+#[tauri::command]
+pub async fn mark_read(
+    app: AppHandle,
+    store: State<'_, Store>,
+    frame_ids: Vec<String>,
+) -> Result<usize, String> {
+    let _guard = begin_write(&app)?;
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let mut seen = HashSet::new();
+    let note_ids: Vec<String> = frame_ids
+        .iter()
+        .map(|id| projection.resolve(id))
+        .filter(|id| projection.notes.get(id).is_some_and(|note| note.unread))
+        .filter(|id| seen.insert(id.clone()))
+        .collect();
+    if note_ids.is_empty() {
+        return Ok(0);
+    }
+    let meta = serde_json::json!({ "note_ids": note_ids });
+    let frame = append_frame(&store, READ_TOPIC, None, Some(meta)).await?;
+    let _ = emit_frame(&app, &frame);
+    Ok(note_ids.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_unread_counts() {
+        let (_dir, store) = testing::store();
+        let yak = append(&store, "yak.create", Some(json!({ "name": "inbox" })));
+        let yak_id = yak.id.to_string();
+        let clip = append(&store, "clip", Some(json!({ "yak_id": yak_id })));
+        let item = append(&store, "feed.item", Some(json!({ "yak_id": yak_id })));
+        let typed = append(&store, "note.create", Some(json!({ "yak_id": yak_id })));
+        let edit = json!({ "yak_id": yak_id, "note_id": item.id.to_string() });
+        let edit = append(&store, "note.edit", Some(edit));
+
+        let mut projection = Projection::from_frames(&read_all_frames(&store).await);
+        assert!(!projection.notes[&typed.id.to_string()].unread);
+        // Edits keep a note unread
+        assert!(projection.notes[&edit.id.to_string()].unread);
+        projection.count_unread();
+        assert_eq!(projection.yaks[&yak_id].unread, 2);
+
+        let read = json!({ "note_ids": [clip.id.to_string()] });
+        projection.apply(&append(&store, READ_TOPIC, Some(read)));
+        projection.count_unread();
+        assert_eq!(projection.yaks[&yak_id].unread, 1);
+        assert!(!projection.notes[&clip.id.to_string()].unread);
+    }
+}
@@ -0,0 +1,472 @@
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, State};
+use tokio::io::AsyncReadExt;
+use xs::store::{Frame, Store};
+
+use crate::import::add_note;
+use crate::windows::emit_frame;
+use crate::{append_frame, mime};
+
+const TOPIC: &str = "attachment.video";
+/// Videos are stored in pieces of this size, so playback can read just the part it needs.
+const CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+/// The most a single range request gets back; players ask again for the rest.
+const MAX_RESPONSE: u64 = 8 * 1024 * 1024;
+const FFMPEG: &str = "ffmpeg";
+const FFPROBE: &str = "ffprobe";
+const TIMEOUT: Duration = Duration::from_secs(60);
+/// Posters are scaled down to at most this wide.
+const POSTER_WIDTH: u32 = 640;
+
+/// What ffprobe reports about a video.
+#[derive(Debug, Default, PartialEq)]
+struct Probe {
+    duration_ms: Option<u64>,
+    width: Option<u64>,
+    height: Option<u64>,
+}
+
+fn parse_probe(output: &[u8]) -> Probe {
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(output) else {
+        return Probe::default();
+    };
+    let stream = &json["streams"][0];
+    Probe {
+        duration_ms: json["format"]["duration"]
+            .as_str()
+            .and_then(|seconds| seconds.parse::<f64>().ok())
+            .map(|seconds| (seconds * 1000.0).round() as u64),
+        width: stream["width"].as_u64(),
+        height: stream["height"].as_u64(),
+    }
+}
+
+async fn run(program: &str, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(TIMEOUT, output)
+        .await
+        .map_err(|_| format!("{program} timed out after {}s", TIMEOUT.as_secs()))?
+        .map_err(|e| format!("Failed to run {program} (is it installed?): {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+async fn probe(path: &str) -> Result<Probe, String> {
+    let output = run(
+        FFPROBE,
+        &[
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "format=duration:stream=width,height",
+            "-of",
+            "json",
+            path,
+        ],
+    )
+    .await?;
+    Ok(parse_probe(&output))
+}
+
+/// A JPEG frame from a second in, or from the start of very short videos.
+async fn poster(path: &str, duration_ms: Option<u64>) -> Result<Vec<u8>, String> {
+    let at = if duration_ms.unwrap_or_default() > 2000 {
+        "1"
+    } else {
+        "0"
+    };
+    let scale = format!("scale='min({POSTER_WIDTH},iw)':-2");
+    let jpeg = run(
+        FFMPEG,
+        &[
+            "-v",
+            "error",
+            "-ss",
+            at,
+            "-i",
+            path,
+            "-frames:v",
+            "1",
+            "-vf",
+            &scale,
+            "-f",
+            "image2",
+            "-c:v",
+            "mjpeg",
+            "-",
+        ],
+    )
+    .await?;
+    if jpeg.is_empty() {
+        return Err("No frame to make a poster from".to_string());
+    }
+    Ok(jpeg)
+}
+
+/// Copies a file into the CAS in `CHUNK_SIZE` pieces. Returns their hashes and the size.
+async fn insert_chunks(store: &Store, path: &str) -> Result<(Vec<String>, u64), String> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| format!("Failed to open {path}: {e}"))?;
+    let mut reader = tokio::io::BufReader::new(file);
+    let (mut chunks, mut size) = (Vec::new(), 0);
+    loop {
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE as usize);
+        (&mut reader)
+            .take(CHUNK_SIZE)
+            .read_to_end(&mut chunk)
+            .await
+            .map_err(|e| format!("Failed to read {path}: {e}"))?;
+        if chunk.is_empty() {
+            break;
+        }
+        size += chunk.len() as u64;
+        let hash = store
+            .cas_insert(&chunk)
+            .await
+            .map_err(|e| format!("Failed to store video: {e}"))?;
+        chunks.push(hash.to_string());
+    }
+    Ok((chunks, size))
+}
+
+fn video_mime(path: &str) -> Result<&'static str, String> {
+    let mime = mime::from_name(path);
+    if !mime.starts_with("video/") {
+        return Err(format!("Not a supported video: {path}"));
+    }
+    Ok(mime)
+}
+
+/// Stores the video at `path` as an `attachment.video` frame. Its content lives in the
+/// chunks listed in its meta; the duration, size and poster are filled in when ffmpeg is
+/// installed, and left out otherwise.
+pub(crate) async fn add_video(
+    store: &Store,
+    yak_id: &str,
+    note_id: &str,
+    path: &str,
+) -> Result<Frame, String> {
+    let name = Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("video");
+    let mime = video_mime(path)?;
+    let (chunks, size) = insert_chunks(store, path).await?;
+    if size == 0 {
+        return Err(format!("{name} is empty"));
+    }
+    let probe = probe(path).await.unwrap_or_else(|e| {
+        eprintln!("Failed to probe {path}: {e}");
+        Probe::default()
+    });
+    let poster = match poster(path, probe.duration_ms).await {
+        Ok(jpeg) => Some(
+            store
+                .cas_insert(&jpeg)
+                .await
+                .map_err(|e| format!("Failed to store poster: {e}"))?
+                .to_string(),
+        ),
+        Err(e) => {
+            eprintln!("Failed to make a poster for {path}: {e}");
+            None
+        }
+    };
+    let meta = serde_json::json!({
+        "yak_id": yak_id,
+        "note_id": note_id,
+        "name": name,
+        "mime": mime,
+        "size": size,
+        "chunks": chunks,
+        "chunk_size": CHUNK_SIZE,
+        "duration_ms": probe.duration_ms,
+        "width": probe.width,
+        "height": probe.height,
+        "poster": poster,
+    });
+    append_frame(store, TOPIC, None, Some(meta)).await
+}
+
+/// Attaches a video file to a note, creating a note named after the file when `note_id`
+/// isn't given. The frontend plays it from `cas://localhost/<frame id>`, and shows the
+/// poster from `cas://localhost/<frame id>/poster`.
+#[tauri::command]
+pub async fn add_video_attachment(
+    app: AppHandle,
+    store: State<'_, Store>,
+    yak_id: String,
+    note_id: Option<String>,
+    path: String,
+) -> Result<String, String> {
+    video_mime(&path)?;
+    let note_id = match note_id {
+        Some(note_id) => note_id,
+        None => {
+            let title = Path::new(&path)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("Video");
+            let note = add_note(
+                &store,
+                &yak_id,
+                &format!("# {title}"),
+                serde_json::json!({}),
+            )
+            .await?;
+            let _ = emit_frame(&app, &note);
+            note.id.to_string()
+        }
+    };
+    let frame = add_video(&store, &yak_id, &note_id, &path).await?;
+    emit_frame(&app, &frame).map_err(|e| format!("Failed to emit frame: {e}"))?;
+    Ok(frame.id.to_string())
+}
+
+/// A frame's bytes: spread over chunks, or read whole from one CAS entry.
+enum Body {
+    Chunks {
+        chunks: Vec<ssri::Integrity>,
+        chunk_size: u64,
+        size: u64,
+    },
+    Bytes(Vec<u8>),
+}
+
+/// The inclusive byte range a `Range` header asks for, cut to `MAX_RESPONSE`. `Err` for
+/// ranges that can't be satisfied; `None` means the whole body.
+fn parse_range(range: Option<&str>, size: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(range) = range else {
+        return Ok(None);
+    };
+    if size == 0 {
+        return Err(());
+    }
+    // Only the first of several ranges is served
+    let spec = range
+        .trim()
+        .strip_prefix("bytes=")
+        .and_then(|ranges| ranges.split(',').next())
+        .ok_or(())?;
+    let (start, end) = spec.trim().split_once('-').ok_or(())?;
+    let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) => (start, end.min(size - 1)),
+        (Ok(start), Err(_)) if end.is_empty() => (start, size - 1),
+        // The last `n` bytes
+        (Err(_), Ok(n)) if start.is_empty() && n > 0 => (size.saturating_sub(n), size - 1),
+        _ => return Err(()),
+    };
+    if start > end {
+        return Err(());
+    }
+    Ok(Some((start, end.min(start + MAX_RESPONSE - 1))))
+}
+
+/// Bytes `start..=end` of a chunked video, reading only the chunks they fall in.
+async fn read_chunks(
+    store: &Store,
+    chunks: &[ssri::Integrity],
+    chunk_size: u64,
+    start: u64,
+    end: u64,
+) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity((end - start + 1) as usize);
+    for index in start / chunk_size..=end / chunk_size {
+        let hash = chunks
+            .get(index as usize)
+            .ok_or("Video is missing a chunk")?;
+        let chunk = crate::integrity::read(store, hash).await?;
+        let offset = index * chunk_size;
+        let from = start.saturating_sub(offset) as usize;
+        let to = ((end - offset + 1) as usize).min(chunk.len());
+        bytes.extend_from_slice(chunk.get(from..to).unwrap_or_default());
+    }
+    Ok(bytes)
+}
+
+/// The body and content type for a `cas://` path: `<frame id>` for a frame's content, or
+/// `<frame id>/poster` for a video's poster.
+async fn locate(store: &Store, path: &str) -> Option<(Body, String)> {
+    let (frame_id, part) = match path.trim_matches('/').split_once('/') {
+        Some((frame_id, part)) => (frame_id, Some(part)),
+        None => (path.trim_matches('/'), None),
+    };
+    let frame = store.get(&frame_id.parse::<scru128::Scru128Id>().ok()?)?;
+    let meta = frame.meta.clone().unwrap_or_default();
+    let mime = meta["mime"].as_str().unwrap_or("application/octet-stream");
+    let hash = match part {
+        Some("poster") => meta["poster"].as_str()?.parse::<ssri::Integrity>().ok()?,
+        Some(_) => return None,
+        None if frame.topic == TOPIC => {
+            let chunks = meta["chunks"]
+                .as_array()?
+                .iter()
+                .map(|hash| hash.as_str()?.parse().ok())
+                .collect::<Option<Vec<_>>>()?;
+            let body = Body::Chunks {
+                chunks,
+                chunk_size: meta["chunk_size"].as_u64().filter(|size| *size > 0)?,
+                size: meta["size"].as_u64()?,
+            };
+            return Some((body, mime.to_string()));
+        }
+        None => frame.hash?,
+    };
+    let bytes = crate::integrity::read(store, &hash).await.ok()?;
+    let mime = if part.is_some() { "image/jpeg" } else { mime };
+    Some((Body::Bytes(bytes), mime.to_string()))
+}
+
+fn status(code: StatusCode) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(code)
+        .body(Vec::new())
+        .unwrap_or_default()
+}
+
+/// Answers `cas://` requests from the webview, with range support so `<video>` can seek
+/// and stream without loading the whole file.
+pub(crate) async fn serve(store: &Store, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let Some((body, mime)) = locate(store, request.uri().path()).await else {
+        return status(StatusCode::NOT_FOUND);
+    };
+    let size = match &body {
+        Body::Chunks { size, .. } => *size,
+        Body::Bytes(bytes) => bytes.len() as u64,
+    };
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok());
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, mime)
+        .header(header::ACCEPT_RANGES, "bytes");
+    let (start, end, partial) = match parse_range(range, size) {
+        Ok(Some((start, end))) => (start, end, true),
+        Ok(None) if size == 0 => return builder.body(Vec::new()).unwrap_or_default(),
+        Ok(None) => (0, size - 1, false),
+        Err(()) => {
+            return Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{size}"))
+                .body(Vec::new())
+                .unwrap_or_default();
+        }
+    };
+    let content = match &body {
+        Body::Bytes(bytes) => bytes[start as usize..=end as usize].to_vec(),
+        Body::Chunks {
+            chunks, chunk_size, ..
+        } => match read_chunks(store, chunks, *chunk_size, start, end).await {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Failed to serve {}: {e}", request.uri());
+                return status(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        },
+    };
+    let builder = if partial {
+        builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{size}"))
+    } else {
+        builder
+    };
+    builder.body(content).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range(None, 100), Ok(None));
+        assert_eq!(parse_range(Some("bytes=0-"), 100), Ok(Some((0, 99))));
+        assert_eq!(parse_range(Some("bytes=10-19"), 100), Ok(Some((10, 19))));
+        assert_eq!(parse_range(Some("bytes=90-200"), 100), Ok(Some((90, 99))));
+        assert_eq!(parse_range(Some("bytes=-10"), 100), Ok(Some((90, 99))));
+        assert_eq!(parse_range(Some("bytes=100-"), 100), Err(()));
+        assert_eq!(parse_range(Some("items=0-1"), 100), Err(()));
+        let size = 3 * MAX_RESPONSE;
+        assert_eq!(
+            parse_range(Some("bytes=0-"), size),
+            Ok(Some((0, MAX_RESPONSE - 1)))
+        );
+    }
+
+    #[test]
+    fn test_parse_probe() {
+        let output = br#"{"streams": [{"width": 1920, "height": 1080}],
+                         "format": {"duration": "12.345600"}}"#;
+        assert_eq!(
+            parse_probe(output),
+            Probe {
+                duration_ms: Some(12346),
+                width: Some(1920),
+                height: Some(1080),
+            }
+        );
+        assert_eq!(parse_probe(b"not json"), Probe::default());
+    }
+
+    #[tokio::test]
+    async fn test_serve_chunks() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().join("store"));
+        let path = dir.path().join("clip.mp4");
+        let video: Vec<u8> = (0..CHUNK_SIZE + 100).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &video).unwrap();
+        let (chunks, size) = insert_chunks(&store, path.to_str().unwrap()).await.unwrap();
+        assert_eq!((chunks.len(), size), (2, video.len() as u64));
+        let meta = serde_json::json!({
+            "mime": "video/mp4",
+            "size": size,
+            "chunks": chunks,
+            "chunk_size": CHUNK_SIZE,
+        });
+        let frame = append_frame(&store, TOPIC, None, Some(meta)).await.unwrap();
+
+        let start = CHUNK_SIZE - 10;
+        let request = Request::builder()
+            .uri(format!("cas://localhost/{}", frame.id))
+            .header(header::RANGE, format!("bytes={start}-{}", start + 19))
+            .body(Vec::new())
+            .unwrap();
+        let response = serve(&store, &request).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers()[header::CONTENT_RANGE],
+            format!("bytes {start}-{}/{size}", start + 19)
+        );
+        assert_eq!(
+            response.body().as_slice(),
+            &video[start as usize..start as usize + 20]
+        );
+
+        let request = Request::builder()
+            .uri(format!("cas://localhost/{}/poster", frame.id))
+            .body(Vec::new())
+            .unwrap();
+        assert_eq!(
+            serve(&store, &request).await.status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
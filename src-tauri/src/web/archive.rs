@@ -0,0 +1,121 @@
+use regex::Regex;
+use std::sync::OnceLock;
+use tauri::{AppHandle, State};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use super::{as_url, extract_meta, fetch_page};
+use crate::windows::emit_frame;
+use crate::{append_frame, html};
+
+/// The readable part of a page: its `<article>` or `<main>` if it has one, else the body.
+fn readable_text(html: &str) -> String {
+    static MAIN: OnceLock<Regex> = OnceLock::new();
+    let main = MAIN
+        .get_or_init(|| Regex::new(r"(?is)<(article|main)\b[^>]*>(.*)</(article|main)>").unwrap());
+    let content = main
+        .captures(html)
+        .map_or(html, |caps| caps.get(2).map_or(html, |m| m.as_str()));
+    html::to_text(content)
+}
+
+/// Fetches `url` and appends a `link.archive` frame: the readable text is the content, and
+/// the page metadata plus the CAS hash of the raw HTML snapshot go in its meta.
+pub(crate) async fn archive(
+    store: &Store,
+    url: &str,
+    yak_id: &str,
+    note_id: Option<&str>,
+) -> Result<Frame, String> {
+    let (final_url, page) = fetch_page(url).await?;
+    let snapshot = store
+        .cas_insert(page.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to store snapshot: {e}"))?;
+    let page_meta = extract_meta(&final_url, &page);
+    let text = readable_text(&page);
+
+    let mut meta = serde_json::to_value(&page_meta).map_err(|e| e.to_string())?;
+    if let Some(fields) = meta.as_object_mut() {
+        fields.insert("yak_id".into(), yak_id.into());
+        fields.insert("note_id".into(), note_id.into());
+        fields.insert("requested_url".into(), url.into());
+        fields.insert("snapshot".into(), snapshot.to_string().into());
+    }
+    let content = (!text.is_empty()).then_some(text.as_bytes());
+    append_frame(store, "link.archive", content, Some(meta)).await
+}
+
+/// Archives new notes that consist of nothing but a URL. Notes arriving through sync were
+/// already archived on the device that created them.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    let mut caught_up = false;
+    while let Some(frame) = rx.recv().await {
+        if frame.topic == "xs.threshold" {
+            caught_up = true;
+            continue;
+        }
+        if !caught_up || frame.topic != "note.create" || crate::sync::origin(&frame).is_some() {
+            continue;
+        }
+        let (Some(hash), Some(yak_id)) = (
+            frame.hash.as_ref(),
+            frame
+                .meta
+                .as_ref()
+                .and_then(|meta| meta.get("yak_id"))
+                .and_then(|id| id.as_str()),
+        ) else {
+            continue;
+        };
+        let Ok(content) = store.cas_read(hash).await else {
+            continue;
+        };
+        let Some(url) = as_url(&String::from_utf8_lossy(&content)).map(String::from) else {
+            continue;
+        };
+
+        let (store, app, yak_id, note_id) = (
+            store.clone(),
+            app.clone(),
+            yak_id.to_string(),
+            frame.id.to_string(),
+        );
+        tokio::spawn(async move {
+            match archive(&store, &url, &yak_id, Some(&note_id)).await {
+                Ok(frame) => {
+                    let _ = emit_frame(&app, &frame);
+                }
+                Err(e) => eprintln!("Failed to archive {url}: {e}"),
+            }
+        });
+    }
+}
+
+/// Archives a web page into a yak as readable text plus a raw HTML snapshot, optionally
+/// linked to the note it came from.
+#[tauri::command]
+pub async fn archive_url(
+    store: State<'_, Store>,
+    app: AppHandle,
+    url: String,
+    yak_id: String,
+    note_id: Option<String>,
+) -> Result<String, String> {
+    let frame = archive(&store, &url, &yak_id, note_id.as_deref()).await?;
+    emit_frame(&app, &frame).map_err(|e| format!("Failed to emit frame: {e}"))?;
+    Ok(frame.id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readable_text_prefers_article() {
+        let page = "<body><nav>Menu</nav><article><h1>Title</h1><p>Body</p></article></body>";
+        assert_eq!(readable_text(page), "Title\nBody");
+        assert_eq!(readable_text("<body><p>Only</p></body>"), "Only");
+    }
+}
@@ -0,0 +1,141 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::html::decode_entities;
+
+mod archive;
+mod unfurl;
+
+pub(crate) use archive::archive;
+pub use archive::archive_url;
+pub(crate) use archive::watch as watch_archive;
+pub(crate) use unfurl::watch as watch_unfurl;
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+/// Pages larger than this are cut off rather than archived whole
+const MAX_PAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Metadata describing a web page, taken from its OpenGraph/Twitter card tags and `<title>`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageMeta {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub site_name: Option<String>,
+}
+
+pub(crate) fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .user_agent(concat!("yaks/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .unwrap_or_default()
+}
+
+/// Returns the URL if `text` is nothing but a single http(s) URL.
+pub(crate) fn as_url(text: &str) -> Option<&str> {
+    let text = text.trim();
+    let is_url = (text.starts_with("http://") || text.starts_with("https://"))
+        && !text.contains(char::is_whitespace);
+    is_url.then_some(text)
+}
+
+/// Fetches an HTML page, returning the URL it was finally served from and its body.
+pub(crate) async fn fetch_page(url: &str) -> Result<(String, String), String> {
+    let response = client()
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to fetch {url}: {e}"))?;
+    let final_url = response.url().to_string();
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read {url}: {e}"))?;
+    let bytes = &bytes[..bytes.len().min(MAX_PAGE_BYTES)];
+    Ok((final_url, String::from_utf8_lossy(bytes).into_owned()))
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    let attribute = ATTRIBUTE
+        .get_or_init(|| Regex::new(r#"(?i)([a-z:_-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+    attribute
+        .captures_iter(tag)
+        .find(|caps| caps[1].eq_ignore_ascii_case(name))
+        .and_then(|caps| caps.get(2).or(caps.get(3)))
+        .map(|value| decode_entities(value.as_str()).trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Resolves a possibly relative link against the page it appeared on.
+fn absolute(base: &str, link: &str) -> String {
+    reqwest::Url::parse(base)
+        .and_then(|base| base.join(link))
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| link.to_string())
+}
+
+pub(crate) fn extract_meta(url: &str, html: &str) -> PageMeta {
+    static META: OnceLock<Regex> = OnceLock::new();
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    let meta_tag = META.get_or_init(|| Regex::new(r"(?is)<meta\b[^>]*>").unwrap());
+    let title_tag = TITLE.get_or_init(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+
+    let mut properties = std::collections::HashMap::new();
+    for tag in meta_tag.find_iter(html) {
+        let key = attribute(tag.as_str(), "property").or_else(|| attribute(tag.as_str(), "name"));
+        if let (Some(key), Some(content)) = (key, attribute(tag.as_str(), "content")) {
+            properties.entry(key.to_lowercase()).or_insert(content);
+        }
+    }
+    let property = |keys: &[&str]| keys.iter().find_map(|key| properties.get(*key).cloned());
+
+    let title = property(&["og:title", "twitter:title"]).or_else(|| {
+        title_tag
+            .captures(html)
+            .map(|caps| decode_entities(caps[1].trim()))
+            .filter(|title| !title.is_empty())
+    });
+    PageMeta {
+        url: property(&["og:url"]).unwrap_or_else(|| url.to_string()),
+        title,
+        description: property(&["og:description", "twitter:description", "description"]),
+        image: property(&["og:image", "twitter:image", "twitter:image:src"])
+            .map(|image| absolute(url, &image)),
+        site_name: property(&["og:site_name"]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_meta() {
+        let html = r#"<html><head><title>Fallback &amp; title</title>
+            <meta property="og:description" content="A page">
+            <meta name="twitter:image" content='/img/card.png'>
+            </head><body></body></html>"#;
+        let meta = extract_meta("https://example.com/post/1", html);
+        assert_eq!(meta.title.as_deref(), Some("Fallback & title"));
+        assert_eq!(meta.description.as_deref(), Some("A page"));
+        assert_eq!(
+            meta.image.as_deref(),
+            Some("https://example.com/img/card.png")
+        );
+    }
+
+    #[test]
+    fn test_as_url() {
+        assert_eq!(
+            as_url(" https://example.com/a \n"),
+            Some("https://example.com/a")
+        );
+        assert_eq!(as_url("see https://example.com"), None);
+    }
+}
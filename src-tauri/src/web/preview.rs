@@ -0,0 +1,124 @@
+use chrono::Utc;
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::State;
+use tokio::sync::Mutex;
+use xs::store::{Frame, Store};
+
+use super::{extract_meta, fetch_page, PageMeta};
+use crate::{append_frame, read_all_frames};
+
+const TOPIC: &str = "link.preview";
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_AGE_DAYS: i64 = 7;
+
+/// Previews are cached as `link.preview` frames whose content is the JSON metadata, so they
+/// survive restarts. This indexes the latest one for each URL.
+#[derive(Default)]
+pub struct PreviewCache(Mutex<Option<HashMap<String, Frame>>>);
+
+fn index(frames: &[Frame]) -> HashMap<String, Frame> {
+    frames
+        .iter()
+        .filter(|frame| frame.topic == TOPIC)
+        .filter_map(|frame| {
+            let url = frame.meta.as_ref()?.get("url")?.as_str()?;
+            Some((url.to_string(), frame.clone()))
+        })
+        .collect()
+}
+
+fn is_fresh(frame: &Frame) -> bool {
+    crate::time::from_id(&frame.id.to_string())
+        .is_some_and(|fetched| Utc::now() - fetched < chrono::Duration::days(MAX_AGE_DAYS))
+}
+
+async fn cached(store: &Store, frame: &Frame) -> Option<PageMeta> {
+    let bytes = store.cas_read(frame.hash.as_ref()?).await.ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+pub(crate) async fn preview(
+    store: &Store,
+    cache: &PreviewCache,
+    url: &str,
+) -> Result<PageMeta, String> {
+    let mut cache = cache.0.lock().await;
+    if cache.is_none() {
+        *cache = Some(index(&read_all_frames(store).await));
+    }
+    let entries = cache.get_or_insert_with(HashMap::new);
+
+    let stale = match entries.get(url) {
+        Some(frame) if is_fresh(frame) => {
+            if let Some(meta) = cached(store, frame).await {
+                return Ok(meta);
+            }
+            None
+        }
+        Some(frame) => cached(store, frame).await,
+        None => None,
+    };
+
+    let fetched = tokio::time::timeout(FETCH_TIMEOUT, fetch_page(url))
+        .await
+        .unwrap_or_else(|_| Err(format!("Timed out fetching {url}")));
+    let meta = match (fetched, stale) {
+        (Ok((final_url, page)), _) => extract_meta(&final_url, &page),
+        // An outdated preview beats none when the site is unreachable
+        (Err(_), Some(stale)) => return Ok(stale),
+        (Err(e), None) => return Err(e),
+    };
+
+    let content = serde_json::to_vec(&meta).map_err(|e| e.to_string())?;
+    let frame = append_frame(
+        store,
+        TOPIC,
+        Some(&content),
+        Some(serde_json::json!({ "url": url })),
+    )
+    .await?;
+    entries.insert(url.to_string(), frame);
+    Ok(meta)
+}
+
+/// OpenGraph/Twitter card metadata for a link, fetched by the backend so the webview doesn't
+/// need cross-origin requests. Results are cached for a week.
+#[tauri::command]
+pub async fn fetch_link_preview(
+    store: State<'_, Store>,
+    cache: State<'_, PreviewCache>,
+    url: String,
+) -> Result<PageMeta, String> {
+    preview(&store, &cache, &url).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_preview_served_from_cache() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let url = "http://127.0.0.1:9/unreachable";
+        let meta = PageMeta {
+            url: url.to_string(),
+            title: Some("Cached".to_string()),
+            ..Default::default()
+        };
+        let content = serde_json::to_vec(&meta).unwrap();
+        append_frame(
+            &store,
+            TOPIC,
+            Some(&content),
+            Some(serde_json::json!({ "url": url })),
+        )
+        .await
+        .unwrap();
+
+        let cache = PreviewCache::default();
+        assert_eq!(preview(&store, &cache, url).await.unwrap(), meta);
+    }
+}
@@ -0,0 +1,274 @@
+use regex::Regex;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tauri::AppHandle;
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use super::{absolute, attribute, extract_meta, fetch_page};
+use crate::locks::is_sealed;
+use crate::projection::Projection;
+use crate::shutdown::begin_write;
+use crate::windows::emit_frames;
+use crate::{append_frame, read_all_frames};
+
+/// What unfurling a clip found: the page's canonical URL and title. Synced with the clip, so
+/// other devices fold repeat clips into the same note.
+const TOPIC: &str = "clip.unfurl";
+/// Waited before unfurling, so the clip's screenshot has been attached.
+const SETTLE: Duration = Duration::from_secs(2);
+/// Query parameters that only track where a visit came from.
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "mc_cid", "mc_eid", "ref"];
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+/// The page's `<link rel="canonical">`, if it has one.
+fn canonical_link(url: &str, html: &str) -> Option<String> {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    let link_tag = LINK.get_or_init(|| Regex::new(r"(?is)<link\b[^>]*>").unwrap());
+    link_tag
+        .find_iter(html)
+        .map(|tag| tag.as_str())
+        .find(|tag| attribute(tag, "rel").is_some_and(|rel| rel.eq_ignore_ascii_case("canonical")))
+        .and_then(|tag| attribute(tag, "href"))
+        .map(|href| absolute(url, &href))
+}
+
+/// `url` without its fragment or tracking parameters, so links to the same page compare equal.
+fn normalize(url: &str) -> String {
+    let Ok(mut parsed) = reqwest::Url::parse(url.trim()) else {
+        return url.trim().to_string();
+    };
+    parsed.set_fragment(None);
+    let kept: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if kept.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(kept);
+    }
+    parsed.to_string()
+}
+
+/// `existing` with the quoted highlights of `clip` it doesn't have yet added at the end, or
+/// `None` when it has them all.
+fn merge_highlights(existing: &str, clip: &str) -> Option<String> {
+    let new: Vec<&str> = clip
+        .lines()
+        .filter(|line| line.starts_with("> "))
+        .filter(|line| !existing.lines().any(|existing| existing == *line))
+        .collect();
+    if new.is_empty() {
+        return None;
+    }
+    let mut merged = existing.trim_end().to_string();
+    merged.push_str("\n\n");
+    for line in new {
+        merged.push_str(line);
+        merged.push('\n');
+    }
+    Some(merged)
+}
+
+/// A clip titled with its bare URL, given the page's title instead.
+fn retitle(content: &str, url: &str, title: &str) -> Option<String> {
+    let rest = content.strip_prefix(&format!("# {url}\n"))?;
+    Some(format!("# {}\n{rest}", title.trim()))
+}
+
+/// Each earlier clip's note, by the canonical URL it was unfurled to, or else its own.
+fn clips_by_url(frames: &[Frame], before: &Frame) -> HashMap<String, String> {
+    let mut clips = HashMap::new();
+    for frame in frames.iter().filter(|frame| frame.id < before.id) {
+        let found = match frame.topic.as_str() {
+            "note.create" if meta_str(frame, "source") == Some("browser") => {
+                meta_str(frame, "url").map(|url| (normalize(url), frame.id.to_string()))
+            }
+            TOPIC => meta_str(frame, "canonical_url")
+                .zip(meta_str(frame, "note_id"))
+                .map(|(url, note_id)| (url.to_string(), note_id.to_string())),
+            _ => None,
+        };
+        if let Some((url, note_id)) = found {
+            clips.insert(url, note_id);
+        }
+    }
+    clips
+}
+
+/// Looks up the page a clip came from: a clip of a page already clipped into the same yak
+/// has its highlights and attachments moved onto the earlier clip and is deleted; otherwise
+/// it's given the page's title if it came without one.
+async fn unfurl(app: &AppHandle, store: &Store, clip: &Frame) -> Result<Vec<Frame>, String> {
+    let (Some(url), Some(yak_id)) = (meta_str(clip, "url"), meta_str(clip, "yak_id")) else {
+        return Ok(Vec::new());
+    };
+    tokio::time::sleep(SETTLE).await;
+    let (canonical, title) = match fetch_page(url).await {
+        Ok((final_url, html)) => {
+            let page = extract_meta(&final_url, &html);
+            let canonical = canonical_link(&final_url, &html).unwrap_or(page.url);
+            (normalize(&canonical), page.title)
+        }
+        Err(e) => {
+            eprintln!("Failed to unfurl {url}: {e}");
+            (normalize(url), None)
+        }
+    };
+
+    let _guard = begin_write(app)?;
+    let frames = read_all_frames(store).await;
+    let mut projection = Projection::from_frames(&frames);
+    projection.resolve_content(store).await;
+    let current = |note_id: &str| {
+        let note_id = projection.resolve(note_id);
+        let live = projection
+            .notes_by_yak
+            .get(yak_id)
+            .is_some_and(|ids| ids.contains(&note_id));
+        projection
+            .notes
+            .get(&note_id)
+            .filter(|note| live && !note.content.as_deref().is_some_and(is_sealed))
+    };
+    // Deleted, moved or locked since it was clipped
+    let Some(note) = current(&clip.id.to_string()) else {
+        return Ok(Vec::new());
+    };
+    let content = note.content.clone().unwrap_or_default();
+
+    let mut appended = Vec::new();
+    let earlier = clips_by_url(&frames, clip)
+        .get(&canonical)
+        .and_then(|note_id| current(note_id));
+    if let Some(earlier) = earlier {
+        let existing = earlier.content.as_deref().unwrap_or_default();
+        if let Some(merged) = merge_highlights(existing, &content) {
+            let meta = json!({ "yak_id": yak_id, "note_id": earlier.id });
+            appended
+                .push(append_frame(store, "note.edit", Some(merged.as_bytes()), Some(meta)).await?);
+        }
+        for attachment in &note.attachments {
+            let Some(hash) = &attachment.hash else {
+                continue;
+            };
+            if earlier
+                .attachments
+                .iter()
+                .any(|kept| kept.hash.as_ref() == Some(hash))
+            {
+                continue;
+            }
+            let mut meta = attachment.meta.clone().unwrap_or_else(|| json!({}));
+            meta["yak_id"] = yak_id.into();
+            meta["note_id"] = earlier.id.clone().into();
+            // Same content, re-attached by hash without copying
+            let frame = Frame {
+                id: scru128::new(),
+                context_id: xs::store::ZERO_CONTEXT,
+                topic: "attachment.add".to_string(),
+                hash: Some(hash.clone()),
+                meta: Some(meta),
+                ttl: None,
+            };
+            appended.push(
+                crate::append_to_store(store, frame)
+                    .map_err(|e| e.context("Failed to append frame"))?,
+            );
+        }
+        let meta = json!({ "yak_id": yak_id, "note_id": note.id, "duplicate_of": earlier.id });
+        appended.push(append_frame(store, "note.delete", None, Some(meta)).await?);
+        return Ok(appended);
+    }
+
+    let meta = json!({
+        "yak_id": yak_id,
+        "note_id": clip.id.to_string(),
+        "url": url,
+        "canonical_url": canonical,
+        "title": title,
+    });
+    appended.push(append_frame(store, TOPIC, None, Some(meta)).await?);
+    if let Some(retitled) = title.and_then(|title| retitle(&content, url, &title)) {
+        let meta = json!({ "yak_id": yak_id, "note_id": note.id });
+        appended
+            .push(append_frame(store, "note.edit", Some(retitled.as_bytes()), Some(meta)).await?);
+    }
+    Ok(appended)
+}
+
+/// Unfurls clips from the browser extension as they arrive.
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(read_options).await;
+    let mut caught_up = false;
+    while let Some(frame) = rx.recv().await {
+        if frame.topic == "xs.threshold" {
+            caught_up = true;
+            continue;
+        }
+        let clip = frame.topic == "note.create" && meta_str(&frame, "source") == Some("browser");
+        if !caught_up || !clip || crate::sync::origin(&frame).is_some() {
+            continue;
+        }
+        let (app, store) = (app.clone(), store.clone());
+        tokio::spawn(async move {
+            match unfurl(&app, &store, &frame).await {
+                Ok(frames) if !frames.is_empty() => {
+                    let _ = emit_frames(&app, &frames);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Failed to unfurl clip {}: {e}", frame.id),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unfurl_helpers() {
+        let html = r#"<head><link rel="stylesheet" href="/a.css">
+            <link href="/post/1" rel="Canonical"></head>"#;
+        assert_eq!(
+            canonical_link("https://example.com/post/1?utm_source=x", html).as_deref(),
+            Some("https://example.com/post/1")
+        );
+        assert_eq!(
+            canonical_link("https://example.com/", "<p>no links</p>"),
+            None
+        );
+
+        assert_eq!(
+            normalize("https://example.com/a?utm_source=feed&id=3&fbclid=x#comments"),
+            "https://example.com/a?id=3"
+        );
+        assert_eq!(
+            normalize("https://example.com/a?utm_medium=email"),
+            "https://example.com/a"
+        );
+
+        let first = "# Post\n\n> one\n\n<https://example.com/a>\n";
+        let second = "# Post\n\n> one\n> two\n\n<https://example.com/a>\n";
+        assert_eq!(
+            merge_highlights(first, second).as_deref(),
+            Some("# Post\n\n> one\n\n<https://example.com/a>\n\n> two\n")
+        );
+        assert_eq!(merge_highlights(second, first), None);
+
+        let bare = "# https://example.com/a\n\n<https://example.com/a>\n";
+        assert_eq!(
+            retitle(bare, "https://example.com/a", "A post").as_deref(),
+            Some("# A post\n\n<https://example.com/a>\n")
+        );
+        assert_eq!(retitle(first, "https://example.com/a", "A post"), None);
+    }
+}
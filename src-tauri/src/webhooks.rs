@@ -0,0 +1,314 @@
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeSet;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::projection::Projection;
+use crate::subscriptions::{note_tags, FrameFilter};
+use crate::{append_frame, health, locks, read_all_frames};
+
+const ADD_TOPIC: &str = "webhook.add";
+const REMOVE_TOPIC: &str = "webhook.remove";
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Tries per delivery before it's given up and reported.
+const MAX_ATTEMPTS: u32 = 5;
+/// Content past this size is left out of the payload; the receiver gets the hash instead.
+const MAX_CONTENT_BYTES: usize = 64 * 1024;
+const SIGNATURE_HEADER: &str = "X-Yaks-Signature";
+
+/// An outgoing webhook, registered with a `webhook.add` frame and dropped by
+/// `webhook.remove`. Webhooks stay on the device they were added on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    /// Which frames are posted; `replay` is ignored, since only new frames are
+    pub filter: FrameFilter,
+    /// Key for the `X-Yaks-Signature` HMAC, for the receiver to check
+    pub secret: String,
+}
+
+/// The JSON body POSTed for each matching frame.
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    webhook_id: &'a str,
+    frame: &'a Frame,
+    /// The frame's content, when it's text that isn't too long or sealed
+    content: Option<String>,
+}
+
+fn meta_str<'a>(frame: &'a Frame, key: &str) -> Option<&'a str> {
+    frame.meta.as_ref()?.get(key)?.as_str()
+}
+
+/// Applies a `webhook.add` or `webhook.remove` frame to `webhooks`.
+fn apply(webhooks: &mut Vec<Webhook>, frame: &Frame) {
+    match frame.topic.as_str() {
+        ADD_TOPIC => {
+            let meta = frame.meta.clone().unwrap_or_default();
+            let (Some(url), Some(secret)) = (meta["url"].as_str(), meta["secret"].as_str()) else {
+                return;
+            };
+            webhooks.push(Webhook {
+                id: frame.id.to_string(),
+                url: url.to_string(),
+                filter: serde_json::from_value(meta["filter"].clone()).unwrap_or_default(),
+                secret: secret.to_string(),
+            });
+        }
+        REMOVE_TOPIC => {
+            let webhook_id = meta_str(frame, "webhook_id");
+            webhooks.retain(|webhook| Some(webhook.id.as_str()) != webhook_id);
+        }
+        _ => {}
+    }
+}
+
+fn webhooks(frames: &[Frame]) -> Vec<Webhook> {
+    let mut webhooks = Vec::new();
+    for frame in frames {
+        apply(&mut webhooks, frame);
+    }
+    webhooks
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `body` keyed with `secret`.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    let digest = mac.finalize().into_bytes();
+    let hex: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!("sha256={hex}")
+}
+
+fn new_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+async fn payload(store: &Store, webhook: &Webhook, frame: &Frame) -> Result<Vec<u8>, String> {
+    let content = match &frame.hash {
+        Some(hash) => store
+            .cas_read(hash)
+            .await
+            .ok()
+            .filter(|bytes| bytes.len() <= MAX_CONTENT_BYTES)
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .filter(|content| !locks::is_sealed(content)),
+        None => None,
+    };
+    let payload = Payload {
+        webhook_id: &webhook.id,
+        frame,
+        content,
+    };
+    serde_json::to_vec(&payload).map_err(|e| format!("Failed to serialize payload: {e}"))
+}
+
+async fn post(
+    client: &reqwest::Client,
+    webhook: &Webhook,
+    frame: &Frame,
+    body: Vec<u8>,
+) -> Result<(), String> {
+    client
+        .post(&webhook.url)
+        .timeout(TIMEOUT)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, sign(&webhook.secret, &body))
+        .header("X-Yaks-Topic", &frame.topic)
+        .header("X-Yaks-Delivery", frame.id.to_string())
+        .body(body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map(|_| ())
+        .map_err(|e| format!("Failed to post to {}: {e}", webhook.url))
+}
+
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs(2u64.pow(attempt))
+}
+
+/// Posts `frame` to `webhook`, retrying with backoff, and reports how it went to health.
+async fn deliver(
+    app: AppHandle,
+    store: Store,
+    client: reqwest::Client,
+    webhook: Webhook,
+    frame: Frame,
+) {
+    let body = match payload(&store, &webhook, &frame).await {
+        Ok(body) => body,
+        Err(e) => return health::error(&app, "webhooks", &e),
+    };
+    for attempt in 1..=MAX_ATTEMPTS {
+        match post(&client, &webhook, &frame, body.clone()).await {
+            Ok(()) => return health::ok(&app, "webhooks"),
+            Err(e) if attempt == MAX_ATTEMPTS => {
+                eprintln!("Giving up on webhook delivery of {}: {e}", frame.id);
+                health::error(&app, "webhooks", &e);
+            }
+            Err(_) => tokio::time::sleep(backoff(attempt)).await,
+        }
+    }
+}
+
+/// Posts new frames to the webhooks whose filter they match. Frames copied in by sync are
+/// left to the device that wrote them, and deliveries run side by side, so a slow endpoint
+/// doesn't hold up the others (or keep their order).
+pub(crate) async fn watch(app: AppHandle, store: Store) {
+    let client = crate::web::client();
+    let options = ReadOptions::builder().follow(FollowOption::On).build();
+    let mut rx = store.read(options).await;
+    // Tag filters need the notes' tags, so every frame is folded in
+    let mut projection = Projection::default();
+    let mut webhooks: Vec<Webhook> = Vec::new();
+    let mut live = false;
+    while let Some(frame) = rx.recv().await {
+        if frame.topic == "xs.threshold" {
+            live = true;
+            continue;
+        }
+        let tagged = webhooks
+            .iter()
+            .any(|webhook| !webhook.filter.tags.is_empty());
+        let mut tags = BTreeSet::new();
+        if tagged {
+            tags = note_tags(&projection, &frame);
+        }
+        projection.apply(&frame);
+        if tagged {
+            tags.extend(note_tags(&projection, &frame));
+        }
+        apply(&mut webhooks, &frame);
+        if !live || frame.topic.starts_with("webhook.") || crate::sync::origin(&frame).is_some() {
+            continue;
+        }
+        for webhook in webhooks
+            .iter()
+            .filter(|webhook| webhook.filter.matches(&frame, &tags))
+        {
+            tokio::spawn(deliver(
+                app.clone(),
+                store.clone(),
+                client.clone(),
+                webhook.clone(),
+                frame.clone(),
+            ));
+        }
+    }
+}
+
+/// Posts every new frame matching `filter` to `url` as JSON, signed with a secret made for
+/// it: `X-Yaks-Signature` is `sha256=` and the hex HMAC-SHA256 of the body. Failed posts
+/// are retried a few times with backoff.
+#[tauri::command]
+pub async fn add_webhook(
+    store: State<'_, Store>,
+    app: AppHandle,
+    url: String,
+    filter: FrameFilter,
+) -> Result<Webhook, String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let parsed = reqwest::Url::parse(&url).map_err(|e| format!("Invalid url: {e}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("Webhooks need an http(s) url: {url}"));
+    }
+    let secret = new_secret();
+    let meta = serde_json::json!({ "url": url, "filter": filter, "secret": secret });
+    let frame = append_frame(&store, ADD_TOPIC, None, Some(meta)).await?;
+    Ok(Webhook {
+        id: frame.id.to_string(),
+        url,
+        filter,
+        secret,
+    })
+}
+
+#[tauri::command]
+pub async fn remove_webhook(
+    store: State<'_, Store>,
+    app: AppHandle,
+    webhook_id: String,
+) -> Result<(), String> {
+    let _guard = crate::shutdown::begin_write(&app)?;
+    let meta = serde_json::json!({ "webhook_id": webhook_id });
+    append_frame(&store, REMOVE_TOPIC, None, Some(meta)).await?;
+    Ok(())
+}
+
+/// Removes every webhook from a forked store, so the fork's frames aren't posted too.
+pub(crate) async fn detach(store: &Store, frames: &[Frame]) -> Result<(), String> {
+    for webhook in webhooks(frames) {
+        let meta = serde_json::json!({ "webhook_id": webhook.id });
+        append_frame(store, REMOVE_TOPIC, None, Some(meta)).await?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_webhooks(store: State<'_, Store>) -> Result<Vec<Webhook>, String> {
+    Ok(webhooks(&read_all_frames(&store).await))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        let mac = sign("Jefe", b"what do ya want for nothing?");
+        assert_eq!(
+            mac,
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhooks() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(dir.path().to_path_buf());
+        let filter = json!({ "topics": ["task.*"] });
+        let meta = json!({ "url": "https://example.com/a", "filter": filter, "secret": "s" });
+        let kept = append_frame(&store, ADD_TOPIC, None, Some(meta))
+            .await
+            .unwrap();
+        let meta = json!({ "url": "https://example.com/b", "secret": "s" });
+        let removed = append_frame(&store, ADD_TOPIC, None, Some(meta))
+            .await
+            .unwrap();
+        let meta = json!({ "webhook_id": removed.id.to_string() });
+        append_frame(&store, REMOVE_TOPIC, None, Some(meta))
+            .await
+            .unwrap();
+
+        let webhooks = webhooks(&read_all_frames(&store).await);
+        assert_eq!(webhooks.len(), 1);
+        assert_eq!(webhooks[0].id, kept.id.to_string());
+        assert_eq!(webhooks[0].filter.topics, vec!["task.*"]);
+
+        let task = append_frame(
+            &store,
+            "task.update",
+            None,
+            Some(json!({ "task_id": "t", "done": true })),
+        )
+        .await
+        .unwrap();
+        assert!(webhooks[0].filter.matches(&task, &BTreeSet::new()));
+        let body = payload(&store, &webhooks[0], &task).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["frame"]["topic"], "task.update");
+        assert_eq!(body["content"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,400 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, TimeZone, Weekday};
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::{AppHandle, Manager, State};
+use xs::store::Store;
+
+use crate::geo;
+use crate::locks::LockState;
+use crate::projection::Projection;
+use crate::{append_batch_to_store, read_all_frames, secrets, shutdown, windows, AppendRequest};
+
+/// When a date is given without a time.
+const DEFAULT_HOUR: u32 = 9;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct When {
+    pub at: DateTime<Local>,
+    /// The text with the date phrase (and any "remind me") taken out
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Captured {
+    pub note_id: String,
+    /// RFC 3339, if the text said when
+    pub reminder: Option<String>,
+}
+
+/// A word without the punctuation around it, lowercased.
+fn word(token: &str) -> String {
+    token
+        .trim_matches(|c: char| matches!(c, ',' | '.' | '!' | '?' | ';' | '(' | ')'))
+        .to_lowercase()
+}
+
+/// "fri", "friday", "fri." — but not "month", or "sat" and "sun" as plain words.
+fn weekday(word: &str) -> Option<Weekday> {
+    if matches!(word, "sat" | "sun" | "wed") {
+        return None;
+    }
+    const DAYS: [(&str, Weekday); 7] = [
+        ("monday", Weekday::Mon),
+        ("tuesday", Weekday::Tue),
+        ("wednesday", Weekday::Wed),
+        ("thursday", Weekday::Thu),
+        ("friday", Weekday::Fri),
+        ("saturday", Weekday::Sat),
+        ("sunday", Weekday::Sun),
+    ];
+    DAYS.iter()
+        .find(|(name, _)| word.len() >= 3 && name.starts_with(word))
+        .map(|(_, day)| *day)
+}
+
+fn month(word: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "january",
+        "february",
+        "march",
+        "april",
+        "may",
+        "june",
+        "july",
+        "august",
+        "september",
+        "october",
+        "november",
+        "december",
+    ];
+    MONTHS
+        .iter()
+        .position(|month| word.len() >= 3 && month.starts_with(word))
+        .map(|i| i as u32 + 1)
+}
+
+/// "14", "14th", "1st".
+fn day_of_month(word: &str) -> Option<u32> {
+    let digits = word.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let suffix = &word[digits.len()..];
+    if !matches!(suffix, "" | "st" | "nd" | "rd" | "th") {
+        return None;
+    }
+    digits.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+/// "3pm", "3:30pm", "15:00", or "3" followed by "pm" as its own word. `bare` allows a lone
+/// hour, as after "at".
+fn clock(words: &[String], bare: bool) -> Option<(usize, NaiveTime)> {
+    let first = words.first()?;
+    let (number, mut suffix) = match first.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => (&first[..i], first[i..].to_string()),
+        None => (first.as_str(), String::new()),
+    };
+    let mut used = 1;
+    if suffix.is_empty() {
+        if let Some(next) = words
+            .get(1)
+            .filter(|next| matches!(next.as_str(), "am" | "pm"))
+        {
+            suffix = next.clone();
+            used = 2;
+        }
+    }
+    let (hour, minute) = match number.split_once(':') {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        None if !suffix.is_empty() || bare => (number.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+    let hour = match suffix.as_str() {
+        "am" if (1..=12).contains(&hour) => hour % 12,
+        "pm" if (1..=12).contains(&hour) => hour % 12 + 12,
+        "" => hour,
+        _ => return None,
+    };
+    Some((used, NaiveTime::from_hms_opt(hour, minute, 0)?))
+}
+
+/// A time of day at the start of `words`.
+fn time(words: &[String]) -> Option<(usize, NaiveTime)> {
+    let at = words.first().is_some_and(|word| word == "at");
+    let skip = usize::from(at);
+    let rest = words.get(skip..)?;
+    let named = match rest.first()?.as_str() {
+        "noon" | "midday" => Some(12),
+        "midnight" => Some(0),
+        "morning" => Some(9),
+        "afternoon" => Some(15),
+        "evening" | "tonight" => Some(19),
+        _ => None,
+    };
+    if let Some(hour) = named {
+        return Some((skip + 1, NaiveTime::from_hms_opt(hour, 0, 0)?));
+    }
+    let (used, time) = clock(rest, at)?;
+    Some((skip + used, time))
+}
+
+/// A day at the start of `words`, relative to `today`.
+fn date(words: &[String], today: NaiveDate) -> Option<(usize, NaiveDate)> {
+    let first = words.first()?.as_str();
+    let second = words.get(1).map(String::as_str).unwrap_or_default();
+    match first {
+        "today" | "tonight" => return Some((1, today)),
+        "tomorrow" | "tmrw" => return Some((1, today.succ_opt()?)),
+        "next" if second == "week" => return Some((2, today + Duration::days(7))),
+        "next" | "on" | "this" => {
+            let (used, date) = date(&words[1..], today)?;
+            return Some((used + 1, date));
+        }
+        _ => {}
+    }
+    if let Some(day) = weekday(first) {
+        // Always ahead: "friday" on a Friday means next week's
+        let ahead = (day.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+        let ahead = if ahead == 0 { 7 } else { ahead };
+        return Some((1, today + Duration::days(ahead.into())));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(first, "%Y-%m-%d") {
+        return Some((1, date));
+    }
+    // "march 14", "mar 14th", "14 march"; the year is the next one that's still ahead
+    let (used, month, day) = match (month(first), day_of_month(second)) {
+        (Some(month), Some(day)) => (2, month, day),
+        _ => (2, month(second)?, day_of_month(first)?),
+    };
+    let this_year = NaiveDate::from_ymd_opt(today.year(), month, day)?;
+    if this_year >= today {
+        Some((used, this_year))
+    } else {
+        Some((used, NaiveDate::from_ymd_opt(today.year() + 1, month, day)?))
+    }
+}
+
+/// "in 20 minutes", "in an hour", "in 3 days".
+fn relative(words: &[String], now: DateTime<Local>) -> Option<(usize, DateTime<Local>)> {
+    if words.first()? != "in" {
+        return None;
+    }
+    let amount: i64 = match words.get(1)?.as_str() {
+        "a" | "an" | "one" => 1,
+        "two" => 2,
+        "few" => 3,
+        n => n.parse().ok()?,
+    };
+    let unit = words.get(2)?.trim_end_matches('s');
+    let duration = match unit {
+        "min" | "minute" => Duration::minutes(amount),
+        "hour" | "hr" => Duration::hours(amount),
+        "day" => Duration::days(amount),
+        "week" => Duration::weeks(amount),
+        _ => return None,
+    };
+    Some((3, now + duration))
+}
+
+fn local(date: NaiveDate, time: NaiveTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&date.and_time(time)).earliest()
+}
+
+/// A date phrase at the start of `words`: a relative offset, or a day and/or a time in
+/// either order.
+fn phrase(words: &[String], now: DateTime<Local>) -> Option<(usize, DateTime<Local>)> {
+    if let Some(found) = relative(words, now) {
+        return Some(found);
+    }
+    let today = now.date_naive();
+    let (used, day, at) = if let Some((used, day)) = date(words, today) {
+        // "tonight" is both
+        let tonight = words[0] == "tonight";
+        match time(&words[used..]) {
+            Some((more, at)) => (used + more, Some(day), Some(at)),
+            None if tonight => (used, Some(day), NaiveTime::from_hms_opt(19, 0, 0)),
+            None => (used, Some(day), None),
+        }
+    } else {
+        let (used, at) = time(words)?;
+        match date(&words[used..], today) {
+            Some((more, day)) => (used + more, Some(day), Some(at)),
+            None => (used, None, Some(at)),
+        }
+    };
+    let at_or_default = at.or_else(|| NaiveTime::from_hms_opt(DEFAULT_HOUR, 0, 0))?;
+    let when = match day {
+        Some(day) => local(day, at_or_default)?,
+        // A bare time is the next one: later today, else tomorrow
+        None => {
+            let today_at = local(today, at_or_default)?;
+            if today_at > now {
+                today_at
+            } else {
+                local(today.succ_opt()?, at_or_default)?
+            }
+        }
+    };
+    Some((used, when))
+}
+
+/// Finds when a line of text like "remind me friday 3pm to call Bo" is about, relative to
+/// `now`. Understands today/tonight/tomorrow, weekdays, "next week", dates ("march 14",
+/// "2025-03-14"), times ("3pm", "15:30", "noon") and offsets ("in 2 hours").
+pub(crate) fn parse_when(text: &str, now: DateTime<Local>) -> Option<When> {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let words: Vec<String> = tokens.iter().map(|token| word(token)).collect();
+    let (start, used, at) =
+        (0..words.len()).find_map(|i| phrase(&words[i..], now).map(|(used, at)| (i, used, at)))?;
+
+    let mut rest: Vec<&str> = tokens[..start]
+        .iter()
+        .chain(&tokens[start + used..])
+        .copied()
+        .collect();
+    let lower: Vec<String> = rest.iter().map(|token| word(token)).collect();
+    if lower.len() >= 2 && lower[0] == "remind" && lower[1] == "me" {
+        rest.drain(..2);
+    }
+    if rest.first().is_some_and(|token| word(token) == "to") {
+        rest.remove(0);
+    }
+    Some(When {
+        at,
+        text: rest.join(" "),
+    })
+}
+
+/// Previews what `parse_when` makes of some text, e.g. as it's typed into the capture box.
+#[tauri::command]
+pub fn parse_when_text(text: String) -> Option<When> {
+    parse_when(&text, Local::now())
+}
+
+fn reminder_request(at: DateTime<Local>, yak_id: &str, key: &str, id: &str) -> AppendRequest {
+    AppendRequest {
+        topic: "reminder.set".to_string(),
+        content: String::new(),
+        meta: Some(HashMap::from([
+            ("yak_id".to_string(), yak_id.into()),
+            (key.to_string(), id.into()),
+            ("at".to_string(), at.to_rfc3339().into()),
+        ])),
+    }
+}
+
+/// Adds a note from the quick-capture box. If the text says when ("call Bo friday 3pm"),
+/// the note gets a reminder then and the date phrase is dropped from its text. With location
+/// enabled, the note also records roughly where it was captured.
+#[tauri::command]
+pub async fn quick_capture(
+    app: AppHandle,
+    store: State<'_, Store>,
+    yak_id: String,
+    text: String,
+) -> Result<Captured, String> {
+    let _guard = shutdown::begin_write(&app)?;
+    if text.trim().is_empty() {
+        return Err("Nothing to capture".to_string());
+    }
+    let when = parse_when(&text, Local::now()).filter(|when| !when.text.is_empty());
+    let content = when.as_ref().map_or(text.clone(), |when| when.text.clone());
+    let content = secrets::check(&app, "note.create", content);
+    let mut meta = HashMap::from([
+        ("yak_id".to_string(), yak_id.clone().into()),
+        ("source".to_string(), "quick-capture".into()),
+    ]);
+    if let Some(place) = geo::current(&app) {
+        let place = serde_json::to_value(place).map_err(|e| format!("Invalid location: {e}"))?;
+        meta.insert("location".to_string(), place);
+    }
+    let request = AppendRequest {
+        topic: "note.create".to_string(),
+        content,
+        meta: Some(meta),
+    };
+    let request = app.state::<LockState>().seal_request(request)?;
+    let mut frames = append_batch_to_store(&store, vec![request]).await?;
+    let note_id = frames[0].id.to_string();
+    if let Some(when) = &when {
+        let request = reminder_request(when.at, &yak_id, "note_id", &note_id);
+        frames.extend(append_batch_to_store(&store, vec![request]).await?);
+    }
+    windows::emit_frames(&app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))?;
+    Ok(Captured {
+        note_id,
+        reminder: when.map(|when| when.at.to_rfc3339()),
+    })
+}
+
+/// Sets a reminder on a note or task from either an RFC 3339 time or words like
+/// "tomorrow 9am". Returns the time set.
+#[tauri::command]
+pub async fn set_reminder(
+    app: AppHandle,
+    store: State<'_, Store>,
+    frame_id: String,
+    when: String,
+) -> Result<String, String> {
+    let _guard = shutdown::begin_write(&app)?;
+    let at = match DateTime::parse_from_rfc3339(when.trim()) {
+        Ok(at) => at.with_timezone(&Local),
+        Err(_) => {
+            parse_when(&when, Local::now())
+                .ok_or_else(|| format!("Couldn't tell when \"{when}\" is"))?
+                .at
+        }
+    };
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let note_id = projection.resolve(&frame_id);
+    let request = if let Some(note) = projection.notes.get(&note_id) {
+        reminder_request(at, &note.yak_id, "note_id", &note_id)
+    } else if let Some(task) = projection.tasks.get(&frame_id) {
+        reminder_request(at, &task.yak_id, "task_id", &frame_id)
+    } else {
+        return Err(format!("No note or task {frame_id}"));
+    };
+    let frames = append_batch_to_store(&store, vec![request]).await?;
+    windows::emit_frames(&app, &frames).map_err(|e| format!("Failed to emit frames: {e}"))?;
+    Ok(at.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Local> {
+        // A Wednesday afternoon
+        Local.with_ymd_and_hms(2025, 3, 12, 14, 0, 0).unwrap()
+    }
+
+    fn at(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2025, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_when() {
+        let when = parse_when("remind me friday 3pm to call Bo", now()).unwrap();
+        assert_eq!(when.at, at(3, 14, 15, 0));
+        assert_eq!(when.text, "call Bo");
+
+        let cases = [
+            ("pay rent tomorrow", at(3, 13, 9, 0)),
+            ("standup at 9:30am tomorrow", at(3, 13, 9, 30)),
+            ("dinner tonight", at(3, 12, 19, 0)),
+            ("gym at 10", at(3, 13, 10, 0)),
+            ("review next wednesday", at(3, 19, 9, 0)),
+            ("taxes april 15th 5pm", at(4, 15, 17, 0)),
+            ("dentist 2025-06-02 at noon", at(6, 2, 12, 0)),
+            ("check the oven in 20 minutes", at(3, 12, 14, 20)),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_when(text, now()).unwrap().at, expected, "{text}");
+        }
+        assert_eq!(
+            parse_when("pay rent tomorrow", now()).unwrap().text,
+            "pay rent"
+        );
+        assert!(parse_when("call Bo about the monthly report", now()).is_none());
+        assert!(parse_when("buy 3 apples", now()).is_none());
+    }
+}
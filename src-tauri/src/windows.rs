@@ -0,0 +1,528 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::{
+    AppHandle, Emitter, EventTarget, Manager, State, WebviewUrl, WebviewWindowBuilder, WindowEvent,
+};
+use xs::store::{FollowOption, Frame, ReadOptions, Store};
+
+use crate::emission;
+use crate::profiles;
+use crate::projection::Projection;
+use crate::read_all_frames;
+use crate::topics;
+
+/// Which yak each secondary window is scoped to, by window label. Windows without an entry
+/// (the main window) see every frame.
+#[derive(Default)]
+pub struct WindowState {
+    scopes: Mutex<HashMap<String, String>>,
+    /// The last frame streamed to each window, by label; saved on quit
+    cursors: Mutex<HashMap<String, String>>,
+    cursors_path: Mutex<Option<PathBuf>>,
+    sequence: Mutex<Sequence>,
+}
+
+/// Numbers every frame `emit_frame` and `emit_frames` send, and keeps the latest so a window
+/// that missed some can have them sent again by `sync_from`.
+#[derive(Default)]
+struct Sequence {
+    last: u64,
+    recent: VecDeque<SequencedFrame>,
+}
+
+/// An emitted frame with its place in the sequence, `seq`, alongside its own fields.
+#[derive(Debug, Clone, Serialize)]
+pub struct SequencedFrame {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub frame: Frame,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Replay {
+    pub frames: Vec<SequencedFrame>,
+    /// The newest sequence number sent
+    pub latest: u64,
+    /// False when frames after the asked-for one have already been dropped from the replay
+    /// buffer, or it comes from before a restart; the window should resubscribe instead
+    pub complete: bool,
+}
+
+const CURSORS_FILE: &str = "window-cursors.json";
+/// Frames per message while a channel replays history; live frames go one at a time.
+const PACKED_BATCH: usize = 256;
+/// Emitted frames kept for `sync_from` to replay.
+const REPLAY_BUFFER: usize = 4096;
+
+impl WindowState {
+    fn cursor(&self, label: &str) -> Option<String> {
+        self.cursors.lock().unwrap().get(label).cloned()
+    }
+
+    fn delivered(&self, label: &str, frame_id: String) {
+        self.cursors
+            .lock()
+            .unwrap()
+            .insert(label.to_string(), frame_id);
+    }
+
+    fn scopes(&self) -> HashMap<String, String> {
+        self.scopes.lock().unwrap().clone()
+    }
+
+    fn scope(&self, label: &str) -> Option<String> {
+        self.scopes.lock().unwrap().get(label).cloned()
+    }
+
+    /// Gives `frames` the next sequence numbers and keeps them for replay.
+    fn sequence(&self, frames: &[Frame]) -> Vec<SequencedFrame> {
+        let mut sequence = self.sequence.lock().unwrap();
+        let mut sequenced = Vec::with_capacity(frames.len());
+        for frame in frames {
+            sequence.last += 1;
+            let frame = SequencedFrame {
+                seq: sequence.last,
+                frame: frame.clone(),
+            };
+            sequence.recent.push_back(frame.clone());
+            sequenced.push(frame);
+        }
+        let excess = sequence.recent.len().saturating_sub(REPLAY_BUFFER);
+        sequence.recent.drain(..excess);
+        sequenced
+    }
+
+    /// The kept frames numbered after `seq`, limited to those `scope` should see.
+    fn since(&self, seq: u64, scope: Option<&str>) -> Replay {
+        let sequence = self.sequence.lock().unwrap();
+        let oldest = sequence
+            .recent
+            .front()
+            .map_or(sequence.last + 1, |frame| frame.seq);
+        Replay {
+            frames: sequence
+                .recent
+                .iter()
+                .filter(|frame| frame.seq > seq)
+                .filter(|frame| scope.map_or(true, |yak_id| relevant(&frame.frame, yak_id)))
+                .cloned()
+                .collect(),
+            latest: sequence.last,
+            complete: seq <= sequence.last && seq + 1 >= oldest,
+        }
+    }
+}
+
+/// The yak a frame belongs to: a `yak.create` frame is its own yak, everything else names
+/// its yak in `meta.yak_id`. Frames without one (settings, `xs.threshold`) belong to no yak.
+pub(crate) fn frame_yak_id(frame: &Frame) -> Option<String> {
+    if frame.topic == "yak.create" {
+        return Some(frame.id.to_string());
+    }
+    frame
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.get("yak_id"))
+        .and_then(|yak_id| yak_id.as_str())
+        .map(String::from)
+}
+
+/// Whether a window scoped to `yak_id` should see `frame`.
+fn relevant(frame: &Frame, yak_id: &str) -> bool {
+    frame_yak_id(frame).map_or(true, |id| id == yak_id)
+}
+
+fn target_label(target: &EventTarget) -> Option<&str> {
+    match target {
+        EventTarget::Window { label }
+        | EventTarget::Webview { label }
+        | EventTarget::WebviewWindow { label }
+        | EventTarget::AnyLabel { label } => Some(label),
+        _ => None,
+    }
+}
+
+/// Emits a `frame` event to every window that should see it. Each emitted frame carries a
+/// `seq` one higher than the last, so a window can tell it missed some and ask `sync_from`
+/// for them, and heavy meta is trimmed by the emit policy.
+pub(crate) fn emit_frame(app: &AppHandle, frame: &Frame) -> tauri::Result<()> {
+    crate::metrics::record_ipc_events(1);
+    let state = app.state::<WindowState>();
+    let scopes = state.scopes();
+    let shaped = emission::shape(frame, &emission::policy(app));
+    let sequenced = state.sequence(std::slice::from_ref(shaped.as_ref().unwrap_or(frame)));
+    app.emit_filter("frame", &sequenced[0], |target| {
+        target_label(target)
+            .and_then(|label| scopes.get(label))
+            .map_or(true, |yak_id| relevant(frame, yak_id))
+    })
+}
+
+/// Emits a `frames` batch, trimmed per window to the frames it should see.
+pub(crate) fn emit_frames(app: &AppHandle, frames: &[Frame]) -> tauri::Result<()> {
+    crate::metrics::record_ipc_events(frames.len() as u64);
+    let state = app.state::<WindowState>();
+    let scopes = state.scopes();
+    let policy = emission::policy(app);
+    let shaped: Vec<Frame> = frames
+        .iter()
+        .map(|frame| emission::shape(frame, &policy).unwrap_or_else(|| frame.clone()))
+        .collect();
+    let frames = state.sequence(&shaped);
+    app.emit_filter("frames", &frames, |target| {
+        target_label(target).map_or(true, |label| !scopes.contains_key(label))
+    })?;
+    for (label, yak_id) in scopes {
+        let frames: Vec<&SequencedFrame> = frames
+            .iter()
+            .filter(|frame| relevant(&frame.frame, &yak_id))
+            .collect();
+        if !frames.is_empty() {
+            app.emit_to(EventTarget::webview_window(label), "frames", &frames)?;
+        }
+    }
+    Ok(())
+}
+
+/// How a window receives its frames.
+pub(crate) enum Delivery {
+    /// A JSON `frame` event per frame
+    Events,
+    /// MessagePack arrays of frames over a channel, which skips JSON for large payloads
+    Packed(Channel<InvokeResponseBody>),
+}
+
+/// Encodes `value` as MessagePack with field names, so it decodes to the same shape as JSON.
+pub(crate) fn pack<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, String> {
+    rmp_serde::to_vec_named(value).map_err(|e| format!("Failed to encode: {e}"))
+}
+
+/// Sends `frames` as one message and empties it.
+fn send_packed(
+    channel: &Channel<InvokeResponseBody>,
+    frames: &mut Vec<Frame>,
+) -> Result<(), String> {
+    if frames.is_empty() {
+        return Ok(());
+    }
+    crate::metrics::record_ipc_events(1);
+    let bytes = pack(&*frames)?;
+    frames.clear();
+    channel
+        .send(InvokeResponseBody::Raw(bytes))
+        .map_err(|e| e.to_string())
+}
+
+/// Streams history and new frames to a single window, respecting its scope. History up to
+/// and including `after` is skipped, as the window already has it, and system frames are
+/// unless `include_system`.
+pub(crate) fn forward_to_window(
+    app: AppHandle,
+    label: String,
+    mut after: Option<String>,
+    mut rx: tokio::sync::mpsc::Receiver<Frame>,
+    delivery: Delivery,
+    include_system: bool,
+) {
+    let scope = app.state::<WindowState>().scope(&label);
+    tokio::spawn(async move {
+        let mut count = 0;
+        let mut caught_up = false;
+        let mut pending: Vec<Frame> = Vec::new();
+        while let Some(frame) = rx.recv().await {
+            let id = frame.id.to_string();
+            if frame.topic == "xs.threshold" {
+                after = None;
+                caught_up = true;
+            } else if after.as_ref().is_some_and(|after| id <= *after) {
+                // SCRU128 ids sort as strings in creation order
+                continue;
+            } else if !include_system && topics::is_system(&frame.topic) {
+                continue;
+            }
+            if !scope
+                .as_deref()
+                .map_or(true, |yak_id| relevant(&frame, yak_id))
+            {
+                continue;
+            }
+            count += 1;
+            let frame = emission::shape(&frame, &emission::policy(&app)).unwrap_or(frame);
+            let delivered = frame.topic != "xs.threshold";
+            let sent = match &delivery {
+                Delivery::Events => {
+                    crate::metrics::record_ipc_events(1);
+                    let target = EventTarget::webview_window(label.clone());
+                    app.emit_to(target, "frame", &frame)
+                        .map_err(|e| e.to_string())
+                }
+                Delivery::Packed(channel) => {
+                    pending.push(frame);
+                    if caught_up || pending.len() >= PACKED_BATCH {
+                        send_packed(channel, &mut pending)
+                    } else {
+                        Ok(())
+                    }
+                }
+            };
+            if let Err(e) = sent {
+                eprintln!("Failed to send frame to {label}: {e}");
+                break;
+            }
+            if delivered {
+                app.state::<WindowState>().delivered(&label, id);
+            }
+        }
+        println!("Event stream for {label} ended after {count} frames");
+    });
+}
+
+/// Loads the cursors saved when the app last quit.
+pub(crate) fn initialize(app: &AppHandle) {
+    let Ok(dir) = profiles::data_dir(app) else {
+        return;
+    };
+    let path = dir.join(CURSORS_FILE);
+    let state = app.state::<WindowState>();
+    if let Ok(json) = std::fs::read(&path) {
+        match serde_json::from_slice(&json) {
+            Ok(cursors) => *state.cursors.lock().unwrap() = cursors,
+            Err(e) => eprintln!("Ignoring saved window cursors: {e}"),
+        }
+    }
+    *state.cursors_path.lock().unwrap() = Some(path);
+}
+
+/// Saves each window's cursor so a window restored after a restart can resume.
+pub(crate) fn save_cursors(app: &AppHandle) {
+    let state = app.state::<WindowState>();
+    let Some(path) = state.cursors_path.lock().unwrap().clone() else {
+        return;
+    };
+    let cursors = state.cursors.lock().unwrap().clone();
+    let result = serde_json::to_vec(&cursors)
+        .map_err(|e| e.to_string())
+        .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        eprintln!("Failed to save window cursors: {e}");
+    }
+}
+
+/// Like `subscribe_to_events`, but only streams frames after `cursor`, for a window that
+/// kept its state across a reload. Without a cursor the window's last delivered frame is
+/// used; if there is none everything is replayed. Returns the cursor resumed from.
+#[tauri::command]
+pub async fn resume_from(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, WindowState>,
+    window: tauri::WebviewWindow,
+    cursor: Option<String>,
+    include_system: Option<bool>,
+) -> Result<Option<String>, String> {
+    let label = window.label().to_string();
+    if let Some(cursor) = &cursor {
+        cursor
+            .parse::<scru128::Scru128Id>()
+            .map_err(|e| format!("Invalid cursor {cursor}: {e}"))?;
+    }
+    let cursor = cursor.or_else(|| state.cursor(&label));
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let rx = store.read(read_options).await;
+    let include_system = include_system.unwrap_or(false);
+    forward_to_window(
+        app,
+        label,
+        cursor.clone(),
+        rx,
+        Delivery::Events,
+        include_system,
+    );
+    Ok(cursor)
+}
+
+/// Like `resume_from`, but frames arrive over `channel` as MessagePack arrays rather than
+/// as JSON events: history in batches, then each new frame as it's appended.
+#[tauri::command]
+pub async fn stream_frames(
+    app: AppHandle,
+    store: State<'_, Store>,
+    window: tauri::WebviewWindow,
+    channel: Channel<InvokeResponseBody>,
+    cursor: Option<String>,
+    include_system: Option<bool>,
+) -> Result<(), String> {
+    if let Some(cursor) = &cursor {
+        cursor
+            .parse::<scru128::Scru128Id>()
+            .map_err(|e| format!("Invalid cursor {cursor}: {e}"))?;
+    }
+    let read_options = ReadOptions::builder().follow(FollowOption::On).build();
+    let rx = store.read(read_options).await;
+    let label = window.label().to_string();
+    let delivery = Delivery::Packed(channel);
+    forward_to_window(
+        app,
+        label,
+        cursor,
+        rx,
+        delivery,
+        include_system.unwrap_or(false),
+    );
+    Ok(())
+}
+
+/// Sends the window the `frame` and `frames` events numbered after `seq` again, for when it
+/// sees a gap in the numbers or has reloaded. Scoped windows skip other yaks' frames, so
+/// for them a gap may have nothing to fill.
+#[tauri::command]
+pub async fn sync_from(
+    state: State<'_, WindowState>,
+    window: tauri::WebviewWindow,
+    seq: u64,
+) -> Result<Replay, String> {
+    let scope = state.scope(window.label());
+    Ok(state.since(seq, scope.as_deref()))
+}
+
+/// Brings the main window to the front, e.g. when a global shortcut or link fires.
+pub(crate) fn focus_main(app: &AppHandle) -> Option<tauri::WebviewWindow> {
+    let window = app.get_webview_window("main")?;
+    let _ = window.unminimize();
+    let _ = window.show();
+    let _ = window.set_focus();
+    Some(window)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct YakWindow {
+    pub label: String,
+    pub yak_id: String,
+}
+
+/// Opens a yak in its own window, or focuses the window already showing it. The window only
+/// receives frames belonging to that yak.
+#[tauri::command]
+pub async fn open_yak_window(
+    app: AppHandle,
+    store: State<'_, Store>,
+    state: State<'_, WindowState>,
+    yak_id: String,
+) -> Result<YakWindow, String> {
+    let label = format!("yak-{yak_id}");
+    if let Some(window) = app.get_webview_window(&label) {
+        window
+            .set_focus()
+            .map_err(|e| format!("Failed to focus window: {e}"))?;
+        return Ok(YakWindow { label, yak_id });
+    }
+
+    let projection = Projection::from_frames(&read_all_frames(&store).await);
+    let yak = projection
+        .yaks
+        .get(&yak_id)
+        .ok_or_else(|| format!("Unknown yak {yak_id}"))?;
+    let title = yak.name.clone().unwrap_or_else(|| "Yaks".to_string());
+
+    // Scope first so the window's own subscription is filtered from its first frame
+    state
+        .scopes
+        .lock()
+        .unwrap()
+        .insert(label.clone(), yak_id.clone());
+    let url = WebviewUrl::App(format!("index.html?yak={yak_id}").into());
+    let window = match WebviewWindowBuilder::new(&app, &label, url)
+        .title(title)
+        .inner_size(800.0, 600.0)
+        .build()
+    {
+        Ok(window) => window,
+        Err(e) => {
+            state.scopes.lock().unwrap().remove(&label);
+            return Err(format!("Failed to open window: {e}"));
+        }
+    };
+
+    let closed_app = app.clone();
+    let closed_label = label.clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::Destroyed = event {
+            let state = closed_app.state::<WindowState>();
+            state.scopes.lock().unwrap().remove(&closed_label);
+        }
+    });
+
+    Ok(YakWindow { label, yak_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, append};
+
+    #[tokio::test]
+    async fn test_relevant() {
+        let (_dir, store) = testing::store();
+        let frame = |topic: &str, meta| append(&store, topic, meta);
+        let yak = frame("yak.create", None);
+        let yak_id = yak.id.to_string();
+        let other = frame("yak.create", None);
+        let note = frame("note.create", Some(serde_json::json!({ "yak_id": yak_id })));
+        let foreign = frame(
+            "note.create",
+            Some(serde_json::json!({ "yak_id": other.id.to_string() })),
+        );
+
+        assert!(relevant(&yak, &yak_id));
+        assert!(relevant(&note, &yak_id));
+        assert!(relevant(&frame("xs.threshold", None), &yak_id));
+        assert!(!relevant(&other, &yak_id));
+        assert!(!relevant(&foreign, &yak_id));
+    }
+
+    #[tokio::test]
+    async fn test_pack_keeps_json_shape() {
+        let (_dir, store) = testing::store();
+        append(
+            &store,
+            "note.create",
+            Some(serde_json::json!({ "yak_id": "y" })),
+        );
+        let frames = read_all_frames(&store).await;
+        let packed: serde_json::Value = rmp_serde::from_slice(&pack(&frames).unwrap()).unwrap();
+        assert_eq!(packed, serde_json::to_value(&frames).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_sequence() {
+        let (_dir, store) = testing::store();
+        let state = WindowState::default();
+        let yak = append(&store, "yak.create", None);
+        let other = append(&store, "yak.create", None);
+        let sequenced = state.sequence(&[yak.clone(), other.clone()]);
+        assert_eq!(sequenced[1].seq, 2);
+        let json = serde_json::to_value(&sequenced[0]).unwrap();
+        assert_eq!(json["seq"], 1);
+        assert_eq!(json["topic"], "yak.create");
+
+        let replay = state.since(1, None);
+        assert!(replay.complete);
+        assert_eq!(replay.latest, 2);
+        assert_eq!(replay.frames[0].frame.id, other.id);
+        assert_eq!(state.since(0, Some(&yak.id.to_string())).frames.len(), 1);
+        // A window from before a restart is ahead of the sequence
+        assert!(!state.since(5, None).complete);
+
+        let many: Vec<Frame> = (0..REPLAY_BUFFER)
+            .map(|_| append(&store, "note.create", None))
+            .collect();
+        state.sequence(&many);
+        assert!(!state.since(1, None).complete);
+        let replay = state.since(2, None);
+        assert!(replay.complete);
+        assert_eq!(replay.frames.len(), REPLAY_BUFFER);
+    }
+}
@@ -6,9 +6,13 @@ import {
   onMount,
   onCleanup,
 } from 'solid-js';
+import { listen } from '@tauri-apps/api/event';
 import { createYakStore } from './store';
 import type { Note } from './store';
 import { Editor } from './components/Editor';
+import { ProfilePicker } from './components/ProfilePicker';
+import { listShortcuts, matchesAccelerator, onShortcuts } from './shortcuts';
+import type { ShortcutBinding } from './shortcuts';
 import './App.css';
 
 function App() {
@@ -16,6 +20,11 @@ function App() {
 
   // Subscribe to events on mount
   onMount(() => {
+    // Windows opened with open_yak_window are scoped to a single yak
+    const scopedYakId = new URLSearchParams(window.location.search).get('yak');
+    if (scopedYakId) {
+      store.setCurrentYakId(scopedYakId);
+    }
     store.subscribe();
   });
 
@@ -42,30 +51,63 @@ function App() {
     }
   });
 
-  // Keyboard shortcuts
+  // Keyboard shortcuts, rebindable from the backend
+  const [shortcuts, setShortcuts] = createSignal<ShortcutBinding[]>([]);
+
+  const runAction = (action: string) => {
+    switch (action) {
+      case 'note.new':
+      case 'capture.quick':
+        openNewNoteEditor();
+        return true;
+      case 'note.edit':
+        if (store.selectedNote() && !isEditorOpen()) {
+          openEditNoteEditor();
+          return true;
+        }
+        return false;
+    }
+    return false;
+  };
+
   const handleKeyDown = (e: KeyboardEvent) => {
-    if (e.metaKey || e.ctrlKey) {
-      switch (e.key) {
-        case 'n':
-          e.preventDefault();
-          openNewNoteEditor();
-          break;
-        case 'Enter':
-          if (store.selectedNote() && !isEditorOpen()) {
-            e.preventDefault();
-            openEditNoteEditor();
-          }
-          break;
-      }
+    const binding = shortcuts().find(
+      binding =>
+        !binding.global &&
+        binding.accelerator &&
+        matchesAccelerator(e, binding.accelerator)
+    );
+    if (binding && runAction(binding.action)) {
+      e.preventDefault();
     }
   };
 
-  onMount(() => {
+  let stopShortcuts: (() => void) | undefined;
+  let stopLinks: (() => void) | undefined;
+
+  onMount(async () => {
     document.addEventListener('keydown', handleKeyDown);
+    try {
+      setShortcuts(await listShortcuts());
+      stopShortcuts = await onShortcuts(setShortcuts, runAction);
+    } catch (error) {
+      console.error('Failed to load shortcuts:', error);
+    }
+    // yaks://note/<id> links, e.g. from OS search results
+    stopLinks = await listen<{ note_id: string; yak_id: string }>(
+      'open-note',
+      event => {
+        store.setCurrentYakId(event.payload.yak_id);
+        store.setSelectedNoteId(event.payload.note_id);
+        scrollToNote(event.payload.note_id);
+      }
+    );
   });
 
   onCleanup(() => {
     document.removeEventListener('keydown', handleKeyDown);
+    stopShortcuts?.();
+    stopLinks?.();
   });
 
   const handleNoteClick = (note: Note) => {
@@ -213,6 +255,8 @@ function App() {
           onSave={handleEditorSave}
         />
       </Show>
+
+      <ProfilePicker />
     </div>
   );
 }
@@ -0,0 +1,60 @@
+import { For, Show, createSignal, onMount } from 'solid-js';
+import { invoke } from '@tauri-apps/api/core';
+
+interface ProfileInfo {
+  name: string;
+  dir: string;
+  active: boolean;
+}
+
+interface ProfileList {
+  profiles: ProfileInfo[];
+  ask_on_launch: boolean;
+  pick: boolean;
+}
+
+// Asks which profile to open when more than one exists and asking on launch is on
+export function ProfilePicker() {
+  const [profiles, setProfiles] = createSignal<ProfileInfo[]>([]);
+  const [isOpen, setIsOpen] = createSignal(false);
+
+  onMount(async () => {
+    try {
+      const list = await invoke<ProfileList>('list_profiles');
+      setProfiles(list.profiles);
+      setIsOpen(list.pick);
+    } catch (error) {
+      console.error('Failed to load profiles:', error);
+    }
+  });
+
+  const choose = async (name: string) => {
+    try {
+      // Choosing another profile restarts the app onto it
+      await invoke<boolean>('switch_profile', { name });
+      setIsOpen(false);
+    } catch (error) {
+      console.error('Failed to switch profile:', error);
+    }
+  };
+
+  return (
+    <Show when={isOpen()}>
+      <div class="editor-overlay">
+        <div class="profile-picker">
+          <h2>Open profile</h2>
+          <For each={profiles()}>
+            {profile => (
+              <button
+                class={`profile-item ${profile.active ? 'selected' : ''}`}
+                onClick={() => choose(profile.name)}
+              >
+                {profile.name}
+              </button>
+            )}
+          </For>
+        </div>
+      </div>
+    </Show>
+  );
+}
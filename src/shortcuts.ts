@@ -0,0 +1,92 @@
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+
+export interface ShortcutBinding {
+  action: string;
+  description: string;
+  // Handled by the OS even when Yaks isn't focused
+  global: boolean;
+  default: string;
+  accelerator: string | null;
+}
+
+const isMac = navigator.platform.toUpperCase().includes('MAC');
+
+const KEY_ALIASES: Record<string, string> = {
+  esc: 'escape',
+  return: 'enter',
+  space: ' ',
+};
+
+// Whether a keydown matches an accelerator such as `CmdOrCtrl+Shift+N`
+export function matchesAccelerator(
+  e: KeyboardEvent,
+  accelerator: string
+): boolean {
+  const parts = accelerator.toLowerCase().split('+');
+  const key = parts.pop() ?? '';
+  const wants = { meta: false, ctrl: false, shift: false, alt: false };
+  for (const part of parts) {
+    switch (part) {
+      case 'cmdorctrl':
+      case 'commandorcontrol':
+        wants[isMac ? 'meta' : 'ctrl'] = true;
+        break;
+      case 'cmd':
+      case 'command':
+      case 'super':
+        wants.meta = true;
+        break;
+      case 'ctrl':
+      case 'control':
+        wants.ctrl = true;
+        break;
+      case 'shift':
+        wants.shift = true;
+        break;
+      case 'alt':
+      case 'option':
+        wants.alt = true;
+        break;
+    }
+  }
+  return (
+    e.metaKey === wants.meta &&
+    e.ctrlKey === wants.ctrl &&
+    e.shiftKey === wants.shift &&
+    e.altKey === wants.alt &&
+    e.key.toLowerCase() === (KEY_ALIASES[key] ?? key)
+  );
+}
+
+export async function listShortcuts(): Promise<ShortcutBinding[]> {
+  return await invoke<ShortcutBinding[]>('list_shortcuts');
+}
+
+export async function rebindShortcut(
+  action: string,
+  accelerator: string | null
+): Promise<ShortcutBinding[]> {
+  return await invoke<ShortcutBinding[]>('rebind_shortcut', {
+    action,
+    accelerator,
+  });
+}
+
+// Bindings as they change, and global shortcuts as they fire
+export async function onShortcuts(
+  onChange: (bindings: ShortcutBinding[]) => void,
+  onTrigger: (action: string) => void
+): Promise<() => void> {
+  const unlistenChange = await listen<ShortcutBinding[]>(
+    'shortcuts-changed',
+    event => onChange(event.payload)
+  );
+  const unlistenTrigger = await listen<string>('shortcut', event =>
+    onTrigger(event.payload)
+  );
+  return () => {
+    unlistenChange();
+    unlistenTrigger();
+  };
+}
@@ -1,6 +1,20 @@
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
-import type { EventStreamInterface, Frame, AppendRequest } from './types';
+import type {
+  EventStreamInterface,
+  Frame,
+  FrameFilter,
+  AppendRequest,
+} from './types';
+
+// Frames emitted app-wide are numbered so a gap shows that some were missed
+type SequencedFrame = Frame & { seq?: number };
+
+interface Replay {
+  frames: SequencedFrame[];
+  latest: number;
+  complete: boolean;
+}
 
 // Override console.log to also send to Tauri backend
 const originalConsoleLog = console.log;
@@ -32,25 +46,102 @@ export class TauriEventStream implements EventStreamInterface {
     return await invoke<string>('append_event', { request });
   }
 
+  async appendBatch(requests: AppendRequest[]): Promise<string[]> {
+    return await invoke<string[]>('append_batch', { requests });
+  }
+
   async getCasContent(hash: string): Promise<string> {
     return await invoke<string>('get_cas_content', { hash });
   }
 
+  // One round trip for many blobs; each hash maps to its content or read error
+  async getCasBatch(
+    hashes: string[]
+  ): Promise<Record<string, { content: string } | { error: string }>> {
+    return await invoke('get_cas_batch', { hashes });
+  }
+
+  // Full meta of a frame that arrived with heavy fields listed in `omitted_meta`
+  async getFrameMeta(id: string): Promise<Record<string, unknown> | null> {
+    return await invoke('get_frame_meta', { id });
+  }
+
   async subscribeToEvents(): Promise<void> {
     return await invoke<void>('subscribe_to_events');
   }
 
+  // Streams only frames after `cursor` (default: the last one this window was sent), for a
+  // window that kept its state; resolves to the cursor used, or null for a full replay
+  async resumeFrom(cursor?: string): Promise<string | null> {
+    return await invoke<string | null>('resume_from', { cursor });
+  }
+
+  // Server-side filtered stream; resolves to a cleanup function that unsubscribes
+  async subscribe(
+    filter: FrameFilter,
+    callback: (frame: Frame) => void
+  ): Promise<() => void> {
+    const id = await invoke<string>('subscribe', { filter });
+    const unlisten = await listen<Frame>(`subscription:${id}`, event =>
+      callback(event.payload)
+    );
+    return () => {
+      unlisten();
+      invoke('unsubscribe', { id }).catch(() => {});
+    };
+  }
+
   onFrame(callback: (frame: Frame) => void): () => void {
     console.log('Setting up frame listener...');
-    const unlisten = listen<Frame>('frame', event => {
+    let lastSeq = 0;
+    // Handled one at a time, so frames arriving while a gap is refilled wait their turn
+    let pending = Promise.resolve();
+    const handle = async (frame: SequencedFrame) => {
+      if (frame.seq === undefined) {
+        callback(frame);
+        return;
+      }
+      if (frame.seq <= lastSeq) return;
+      if (lastSeq > 0 && frame.seq > lastSeq + 1) {
+        console.log(`Missed frames after ${lastSeq}, replaying`);
+        try {
+          const replay = await invoke<Replay>('sync_from', { seq: lastSeq });
+          if (!replay.complete) {
+            console.error('Missed frames are gone; reload to catch up');
+          }
+          for (const missed of replay.frames) {
+            if (missed.seq !== undefined && missed.seq > lastSeq) {
+              callback(missed);
+              lastSeq = missed.seq;
+            }
+          }
+        } catch (error) {
+          console.error('Failed to replay missed frames:', error);
+        }
+        if (frame.seq <= lastSeq) return;
+      }
+      callback(frame);
+      lastSeq = frame.seq;
+    };
+    const receive = (frames: SequencedFrame[]) => {
+      pending = pending.then(async () => {
+        for (const frame of frames) await handle(frame);
+      });
+    };
+    const unlisten = listen<SequencedFrame>('frame', event => {
       console.log('Received frame event:', event.payload);
-      callback(event.payload);
+      receive([event.payload]);
+    });
+    const unlistenBatch = listen<SequencedFrame[]>('frames', event => {
+      console.log('Received frame batch:', event.payload.length);
+      receive(event.payload);
     });
 
     // Return cleanup function
     return () => {
       console.log('Cleaning up frame listener');
       unlisten.then(fn => fn());
+      unlistenBatch.then(fn => fn());
     };
   }
 }
@@ -12,6 +12,15 @@ export interface AppendRequest {
   meta?: Record<string, unknown>;
 }
 
+export interface FrameFilter {
+  // Exact topics, or prefixes ending in `*` such as `note.*`
+  topics?: string[];
+  yak_id?: string;
+  tags?: string[];
+  // Also deliver matching history before following new frames
+  replay?: boolean;
+}
+
 export interface EventStreamInterface {
   // Append a new event
   appendEvent(request: AppendRequest): Promise<string>;